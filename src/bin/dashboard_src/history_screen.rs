@@ -7,23 +7,27 @@ use std::{
 use super::{dashboard_app::DashboardEvent, screen::Screen};
 use crossterm::event::{Event, KeyCode, KeyEventKind};
 use itertools::Itertools;
+use neptune_core::prelude::twenty_first;
 use neptune_core::{
     models::blockchain::block::block_height::BlockHeight,
-    models::blockchain::transaction::neptune_coins::NeptuneCoins, rpc_server::RPCClient,
+    models::blockchain::transaction::neptune_coins::NeptuneCoins,
+    models::state::wallet::outgoing_transaction::OutgoingTransaction, rpc_server::RPCClient,
 };
 use num_traits::{CheckedSub, Zero};
 use ratatui::{
-    layout::{Constraint, Margin},
+    layout::{Constraint, Direction, Layout, Margin},
     style::{Color, Modifier, Style},
     widgets::{Block, Borders, Cell, Row, StatefulWidget, Table, TableState, Widget},
 };
 use tarpc::context;
 use tokio::time::sleep;
 use tokio::{select, task::JoinHandle};
+use twenty_first::util_types::emojihash_trait::Emojihash;
 use unicode_width::UnicodeWidthStr;
 
 type BalanceUpdate = (BlockHeight, Duration, NeptuneCoins, NeptuneCoins);
 type BalanceUpdateArc = Arc<std::sync::Mutex<Vec<BalanceUpdate>>>;
+type OutgoingTransactionsArc = Arc<std::sync::Mutex<Vec<OutgoingTransaction>>>;
 type DashboardEventArc = Arc<std::sync::Mutex<Option<DashboardEvent>>>;
 type JoinHandleArc = Arc<Mutex<JoinHandle<()>>>;
 
@@ -98,6 +102,7 @@ pub struct HistoryScreen {
     bg: Color,
     in_focus: bool,
     data: BalanceUpdateArc,
+    outgoing: OutgoingTransactionsArc,
     server: Arc<RPCClient>,
     poll_thread: Option<JoinHandleArc>,
     escalatable_event: DashboardEventArc,
@@ -113,6 +118,7 @@ impl HistoryScreen {
             bg: Color::Black,
             in_focus: false,
             data: data.clone(),
+            outgoing: Arc::new(Mutex::new(vec![])),
             server: rpc_server,
             poll_thread: None,
             escalatable_event: Arc::new(std::sync::Mutex::new(None)),
@@ -123,6 +129,7 @@ impl HistoryScreen {
     async fn run_polling_loop(
         rpc_client: Arc<RPCClient>,
         balance_updates: BalanceUpdateArc,
+        outgoing_transactions: OutgoingTransactionsArc,
         escalatable_event: DashboardEventArc,
     ) -> ! {
         // use macros to reduce boilerplate
@@ -159,6 +166,9 @@ impl HistoryScreen {
                     }
                     *balance_updates.lock().unwrap() = history_builder;
 
+                    *outgoing_transactions.lock().unwrap() =
+                        rpc_client.outgoing_transactions(context::current()).await.unwrap();
+
                     *escalatable_event.lock().unwrap() = Some(DashboardEvent::RefreshScreen);
 
                     reset_poller!(balance_history, Duration::from_secs(10));
@@ -200,9 +210,16 @@ impl Screen for HistoryScreen {
         self.active = true;
         let server_arc = self.server.clone();
         let data_arc = self.data.clone();
+        let outgoing_arc = self.outgoing.clone();
         let escalatable_event_arc = self.escalatable_event.clone();
         self.poll_thread = Some(Arc::new(Mutex::new(tokio::spawn(async move {
-            HistoryScreen::run_polling_loop(server_arc, data_arc, escalatable_event_arc).await;
+            HistoryScreen::run_polling_loop(
+                server_arc,
+                data_arc,
+                outgoing_arc,
+                escalatable_event_arc,
+            )
+            .await;
         }))));
     }
 
@@ -252,6 +269,18 @@ impl Widget for HistoryScreen {
         // ?
         // todo
 
+        // balance updates table on top, sent transactions table below
+        let outgoing_transactions = self.outgoing.lock().unwrap().clone();
+        let canvases = Layout::default()
+            .direction(Direction::Vertical)
+            .constraints(if outgoing_transactions.is_empty() {
+                vec![Constraint::Min(0)]
+            } else {
+                vec![Constraint::Percentage(60), Constraint::Min(0)]
+            })
+            .split(table_canvas);
+        table_canvas = canvases[0];
+
         // table
         let style = Style::default().fg(self.fg).bg(self.bg);
         let selected_style = style.add_modifier(Modifier::REVERSED);
@@ -278,102 +307,8 @@ impl Widget for HistoryScreen {
                 ]
             })
             .collect_vec();
-        let ncols = header.len();
-        let mut widths: Vec<usize> = header.iter().map(|h| h.width()).collect();
-        for (i, w) in widths.iter_mut().enumerate() {
-            if let Some(body_max) = matrix.iter().map(|row| row[i].width()).max() {
-                *w = max(*w, body_max);
-            }
-        }
-        let mut widths_with_bars = vec![1];
-        widths_with_bars.append(
-            &mut widths
-                .iter()
-                .zip(vec![1; ncols].iter())
-                .map(|(w, o)| vec![*w, *o])
-                .collect_vec()
-                .concat(),
-        );
-        let mut header_with_bars = vec!["│".to_string()];
-        header_with_bars.append(
-            &mut header
-                .clone()
-                .into_iter()
-                .zip(vec!["│"; ncols].iter())
-                .map(|(h, b)| vec![h.to_string(), b.to_string()])
-                .collect_vec()
-                .concat(),
-        );
-        let mut top_with_bars = widths_with_bars
-            .iter()
-            .enumerate()
-            .map(|(i, w)| {
-                if i % 2 == 0 {
-                    "┬".to_string()
-                } else {
-                    "─".to_string().repeat(*w)
-                }
-            })
-            .collect_vec();
-        top_with_bars[0] = "╭".to_string();
-        *top_with_bars.last_mut().unwrap() = "╮".to_string();
-        let mut separator_with_bars = widths_with_bars
-            .iter()
-            .enumerate()
-            .map(|(i, w)| {
-                if i % 2 == 0 {
-                    "┼".to_string()
-                } else {
-                    "─".to_string().repeat(*w)
-                }
-            })
-            .collect_vec();
-        separator_with_bars[0] = "├".to_string();
-        *separator_with_bars.last_mut().unwrap() = "┤".to_string();
-        let mut footer_with_bars = widths_with_bars
-            .iter()
-            .enumerate()
-            .map(|(i, w)| {
-                if i % 2 == 0 {
-                    "┴".to_string()
-                } else {
-                    "─".to_string().repeat(*w)
-                }
-            })
-            .collect_vec();
-        footer_with_bars[0] = "╰".to_string();
-        *footer_with_bars.last_mut().unwrap() = "╯".to_string();
-
-        let mut body = matrix
-            .iter()
-            .map(|row| {
-                let mut row_with_bars = vec!["│".to_string()];
-                row_with_bars.append(
-                    &mut row
-                        .iter()
-                        .zip(vec!["│"; ncols].iter())
-                        .map(|(r, b)| vec![r.to_string(), b.to_string()])
-                        .collect_vec()
-                        .concat(),
-                );
-                row_with_bars
-            })
-            .map(|row| Row::new(row.iter().map(|c| Cell::from(c.to_string()))))
-            .collect_vec();
-        for (i, item) in body.iter_mut().enumerate() {
-            if i % 2 == 0 {
-                *item = item.clone().style(Style::default().bg(Color::DarkGray));
-            }
-        }
-        let mut rows: Vec<Row> = vec![
-            Row::new(top_with_bars),
-            Row::new(header_with_bars),
-            Row::new(separator_with_bars),
-        ];
-        rows.append(&mut body);
-        rows.push(Row::new(footer_with_bars));
-
-        let width_constraints = widths_with_bars
+        let (rows, widths) = table_rows_with_bars(&header, &matrix);
+        let width_constraints = widths_with_bars(&widths)
             .iter()
             .map(|w| Constraint::Length(*w as u16))
             .collect_vec();
@@ -386,5 +321,145 @@ impl Widget for HistoryScreen {
             widths.iter().sum::<usize>() as u16 + 3 * widths.len() as u16 + 1,
         );
         StatefulWidget::render(table, table_canvas, buf, &mut self.events.state);
+
+        // sent transactions, newest first
+        if let Some(mut sent_canvas) = canvases.get(1).copied() {
+            let header = vec!["date", "transaction", "sent", "fee", "status", "label"];
+            let matrix = outgoing_transactions
+                .iter()
+                .rev()
+                .map(|record| {
+                    vec![
+                        neptune_core::utc_timestamp_to_localtime(record.timestamp.as_millis())
+                            .to_string(),
+                        record.txid.emojihash(),
+                        record.amount().to_string(),
+                        record.fee.to_string(),
+                        record.status.to_string(),
+                        record.label.clone(),
+                    ]
+                })
+                .collect_vec();
+            let (rows, widths) = table_rows_with_bars(&header, &matrix);
+            let width_constraints = widths_with_bars(&widths)
+                .iter()
+                .map(|w| Constraint::Length(*w as u16))
+                .collect_vec();
+            let table = Table::new(rows).widths(&width_constraints).style(style);
+            sent_canvas.width = min(
+                sent_canvas.width,
+                widths.iter().sum::<usize>() as u16 + 3 * widths.len() as u16 + 1,
+            );
+            Widget::render(table, sent_canvas, buf);
+        }
+    }
+}
+
+/// Interleave the column widths with the widths of the vertical bars between the columns.
+fn widths_with_bars(widths: &[usize]) -> Vec<usize> {
+    let mut widths_with_bars = vec![1];
+    widths_with_bars.append(
+        &mut widths
+            .iter()
+            .zip(vec![1; widths.len()].iter())
+            .map(|(w, o)| vec![*w, *o])
+            .collect_vec()
+            .concat(),
+    );
+    widths_with_bars
+}
+
+/// Build the rows of a table with box-drawing borders, including the header and the
+/// alternating row background. Also returns the width of each column.
+fn table_rows_with_bars(
+    header: &[&str],
+    matrix: &[Vec<String>],
+) -> (Vec<Row<'static>>, Vec<usize>) {
+    let ncols = header.len();
+    let mut widths: Vec<usize> = header.iter().map(|h| h.width()).collect();
+    for (i, w) in widths.iter_mut().enumerate() {
+        if let Some(body_max) = matrix.iter().map(|row| row[i].width()).max() {
+            *w = max(*w, body_max);
+        }
     }
+    let widths_with_bars = widths_with_bars(&widths);
+    let mut header_with_bars = vec!["│".to_string()];
+    header_with_bars.append(
+        &mut header
+            .iter()
+            .zip(vec!["│"; ncols].iter())
+            .map(|(h, b)| vec![h.to_string(), b.to_string()])
+            .collect_vec()
+            .concat(),
+    );
+    let mut top_with_bars = widths_with_bars
+        .iter()
+        .enumerate()
+        .map(|(i, w)| {
+            if i % 2 == 0 {
+                "┬".to_string()
+            } else {
+                "─".to_string().repeat(*w)
+            }
+        })
+        .collect_vec();
+    top_with_bars[0] = "╭".to_string();
+    *top_with_bars.last_mut().unwrap() = "╮".to_string();
+    let mut separator_with_bars = widths_with_bars
+        .iter()
+        .enumerate()
+        .map(|(i, w)| {
+            if i % 2 == 0 {
+                "┼".to_string()
+            } else {
+                "─".to_string().repeat(*w)
+            }
+        })
+        .collect_vec();
+    separator_with_bars[0] = "├".to_string();
+    *separator_with_bars.last_mut().unwrap() = "┤".to_string();
+    let mut footer_with_bars = widths_with_bars
+        .iter()
+        .enumerate()
+        .map(|(i, w)| {
+            if i % 2 == 0 {
+                "┴".to_string()
+            } else {
+                "─".to_string().repeat(*w)
+            }
+        })
+        .collect_vec();
+    footer_with_bars[0] = "╰".to_string();
+    *footer_with_bars.last_mut().unwrap() = "╯".to_string();
+
+    let mut body = matrix
+        .iter()
+        .map(|row| {
+            let mut row_with_bars = vec!["│".to_string()];
+            row_with_bars.append(
+                &mut row
+                    .iter()
+                    .zip(vec!["│"; ncols].iter())
+                    .map(|(r, b)| vec![r.to_string(), b.to_string()])
+                    .collect_vec()
+                    .concat(),
+            );
+            row_with_bars
+        })
+        .map(|row| Row::new(row.iter().map(|c| Cell::from(c.to_string()))))
+        .collect_vec();
+    for (i, item) in body.iter_mut().enumerate() {
+        if i % 2 == 0 {
+            *item = item.clone().style(Style::default().bg(Color::DarkGray));
+        }
+    }
+    let mut rows: Vec<Row> = vec![
+        Row::new(top_with_bars),
+        Row::new(header_with_bars),
+        Row::new(separator_with_bars),
+    ];
+    rows.append(&mut body);
+    rows.push(Row::new(footer_with_bars));
+
+    (rows, widths)
 }
@@ -63,7 +63,6 @@ impl ReceiveScreen {
             let escalatable_event = self.escalatable_event.clone();
 
             tokio::spawn(async move {
-                // TODO: change to receive most recent wallet
                 let receiving_address = rpc_client
                     .own_receiving_address(context::current())
                     .await
@@ -85,10 +84,12 @@ impl ReceiveScreen {
         tokio::spawn(async move {
             *generating.lock().unwrap() = true;
             let receiving_address = rpc_client
-                .own_receiving_address(context::current())
+                .next_receiving_address(context::current())
                 .await
                 .unwrap();
-            *data.lock().unwrap() = Some(receiving_address.to_bech32m(network).unwrap());
+            if let Some(receiving_address) = receiving_address {
+                *data.lock().unwrap() = Some(receiving_address.to_bech32m(network).unwrap());
+            }
             *generating.lock().unwrap() = false;
             *escalatable_event.lock().unwrap() = Some(DashboardEvent::RefreshScreen);
         });
@@ -13,14 +13,14 @@ use super::{
 use crossterm::event::{Event, KeyCode, KeyEventKind};
 use neptune_core::{
     config_models::network::Network,
-    models::{
-        blockchain::transaction::neptune_coins::NeptuneCoins,
-        state::wallet::address::generation_address,
+    models::state::{
+        fee_estimation::DEFAULT_FEE_ESTIMATION_TARGET_BLOCKS,
+        wallet::{address::generation_address, coin_selection::CoinSelectionStrategy},
+        UtxoNotificationMedium,
     },
     rpc_server::RPCClient,
 };
 
-use num_traits::Zero;
 use ratatui::{
     layout::{Alignment, Margin},
     style::{Color, Modifier, Style},
@@ -30,10 +30,14 @@ use ratatui::{
 use tarpc::context;
 use tokio::{sync::Mutex, time::sleep};
 
+/// Fee input that makes the node estimate the fee
+const AUTO_FEE: &str = "auto";
+
 #[derive(Debug, Clone, PartialEq, Eq)]
 pub enum SendScreenWidget {
     Address,
     Amount,
+    Fee,
     Ok,
     Notice,
 }
@@ -48,6 +52,7 @@ pub struct SendScreen {
     rpc_client: Arc<RPCClient>,
     focus: Arc<Mutex<SendScreenWidget>>,
     amount: String,
+    fee: String,
     notice: Arc<Mutex<String>>,
     reset_me: Arc<Mutex<bool>>,
     escalatable_event: Arc<std::sync::Mutex<Option<DashboardEvent>>>,
@@ -65,6 +70,7 @@ impl SendScreen {
             rpc_client: rpc_server,
             focus: Arc::new(Mutex::new(SendScreenWidget::Address)),
             amount: "".to_string(),
+            fee: AUTO_FEE.to_string(),
             notice: Arc::new(Mutex::new("".to_string())),
             reset_me: Arc::new(Mutex::new(false)),
             escalatable_event: Arc::new(std::sync::Mutex::new(None)),
@@ -72,10 +78,12 @@ impl SendScreen {
         }
     }
 
+    #[allow(clippy::too_many_arguments)]
     async fn check_and_pay_sequence(
         rpc_client: Arc<RPCClient>,
         address: String,
         amount: String,
+        fee: String,
         notice_arc: Arc<Mutex<String>>,
         focus_arc: Arc<Mutex<SendScreenWidget>>,
         reset_me: Arc<Mutex<bool>>,
@@ -123,17 +131,53 @@ impl SendScreen {
             return;
         }
 
-        *notice_arc.lock().await = "Validated inputs; sending ...".to_string();
+        let fee = if fee.trim() == AUTO_FEE {
+            *notice_arc.lock().await = "Validated inputs; estimating fee ...".to_string();
+            let maybe_estimated_fee = rpc_client
+                .estimate_send_fee(
+                    context::current(),
+                    vec![(
+                        valid_address,
+                        valid_amount,
+                        UtxoNotificationMedium::OnChain,
+                        None,
+                    )],
+                    CoinSelectionStrategy::default(),
+                    DEFAULT_FEE_ESTIMATION_TARGET_BLOCKS,
+                )
+                .await
+                .unwrap();
+            match maybe_estimated_fee {
+                Some(fee) => fee,
+                None => {
+                    *notice_arc.lock().await = "Could not estimate fee.".to_string();
+                    *focus_arc.lock().await = SendScreenWidget::Fee;
+                    return;
+                }
+            }
+        } else {
+            let maybe_valid_fee = rpc_client
+                .validate_amount(context::current(), fee)
+                .await
+                .unwrap();
+            match maybe_valid_fee {
+                Some(fee) => fee,
+                None => {
+                    *notice_arc.lock().await = "Invalid fee.".to_string();
+                    *focus_arc.lock().await = SendScreenWidget::Fee;
+                    return;
+                }
+            }
+        };
 
-        // TODO: Let user specify this number
-        let fee = NeptuneCoins::zero();
+        *notice_arc.lock().await = format!("Validated inputs; sending with fee {fee} ...");
 
         // Allow the generation of proves to take some time...
         let mut send_ctx = context::current();
         const SEND_DEADLINE_IN_SECONDS: u64 = 40;
         send_ctx.deadline = SystemTime::now() + Duration::from_secs(SEND_DEADLINE_IN_SECONDS);
         let send_result = rpc_client
-            .send(send_ctx, valid_amount, valid_address, fee)
+            .send(send_ctx, valid_amount, valid_address, fee, None)
             .await
             .unwrap();
 
@@ -159,6 +203,7 @@ impl SendScreen {
         if let Ok(mut reset_me_mutex_guard) = self.reset_me.try_lock() {
             if reset_me_mutex_guard.to_owned() {
                 self.amount = "".to_string();
+                self.fee = AUTO_FEE.to_string();
                 self.address = "".to_string();
                 *reset_me_mutex_guard = false;
             }
@@ -181,6 +226,10 @@ impl SendScreen {
                                         )));
                                     }
                                     SendScreenWidget::Amount => {
+                                        *own_focus = SendScreenWidget::Fee;
+                                        escalate_event = Some(DashboardEvent::RefreshScreen);
+                                    }
+                                    SendScreenWidget::Fee => {
                                         *own_focus = SendScreenWidget::Ok;
                                         escalate_event = Some(DashboardEvent::RefreshScreen);
                                     }
@@ -189,6 +238,7 @@ impl SendScreen {
                                         let rpc_client = self.rpc_client.clone();
                                         let address = self.address.clone();
                                         let amount = self.amount.clone();
+                                        let fee = self.fee.clone();
                                         let notice = self.notice.clone();
                                         let focus = self.focus.clone();
                                         let reset_me = self.reset_me.clone();
@@ -196,7 +246,7 @@ impl SendScreen {
 
                                         tokio::spawn(async move {
                                             Self::check_and_pay_sequence(
-                                                rpc_client, address, amount, notice, focus,
+                                                rpc_client, address, amount, fee, notice, focus,
                                                 reset_me, network,
                                             )
                                             .await;
@@ -214,7 +264,8 @@ impl SendScreen {
                                 *own_focus = match own_focus.to_owned() {
                                     SendScreenWidget::Address => SendScreenWidget::Ok,
                                     SendScreenWidget::Amount => SendScreenWidget::Address,
-                                    SendScreenWidget::Ok => SendScreenWidget::Amount,
+                                    SendScreenWidget::Fee => SendScreenWidget::Amount,
+                                    SendScreenWidget::Ok => SendScreenWidget::Fee,
                                     SendScreenWidget::Notice => SendScreenWidget::Notice,
                                 };
                                 escalate_event = Some(DashboardEvent::RefreshScreen);
@@ -226,7 +277,8 @@ impl SendScreen {
                             if let Ok(mut own_focus) = self.focus.try_lock() {
                                 *own_focus = match own_focus.to_owned() {
                                     SendScreenWidget::Address => SendScreenWidget::Amount,
-                                    SendScreenWidget::Amount => SendScreenWidget::Ok,
+                                    SendScreenWidget::Amount => SendScreenWidget::Fee,
+                                    SendScreenWidget::Fee => SendScreenWidget::Ok,
                                     SendScreenWidget::Ok => SendScreenWidget::Address,
                                     SendScreenWidget::Notice => SendScreenWidget::Notice,
                                 };
@@ -240,6 +292,9 @@ impl SendScreen {
                                 if own_focus.to_owned() == SendScreenWidget::Amount {
                                     self.amount = format!("{}{}", self.amount, c);
                                     escalate_event = Some(DashboardEvent::RefreshScreen);
+                                } else if own_focus.to_owned() == SendScreenWidget::Fee {
+                                    self.fee = format!("{}{}", self.fee, c);
+                                    escalate_event = Some(DashboardEvent::RefreshScreen);
                                 } else {
                                     escalate_event = Some(event);
                                 }
@@ -254,6 +309,9 @@ impl SendScreen {
                                         self.amount.drain(self.amount.len() - 1..);
                                     }
                                     escalate_event = Some(DashboardEvent::RefreshScreen);
+                                } else if own_focus.to_owned() == SendScreenWidget::Fee {
+                                    self.fee.pop();
+                                    escalate_event = Some(DashboardEvent::RefreshScreen);
                                 }
                             } else {
                                 escalate_event = Some(event);
@@ -431,6 +489,49 @@ impl Widget for SendScreen {
             );
             amount_widget.render(amount_rect, buf);
 
+            // display fee widget
+            let fee = if let Ok(mg) = self.reset_me.try_lock() {
+                if mg.to_owned() {
+                    AUTO_FEE.to_string()
+                } else {
+                    self.fee
+                }
+            } else {
+                self.fee
+            };
+            let fee_rect = vrecter.next(3);
+            let fee_widget = Paragraph::new(Line::from(vec![
+                Span::from(fee),
+                if own_focus == SendScreenWidget::Fee {
+                    Span::styled(
+                        "|",
+                        if self.in_focus {
+                            Style::default().add_modifier(Modifier::RAPID_BLINK)
+                        } else {
+                            style
+                        },
+                    )
+                } else {
+                    Span::from(" ")
+                },
+            ]))
+            .style(if own_focus == SendScreenWidget::Fee && self.in_focus {
+                focus_style
+            } else {
+                style
+            })
+            .block(
+                Block::default()
+                    .borders(Borders::ALL)
+                    .title("Fee (amount or \"auto\")")
+                    .style(if own_focus == SendScreenWidget::Fee && self.in_focus {
+                        focus_style
+                    } else {
+                        style
+                    }),
+            );
+            fee_widget.render(fee_rect, buf);
+
             // send button
             let mut button_rect = vrecter.next(3);
             button_rect.width = 8;
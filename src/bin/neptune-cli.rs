@@ -1,25 +1,233 @@
 use neptune_core::prelude::twenty_first;
 
 use anyhow::{bail, Result};
+use chrono::{DateTime, NaiveDate};
 use clap::{CommandFactory, Parser};
 use clap_complete::{generate, Shell};
 
 use neptune_core::config_models::data_directory::DataDirectory;
 use neptune_core::config_models::network::Network;
+use neptune_core::config_models::peer_list::{
+    read_or_create_peer_list_signing_key, PeerList, SignedPeerList, PEER_LIST_FILE_VERSION,
+};
 use neptune_core::models::blockchain::transaction::neptune_coins::NeptuneCoins;
+use neptune_core::models::blockchain::transaction::timelock::Timelock;
+use neptune_core::models::blockchain::transaction::token::TokenDefinition;
+use neptune_core::models::state::fee_estimation::DEFAULT_FEE_ESTIMATION_TARGET_BLOCKS;
 use neptune_core::models::state::wallet::address::generation_address;
-use neptune_core::models::state::wallet::WalletSecret;
+use neptune_core::models::state::wallet::address::multisig_address::MultisigAddress;
+use neptune_core::models::state::wallet::coin_selection::CoinSelectionStrategy;
+use neptune_core::models::state::wallet::encrypted_secret::{
+    prompt_for_new_passphrase, prompt_for_passphrase,
+};
+use neptune_core::models::state::wallet::multisig::{MultisigSetup, PartiallySignedTransaction};
+use neptune_core::models::state::wallet::unsigned_transaction::{
+    SignedTransaction, UnsignedTransaction,
+};
+use neptune_core::models::state::wallet::utxo_transfer::UtxoTransferNotification;
+use neptune_core::models::state::wallet::wallet_state::GENERATION_ADDRESS_GAP_LIMIT;
+use neptune_core::models::state::wallet::{watch_only, WalletFileFormat, WalletSecret};
+use neptune_core::models::state::UtxoNotificationMedium;
+use serde::Deserialize;
 use std::io;
 use std::io::Write;
 use std::net::IpAddr;
 use std::net::SocketAddr;
+use std::path::{Path, PathBuf};
+use std::str::FromStr;
+use std::time::{Duration, Instant};
 use tarpc::{client, context, tokio_serde::formats::Json};
+use zeroize::Zeroize;
 
 use neptune_core::models::state::wallet::wallet_status::WalletStatus;
 use neptune_core::rpc_server::RPCClient;
 use std::io::stdout;
 use twenty_first::shared_math::digest::Digest;
 
+/// How long the client waits for wallet recovery requests, which walk the block history
+const WALLET_RECOVERY_TIMEOUT: Duration = Duration::from_secs(60 * 60);
+
+/// An output of `send-to-many` as given in a JSON file
+#[derive(Debug, Deserialize)]
+struct SendToManyOutput {
+    address: String,
+    amount: String,
+    #[serde(default)]
+    notification: NotificationMediumArg,
+}
+
+#[derive(Debug, Default, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+enum NotificationMediumArg {
+    #[default]
+    OnChain,
+    OffChain,
+}
+
+impl SendToManyOutput {
+    fn parse(
+        self,
+        network: Network,
+    ) -> Result<(
+        generation_address::ReceivingAddress,
+        NeptuneCoins,
+        UtxoNotificationMedium,
+    )> {
+        let address = generation_address::ReceivingAddress::from_bech32m(self.address, network)?;
+        let amount = NeptuneCoins::from_str(&self.amount)?;
+        if amount.is_negative() {
+            bail!("Cannot send negative amount {amount}.");
+        }
+        let notification_medium = match self.notification {
+            NotificationMediumArg::OnChain => UtxoNotificationMedium::OnChain,
+            NotificationMediumArg::OffChain => UtxoNotificationMedium::OffChain,
+        };
+
+        Ok((address, amount, notification_medium))
+    }
+}
+
+/// Parse an output of `send-to-many` given as `address:amount` or
+/// `address:amount:on-chain|off-chain`.
+fn parse_send_to_many_output(
+    output: &str,
+    network: Network,
+) -> Result<(
+    generation_address::ReceivingAddress,
+    NeptuneCoins,
+    UtxoNotificationMedium,
+)> {
+    let parts = output.split(':').collect::<Vec<_>>();
+    let notification = match parts.get(2) {
+        None | Some(&"on-chain") => NotificationMediumArg::OnChain,
+        Some(&"off-chain") => NotificationMediumArg::OffChain,
+        Some(other) => bail!("Unknown notification medium \"{other}\" in output \"{output}\"."),
+    };
+    if parts.len() < 2 || parts.len() > 3 {
+        bail!("Output \"{output}\" must have the form address:amount[:on-chain|off-chain].");
+    }
+
+    SendToManyOutput {
+        address: parts[0].to_owned(),
+        amount: parts[1].to_owned(),
+        notification,
+    }
+    .parse(network)
+}
+
+/// The fee of a transaction: either an amount, or `auto` for the fee that the node
+/// estimates is needed to be mined within a few blocks
+#[derive(Debug, Clone, Copy)]
+enum FeeArg {
+    Auto,
+    Amount(NeptuneCoins),
+}
+
+impl FromStr for FeeArg {
+    type Err = anyhow::Error;
+
+    fn from_str(input: &str) -> Result<Self> {
+        if input == "auto" {
+            return Ok(FeeArg::Auto);
+        }
+        let amount = NeptuneCoins::from_str(input)?;
+        if amount.is_negative() {
+            bail!("Fee cannot be negative.");
+        }
+
+        Ok(FeeArg::Amount(amount))
+    }
+}
+
+/// The time until which sent coins are locked: an RFC 3339 timestamp, or a date
+/// `YYYY-MM-DD` for midnight UTC at the start of that day
+#[derive(Debug, Clone, Copy)]
+struct ReleaseTimeArg(u64);
+
+impl FromStr for ReleaseTimeArg {
+    type Err = anyhow::Error;
+
+    fn from_str(input: &str) -> Result<Self> {
+        let release_time = match DateTime::parse_from_rfc3339(input) {
+            Ok(date_time) => date_time.timestamp_millis(),
+            Err(_) => match NaiveDate::parse_from_str(input, "%Y-%m-%d") {
+                Ok(date) => date
+                    .and_hms_opt(0, 0, 0)
+                    .unwrap()
+                    .and_utc()
+                    .timestamp_millis(),
+                Err(_) => bail!(
+                    "Release time \"{input}\" must be an RFC 3339 timestamp or a date YYYY-MM-DD."
+                ),
+            },
+        };
+        if release_time < 0 {
+            bail!("Release time cannot be before the UNIX epoch.");
+        }
+
+        Ok(ReleaseTimeArg(release_time as u64))
+    }
+}
+
+impl FeeArg {
+    /// Resolve the fee of a transaction of the given size. Without a size, an automatic fee
+    /// is estimated for a transaction of the average size in the mempool.
+    async fn resolve(
+        self,
+        client: &RPCClient,
+        ctx: context::Context,
+        transaction_size: Option<usize>,
+    ) -> Result<NeptuneCoins> {
+        match self {
+            FeeArg::Amount(amount) => Ok(amount),
+            FeeArg::Auto => {
+                let Some(fee) = client
+                    .estimate_fee(ctx, DEFAULT_FEE_ESTIMATION_TARGET_BLOCKS, transaction_size)
+                    .await?
+                else {
+                    bail!("Could not estimate fee. See the log of neptune-core for details.");
+                };
+                println!("Using estimated fee: {fee}");
+                Ok(fee)
+            }
+        }
+    }
+
+    /// Resolve the fee of a transaction that pays the given outputs from this wallet. An
+    /// automatic fee is estimated for the size of that transaction.
+    async fn resolve_for_outputs(
+        self,
+        client: &RPCClient,
+        ctx: context::Context,
+        outputs: Vec<(
+            generation_address::ReceivingAddress,
+            NeptuneCoins,
+            UtxoNotificationMedium,
+            Option<Timelock>,
+        )>,
+        coin_selection: CoinSelectionStrategy,
+    ) -> Result<NeptuneCoins> {
+        match self {
+            FeeArg::Amount(amount) => Ok(amount),
+            FeeArg::Auto => {
+                let Some(fee) = client
+                    .estimate_send_fee(
+                        ctx,
+                        outputs,
+                        coin_selection,
+                        DEFAULT_FEE_ESTIMATION_TARGET_BLOCKS,
+                    )
+                    .await?
+                else {
+                    bail!("Could not estimate fee. See the log of neptune-core for details.");
+                };
+                println!("Using estimated fee: {fee}");
+                Ok(fee)
+            }
+        }
+    }
+}
+
 #[derive(Debug, Parser)]
 enum Command {
     /// Dump shell completions.
@@ -43,29 +251,268 @@ enum Command {
     },
     SyncedBalance,
     WalletStatus,
+
+    /// List the transactions sent from this wallet that are not confirmed yet
+    UnconfirmedSends,
+
+    /// List the transactions sent from this wallet, with their recipients, fee, change,
+    /// label, and status
+    OutgoingTransactions,
     OwnReceivingAddress,
     MempoolTxCount,
     MempoolSize,
 
+    /// Estimate the fee needed for a transaction to be mined within the given number of
+    /// blocks. Without a transaction size, the average size of mempool transactions is used.
+    EstimateFee {
+        #[clap(long, default_value_t = DEFAULT_FEE_ESTIMATION_TARGET_BLOCKS)]
+        target_blocks: usize,
+
+        #[clap(long)]
+        transaction_size: Option<usize>,
+    },
+
     /******** CHANGE STATE ********/
     Shutdown,
     ClearAllStandings,
     ClearStandingByIp {
         ip: IpAddr,
     },
+    /// Generate a receiving address that was not handed out before
+    NewAddress,
     Send {
         amount: NeptuneCoins,
         address: String,
-        fee: NeptuneCoins,
+
+        /// An amount, or `auto` to use the estimated fee
+        fee: FeeArg,
+
+        /// A note on the payment, kept only in this wallet
+        #[clap(long)]
+        label: Option<String>,
+
+        /// Lock the coins until the given time: an RFC 3339 timestamp, or a date
+        /// YYYY-MM-DD for midnight UTC. The receiver cannot spend them before then.
+        #[clap(long)]
+        locked_until: Option<ReleaseTimeArg>,
+
+        /// Lock the coins until the given block height. The receiver cannot spend them
+        /// in a block below that height.
+        #[clap(long, conflicts_with = "locked_until")]
+        locked_until_height: Option<u64>,
+    },
+
+    /// Send to many receivers in one transaction. Outputs are given with repeated
+    /// `--to address:amount` arguments, where `address:amount:off-chain` leaves the
+    /// notification of the receiver out of the transaction, and/or as a JSON file with a
+    /// list of `{"address": .., "amount": .., "notification": "on-chain" | "off-chain"}`.
+    /// Notifications of off-chain outputs must be exported with `export-utxo-notification`
+    /// and delivered to their receivers.
+    SendToMany {
+        #[clap(long = "to")]
+        outputs: Vec<String>,
+
+        #[clap(long)]
+        file: Option<PathBuf>,
+
+        /// How the UTXOs that fund the transaction are selected: largest-first,
+        /// smallest-first, branch-and-bound, or random
+        #[clap(long, default_value_t = CoinSelectionStrategy::default())]
+        coin_selection: CoinSelectionStrategy,
+
+        /// An amount, or `auto` to use the estimated fee
+        #[clap(long)]
+        fee: FeeArg,
+
+        /// A note on the payment, kept only in this wallet
+        #[clap(long)]
+        label: Option<String>,
+    },
+
+    /// Set the label of a transaction sent from this wallet
+    LabelTransaction {
+        txid: Digest,
+        label: String,
+    },
+
+    /// Replace an unconfirmed transaction sent from this wallet with one that pays a higher
+    /// fee, such that it is mined sooner. The increase is taken from the change.
+    BumpFee {
+        txid: Digest,
+
+        /// An amount, or `auto` to use the estimated fee
+        fee: FeeArg,
     },
     PauseMiner,
     RestartMiner,
     PruneAbandonedMonitoredUtxos,
 
+    /// Write a file for each output of a sent transaction whose receiver must be notified
+    /// off-chain. The receiver claims the UTXO with `claim-utxo`.
+    ExportUtxoNotification {
+        txid: Digest,
+
+        #[clap(long, default_value = ".")]
+        output_dir: PathBuf,
+    },
+
+    /// Claim a UTXO sent to this wallet, from a file written by `export-utxo-notification`
+    ClaimUtxo {
+        file: PathBuf,
+    },
+
+    /// Rebuild the wallet's monitored UTXOs from the blocks from the given height up to
+    /// the tip, e.g. after importing a seed phrase
+    Rescan {
+        #[clap(long, default_value_t = 0)]
+        from_height: u64,
+    },
+
+    /// Restore monitored UTXOs that are missing from the wallet database from the file of
+    /// incoming randomness
+    RestoreMonitoredUtxos,
+
+    /******** MULTISIG ********/
+    /// Print this wallet's public key for multisig addresses. The participants of a
+    /// multisig address exchange their public keys to create it with `create-multisig`.
+    MultisigKey {
+        /// Use a new index for each multisig address, as a key signs only one transaction
+        #[clap(long, default_value_t = 0)]
+        index: u16,
+    },
+
+    /// Create an m-of-n multisig address from the public keys of its participants, and
+    /// write its setup to a file. Every participant imports the file with
+    /// `import-multisig`.
+    CreateMultisig {
+        #[clap(long)]
+        threshold: u8,
+
+        #[clap(long = "key")]
+        keys: Vec<Digest>,
+
+        #[clap(long)]
+        output: PathBuf,
+    },
+
+    /// Track the UTXOs of a multisig address that this wallet participates in, from a file
+    /// written by `create-multisig`
+    ImportMultisig {
+        file: PathBuf,
+    },
+
+    /// List the multisig addresses that this wallet participates in, with their balances
+    ListMultisig,
+
+    /// Create, sign, combine, and broadcast multisig transactions, and inspect
+    /// transactions
+    Tx {
+        #[clap(subcommand)]
+        command: TxCommand,
+    },
+
+    /******** TOKENS ********/
+    /// Define a new token that this wallet issues, and register it under the given name.
+    /// Prints the token definition, which holders register with `register-token`.
+    CreateToken {
+        name: String,
+    },
+
+    /// Register a token under the given name, such that this wallet can hold and send it
+    RegisterToken {
+        name: String,
+        definition: String,
+    },
+
+    /// List the registered tokens with their synced, unspent amounts
+    ListTokens,
+
+    /// Create new tokens of a token that this wallet issues, and send them to an address
+    IssueToken {
+        name: String,
+        amount: u64,
+        address: String,
+
+        /// An amount, or `auto` to use the estimated fee
+        #[clap(long)]
+        fee: FeeArg,
+    },
+
+    /// Send tokens of a registered token to an address. The fee is paid in native coins.
+    SendToken {
+        name: String,
+        amount: u64,
+        address: String,
+
+        /// An amount, or `auto` to use the estimated fee
+        #[clap(long)]
+        fee: FeeArg,
+    },
+
+    /******** OFFLINE SIGNING ********/
+    /// Create a transaction that pays the given outputs from this node's wallet, and write
+    /// it to a file for signing with `sign` on a machine that holds the wallet secret.
+    /// Works on nodes started with `--watch-only`. Outputs are given with repeated
+    /// `--to address:amount` arguments.
+    CreateUnsigned {
+        #[clap(long = "to")]
+        outputs: Vec<String>,
+
+        /// An amount, or `auto` to use the estimated fee
+        #[clap(long)]
+        fee: FeeArg,
+
+        #[clap(long)]
+        output: PathBuf,
+    },
+
+    /// Sign and prove a transaction written by `create-unsigned`, using only the wallet
+    /// file. Needs no running node, so it can run on a machine that is kept offline.
+    Sign {
+        #[clap(long, default_value_t=Network::default())]
+        network: Network,
+
+        file: PathBuf,
+
+        #[clap(long)]
+        output: PathBuf,
+
+        /// Prove all claims of the transaction, not only its lock scripts
+        #[clap(long)]
+        prove_all: bool,
+    },
+
+    /// Broadcast a transaction written by `sign`
+    BroadcastSigned {
+        file: PathBuf,
+    },
+
+    /******** PEER LISTS ********/
+    /// Sign a list of peers for nodes to load with `--peer-list-file`. The signing key is
+    /// read from the key file, which is created with a new key if it does not exist. Nodes
+    /// must trust the printed public key with `--peer-list-signer`.
+    SignPeerList {
+        #[clap(long, default_value_t=Network::default())]
+        network: Network,
+
+        #[clap(long = "peer")]
+        peers: Vec<SocketAddr>,
+
+        #[clap(long)]
+        key_file: PathBuf,
+
+        #[clap(long)]
+        output: PathBuf,
+    },
+
     /******** WALLET ********/
     GenerateWallet {
         #[clap(long, default_value_t=Network::default())]
         network: Network,
+
+        /// Encrypt the new wallet file under a passphrase
+        #[clap(long)]
+        encrypt: bool,
     },
     WhichWallet {
         #[clap(long, default_value_t=Network::default())]
@@ -78,6 +525,91 @@ enum Command {
     ImportSeedPhrase {
         #[clap(long, default_value_t=Network::default())]
         network: Network,
+
+        /// Encrypt the imported wallet file under a passphrase
+        #[clap(long)]
+        encrypt: bool,
+    },
+    /// Encrypt the wallet file under a new passphrase. Unencrypted (version 0) wallet
+    /// files are migrated to the encrypted format.
+    ChangePassphrase {
+        #[clap(long, default_value_t=Network::default())]
+        network: Network,
+    },
+
+    /// Print the view keys of the first addresses of the wallet, one per line. View keys
+    /// let a watch-only node see the UTXOs sent to these addresses, but not spend them.
+    ExportViewKeys {
+        #[clap(long, default_value_t=Network::default())]
+        network: Network,
+
+        /// The number of addresses to export view keys for, starting from derivation
+        /// index 0. Must cover all handed-out addresses.
+        #[clap(long, default_value_t=GENERATION_ADDRESS_GAP_LIMIT + 1)]
+        count: u16,
+    },
+
+    /// Import view keys, as printed by `export-view-keys`, from a file for use by a node
+    /// started with `--watch-only`
+    ImportViewKeys {
+        #[clap(long, default_value_t=Network::default())]
+        network: Network,
+
+        file: PathBuf,
+    },
+}
+
+#[derive(Debug, Parser)]
+enum TxCommand {
+    /// Create a transaction that spends all UTXOs of a multisig address, and write it to a
+    /// file for the participants to sign. Outputs are given with repeated
+    /// `--to address:amount` arguments. The rest goes to the `--change` multisig address.
+    Create {
+        from: String,
+
+        #[clap(long = "to")]
+        outputs: Vec<String>,
+
+        #[clap(long)]
+        change: Option<String>,
+
+        /// An amount, or `auto` to use the estimated fee
+        #[clap(long)]
+        fee: FeeArg,
+
+        #[clap(long)]
+        output: PathBuf,
+    },
+
+    /// Add this wallet's signature to a multisig transaction. A wallet's key for a multisig
+    /// address signs only one transaction.
+    Sign {
+        file: PathBuf,
+
+        /// Where to write the signed transaction. Defaults to the given file.
+        #[clap(long)]
+        output: Option<PathBuf>,
+    },
+
+    /// Merge the signatures of copies of the same multisig transaction
+    Combine {
+        files: Vec<PathBuf>,
+
+        #[clap(long)]
+        output: PathBuf,
+    },
+
+    /// Broadcast a multisig transaction that has enough signatures
+    Broadcast { file: PathBuf },
+
+    /// Print the kernel fields, witness type, and sizes of a transaction. The transaction
+    /// is looked up by ID in the mempool and the canonical chain, or decoded from a file
+    /// holding the bytes that peers send.
+    Show {
+        txid: Option<Digest>,
+
+        #[clap(long, conflicts_with = "txid")]
+        file: Option<PathBuf>,
     },
 }
 
@@ -95,6 +627,20 @@ struct Config {
     pub network: Network,
 }
 
+/// Read the wallet secret from file, asking for the passphrase if the file is encrypted.
+fn read_wallet_secret_interactively(wallet_file: &Path) -> Result<WalletSecret> {
+    match WalletSecret::wallet_file_format(wallet_file)? {
+        WalletFileFormat::Plaintext => WalletSecret::read_from_file(wallet_file),
+        WalletFileFormat::Encrypted => {
+            let mut passphrase = prompt_for_passphrase("Enter wallet passphrase: ")?;
+            let wallet_secret_res =
+                WalletSecret::read_from_file_with_passphrase(wallet_file, Some(&passphrase));
+            passphrase.zeroize();
+            wallet_secret_res
+        }
+    }
+}
+
 #[tokio::main]
 async fn main() -> Result<()> {
     let args: Config = Config::parse();
@@ -123,7 +669,35 @@ async fn main() -> Result<()> {
             }
             return Ok(());
         }
-        Command::GenerateWallet { network } => {
+        Command::SignPeerList {
+            network,
+            peers,
+            key_file,
+            output,
+        } => {
+            if peers.is_empty() {
+                bail!("No peers given.");
+            }
+            let (signing_key, is_new) = read_or_create_peer_list_signing_key(&key_file)?;
+            if is_new {
+                println!("Wrote new signing key to {}.", key_file.display());
+            }
+            let peer_list = PeerList {
+                version: PEER_LIST_FILE_VERSION,
+                network,
+                peers,
+            };
+            let signed_peer_list = SignedPeerList::sign(peer_list, &signing_key)?;
+            signed_peer_list.save_to_file(&output)?;
+            println!(
+                "Wrote peer list to {}. Nodes load it with `--peer-list-file {} --peer-list-signer {}`.",
+                output.display(),
+                output.display(),
+                signed_peer_list.signer
+            );
+            return Ok(());
+        }
+        Command::GenerateWallet { network, encrypt } => {
             // The root path is where both the wallet and all databases are stored
             let data_dir = DataDirectory::get(None, network)?;
 
@@ -131,8 +705,23 @@ async fn main() -> Result<()> {
             let wallet_dir = data_dir.wallet_directory_path();
             DataDirectory::create_dir_if_not_exists(&wallet_dir).await?;
 
-            let (wallet_secret, secret_file_paths) =
-                WalletSecret::read_from_file_or_create(&wallet_dir).unwrap();
+            let wallet_file = WalletSecret::wallet_secret_path(&wallet_dir);
+            let mut passphrase = if wallet_file.exists() {
+                match WalletSecret::wallet_file_format(&wallet_file)? {
+                    WalletFileFormat::Encrypted => {
+                        Some(prompt_for_passphrase("Enter wallet passphrase: ")?)
+                    }
+                    WalletFileFormat::Plaintext => None,
+                }
+            } else if encrypt {
+                Some(prompt_for_new_passphrase()?)
+            } else {
+                None
+            };
+            let wallet_res =
+                WalletSecret::read_from_file_or_create(&wallet_dir, passphrase.as_deref());
+            passphrase.zeroize();
+            let (wallet_secret, secret_file_paths) = wallet_res?;
 
             println!(
                 "Wallet stored in: {}\nMake sure you also see this path if you run the neptune-core client",
@@ -152,7 +741,7 @@ async fn main() -> Result<()> {
 
             return Ok(());
         }
-        Command::ImportSeedPhrase { network } => {
+        Command::ImportSeedPhrase { network, encrypt } => {
             // The root path is where both the wallet and all databases are stored
             let data_dir = DataDirectory::get(None, network)?;
             let wallet_dir = data_dir.wallet_directory_path();
@@ -203,9 +792,19 @@ async fn main() -> Result<()> {
             };
 
             // wallet file does not exist yet, so create it and save
+            let mut passphrase = if encrypt {
+                Some(prompt_for_new_passphrase()?)
+            } else {
+                None
+            };
             println!("Saving wallet to disk at {} ...", wallet_file.display());
             DataDirectory::create_dir_if_not_exists(&wallet_dir).await?;
-            match wallet_secret.save_to_disk(&wallet_file) {
+            let save_res = match passphrase.as_deref() {
+                Some(passphrase) => wallet_secret.save_to_disk_encrypted(&wallet_file, passphrase),
+                None => wallet_secret.save_to_disk(&wallet_file),
+            };
+            passphrase.zeroize();
+            match save_res {
                 Err(e) => {
                     println!("Could not save imported wallet to disk.");
                     println!("Error:");
@@ -213,6 +812,7 @@ async fn main() -> Result<()> {
                 }
                 Ok(_) => {
                     println!("Success.");
+                    println!("Run `neptune-cli rescan` once neptune-core has synced to recover the balance of the imported wallet.");
                 }
             }
 
@@ -232,7 +832,7 @@ async fn main() -> Result<()> {
                 println!("Generate one using `neptune-cli generate-wallet` or `neptune-wallet-gen`, or import a seed phrase using `neptune-cli import-seed-phrase`.");
                 return Ok(());
             }
-            let wallet_secret = match WalletSecret::read_from_file(&wallet_file) {
+            let wallet_secret = match read_wallet_secret_interactively(&wallet_file) {
                 Err(e) => {
                     println!("Could not export seed phrase.");
                     println!("Error:");
@@ -246,6 +846,158 @@ async fn main() -> Result<()> {
             }
             return Ok(());
         }
+        Command::ChangePassphrase { network } => {
+            // The root path is where both the wallet and all databases are stored
+            let data_dir = DataDirectory::get(None, network)?;
+            let wallet_dir = data_dir.wallet_directory_path();
+            let wallet_file = WalletSecret::wallet_secret_path(&wallet_dir);
+            if !wallet_file.exists() {
+                println!("No wallet file found at {}.", wallet_file.display());
+                return Ok(());
+            }
+
+            if WalletSecret::wallet_file_format(&wallet_file)? == WalletFileFormat::Plaintext {
+                println!(
+                    "Wallet file {} is not encrypted. It will be encrypted under the new passphrase.",
+                    wallet_file.display()
+                );
+            }
+            let wallet_secret = read_wallet_secret_interactively(&wallet_file)?;
+
+            let mut new_passphrase = prompt_for_new_passphrase()?;
+            let replace_res =
+                wallet_secret.replace_on_disk_encrypted(&wallet_file, &new_passphrase);
+            new_passphrase.zeroize();
+            replace_res?;
+
+            println!(
+                "Wallet file {} is now encrypted under the new passphrase.",
+                wallet_file.display()
+            );
+            println!("Start neptune-core with `--unlock` to unlock it.");
+            return Ok(());
+        }
+        Command::ExportViewKeys { network, count } => {
+            let data_dir = DataDirectory::get(None, network)?;
+            let wallet_dir = data_dir.wallet_directory_path();
+            let wallet_file = WalletSecret::wallet_secret_path(&wallet_dir);
+            if !wallet_file.exists() {
+                println!("No wallet file found at {}.", wallet_file.display());
+                return Ok(());
+            }
+            let wallet_secret = read_wallet_secret_interactively(&wallet_file)?;
+            for index in 0..count {
+                let view_key = wallet_secret
+                    .nth_generation_spending_key(index)
+                    .to_view_key(index);
+                println!("{}", view_key.to_bech32m(network)?);
+            }
+            return Ok(());
+        }
+        Command::ImportViewKeys { network, file } => {
+            let view_keys = std::fs::read_to_string(&file)?
+                .lines()
+                .map(str::trim)
+                .filter(|line| !line.is_empty())
+                .map(|line| generation_address::ViewKey::from_bech32m(line, network))
+                .collect::<Result<Vec<_>>>()?;
+            if view_keys.is_empty() {
+                bail!("No view keys found in {}", file.display());
+            }
+
+            let data_dir = DataDirectory::get(None, network)?;
+            let wallet_dir = data_dir.wallet_directory_path();
+            DataDirectory::create_dir_if_not_exists(&wallet_dir).await?;
+            watch_only::save_view_keys(&wallet_dir, &view_keys)?;
+
+            println!(
+                "Imported {} view keys to {}.",
+                view_keys.len(),
+                watch_only::view_keys_path(&wallet_dir).display()
+            );
+            println!("Start neptune-core with `--watch-only`, and run `neptune-cli rescan` once it has synced to recover the history of the watched addresses.");
+            return Ok(());
+        }
+        Command::CreateMultisig {
+            threshold,
+            keys,
+            output,
+        } => {
+            let setup = MultisigSetup::new(threshold, keys, rand::random())?;
+            setup.save_to_file(&output)?;
+            println!(
+                "Created {}-of-{} multisig address:",
+                threshold,
+                setup.address.public_keys.len()
+            );
+            println!("{}", setup.address.to_bech32m(args.network)?);
+            println!(
+                "Hand {} to the participants, who import it with `neptune-cli import-multisig`. It lets its holder see the UTXOs of the address.",
+                output.display()
+            );
+            return Ok(());
+        }
+        Command::Sign {
+            network,
+            file,
+            output,
+            prove_all,
+        } => {
+            if output.exists() {
+                bail!("File {} already exists", output.display());
+            }
+            let unsigned_transaction = UnsignedTransaction::read_from_file(&file)?;
+            if unsigned_transaction.network != network {
+                bail!(
+                    "Transaction is for network {}, not {network}",
+                    unsigned_transaction.network
+                );
+            }
+            let data_dir = DataDirectory::get(None, network)?;
+            let wallet_dir = data_dir.wallet_directory_path();
+            let wallet_file = WalletSecret::wallet_secret_path(&wallet_dir);
+            if !wallet_file.exists() {
+                println!("No wallet file found at {}.", wallet_file.display());
+                return Ok(());
+            }
+            let wallet_secret = read_wallet_secret_interactively(&wallet_file)?;
+
+            println!("Transaction {}", unsigned_transaction.kernel_hash());
+            println!("Spends: {}", unsigned_transaction.input_amount());
+            for (address, amount) in unsigned_transaction.recipients.iter() {
+                println!("Pays: {amount} to {}", address.to_bech32m(network)?);
+            }
+            println!("Change: {}", unsigned_transaction.change_amount());
+            println!("Fee: {}", unsigned_transaction.kernel.fee);
+            println!("Proving. This can take a while.");
+            let signed_transaction =
+                SignedTransaction::new(&unsigned_transaction, &wallet_secret, prove_all)?;
+            signed_transaction.save_to_file(&output)?;
+            println!(
+                "Wrote signed transaction {} to {}. Broadcast it with `neptune-cli broadcast-signed`.",
+                signed_transaction.txid(),
+                output.display()
+            );
+            return Ok(());
+        }
+        Command::Tx {
+            command: TxCommand::Combine { files, output },
+        } => {
+            let Some((first_file, other_files)) = files.split_first() else {
+                bail!("No transaction files given.");
+            };
+            let mut transaction = PartiallySignedTransaction::read_from_file(first_file)?;
+            for file in other_files {
+                transaction.combine(&PartiallySignedTransaction::read_from_file(file)?)?;
+            }
+            transaction.write_to_file(&output)?;
+            println!(
+                "Combined transaction has {} of the {} required signatures.",
+                transaction.signature_count(),
+                transaction.address.threshold
+            );
+            return Ok(());
+        }
         _ => {}
     }
 
@@ -259,7 +1011,16 @@ async fn main() -> Result<()> {
         | Command::GenerateWallet { .. }
         | Command::WhichWallet { .. }
         | Command::ExportSeedPhrase { .. }
-        | Command::ImportSeedPhrase { .. } => unreachable!("Case should be handled earlier."),
+        | Command::ImportSeedPhrase { .. }
+        | Command::ChangePassphrase { .. }
+        | Command::ExportViewKeys { .. }
+        | Command::ImportViewKeys { .. }
+        | Command::CreateMultisig { .. }
+        | Command::Sign { .. }
+        | Command::SignPeerList { .. }
+        | Command::Tx {
+            command: TxCommand::Combine { .. },
+        } => unreachable!("Case should be handled earlier."),
 
         /******** READ STATE ********/
         Command::Network => {
@@ -337,6 +1098,28 @@ async fn main() -> Result<()> {
             let wallet_status: WalletStatus = client.wallet_status(ctx).await?;
             println!("{}", wallet_status)
         }
+        Command::UnconfirmedSends => {
+            let unconfirmed_sends = client.unconfirmed_sends(ctx).await?;
+            if unconfirmed_sends.is_empty() {
+                println!("No unconfirmed transactions.");
+            }
+            for (txid, amount, fee) in unconfirmed_sends {
+                println!("{txid}: amount {amount}, fee {fee}");
+            }
+        }
+        Command::OutgoingTransactions => {
+            for record in client.outgoing_transactions(ctx).await? {
+                let time = neptune_core::utc_timestamp_to_localtime(record.timestamp.as_millis());
+                println!("{} [{}] {time}", record.txid, record.status);
+                for (address, amount) in record.recipients.iter() {
+                    println!("    to {}: {amount}", address.to_bech32m(args.network)?);
+                }
+                println!("    fee: {}; change: {}", record.fee, record.change);
+                if !record.label.is_empty() {
+                    println!("    label: {}", record.label);
+                }
+            }
+        }
         Command::OwnReceivingAddress => {
             let rec_addr: generation_address::ReceivingAddress =
                 client.own_receiving_address(ctx).await?;
@@ -350,6 +1133,16 @@ async fn main() -> Result<()> {
             let size_in_bytes: usize = client.mempool_size(ctx).await?;
             println!("{} bytes", size_in_bytes);
         }
+        Command::EstimateFee {
+            target_blocks,
+            transaction_size,
+        } => match client
+            .estimate_fee(ctx, target_blocks, transaction_size)
+            .await?
+        {
+            Some(fee) => println!("{fee}"),
+            None => println!("Could not estimate fee. See the log of neptune-core for details."),
+        },
 
         /******** CHANGE STATE ********/
         Command::Shutdown => {
@@ -365,17 +1158,123 @@ async fn main() -> Result<()> {
             client.clear_standing_by_ip(ctx, ip).await?;
             println!("Cleared standing of {}", ip);
         }
+        Command::NewAddress => match client.next_receiving_address(ctx).await? {
+            Some(rec_addr) => println!("{}", rec_addr.to_bech32m(args.network).unwrap()),
+            None => println!("Could not generate new receiving address."),
+        },
         Command::Send {
             amount,
             address,
             fee,
+            label,
+            locked_until,
+            locked_until_height,
         } => {
             // Parse on client
             let receiving_address =
                 generation_address::ReceivingAddress::from_bech32m(address.clone(), args.network)?;
 
-            client.send(ctx, amount, receiving_address, fee).await?;
+            let timelock = locked_until
+                .map(|ReleaseTimeArg(release_time)| Timelock::Time(release_time))
+                .or(locked_until_height.map(Timelock::Height));
+            let fee = fee
+                .resolve_for_outputs(
+                    &client,
+                    ctx,
+                    vec![(
+                        receiving_address,
+                        amount,
+                        UtxoNotificationMedium::OnChain,
+                        timelock,
+                    )],
+                    CoinSelectionStrategy::default(),
+                )
+                .await?;
+            let maybe_txid = client
+                .send(ctx, amount, receiving_address, fee, timelock)
+                .await?;
             println!("Send-command issues. Recipient: {address}; amount: {amount}");
+            if let (Some(txid), Some(label)) = (maybe_txid, label) {
+                client.label_outgoing_transaction(ctx, txid, label).await?;
+            }
+        }
+        Command::SendToMany {
+            outputs,
+            file,
+            coin_selection,
+            fee,
+            label,
+        } => {
+            // Parse on client
+            let mut parsed_outputs = vec![];
+            for output in outputs.iter() {
+                parsed_outputs.push(parse_send_to_many_output(output, args.network)?);
+            }
+            if let Some(file) = file {
+                let file_outputs: Vec<SendToManyOutput> =
+                    serde_json::from_str(&std::fs::read_to_string(&file)?)?;
+                for output in file_outputs {
+                    parsed_outputs.push(output.parse(args.network)?);
+                }
+            }
+            if parsed_outputs.is_empty() {
+                bail!("No outputs given. Use `--to` or `--file`.");
+            }
+
+            let output_count = parsed_outputs.len();
+            let fee = fee
+                .resolve_for_outputs(
+                    &client,
+                    ctx,
+                    parsed_outputs
+                        .iter()
+                        .map(|(address, amount, notification_medium)| {
+                            (*address, *amount, *notification_medium, None)
+                        })
+                        .collect(),
+                    coin_selection,
+                )
+                .await?;
+
+            match client
+                .send_to_many(ctx, parsed_outputs, coin_selection, fee)
+                .await?
+            {
+                Some((txid, off_chain_notifications)) => {
+                    println!("Sent transaction {txid} with {output_count} outputs.");
+                    if let Some(label) = label {
+                        client.label_outgoing_transaction(ctx, txid, label).await?;
+                    }
+                    if !off_chain_notifications.is_empty() {
+                        println!(
+                            "{} outputs must be notified off-chain. Run `neptune-cli export-utxo-notification {txid}` and deliver the files to their receivers.",
+                            off_chain_notifications.len()
+                        );
+                    }
+                }
+                None => println!("Send failed. See the log of neptune-core for details."),
+            }
+        }
+        Command::LabelTransaction { txid, label } => {
+            if client.label_outgoing_transaction(ctx, txid, label).await? {
+                println!("Labeled transaction {txid}.");
+            } else {
+                println!("This wallet did not send a transaction with ID {txid}.");
+            }
+        }
+        Command::BumpFee { txid, fee } => {
+            // The replacement has the size of the transaction it replaces
+            let transaction_size = client
+                .get_transaction(ctx, txid)
+                .await?
+                .map(|transaction_details| transaction_details.sizes.total);
+            let fee = fee.resolve(&client, ctx, transaction_size).await?;
+            match client.bump_fee(ctx, txid, fee).await? {
+                Some(replacement_txid) => {
+                    println!("Replaced transaction {txid} by {replacement_txid} with fee {fee}.")
+                }
+                None => println!("Could not bump fee. See the log of neptune-core for details."),
+            }
         }
         Command::PauseMiner => {
             println!("Sending command to pause miner.");
@@ -392,6 +1291,295 @@ async fn main() -> Result<()> {
             let prunt_res_count = client.prune_abandoned_monitored_utxos(ctx).await?;
             println!("{prunt_res_count} monitored UTXOs marked as abandoned");
         }
+
+        Command::ExportUtxoNotification { txid, output_dir } => {
+            let Some(notifications) = client.utxo_transfer_notifications(ctx, txid).await? else {
+                bail!(
+                    "Could not export UTXO notifications. See the log of neptune-core for details."
+                );
+            };
+            if notifications.is_empty() {
+                println!("Transaction {txid} has no outputs that must be notified off-chain.");
+            }
+            for (i, notification) in notifications.iter().enumerate() {
+                let path = output_dir.join(format!(
+                    "utxo-{:016x}-{i}.json",
+                    notification.addition_record.canonical_commitment.values()[0].value()
+                ));
+                notification.save_to_file(&path)?;
+                println!("{}", path.to_string_lossy());
+            }
+        }
+
+        Command::ClaimUtxo { file } => {
+            let notification = UtxoTransferNotification::read_from_file(&file)?;
+            let mut claim_ctx = context::current();
+            claim_ctx.deadline = Instant::now() + WALLET_RECOVERY_TIMEOUT;
+            match client.claim_utxo(claim_ctx, notification).await? {
+                Some(status) => println!("Claimed UTXO: {status}"),
+                None => println!("Could not claim UTXO. See the log of neptune-core for details."),
+            }
+        }
+
+        Command::Rescan { from_height } => {
+            let mut rescan_ctx = context::current();
+            rescan_ctx.deadline = Instant::now() + WALLET_RECOVERY_TIMEOUT;
+            match client.rescan(rescan_ctx, from_height.into()).await? {
+                Some(restored_count) => {
+                    println!("Rescan restored {restored_count} monitored UTXOs")
+                }
+                None => println!("Rescan failed. See the log of neptune-core for details."),
+            }
+        }
+
+        Command::RestoreMonitoredUtxos => {
+            let mut restore_ctx = context::current();
+            restore_ctx.deadline = Instant::now() + WALLET_RECOVERY_TIMEOUT;
+            match client.restore_monitored_utxos(restore_ctx).await? {
+                Some(restored_count) => println!("Restored {restored_count} monitored UTXOs"),
+                None => println!(
+                    "Restoring monitored UTXOs failed. See the log of neptune-core for details."
+                ),
+            }
+        }
+
+        /******** MULTISIG ********/
+        Command::MultisigKey { index } => match client.multisig_public_key(ctx, index).await? {
+            Some(public_key) => println!("{public_key}"),
+            None => {
+                bail!("Could not derive multisig key. See the log of neptune-core for details.")
+            }
+        },
+
+        Command::ImportMultisig { file } => {
+            let setup = MultisigSetup::read_from_file(&file)?;
+            let mut import_ctx = context::current();
+            import_ctx.deadline = Instant::now() + WALLET_RECOVERY_TIMEOUT;
+            match client.import_multisig_setup(import_ctx, setup).await? {
+                Some(utxo_count) => {
+                    println!("Imported multisig address with {utxo_count} unspent UTXOs")
+                }
+                None => println!(
+                    "Could not import multisig address. See the log of neptune-core for details."
+                ),
+            }
+        }
+
+        Command::ListMultisig => {
+            for (address, balance) in client.multisig_addresses(ctx).await? {
+                println!(
+                    "{} {}-of-{} {balance}",
+                    address.to_bech32m(args.network)?,
+                    address.threshold,
+                    address.public_keys.len()
+                );
+            }
+        }
+
+        Command::Tx { command } => match command {
+            TxCommand::Create {
+                from,
+                outputs,
+                change,
+                fee,
+                output,
+            } => {
+                if output.exists() {
+                    bail!("File {} already exists", output.display());
+                }
+                let address = MultisigAddress::from_bech32m(&from, args.network)?;
+                let change_address = change
+                    .map(|change| MultisigAddress::from_bech32m(&change, args.network))
+                    .transpose()?;
+                let mut parsed_outputs = vec![];
+                for output in outputs.iter() {
+                    let (receiving_address, amount, notification_medium) =
+                        parse_send_to_many_output(output, args.network)?;
+                    if notification_medium == UtxoNotificationMedium::OffChain {
+                        bail!("Outputs of multisig transactions are notified on-chain.");
+                    }
+                    parsed_outputs.push((receiving_address, amount));
+                }
+                let fee = fee.resolve(&client, ctx, None).await?;
+
+                let Some(transaction) = client
+                    .create_multisig_transaction(ctx, address, parsed_outputs, change_address, fee)
+                    .await?
+                else {
+                    bail!("Could not create multisig transaction. See the log of neptune-core for details.");
+                };
+                transaction.write_to_file(&output)?;
+                println!(
+                    "Wrote transaction {} to {}. It needs {} signatures.",
+                    transaction.kernel_hash(),
+                    output.display(),
+                    transaction.address.threshold
+                );
+            }
+            TxCommand::Sign { file, output } => {
+                let transaction = PartiallySignedTransaction::read_from_file(&file)?;
+                let Some(transaction) = client.sign_multisig_transaction(ctx, transaction).await?
+                else {
+                    bail!("Could not sign multisig transaction. See the log of neptune-core for details.");
+                };
+                let output = output.unwrap_or(file);
+                transaction.write_to_file(&output)?;
+                println!(
+                    "Signed transaction {}. It has {} of the {} required signatures.",
+                    transaction.kernel_hash(),
+                    transaction.signature_count(),
+                    transaction.address.threshold
+                );
+            }
+            TxCommand::Broadcast { file } => {
+                let transaction = PartiallySignedTransaction::read_from_file(&file)?;
+                let mut broadcast_ctx = context::current();
+                broadcast_ctx.deadline = Instant::now() + WALLET_RECOVERY_TIMEOUT;
+                match client
+                    .broadcast_multisig_transaction(broadcast_ctx, transaction)
+                    .await?
+                {
+                    Some(txid) => println!("Broadcast transaction {txid}"),
+                    None => println!(
+                        "Could not broadcast multisig transaction. See the log of neptune-core for details."
+                    ),
+                }
+            }
+            TxCommand::Show { txid, file } => {
+                let details = match (txid, file) {
+                    (Some(txid), None) => {
+                        // The lookup walks the block history if the transaction is not
+                        // in the mempool
+                        let mut lookup_ctx = context::current();
+                        lookup_ctx.deadline = Instant::now() + WALLET_RECOVERY_TIMEOUT;
+                        client.get_transaction(lookup_ctx, txid).await?
+                    }
+                    (None, Some(file)) => {
+                        let bytes = std::fs::read(&file)?;
+                        client.decode_transaction(ctx, bytes).await?
+                    }
+                    _ => bail!("Give either a transaction ID or a file."),
+                };
+                match details {
+                    Some(details) => println!("{details}"),
+                    None => println!(
+                        "Could not find or decode transaction. See the log of neptune-core for details."
+                    ),
+                }
+            }
+            TxCommand::Combine { .. } => unreachable!("Case should be handled earlier."),
+        },
+
+        /******** TOKENS ********/
+        Command::CreateToken { name } => match client.create_token(ctx, name.clone()).await? {
+            Some(definition) => {
+                println!("Registered token {name} with definition:");
+                println!("{}", definition.to_bech32m(args.network)?);
+            }
+            None => bail!("Could not create token. See the log of neptune-core for details."),
+        },
+
+        Command::RegisterToken { name, definition } => {
+            let definition = TokenDefinition::from_bech32m(&definition, args.network)?;
+            if client.register_token(ctx, name.clone(), definition).await? {
+                println!("Registered token {name}");
+            } else {
+                bail!("Could not register token. See the log of neptune-core for details.");
+            }
+        }
+
+        Command::ListTokens => {
+            for (known_token, amount) in client.tokens(ctx).await? {
+                println!(
+                    "{} {} {amount}",
+                    known_token.name,
+                    known_token.definition.to_bech32m(args.network)?
+                );
+            }
+        }
+
+        Command::IssueToken {
+            name,
+            amount,
+            address,
+            fee,
+        } => {
+            let receiving_address =
+                generation_address::ReceivingAddress::from_bech32m(address.clone(), args.network)?;
+            let fee = fee.resolve(&client, ctx, None).await?;
+            match client
+                .issue_token(ctx, name.clone(), amount, receiving_address, fee)
+                .await?
+            {
+                Some(txid) => println!("Issued {amount} {name} to {address} in transaction {txid}"),
+                None => bail!("Could not issue tokens. See the log of neptune-core for details."),
+            }
+        }
+
+        Command::SendToken {
+            name,
+            amount,
+            address,
+            fee,
+        } => {
+            let receiving_address =
+                generation_address::ReceivingAddress::from_bech32m(address.clone(), args.network)?;
+            let fee = fee.resolve(&client, ctx, None).await?;
+            match client
+                .send_token(ctx, name.clone(), amount, receiving_address, fee)
+                .await?
+            {
+                Some(txid) => println!("Sent {amount} {name} to {address} in transaction {txid}"),
+                None => bail!("Could not send tokens. See the log of neptune-core for details."),
+            }
+        }
+
+        /******** OFFLINE SIGNING ********/
+        Command::CreateUnsigned {
+            outputs,
+            fee,
+            output,
+        } => {
+            if output.exists() {
+                bail!("File {} already exists", output.display());
+            }
+            let mut parsed_outputs = vec![];
+            for output in outputs.iter() {
+                let (receiving_address, amount, notification_medium) =
+                    parse_send_to_many_output(output, args.network)?;
+                if notification_medium == UtxoNotificationMedium::OffChain {
+                    bail!("Outputs of unsigned transactions are notified on-chain.");
+                }
+                parsed_outputs.push((receiving_address, amount));
+            }
+            let fee = fee.resolve(&client, ctx, None).await?;
+
+            let Some(unsigned_transaction) = client
+                .create_unsigned_transaction(ctx, parsed_outputs, fee)
+                .await?
+            else {
+                bail!("Could not create unsigned transaction. See the log of neptune-core for details.");
+            };
+            unsigned_transaction.save_to_file(&output)?;
+            println!(
+                "Wrote unsigned transaction {} to {}. Sign it with `neptune-cli sign`.",
+                unsigned_transaction.kernel_hash(),
+                output.display()
+            );
+        }
+
+        Command::BroadcastSigned { file } => {
+            let signed_transaction = SignedTransaction::read_from_file(&file)?;
+            match client
+                .broadcast_signed_transaction(ctx, signed_transaction)
+                .await?
+            {
+                Some(txid) => println!("Broadcast transaction {txid}"),
+                None => println!(
+                    "Could not broadcast signed transaction. See the log of neptune-core for details."
+                ),
+            }
+        }
     }
 
     Ok(())
@@ -1,4 +1,5 @@
 use super::network::Network;
+use super::peer_list::PeerListPublicKey;
 use bytesize::ByteSize;
 use clap::builder::RangedI64ValueParser;
 use clap::Parser;
@@ -55,6 +56,14 @@ pub struct Args {
     #[clap(long)]
     pub unrestricted_mining: bool,
 
+    /// If mining, prove the validity of a found block before announcing it. Ignored if
+    /// mine flag not set.
+    ///
+    /// Proving takes much longer than finding a block, so it is disabled by default.
+    /// Blocks without a validity proof are validated by their receivers.
+    #[clap(long)]
+    pub prove_mined_blocks: bool,
+
     /// Prune the mempool when it exceeds this size in RAM.
     ///
     /// Units: B (bytes), K (kilobytes), M (megabytes), G (gigabytes)
@@ -104,6 +113,31 @@ pub struct Args {
     #[structopt(long)]
     pub peers: Vec<SocketAddr>,
 
+    /// Encrypt and authenticate outgoing peer connections, and refuse incoming peer
+    /// connections that are not encrypted.
+    ///
+    /// Incoming encrypted connections are accepted without this flag. Connections are
+    /// authenticated with a static key that is kept in the data directory.
+    #[clap(long)]
+    pub encrypt_peer_connections: bool,
+
+    /// Don't connect to the built-in seed nodes of the network.
+    #[clap(long)]
+    pub no_seed_peers: bool,
+
+    /// A signed file of peers to connect to, as written by `neptune-cli sign-peer-list`.
+    ///
+    /// The file is only used if its signer is given with `--peer-list-signer`.
+    #[clap(long, value_name = "PATH", requires = "peer_list_signers")]
+    pub peer_list_file: Option<PathBuf>,
+
+    /// The public key of a trusted signer of peer list files, as printed by
+    /// `neptune-cli sign-peer-list`.
+    ///
+    /// E.g.: --peer-list-signer <64 hex digits>
+    #[clap(long = "peer-list-signer", value_name = "KEY")]
+    pub peer_list_signers: Vec<PeerListPublicKey>,
+
     /// Specify network, `alpha`, `testnet`, or `regtest`
     #[structopt(long, short, default_value = "alpha")]
     pub network: Network,
@@ -124,6 +158,26 @@ pub struct Args {
     #[clap(long, default_value = "false")]
     pub privacy: bool,
 
+    /// Unlock an encrypted wallet file by asking for its passphrase at startup.
+    ///
+    /// If no wallet file exists yet, a new wallet is created and encrypted under a
+    /// passphrase that is asked for twice. To encrypt an existing wallet file, or to change
+    /// its passphrase, use `neptune-cli change-passphrase`.
+    #[clap(long)]
+    pub unlock: bool,
+
+    /// Rebuild the wallet database from the file of incoming randomness in the wallet
+    /// directory. Use this if the wallet database was deleted or is corrupted. An existing
+    /// wallet database is kept as a backup next to the new one.
+    #[clap(long)]
+    pub restore_wallet_db: bool,
+
+    /// Run with a watch-only wallet, which monitors the addresses of the view keys that
+    /// were imported with `neptune-cli import-view-keys`. The wallet secret is not read,
+    /// so the node can show balances and history, but cannot send or mine.
+    #[clap(long)]
+    pub watch_only: bool,
+
     /// Enable tokio tracing for consumption by the tokio-console application
     /// note: this will attempt to connect to localhost:6669
     #[structopt(long, name = "tokio-console", default_value = "false")]
@@ -5,11 +5,12 @@ use std::path::{Path, PathBuf};
 use crate::config_models::network::Network;
 use crate::models::database::DATABASE_DIRECTORY_ROOT_NAME;
 use crate::models::state::archival_state::{BLOCK_INDEX_DB_NAME, MUTATOR_SET_DIRECTORY_NAME};
-use crate::models::state::networking_state::BANNED_IPS_DB_NAME;
+use crate::models::state::networking_state::{BANNED_IPS_DB_NAME, PEER_ADDRESS_BOOK_DB_NAME};
 use crate::models::state::shared::{
     BLOCK_FILENAME_EXTENSION, BLOCK_FILENAME_PREFIX, DIR_NAME_FOR_BLOCKS,
 };
 use crate::models::state::wallet::{WALLET_DB_NAME, WALLET_DIRECTORY, WALLET_OUTPUT_COUNT_DB_NAME};
+use crate::peer_transport::PEER_TRANSPORT_KEY_FILE_NAME;
 
 // TODO: Add `rusty_leveldb::Options` and `fs::OpenOptions` here too, since they keep being repeated.
 #[derive(Debug, Clone)]
@@ -84,6 +85,23 @@ impl DataDirectory {
         self.database_dir_path().join(Path::new(BANNED_IPS_DB_NAME))
     }
 
+    /// The peer address book database directory path.
+    ///
+    /// This directory lives within `DataDirectory::database_dir_path()`.
+    pub fn peer_address_book_database_dir_path(&self) -> PathBuf {
+        self.database_dir_path()
+            .join(Path::new(PEER_ADDRESS_BOOK_DB_NAME))
+    }
+
+    ///////////////////////////////////////////////////////////////////////////
+    ///
+    /// The path of the file that holds the static key of encrypted peer connections.
+    ///
+    /// This file lives within `DataDirectory::root_dir_path()`.
+    pub fn peer_transport_key_path(&self) -> PathBuf {
+        self.data_dir.join(Path::new(PEER_TRANSPORT_KEY_FILE_NAME))
+    }
+
     ///////////////////////////////////////////////////////////////////////////
     ///
     /// The wallet file path
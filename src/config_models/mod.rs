@@ -1,3 +1,4 @@
 pub mod cli_args;
 pub mod data_directory;
 pub mod network;
+pub mod peer_list;
@@ -1,5 +1,6 @@
 use serde::{Deserialize, Serialize};
 use std::fmt;
+use std::net::SocketAddr;
 use std::str::FromStr;
 use strum::EnumIter;
 
@@ -11,6 +12,23 @@ pub enum Network {
     RegTest,
 }
 
+impl Network {
+    /// The nodes that a fresh node on this network can connect to, before it knows any
+    /// other peers.
+    pub fn seed_peers(&self) -> Vec<SocketAddr> {
+        let seed_peers: &[&str] = match self {
+            Network::Alpha => &["139.162.193.206:9798", "[2001:bc8:611:1c72::1]:9798"],
+            // No public nodes are run on these networks
+            Network::Testnet | Network::RegTest => &[],
+        };
+
+        seed_peers
+            .iter()
+            .map(|address| address.parse().unwrap())
+            .collect()
+    }
+}
+
 impl fmt::Display for Network {
     fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
         let string = match self {
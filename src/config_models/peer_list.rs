@@ -0,0 +1,265 @@
+use anyhow::{bail, ensure, Context, Result};
+use ed25519_dalek::{Signature, Signer, SigningKey, Verifier, VerifyingKey};
+use itertools::Itertools;
+use rand::rngs::OsRng;
+use serde::{Deserialize, Serialize};
+use std::fmt;
+use std::fs;
+use std::net::SocketAddr;
+use std::path::Path;
+use std::str::FromStr;
+
+use super::network::Network;
+use crate::util_types::secret_file::create_secret_file;
+
+/// The version of the format of peer list files.
+pub const PEER_LIST_FILE_VERSION: u8 = 0;
+
+/// A list of peers that a node can connect to on the given network, as distributed
+/// in peer list files.
+#[derive(Clone, Debug, Serialize, Deserialize, PartialEq, Eq)]
+pub struct PeerList {
+    pub version: u8,
+    pub network: Network,
+    pub peers: Vec<SocketAddr>,
+}
+
+/// A peer list file: a peer list, the public key of its signer, and the Ed25519
+/// signature of the bincode encoding of the peer list. Stored as JSON, e.g.
+///
+/// ```json
+/// {
+///   "peer_list": {
+///     "version": 0,
+///     "network": "Alpha",
+///     "peers": ["139.162.193.206:9798", "[2001:bc8:611:1c72::1]:9798"]
+///   },
+///   "signer": "<public key, 64 hex digits>",
+///   "signature": "<signature, 128 hex digits>"
+/// }
+/// ```
+///
+/// Nodes only use a peer list file if its signer is given with `--peer-list-signer`.
+#[derive(Clone, Debug, Serialize, Deserialize, PartialEq, Eq)]
+pub struct SignedPeerList {
+    pub peer_list: PeerList,
+    pub signer: PeerListPublicKey,
+    signature: String,
+}
+
+impl SignedPeerList {
+    pub fn sign(peer_list: PeerList, signing_key: &SigningKey) -> Result<Self> {
+        let signature = signing_key.sign(&bincode::serialize(&peer_list)?);
+
+        Ok(Self {
+            peer_list,
+            signer: PeerListPublicKey(signing_key.verifying_key()),
+            signature: to_hex(&signature.to_bytes()),
+        })
+    }
+
+    /// Return the peers of the list if it is for the given network, and is signed by
+    /// one of the trusted signers.
+    pub fn verify(
+        &self,
+        network: Network,
+        trusted_signers: &[PeerListPublicKey],
+    ) -> Result<&[SocketAddr]> {
+        ensure!(
+            self.peer_list.version == PEER_LIST_FILE_VERSION,
+            "Unsupported peer list version {}",
+            self.peer_list.version
+        );
+        ensure!(
+            self.peer_list.network == network,
+            "Peer list is for network {}, not {network}",
+            self.peer_list.network
+        );
+        ensure!(
+            trusted_signers.contains(&self.signer),
+            "Peer list is signed by {}, which is not a trusted signer",
+            self.signer
+        );
+
+        let signature_bytes: [u8; 64] = from_hex(&self.signature)?
+            .try_into()
+            .ok()
+            .context("Peer list signature must be 64 bytes")?;
+        self.signer
+            .0
+            .verify(
+                &bincode::serialize(&self.peer_list)?,
+                &Signature::from_bytes(&signature_bytes),
+            )
+            .context("Invalid peer list signature")?;
+
+        Ok(&self.peer_list.peers)
+    }
+
+    /// Write the signed peer list to a new file. Refuses to overwrite existing files.
+    pub fn save_to_file(&self, path: &Path) -> Result<()> {
+        if path.exists() {
+            bail!("File {} already exists", path.to_string_lossy());
+        }
+        let as_json = serde_json::to_string_pretty(self)?;
+        fs::write(path, as_json)
+            .with_context(|| format!("Failed to write {}", path.to_string_lossy()))
+    }
+
+    pub fn read_from_file(path: &Path) -> Result<Self> {
+        let as_json = fs::read_to_string(path)
+            .with_context(|| format!("Failed to read {}", path.to_string_lossy()))?;
+        serde_json::from_str(&as_json)
+            .with_context(|| format!("Failed to decode peer list from {}", path.to_string_lossy()))
+    }
+}
+
+/// The public key of a peer list signer, written as 64 hex digits.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct PeerListPublicKey(pub VerifyingKey);
+
+impl fmt::Display for PeerListPublicKey {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", to_hex(self.0.as_bytes()))
+    }
+}
+
+impl FromStr for PeerListPublicKey {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self> {
+        let bytes: [u8; 32] = from_hex(s)?
+            .try_into()
+            .ok()
+            .context("Peer list public key must be 32 bytes")?;
+        let verifying_key =
+            VerifyingKey::from_bytes(&bytes).context("Invalid peer list public key")?;
+        Ok(Self(verifying_key))
+    }
+}
+
+impl Serialize for PeerListPublicKey {
+    fn serialize<S: serde::Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.serialize_str(&self.to_string())
+    }
+}
+
+impl<'de> Deserialize<'de> for PeerListPublicKey {
+    fn deserialize<D: serde::Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        let as_hex = String::deserialize(deserializer)?;
+        Self::from_str(&as_hex).map_err(serde::de::Error::custom)
+    }
+}
+
+/// Read the key for signing peer lists from a file, or create the file with a new
+/// key if it does not exist. The second return value is true if the key is new.
+pub fn read_or_create_peer_list_signing_key(path: &Path) -> Result<(SigningKey, bool)> {
+    if path.exists() {
+        let as_hex = fs::read_to_string(path)
+            .with_context(|| format!("Failed to read {}", path.to_string_lossy()))?;
+        let bytes: [u8; 32] = from_hex(as_hex.trim())?
+            .try_into()
+            .ok()
+            .context("Peer list signing key must be 32 bytes")?;
+        return Ok((SigningKey::from_bytes(&bytes), false));
+    }
+
+    let signing_key = SigningKey::generate(&mut OsRng);
+    create_secret_file(path, to_hex(signing_key.as_bytes()).as_bytes())
+        .with_context(|| format!("Failed to write {}", path.to_string_lossy()))?;
+    Ok((signing_key, true))
+}
+
+fn to_hex(bytes: &[u8]) -> String {
+    bytes.iter().map(|byte| format!("{byte:02x}")).join("")
+}
+
+fn from_hex(s: &str) -> Result<Vec<u8>> {
+    ensure!(s.len() % 2 == 0, "Hex string has odd length");
+    (0..s.len())
+        .step_by(2)
+        .map(|i| {
+            s.get(i..i + 2)
+                .and_then(|pair| u8::from_str_radix(pair, 16).ok())
+                .with_context(|| format!("Invalid hex string: {s}"))
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::tests::shared::unit_test_data_directory;
+
+    fn peer_list(network: Network) -> PeerList {
+        PeerList {
+            version: PEER_LIST_FILE_VERSION,
+            network,
+            peers: vec![
+                "139.162.193.206:9798".parse().unwrap(),
+                "[2001:bc8:611:1c72::1]:9798".parse().unwrap(),
+            ],
+        }
+    }
+
+    #[test]
+    fn signed_peer_list_verifies_only_for_trusted_signer_and_network() {
+        let signing_key = SigningKey::generate(&mut OsRng);
+        let signer = PeerListPublicKey(signing_key.verifying_key());
+        let other_signer = PeerListPublicKey(SigningKey::generate(&mut OsRng).verifying_key());
+        let signed = SignedPeerList::sign(peer_list(Network::Alpha), &signing_key).unwrap();
+
+        assert_eq!(
+            peer_list(Network::Alpha).peers,
+            signed
+                .verify(Network::Alpha, &[other_signer, signer])
+                .unwrap()
+        );
+        assert!(signed.verify(Network::Alpha, &[other_signer]).is_err());
+        assert!(signed.verify(Network::Testnet, &[signer]).is_err());
+
+        // The file format survives a round trip, and tampering is detected
+        let as_json = serde_json::to_string_pretty(&signed).unwrap();
+        let decoded: SignedPeerList = serde_json::from_str(&as_json).unwrap();
+        assert_eq!(signed, decoded);
+
+        let mut tampered = decoded;
+        tampered
+            .peer_list
+            .peers
+            .push("1.2.3.4:9798".parse().unwrap());
+        assert!(tampered.verify(Network::Alpha, &[signer]).is_err());
+    }
+
+    #[test]
+    fn public_key_hex_round_trip() {
+        let signer = PeerListPublicKey(SigningKey::generate(&mut OsRng).verifying_key());
+        assert_eq!(signer, signer.to_string().parse().unwrap());
+        assert!("abc".parse::<PeerListPublicKey>().is_err());
+        assert!("zz".repeat(32).parse::<PeerListPublicKey>().is_err());
+    }
+
+    #[test]
+    fn signing_key_file_is_created_owner_only() {
+        let data_dir = unit_test_data_directory(Network::Alpha).unwrap();
+        fs::create_dir_all(data_dir.root_dir_path()).unwrap();
+        let path = data_dir.root_dir_path().join("peer_list_signing_key");
+
+        let (signing_key, is_new) = read_or_create_peer_list_signing_key(&path).unwrap();
+        assert!(is_new);
+        let (read_key, is_new) = read_or_create_peer_list_signing_key(&path).unwrap();
+        assert!(!is_new);
+        assert_eq!(signing_key.as_bytes(), read_key.as_bytes());
+
+        #[cfg(unix)]
+        {
+            use std::os::unix::fs::PermissionsExt;
+            let mode = fs::metadata(&path).unwrap().permissions().mode();
+            assert_eq!(
+                0o600,
+                mode & 0o777,
+                "Only the owner may read the signing key"
+            );
+        }
+    }
+}
@@ -1,39 +1,214 @@
 use anyhow::{bail, Result};
+use bytes::{Bytes, BytesMut};
 use futures::{FutureExt, SinkExt, TryStreamExt};
-use std::{fmt::Debug, net::SocketAddr};
+use std::{
+    fmt::{self, Debug},
+    io,
+    net::SocketAddr,
+    pin::Pin,
+};
 use tokio::{
-    io::{AsyncRead, AsyncWrite},
+    io::{AsyncRead, AsyncReadExt, AsyncWrite},
     sync::{broadcast, mpsc},
 };
-use tokio_serde::{
-    formats::{Bincode, SymmetricalBincode},
-    SymmetricallyFramed,
-};
-use tokio_util::codec::{Framed, LengthDelimitedCodec};
+use tokio_serde::SymmetricallyFramed;
+use tokio_util::codec::{Framed, FramedParts};
 use tracing::{debug, error, info, warn};
 
 use crate::{
     models::{
         channel::{MainToPeerThread, PeerThreadToMain},
         peer::{
-            ConnectionRefusedReason, ConnectionStatus, HandshakeData, PeerMessage, PeerStanding,
+            ConnectionRefusedReason, ConnectionStatus, HandshakeData, InstanceId, PeerMessage,
+            PeerMessageType, PeerStanding,
         },
         state::GlobalStateLock,
     },
     peer_loop::PeerLoopHandler,
+    peer_transport::{
+        initiate_encrypted_transport, instance_id_from_public_key, respond_encrypted_transport,
+        PeerCodec, ENCRYPTED_TRANSPORT_MARKER,
+    },
     MAGIC_STRING_REQUEST, MAGIC_STRING_RESPONSE,
 };
 
-// Max peer message size is 2000MB
-pub const MAX_PEER_FRAME_LENGTH_IN_BYTES: usize = 2000 * 1024 * 1024;
+// Max peer message size is 256MB and 1kB, the size limit of the largest type of
+// message, see `PeerMessageType::max_size`
+pub const MAX_PEER_FRAME_LENGTH_IN_BYTES: usize = 256 * 1024 * 1024 + 1024;
+
+/// Serialization of peer messages with bincode, which checks the size of a frame
+/// against the size limit of the type of message that it holds before decoding it.
+#[derive(Clone, Copy, Debug, Default)]
+pub struct PeerMessageCodec;
+
+/// The error of a frame that is larger than its type of message allows
+#[derive(Clone, Copy, Debug)]
+pub struct OversizedPeerMessage {
+    pub message_type: PeerMessageType,
+    pub size: usize,
+}
+
+impl fmt::Display for OversizedPeerMessage {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "{:?} message of {} bytes, exceeding the limit of {} bytes",
+            self.message_type,
+            self.size,
+            self.message_type.max_size()
+        )
+    }
+}
+
+impl std::error::Error for OversizedPeerMessage {}
+
+impl OversizedPeerMessage {
+    /// The size limit violation that a stream of peer messages failed with, if any
+    pub fn from_stream_error(err: &(dyn std::error::Error + 'static)) -> Option<Self> {
+        err.downcast_ref::<io::Error>()
+            .and_then(|err| err.get_ref())
+            .and_then(|err| err.downcast_ref::<Self>())
+            .copied()
+    }
+}
+
+impl tokio_serde::Deserializer<PeerMessage> for PeerMessageCodec {
+    type Error = io::Error;
+
+    fn deserialize(self: Pin<&mut Self>, src: &BytesMut) -> Result<PeerMessage, io::Error> {
+        // Bincode encodes the variant of a message as the same `u32` as its type
+        let message_type: PeerMessageType = src
+            .get(..4)
+            .and_then(|variant| bincode::deserialize(variant).ok())
+            .ok_or_else(|| {
+                io::Error::new(io::ErrorKind::InvalidData, "Unknown type of peer message")
+            })?;
+        if src.len() > message_type.max_size() {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidData,
+                OversizedPeerMessage {
+                    message_type,
+                    size: src.len(),
+                },
+            ));
+        }
+
+        bincode::deserialize(src).map_err(|err| io::Error::new(io::ErrorKind::InvalidData, err))
+    }
+}
+
+impl tokio_serde::Serializer<PeerMessage> for PeerMessageCodec {
+    type Error = io::Error;
+
+    fn serialize(self: Pin<&mut Self>, item: &PeerMessage) -> Result<Bytes, io::Error> {
+        bincode::serialize(item)
+            .map(Bytes::from)
+            .map_err(|err| io::Error::new(io::ErrorKind::InvalidData, err))
+    }
+}
 
 /// Use this function to ensure that the same rules apply for both
 /// ingoing and outgoing connections. This limits the size of messages
 /// peers can send.
-fn get_codec_rules() -> LengthDelimitedCodec {
-    let mut codec_rules = LengthDelimitedCodec::new();
-    codec_rules.set_max_frame_length(MAX_PEER_FRAME_LENGTH_IN_BYTES);
-    codec_rules
+fn get_codec_rules() -> PeerCodec {
+    PeerCodec::plain(MAX_PEER_FRAME_LENGTH_IN_BYTES)
+}
+
+/// Set up the transport of an incoming connection, which is encrypted if the peer starts
+/// with the encrypted transport marker. Returns the framed stream, and the instance ID
+/// that the peer must use if the connection is encrypted. Fails if the peer does not
+/// complete the encrypted handshake in time. Unlike in outgoing connections, the static
+/// key of the peer is not trusted on first use, as the peer has not yet claimed an
+/// address for it to be recorded under.
+///
+/// Locking:
+///   * acquires `global_state_lock` for read
+async fn accept_transport<S>(
+    mut stream: S,
+    global_state_lock: &GlobalStateLock,
+    peer_address: SocketAddr,
+) -> Result<(Framed<S, PeerCodec>, Option<InstanceId>)>
+where
+    S: AsyncRead + AsyncWrite + Unpin,
+{
+    let first_byte = stream.read_u8().await?;
+    if first_byte == ENCRYPTED_TRANSPORT_MARKER {
+        let transport_key = global_state_lock
+            .lock_guard()
+            .await
+            .net
+            .transport_key
+            .clone();
+        let (transport_state, peer_static_key) =
+            respond_encrypted_transport(&mut stream, &transport_key).await?;
+        info!("Incoming connection from {peer_address} is encrypted");
+        let codec = PeerCodec::encrypted(MAX_PEER_FRAME_LENGTH_IN_BYTES, transport_state);
+        let peer_instance_id = instance_id_from_public_key(&peer_static_key);
+        return Ok((Framed::new(stream, codec), Some(peer_instance_id)));
+    }
+
+    if global_state_lock.cli().encrypt_peer_connections {
+        bail!("Refusing unencrypted incoming connection from {peer_address}");
+    }
+
+    // The first byte belongs to the first frame
+    let mut parts = FramedParts::new::<Bytes>(stream, get_codec_rules());
+    parts.read_buf.extend_from_slice(&[first_byte]);
+    Ok((Framed::from_parts(parts), None))
+}
+
+/// Set up the transport of an outgoing connection, which is encrypted if the CLI
+/// arguments say so. Returns the framed stream, and the instance ID that the peer must use
+/// if the connection is encrypted. The static key of the peer is trusted on first use.
+///
+/// Locking:
+///   * acquires `global_state_lock` for read
+///   * acquires `global_state_lock` for write
+async fn make_transport<S>(
+    mut stream: S,
+    global_state_lock: &GlobalStateLock,
+    peer_address: SocketAddr,
+) -> Result<(Framed<S, PeerCodec>, Option<InstanceId>)>
+where
+    S: AsyncRead + AsyncWrite + Unpin,
+{
+    if !global_state_lock.cli().encrypt_peer_connections {
+        return Ok((Framed::new(stream, get_codec_rules()), None));
+    }
+
+    let transport_key = global_state_lock
+        .lock_guard()
+        .await
+        .net
+        .transport_key
+        .clone();
+    let (transport_state, peer_static_key) =
+        initiate_encrypted_transport(&mut stream, &transport_key).await?;
+    global_state_lock
+        .lock_guard_mut()
+        .await
+        .net
+        .check_peer_static_key(peer_address, peer_static_key)
+        .await?;
+    let codec = PeerCodec::encrypted(MAX_PEER_FRAME_LENGTH_IN_BYTES, transport_state);
+    let peer_instance_id = instance_id_from_public_key(&peer_static_key);
+    Ok((Framed::new(stream, codec), Some(peer_instance_id)))
+}
+
+/// Check that a peer uses the instance ID bound to its static key, if the connection is
+/// encrypted.
+fn check_instance_id_binding(
+    bound_instance_id: Option<InstanceId>,
+    peer_handshake: &HandshakeData,
+    peer_address: &SocketAddr,
+) -> Result<()> {
+    match bound_instance_id {
+        Some(bound_instance_id) if bound_instance_id != peer_handshake.instance_id => bail!(
+            "Peer {peer_address} claims instance ID {}, but its key is bound to {bound_instance_id}",
+            peer_handshake.instance_id
+        ),
+        _ => Ok(()),
+    }
 }
 
 /// Check if connection is allowed. Used for both ingoing and outgoing connections.
@@ -189,13 +364,14 @@ where
     info!("Established incoming TCP connection with {peer_address}");
 
     // Build the communication/serialization/frame handler
-    let length_delimited = Framed::new(stream, get_codec_rules());
+    let (length_delimited, bound_instance_id) =
+        accept_transport(stream, &state, peer_address).await?;
     let mut peer: tokio_serde::Framed<
-        Framed<S, LengthDelimitedCodec>,
+        Framed<S, PeerCodec>,
         PeerMessage,
         PeerMessage,
-        Bincode<PeerMessage, PeerMessage>,
-    > = SymmetricallyFramed::new(length_delimited, SymmetricalBincode::default());
+        PeerMessageCodec,
+    > = SymmetricallyFramed::new(length_delimited, PeerMessageCodec);
 
     // Complete Neptune handshake
     let peer_handshake_data: HandshakeData = match peer.try_next().await? {
@@ -204,6 +380,7 @@ where
             if v != crate::MAGIC_STRING_REQUEST {
                 bail!("Expected magic value, got {:?}", v);
             }
+            check_instance_id_binding(bound_instance_id, &hsd, &peer_address)?;
 
             peer.send(PeerMessage::Handshake(Box::new((
                 crate::MAGIC_STRING_RESPONSE.to_vec(),
@@ -281,11 +458,17 @@ pub async fn call_peer_wrapper(
         match tokio::net::TcpStream::connect(peer_address).await {
             Err(e) => {
                 warn!("Failed to establish connection: {}", e);
+                state
+                    .lock_guard_mut()
+                    .await
+                    .net
+                    .record_peer_connection_failure(peer_address)
+                    .await;
             }
             Ok(stream) => {
                 match call_peer(
                     stream,
-                    state,
+                    state.clone(),
                     peer_address,
                     main_to_peer_thread_rx,
                     peer_thread_to_main_tx,
@@ -295,7 +478,18 @@ pub async fn call_peer_wrapper(
                 .await
                 {
                     Ok(()) => (),
-                    Err(e) => error!("An error occurred: {}. Connection closing", e),
+                    Err(e) => {
+                        error!("An error occurred: {}. Connection closing", e);
+
+                        // Includes refused handshakes, so peers that turn us away are
+                        // retried with backoff.
+                        state
+                            .lock_guard_mut()
+                            .await
+                            .net
+                            .record_peer_connection_failure(peer_address)
+                            .await;
+                    }
                 }
             }
         };
@@ -334,13 +528,14 @@ where
     info!("Established outgoing TCP connection with {peer_address}");
 
     // Build the communication/serialization/frame handler
-    let length_delimited = Framed::new(stream, get_codec_rules());
+    let (length_delimited, bound_instance_id) =
+        make_transport(stream, &state, peer_address).await?;
     let mut peer: tokio_serde::Framed<
-        Framed<S, LengthDelimitedCodec>,
+        Framed<S, PeerCodec>,
         PeerMessage,
         PeerMessage,
-        Bincode<PeerMessage, PeerMessage>,
-    > = SymmetricallyFramed::new(length_delimited, SymmetricalBincode::default());
+        PeerMessageCodec,
+    > = SymmetricallyFramed::new(length_delimited, PeerMessageCodec);
 
     // Make Neptune handshake
     peer.send(PeerMessage::Handshake(Box::new((
@@ -356,6 +551,7 @@ where
             if v != MAGIC_STRING_RESPONSE {
                 bail!("Didn't get expected magic value for handshake");
             }
+            check_instance_id_binding(bound_instance_id, &hsd, &peer_address)?;
             if hsd.network != own_handshake.network {
                 bail!(
                     "Cannot connect with {}: Peer runs {}, this client runs {}.",
@@ -472,6 +668,7 @@ mod connect_tests {
     use crate::models::peer::{
         ConnectionStatus, PeerInfo, PeerMessage, PeerSanctionReason, PeerStanding,
     };
+    use crate::peer_transport::PeerTransportKey;
     use crate::tests::shared::{
         get_dummy_handshake_data_for_genesis, get_dummy_latest_block,
         get_dummy_peer_connection_data_genesis, get_dummy_socket_address, get_test_genesis_setup,
@@ -714,6 +911,125 @@ mod connect_tests {
         Ok(())
     }
 
+    #[traced_test]
+    #[tokio::test]
+    async fn test_incoming_unencrypted_connection_refused_if_encryption_required() -> Result<()> {
+        let network = Network::Alpha;
+        let other_handshake = get_dummy_handshake_data_for_genesis(network);
+        let own_handshake = get_dummy_handshake_data_for_genesis(network);
+
+        // Only the first byte is read before the connection is refused
+        let handshake_bytes = to_bytes(&PeerMessage::Handshake(Box::new((
+            MAGIC_STRING_REQUEST.to_vec(),
+            other_handshake,
+        ))))?;
+        let mock = Builder::new().read(&handshake_bytes[..1]).build();
+        let (
+            _peer_broadcast_tx,
+            from_main_rx_clone,
+            to_main_tx,
+            _to_main_rx1,
+            mut state_lock,
+            _hsd,
+        ) = get_test_genesis_setup(network, 0).await?;
+        let mut cli = state_lock.cli().clone();
+        cli.encrypt_peer_connections = true;
+        state_lock.set_cli(cli).await;
+
+        let answer = answer_peer(
+            mock,
+            state_lock.clone(),
+            get_dummy_socket_address(0),
+            from_main_rx_clone,
+            to_main_tx,
+            own_handshake,
+        )
+        .await;
+        assert!(answer.is_err(), "unencrypted connection must be refused");
+
+        Ok(())
+    }
+
+    #[traced_test]
+    #[tokio::test]
+    async fn test_incoming_encrypted_connection_binds_instance_id() -> Result<()> {
+        let network = Network::Alpha;
+        for claim_bound_instance_id in [true, false] {
+            let (
+                _peer_broadcast_tx,
+                from_main_rx_clone,
+                to_main_tx,
+                _to_main_rx1,
+                state_lock,
+                _hsd,
+            ) = get_test_genesis_setup(network, 0).await?;
+            let mut own_handshake = get_dummy_handshake_data_for_genesis(network);
+            own_handshake.instance_id = state_lock.lock_guard().await.net.instance_id;
+            let (mut client_stream, server_stream) = tokio::io::duplex(1 << 16);
+            let server = tokio::spawn(answer_peer(
+                server_stream,
+                state_lock.clone(),
+                get_dummy_socket_address(0),
+                from_main_rx_clone,
+                to_main_tx,
+                own_handshake.clone(),
+            ));
+
+            let client_key = PeerTransportKey::generate();
+            let (transport_state, server_static_key) =
+                initiate_encrypted_transport(&mut client_stream, &client_key).await?;
+            assert_eq!(
+                own_handshake.instance_id,
+                instance_id_from_public_key(&server_static_key)
+            );
+            let mut client: tokio_serde::Framed<
+                Framed<tokio::io::DuplexStream, PeerCodec>,
+                PeerMessage,
+                PeerMessage,
+                PeerMessageCodec,
+            > = SymmetricallyFramed::new(
+                Framed::new(
+                    client_stream,
+                    PeerCodec::encrypted(MAX_PEER_FRAME_LENGTH_IN_BYTES, transport_state),
+                ),
+                PeerMessageCodec,
+            );
+
+            let mut other_handshake = get_dummy_handshake_data_for_genesis(network);
+            if claim_bound_instance_id {
+                other_handshake.instance_id = client_key.instance_id();
+            }
+            client
+                .send(PeerMessage::Handshake(Box::new((
+                    MAGIC_STRING_REQUEST.to_vec(),
+                    other_handshake,
+                ))))
+                .await?;
+
+            if claim_bound_instance_id {
+                match client.try_next().await? {
+                    Some(PeerMessage::Handshake(payload)) => {
+                        assert_eq!(own_handshake, payload.1);
+                    }
+                    _ => bail!("Expected handshake response"),
+                }
+                match client.try_next().await? {
+                    Some(PeerMessage::ConnectionStatus(ConnectionStatus::Accepted)) => (),
+                    _ => bail!("Expected connection to be accepted"),
+                }
+                client.send(PeerMessage::Bye).await?;
+                server.await??;
+            } else {
+                assert!(
+                    server.await?.is_err(),
+                    "instance ID that is not bound to the key must be refused"
+                );
+            }
+        }
+
+        Ok(())
+    }
+
     #[traced_test]
     #[tokio::test]
     async fn test_incoming_connection_fail_bad_magic_value() -> Result<()> {
@@ -956,4 +1272,32 @@ mod connect_tests {
 
         Ok(())
     }
+
+    #[test]
+    fn codec_rejects_oversized_frames_before_decoding() {
+        let mut codec = PeerMessageCodec;
+        let message = PeerMessage::BlockRequestBatch(vec![Digest::default(); 10], 5);
+        let encoded = BytesMut::from(
+            &tokio_serde::Serializer::serialize(Pin::new(&mut codec), &message).unwrap()[..],
+        );
+        let decoded = tokio_serde::Deserializer::deserialize(Pin::new(&mut codec), &encoded);
+        assert_eq!(message, decoded.unwrap());
+
+        // A frame that is too large for its type is rejected whatever its content
+        let mut oversized = encoded.clone();
+        oversized.resize(PeerMessageType::BlockRequestBatch.max_size() + 1, 0);
+        let err =
+            tokio_serde::Deserializer::<PeerMessage>::deserialize(Pin::new(&mut codec), &oversized)
+                .unwrap_err();
+        let oversized = OversizedPeerMessage::from_stream_error(&err).unwrap();
+        assert_eq!(PeerMessageType::BlockRequestBatch, oversized.message_type);
+
+        let unknown_type = BytesMut::from(&u32::MAX.to_le_bytes()[..]);
+        let err = tokio_serde::Deserializer::<PeerMessage>::deserialize(
+            Pin::new(&mut codec),
+            &unknown_type,
+        )
+        .unwrap_err();
+        assert!(OversizedPeerMessage::from_stream_error(&err).is_none());
+    }
 }
@@ -11,6 +11,7 @@ pub mod main_loop;
 pub mod mine_loop;
 pub mod models;
 pub mod peer_loop;
+pub mod peer_transport;
 pub mod prelude;
 pub mod rpc_server;
 pub mod util_types;
@@ -23,6 +24,7 @@ use triton_vm::prelude::BFieldElement;
 pub mod tests;
 
 use crate::config_models::data_directory::DataDirectory;
+use crate::config_models::peer_list::SignedPeerList;
 use crate::connect_to_peers::call_peer_wrapper;
 use crate::main_loop::MainLoopHandler;
 use crate::models::channel::RPCServerToMain;
@@ -32,11 +34,15 @@ use crate::models::state::blockchain_state::{BlockchainArchivalState, Blockchain
 use crate::models::state::light_state::LightState;
 use crate::models::state::mempool::Mempool;
 use crate::models::state::networking_state::NetworkingState;
+use crate::models::state::wallet::encrypted_secret::{
+    prompt_for_new_passphrase, prompt_for_passphrase,
+};
 use crate::models::state::wallet::wallet_state::WalletState;
-use crate::models::state::wallet::WalletSecret;
+use crate::models::state::wallet::{watch_only, WalletFileFormat, WalletSecret};
 use crate::models::state::GlobalStateLock;
+use crate::peer_transport::PeerTransportKey;
 use crate::rpc_server::RPC;
-use anyhow::{Context, Result};
+use anyhow::{bail, Context, Result};
 use config_models::cli_args;
 
 use crate::twenty_first::sync::{LockCallbackFn, LockEvent};
@@ -45,12 +51,14 @@ use chrono::{DateTime, Local, NaiveDateTime, Utc};
 use futures::future;
 use futures::Future;
 use futures::StreamExt;
+use itertools::Itertools;
 use models::blockchain::block::Block;
 use models::blockchain::shared::Hash;
 use models::peer::PeerInfo;
 use std::collections::HashMap;
 use std::env;
 use std::net::SocketAddr;
+use std::path::Path;
 use tarpc::server;
 use tarpc::server::incoming::Incoming;
 use tarpc::server::Channel;
@@ -58,7 +66,8 @@ use tarpc::tokio_serde::formats::*;
 use tokio::net::TcpListener;
 use tokio::sync::{broadcast, mpsc, watch};
 use tokio::time::Instant;
-use tracing::{info, trace};
+use tracing::{info, trace, warn};
+use zeroize::Zeroize;
 
 use crate::models::channel::{MainToMiner, MainToPeerThread, MinerToMain, PeerThreadToMain};
 use crate::models::peer::HandshakeData;
@@ -80,11 +89,32 @@ pub async fn initialize(cli_args: cli_args::Args) -> Result<()> {
     // Get wallet object, create various wallet secret files
     let wallet_dir = data_dir.wallet_directory_path();
     DataDirectory::create_dir_if_not_exists(&wallet_dir).await?;
-    let (wallet_secret, _) =
-        WalletSecret::read_from_file_or_create(&data_dir.wallet_directory_path())?;
-    info!("Now getting wallet state. This may take a while if the database needs pruning.");
-    let wallet_state =
-        WalletState::new_from_wallet_secret(&data_dir, wallet_secret, &cli_args).await;
+    let wallet_state = if cli_args.watch_only {
+        if cli_args.mine {
+            bail!("A watch-only node cannot mine, as it cannot spend the mining reward.");
+        }
+        let view_keys = watch_only::read_view_keys(&wallet_dir)?;
+        info!(
+            "Watching {} addresses with imported view keys",
+            view_keys.len()
+        );
+        if cli_args.restore_wallet_db {
+            move_wallet_database_aside(&data_dir).await?;
+        }
+        info!("Now getting wallet state. This may take a while if the database needs pruning.");
+        WalletState::new_watch_only(&data_dir, view_keys, &cli_args).await
+    } else {
+        let mut passphrase = unlock_passphrase(&wallet_dir, cli_args.unlock)?;
+        let wallet_secret_res =
+            WalletSecret::read_from_file_or_create(&wallet_dir, passphrase.as_deref());
+        passphrase.zeroize();
+        let (wallet_secret, _) = wallet_secret_res?;
+        if cli_args.restore_wallet_db {
+            move_wallet_database_aside(&data_dir).await?;
+        }
+        info!("Now getting wallet state. This may take a while if the database needs pruning.");
+        WalletState::new_from_wallet_secret(&data_dir, wallet_secret, &cli_args).await
+    };
     info!("Got wallet state.");
 
     // Connect to or create databases for block index, peers, mutator set, block sync
@@ -94,6 +124,10 @@ pub async fn initialize(cli_args: cli_args::Args) -> Result<()> {
     let peer_databases = NetworkingState::initialize_peer_databases(&data_dir).await?;
     info!("Got peer database");
 
+    let transport_key =
+        PeerTransportKey::read_from_file_or_create(&data_dir.peer_transport_key_path())?;
+    info!("Got peer transport key");
+
     let archival_mutator_set = ArchivalState::initialize_mutator_set(&data_dir).await?;
     info!("Got archival mutator set");
 
@@ -121,7 +155,7 @@ pub async fn initialize(cli_args: cli_args::Args) -> Result<()> {
     // Create handshake data which is used when connecting to outgoing peers specified in the
     // CLI arguments
     let syncing = false;
-    let networking_state = NetworkingState::new(peer_map, peer_databases, syncing);
+    let networking_state = NetworkingState::new(peer_map, peer_databases, syncing, transport_key);
 
     let light_state: LightState = LightState::from(latest_block.clone());
     let blockchain_archival_state = BlockchainArchivalState {
@@ -150,12 +184,12 @@ pub async fn initialize(cli_args: cli_args::Args) -> Result<()> {
 
     // Check if we need to restore the wallet database, and if so, do it.
     info!("Checking if we need to restore UTXOs");
-    global_state_lock
+    let restored_mutxo_count = global_state_lock
         .lock_guard_mut()
         .await
         .restore_monitored_utxos_from_recovery_data()
         .await?;
-    info!("UTXO restoration check complete");
+    info!("UTXO restoration check complete. Restored {restored_mutxo_count} monitored UTXOs.");
 
     // Connect to peers, and provide each peer thread with a thread-safe copy of the state
     let mut thread_join_handles = vec![];
@@ -181,6 +215,75 @@ pub async fn initialize(cli_args: cli_args::Args) -> Result<()> {
             })?;
         thread_join_handles.push(peer_join_handle);
     }
+
+    // Add the seed nodes of the network and the peers of the peer list file to the address
+    // book, such that a fresh node can find the network.
+    let mut bootstrap_peers = vec![];
+    if !global_state_lock.cli().no_seed_peers {
+        bootstrap_peers.extend(global_state_lock.cli().network.seed_peers());
+    }
+    if let Some(peer_list_file) = &global_state_lock.cli().peer_list_file {
+        let signed_peer_list = SignedPeerList::read_from_file(peer_list_file)?;
+        let peers = signed_peer_list
+            .verify(
+                global_state_lock.cli().network,
+                &global_state_lock.cli().peer_list_signers,
+            )
+            .with_context(|| format!("Rejected peer list file {}", peer_list_file.display()))?;
+        info!(
+            "Read {} peers from peer list file {}",
+            peers.len(),
+            peer_list_file.display()
+        );
+        bootstrap_peers.extend_from_slice(peers);
+    }
+    {
+        let mut global_state_mut = global_state_lock.lock_guard_mut().await;
+        for peer_address in bootstrap_peers {
+            global_state_mut
+                .net
+                .record_peer_address_seen(peer_address)
+                .await;
+        }
+    }
+
+    // Connect to the best peers from the address book, such that we don't have to wait for
+    // peer discovery after a restart. One slot is left for an incoming connection.
+    let cli_peers = global_state_lock.cli().peers.clone();
+    let address_book_peer_count =
+        (global_state_lock.cli().max_peers as usize).saturating_sub(cli_peers.len() + 1);
+    let address_book_peers = global_state_lock
+        .lock_guard()
+        .await
+        .net
+        .peer_address_book_candidates(global_state_lock.cli().peer_tolerance)
+        .await
+        .into_iter()
+        .filter(|address| !cli_peers.contains(address))
+        .take(address_book_peer_count)
+        .collect_vec();
+    for peer_address in address_book_peers {
+        let peer_state_var = global_state_lock.clone(); // bump arc refcount
+        let main_to_peer_broadcast_rx_clone: broadcast::Receiver<MainToPeerThread> =
+            main_to_peer_broadcast_tx.subscribe();
+        let peer_thread_to_main_tx_clone: mpsc::Sender<PeerThreadToMain> =
+            peer_thread_to_main_tx.clone();
+        let own_handshake_data_clone = own_handshake_data.clone();
+        let peer_join_handle = tokio::task::Builder::new()
+            .name("call_peer_wrapper_address_book")
+            .spawn(async move {
+                call_peer_wrapper(
+                    peer_address,
+                    peer_state_var.clone(),
+                    main_to_peer_broadcast_rx_clone,
+                    peer_thread_to_main_tx_clone,
+                    own_handshake_data_clone,
+                    1, // Peers from the address book are treated like CLI-specified peers
+                )
+                .await;
+            })?;
+        thread_join_handles.push(peer_join_handle);
+    }
     info!("Made outgoing connections to peers");
 
     // Start mining threads if requested
@@ -265,6 +368,68 @@ pub async fn initialize(cli_args: cli_args::Args) -> Result<()> {
         .await
 }
 
+/// Move an existing wallet database out of the way, such that a new one is created and
+/// rebuilt from the file of incoming randomness. The old database is kept as a backup.
+async fn move_wallet_database_aside(data_dir: &DataDirectory) -> Result<()> {
+    let wallet_db_path = data_dir.wallet_database_dir_path();
+    if !wallet_db_path.exists() {
+        info!("No wallet database found. A new one will be restored from the incoming randomness file.");
+        return Ok(());
+    }
+
+    let mut backup_path = wallet_db_path.clone().into_os_string();
+    backup_path.push(format!(".backup-{}", Utc::now().format("%Y%m%d%H%M%S")));
+    tokio::fs::rename(&wallet_db_path, &backup_path)
+        .await
+        .with_context(|| {
+            format!(
+                "Failed to move wallet database {} aside",
+                wallet_db_path.display()
+            )
+        })?;
+    info!(
+        "Moved wallet database to {}. A new one will be restored from the incoming randomness file.",
+        Path::new(&backup_path).display()
+    );
+
+    Ok(())
+}
+
+/// Ask the user for the wallet passphrase if the node was started with `--unlock`.
+///
+/// Refuses to continue if the wallet file is encrypted but `--unlock` was not given, and
+/// asks for a new passphrase twice if `--unlock` was given but no wallet file exists yet.
+fn unlock_passphrase(wallet_dir: &Path, unlock: bool) -> Result<Option<String>> {
+    let wallet_secret_path = WalletSecret::wallet_secret_path(wallet_dir);
+    let wallet_file_format = if wallet_secret_path.exists() {
+        Some(WalletSecret::wallet_file_format(&wallet_secret_path)?)
+    } else {
+        None
+    };
+
+    match (unlock, wallet_file_format) {
+        (false, Some(WalletFileFormat::Encrypted)) => bail!(
+            "Wallet file {} is encrypted. Start neptune-core with `--unlock` to unlock it.",
+            wallet_secret_path.display()
+        ),
+        (false, _) => Ok(None),
+        (true, Some(WalletFileFormat::Plaintext)) => {
+            warn!(
+                "`--unlock` was given but wallet file {} is not encrypted. Run `neptune-cli change-passphrase` to encrypt it.",
+                wallet_secret_path.display()
+            );
+            Ok(None)
+        }
+        (true, Some(WalletFileFormat::Encrypted)) => {
+            Ok(Some(prompt_for_passphrase("Enter wallet passphrase: ")?))
+        }
+        (true, None) => {
+            println!("Creating new encrypted wallet.");
+            Ok(Some(prompt_for_new_passphrase()?))
+        }
+    }
+}
+
 /// Time a fn call.  Duration is returned as a float in seconds.
 pub fn time_fn_call<O>(f: impl FnOnce() -> O) -> (O, f64) {
     let start = Instant::now();
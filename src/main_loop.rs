@@ -340,6 +340,11 @@ impl MainLoopHandler {
                         new_block_info.coinbase_utxo_info.as_ref().clone(),
                     )
                     .await?;
+
+                // Keep the transactions of the block, so peers can rebuild it from a compact block
+                global_state_mut
+                    .net
+                    .store_block_composition(new_block.hash(), *new_block_info.composition);
                 drop(global_state_mut);
 
                 // Inform miner that mempool has been updated and that it is safe
@@ -492,6 +497,13 @@ impl MainLoopHandler {
             }
             PeerThreadToMain::PeerDiscoveryAnswer((pot_peers, reported_by, distance)) => {
                 let max_peers = self.global_state_lock.cli().max_peers;
+                let reported_addresses = pot_peers.iter().map(|pot_peer| pot_peer.0).collect_vec();
+                self.global_state_lock
+                    .lock_guard_mut()
+                    .await
+                    .net
+                    .record_peer_addresses_reported(&reported_addresses)
+                    .await;
                 for pot_peer in pot_peers {
                     main_loop_state.potential_peers.add(
                         reported_by,
@@ -536,7 +548,8 @@ impl MainLoopHandler {
     }
 
     /// Function to perform peer discovery: Finds potential peers from connected peers and attempts
-    /// to establish connections with one of those potential peers.
+    /// to establish connections with one of those potential peers. Also connects to the best peer
+    /// from the address book that backoff allows.
     ///
     /// Locking:
     ///   * acquires `global_state_lock` for read
@@ -641,6 +654,41 @@ impl MainLoopHandler {
             return Ok(());
         }
 
+        // Connect to the best peer from the address book that we are not connected to. Peers
+        // specified in the CLI arguments were handled above.
+        let address_book_candidate = global_state
+            .net
+            .peer_address_book_candidates(global_state.cli().peer_tolerance)
+            .await
+            .into_iter()
+            .find(|address| !global_state.cli().peers.contains(address));
+        if let Some(address_book_candidate) = address_book_candidate {
+            info!("Connecting to peer {address_book_candidate} from address book");
+            let own_handshake_data: HandshakeData = global_state.get_own_handshakedata().await;
+            let main_to_peer_broadcast_rx = self.main_to_peer_broadcast_tx.subscribe();
+            let global_state_lock_clone = self.global_state_lock.clone();
+            let peer_thread_to_main_tx_clone = self.peer_thread_to_main_tx.to_owned();
+            let outgoing_connection_thread = tokio::task::Builder::new()
+                .name("call_peer_wrapper_address_book")
+                .spawn(async move {
+                    call_peer_wrapper(
+                        address_book_candidate,
+                        global_state_lock_clone,
+                        main_to_peer_broadcast_rx,
+                        peer_thread_to_main_tx_clone,
+                        own_handshake_data,
+                        1, // Peers from the address book are treated like CLI-specified peers
+                    )
+                    .await;
+                })?;
+            main_loop_state
+                .thread_handles
+                .push(outgoing_connection_thread);
+            main_loop_state
+                .thread_handles
+                .retain(|th| !th.is_finished());
+        }
+
         info!("Performing peer discovery");
         // Potential procedure for peer discovey:
         // 0) Ask all peers for their peer lists
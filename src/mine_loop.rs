@@ -1,6 +1,7 @@
 use crate::models::blockchain::block::block_body::BlockBody;
 use crate::models::blockchain::block::block_header::BlockHeader;
 use crate::models::blockchain::block::block_height::BlockHeight;
+use crate::models::blockchain::block::compact_block::BlockComposition;
 use crate::models::blockchain::block::mutator_set_update::*;
 use crate::models::blockchain::block::*;
 use crate::models::blockchain::shared::*;
@@ -104,17 +105,18 @@ fn make_block_template(
 async fn mine_block(
     mut block_header: BlockHeader,
     block_body: BlockBody,
-    sender: oneshot::Sender<NewBlockFound>,
+    mut sender: oneshot::Sender<NewBlockFound>,
     global_state_lock: GlobalStateLock,
     coinbase_utxo_info: ExpectedUtxo,
-    difficulty: U32s<5>,
+    composition: BlockComposition,
+    previous_block: Block,
 ) {
     info!(
         "Mining on block with {} outputs. Attempting to find block with height {}",
         block_body.transaction.kernel.outputs.len(),
         block_header.height
     );
-    let threshold = Block::difficulty_to_digest_threshold(difficulty);
+    let threshold = Block::difficulty_to_digest_threshold(previous_block.kernel.header.difficulty);
 
     // The RNG used to sample nonces must be thread-safe, which `thread_rng()` is not.
     // Solution: use `thread_rng()` to generate a seed, and generate a thread-safe RNG
@@ -154,9 +156,39 @@ async fn mine_block(
         block_header.nonce[0], block_header.nonce[1], block_header.nonce[2]
     );
 
+    let mut block = Block::new(block_header, block_body, None);
+
+    // The proof is about the block kernel, nonce included, so proving cannot start before
+    // the proof-of-work is found. It takes long and would block the executor, so it runs
+    // on a thread of its own, which is abandoned along with the block.
+    if global_state_lock.cli().prove_mined_blocks {
+        let height = block.kernel.header.height;
+        let proving_task = tokio::task::spawn_blocking(move || {
+            block.prove_validity(&previous_block).map(|()| block)
+        });
+        block = select! {
+            proven = proving_task => match proven {
+                Ok(Ok(block)) => block,
+                Ok(Err(err)) => {
+                    error!("Could not prove validity of mined block: {err}");
+                    return;
+                }
+                Err(err) => {
+                    error!("Prover of mined block failed: {err}");
+                    return;
+                }
+            },
+            _ = sender.cancellation() => {
+                info!("Abandoning proving of mined block with height {height}");
+                return;
+            }
+        };
+    }
+
     let new_block_info = NewBlockFound {
-        block: Box::new(Block::new(block_header, block_body, None)),
+        block: Box::new(block),
         coinbase_utxo_info: Box::new(coinbase_utxo_info),
+        composition: Box::new(composition),
     };
 
     info!(
@@ -213,6 +245,7 @@ fn make_coinbase_transaction(
         timestamp,
         coinbase: Some(coinbase_amount),
         mutator_set_hash: mutator_set_accumulator.hash(),
+        minimum_block_height: block_height.into(),
     };
 
     let primitive_witness = TransactionPrimitiveWitness {
@@ -222,6 +255,8 @@ fn make_coinbase_transaction(
         lock_script_witnesses: vec![],
         input_membership_proofs: vec![],
         output_utxos: vec![coinbase_utxo.clone()],
+        output_sender_randomnesses: vec![sender_randomness],
+        output_receiver_digests: vec![receiver_digest],
         public_announcements: vec![],
         mutator_set_accumulator,
     };
@@ -238,29 +273,35 @@ fn make_coinbase_transaction(
 
 /// Create the transaction that goes into the block template. The transaction is
 /// built from the mempool and from the coinbase transaction. Also returns the
-/// "sender randomness" used in the coinbase transaction.
+/// "sender randomness" used in the coinbase transaction, and the transactions that
+/// were merged.
 fn create_block_transaction(
     latest_block: &Block,
     global_state: &GlobalState,
-) -> (Transaction, ExpectedUtxo) {
+) -> (Transaction, ExpectedUtxo, BlockComposition) {
     let block_capacity_for_transactions = SIZE_20MB_IN_BYTES;
+    let next_block_height: BlockHeight = latest_block.kernel.header.height.next();
 
-    // Get most valuable transactions from mempool
-    let transactions_to_include = global_state
+    // Get most valuable transactions from mempool that are valid in the next block
+    let mut transactions_to_include = global_state
         .mempool
         .get_transactions_for_block(block_capacity_for_transactions);
+    transactions_to_include.retain(|transaction| {
+        transaction.kernel.minimum_block_height.value() <= u64::from(next_block_height)
+    });
 
     // Build coinbase UTXO
     let transaction_fees = transactions_to_include
         .iter()
         .fold(NeptuneCoins::zero(), |acc, tx| acc + tx.kernel.fee);
 
-    let coinbase_recipient_spending_key = global_state
+    // The node refuses to mine with a watch-only wallet, as it could not spend the reward
+    let wallet_secret = global_state
         .wallet_state
-        .wallet_secret
-        .nth_generation_spending_key(0);
-    let receiving_address = coinbase_recipient_spending_key.to_address();
-    let next_block_height: BlockHeight = latest_block.kernel.header.height.next();
+        .spending_secret()
+        .expect("Miner requires a wallet that can spend");
+    let coinbase_recipient_view_key = global_state.wallet_state.known_view_keys()[0];
+    let receiving_address = coinbase_recipient_view_key.receiving_address;
 
     let lock_script = receiving_address.lock_script();
     let coinbase_amount = Block::get_mining_reward(next_block_height) + transaction_fees;
@@ -269,7 +310,7 @@ fn create_block_transaction(
     let (coinbase_transaction, coinbase_sender_randomness) = make_coinbase_transaction(
         &coinbase_utxo,
         receiving_address.privacy_digest,
-        &global_state.wallet_state.wallet_secret,
+        wallet_secret,
         next_block_height,
         latest_block.kernel.body.mutator_set_accumulator.clone(),
     );
@@ -284,6 +325,12 @@ fn create_block_transaction(
             .emojihash()
     );
 
+    let composition = BlockComposition {
+        coinbase_transaction: coinbase_transaction.clone(),
+        short_id_nonce: thread_rng().gen(),
+        transactions: transactions_to_include.clone(),
+    };
+
     // Merge incoming transactions with the coinbase transaction
     let merged_transaction = transactions_to_include
         .into_iter()
@@ -294,11 +341,11 @@ fn create_block_transaction(
     let utxo_info_for_coinbase = ExpectedUtxo::new(
         coinbase_utxo,
         coinbase_sender_randomness,
-        coinbase_recipient_spending_key.privacy_preimage,
+        coinbase_recipient_view_key.privacy_preimage,
         UtxoNotifier::OwnMiner,
     );
 
-    (merged_transaction, utxo_info_for_coinbase)
+    (merged_transaction, utxo_info_for_coinbase, composition)
 }
 
 /// Locking:
@@ -329,7 +376,7 @@ pub async fn mine(
                 None
             } else {
                 // Build the block template and spawn the worker thread to mine on it
-                let (transaction, coinbase_utxo_info) = create_block_transaction(
+                let (transaction, coinbase_utxo_info, composition) = create_block_transaction(
                     &latest_block,
                     global_state_lock.lock_guard().await.deref(),
                 );
@@ -340,7 +387,8 @@ pub async fn mine(
                     worker_thread_tx,
                     global_state_lock.clone(),
                     coinbase_utxo_info,
-                    latest_block.kernel.header.difficulty,
+                    composition,
+                    latest_block.clone(),
                 );
                 global_state_lock.set_mining(true).await;
                 Some(
@@ -450,8 +498,8 @@ mod mine_loop_tests {
     use tracing_test::traced_test;
 
     use crate::{
-        config_models::network::Network, models::state::UtxoReceiverData,
-        tests::shared::get_mock_global_state,
+        config_models::network::Network, models::blockchain::block::compact_block::CompactBlock,
+        models::state::UtxoReceiverData, tests::shared::get_mock_global_state,
     };
 
     use super::*;
@@ -471,7 +519,7 @@ mod mine_loop_tests {
 
         // Verify constructed coinbase transaction and block template when mempool is empty
         let genesis_block = Block::genesis_block();
-        let (transaction_empty_mempool, _coinbase_sender_randomness) =
+        let (transaction_empty_mempool, _coinbase_sender_randomness, _composition) =
             create_block_transaction(&genesis_block, &premine_receiver_global_state);
         assert_eq!(
             1,
@@ -523,7 +571,7 @@ mod mine_loop_tests {
         assert_eq!(1, premine_receiver_global_state.mempool.len());
 
         // Build transaction
-        let (transaction_non_empty_mempool, _new_coinbase_sender_randomness) =
+        let (transaction_non_empty_mempool, _new_coinbase_sender_randomness, composition) =
             create_block_transaction(&genesis_block, &premine_receiver_global_state);
         assert_eq!(
             3,
@@ -541,6 +589,21 @@ mod mine_loop_tests {
             "Block template created by miner with non-empty mempool must be valid"
         );
 
+        // Peers with the same mempool rebuild the block from its compact form
+        let compact_block = CompactBlock::new(&block_template_non_empty_mempool, &composition)
+            .expect("Block with mempool transactions must have a compact form");
+        let found_transactions =
+            compact_block.find_transactions(&premine_receiver_global_state.mempool);
+        let reconstructed_block = compact_block
+            .reconstruct(found_transactions.into_iter().flatten().collect())
+            .unwrap();
+        assert_eq!(
+            block_template_non_empty_mempool.hash(),
+            reconstructed_block.hash()
+        );
+        assert_eq!(block_template_non_empty_mempool, reconstructed_block);
+        assert!(reconstructed_block.is_valid(&genesis_block));
+
         Ok(())
     }
 }
@@ -31,7 +31,8 @@ impl HasDiscriminant for BlockBodyField {
 #[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize, BFieldCodec, GetSize)]
 pub struct BlockBody {
     /// Every block contains exactly one transaction, which represents the merger of all
-    /// broadcasted transactions that the miner decided to confirm.
+    /// broadcasted transactions that the miner decided to confirm. The block's MAST hash
+    /// commits to the MAST hash of the transaction's kernel, and not to its witness.
     pub transaction: Transaction,
 
     /// The mutator set accumulator represents the UTXO set. It is simultaneously an
@@ -58,7 +59,7 @@ impl MastHash for BlockBody {
 
     fn mast_sequences(&self) -> Vec<Vec<BFieldElement>> {
         vec![
-            self.transaction.encode(),
+            self.transaction.kernel.mast_hash().encode(),
             self.mutator_set_accumulator.encode(),
             self.lock_free_mmr_accumulator.encode(),
             self.block_mmr_accumulator.encode(),
@@ -0,0 +1,359 @@
+use crate::prelude::twenty_first;
+
+use anyhow::{ensure, Result};
+use itertools::Itertools;
+use serde::{Deserialize, Serialize};
+use tasm_lib::twenty_first::util_types::mmr::mmr_accumulator::MmrAccumulator;
+use twenty_first::shared_math::b_field_element::BFieldElement;
+use twenty_first::shared_math::bfield_codec::BFieldCodec;
+use twenty_first::shared_math::digest::Digest;
+use twenty_first::util_types::algebraic_hasher::AlgebraicHasher;
+
+use super::block_body::{BlockBody, BlockBodyField};
+use super::block_header::BlockHeader;
+use super::block_kernel::BlockKernelField;
+use super::validity::PrincipalBlockValidationLogic;
+use super::Block;
+use crate::models::blockchain::shared::Hash;
+use crate::models::blockchain::transaction::Transaction;
+use crate::models::consensus::mast_hash::MastHash;
+use crate::models::state::mempool::Mempool;
+use crate::util_types::mutator_set::mutator_set_accumulator::MutatorSetAccumulator;
+
+/// The first element of the digest of a transaction hashed with the salt of a compact
+/// block. Identifies a transaction in a compact block with 8 bytes instead of 40. As
+/// the salt differs per compact block, transactions cannot be crafted to collide in
+/// every block. Short IDs can still collide by chance, in which case the reconstructed
+/// block does not have the announced hash and the receiver falls back to requesting the
+/// full block.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash, Serialize, Deserialize)]
+pub struct ShortTransactionId(pub u64);
+
+impl ShortTransactionId {
+    pub fn new(transaction_digest: Digest, salt: Digest) -> Self {
+        Self(Hash::hash_pair(salt, transaction_digest).values()[0].value())
+    }
+}
+
+/// The transactions that a miner merged into the transaction of a block: its coinbase
+/// transaction, and the mempool transactions in the order in which they were merged.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct BlockComposition {
+    pub coinbase_transaction: Transaction,
+    pub transactions: Vec<Transaction>,
+
+    /// Chosen at random by the node that records the composition, and salts the short
+    /// IDs of the compact form of the block along with its header
+    pub short_id_nonce: u64,
+}
+
+/// A block whose transaction is replaced by its coinbase transaction and the short IDs
+/// of the transactions that were merged with it. Peers that have these transactions in
+/// their mempool rebuild the block without downloading them again. The coinbase
+/// transaction is shared with its witness, which the block transaction contains anyway.
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub struct CompactBlock {
+    pub header: BlockHeader,
+    pub coinbase_transaction: Transaction,
+    pub short_transaction_ids: Vec<ShortTransactionId>,
+
+    /// Salts the short IDs along with the header, see [`BlockComposition`]
+    pub short_id_nonce: u64,
+
+    /// The MAST hash of the kernel of the block transaction, which determines the hash
+    /// of the block along with the rest of the compact block
+    pub transaction_kernel_mast_hash: Digest,
+
+    // The rest of the block body
+    pub mutator_set_accumulator: MutatorSetAccumulator,
+    pub lock_free_mmr_accumulator: MmrAccumulator<Hash>,
+    pub block_mmr_accumulator: MmrAccumulator<Hash>,
+    pub uncle_blocks: Vec<Digest>,
+
+    pub proof: Option<PrincipalBlockValidationLogic>,
+}
+
+/// The body of the block that a compact block describes, whose MAST hash only commits
+/// to the MAST hash of the transaction kernel
+struct CompactBlockBody<'a>(&'a CompactBlock);
+
+impl MastHash for CompactBlockBody<'_> {
+    type FieldEnum = BlockBodyField;
+
+    fn mast_sequences(&self) -> Vec<Vec<BFieldElement>> {
+        vec![
+            self.0.transaction_kernel_mast_hash.encode(),
+            self.0.mutator_set_accumulator.encode(),
+            self.0.lock_free_mmr_accumulator.encode(),
+            self.0.block_mmr_accumulator.encode(),
+            self.0.uncle_blocks.encode(),
+        ]
+    }
+}
+
+/// The kernel of the block that a compact block describes
+struct CompactBlockKernel<'a>(&'a CompactBlock);
+
+impl MastHash for CompactBlockKernel<'_> {
+    type FieldEnum = BlockKernelField;
+
+    fn mast_sequences(&self) -> Vec<Vec<BFieldElement>> {
+        vec![
+            self.0.header.mast_hash().encode(),
+            CompactBlockBody(self.0).mast_hash().encode(),
+        ]
+    }
+}
+
+impl CompactBlock {
+    /// Describe the block compactly, if its transaction is the merger of the given
+    /// coinbase transaction with at least one other transaction. A block that only
+    /// contains its coinbase transaction is not smaller when described compactly.
+    pub fn new(block: &Block, composition: &BlockComposition) -> Option<Self> {
+        if composition.transactions.is_empty() {
+            return None;
+        }
+
+        let body = block.body();
+        let mut compact_block = Self {
+            header: block.header().clone(),
+            coinbase_transaction: composition.coinbase_transaction.clone(),
+            short_transaction_ids: vec![],
+            short_id_nonce: composition.short_id_nonce,
+            transaction_kernel_mast_hash: body.transaction.kernel.mast_hash(),
+            mutator_set_accumulator: body.mutator_set_accumulator.clone(),
+            lock_free_mmr_accumulator: body.lock_free_mmr_accumulator.clone(),
+            block_mmr_accumulator: body.block_mmr_accumulator.clone(),
+            uncle_blocks: body.uncle_blocks.clone(),
+            proof: block.proof.clone(),
+        };
+        let salt = compact_block.short_id_salt();
+        compact_block.short_transaction_ids = composition
+            .transactions
+            .iter()
+            .map(|transaction| ShortTransactionId::new(Hash::hash(transaction), salt))
+            .collect_vec();
+
+        Some(compact_block)
+    }
+
+    /// The hash of the block that this compact block describes
+    pub fn hash(&self) -> Digest {
+        CompactBlockKernel(self).mast_hash()
+    }
+
+    /// The salt of the short IDs of this compact block, from its header and nonce
+    pub fn short_id_salt(&self) -> Digest {
+        Hash::hash_varlen(
+            &[
+                self.header.mast_hash().encode(),
+                self.short_id_nonce.encode(),
+            ]
+            .concat(),
+        )
+    }
+
+    /// Look up the transactions of the block in the mempool. Transactions that are not
+    /// there, or that are about another mutator set than the block's, are `None`.
+    pub fn find_transactions(&self, mempool: &Mempool) -> Vec<Option<Transaction>> {
+        mempool
+            .get_by_short_ids(&self.short_transaction_ids, self.short_id_salt())
+            .into_iter()
+            .map(|transaction| {
+                transaction.filter(|transaction| {
+                    transaction.kernel.mutator_set_hash
+                        == self.coinbase_transaction.kernel.mutator_set_hash
+                })
+            })
+            .collect_vec()
+    }
+
+    /// Rebuild the block from its transactions, given in the order of the short IDs, by
+    /// merging the coinbase transaction with them. Fails if the transactions do not
+    /// match the short IDs, or if the rebuilt block does not have the hash of the compact
+    /// block.
+    pub fn reconstruct(&self, transactions: Vec<Transaction>) -> Result<Block> {
+        ensure!(
+            transactions.len() == self.short_transaction_ids.len(),
+            "Got {} transactions for a compact block with {} short IDs",
+            transactions.len(),
+            self.short_transaction_ids.len()
+        );
+        let salt = self.short_id_salt();
+        for (transaction, short_id) in transactions.iter().zip(self.short_transaction_ids.iter()) {
+            ensure!(
+                ShortTransactionId::new(Hash::hash(transaction), salt) == *short_id,
+                "Transaction does not match short ID {}",
+                short_id.0
+            );
+            ensure!(
+                transaction.kernel.mutator_set_hash
+                    == self.coinbase_transaction.kernel.mutator_set_hash,
+                "Transaction with short ID {} is about another mutator set",
+                short_id.0
+            );
+            ensure!(
+                transaction.kernel.coinbase.is_none(),
+                "Transaction with short ID {} has a coinbase",
+                short_id.0
+            );
+        }
+
+        let transaction = transactions
+            .into_iter()
+            .fold(self.coinbase_transaction.clone(), Transaction::merge_with);
+        ensure!(
+            transaction.kernel.mast_hash() == self.transaction_kernel_mast_hash,
+            "Rebuilt transaction does not have the kernel of the compact block"
+        );
+
+        let body = BlockBody {
+            transaction,
+            mutator_set_accumulator: self.mutator_set_accumulator.clone(),
+            lock_free_mmr_accumulator: self.lock_free_mmr_accumulator.clone(),
+            block_mmr_accumulator: self.block_mmr_accumulator.clone(),
+            uncle_blocks: self.uncle_blocks.clone(),
+        };
+
+        Ok(Block::new(self.header.clone(), body, self.proof.clone()))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::models::blockchain::transaction::neptune_coins::NeptuneCoins;
+    use crate::models::blockchain::transaction::TransactionWitness;
+    use crate::tests::shared::{make_mock_transaction, random_transaction_kernel};
+    use crate::util_types::mutator_set::addition_record::pseudorandom_addition_record;
+    use crate::util_types::mutator_set::mutator_set_trait::MutatorSet;
+    use crate::util_types::mutator_set::removal_record::pseudorandom_removal_record;
+    use bytesize::ByteSize;
+    use rand::random;
+
+    fn block_with_merged_transactions() -> (Block, BlockComposition) {
+        let genesis = Block::genesis_block();
+        let mutator_set_hash = genesis.body().mutator_set_accumulator.hash();
+
+        let mut coinbase_kernel = random_transaction_kernel();
+        coinbase_kernel.inputs = vec![];
+        coinbase_kernel.coinbase = Some(NeptuneCoins::new(100));
+        coinbase_kernel.mutator_set_hash = mutator_set_hash;
+
+        let transactions = (0..3)
+            .map(|_| {
+                let mut transaction = make_mock_transaction(
+                    vec![pseudorandom_removal_record(random())],
+                    vec![pseudorandom_addition_record(random())],
+                );
+                transaction.kernel.mutator_set_hash = mutator_set_hash;
+                transaction
+            })
+            .collect_vec();
+
+        let composition = BlockComposition {
+            coinbase_transaction: Transaction {
+                kernel: coinbase_kernel,
+                witness: TransactionWitness::Faith,
+            },
+            transactions,
+            short_id_nonce: random(),
+        };
+        let block_transaction = composition.transactions.iter().cloned().fold(
+            composition.coinbase_transaction.clone(),
+            Transaction::merge_with,
+        );
+        let mut body = genesis.body().clone();
+        body.transaction = block_transaction;
+        let block = Block::new(genesis.header().clone(), body, None);
+
+        (block, composition)
+    }
+
+    #[test]
+    fn compact_block_is_rebuilt_from_mempool() {
+        let (block, composition) = block_with_merged_transactions();
+        let compact_block = CompactBlock::new(&block, &composition).unwrap();
+        assert_eq!(block.hash(), compact_block.hash());
+
+        let mut mempool = Mempool::new(ByteSize::gb(1));
+        for transaction in composition.transactions.iter() {
+            mempool.insert(transaction);
+        }
+        let found = compact_block.find_transactions(&mempool);
+        assert!(found.iter().all(Option::is_some));
+
+        let reconstructed = compact_block
+            .reconstruct(found.into_iter().flatten().collect_vec())
+            .unwrap();
+        assert_eq!(block.hash(), reconstructed.hash());
+        assert_eq!(block, reconstructed);
+
+        // The compact block is much smaller than the block
+        assert!(
+            bincode::serialized_size(&compact_block).unwrap()
+                < bincode::serialized_size(&block).unwrap()
+        );
+    }
+
+    #[test]
+    fn missing_and_mismatched_transactions_are_detected() {
+        let (block, composition) = block_with_merged_transactions();
+        let compact_block = CompactBlock::new(&block, &composition).unwrap();
+
+        // A transaction about another mutator set counts as missing
+        let mut mempool = Mempool::new(ByteSize::gb(1));
+        mempool.insert(&composition.transactions[0]);
+        let mut updated = composition.transactions[2].clone();
+        updated.kernel.mutator_set_hash = random();
+        mempool.insert(&updated);
+        let found = compact_block.find_transactions(&mempool);
+        assert!(found[0].is_some());
+        assert!(found[1].is_none());
+        assert!(found[2].is_none());
+
+        // Transactions must match the short IDs in number and order
+        let mut transactions = composition.transactions.clone();
+        assert!(compact_block
+            .reconstruct(transactions[..2].to_vec())
+            .is_err());
+        transactions.swap(0, 1);
+        assert!(compact_block.reconstruct(transactions).is_err());
+
+        // The coinbase transaction must be the one of the block
+        let mut other_coinbase = compact_block.clone();
+        other_coinbase.coinbase_transaction.kernel.timestamp += BFieldElement::new(1);
+        assert!(other_coinbase
+            .reconstruct(composition.transactions.clone())
+            .is_err());
+
+        // A block with only a coinbase transaction has no compact form
+        let coinbase_only = BlockComposition {
+            coinbase_transaction: composition.coinbase_transaction.clone(),
+            transactions: vec![],
+            short_id_nonce: random(),
+        };
+        assert!(CompactBlock::new(&Block::genesis_block(), &coinbase_only).is_none());
+    }
+
+    #[test]
+    fn short_ids_are_salted_per_compact_block() {
+        let (block, composition) = block_with_merged_transactions();
+        let compact_block = CompactBlock::new(&block, &composition).unwrap();
+        let mut other_compact_block = CompactBlock::new(&block, &composition).unwrap();
+        other_compact_block.short_id_nonce = compact_block.short_id_nonce.wrapping_add(1);
+        assert_ne!(
+            compact_block.short_id_salt(),
+            other_compact_block.short_id_salt()
+        );
+
+        // Short IDs computed with another salt do not match
+        other_compact_block.short_transaction_ids = compact_block.short_transaction_ids.clone();
+        assert!(other_compact_block
+            .reconstruct(composition.transactions.clone())
+            .is_err());
+
+        // The nonce and short IDs are not part of the block hash
+        assert_eq!(block.hash(), other_compact_block.hash());
+    }
+}
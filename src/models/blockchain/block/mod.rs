@@ -2,6 +2,7 @@ use crate::config_models::network::Network;
 use crate::models::consensus::mast_hash::MastHash;
 use crate::prelude::twenty_first;
 
+use anyhow::Result;
 use get_size::GetSize;
 use itertools::Itertools;
 use num_bigint::BigUint;
@@ -10,12 +11,11 @@ use num_traits::{abs, Zero};
 use serde::{Deserialize, Serialize};
 use std::cmp::max;
 use std::time::{Duration, SystemTime};
-use tasm_lib::triton_vm::proof::Proof;
 use tasm_lib::twenty_first::util_types::mmr::mmr_accumulator::MmrAccumulator;
 use tasm_lib::twenty_first::util_types::mmr::mmr_trait::Mmr;
 use twenty_first::shared_math::bfield_codec::BFieldCodec;
 
-use tracing::{debug, error, warn};
+use tracing::{debug, warn};
 
 use twenty_first::amount::u32s::U32s;
 use twenty_first::shared_math::b_field_element::BFieldElement;
@@ -27,6 +27,7 @@ pub mod block_body;
 pub mod block_header;
 pub mod block_height;
 pub mod block_kernel;
+pub mod compact_block;
 pub mod mutator_set_update;
 pub mod transfer_block;
 pub mod validity;
@@ -39,6 +40,7 @@ use self::block_height::BlockHeight;
 use self::block_kernel::BlockKernel;
 use self::mutator_set_update::MutatorSetUpdate;
 use self::transfer_block::TransferBlock;
+use self::validity::{BlockPrimitiveWitness, PrincipalBlockValidationLogic};
 use super::transaction::transaction_kernel::TransactionKernel;
 use super::transaction::utxo::Utxo;
 use super::transaction::{neptune_coins::NeptuneCoins, Transaction};
@@ -57,7 +59,7 @@ pub struct Block {
     /// All blocks have proofs except:
     ///  - the genesis block
     ///  - blocks being generated
-    pub proof: Option<Proof>,
+    pub proof: Option<PrincipalBlockValidationLogic>,
 }
 
 impl From<TransferBlock> for Block {
@@ -67,24 +69,17 @@ impl From<TransferBlock> for Block {
                 header: t_block.header,
                 body: t_block.body,
             },
-            proof: Some(t_block.proof),
+            proof: t_block.proof,
         }
     }
 }
 
 impl From<Block> for TransferBlock {
     fn from(block: Block) -> Self {
-        let proof = match block.proof {
-            Some(p) => p,
-            None => {
-                error!("In order to be transferred, a Block must have a non-None proof field.");
-                panic!()
-            }
-        };
         Self {
             header: block.kernel.header,
             body: block.kernel.body,
-            proof,
+            proof: block.proof,
         }
     }
 }
@@ -143,6 +138,7 @@ impl Block {
                 public_announcements: vec![],
                 coinbase: Some(total_premine_amount),
                 mutator_set_hash: MutatorSetAccumulator::new().hash(),
+                minimum_block_height: BlockHeight::genesis().into(),
             },
             witness: Witness::Faith,
         };
@@ -207,7 +203,11 @@ impl Block {
         ]
     }
 
-    pub fn new(header: BlockHeader, body: BlockBody, proof: Option<Proof>) -> Self {
+    pub fn new(
+        header: BlockHeader,
+        body: BlockBody,
+        proof: Option<PrincipalBlockValidationLogic>,
+    ) -> Self {
         Self {
             kernel: BlockKernel { body, header },
             proof,
@@ -239,17 +239,11 @@ impl Block {
         // accumulate mutator set updates
         // Can't use the current mutator sat accumulator because it is in an in-between state.
         let mut new_mutator_set_accumulator = old_mutator_set_accumulator.clone();
+        // The merged transaction shuffles the inputs and outputs, so take them from there
+        // for the order of the mutator set update to match that of the block.
         let mutator_set_update = MutatorSetUpdate::new(
-            [
-                self.kernel.body.transaction.kernel.inputs.clone(),
-                transaction.kernel.inputs,
-            ]
-            .concat(),
-            [
-                self.kernel.body.transaction.kernel.outputs.clone(),
-                transaction.kernel.outputs.clone(),
-            ]
-            .concat(),
+            new_transaction.kernel.inputs.clone(),
+            new_transaction.kernel.outputs.clone(),
         );
 
         // Apply the mutator set update to get the `next_mutator_set_accumulator`
@@ -304,7 +298,8 @@ impl Block {
         //   c) Verify that all removal records have unique index sets
         //   d) verify that adding `mutator_set_update` to previous `mutator_set_accumulator`
         //      gives `next_mutator_set_accumulator`,
-        //   e) transaction timestamp <= block timestamp
+        //   e) transaction timestamp <= block timestamp, and
+        //      transaction minimum block height <= block height
         //   f) transaction coinbase <= miner reward
         //   g) transaction is valid (internally consistent)
 
@@ -422,6 +417,18 @@ impl Block {
             warn!("Transaction with invalid timestamp found");
             return false;
         }
+        if block_copy
+            .kernel
+            .body
+            .transaction
+            .kernel
+            .minimum_block_height
+            .value()
+            > u64::from(block_copy.kernel.header.height)
+        {
+            warn!("Transaction that is only valid in later blocks found");
+            return false;
+        }
 
         // 1.f) Verify that the coinbase claimed by the transaction does not exceed
         // the allowed coinbase based on block height, epoch, etc., and fee
@@ -456,9 +463,39 @@ impl Block {
         //  4.1. verify that uncle's prev_block_digest matches with parent's prev_block_digest
         //  4.2. verify that all uncles' hash are below parent's target_difficulty
 
+        // 5. if the block comes with a validity proof, verify it. Secret witnesses are for
+        // the prover; a block must not ask its verifiers to run the programs instead. The
+        // proof only links the block to its predecessor and its transaction, so it does not
+        // replace any of the checks above.
+        if let Some(proof) = &block_copy.proof {
+            if !proof.is_proven() {
+                warn!("Block validity claims are not supported by proofs");
+                return false;
+            }
+            if !proof.verify(&block_copy.kernel) {
+                warn!("Block validity proof does not verify");
+                return false;
+            }
+        }
+
         true
     }
 
+    /// Prove the validity of this block relative to its predecessor, and attach the
+    /// proof. Until a proof is attached, the block can only be validated natively.
+    pub fn prove_validity(&mut self, predecessor: &Block) -> Result<()> {
+        let primitive_witness = BlockPrimitiveWitness {
+            predecessor: predecessor.kernel.clone(),
+        };
+        let mut validation_logic = PrincipalBlockValidationLogic::new_from_primitive_witness(
+            &primitive_witness,
+            &self.kernel,
+        );
+        validation_logic.prove()?;
+        self.proof = Some(validation_logic);
+        Ok(())
+    }
+
     /// Determine if the the proof-of-work puzzle was solved correctly. Specifically,
     /// compare the hash of the current block against the difficulty determined by
     /// the previous.
@@ -495,14 +532,30 @@ impl Block {
     pub fn difficulty_control(
         old_block: &Block,
         new_timestamp: u64,
+    ) -> U32s<TARGET_DIFFICULTY_U32_SIZE> {
+        Self::next_difficulty(
+            old_block.kernel.header.height,
+            old_block.kernel.header.timestamp.value(),
+            old_block.kernel.header.difficulty,
+            new_timestamp,
+        )
+    }
+
+    /// Compute the difficulty of a block from its timestamp and from the height,
+    /// timestamp, and difficulty of its predecessor. See [`Block::difficulty_control`].
+    pub fn next_difficulty(
+        old_height: BlockHeight,
+        old_timestamp: u64,
+        old_difficulty: U32s<TARGET_DIFFICULTY_U32_SIZE>,
+        new_timestamp: u64,
     ) -> U32s<TARGET_DIFFICULTY_U32_SIZE> {
         // no adjustment if the previous block is the genesis block
-        if old_block.kernel.header.height.is_genesis() {
-            return old_block.kernel.header.difficulty;
+        if old_height.is_genesis() {
+            return old_difficulty;
         }
 
         // otherwise, compute PID control signal
-        let t = new_timestamp - old_block.kernel.header.timestamp.value();
+        let t = new_timestamp - old_timestamp;
 
         let new_error = t as i64 - TARGET_BLOCK_INTERVAL as i64;
 
@@ -514,11 +567,11 @@ impl Block {
         let adjustment_u32s =
             U32s::<TARGET_DIFFICULTY_U32_SIZE>::new([adj_lo, adj_hi, 0u32, 0u32, 0u32]);
         if adjustment_is_positive {
-            old_block.kernel.header.difficulty + adjustment_u32s
-        } else if adjustment_u32s > old_block.kernel.header.difficulty - MINIMUM_DIFFICULTY.into() {
+            old_difficulty + adjustment_u32s
+        } else if adjustment_u32s > old_difficulty - MINIMUM_DIFFICULTY.into() {
             MINIMUM_DIFFICULTY.into()
         } else {
-            old_block.kernel.header.difficulty - adjustment_u32s
+            old_difficulty - adjustment_u32s
         }
     }
 }
@@ -558,7 +611,8 @@ mod block_tests {
             .lock_guard()
             .await
             .wallet_state
-            .wallet_secret
+            .spending_secret()
+            .unwrap()
             .nth_generation_spending_key(0);
         let address = spending_key.to_address();
         let other_wallet_secret = WalletSecret::new_random();
@@ -662,6 +716,32 @@ mod block_tests {
         assert!(!block_1.is_valid(&genesis_block));
     }
 
+    #[traced_test]
+    #[test]
+    fn block_validity_logic_must_match_block() {
+        let genesis_block = Block::genesis_block();
+
+        let a_wallet_secret = WalletSecret::new_random();
+        let a_recipient_address = a_wallet_secret.nth_generation_spending_key(0).to_address();
+        let (mut block_1, _, _) = make_mock_block(&genesis_block, None, a_recipient_address);
+        let (other_block_1, _, _) = make_mock_block(&genesis_block, None, a_recipient_address);
+
+        let primitive_witness = BlockPrimitiveWitness {
+            predecessor: genesis_block.kernel.clone(),
+        };
+        let validation_logic = PrincipalBlockValidationLogic::new_from_primitive_witness(
+            &primitive_witness,
+            &block_1.kernel,
+        );
+        assert!(validation_logic.verify(&block_1.kernel));
+        assert!(!validation_logic.verify(&other_block_1.kernel));
+
+        // a block must come with proofs, not with the witnesses to prove them
+        assert!(block_1.is_valid(&genesis_block));
+        block_1.proof = Some(validation_logic);
+        assert!(!block_1.is_valid(&genesis_block));
+    }
+
     #[traced_test]
     #[test]
     fn block_with_far_future_timestamp_is_invalid() {
@@ -1,7 +1,8 @@
 use serde::{Deserialize, Serialize};
-use tasm_lib::triton_vm::proof::Proof;
 
-use super::{block_body::BlockBody, block_header::BlockHeader};
+use super::{
+    block_body::BlockBody, block_header::BlockHeader, validity::PrincipalBlockValidationLogic,
+};
 
 /// Data structure for communicating blocks with peers. The hash digest is not
 /// communicated such that the receiver is forced to calculate it themselves.
@@ -9,5 +10,5 @@ use super::{block_body::BlockBody, block_header::BlockHeader};
 pub struct TransferBlock {
     pub header: BlockHeader,
     pub body: BlockBody,
-    pub proof: Proof,
+    pub proof: Option<PrincipalBlockValidationLogic>,
 }
@@ -1,12 +1,21 @@
+use crate::models::consensus::mast_hash::{HasDiscriminant, MastHash};
+use crate::prelude::twenty_first;
+
+use anyhow::Result;
 use get_size::GetSize;
+use itertools::Itertools;
 use serde::{Deserialize, Serialize};
-use tasm_lib::{
-    triton_vm::program::{NonDeterminism, Program},
-    twenty_first::{self, shared_math::b_field_element::BFieldElement},
-};
+use tasm_lib::traits::compiled_program::CompiledProgram;
+use tasm_lib::triton_vm::program::{NonDeterminism, Program};
+use tracing::warn;
+use twenty_first::shared_math::b_field_element::BFieldElement;
 use twenty_first::shared_math::bfield_codec::BFieldCodec;
+use twenty_first::shared_math::tip5::Digest;
+use twenty_first::util_types::algebraic_hasher::AlgebraicHasher;
 
-use crate::models::consensus::SecretWitness;
+use crate::models::blockchain::shared::Hash;
+use crate::models::blockchain::transaction::transaction_kernel::TransactionKernelField;
+use crate::models::consensus::{ClaimSupport, SecretWitness, ValidationLogic};
 
 use self::{
     coinbase_is_valid::CoinbaseIsValid,
@@ -16,6 +25,9 @@ use self::{
     transaction_is_valid::TransactionIsValid,
 };
 
+use super::block_body::BlockBodyField;
+use super::block_header::BlockHeaderField;
+use super::block_kernel::{BlockKernel, BlockKernelField};
 use super::Block;
 
 pub mod coinbase_is_valid;
@@ -24,30 +36,265 @@ pub mod correct_mmr_update;
 pub mod correct_mutator_set_update;
 pub mod mmr_membership;
 pub mod predecessor_is_valid;
+pub mod tasm;
 pub mod transaction_is_valid;
 
 /// The validity of a block, in the principal case, decomposes into these subclaims.
 #[derive(Clone, Debug, Serialize, Deserialize, PartialEq, Eq, GetSize, BFieldCodec)]
 pub struct PrincipalBlockValidationLogic {
-    // program: recursive-verify-or-is-genesis, input: block kernel, output: []
+    // program: link-to-predecessor, input: block kernel mast hash, witness: heights + paths, output: predecessor digest
     pub predecessor_is_valid: PredecessorIsValid,
 
-    // program: verify-transaction, input: block kernel, output: []
+    // program: link-to-transaction, input: block kernel mast hash, witness: timestamps + paths, output: transaction kernel mast hash
     pub transaction_is_valid: TransactionIsValid,
 
-    // program: verify-coinbase, input: block kernel, output: []
+    // program: verify-coinbase, input: block kernel mast hash, witness: height, fee, coinbase + paths, output: []
     pub coinbase_is_valid: CoinbaseIsValid,
 
-    // program: update-mutator-set, input: block kernel, output: []
+    // program: update-mutator-set, input: block kernel mast hash, witness: mutator sets, outputs + paths, output: []
     pub correct_mutator_set_update: CorrectMutatorSetUpdate,
 
-    // program: update-mmr, input: block kernel, output: []
+    // program: update-mmr, input: block kernel mast hash, witness: block mmrs + paths, output: []
     pub correct_mmr_update: CorrectMmrUpdate,
 
-    // program: update-control-parameters, input: block kernel, output: []
+    // program: update-control-parameters, input: block kernel mast hash, witness: timestamps, difficulties + paths, output: []
     pub correct_control_parameter_update: CorrectControlParameterUpdate,
 }
 
+/// Everything needed to prove the validity of a block beyond the block itself.
+#[derive(Clone, Debug, Serialize, Deserialize, PartialEq, Eq, GetSize, BFieldCodec)]
+pub struct BlockPrimitiveWitness {
+    pub predecessor: BlockKernel,
+}
+
+/// A field of a block kernel, or of one of the kernels nested inside it, as a leaf of the
+/// Merkle trees that make up the block's MAST hash.
+#[derive(Debug, Clone)]
+pub enum BlockKernelLeaf {
+    Header(BlockHeaderField),
+    Body(BlockBodyField),
+    TransactionKernel(TransactionKernelField),
+}
+
+impl BlockKernelLeaf {
+    /// The index of the leaf, and the height of the Merkle tree, on every level from the
+    /// field up to the block kernel. The root of every level but the last is hashed into
+    /// the leaf of the next.
+    pub fn levels(&self) -> Vec<(usize, usize)> {
+        let header = (BlockKernelField::Header.discriminant(), 1);
+        let body = (BlockKernelField::Body.discriminant(), 1);
+        match self {
+            BlockKernelLeaf::Header(field) => vec![(field.discriminant(), 4), header],
+            BlockKernelLeaf::Body(field) => vec![(field.discriminant(), 3), body],
+            BlockKernelLeaf::TransactionKernel(field) => vec![
+                (field.discriminant(), 3),
+                (BlockBodyField::Transaction.discriminant(), 3),
+                body,
+            ],
+        }
+    }
+
+    /// The siblings of the field on all levels, bottom-up.
+    pub fn path(&self, kernel: &BlockKernel) -> Vec<Digest> {
+        match self {
+            BlockKernelLeaf::Header(field) => [
+                kernel.header.mast_path(field.clone()),
+                kernel.mast_path(BlockKernelField::Header),
+            ]
+            .concat(),
+            BlockKernelLeaf::Body(field) => [
+                kernel.body.mast_path(field.clone()),
+                kernel.mast_path(BlockKernelField::Body),
+            ]
+            .concat(),
+            BlockKernelLeaf::TransactionKernel(field) => [
+                kernel.body.transaction.kernel.mast_path(field.clone()),
+                kernel.body.mast_path(BlockBodyField::Transaction),
+                kernel.mast_path(BlockKernelField::Body),
+            ]
+            .concat(),
+        }
+    }
+
+    /// The MAST hash of the block kernel in which a field with the given encoding lives
+    /// at this leaf, according to the given path. Returns `None` if the path is too short.
+    pub fn root(&self, encoding: &[BFieldElement], path: &[Digest]) -> Option<Digest> {
+        let mut siblings = path.iter();
+        let mut node = Hash::hash_varlen(encoding);
+        for (level, (leaf_index, height)) in self.levels().into_iter().enumerate() {
+            if level != 0 {
+                node = Hash::hash_varlen(&node.encode());
+            }
+            let mut index = leaf_index;
+            for _ in 0..height {
+                let sibling = *siblings.next()?;
+                node = if index % 2 == 0 {
+                    Hash::hash_pair(node, sibling)
+                } else {
+                    Hash::hash_pair(sibling, node)
+                };
+                index /= 2;
+            }
+        }
+
+        Some(node)
+    }
+}
+
+impl PrincipalBlockValidationLogic {
+    pub fn new_from_primitive_witness(
+        primitive_witness: &BlockPrimitiveWitness,
+        block_kernel: &BlockKernel,
+    ) -> Self {
+        Self {
+            predecessor_is_valid: PredecessorIsValid::new_from_primitive_witness(
+                primitive_witness,
+                block_kernel,
+            ),
+            transaction_is_valid: TransactionIsValid::new_from_primitive_witness(
+                primitive_witness,
+                block_kernel,
+            ),
+            coinbase_is_valid: CoinbaseIsValid::new_from_primitive_witness(
+                primitive_witness,
+                block_kernel,
+            ),
+            correct_mutator_set_update: CorrectMutatorSetUpdate::new_from_primitive_witness(
+                primitive_witness,
+                block_kernel,
+            ),
+            correct_mmr_update: CorrectMmrUpdate::new_from_primitive_witness(
+                primitive_witness,
+                block_kernel,
+            ),
+            correct_control_parameter_update:
+                CorrectControlParameterUpdate::new_from_primitive_witness(
+                    primitive_witness,
+                    block_kernel,
+                ),
+        }
+    }
+
+    pub fn prove(&mut self) -> Result<()> {
+        self.predecessor_is_valid.prove()?;
+        self.transaction_is_valid.prove()?;
+        self.coinbase_is_valid.prove()?;
+        self.correct_mutator_set_update.prove()?;
+        self.correct_mmr_update.prove()?;
+        self.correct_control_parameter_update.prove()?;
+
+        Ok(())
+    }
+
+    /// Return true iff every subclaim is supported by a proof, rather than by the secret
+    /// witness to prove it.
+    pub fn is_proven(&self) -> bool {
+        [
+            matches!(self.predecessor_is_valid.support(), ClaimSupport::Proof(_)),
+            matches!(self.transaction_is_valid.support(), ClaimSupport::Proof(_)),
+            matches!(self.coinbase_is_valid.support(), ClaimSupport::Proof(_)),
+            matches!(
+                self.correct_mutator_set_update.support(),
+                ClaimSupport::Proof(_)
+            ),
+            matches!(self.correct_mmr_update.support(), ClaimSupport::Proof(_)),
+            matches!(
+                self.correct_control_parameter_update.support(),
+                ClaimSupport::Proof(_)
+            ),
+        ]
+        .into_iter()
+        .all(|is_proof| is_proof)
+    }
+
+    /// Verify that the subclaims are about the given block kernel and that each of them
+    /// holds.
+    ///
+    /// The predecessor claim outputs the digest of the block that the kernel builds on,
+    /// and the transaction claim outputs the MAST hash of the kernel of the block's
+    /// transaction. These claims only establish linkage: the proof does not attest that
+    /// the predecessor or the transaction is valid. Until recursive verification is
+    /// available, the caller establishes the validity of both on its own, so a verified
+    /// block proof is not sufficient for succinct sync or for light clients.
+    pub fn verify(&self, block_kernel: &BlockKernel) -> bool {
+        let block_hash = block_claim_input(block_kernel);
+        let program_digest = |program: Program| Hash::hash_varlen(&program.encode());
+
+        let claims = [
+            (
+                self.predecessor_is_valid.claim(),
+                PredecessorIsValid::program(),
+                block_kernel.header.prev_block_digest.values().to_vec(),
+            ),
+            (
+                self.transaction_is_valid.claim(),
+                TransactionIsValid::program(),
+                block_kernel
+                    .body
+                    .transaction
+                    .kernel
+                    .mast_hash()
+                    .values()
+                    .to_vec(),
+            ),
+            (
+                self.coinbase_is_valid.claim(),
+                CoinbaseIsValid::program(),
+                vec![],
+            ),
+            (
+                self.correct_mutator_set_update.claim(),
+                CorrectMutatorSetUpdate::program(),
+                vec![],
+            ),
+            (
+                self.correct_mmr_update.claim(),
+                CorrectMmrUpdate::program(),
+                vec![],
+            ),
+            (
+                self.correct_control_parameter_update.claim(),
+                CorrectControlParameterUpdate::program(),
+                vec![],
+            ),
+        ];
+        if claims
+            .iter()
+            .any(|(claim, program, _)| claim.program_digest != program_digest(program.clone()))
+        {
+            warn!("Block validity claims are about the wrong programs");
+            return false;
+        }
+        if claims.iter().any(|(claim, _, _)| claim.input != block_hash) {
+            warn!("Block validity claims are not about the block kernel");
+            return false;
+        }
+        if let Some((claim, _, _)) = claims
+            .iter()
+            .find(|(claim, _, output)| claim.output != *output)
+        {
+            warn!(
+                "Block validity claim has unexpected output: {}",
+                claim.output.iter().join(",")
+            );
+            return false;
+        }
+
+        self.predecessor_is_valid.verify()
+            && self.transaction_is_valid.verify()
+            && self.coinbase_is_valid.verify()
+            && self.correct_mutator_set_update.verify()
+            && self.correct_mmr_update.verify()
+            && self.correct_control_parameter_update.verify()
+    }
+}
+
+/// The public input of every block validity subclaim: the reversed MAST hash of the
+/// block kernel.
+pub fn block_claim_input(block_kernel: &BlockKernel) -> Vec<BFieldElement> {
+    block_kernel.mast_hash().reversed().values().to_vec()
+}
+
 /// Alternatively, the validity of a block follows from that of a successor. This pathway
 /// two subclaims, both of which are relative to the successor block.
 ///  1. the current block lives in the block mmr of the successor block
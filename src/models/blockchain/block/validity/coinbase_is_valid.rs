@@ -1,32 +1,125 @@
+use crate::prelude::{triton_vm, twenty_first};
+
+use field_count::FieldCount;
 use get_size::GetSize;
 use serde::{Deserialize, Serialize};
-use tasm_lib::{
-    triton_vm::program::{NonDeterminism, Program},
-    twenty_first::{self, shared_math::b_field_element::BFieldElement},
-};
+use std::collections::HashMap;
+use tasm_lib::memory::{encode_to_memory, FIRST_NON_DETERMINISTICALLY_INITIALIZED_MEMORY_ADDRESS};
+use tasm_lib::structure::tasm_object::TasmObject;
+use tasm_lib::traits::compiled_program::CompiledProgram;
+use triton_vm::prelude::{BFieldElement, Claim, NonDeterminism, Program, Proof};
 use twenty_first::shared_math::bfield_codec::BFieldCodec;
+use twenty_first::shared_math::tip5::Digest;
+use twenty_first::util_types::algebraic_hasher::AlgebraicHasher;
 
-use crate::models::{
-    blockchain::block::Block,
-    consensus::{SecretWitness, SupportedClaim},
-};
+use super::{block_claim_input, BlockKernelLeaf, BlockPrimitiveWitness};
+use crate::models::blockchain::block::block_header::BlockHeaderField;
+use crate::models::blockchain::block::block_kernel::BlockKernel;
+use crate::models::blockchain::shared::Hash;
+use crate::models::blockchain::transaction::neptune_coins::NeptuneCoins;
+use crate::models::blockchain::transaction::transaction_kernel::TransactionKernelField;
+use crate::models::consensus::{ClaimSupport, SecretWitness, SupportedClaim, ValidationLogic};
 
-#[derive(Debug, Clone, BFieldCodec, GetSize, PartialEq, Eq, Serialize, Deserialize)]
+/// The height of the block and the fee and coinbase of its transaction, each with its
+/// authentication path, along with the generation of the block, which is the number of
+/// times the block subsidy was halved.
+#[derive(
+    Clone,
+    Debug,
+    Serialize,
+    Deserialize,
+    PartialEq,
+    Eq,
+    GetSize,
+    BFieldCodec,
+    FieldCount,
+    TasmObject,
+)]
 pub struct CoinbaseIsValidWitness {
-    pub block: Block,
+    pub height: BFieldElement,
+    pub height_path: Vec<Digest>,
+    pub fee: NeptuneCoins,
+    pub fee_path: Vec<Digest>,
+    pub coinbase: Option<NeptuneCoins>,
+    pub coinbase_path: Vec<Digest>,
+    pub generation: BFieldElement,
+}
+
+impl CoinbaseIsValidWitness {
+    pub fn new(_primitive_witness: &BlockPrimitiveWitness, block_kernel: &BlockKernel) -> Self {
+        let transaction_kernel = &block_kernel.body.transaction.kernel;
+
+        Self {
+            height: block_kernel.header.height.into(),
+            height_path: BlockKernelLeaf::Header(BlockHeaderField::Height).path(block_kernel),
+            fee: transaction_kernel.fee,
+            fee_path: BlockKernelLeaf::TransactionKernel(TransactionKernelField::Fee)
+                .path(block_kernel),
+            coinbase: transaction_kernel.coinbase,
+            coinbase_path: BlockKernelLeaf::TransactionKernel(TransactionKernelField::Coinbase)
+                .path(block_kernel),
+            generation: BFieldElement::new(block_kernel.header.height.get_generation()),
+        }
+    }
 }
 
 impl SecretWitness for CoinbaseIsValidWitness {
     fn nondeterminism(&self) -> NonDeterminism<BFieldElement> {
-        todo!()
+        let mut memory = HashMap::default();
+        encode_to_memory(
+            &mut memory,
+            FIRST_NON_DETERMINISTICALLY_INITIALIZED_MEMORY_ADDRESS,
+            self.clone(),
+        );
+        NonDeterminism::default().with_ram(memory)
     }
 
     fn subprogram(&self) -> Program {
-        todo!()
+        CoinbaseIsValid::program()
     }
 }
 
 #[derive(Debug, Clone, BFieldCodec, GetSize, PartialEq, Eq, Serialize, Deserialize)]
 pub struct CoinbaseIsValid {
-    supported_claim: SupportedClaim<CoinbaseIsValidWitness>,
+    pub supported_claim: SupportedClaim<CoinbaseIsValidWitness>,
+}
+
+impl ValidationLogic<CoinbaseIsValidWitness> for CoinbaseIsValid {
+    type PrimitiveWitness = BlockPrimitiveWitness;
+
+    type Kernel = BlockKernel;
+
+    fn new_from_primitive_witness(
+        primitive_witness: &BlockPrimitiveWitness,
+        block_kernel: &BlockKernel,
+    ) -> Self {
+        let coinbase_is_valid_witness =
+            CoinbaseIsValidWitness::new(primitive_witness, block_kernel);
+        let claim = Claim {
+            input: block_claim_input(block_kernel),
+            output: vec![],
+            program_digest: Hash::hash_varlen(&Self::program().encode()),
+        };
+        let supported_claim = SupportedClaim {
+            claim,
+            support: ClaimSupport::SecretWitness(coinbase_is_valid_witness),
+        };
+        Self { supported_claim }
+    }
+
+    fn upgrade(&mut self, proof: Proof) {
+        self.supported_claim.support = ClaimSupport::Proof(proof);
+    }
+
+    fn subprogram(&self) -> Program {
+        Self::program()
+    }
+
+    fn support(&self) -> ClaimSupport<CoinbaseIsValidWitness> {
+        self.supported_claim.support.clone()
+    }
+
+    fn claim(&self) -> Claim {
+        self.supported_claim.claim.clone()
+    }
 }
@@ -1,27 +1,104 @@
+use crate::prelude::{triton_vm, twenty_first};
+
+use field_count::FieldCount;
 use get_size::GetSize;
 use serde::{Deserialize, Serialize};
-use tasm_lib::{
-    triton_vm::program::{NonDeterminism, Program},
-    twenty_first::shared_math::{b_field_element::BFieldElement, bfield_codec::BFieldCodec},
-};
+use std::collections::HashMap;
+use tasm_lib::memory::{encode_to_memory, FIRST_NON_DETERMINISTICALLY_INITIALIZED_MEMORY_ADDRESS};
+use tasm_lib::structure::tasm_object::TasmObject;
+use tasm_lib::traits::compiled_program::CompiledProgram;
+use triton_vm::prelude::{BFieldElement, Claim, NonDeterminism, Program, Proof};
+use twenty_first::amount::u32s::U32s;
+use twenty_first::shared_math::bfield_codec::BFieldCodec;
+use twenty_first::shared_math::tip5::Digest;
+use twenty_first::util_types::algebraic_hasher::AlgebraicHasher;
 
-use crate::models::{
-    blockchain::block::Block,
-    consensus::{SecretWitness, SupportedClaim},
+use super::{block_claim_input, BlockKernelLeaf, BlockPrimitiveWitness};
+use crate::models::blockchain::block::block_header::{
+    BlockHeaderField, TARGET_BLOCK_INTERVAL, TARGET_DIFFICULTY_U32_SIZE,
 };
+use crate::models::blockchain::block::block_kernel::BlockKernel;
+use crate::models::blockchain::shared::Hash;
+use crate::models::consensus::{ClaimSupport, SecretWitness, SupportedClaim, ValidationLogic};
 
-#[derive(Debug, Clone, BFieldCodec, GetSize, PartialEq, Eq, Serialize, Deserialize)]
+/// The timestamps and difficulties of the block and of its predecessor, the height of the
+/// predecessor, and the digest of the predecessor, each with its authentication path.
+/// The difficulty adjustment is the magnitude of the control signal: the distance between
+/// the block interval and its target, divided by 100.
+#[derive(
+    Clone,
+    Debug,
+    Serialize,
+    Deserialize,
+    PartialEq,
+    Eq,
+    GetSize,
+    BFieldCodec,
+    FieldCount,
+    TasmObject,
+)]
 pub struct CorrectControlParameterUpdateWitness {
-    pub previous_block: Block,
+    pub prev_block_digest: Digest,
+    pub prev_block_digest_path: Vec<Digest>,
+    pub timestamp: BFieldElement,
+    pub timestamp_path: Vec<Digest>,
+    pub difficulty: U32s<TARGET_DIFFICULTY_U32_SIZE>,
+    pub difficulty_path: Vec<Digest>,
+    pub predecessor_height: BFieldElement,
+    pub predecessor_height_path: Vec<Digest>,
+    pub predecessor_timestamp: BFieldElement,
+    pub predecessor_timestamp_path: Vec<Digest>,
+    pub predecessor_difficulty: U32s<TARGET_DIFFICULTY_U32_SIZE>,
+    pub predecessor_difficulty_path: Vec<Digest>,
+    pub difficulty_adjustment: BFieldElement,
+}
+
+impl CorrectControlParameterUpdateWitness {
+    pub fn new(primitive_witness: &BlockPrimitiveWitness, block_kernel: &BlockKernel) -> Self {
+        let predecessor = &primitive_witness.predecessor;
+        let timestamp = BlockKernelLeaf::Header(BlockHeaderField::Timestamp);
+        let difficulty = BlockKernelLeaf::Header(BlockHeaderField::Difficulty);
+
+        let block_interval = block_kernel
+            .header
+            .timestamp
+            .value()
+            .wrapping_sub(predecessor.header.timestamp.value());
+        let difficulty_adjustment = block_interval.abs_diff(TARGET_BLOCK_INTERVAL) / 100;
+
+        Self {
+            prev_block_digest: block_kernel.header.prev_block_digest,
+            prev_block_digest_path: BlockKernelLeaf::Header(BlockHeaderField::PrevBlockDigest)
+                .path(block_kernel),
+            timestamp: block_kernel.header.timestamp,
+            timestamp_path: timestamp.path(block_kernel),
+            difficulty: block_kernel.header.difficulty,
+            difficulty_path: difficulty.path(block_kernel),
+            predecessor_height: predecessor.header.height.into(),
+            predecessor_height_path: BlockKernelLeaf::Header(BlockHeaderField::Height)
+                .path(predecessor),
+            predecessor_timestamp: predecessor.header.timestamp,
+            predecessor_timestamp_path: timestamp.path(predecessor),
+            predecessor_difficulty: predecessor.header.difficulty,
+            predecessor_difficulty_path: difficulty.path(predecessor),
+            difficulty_adjustment: BFieldElement::new(difficulty_adjustment),
+        }
+    }
 }
 
 impl SecretWitness for CorrectControlParameterUpdateWitness {
     fn nondeterminism(&self) -> NonDeterminism<BFieldElement> {
-        todo!()
+        let mut memory = HashMap::default();
+        encode_to_memory(
+            &mut memory,
+            FIRST_NON_DETERMINISTICALLY_INITIALIZED_MEMORY_ADDRESS,
+            self.clone(),
+        );
+        NonDeterminism::default().with_ram(memory)
     }
 
     fn subprogram(&self) -> Program {
-        todo!()
+        CorrectControlParameterUpdate::program()
     }
 }
 
@@ -29,3 +106,43 @@ impl SecretWitness for CorrectControlParameterUpdateWitness {
 pub struct CorrectControlParameterUpdate {
     pub supported_claim: SupportedClaim<CorrectControlParameterUpdateWitness>,
 }
+
+impl ValidationLogic<CorrectControlParameterUpdateWitness> for CorrectControlParameterUpdate {
+    type PrimitiveWitness = BlockPrimitiveWitness;
+
+    type Kernel = BlockKernel;
+
+    fn new_from_primitive_witness(
+        primitive_witness: &BlockPrimitiveWitness,
+        block_kernel: &BlockKernel,
+    ) -> Self {
+        let correct_control_parameter_update_witness =
+            CorrectControlParameterUpdateWitness::new(primitive_witness, block_kernel);
+        let claim = Claim {
+            input: block_claim_input(block_kernel),
+            output: vec![],
+            program_digest: Hash::hash_varlen(&Self::program().encode()),
+        };
+        let supported_claim = SupportedClaim {
+            claim,
+            support: ClaimSupport::SecretWitness(correct_control_parameter_update_witness),
+        };
+        Self { supported_claim }
+    }
+
+    fn upgrade(&mut self, proof: Proof) {
+        self.supported_claim.support = ClaimSupport::Proof(proof);
+    }
+
+    fn subprogram(&self) -> Program {
+        Self::program()
+    }
+
+    fn support(&self) -> ClaimSupport<CorrectControlParameterUpdateWitness> {
+        self.supported_claim.support.clone()
+    }
+
+    fn claim(&self) -> Claim {
+        self.supported_claim.claim.clone()
+    }
+}
@@ -1,28 +1,78 @@
-use crate::Hash;
+use crate::prelude::{triton_vm, twenty_first};
+
+use field_count::FieldCount;
 use get_size::GetSize;
 use serde::{Deserialize, Serialize};
-use tasm_lib::{
-    triton_vm::program::{NonDeterminism, Program},
-    twenty_first::{
-        shared_math::{b_field_element::BFieldElement, bfield_codec::BFieldCodec},
-        util_types::mmr::mmr_accumulator::MmrAccumulator,
-    },
-};
+use std::collections::HashMap;
+use tasm_lib::memory::{encode_to_memory, FIRST_NON_DETERMINISTICALLY_INITIALIZED_MEMORY_ADDRESS};
+use tasm_lib::structure::tasm_object::TasmObject;
+use tasm_lib::traits::compiled_program::CompiledProgram;
+use triton_vm::prelude::{BFieldElement, Claim, NonDeterminism, Program, Proof};
+use twenty_first::shared_math::bfield_codec::BFieldCodec;
+use twenty_first::shared_math::tip5::Digest;
+use twenty_first::util_types::algebraic_hasher::AlgebraicHasher;
+use twenty_first::util_types::mmr::mmr_accumulator::MmrAccumulator;
 
-use crate::models::consensus::{SecretWitness, SupportedClaim};
+use super::{block_claim_input, BlockKernelLeaf, BlockPrimitiveWitness};
+use crate::models::blockchain::block::block_body::BlockBodyField;
+use crate::models::blockchain::block::block_header::BlockHeaderField;
+use crate::models::blockchain::block::block_kernel::BlockKernel;
+use crate::models::blockchain::shared::Hash;
+use crate::models::consensus::{ClaimSupport, SecretWitness, SupportedClaim, ValidationLogic};
 
-#[derive(Debug, Clone, BFieldCodec, GetSize, PartialEq, Eq, Serialize, Deserialize)]
+/// The block MMR accumulators of the block and of its predecessor, and the digest of the
+/// predecessor, each with its authentication path.
+#[derive(
+    Clone,
+    Debug,
+    Serialize,
+    Deserialize,
+    PartialEq,
+    Eq,
+    GetSize,
+    BFieldCodec,
+    FieldCount,
+    TasmObject,
+)]
 pub struct CorrectMmrUpdateWitness {
-    pub previous_mmr_accumulator: MmrAccumulator<Hash>,
+    pub prev_block_digest: Digest,
+    pub prev_block_digest_path: Vec<Digest>,
+    pub block_mmr_accumulator: MmrAccumulator<Hash>,
+    pub block_mmr_accumulator_path: Vec<Digest>,
+    pub predecessor_block_mmr_accumulator: MmrAccumulator<Hash>,
+    pub predecessor_block_mmr_accumulator_path: Vec<Digest>,
+}
+
+impl CorrectMmrUpdateWitness {
+    pub fn new(primitive_witness: &BlockPrimitiveWitness, block_kernel: &BlockKernel) -> Self {
+        let predecessor = &primitive_witness.predecessor;
+        let block_mmr_accumulator = BlockKernelLeaf::Body(BlockBodyField::BlockMmrAccumulator);
+
+        Self {
+            prev_block_digest: block_kernel.header.prev_block_digest,
+            prev_block_digest_path: BlockKernelLeaf::Header(BlockHeaderField::PrevBlockDigest)
+                .path(block_kernel),
+            block_mmr_accumulator: block_kernel.body.block_mmr_accumulator.clone(),
+            block_mmr_accumulator_path: block_mmr_accumulator.path(block_kernel),
+            predecessor_block_mmr_accumulator: predecessor.body.block_mmr_accumulator.clone(),
+            predecessor_block_mmr_accumulator_path: block_mmr_accumulator.path(predecessor),
+        }
+    }
 }
 
 impl SecretWitness for CorrectMmrUpdateWitness {
     fn nondeterminism(&self) -> NonDeterminism<BFieldElement> {
-        todo!()
+        let mut memory = HashMap::default();
+        encode_to_memory(
+            &mut memory,
+            FIRST_NON_DETERMINISTICALLY_INITIALIZED_MEMORY_ADDRESS,
+            self.clone(),
+        );
+        NonDeterminism::default().with_ram(memory)
     }
 
     fn subprogram(&self) -> Program {
-        todo!()
+        CorrectMmrUpdate::program()
     }
 }
 
@@ -30,3 +80,43 @@ impl SecretWitness for CorrectMmrUpdateWitness {
 pub struct CorrectMmrUpdate {
     pub supported_claim: SupportedClaim<CorrectMmrUpdateWitness>,
 }
+
+impl ValidationLogic<CorrectMmrUpdateWitness> for CorrectMmrUpdate {
+    type PrimitiveWitness = BlockPrimitiveWitness;
+
+    type Kernel = BlockKernel;
+
+    fn new_from_primitive_witness(
+        primitive_witness: &BlockPrimitiveWitness,
+        block_kernel: &BlockKernel,
+    ) -> Self {
+        let correct_mmr_update_witness =
+            CorrectMmrUpdateWitness::new(primitive_witness, block_kernel);
+        let claim = Claim {
+            input: block_claim_input(block_kernel),
+            output: vec![],
+            program_digest: Hash::hash_varlen(&Self::program().encode()),
+        };
+        let supported_claim = SupportedClaim {
+            claim,
+            support: ClaimSupport::SecretWitness(correct_mmr_update_witness),
+        };
+        Self { supported_claim }
+    }
+
+    fn upgrade(&mut self, proof: Proof) {
+        self.supported_claim.support = ClaimSupport::Proof(proof);
+    }
+
+    fn subprogram(&self) -> Program {
+        Self::program()
+    }
+
+    fn support(&self) -> ClaimSupport<CorrectMmrUpdateWitness> {
+        self.supported_claim.support.clone()
+    }
+
+    fn claim(&self) -> Claim {
+        self.supported_claim.claim.clone()
+    }
+}
@@ -1,27 +1,101 @@
+use crate::prelude::{triton_vm, twenty_first};
+
+use field_count::FieldCount;
 use get_size::GetSize;
 use serde::{Deserialize, Serialize};
-use tasm_lib::{
-    triton_vm::program::{NonDeterminism, Program},
-    twenty_first::{bfieldcodec_derive::BFieldCodec, shared_math::b_field_element::BFieldElement},
-};
+use std::collections::HashMap;
+use tasm_lib::memory::{encode_to_memory, FIRST_NON_DETERMINISTICALLY_INITIALIZED_MEMORY_ADDRESS};
+use tasm_lib::structure::tasm_object::TasmObject;
+use tasm_lib::traits::compiled_program::CompiledProgram;
+use triton_vm::prelude::{BFieldElement, Claim, NonDeterminism, Program, Proof};
+use twenty_first::shared_math::bfield_codec::BFieldCodec;
+use twenty_first::shared_math::tip5::Digest;
+use twenty_first::util_types::algebraic_hasher::AlgebraicHasher;
+use twenty_first::util_types::mmr::mmr_accumulator::MmrAccumulator;
+use twenty_first::util_types::mmr::mmr_trait::Mmr;
 
-use crate::{
-    models::consensus::{SecretWitness, SupportedClaim},
-    util_types::mutator_set::mutator_set_accumulator::MutatorSetAccumulator,
-};
+use super::{block_claim_input, BlockKernelLeaf, BlockPrimitiveWitness};
+use crate::models::blockchain::block::block_body::BlockBodyField;
+use crate::models::blockchain::block::block_header::BlockHeaderField;
+use crate::models::blockchain::block::block_kernel::BlockKernel;
+use crate::models::blockchain::shared::Hash;
+use crate::models::blockchain::transaction::transaction_kernel::TransactionKernelField;
+use crate::models::consensus::{ClaimSupport, SecretWitness, SupportedClaim, ValidationLogic};
+use crate::util_types::mutator_set::addition_record::AdditionRecord;
+use crate::util_types::mutator_set::mutator_set_accumulator::MutatorSetAccumulator;
 
-#[derive(Debug, Clone, BFieldCodec, GetSize, PartialEq, Eq, Serialize, Deserialize)]
+/// The mutator set accumulators of the block and of its predecessor, the outputs of the
+/// block's transaction, and the digest of the predecessor, each with its authentication
+/// path. The AOCL accumulators list the AOCL before and after every output is added.
+///
+/// The claim covers the AOCL only. Applying the removal records to the sliding-window
+/// Bloom filter is checked natively, in [`Block::is_valid`](super::super::Block::is_valid).
+#[derive(
+    Clone,
+    Debug,
+    Serialize,
+    Deserialize,
+    PartialEq,
+    Eq,
+    GetSize,
+    BFieldCodec,
+    FieldCount,
+    TasmObject,
+)]
 pub struct CorrectMutatorSetUpdateWitness {
-    previous_mutator_set_accumulator: MutatorSetAccumulator,
+    pub prev_block_digest: Digest,
+    pub prev_block_digest_path: Vec<Digest>,
+    pub mutator_set_accumulator: MutatorSetAccumulator,
+    pub mutator_set_accumulator_path: Vec<Digest>,
+    pub predecessor_mutator_set_accumulator: MutatorSetAccumulator,
+    pub predecessor_mutator_set_accumulator_path: Vec<Digest>,
+    pub outputs: Vec<AdditionRecord>,
+    pub outputs_path: Vec<Digest>,
+    pub aocl_accumulators: Vec<MmrAccumulator<Hash>>,
+}
+
+impl CorrectMutatorSetUpdateWitness {
+    pub fn new(primitive_witness: &BlockPrimitiveWitness, block_kernel: &BlockKernel) -> Self {
+        let predecessor = &primitive_witness.predecessor;
+        let mutator_set_accumulator = BlockKernelLeaf::Body(BlockBodyField::MutatorSetAccumulator);
+        let outputs = block_kernel.body.transaction.kernel.outputs.clone();
+
+        let mut aocl = predecessor.body.mutator_set_accumulator.kernel.aocl.clone();
+        let mut aocl_accumulators = vec![aocl.clone()];
+        for addition_record in outputs.iter() {
+            aocl.append(addition_record.canonical_commitment);
+            aocl_accumulators.push(aocl.clone());
+        }
+
+        Self {
+            prev_block_digest: block_kernel.header.prev_block_digest,
+            prev_block_digest_path: BlockKernelLeaf::Header(BlockHeaderField::PrevBlockDigest)
+                .path(block_kernel),
+            mutator_set_accumulator: block_kernel.body.mutator_set_accumulator.clone(),
+            mutator_set_accumulator_path: mutator_set_accumulator.path(block_kernel),
+            predecessor_mutator_set_accumulator: predecessor.body.mutator_set_accumulator.clone(),
+            predecessor_mutator_set_accumulator_path: mutator_set_accumulator.path(predecessor),
+            outputs,
+            outputs_path: BlockKernelLeaf::TransactionKernel(TransactionKernelField::OutputUtxos)
+                .path(block_kernel),
+            aocl_accumulators,
+        }
+    }
 }
 
 impl SecretWitness for CorrectMutatorSetUpdateWitness {
     fn nondeterminism(&self) -> NonDeterminism<BFieldElement> {
-        todo!()
+        let mut memory = HashMap::default();
+        encode_to_memory(
+            &mut memory,
+            FIRST_NON_DETERMINISTICALLY_INITIALIZED_MEMORY_ADDRESS,
+            self.clone(),
+        );
+        NonDeterminism::default().with_ram(memory)
     }
 
     fn subprogram(&self) -> Program {
-        todo!()
+        CorrectMutatorSetUpdate::program()
     }
 }
 
@@ -29,3 +103,43 @@ impl SecretWitness for CorrectMutatorSetUpdateWitness {
 pub struct CorrectMutatorSetUpdate {
     pub supported_claim: SupportedClaim<CorrectMutatorSetUpdateWitness>,
 }
+
+impl ValidationLogic<CorrectMutatorSetUpdateWitness> for CorrectMutatorSetUpdate {
+    type PrimitiveWitness = BlockPrimitiveWitness;
+
+    type Kernel = BlockKernel;
+
+    fn new_from_primitive_witness(
+        primitive_witness: &BlockPrimitiveWitness,
+        block_kernel: &BlockKernel,
+    ) -> Self {
+        let correct_mutator_set_update_witness =
+            CorrectMutatorSetUpdateWitness::new(primitive_witness, block_kernel);
+        let claim = Claim {
+            input: block_claim_input(block_kernel),
+            output: vec![],
+            program_digest: Hash::hash_varlen(&Self::program().encode()),
+        };
+        let supported_claim = SupportedClaim {
+            claim,
+            support: ClaimSupport::SecretWitness(correct_mutator_set_update_witness),
+        };
+        Self { supported_claim }
+    }
+
+    fn upgrade(&mut self, proof: Proof) {
+        self.supported_claim.support = ClaimSupport::Proof(proof);
+    }
+
+    fn subprogram(&self) -> Program {
+        Self::program()
+    }
+
+    fn support(&self) -> ClaimSupport<CorrectMutatorSetUpdateWitness> {
+        self.supported_claim.support.clone()
+    }
+
+    fn claim(&self) -> Claim {
+        self.supported_claim.claim.clone()
+    }
+}
@@ -1,27 +1,77 @@
+use crate::prelude::{triton_vm, twenty_first};
+
+use field_count::FieldCount;
 use get_size::GetSize;
 use serde::{Deserialize, Serialize};
-use tasm_lib::{
-    triton_vm::program::{NonDeterminism, Program},
-    twenty_first::shared_math::{b_field_element::BFieldElement, bfield_codec::BFieldCodec},
-};
+use std::collections::HashMap;
+use tasm_lib::memory::{encode_to_memory, FIRST_NON_DETERMINISTICALLY_INITIALIZED_MEMORY_ADDRESS};
+use tasm_lib::structure::tasm_object::TasmObject;
+use tasm_lib::traits::compiled_program::CompiledProgram;
+use triton_vm::prelude::{BFieldElement, Claim, NonDeterminism, Program, Proof};
+use twenty_first::shared_math::bfield_codec::BFieldCodec;
+use twenty_first::shared_math::tip5::Digest;
+use twenty_first::util_types::algebraic_hasher::AlgebraicHasher;
 
-use crate::models::{
-    blockchain::block::Block,
-    consensus::{SecretWitness, SupportedClaim},
-};
+use super::{block_claim_input, BlockKernelLeaf, BlockPrimitiveWitness};
+use crate::models::blockchain::block::block_header::BlockHeaderField;
+use crate::models::blockchain::block::block_kernel::BlockKernel;
+use crate::models::blockchain::shared::Hash;
+use crate::models::consensus::{ClaimSupport, SecretWitness, SupportedClaim, ValidationLogic};
 
-#[derive(Debug, Clone, BFieldCodec, GetSize, PartialEq, Eq, Serialize, Deserialize)]
+/// The fields that link a block to its predecessor, each with its authentication path.
+/// The height of the predecessor is authenticated against the digest that the block
+/// points to.
+#[derive(
+    Clone,
+    Debug,
+    Serialize,
+    Deserialize,
+    PartialEq,
+    Eq,
+    GetSize,
+    BFieldCodec,
+    FieldCount,
+    TasmObject,
+)]
 pub struct PredecessorIsValidWitness {
-    pub predecessor_block: Block,
+    pub height: BFieldElement,
+    pub height_path: Vec<Digest>,
+    pub prev_block_digest: Digest,
+    pub prev_block_digest_path: Vec<Digest>,
+    pub predecessor_height: BFieldElement,
+    pub predecessor_height_path: Vec<Digest>,
+}
+
+impl PredecessorIsValidWitness {
+    pub fn new(primitive_witness: &BlockPrimitiveWitness, block_kernel: &BlockKernel) -> Self {
+        let predecessor = &primitive_witness.predecessor;
+        let height = BlockKernelLeaf::Header(BlockHeaderField::Height);
+        let prev_block_digest = BlockKernelLeaf::Header(BlockHeaderField::PrevBlockDigest);
+
+        Self {
+            height: block_kernel.header.height.into(),
+            height_path: height.path(block_kernel),
+            prev_block_digest: block_kernel.header.prev_block_digest,
+            prev_block_digest_path: prev_block_digest.path(block_kernel),
+            predecessor_height: predecessor.header.height.into(),
+            predecessor_height_path: height.path(predecessor),
+        }
+    }
 }
 
 impl SecretWitness for PredecessorIsValidWitness {
     fn nondeterminism(&self) -> NonDeterminism<BFieldElement> {
-        todo!()
+        let mut memory = HashMap::default();
+        encode_to_memory(
+            &mut memory,
+            FIRST_NON_DETERMINISTICALLY_INITIALIZED_MEMORY_ADDRESS,
+            self.clone(),
+        );
+        NonDeterminism::default().with_ram(memory)
     }
 
     fn subprogram(&self) -> Program {
-        todo!()
+        PredecessorIsValid::program()
     }
 }
 
@@ -29,3 +79,46 @@ impl SecretWitness for PredecessorIsValidWitness {
 pub struct PredecessorIsValid {
     pub supported_claim: SupportedClaim<PredecessorIsValidWitness>,
 }
+
+impl ValidationLogic<PredecessorIsValidWitness> for PredecessorIsValid {
+    type PrimitiveWitness = BlockPrimitiveWitness;
+
+    type Kernel = BlockKernel;
+
+    fn new_from_primitive_witness(
+        primitive_witness: &BlockPrimitiveWitness,
+        block_kernel: &BlockKernel,
+    ) -> Self {
+        let predecessor_is_valid_witness =
+            PredecessorIsValidWitness::new(primitive_witness, block_kernel);
+        let claim = Claim {
+            input: block_claim_input(block_kernel),
+            output: predecessor_is_valid_witness
+                .prev_block_digest
+                .values()
+                .to_vec(),
+            program_digest: Hash::hash_varlen(&Self::program().encode()),
+        };
+        let supported_claim = SupportedClaim {
+            claim,
+            support: ClaimSupport::SecretWitness(predecessor_is_valid_witness),
+        };
+        Self { supported_claim }
+    }
+
+    fn upgrade(&mut self, proof: Proof) {
+        self.supported_claim.support = ClaimSupport::Proof(proof);
+    }
+
+    fn subprogram(&self) -> Program {
+        Self::program()
+    }
+
+    fn support(&self) -> ClaimSupport<PredecessorIsValidWitness> {
+        self.supported_claim.support.clone()
+    }
+
+    fn claim(&self) -> Claim {
+        self.supported_claim.claim.clone()
+    }
+}
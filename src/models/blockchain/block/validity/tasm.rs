@@ -0,0 +1,106 @@
+use crate::prelude::{triton_vm, twenty_first};
+
+use itertools::Itertools;
+use tasm_lib::DIGEST_LENGTH;
+use triton_vm::instruction::LabelledInstruction;
+use triton_vm::prelude::PublicInput;
+use triton_vm::triton_asm;
+use twenty_first::shared_math::bfield_codec::BFieldCodec;
+use twenty_first::shared_math::tip5::Digest;
+
+use super::BlockKernelLeaf;
+
+pub mod coinbase_is_valid;
+pub mod correct_control_parameter_update;
+pub mod correct_mmr_update;
+pub mod correct_mutator_set_update;
+pub mod predecessor_is_valid;
+pub mod transaction_is_valid;
+pub(crate) mod verify_mmr_append;
+
+/// Read the MAST hash of the block kernel, which is reversed, from the public input of a
+/// block validity program.
+pub(crate) fn block_hash_from_public_input(public_input: &PublicInput) -> Digest {
+    *Digest::decode(
+        &public_input
+            .individual_tokens
+            .iter()
+            .copied()
+            .take(DIGEST_LENGTH)
+            .rev()
+            .collect_vec(),
+    )
+    .expect("Could not decode block hash in public input")
+}
+
+/// Return the instructions that authenticate a field of the witness against the MAST hash
+/// of a block kernel, as the given leaf. The authentication path of the field lists the
+/// siblings on all levels, bottom-up, as produced by [`BlockKernelLeaf::path`]. The field
+/// getters map the pointer to the witness to the field with its size and to the path.
+///
+/// BEFORE: _ [root] *witness
+/// AFTER: _ [root]
+pub(crate) fn authenticate_field(
+    leaf: BlockKernelLeaf,
+    witness_to_field_with_size: &[LabelledInstruction],
+    witness_to_path: &[LabelledInstruction],
+    hash_varlen: &str,
+) -> Vec<LabelledInstruction> {
+    let mut code = triton_asm! {
+        dup 0                           // _ [root] *witness *witness
+        {&witness_to_path}              // _ [root] *witness *path
+        swap 1                          // _ [root] *path *witness
+        {&witness_to_field_with_size}   // _ [root] *path *field field_size
+        call {hash_varlen}              // _ [root] *path [node]
+    };
+
+    let mut sibling_count = 0;
+    for (level, (leaf_index, height)) in leaf.levels().into_iter().enumerate() {
+        if level != 0 {
+            // the root of the previous level, hashed as a sequence, is the next leaf
+            code.extend(triton_asm! {
+                push 0 push 0 push 0 push 0 push 1
+                dup 9 dup 9 dup 9 dup 9 dup 9
+                sponge_init
+                sponge_absorb           // _ [root] *path [node]
+                sponge_squeeze          // _ [root] *path [node] s9 s8 s7 s6 s5 [leaf]
+                swap 5 pop 1 swap 5 pop 1 swap 5 pop 1 swap 5 pop 1 swap 5 pop 1
+                swap 5 pop 1 swap 5 pop 1 swap 5 pop 1 swap 5 pop 1 swap 5 pop 1
+                                        // _ [root] *path [leaf]
+            });
+        }
+
+        let mut index = leaf_index;
+        for _ in 0..height {
+            let sibling_last_word = 5 + 5 * sibling_count;
+            code.extend(triton_asm! {
+                dup 5
+                push {sibling_last_word}
+                add
+                read_mem 5
+                pop 1                   // _ [root] *path [node] [sibling]
+            });
+            if index % 2 == 0 {
+                code.extend(triton_asm! {
+                    dup 9 dup 9 dup 9 dup 9 dup 9
+                    hash                // _ [root] *path [node] [parent]
+                    swap 5 pop 1 swap 5 pop 1 swap 5 pop 1 swap 5 pop 1 swap 5 pop 1
+                });
+            } else {
+                code.extend(triton_asm!(hash));
+            }
+            index /= 2;
+            sibling_count += 1;
+        }
+    }
+
+    code.extend(triton_asm! {
+        // _ [root] *path [computed_root]
+        dup 10 dup 10 dup 10 dup 10 dup 10
+        assert_vector
+        pop 5
+        pop 1                           // _ [root]
+    });
+
+    code
+}
@@ -0,0 +1,384 @@
+use crate::prelude::{triton_vm, twenty_first};
+
+use tasm_lib::hashing::hash_varlen::HashVarlen;
+use tasm_lib::library::Library;
+use tasm_lib::memory::FIRST_NON_DETERMINISTICALLY_INITIALIZED_MEMORY_ADDRESS;
+use tasm_lib::structure::tasm_object::TasmObject;
+use tasm_lib::traits::compiled_program::CompiledProgram;
+use triton_vm::instruction::LabelledInstruction;
+use triton_vm::prelude::{triton_asm, BFieldElement, NonDeterminism, PublicInput};
+use twenty_first::shared_math::bfield_codec::BFieldCodec;
+
+use super::{authenticate_field, block_hash_from_public_input};
+use crate::models::blockchain::block::block_header::BlockHeaderField;
+use crate::models::blockchain::block::block_height::BLOCKS_PER_GENERATION;
+use crate::models::blockchain::block::validity::coinbase_is_valid::{
+    CoinbaseIsValid, CoinbaseIsValidWitness,
+};
+use crate::models::blockchain::block::validity::BlockKernelLeaf;
+use crate::models::blockchain::block::Block;
+use crate::models::blockchain::transaction::neptune_coins::NeptuneCoins;
+use crate::models::blockchain::transaction::transaction_kernel::TransactionKernelField;
+
+impl CompiledProgram for CoinbaseIsValid {
+    fn rust_shadow(
+        public_input: &PublicInput,
+        nondeterminism: &NonDeterminism<BFieldElement>,
+    ) -> anyhow::Result<Vec<BFieldElement>> {
+        let block_hash = block_hash_from_public_input(public_input);
+
+        // 1. read witness data
+        let witness = *CoinbaseIsValidWitness::decode_from_memory(
+            &nondeterminism.ram,
+            FIRST_NON_DETERMINISTICALLY_INITIALIZED_MEMORY_ADDRESS,
+        )
+        .unwrap();
+
+        // 2. authenticate the fields
+        assert_eq!(
+            Some(block_hash),
+            BlockKernelLeaf::Header(BlockHeaderField::Height)
+                .root(&witness.height.encode(), &witness.height_path)
+        );
+        assert_eq!(
+            Some(block_hash),
+            BlockKernelLeaf::TransactionKernel(TransactionKernelField::Fee)
+                .root(&witness.fee.encode(), &witness.fee_path)
+        );
+        assert_eq!(
+            Some(block_hash),
+            BlockKernelLeaf::TransactionKernel(TransactionKernelField::Coinbase)
+                .root(&witness.coinbase.encode(), &witness.coinbase_path)
+        );
+
+        // 3. the generation is the height divided by the number of blocks per generation
+        assert!(witness.height.value() <= u32::MAX as u64);
+        assert_eq!(
+            witness.height.value() / BLOCKS_PER_GENERATION,
+            witness.generation.value()
+        );
+
+        // 4. the coinbase does not exceed the block subsidy plus the fee
+        if let Some(coinbase) = witness.coinbase {
+            assert!(coinbase <= Block::get_mining_reward(witness.height.into()) + witness.fee);
+        }
+
+        Ok(vec![])
+    }
+
+    fn code() -> (Vec<LabelledInstruction>, Library) {
+        let mut library = Library::new();
+        let hash_varlen = library.import(Box::new(HashVarlen));
+
+        // field getters
+        type W = CoinbaseIsValidWitness;
+        let witness_to_height = tasm_lib::field!(W::height);
+        let witness_to_height_with_size = tasm_lib::field_with_size!(W::height);
+        let witness_to_height_path = tasm_lib::field!(W::height_path);
+        let witness_to_fee = tasm_lib::field!(W::fee);
+        let witness_to_fee_with_size = tasm_lib::field_with_size!(W::fee);
+        let witness_to_fee_path = tasm_lib::field!(W::fee_path);
+        let witness_to_coinbase = tasm_lib::field!(W::coinbase);
+        let witness_to_coinbase_with_size = tasm_lib::field_with_size!(W::coinbase);
+        let witness_to_coinbase_path = tasm_lib::field!(W::coinbase_path);
+        let witness_to_generation = tasm_lib::field!(W::generation);
+
+        let authenticate_height = authenticate_field(
+            BlockKernelLeaf::Header(BlockHeaderField::Height),
+            &witness_to_height_with_size,
+            &witness_to_height_path,
+            &hash_varlen,
+        );
+        let authenticate_fee = authenticate_field(
+            BlockKernelLeaf::TransactionKernel(TransactionKernelField::Fee),
+            &witness_to_fee_with_size,
+            &witness_to_fee_path,
+            &hash_varlen,
+        );
+        let authenticate_coinbase = authenticate_field(
+            BlockKernelLeaf::TransactionKernel(TransactionKernelField::Coinbase),
+            &witness_to_coinbase_with_size,
+            &witness_to_coinbase_path,
+            &hash_varlen,
+        );
+
+        let minus_blocks_per_generation = (-BFieldElement::new(BLOCKS_PER_GENERATION)).value();
+        let max_remainder = BLOCKS_PER_GENERATION - 1;
+
+        // the block subsidy of the first generation, pushed most significant limb first
+        let push_initial_subsidy = NeptuneCoins::new(100)
+            .encode()
+            .into_iter()
+            .rev()
+            .flat_map(|limb| triton_asm!(push { limb }))
+            .collect::<Vec<_>>();
+
+        // shift a 128-bit number right by one bit
+        // BEFORE: _ l3 l2 l1 l0
+        // AFTER: _ (l >> 1)_3 (l >> 1)_2 (l >> 1)_1 (l >> 1)_0
+        let mut halve = triton_asm! {
+            dup 3
+            push 2147483648
+            mul
+            split                   // _ l3 l2 l1 l0 n3 c3
+        };
+        for _ in 0..3 {
+            halve.extend(triton_asm! {
+                dup 4
+                push 2147483648
+                mul
+                split               // _ .. n_{i+1} c_{i+1} (l_i >> 1) c_i
+                swap 2
+                add
+                swap 1              // _ .. n_{i+1} n_i c_i
+            });
+        }
+        halve.extend(triton_asm! {
+            pop 1                   // _ l3 l2 l1 l0 n3 n2 n1 n0
+            swap 4 pop 1
+            swap 4 pop 1
+            swap 4 pop 1
+            swap 4 pop 1            // _ n3 n2 n1 n0
+        });
+
+        // compare the coinbase with the subsidy plus the fee, limb by limb, carrying
+        // a signed carry k in {-1, 0, 1}
+        // BEFORE: _ *witness [block_hash] 0 s3 s2 s1 s0
+        // AFTER: _ *witness [block_hash] 0 k
+        let mut compare = vec![];
+        for i in 0..4 {
+            if i != 0 {
+                compare.extend(triton_asm!(add));
+            }
+            let witness_depth = 10 - i;
+            let coinbase_limb_offset = i + 1;
+            compare.extend(triton_asm! {
+                dup {witness_depth}
+                {&witness_to_fee}
+                push {i}
+                add
+                read_mem 1
+                pop 1               // _ *witness [block_hash] 0 .. s_i fee_i
+                dup 0 split pop 1 push 0 eq assert
+                add
+                dup {witness_depth}
+                {&witness_to_coinbase}
+                push {coinbase_limb_offset}
+                add
+                read_mem 1
+                pop 1               // _ *witness [block_hash] 0 .. (s_i + fee_i) coinbase_i
+                dup 0 split pop 1 push 0 eq assert
+                push -1
+                mul
+                add
+                push 4294967296
+                add
+                split
+                pop 1
+                push -1
+                add                 // _ *witness [block_hash] 0 .. k
+            });
+        }
+
+        let check_coinbase = "tasm_neptune_block_coinbase_is_valid_check_coinbase".to_string();
+        let halve_subsidy = "tasm_neptune_block_coinbase_is_valid_halve_subsidy".to_string();
+
+        let code = triton_asm! {
+
+        // 1. Witness was already loaded into memory, just point to it
+        push {FIRST_NON_DETERMINISTICALLY_INITIALIZED_MEMORY_ADDRESS} // _ *witness
+        read_io 5                   // _ *witness [block_hash]
+
+        // 2. authenticate the fields
+        dup 5
+        {&authenticate_height}      // _ *witness [block_hash]
+        dup 5
+        {&authenticate_fee}         // _ *witness [block_hash]
+        dup 5
+        {&authenticate_coinbase}    // _ *witness [block_hash]
+
+        // 3. assert that the generation is the height divided by the number of blocks
+        // per generation
+        dup 5
+        {&witness_to_height}
+        read_mem 1
+        pop 1                       // _ *witness [block_hash] height
+        dup 0 split pop 1 push 0 eq assert
+        dup 6
+        {&witness_to_generation}
+        read_mem 1
+        pop 1                       // _ *witness [block_hash] height generation
+        dup 0 split pop 1 push 0 eq assert
+        push {minus_blocks_per_generation}
+        mul
+        add                         // _ *witness [block_hash] remainder
+        dup 0 split pop 1 push 0 eq assert
+        push -1
+        mul
+        push {max_remainder}
+        add
+        split
+        pop 1
+        push 0 eq
+        assert                      // _ *witness [block_hash]
+
+        // 4. if there is a coinbase, assert that it does not exceed the block subsidy
+        // plus the fee
+        dup 5
+        {&witness_to_coinbase}
+        read_mem 1
+        pop 1                       // _ *witness [block_hash] is_some
+        skiz call {check_coinbase}  // _ *witness [block_hash]
+
+        pop 5
+        pop 1                       // _
+
+        halt
+
+        // BEFORE: _ *witness [block_hash]
+        // AFTER: _ *witness [block_hash]
+        {check_coinbase}:
+            // halve the subsidy once per generation, but no more than 128 times
+            dup 5
+            {&witness_to_generation}
+            read_mem 1
+            pop 1                   // _ *witness [block_hash] generation
+            push 127
+            dup 1
+            push -1
+            mul
+            add
+            split
+            pop 1
+            push 0 eq               // _ *witness [block_hash] generation (generation <= 127)
+            dup 0
+            push -1
+            mul
+            push 1
+            add
+            push 128
+            mul
+            swap 2
+            mul
+            add                     // _ *witness [block_hash] num_halvings
+            {&push_initial_subsidy} // _ *witness [block_hash] num_halvings [initial_subsidy]
+            call {halve_subsidy}    // _ *witness [block_hash] 0 [subsidy]
+
+            {&compare}              // _ *witness [block_hash] 0 k
+            push 1
+            add
+            push 0 eq
+            push 0 eq
+            assert                  // _ *witness [block_hash] 0
+            pop 1
+            return
+
+        // BEFORE: _ n [subsidy]
+        // AFTER: _ 0 [subsidy >> n]
+        {halve_subsidy}:
+            dup 4
+            push 0 eq
+            skiz return
+            swap 4
+            push -1
+            add
+            swap 4
+            {&halve}
+            recurse
+        };
+
+        (code, library)
+    }
+
+    fn crash_conditions() -> Vec<String> {
+        vec![
+            "a field does not match the block kernel MAST hash".to_string(),
+            "the generation does not match the block height".to_string(),
+            "the coinbase exceeds the block subsidy plus the fee".to_string(),
+        ]
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::models::blockchain::block::block_kernel::BlockKernel;
+    use crate::models::blockchain::block::validity::block_claim_input;
+    use crate::models::consensus::SecretWitness;
+    use crate::tests::shared::make_mock_block_kernel_and_primitive_witness;
+    use tasm_lib::traits::compiled_program::test_rust_shadow;
+
+    fn halts(witness: &CoinbaseIsValidWitness, block_kernel: &BlockKernel) -> bool {
+        CoinbaseIsValid::program()
+            .run(
+                PublicInput::new(block_claim_input(block_kernel)),
+                witness.nondeterminism(),
+            )
+            .is_ok()
+    }
+
+    #[test]
+    fn test_graceful_halt() {
+        let (block_kernel, primitive_witness) = make_mock_block_kernel_and_primitive_witness(None);
+        let witness = CoinbaseIsValidWitness::new(&primitive_witness, &block_kernel);
+
+        let output = CoinbaseIsValid::program()
+            .run(
+                PublicInput::new(block_claim_input(&block_kernel)),
+                witness.nondeterminism(),
+            )
+            .unwrap();
+        assert!(output.is_empty());
+    }
+
+    #[test]
+    fn program_is_deterministic() {
+        let program = CoinbaseIsValid::program();
+        let other_program = CoinbaseIsValid::program();
+        assert_eq!(program, other_program);
+    }
+
+    #[test]
+    fn tasm_matches_rust() {
+        let (block_kernel, primitive_witness) = make_mock_block_kernel_and_primitive_witness(None);
+        let witness = CoinbaseIsValidWitness::new(&primitive_witness, &block_kernel);
+
+        test_rust_shadow::<CoinbaseIsValid>(
+            &PublicInput::new(block_claim_input(&block_kernel)),
+            &witness.nondeterminism(),
+        );
+    }
+
+    #[test]
+    fn accepts_fee_on_top_of_subsidy_and_no_coinbase() {
+        let (mut block_kernel, primitive_witness) =
+            make_mock_block_kernel_and_primitive_witness(None);
+        let subsidy = Block::get_mining_reward(block_kernel.header.height);
+
+        block_kernel.body.transaction.kernel.fee = NeptuneCoins::new(3);
+        block_kernel.body.transaction.kernel.coinbase = Some(subsidy + NeptuneCoins::new(3));
+        let witness = CoinbaseIsValidWitness::new(&primitive_witness, &block_kernel);
+        assert!(halts(&witness, &block_kernel));
+
+        block_kernel.body.transaction.kernel.coinbase = None;
+        let witness = CoinbaseIsValidWitness::new(&primitive_witness, &block_kernel);
+        assert!(halts(&witness, &block_kernel));
+    }
+
+    #[test]
+    fn crashes_on_excessive_coinbase() {
+        let (mut block_kernel, primitive_witness) =
+            make_mock_block_kernel_and_primitive_witness(None);
+        let witness = CoinbaseIsValidWitness::new(&primitive_witness, &block_kernel);
+        assert!(halts(&witness, &block_kernel));
+
+        let mut wrong_generation = witness.clone();
+        wrong_generation.generation += BFieldElement::new(1);
+        assert!(!halts(&wrong_generation, &block_kernel));
+
+        let subsidy = Block::get_mining_reward(block_kernel.header.height);
+        block_kernel.body.transaction.kernel.coinbase = Some(subsidy + NeptuneCoins::one());
+        let witness = CoinbaseIsValidWitness::new(&primitive_witness, &block_kernel);
+        assert!(!halts(&witness, &block_kernel));
+    }
+}
@@ -0,0 +1,623 @@
+use crate::prelude::{triton_vm, twenty_first};
+
+use tasm_lib::data_type::DataType;
+use tasm_lib::hashing::hash_varlen::HashVarlen;
+use tasm_lib::library::Library;
+use tasm_lib::memory::push_ram_to_stack::PushRamToStack;
+use tasm_lib::memory::FIRST_NON_DETERMINISTICALLY_INITIALIZED_MEMORY_ADDRESS;
+use tasm_lib::structure::tasm_object::TasmObject;
+use tasm_lib::traits::compiled_program::CompiledProgram;
+use triton_vm::instruction::LabelledInstruction;
+use triton_vm::prelude::{triton_asm, BFieldElement, NonDeterminism, PublicInput};
+use twenty_first::amount::u32s::U32s;
+use twenty_first::shared_math::bfield_codec::BFieldCodec;
+
+use super::{authenticate_field, block_hash_from_public_input};
+use crate::models::blockchain::block::block_header::{
+    BlockHeaderField, MINIMUM_DIFFICULTY, TARGET_BLOCK_INTERVAL, TARGET_DIFFICULTY_U32_SIZE,
+};
+use crate::models::blockchain::block::validity::correct_control_parameter_update::{
+    CorrectControlParameterUpdate, CorrectControlParameterUpdateWitness,
+};
+use crate::models::blockchain::block::validity::BlockKernelLeaf;
+use crate::models::blockchain::block::Block;
+
+impl CompiledProgram for CorrectControlParameterUpdate {
+    fn rust_shadow(
+        public_input: &PublicInput,
+        nondeterminism: &NonDeterminism<BFieldElement>,
+    ) -> anyhow::Result<Vec<BFieldElement>> {
+        let block_hash = block_hash_from_public_input(public_input);
+
+        // 1. read witness data
+        let witness = *CorrectControlParameterUpdateWitness::decode_from_memory(
+            &nondeterminism.ram,
+            FIRST_NON_DETERMINISTICALLY_INITIALIZED_MEMORY_ADDRESS,
+        )
+        .unwrap();
+
+        // 2. authenticate the fields of the block, and the fields of the predecessor
+        // against the digest that the block points to
+        let timestamp = BlockKernelLeaf::Header(BlockHeaderField::Timestamp);
+        let difficulty = BlockKernelLeaf::Header(BlockHeaderField::Difficulty);
+        assert_eq!(
+            Some(block_hash),
+            BlockKernelLeaf::Header(BlockHeaderField::PrevBlockDigest).root(
+                &witness.prev_block_digest.encode(),
+                &witness.prev_block_digest_path
+            )
+        );
+        assert_eq!(
+            Some(block_hash),
+            timestamp.root(&witness.timestamp.encode(), &witness.timestamp_path)
+        );
+        assert_eq!(
+            Some(block_hash),
+            difficulty.root(&witness.difficulty.encode(), &witness.difficulty_path)
+        );
+        assert_eq!(
+            Some(witness.prev_block_digest),
+            BlockKernelLeaf::Header(BlockHeaderField::Height).root(
+                &witness.predecessor_height.encode(),
+                &witness.predecessor_height_path
+            )
+        );
+        assert_eq!(
+            Some(witness.prev_block_digest),
+            timestamp.root(
+                &witness.predecessor_timestamp.encode(),
+                &witness.predecessor_timestamp_path
+            )
+        );
+        assert_eq!(
+            Some(witness.prev_block_digest),
+            difficulty.root(
+                &witness.predecessor_difficulty.encode(),
+                &witness.predecessor_difficulty_path
+            )
+        );
+
+        // 3. the block is timestamped after its predecessor
+        let block_interval = (witness.timestamp - witness.predecessor_timestamp).value();
+        assert!(block_interval != 0 && block_interval < 1 << 63);
+
+        // 4. the difficulty follows from the control rule
+        if witness.predecessor_height.value() != 0 {
+            assert_eq!(
+                block_interval.abs_diff(TARGET_BLOCK_INTERVAL) / 100,
+                witness.difficulty_adjustment.value()
+            );
+        }
+        assert_eq!(
+            Block::next_difficulty(
+                witness.predecessor_height.into(),
+                witness.predecessor_timestamp.value(),
+                witness.predecessor_difficulty,
+                witness.timestamp.value(),
+            ),
+            witness.difficulty
+        );
+
+        Ok(vec![])
+    }
+
+    fn code() -> (Vec<LabelledInstruction>, Library) {
+        let mut library = Library::new();
+        let hash_varlen = library.import(Box::new(HashVarlen));
+        let read_digest = library.import(Box::new(PushRamToStack {
+            data_type: DataType::Digest,
+        }));
+
+        // field getters
+        type W = CorrectControlParameterUpdateWitness;
+        let witness_to_prev_block_digest = tasm_lib::field!(W::prev_block_digest);
+        let witness_to_prev_block_digest_with_size =
+            tasm_lib::field_with_size!(W::prev_block_digest);
+        let witness_to_prev_block_digest_path = tasm_lib::field!(W::prev_block_digest_path);
+        let witness_to_timestamp = tasm_lib::field!(W::timestamp);
+        let witness_to_timestamp_with_size = tasm_lib::field_with_size!(W::timestamp);
+        let witness_to_timestamp_path = tasm_lib::field!(W::timestamp_path);
+        let witness_to_difficulty = tasm_lib::field!(W::difficulty);
+        let witness_to_difficulty_with_size = tasm_lib::field_with_size!(W::difficulty);
+        let witness_to_difficulty_path = tasm_lib::field!(W::difficulty_path);
+        let witness_to_predecessor_height = tasm_lib::field!(W::predecessor_height);
+        let witness_to_predecessor_height_with_size =
+            tasm_lib::field_with_size!(W::predecessor_height);
+        let witness_to_predecessor_height_path = tasm_lib::field!(W::predecessor_height_path);
+        let witness_to_predecessor_timestamp = tasm_lib::field!(W::predecessor_timestamp);
+        let witness_to_predecessor_timestamp_with_size =
+            tasm_lib::field_with_size!(W::predecessor_timestamp);
+        let witness_to_predecessor_timestamp_path = tasm_lib::field!(W::predecessor_timestamp_path);
+        let witness_to_predecessor_difficulty = tasm_lib::field!(W::predecessor_difficulty);
+        let witness_to_predecessor_difficulty_with_size =
+            tasm_lib::field_with_size!(W::predecessor_difficulty);
+        let witness_to_predecessor_difficulty_path =
+            tasm_lib::field!(W::predecessor_difficulty_path);
+        let witness_to_difficulty_adjustment = tasm_lib::field!(W::difficulty_adjustment);
+
+        let authenticate_prev_block_digest = authenticate_field(
+            BlockKernelLeaf::Header(BlockHeaderField::PrevBlockDigest),
+            &witness_to_prev_block_digest_with_size,
+            &witness_to_prev_block_digest_path,
+            &hash_varlen,
+        );
+        let authenticate_timestamp = authenticate_field(
+            BlockKernelLeaf::Header(BlockHeaderField::Timestamp),
+            &witness_to_timestamp_with_size,
+            &witness_to_timestamp_path,
+            &hash_varlen,
+        );
+        let authenticate_difficulty = authenticate_field(
+            BlockKernelLeaf::Header(BlockHeaderField::Difficulty),
+            &witness_to_difficulty_with_size,
+            &witness_to_difficulty_path,
+            &hash_varlen,
+        );
+        let authenticate_predecessor_height = authenticate_field(
+            BlockKernelLeaf::Header(BlockHeaderField::Height),
+            &witness_to_predecessor_height_with_size,
+            &witness_to_predecessor_height_path,
+            &hash_varlen,
+        );
+        let authenticate_predecessor_timestamp = authenticate_field(
+            BlockKernelLeaf::Header(BlockHeaderField::Timestamp),
+            &witness_to_predecessor_timestamp_with_size,
+            &witness_to_predecessor_timestamp_path,
+            &hash_varlen,
+        );
+        let authenticate_predecessor_difficulty = authenticate_field(
+            BlockKernelLeaf::Header(BlockHeaderField::Difficulty),
+            &witness_to_predecessor_difficulty_with_size,
+            &witness_to_predecessor_difficulty_path,
+            &hash_varlen,
+        );
+
+        let minus_target_block_interval = (-BFieldElement::new(TARGET_BLOCK_INTERVAL)).value();
+        let minimum_difficulty =
+            U32s::<TARGET_DIFFICULTY_U32_SIZE>::from(MINIMUM_DIFFICULTY).encode();
+
+        // assert that the difficulty is that of the predecessor
+        // BEFORE: _ *witness [block_hash] block_interval is_genesis
+        // AFTER: _ *witness [block_hash] block_interval is_genesis
+        let mut difficulty_is_unchanged = vec![];
+        for i in 0..TARGET_DIFFICULTY_U32_SIZE {
+            difficulty_is_unchanged.extend(triton_asm! {
+                dup 7
+                {&witness_to_difficulty}
+                push {i}
+                add
+                read_mem 1
+                pop 1               // _ *witness [block_hash] block_interval is_genesis new_i
+                dup 8
+                {&witness_to_predecessor_difficulty}
+                push {i}
+                add
+                read_mem 1
+                pop 1               // _ *witness [block_hash] block_interval is_genesis new_i old_i
+                eq
+                assert
+            });
+        }
+
+        // assert that the difficulty is that of the predecessor plus the adjustment
+        // BEFORE: _ *witness [block_hash] block_interval |error| sign adjustment
+        // AFTER: _ *witness [block_hash] block_interval |error| sign adjustment
+        let mut increase_difficulty = triton_asm! {
+            dup 0
+            split                   // _ *witness [block_hash] t |e| s q q_hi q_lo
+            dup 11
+            {&witness_to_predecessor_difficulty}
+            read_mem 1
+            pop 1
+            add
+            split                   // _ *witness [block_hash] t |e| s q q_hi carry sum_0
+            dup 12
+            {&witness_to_difficulty}
+            read_mem 1
+            pop 1
+            eq
+            assert                  // _ *witness [block_hash] t |e| s q q_hi carry
+            add                     // _ *witness [block_hash] t |e| s q carry
+        };
+        for i in 1..TARGET_DIFFICULTY_U32_SIZE {
+            increase_difficulty.extend(triton_asm! {
+                dup 10
+                {&witness_to_predecessor_difficulty}
+                push {i}
+                add
+                read_mem 1
+                pop 1
+                add
+                split               // _ *witness [block_hash] t |e| s q carry sum_i
+                dup 11
+                {&witness_to_difficulty}
+                push {i}
+                add
+                read_mem 1
+                pop 1
+                eq
+                assert              // _ *witness [block_hash] t |e| s q carry
+            });
+        }
+        increase_difficulty.extend(triton_asm! {
+            push 0
+            eq
+            assert                  // _ *witness [block_hash] t |e| s q
+            return
+        });
+
+        // assert that the difficulty is that of the predecessor minus the adjustment, or
+        // the minimum difficulty if that difference would be smaller
+        // BEFORE: _ *witness [block_hash] block_interval |error| sign adjustment
+        // AFTER: _ *witness [block_hash] block_interval |error| sign adjustment
+        let mut decrease_difficulty = triton_asm! {
+            dup 0
+            split                   // _ *witness [block_hash] t |e| s q q_hi q_lo
+            push -1
+            mul
+            dup 11
+            {&witness_to_predecessor_difficulty}
+            read_mem 1
+            pop 1
+            add
+            push 4294967296
+            add
+            split                   // _ *witness [block_hash] t |e| s q q_hi no_borrow d_0
+            swap 2
+            push -1
+            mul
+            add
+            push -1
+            add                     // _ *witness [block_hash] t |e| s q d_0 minus_subtrahend
+        };
+        for i in 1..TARGET_DIFFICULTY_U32_SIZE {
+            let witness_depth = 10 + i;
+            decrease_difficulty.extend(triton_asm! {
+                dup {witness_depth}
+                {&witness_to_predecessor_difficulty}
+                push {i}
+                add
+                read_mem 1
+                pop 1
+                add
+                push 4294967296
+                add
+                split               // _ *witness [block_hash] t |e| s q d_0 .. no_borrow d_i
+                swap 1
+                push -1
+                add                 // _ *witness [block_hash] t |e| s q d_0 .. d_i minus_subtrahend
+            });
+        }
+        decrease_difficulty.extend(triton_asm! {
+            push 1
+            add                     // _ *witness [block_hash] t |e| s q d_0 d_1 d_2 d_3 d_4 no_borrow
+            dup 1
+            dup 3
+            dup 5
+            dup 7
+            add
+            add
+            add
+            push 0
+            eq                      // _ *witness [block_hash] t |e| s q d_0 d_1 d_2 d_3 d_4 no_borrow high_limbs_are_zero
+            dup 6
+            dup 0
+            push -1
+            add
+            mul
+            push 0
+            eq
+            mul                     // _ *witness [block_hash] t |e| s q d_0 d_1 d_2 d_3 d_4 no_borrow (d < 2 = minimum)
+            push -1
+            mul
+            push 1
+            add
+            mul
+            push -1
+            mul
+            push 1
+            add                     // _ *witness [block_hash] t |e| s q d_0 d_1 d_2 d_3 d_4 clamp
+        });
+        for i in (0..TARGET_DIFFICULTY_U32_SIZE).rev() {
+            let minimum_difficulty_limb = minimum_difficulty[i];
+            let witness_depth = 11 + i;
+            decrease_difficulty.extend(triton_asm! {
+                swap 1              // _ *witness [block_hash] t |e| s q d_0 .. d_{i-1} clamp d_i
+                dup 1
+                push -1
+                mul
+                push 1
+                add
+                mul
+                dup 1
+                push {minimum_difficulty_limb}
+                mul
+                add                 // _ *witness [block_hash] t |e| s q d_0 .. d_{i-1} clamp expected_i
+                dup {witness_depth}
+                {&witness_to_difficulty}
+                push {i}
+                add
+                read_mem 1
+                pop 1
+                eq
+                assert              // _ *witness [block_hash] t |e| s q d_0 .. d_{i-1} clamp
+            });
+        }
+        decrease_difficulty.extend(triton_asm! {
+            pop 1                   // _ *witness [block_hash] t |e| s q
+            return
+        });
+
+        let unchanged = "tasm_neptune_block_correct_control_parameter_update_unchanged";
+        let controlled = "tasm_neptune_block_correct_control_parameter_update_controlled";
+        let increase = "tasm_neptune_block_correct_control_parameter_update_increase";
+        let decrease = "tasm_neptune_block_correct_control_parameter_update_decrease";
+
+        let code = triton_asm! {
+
+        // 1. Witness was already loaded into memory, just point to it
+        push {FIRST_NON_DETERMINISTICALLY_INITIALIZED_MEMORY_ADDRESS} // _ *witness
+        read_io 5                                   // _ *witness [block_hash]
+
+        // 2. authenticate the fields of the block
+        dup 5
+        {&authenticate_prev_block_digest}           // _ *witness [block_hash]
+        dup 5
+        {&authenticate_timestamp}                   // _ *witness [block_hash]
+        dup 5
+        {&authenticate_difficulty}                  // _ *witness [block_hash]
+
+        // 3. authenticate the fields of the predecessor
+        dup 5
+        {&witness_to_prev_block_digest}
+        call {read_digest}                          // _ *witness [block_hash] [prev_block_digest]
+        dup 10
+        {&authenticate_predecessor_height}          // _ *witness [block_hash] [prev_block_digest]
+        dup 10
+        {&authenticate_predecessor_timestamp}       // _ *witness [block_hash] [prev_block_digest]
+        dup 10
+        {&authenticate_predecessor_difficulty}      // _ *witness [block_hash] [prev_block_digest]
+        pop 5                                       // _ *witness [block_hash]
+
+        // 4. assert that the block is timestamped after its predecessor, that is, that the
+        // block interval is nonzero and less than 2^63 in the field
+        dup 5
+        {&witness_to_timestamp}
+        read_mem 1
+        pop 1                                       // _ *witness [block_hash] timestamp
+        dup 6
+        {&witness_to_predecessor_timestamp}
+        read_mem 1
+        pop 1                                       // _ *witness [block_hash] timestamp predecessor_timestamp
+        push -1
+        mul
+        add                                         // _ *witness [block_hash] block_interval
+        dup 0
+        push 0 eq
+        push 0 eq
+        assert
+        dup 0
+        split
+        pop 1
+        push 2147483648
+        and
+        push 0 eq
+        assert                                      // _ *witness [block_hash] block_interval
+
+        // 5. the difficulty is unchanged after the genesis block, and follows from the
+        // control rule otherwise
+        dup 6
+        {&witness_to_predecessor_height}
+        read_mem 1
+        pop 1
+        push 0 eq                                   // _ *witness [block_hash] block_interval is_genesis
+        dup 0
+        skiz call {unchanged}                       // _ *witness [block_hash] block_interval is_genesis
+        push 0 eq
+        skiz call {controlled}                      // _ *witness [block_hash] block_interval
+
+        pop 1
+        pop 5
+        pop 1                                       // _
+
+        halt
+
+        // BEFORE: _ *witness [block_hash] block_interval is_genesis
+        // AFTER: _ *witness [block_hash] block_interval is_genesis
+        {unchanged}:
+            {&difficulty_is_unchanged}
+            return
+
+        // BEFORE: _ *witness [block_hash] block_interval
+        // AFTER: _ *witness [block_hash] block_interval
+        {controlled}:
+            // the sign of the error tells whether the difficulty goes up or down
+            dup 0
+            push {minus_target_block_interval}
+            add                                     // _ *witness [block_hash] t e
+            dup 0
+            split
+            pop 1
+            push 2147483648
+            and
+            push 0 eq                               // _ *witness [block_hash] t e (e >= 0)
+            dup 0
+            push 2 mul
+            push -1 add
+            dup 2
+            mul                                     // _ *witness [block_hash] t e s |e|
+            swap 2
+            pop 1                                   // _ *witness [block_hash] t |e| s
+
+            // the adjustment is the absolute error divided by 100
+            dup 8
+            {&witness_to_difficulty_adjustment}
+            read_mem 1
+            pop 1                                   // _ *witness [block_hash] t |e| s q
+            dup 0
+            split
+            pop 1
+            push 4261412864
+            and
+            push 0 eq
+            assert
+            dup 2
+            dup 1
+            push -100
+            mul
+            add                                     // _ *witness [block_hash] t |e| s q (|e| - 100 * q)
+            dup 0
+            split
+            pop 1
+            push 0 eq
+            assert
+            push -1
+            mul
+            push 99
+            add
+            split
+            pop 1
+            push 0 eq
+            assert                                  // _ *witness [block_hash] t |e| s q
+
+            dup 1
+            skiz call {decrease}
+            dup 1
+            push 0 eq
+            skiz call {increase}                    // _ *witness [block_hash] t |e| s q
+
+            pop 3                                   // _ *witness [block_hash] t
+            return
+
+        {increase}:
+            {&increase_difficulty}
+
+        {decrease}:
+            {&decrease_difficulty}
+        };
+
+        (code, library)
+    }
+
+    fn crash_conditions() -> Vec<String> {
+        vec![
+            "a field does not match the block kernel MAST hash".to_string(),
+            "the block is not timestamped after its predecessor".to_string(),
+            "the difficulty adjustment is not the block interval error divided by 100".to_string(),
+            "the difficulty does not follow from the control rule".to_string(),
+        ]
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::models::blockchain::block::block_kernel::BlockKernel;
+    use crate::models::blockchain::block::validity::{block_claim_input, BlockPrimitiveWitness};
+    use crate::models::consensus::SecretWitness;
+    use crate::models::state::wallet::WalletSecret;
+    use crate::tests::shared::{make_mock_block, make_mock_block_kernel_and_primitive_witness};
+    use tasm_lib::traits::compiled_program::test_rust_shadow;
+
+    fn halts(witness: &CorrectControlParameterUpdateWitness, block_kernel: &BlockKernel) -> bool {
+        CorrectControlParameterUpdate::program()
+            .run(
+                PublicInput::new(block_claim_input(block_kernel)),
+                witness.nondeterminism(),
+            )
+            .is_ok()
+    }
+
+    #[test]
+    fn test_graceful_halt() {
+        let (block_kernel, primitive_witness) = make_mock_block_kernel_and_primitive_witness(None);
+        let witness = CorrectControlParameterUpdateWitness::new(&primitive_witness, &block_kernel);
+
+        let output = CorrectControlParameterUpdate::program()
+            .run(
+                PublicInput::new(block_claim_input(&block_kernel)),
+                witness.nondeterminism(),
+            )
+            .unwrap();
+        assert!(output.is_empty());
+    }
+
+    #[test]
+    fn program_is_deterministic() {
+        let program = CorrectControlParameterUpdate::program();
+        let other_program = CorrectControlParameterUpdate::program();
+        assert_eq!(program, other_program);
+    }
+
+    #[test]
+    fn tasm_matches_rust() {
+        for block_interval in [
+            1,
+            TARGET_BLOCK_INTERVAL / 2,
+            TARGET_BLOCK_INTERVAL,
+            TARGET_BLOCK_INTERVAL + 150,
+            3 * TARGET_BLOCK_INTERVAL,
+        ] {
+            let (block_kernel, primitive_witness) =
+                make_mock_block_kernel_and_primitive_witness(Some(block_interval));
+            let witness =
+                CorrectControlParameterUpdateWitness::new(&primitive_witness, &block_kernel);
+
+            test_rust_shadow::<CorrectControlParameterUpdate>(
+                &PublicInput::new(block_claim_input(&block_kernel)),
+                &witness.nondeterminism(),
+            );
+        }
+    }
+
+    #[test]
+    fn difficulty_is_unchanged_after_genesis() {
+        let genesis_block = Block::genesis_block();
+        let address = WalletSecret::new_random()
+            .nth_generation_spending_key(0)
+            .to_address();
+        let (block_1, _, _) = make_mock_block(&genesis_block, None, address);
+        let primitive_witness = BlockPrimitiveWitness {
+            predecessor: genesis_block.kernel,
+        };
+        let witness =
+            CorrectControlParameterUpdateWitness::new(&primitive_witness, &block_1.kernel);
+        assert!(halts(&witness, &block_1.kernel));
+
+        let mut other_difficulty = block_1.kernel.clone();
+        other_difficulty.header.difficulty = other_difficulty.header.difficulty + 1.into();
+        let witness =
+            CorrectControlParameterUpdateWitness::new(&primitive_witness, &other_difficulty);
+        assert!(!halts(&witness, &other_difficulty));
+    }
+
+    #[test]
+    fn crashes_on_wrong_difficulty() {
+        for block_interval in [TARGET_BLOCK_INTERVAL / 2, 3 * TARGET_BLOCK_INTERVAL] {
+            let (block_kernel, primitive_witness) =
+                make_mock_block_kernel_and_primitive_witness(Some(block_interval));
+            let witness =
+                CorrectControlParameterUpdateWitness::new(&primitive_witness, &block_kernel);
+            assert!(halts(&witness, &block_kernel));
+
+            let mut wrong_adjustment = witness.clone();
+            wrong_adjustment.difficulty_adjustment += BFieldElement::new(1);
+            assert!(!halts(&wrong_adjustment, &block_kernel));
+
+            let mut other_difficulty = block_kernel.clone();
+            other_difficulty.header.difficulty = other_difficulty.header.difficulty + 1.into();
+            let witness =
+                CorrectControlParameterUpdateWitness::new(&primitive_witness, &other_difficulty);
+            assert!(!halts(&witness, &other_difficulty));
+        }
+    }
+
+    #[test]
+    fn crashes_on_block_from_the_past() {
+        let (mut block_kernel, primitive_witness) =
+            make_mock_block_kernel_and_primitive_witness(None);
+        block_kernel.header.timestamp = primitive_witness.predecessor.header.timestamp;
+        let witness = CorrectControlParameterUpdateWitness::new(&primitive_witness, &block_kernel);
+        assert!(!halts(&witness, &block_kernel));
+    }
+}
@@ -0,0 +1,245 @@
+use crate::prelude::{triton_vm, twenty_first};
+
+use tasm_lib::data_type::DataType;
+use tasm_lib::hashing::hash_varlen::HashVarlen;
+use tasm_lib::library::Library;
+use tasm_lib::memory::push_ram_to_stack::PushRamToStack;
+use tasm_lib::memory::FIRST_NON_DETERMINISTICALLY_INITIALIZED_MEMORY_ADDRESS;
+use tasm_lib::structure::tasm_object::TasmObject;
+use tasm_lib::traits::compiled_program::CompiledProgram;
+use triton_vm::instruction::LabelledInstruction;
+use triton_vm::prelude::{triton_asm, BFieldElement, NonDeterminism, PublicInput};
+use twenty_first::shared_math::bfield_codec::BFieldCodec;
+use twenty_first::util_types::mmr::mmr_trait::Mmr;
+
+use super::verify_mmr_append::VerifyMmrAppend;
+use super::{authenticate_field, block_hash_from_public_input};
+use crate::models::blockchain::block::block_body::BlockBodyField;
+use crate::models::blockchain::block::block_header::BlockHeaderField;
+use crate::models::blockchain::block::validity::correct_mmr_update::{
+    CorrectMmrUpdate, CorrectMmrUpdateWitness,
+};
+use crate::models::blockchain::block::validity::BlockKernelLeaf;
+
+impl CompiledProgram for CorrectMmrUpdate {
+    fn rust_shadow(
+        public_input: &PublicInput,
+        nondeterminism: &NonDeterminism<BFieldElement>,
+    ) -> anyhow::Result<Vec<BFieldElement>> {
+        let block_hash = block_hash_from_public_input(public_input);
+
+        // 1. read witness data
+        let witness = *CorrectMmrUpdateWitness::decode_from_memory(
+            &nondeterminism.ram,
+            FIRST_NON_DETERMINISTICALLY_INITIALIZED_MEMORY_ADDRESS,
+        )
+        .unwrap();
+
+        // 2. authenticate the fields of the block, and the block MMR of the predecessor
+        // against the digest that the block points to
+        let block_mmr_accumulator = BlockKernelLeaf::Body(BlockBodyField::BlockMmrAccumulator);
+        assert_eq!(
+            Some(block_hash),
+            BlockKernelLeaf::Header(BlockHeaderField::PrevBlockDigest).root(
+                &witness.prev_block_digest.encode(),
+                &witness.prev_block_digest_path
+            )
+        );
+        assert_eq!(
+            Some(block_hash),
+            block_mmr_accumulator.root(
+                &witness.block_mmr_accumulator.encode(),
+                &witness.block_mmr_accumulator_path
+            )
+        );
+        assert_eq!(
+            Some(witness.prev_block_digest),
+            block_mmr_accumulator.root(
+                &witness.predecessor_block_mmr_accumulator.encode(),
+                &witness.predecessor_block_mmr_accumulator_path
+            )
+        );
+
+        // 3. the block MMR is that of the predecessor with the predecessor appended
+        let mut block_mmr_accumulator = witness.predecessor_block_mmr_accumulator.clone();
+        block_mmr_accumulator.append(witness.prev_block_digest);
+        assert_eq!(block_mmr_accumulator, witness.block_mmr_accumulator);
+
+        Ok(vec![])
+    }
+
+    fn code() -> (Vec<LabelledInstruction>, Library) {
+        let mut library = Library::new();
+        let hash_varlen = library.import(Box::new(HashVarlen));
+        let read_digest = library.import(Box::new(PushRamToStack {
+            data_type: DataType::Digest,
+        }));
+        let verify_mmr_append = library.import(Box::new(VerifyMmrAppend));
+
+        // field getters
+        type W = CorrectMmrUpdateWitness;
+        let witness_to_prev_block_digest = tasm_lib::field!(W::prev_block_digest);
+        let witness_to_prev_block_digest_with_size =
+            tasm_lib::field_with_size!(W::prev_block_digest);
+        let witness_to_prev_block_digest_path = tasm_lib::field!(W::prev_block_digest_path);
+        let witness_to_block_mmr_accumulator = tasm_lib::field!(W::block_mmr_accumulator);
+        let witness_to_block_mmr_accumulator_with_size =
+            tasm_lib::field_with_size!(W::block_mmr_accumulator);
+        let witness_to_block_mmr_accumulator_path = tasm_lib::field!(W::block_mmr_accumulator_path);
+        let witness_to_predecessor_block_mmr_accumulator =
+            tasm_lib::field!(W::predecessor_block_mmr_accumulator);
+        let witness_to_predecessor_block_mmr_accumulator_with_size =
+            tasm_lib::field_with_size!(W::predecessor_block_mmr_accumulator);
+        let witness_to_predecessor_block_mmr_accumulator_path =
+            tasm_lib::field!(W::predecessor_block_mmr_accumulator_path);
+
+        let authenticate_prev_block_digest = authenticate_field(
+            BlockKernelLeaf::Header(BlockHeaderField::PrevBlockDigest),
+            &witness_to_prev_block_digest_with_size,
+            &witness_to_prev_block_digest_path,
+            &hash_varlen,
+        );
+        let authenticate_block_mmr_accumulator = authenticate_field(
+            BlockKernelLeaf::Body(BlockBodyField::BlockMmrAccumulator),
+            &witness_to_block_mmr_accumulator_with_size,
+            &witness_to_block_mmr_accumulator_path,
+            &hash_varlen,
+        );
+        let authenticate_predecessor_block_mmr_accumulator = authenticate_field(
+            BlockKernelLeaf::Body(BlockBodyField::BlockMmrAccumulator),
+            &witness_to_predecessor_block_mmr_accumulator_with_size,
+            &witness_to_predecessor_block_mmr_accumulator_path,
+            &hash_varlen,
+        );
+
+        let code = triton_asm! {
+
+        // 1. Witness was already loaded into memory, just point to it
+        push {FIRST_NON_DETERMINISTICALLY_INITIALIZED_MEMORY_ADDRESS} // _ *witness
+        read_io 5                                       // _ *witness [block_hash]
+
+        // 2. authenticate the fields of the block
+        dup 5
+        {&authenticate_prev_block_digest}               // _ *witness [block_hash]
+        dup 5
+        {&authenticate_block_mmr_accumulator}           // _ *witness [block_hash]
+
+        // 3. authenticate the block MMR of the predecessor
+        dup 5
+        {&witness_to_prev_block_digest}
+        call {read_digest}                              // _ *witness [block_hash] [prev_block_digest]
+        dup 10
+        {&authenticate_predecessor_block_mmr_accumulator} // _ *witness [block_hash] [prev_block_digest]
+
+        // 4. assert that the block MMR is that of the predecessor with the predecessor
+        // appended
+        dup 10
+        {&witness_to_predecessor_block_mmr_accumulator}
+        dup 11
+        {&witness_to_block_mmr_accumulator}             // _ *witness [block_hash] [prev_block_digest] *predecessor_mmra *mmra
+        call {verify_mmr_append}                        // _ *witness [block_hash]
+
+        pop 5
+        pop 1                                           // _
+
+        halt
+        };
+
+        (code, library)
+    }
+
+    fn crash_conditions() -> Vec<String> {
+        vec![
+            "a field does not match the block kernel MAST hash".to_string(),
+            "the block MMR is not that of the predecessor with the predecessor appended"
+                .to_string(),
+        ]
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::models::blockchain::block::block_kernel::BlockKernel;
+    use crate::models::blockchain::block::validity::block_claim_input;
+    use crate::models::consensus::SecretWitness;
+    use crate::tests::shared::make_mock_block_kernel_and_primitive_witness;
+    use rand::random;
+    use tasm_lib::traits::compiled_program::test_rust_shadow;
+
+    fn halts(witness: &CorrectMmrUpdateWitness, block_kernel: &BlockKernel) -> bool {
+        CorrectMmrUpdate::program()
+            .run(
+                PublicInput::new(block_claim_input(block_kernel)),
+                witness.nondeterminism(),
+            )
+            .is_ok()
+    }
+
+    #[test]
+    fn test_graceful_halt() {
+        let (block_kernel, primitive_witness) = make_mock_block_kernel_and_primitive_witness(None);
+        let witness = CorrectMmrUpdateWitness::new(&primitive_witness, &block_kernel);
+
+        let output = CorrectMmrUpdate::program()
+            .run(
+                PublicInput::new(block_claim_input(&block_kernel)),
+                witness.nondeterminism(),
+            )
+            .unwrap();
+        assert!(output.is_empty());
+    }
+
+    #[test]
+    fn program_is_deterministic() {
+        let program = CorrectMmrUpdate::program();
+        let other_program = CorrectMmrUpdate::program();
+        assert_eq!(program, other_program);
+    }
+
+    #[test]
+    fn tasm_matches_rust() {
+        let (block_kernel, primitive_witness) = make_mock_block_kernel_and_primitive_witness(None);
+        let witness = CorrectMmrUpdateWitness::new(&primitive_witness, &block_kernel);
+
+        test_rust_shadow::<CorrectMmrUpdate>(
+            &PublicInput::new(block_claim_input(&block_kernel)),
+            &witness.nondeterminism(),
+        );
+    }
+
+    #[test]
+    fn crashes_on_wrong_block_mmr() {
+        let (block_kernel, primitive_witness) = make_mock_block_kernel_and_primitive_witness(None);
+        let witness = CorrectMmrUpdateWitness::new(&primitive_witness, &block_kernel);
+        assert!(halts(&witness, &block_kernel));
+
+        // another leaf appended
+        let mut wrong_leaf = block_kernel.clone();
+        let mut block_mmr_accumulator = primitive_witness
+            .predecessor
+            .body
+            .block_mmr_accumulator
+            .clone();
+        block_mmr_accumulator.append(random());
+        wrong_leaf.body.block_mmr_accumulator = block_mmr_accumulator;
+        let witness = CorrectMmrUpdateWitness::new(&primitive_witness, &wrong_leaf);
+        assert!(!halts(&witness, &wrong_leaf));
+
+        // one leaf too many
+        let mut extra_leaf = block_kernel.clone();
+        extra_leaf.body.block_mmr_accumulator.append(random());
+        let witness = CorrectMmrUpdateWitness::new(&primitive_witness, &extra_leaf);
+        assert!(!halts(&witness, &extra_leaf));
+
+        // not updated at all
+        let mut not_updated = block_kernel.clone();
+        not_updated.body.block_mmr_accumulator = primitive_witness
+            .predecessor
+            .body
+            .block_mmr_accumulator
+            .clone();
+        let witness = CorrectMmrUpdateWitness::new(&primitive_witness, &not_updated);
+        assert!(!halts(&witness, &not_updated));
+    }
+}
@@ -0,0 +1,396 @@
+use crate::prelude::{triton_vm, twenty_first};
+
+use tasm_lib::data_type::DataType;
+use tasm_lib::hashing::hash_varlen::HashVarlen;
+use tasm_lib::library::Library;
+use tasm_lib::list::contiguous_list::get_pointer_list::GetPointerList;
+use tasm_lib::list::ListType;
+use tasm_lib::memory::push_ram_to_stack::PushRamToStack;
+use tasm_lib::memory::FIRST_NON_DETERMINISTICALLY_INITIALIZED_MEMORY_ADDRESS;
+use tasm_lib::structure::tasm_object::TasmObject;
+use tasm_lib::traits::compiled_program::CompiledProgram;
+use triton_vm::instruction::LabelledInstruction;
+use triton_vm::prelude::{triton_asm, BFieldElement, NonDeterminism, PublicInput};
+use twenty_first::shared_math::bfield_codec::BFieldCodec;
+use twenty_first::util_types::mmr::mmr_trait::Mmr;
+
+use super::verify_mmr_append::VerifyMmrAppend;
+use super::{authenticate_field, block_hash_from_public_input};
+use crate::models::blockchain::block::block_body::BlockBodyField;
+use crate::models::blockchain::block::block_header::BlockHeaderField;
+use crate::models::blockchain::block::validity::correct_mutator_set_update::{
+    CorrectMutatorSetUpdate, CorrectMutatorSetUpdateWitness,
+};
+use crate::models::blockchain::block::validity::BlockKernelLeaf;
+use crate::models::blockchain::transaction::transaction_kernel::TransactionKernelField;
+
+/// The offset of the AOCL in the encoding of a mutator set accumulator, past the length
+/// of its kernel and the length of the AOCL itself.
+const AOCL_OFFSET: usize = 2;
+
+impl CompiledProgram for CorrectMutatorSetUpdate {
+    fn rust_shadow(
+        public_input: &PublicInput,
+        nondeterminism: &NonDeterminism<BFieldElement>,
+    ) -> anyhow::Result<Vec<BFieldElement>> {
+        let block_hash = block_hash_from_public_input(public_input);
+
+        // 1. read witness data
+        let witness = *CorrectMutatorSetUpdateWitness::decode_from_memory(
+            &nondeterminism.ram,
+            FIRST_NON_DETERMINISTICALLY_INITIALIZED_MEMORY_ADDRESS,
+        )
+        .unwrap();
+
+        // 2. authenticate the fields of the block, and the mutator set accumulator of the
+        // predecessor against the digest that the block points to
+        let mutator_set_accumulator = BlockKernelLeaf::Body(BlockBodyField::MutatorSetAccumulator);
+        assert_eq!(
+            Some(block_hash),
+            BlockKernelLeaf::Header(BlockHeaderField::PrevBlockDigest).root(
+                &witness.prev_block_digest.encode(),
+                &witness.prev_block_digest_path
+            )
+        );
+        assert_eq!(
+            Some(block_hash),
+            mutator_set_accumulator.root(
+                &witness.mutator_set_accumulator.encode(),
+                &witness.mutator_set_accumulator_path
+            )
+        );
+        assert_eq!(
+            Some(block_hash),
+            BlockKernelLeaf::TransactionKernel(TransactionKernelField::OutputUtxos)
+                .root(&witness.outputs.encode(), &witness.outputs_path)
+        );
+        assert_eq!(
+            Some(witness.prev_block_digest),
+            mutator_set_accumulator.root(
+                &witness.predecessor_mutator_set_accumulator.encode(),
+                &witness.predecessor_mutator_set_accumulator_path
+            )
+        );
+
+        // 3. the AOCL accumulators go from the AOCL of the predecessor to that of the
+        // block, adding one output at a time
+        let aocl_accumulators = &witness.aocl_accumulators;
+        assert_eq!(witness.outputs.len() + 1, aocl_accumulators.len());
+        assert_eq!(
+            witness.predecessor_mutator_set_accumulator.kernel.aocl,
+            aocl_accumulators[0]
+        );
+        assert_eq!(
+            witness.mutator_set_accumulator.kernel.aocl,
+            aocl_accumulators[witness.outputs.len()]
+        );
+        for (i, addition_record) in witness.outputs.iter().enumerate() {
+            let mut aocl = aocl_accumulators[i].clone();
+            aocl.append(addition_record.canonical_commitment);
+            assert_eq!(aocl, aocl_accumulators[i + 1]);
+        }
+
+        Ok(vec![])
+    }
+
+    fn code() -> (Vec<LabelledInstruction>, Library) {
+        let mut library = Library::new();
+        let hash_varlen = library.import(Box::new(HashVarlen));
+        let read_digest = library.import(Box::new(PushRamToStack {
+            data_type: DataType::Digest,
+        }));
+        let get_pointer_list = library.import(Box::new(GetPointerList {
+            output_list_type: ListType::Unsafe,
+        }));
+        let verify_mmr_append = library.import(Box::new(VerifyMmrAppend));
+
+        // field getters
+        type W = CorrectMutatorSetUpdateWitness;
+        let witness_to_prev_block_digest = tasm_lib::field!(W::prev_block_digest);
+        let witness_to_prev_block_digest_with_size =
+            tasm_lib::field_with_size!(W::prev_block_digest);
+        let witness_to_prev_block_digest_path = tasm_lib::field!(W::prev_block_digest_path);
+        let witness_to_mutator_set_accumulator = tasm_lib::field!(W::mutator_set_accumulator);
+        let witness_to_mutator_set_accumulator_with_size =
+            tasm_lib::field_with_size!(W::mutator_set_accumulator);
+        let witness_to_mutator_set_accumulator_path =
+            tasm_lib::field!(W::mutator_set_accumulator_path);
+        let witness_to_predecessor_mutator_set_accumulator =
+            tasm_lib::field!(W::predecessor_mutator_set_accumulator);
+        let witness_to_predecessor_mutator_set_accumulator_with_size =
+            tasm_lib::field_with_size!(W::predecessor_mutator_set_accumulator);
+        let witness_to_predecessor_mutator_set_accumulator_path =
+            tasm_lib::field!(W::predecessor_mutator_set_accumulator_path);
+        let witness_to_outputs = tasm_lib::field!(W::outputs);
+        let witness_to_outputs_with_size = tasm_lib::field_with_size!(W::outputs);
+        let witness_to_outputs_path = tasm_lib::field!(W::outputs_path);
+        let witness_to_aocl_accumulators = tasm_lib::field!(W::aocl_accumulators);
+
+        let authenticate_prev_block_digest = authenticate_field(
+            BlockKernelLeaf::Header(BlockHeaderField::PrevBlockDigest),
+            &witness_to_prev_block_digest_with_size,
+            &witness_to_prev_block_digest_path,
+            &hash_varlen,
+        );
+        let authenticate_mutator_set_accumulator = authenticate_field(
+            BlockKernelLeaf::Body(BlockBodyField::MutatorSetAccumulator),
+            &witness_to_mutator_set_accumulator_with_size,
+            &witness_to_mutator_set_accumulator_path,
+            &hash_varlen,
+        );
+        let authenticate_outputs = authenticate_field(
+            BlockKernelLeaf::TransactionKernel(TransactionKernelField::OutputUtxos),
+            &witness_to_outputs_with_size,
+            &witness_to_outputs_path,
+            &hash_varlen,
+        );
+        let authenticate_predecessor_mutator_set_accumulator = authenticate_field(
+            BlockKernelLeaf::Body(BlockBodyField::MutatorSetAccumulator),
+            &witness_to_predecessor_mutator_set_accumulator_with_size,
+            &witness_to_predecessor_mutator_set_accumulator_path,
+            &hash_varlen,
+        );
+
+        // BEFORE: _ *aocl
+        // AFTER: _ [aocl_hash]
+        let hash_aocl = triton_asm! {
+            dup 0
+            push -1
+            add
+            read_mem 1
+            pop 1                   // _ *aocl aocl_size
+            call {hash_varlen}      // _ [aocl_hash]
+        };
+
+        let append_outputs = "tasm_neptune_block_correct_mutator_set_update_append_outputs";
+
+        let code = triton_asm! {
+
+        // 1. Witness was already loaded into memory, just point to it
+        push {FIRST_NON_DETERMINISTICALLY_INITIALIZED_MEMORY_ADDRESS} // _ *witness
+        read_io 5                                   // _ *witness [block_hash]
+
+        // 2. authenticate the fields of the block
+        dup 5
+        {&authenticate_prev_block_digest}           // _ *witness [block_hash]
+        dup 5
+        {&authenticate_mutator_set_accumulator}     // _ *witness [block_hash]
+        dup 5
+        {&authenticate_outputs}                     // _ *witness [block_hash]
+
+        // 3. authenticate the mutator set accumulator of the predecessor
+        dup 5
+        {&witness_to_prev_block_digest}
+        call {read_digest}                          // _ *witness [block_hash] [prev_block_digest]
+        dup 10
+        {&authenticate_predecessor_mutator_set_accumulator}
+        pop 5                                       // _ *witness [block_hash]
+
+        // 4. assert that there is one AOCL accumulator more than there are outputs
+        dup 5
+        {&witness_to_aocl_accumulators}
+        call {get_pointer_list}                     // _ *witness [block_hash] *[*aocl]
+        dup 6
+        {&witness_to_outputs}                       // _ *witness [block_hash] *[*aocl] *outputs
+        dup 1
+        read_mem 1
+        pop 1                                       // _ *witness [block_hash] *[*aocl] *outputs num_aocls
+        dup 1
+        read_mem 1
+        pop 1                                       // _ *witness [block_hash] *[*aocl] *outputs num_aocls num_outputs
+        swap 1
+        push -1
+        add
+        dup 1
+        eq
+        assert                                      // _ *witness [block_hash] *[*aocl] *outputs num_outputs
+
+        // 5. assert that the first AOCL accumulator is that of the predecessor
+        dup 2
+        push 1
+        add
+        read_mem 1
+        pop 1                                       // _ *witness [block_hash] *[*aocl] *outputs num_outputs *aocl_0
+        {&hash_aocl}                                // _ *witness [block_hash] *[*aocl] *outputs num_outputs [aocl_0_hash]
+        dup 13
+        {&witness_to_predecessor_mutator_set_accumulator}
+        push {AOCL_OFFSET}
+        add
+        {&hash_aocl}                                // _ *witness [block_hash] *[*aocl] *outputs num_outputs [aocl_0_hash] [predecessor_aocl_hash]
+        assert_vector
+        pop 5                                       // _ *witness [block_hash] *[*aocl] *outputs num_outputs
+
+        // 6. assert that the last AOCL accumulator is that of the block
+        dup 2
+        push 1
+        add
+        dup 1
+        add
+        read_mem 1
+        pop 1                                       // _ *witness [block_hash] *[*aocl] *outputs num_outputs *aocl_last
+        {&hash_aocl}                                // _ *witness [block_hash] *[*aocl] *outputs num_outputs [aocl_last_hash]
+        dup 13
+        {&witness_to_mutator_set_accumulator}
+        push {AOCL_OFFSET}
+        add
+        {&hash_aocl}                                // _ *witness [block_hash] *[*aocl] *outputs num_outputs [aocl_last_hash] [aocl_hash]
+        assert_vector
+        pop 5                                       // _ *witness [block_hash] *[*aocl] *outputs num_outputs
+
+        // 7. assert that every AOCL accumulator follows from the previous one by adding
+        // the next output
+        push 0                                      // _ *witness [block_hash] *[*aocl] *outputs num_outputs 0
+        call {append_outputs}                       // _ *witness [block_hash] *[*aocl] *outputs num_outputs num_outputs
+
+        pop 4
+        pop 5
+        pop 1                                       // _
+
+        halt
+
+        // BEFORE: _ *[*aocl] *outputs num_outputs i
+        // AFTER: _ *[*aocl] *outputs num_outputs num_outputs
+        {append_outputs}:
+            dup 1 dup 1 eq              // _ *[*aocl] *outputs num_outputs i (num_outputs == i)
+            skiz return                 // _ *[*aocl] *outputs num_outputs i
+
+            dup 2
+            push 5 add
+            dup 1
+            push 5 mul
+            add
+            read_mem 5
+            pop 1                       // _ *[*aocl] *outputs num_outputs i [canonical_commitment]
+
+            dup 8
+            push 1 add
+            dup 6
+            add
+            read_mem 1
+            pop 1                       // _ *[*aocl] *outputs num_outputs i [canonical_commitment] *aocl_i
+            dup 9
+            push 2 add
+            dup 7
+            add
+            read_mem 1
+            pop 1                       // _ *[*aocl] *outputs num_outputs i [canonical_commitment] *aocl_i *aocl_{i+1}
+            call {verify_mmr_append}    // _ *[*aocl] *outputs num_outputs i
+
+            push 1 add                  // _ *[*aocl] *outputs num_outputs (i + 1)
+            recurse
+        };
+
+        (code, library)
+    }
+
+    fn crash_conditions() -> Vec<String> {
+        vec![
+            "a field does not match the block kernel MAST hash".to_string(),
+            "the AOCL accumulators do not start at the AOCL of the predecessor".to_string(),
+            "the AOCL accumulators do not end at the AOCL of the block".to_string(),
+            "an AOCL accumulator does not follow from the previous one and the next output"
+                .to_string(),
+        ]
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::models::blockchain::block::block_kernel::BlockKernel;
+    use crate::models::blockchain::block::validity::block_claim_input;
+    use crate::models::consensus::SecretWitness;
+    use crate::tests::shared::make_mock_block_kernel_and_primitive_witness;
+    use rand::random;
+    use tasm_lib::traits::compiled_program::test_rust_shadow;
+
+    fn halts(witness: &CorrectMutatorSetUpdateWitness, block_kernel: &BlockKernel) -> bool {
+        CorrectMutatorSetUpdate::program()
+            .run(
+                PublicInput::new(block_claim_input(block_kernel)),
+                witness.nondeterminism(),
+            )
+            .is_ok()
+    }
+
+    #[test]
+    fn aocl_offset_matches_encoding() {
+        let (block_kernel, _) = make_mock_block_kernel_and_primitive_witness(None);
+        let mutator_set_accumulator = block_kernel.body.mutator_set_accumulator.encode();
+        let aocl = block_kernel
+            .body
+            .mutator_set_accumulator
+            .kernel
+            .aocl
+            .encode();
+
+        assert_eq!(
+            BFieldElement::new(aocl.len() as u64),
+            mutator_set_accumulator[AOCL_OFFSET - 1]
+        );
+        assert_eq!(
+            aocl,
+            mutator_set_accumulator[AOCL_OFFSET..AOCL_OFFSET + aocl.len()]
+        );
+    }
+
+    #[test]
+    fn test_graceful_halt() {
+        let (block_kernel, primitive_witness) = make_mock_block_kernel_and_primitive_witness(None);
+        let witness = CorrectMutatorSetUpdateWitness::new(&primitive_witness, &block_kernel);
+
+        let output = CorrectMutatorSetUpdate::program()
+            .run(
+                PublicInput::new(block_claim_input(&block_kernel)),
+                witness.nondeterminism(),
+            )
+            .unwrap();
+        assert!(output.is_empty());
+    }
+
+    #[test]
+    fn program_is_deterministic() {
+        let program = CorrectMutatorSetUpdate::program();
+        let other_program = CorrectMutatorSetUpdate::program();
+        assert_eq!(program, other_program);
+    }
+
+    #[test]
+    fn tasm_matches_rust() {
+        let (block_kernel, primitive_witness) = make_mock_block_kernel_and_primitive_witness(None);
+        let witness = CorrectMutatorSetUpdateWitness::new(&primitive_witness, &block_kernel);
+
+        test_rust_shadow::<CorrectMutatorSetUpdate>(
+            &PublicInput::new(block_claim_input(&block_kernel)),
+            &witness.nondeterminism(),
+        );
+    }
+
+    #[test]
+    fn crashes_on_wrong_aocl() {
+        let (block_kernel, primitive_witness) = make_mock_block_kernel_and_primitive_witness(None);
+        let witness = CorrectMutatorSetUpdateWitness::new(&primitive_witness, &block_kernel);
+        assert!(halts(&witness, &block_kernel));
+
+        // an AOCL accumulator skipped
+        let mut skipped = witness.clone();
+        skipped.aocl_accumulators[1] = skipped.aocl_accumulators[0].clone();
+        assert!(!halts(&skipped, &block_kernel));
+
+        // an AOCL accumulator missing
+        let mut missing = witness.clone();
+        missing.aocl_accumulators.pop();
+        assert!(!halts(&missing, &block_kernel));
+
+        // an output that is not in the mutator set
+        let mut unlisted_output = block_kernel.clone();
+        unlisted_output
+            .body
+            .mutator_set_accumulator
+            .kernel
+            .aocl
+            .append(random());
+        let witness = CorrectMutatorSetUpdateWitness::new(&primitive_witness, &unlisted_output);
+        assert!(!halts(&witness, &unlisted_output));
+    }
+}
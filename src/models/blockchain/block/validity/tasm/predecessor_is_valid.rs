@@ -0,0 +1,230 @@
+use crate::prelude::{triton_vm, twenty_first};
+
+use tasm_lib::data_type::DataType;
+use tasm_lib::hashing::hash_varlen::HashVarlen;
+use tasm_lib::library::Library;
+use tasm_lib::memory::push_ram_to_stack::PushRamToStack;
+use tasm_lib::memory::FIRST_NON_DETERMINISTICALLY_INITIALIZED_MEMORY_ADDRESS;
+use tasm_lib::structure::tasm_object::TasmObject;
+use tasm_lib::traits::compiled_program::CompiledProgram;
+use triton_vm::instruction::LabelledInstruction;
+use triton_vm::prelude::{triton_asm, BFieldElement, NonDeterminism, PublicInput};
+use twenty_first::shared_math::bfield_codec::BFieldCodec;
+
+use super::{authenticate_field, block_hash_from_public_input};
+use crate::models::blockchain::block::block_header::BlockHeaderField;
+use crate::models::blockchain::block::validity::predecessor_is_valid::{
+    PredecessorIsValid, PredecessorIsValidWitness,
+};
+use crate::models::blockchain::block::validity::BlockKernelLeaf;
+
+impl CompiledProgram for PredecessorIsValid {
+    fn rust_shadow(
+        public_input: &PublicInput,
+        nondeterminism: &NonDeterminism<BFieldElement>,
+    ) -> anyhow::Result<Vec<BFieldElement>> {
+        let block_hash = block_hash_from_public_input(public_input);
+
+        // 1. read witness data
+        let witness = *PredecessorIsValidWitness::decode_from_memory(
+            &nondeterminism.ram,
+            FIRST_NON_DETERMINISTICALLY_INITIALIZED_MEMORY_ADDRESS,
+        )
+        .unwrap();
+
+        // 2. authenticate the fields of the block, and the height of the predecessor
+        // against the digest that the block points to
+        let height = BlockKernelLeaf::Header(BlockHeaderField::Height);
+        let prev_block_digest = BlockKernelLeaf::Header(BlockHeaderField::PrevBlockDigest);
+        assert_eq!(
+            Some(block_hash),
+            height.root(&witness.height.encode(), &witness.height_path)
+        );
+        assert_eq!(
+            Some(block_hash),
+            prev_block_digest.root(
+                &witness.prev_block_digest.encode(),
+                &witness.prev_block_digest_path
+            )
+        );
+        assert_eq!(
+            Some(witness.prev_block_digest),
+            height.root(
+                &witness.predecessor_height.encode(),
+                &witness.predecessor_height_path
+            )
+        );
+
+        // 3. the block succeeds its predecessor
+        assert_eq!(
+            witness.predecessor_height + BFieldElement::new(1),
+            witness.height
+        );
+
+        // 4. output the digest of the predecessor
+        Ok(witness.prev_block_digest.values().to_vec())
+    }
+
+    fn code() -> (Vec<LabelledInstruction>, Library) {
+        let mut library = Library::new();
+        let hash_varlen = library.import(Box::new(HashVarlen));
+        let read_digest = library.import(Box::new(PushRamToStack {
+            data_type: DataType::Digest,
+        }));
+
+        // field getters
+        type W = PredecessorIsValidWitness;
+        let witness_to_height = tasm_lib::field!(W::height);
+        let witness_to_height_with_size = tasm_lib::field_with_size!(W::height);
+        let witness_to_height_path = tasm_lib::field!(W::height_path);
+        let witness_to_prev_block_digest = tasm_lib::field!(W::prev_block_digest);
+        let witness_to_prev_block_digest_with_size =
+            tasm_lib::field_with_size!(W::prev_block_digest);
+        let witness_to_prev_block_digest_path = tasm_lib::field!(W::prev_block_digest_path);
+        let witness_to_predecessor_height = tasm_lib::field!(W::predecessor_height);
+        let witness_to_predecessor_height_with_size =
+            tasm_lib::field_with_size!(W::predecessor_height);
+        let witness_to_predecessor_height_path = tasm_lib::field!(W::predecessor_height_path);
+
+        let authenticate_height = authenticate_field(
+            BlockKernelLeaf::Header(BlockHeaderField::Height),
+            &witness_to_height_with_size,
+            &witness_to_height_path,
+            &hash_varlen,
+        );
+        let authenticate_prev_block_digest = authenticate_field(
+            BlockKernelLeaf::Header(BlockHeaderField::PrevBlockDigest),
+            &witness_to_prev_block_digest_with_size,
+            &witness_to_prev_block_digest_path,
+            &hash_varlen,
+        );
+        let authenticate_predecessor_height = authenticate_field(
+            BlockKernelLeaf::Header(BlockHeaderField::Height),
+            &witness_to_predecessor_height_with_size,
+            &witness_to_predecessor_height_path,
+            &hash_varlen,
+        );
+
+        let code = triton_asm! {
+
+        // 1. Witness was already loaded into memory, just point to it
+        push {FIRST_NON_DETERMINISTICALLY_INITIALIZED_MEMORY_ADDRESS} // _ *witness
+        read_io 5                           // _ *witness [block_hash]
+
+        // 2. authenticate the fields of the block
+        dup 5
+        {&authenticate_height}              // _ *witness [block_hash]
+        dup 5
+        {&authenticate_prev_block_digest}   // _ *witness [block_hash]
+
+        // 3. authenticate the height of the predecessor
+        dup 5
+        {&witness_to_prev_block_digest}
+        call {read_digest}                  // _ *witness [block_hash] [prev_block_digest]
+        dup 10
+        {&authenticate_predecessor_height}  // _ *witness [block_hash] [prev_block_digest]
+
+        // 4. assert that the block succeeds its predecessor
+        dup 10
+        {&witness_to_height}
+        read_mem 1
+        pop 1                               // _ *witness [block_hash] [prev_block_digest] height
+        dup 11
+        {&witness_to_predecessor_height}
+        read_mem 1
+        pop 1                               // _ *witness [block_hash] [prev_block_digest] height predecessor_height
+        push 1 add
+        eq
+        assert                              // _ *witness [block_hash] [prev_block_digest]
+
+        // 5. output the digest of the predecessor
+        write_io 5                          // _ *witness [block_hash]
+        pop 5
+        pop 1                               // _
+
+        halt
+        };
+
+        (code, library)
+    }
+
+    fn crash_conditions() -> Vec<String> {
+        vec![
+            "a field does not match the block kernel MAST hash".to_string(),
+            "the block height is not one more than that of its predecessor".to_string(),
+        ]
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::models::blockchain::block::block_kernel::BlockKernel;
+    use crate::models::blockchain::block::validity::block_claim_input;
+    use crate::models::consensus::mast_hash::MastHash;
+    use crate::models::consensus::SecretWitness;
+    use crate::tests::shared::make_mock_block_kernel_and_primitive_witness;
+    use tasm_lib::traits::compiled_program::test_rust_shadow;
+
+    fn halts(witness: &PredecessorIsValidWitness, block_kernel: &BlockKernel) -> bool {
+        PredecessorIsValid::program()
+            .run(
+                PublicInput::new(block_claim_input(block_kernel)),
+                witness.nondeterminism(),
+            )
+            .is_ok()
+    }
+
+    #[test]
+    fn test_graceful_halt() {
+        let (block_kernel, primitive_witness) = make_mock_block_kernel_and_primitive_witness(None);
+        let witness = PredecessorIsValidWitness::new(&primitive_witness, &block_kernel);
+
+        let output = PredecessorIsValid::program()
+            .run(
+                PublicInput::new(block_claim_input(&block_kernel)),
+                witness.nondeterminism(),
+            )
+            .unwrap();
+        assert_eq!(
+            primitive_witness.predecessor.mast_hash().values().to_vec(),
+            output
+        );
+    }
+
+    #[test]
+    fn program_is_deterministic() {
+        let program = PredecessorIsValid::program();
+        let other_program = PredecessorIsValid::program();
+        assert_eq!(program, other_program);
+    }
+
+    #[test]
+    fn tasm_matches_rust() {
+        let (block_kernel, primitive_witness) = make_mock_block_kernel_and_primitive_witness(None);
+        let witness = PredecessorIsValidWitness::new(&primitive_witness, &block_kernel);
+
+        test_rust_shadow::<PredecessorIsValid>(
+            &PublicInput::new(block_claim_input(&block_kernel)),
+            &witness.nondeterminism(),
+        );
+    }
+
+    #[test]
+    fn crashes_on_unrelated_predecessor() {
+        let (block_kernel, primitive_witness) = make_mock_block_kernel_and_primitive_witness(None);
+        let witness = PredecessorIsValidWitness::new(&primitive_witness, &block_kernel);
+        assert!(halts(&witness, &block_kernel));
+
+        let mut wrong_height = witness.clone();
+        wrong_height.height += BFieldElement::new(1);
+        assert!(!halts(&wrong_height, &block_kernel));
+
+        let mut wrong_predecessor_height = witness.clone();
+        wrong_predecessor_height.predecessor_height = witness.height;
+        assert!(!halts(&wrong_predecessor_height, &block_kernel));
+
+        let (other_block_kernel, _) = make_mock_block_kernel_and_primitive_witness(None);
+        assert!(!halts(&witness, &other_block_kernel));
+    }
+}
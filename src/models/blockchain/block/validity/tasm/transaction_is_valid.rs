@@ -0,0 +1,238 @@
+use crate::prelude::{triton_vm, twenty_first};
+
+use tasm_lib::data_type::DataType;
+use tasm_lib::hashing::hash_varlen::HashVarlen;
+use tasm_lib::library::Library;
+use tasm_lib::memory::push_ram_to_stack::PushRamToStack;
+use tasm_lib::memory::FIRST_NON_DETERMINISTICALLY_INITIALIZED_MEMORY_ADDRESS;
+use tasm_lib::structure::tasm_object::TasmObject;
+use tasm_lib::traits::compiled_program::CompiledProgram;
+use triton_vm::instruction::LabelledInstruction;
+use triton_vm::prelude::{triton_asm, BFieldElement, NonDeterminism, PublicInput};
+use twenty_first::shared_math::bfield_codec::BFieldCodec;
+
+use super::{authenticate_field, block_hash_from_public_input};
+use crate::models::blockchain::block::block_body::BlockBodyField;
+use crate::models::blockchain::block::block_header::BlockHeaderField;
+use crate::models::blockchain::block::validity::transaction_is_valid::{
+    TransactionIsValid, TransactionIsValidWitness,
+};
+use crate::models::blockchain::block::validity::BlockKernelLeaf;
+use crate::models::blockchain::transaction::transaction_kernel::TransactionKernelField;
+
+impl CompiledProgram for TransactionIsValid {
+    fn rust_shadow(
+        public_input: &PublicInput,
+        nondeterminism: &NonDeterminism<BFieldElement>,
+    ) -> anyhow::Result<Vec<BFieldElement>> {
+        let block_hash = block_hash_from_public_input(public_input);
+
+        // 1. read witness data
+        let witness = *TransactionIsValidWitness::decode_from_memory(
+            &nondeterminism.ram,
+            FIRST_NON_DETERMINISTICALLY_INITIALIZED_MEMORY_ADDRESS,
+        )
+        .unwrap();
+
+        // 2. authenticate the fields
+        assert_eq!(
+            Some(block_hash),
+            BlockKernelLeaf::Body(BlockBodyField::Transaction).root(
+                &witness.transaction_kernel_mast_hash.encode(),
+                &witness.transaction_kernel_mast_hash_path
+            )
+        );
+        assert_eq!(
+            Some(block_hash),
+            BlockKernelLeaf::Header(BlockHeaderField::Timestamp)
+                .root(&witness.timestamp.encode(), &witness.timestamp_path)
+        );
+        assert_eq!(
+            Some(block_hash),
+            BlockKernelLeaf::TransactionKernel(TransactionKernelField::Timestamp).root(
+                &witness.transaction_timestamp.encode(),
+                &witness.transaction_timestamp_path
+            )
+        );
+
+        // 3. the transaction is not timestamped after the block
+        assert!((witness.timestamp - witness.transaction_timestamp).value() < 1 << 63);
+
+        // 4. output the MAST hash of the transaction kernel
+        Ok(witness.transaction_kernel_mast_hash.values().to_vec())
+    }
+
+    fn code() -> (Vec<LabelledInstruction>, Library) {
+        let mut library = Library::new();
+        let hash_varlen = library.import(Box::new(HashVarlen));
+        let read_digest = library.import(Box::new(PushRamToStack {
+            data_type: DataType::Digest,
+        }));
+
+        // field getters
+        type W = TransactionIsValidWitness;
+        let witness_to_transaction_kernel_mast_hash =
+            tasm_lib::field!(W::transaction_kernel_mast_hash);
+        let witness_to_transaction_kernel_mast_hash_with_size =
+            tasm_lib::field_with_size!(W::transaction_kernel_mast_hash);
+        let witness_to_transaction_kernel_mast_hash_path =
+            tasm_lib::field!(W::transaction_kernel_mast_hash_path);
+        let witness_to_timestamp = tasm_lib::field!(W::timestamp);
+        let witness_to_timestamp_with_size = tasm_lib::field_with_size!(W::timestamp);
+        let witness_to_timestamp_path = tasm_lib::field!(W::timestamp_path);
+        let witness_to_transaction_timestamp = tasm_lib::field!(W::transaction_timestamp);
+        let witness_to_transaction_timestamp_with_size =
+            tasm_lib::field_with_size!(W::transaction_timestamp);
+        let witness_to_transaction_timestamp_path = tasm_lib::field!(W::transaction_timestamp_path);
+
+        let authenticate_transaction_kernel_mast_hash = authenticate_field(
+            BlockKernelLeaf::Body(BlockBodyField::Transaction),
+            &witness_to_transaction_kernel_mast_hash_with_size,
+            &witness_to_transaction_kernel_mast_hash_path,
+            &hash_varlen,
+        );
+        let authenticate_timestamp = authenticate_field(
+            BlockKernelLeaf::Header(BlockHeaderField::Timestamp),
+            &witness_to_timestamp_with_size,
+            &witness_to_timestamp_path,
+            &hash_varlen,
+        );
+        let authenticate_transaction_timestamp = authenticate_field(
+            BlockKernelLeaf::TransactionKernel(TransactionKernelField::Timestamp),
+            &witness_to_transaction_timestamp_with_size,
+            &witness_to_transaction_timestamp_path,
+            &hash_varlen,
+        );
+
+        let code = triton_asm! {
+
+        // 1. Witness was already loaded into memory, just point to it
+        push {FIRST_NON_DETERMINISTICALLY_INITIALIZED_MEMORY_ADDRESS} // _ *witness
+        read_io 5                                       // _ *witness [block_hash]
+
+        // 2. authenticate the fields
+        dup 5
+        {&authenticate_transaction_kernel_mast_hash}    // _ *witness [block_hash]
+        dup 5
+        {&authenticate_timestamp}                       // _ *witness [block_hash]
+        dup 5
+        {&authenticate_transaction_timestamp}           // _ *witness [block_hash]
+
+        // 3. assert that the transaction is not timestamped after the block, that is,
+        // that the difference between the timestamps is less than 2^63 in the field
+        dup 5
+        {&witness_to_timestamp}
+        read_mem 1
+        pop 1                                           // _ *witness [block_hash] timestamp
+        dup 6
+        {&witness_to_transaction_timestamp}
+        read_mem 1
+        pop 1                                           // _ *witness [block_hash] timestamp transaction_timestamp
+        push -1 mul
+        add
+        split
+        pop 1                                           // _ *witness [block_hash] hi
+        push 2147483648
+        and
+        push 0 eq
+        assert                                          // _ *witness [block_hash]
+
+        // 4. output the MAST hash of the transaction kernel
+        dup 5
+        {&witness_to_transaction_kernel_mast_hash}
+        call {read_digest}                              // _ *witness [block_hash] [transaction_kernel_mast_hash]
+        write_io 5                                      // _ *witness [block_hash]
+        pop 5
+        pop 1                                           // _
+
+        halt
+        };
+
+        (code, library)
+    }
+
+    fn crash_conditions() -> Vec<String> {
+        vec![
+            "a field does not match the block kernel MAST hash".to_string(),
+            "the transaction is timestamped after the block".to_string(),
+        ]
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::models::blockchain::block::block_kernel::BlockKernel;
+    use crate::models::blockchain::block::validity::block_claim_input;
+    use crate::models::consensus::mast_hash::MastHash;
+    use crate::models::consensus::SecretWitness;
+    use crate::tests::shared::make_mock_block_kernel_and_primitive_witness;
+    use tasm_lib::traits::compiled_program::test_rust_shadow;
+
+    fn halts(witness: &TransactionIsValidWitness, block_kernel: &BlockKernel) -> bool {
+        TransactionIsValid::program()
+            .run(
+                PublicInput::new(block_claim_input(block_kernel)),
+                witness.nondeterminism(),
+            )
+            .is_ok()
+    }
+
+    #[test]
+    fn test_graceful_halt() {
+        let (block_kernel, primitive_witness) = make_mock_block_kernel_and_primitive_witness(None);
+        let witness = TransactionIsValidWitness::new(&primitive_witness, &block_kernel);
+
+        let output = TransactionIsValid::program()
+            .run(
+                PublicInput::new(block_claim_input(&block_kernel)),
+                witness.nondeterminism(),
+            )
+            .unwrap();
+        assert_eq!(
+            block_kernel
+                .body
+                .transaction
+                .kernel
+                .mast_hash()
+                .values()
+                .to_vec(),
+            output
+        );
+    }
+
+    #[test]
+    fn program_is_deterministic() {
+        let program = TransactionIsValid::program();
+        let other_program = TransactionIsValid::program();
+        assert_eq!(program, other_program);
+    }
+
+    #[test]
+    fn tasm_matches_rust() {
+        let (block_kernel, primitive_witness) = make_mock_block_kernel_and_primitive_witness(None);
+        let witness = TransactionIsValidWitness::new(&primitive_witness, &block_kernel);
+
+        test_rust_shadow::<TransactionIsValid>(
+            &PublicInput::new(block_claim_input(&block_kernel)),
+            &witness.nondeterminism(),
+        );
+    }
+
+    #[test]
+    fn crashes_on_transaction_from_the_future() {
+        let (mut block_kernel, primitive_witness) =
+            make_mock_block_kernel_and_primitive_witness(None);
+        let witness = TransactionIsValidWitness::new(&primitive_witness, &block_kernel);
+        assert!(halts(&witness, &block_kernel));
+
+        let mut other_transaction_kernel_mast_hash = witness.clone();
+        other_transaction_kernel_mast_hash.transaction_kernel_mast_hash =
+            block_kernel.body.transaction.kernel.mutator_set_hash;
+        assert!(!halts(&other_transaction_kernel_mast_hash, &block_kernel));
+
+        block_kernel.body.transaction.kernel.timestamp += BFieldElement::new(1);
+        let witness = TransactionIsValidWitness::new(&primitive_witness, &block_kernel);
+        assert!(!halts(&witness, &block_kernel));
+    }
+}
@@ -0,0 +1,199 @@
+use crate::prelude::{triton_vm, twenty_first};
+
+use tasm_lib::data_type::DataType;
+use tasm_lib::library::Library;
+use tasm_lib::traits::basic_snippet::BasicSnippet;
+use triton_vm::instruction::LabelledInstruction;
+use triton_vm::triton_asm;
+use twenty_first::util_types::mmr::mmr_accumulator::MmrAccumulator;
+
+use crate::models::blockchain::shared::Hash;
+
+/// Given a leaf and two MMR accumulators in memory, assert that the second one is the
+/// result of appending the leaf to the first one.
+///
+/// The leaf merges with the trailing peaks of the first accumulator, one for every
+/// trailing one bit of its leaf count. The second accumulator must have one more leaf,
+/// keep the remaining peaks of the first one, and end in the merged peak.
+///
+/// input:  _ l4 l3 l2 l1 l0 *mmra *new_mmra
+///
+/// output: _
+#[derive(Debug, Clone)]
+pub(crate) struct VerifyMmrAppend;
+
+impl BasicSnippet for VerifyMmrAppend {
+    fn inputs(&self) -> Vec<(DataType, String)> {
+        vec![
+            (DataType::Digest, "leaf".to_string()),
+            (DataType::VoidPointer, "*mmra".to_string()),
+            (DataType::VoidPointer, "*new_mmra".to_string()),
+        ]
+    }
+
+    fn outputs(&self) -> Vec<(DataType, String)> {
+        vec![]
+    }
+
+    fn entrypoint(&self) -> String {
+        "tasm_neptune_block_verify_mmr_append".to_string()
+    }
+
+    fn code(&self, _library: &mut Library) -> Vec<LabelledInstruction> {
+        type MmraH = MmrAccumulator<Hash>;
+        let mmra_to_leaf_count = tasm_lib::field!(MmraH::leaf_count);
+        let mmra_to_peaks = tasm_lib::field!(MmraH::peaks);
+
+        let entrypoint = self.entrypoint();
+        let merge_trailing_peaks = format!("{entrypoint}_merge_trailing_peaks");
+        let compare_remaining_peaks = format!("{entrypoint}_compare_remaining_peaks");
+
+        triton_asm! {
+        // BEFORE: _ [leaf] *mmra *new_mmra
+        // AFTER: _
+        {entrypoint}:
+            // read the leaf counts, which are u64s
+            dup 1
+            {&mmra_to_leaf_count}
+            push 1 add
+            read_mem 2
+            pop 1                   // _ [leaf] *mmra *new_mmra hi lo
+            dup 1 split pop 1 push 0 eq assert
+            dup 0 split pop 1 push 0 eq assert
+            dup 2
+            {&mmra_to_leaf_count}
+            push 1 add
+            read_mem 2
+            pop 1                   // _ [leaf] *mmra *new_mmra hi lo new_hi new_lo
+            dup 1 split pop 1 push 0 eq assert
+            dup 0 split pop 1 push 0 eq assert
+
+            // assert that the new accumulator has one more leaf
+            swap 1
+            push 4294967296
+            mul
+            add                     // _ [leaf] *mmra *new_mmra hi lo new_leaf_count
+            dup 2
+            push 4294967296
+            mul
+            dup 2
+            add                     // _ [leaf] *mmra *new_mmra hi lo new_leaf_count leaf_count
+            push 1 add
+            eq
+            assert                  // _ [leaf] *mmra *new_mmra hi lo
+
+            // merge the leaf with the trailing peaks
+            dup 3
+            {&mmra_to_peaks}
+            read_mem 1
+            pop 1                   // _ [leaf] *mmra *new_mmra hi lo num_peaks
+            dup 9 dup 9 dup 9 dup 9 dup 9
+                                    // _ [leaf] *mmra *new_mmra hi lo num_peaks [leaf]
+            call {merge_trailing_peaks}
+                                    // _ [leaf] *mmra *new_mmra hi' lo' j [acc]
+
+            // the new accumulator has the j remaining peaks plus the merged one
+            dup 8
+            {&mmra_to_peaks}
+            read_mem 1
+            pop 1                   // _ [leaf] *mmra *new_mmra hi' lo' j [acc] new_num_peaks
+            dup 6
+            push 1 add
+            eq
+            assert                  // _ [leaf] *mmra *new_mmra hi' lo' j [acc]
+            dup 5
+            push 5 mul
+            dup 9
+            {&mmra_to_peaks}
+            add
+            push 5 add
+            read_mem 5
+            pop 1                   // _ [leaf] *mmra *new_mmra hi' lo' j [acc] [new_peaks[j]]
+            assert_vector
+            pop 5                   // _ [leaf] *mmra *new_mmra hi' lo' j
+
+            swap 2
+            pop 2                   // _ [leaf] *mmra *new_mmra j
+            push 0                  // _ [leaf] *mmra *new_mmra j 0
+            call {compare_remaining_peaks}
+                                    // _ [leaf] *mmra *new_mmra j j
+            pop 4
+            pop 5                   // _
+            return
+
+        // BEFORE: _ *mmra *new_mmra hi lo j [acc]
+        // AFTER: _ *mmra *new_mmra hi' lo' j' [acc']
+        {merge_trailing_peaks}:
+            dup 6
+            push 1
+            and
+            push 0 eq
+            skiz return
+
+            // merge the accumulated node with peak j - 1
+            swap 5
+            push -1 add
+            swap 5                  // _ *mmra *new_mmra hi lo (j - 1) [acc]
+            dup 5
+            push 5 mul
+            dup 10
+            {&mmra_to_peaks}
+            add
+            push 5 add
+            read_mem 5
+            pop 1                   // _ *mmra *new_mmra hi lo (j - 1) [acc] [peaks[j - 1]]
+            hash                    // _ *mmra *new_mmra hi lo (j - 1) [acc']
+
+            // shift the leaf count one bit to the right
+            dup 6
+            push 2147483648
+            mul
+            split
+            pop 1                   // _ *mmra *new_mmra hi lo (j - 1) [acc'] (lo >> 1)
+            dup 8
+            push 1
+            and
+            push 2147483648
+            mul
+            add                     // _ *mmra *new_mmra hi lo (j - 1) [acc'] lo'
+            dup 8
+            push 2147483648
+            mul
+            split
+            pop 1                   // _ *mmra *new_mmra hi lo (j - 1) [acc'] lo' hi'
+            swap 9
+            pop 1
+            swap 7
+            pop 1                   // _ *mmra *new_mmra hi' lo' (j - 1) [acc']
+            recurse
+
+        // BEFORE: _ *mmra *new_mmra j i
+        // AFTER: _ *mmra *new_mmra j j
+        {compare_remaining_peaks}:
+            dup 1 dup 1 eq
+            skiz return
+
+            dup 3
+            {&mmra_to_peaks}
+            dup 1
+            push 5 mul
+            add
+            push 5 add
+            read_mem 5
+            pop 1                   // _ *mmra *new_mmra j i [peaks[i]]
+            dup 7
+            {&mmra_to_peaks}
+            dup 6
+            push 5 mul
+            add
+            push 5 add
+            read_mem 5
+            pop 1                   // _ *mmra *new_mmra j i [peaks[i]] [new_peaks[i]]
+            assert_vector
+            pop 5                   // _ *mmra *new_mmra j i
+
+            push 1 add
+            recurse
+        }
+    }
+}
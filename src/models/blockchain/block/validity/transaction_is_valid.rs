@@ -1,31 +1,128 @@
+use crate::prelude::{triton_vm, twenty_first};
+
+use field_count::FieldCount;
 use get_size::GetSize;
 use serde::{Deserialize, Serialize};
-use tasm_lib::{
-    triton_vm::program::{NonDeterminism, Program},
-    twenty_first::shared_math::{b_field_element::BFieldElement, bfield_codec::BFieldCodec},
-};
+use std::collections::HashMap;
+use tasm_lib::memory::{encode_to_memory, FIRST_NON_DETERMINISTICALLY_INITIALIZED_MEMORY_ADDRESS};
+use tasm_lib::structure::tasm_object::TasmObject;
+use tasm_lib::traits::compiled_program::CompiledProgram;
+use triton_vm::prelude::{BFieldElement, Claim, NonDeterminism, Program, Proof};
+use twenty_first::shared_math::bfield_codec::BFieldCodec;
+use twenty_first::shared_math::tip5::Digest;
+use twenty_first::util_types::algebraic_hasher::AlgebraicHasher;
 
-use crate::models::{
-    blockchain::transaction::Transaction,
-    consensus::{SecretWitness, SupportedClaim},
-};
+use super::{block_claim_input, BlockKernelLeaf, BlockPrimitiveWitness};
+use crate::models::blockchain::block::block_body::BlockBodyField;
+use crate::models::blockchain::block::block_header::BlockHeaderField;
+use crate::models::blockchain::block::block_kernel::BlockKernel;
+use crate::models::blockchain::shared::Hash;
+use crate::models::blockchain::transaction::transaction_kernel::TransactionKernelField;
+use crate::models::consensus::mast_hash::MastHash;
+use crate::models::consensus::{ClaimSupport, SecretWitness, SupportedClaim, ValidationLogic};
 
-#[derive(Debug, Clone, BFieldCodec, GetSize, PartialEq, Eq, Serialize, Deserialize)]
+/// The MAST hash of the kernel of the block's transaction, along with the timestamps of
+/// the block and of the transaction, each with its authentication path.
+#[derive(
+    Clone,
+    Debug,
+    Serialize,
+    Deserialize,
+    PartialEq,
+    Eq,
+    GetSize,
+    BFieldCodec,
+    FieldCount,
+    TasmObject,
+)]
 pub struct TransactionIsValidWitness {
-    transaction: Transaction,
+    pub transaction_kernel_mast_hash: Digest,
+    pub transaction_kernel_mast_hash_path: Vec<Digest>,
+    pub timestamp: BFieldElement,
+    pub timestamp_path: Vec<Digest>,
+    pub transaction_timestamp: BFieldElement,
+    pub transaction_timestamp_path: Vec<Digest>,
+}
+
+impl TransactionIsValidWitness {
+    pub fn new(_primitive_witness: &BlockPrimitiveWitness, block_kernel: &BlockKernel) -> Self {
+        let transaction_kernel = &block_kernel.body.transaction.kernel;
+
+        Self {
+            transaction_kernel_mast_hash: transaction_kernel.mast_hash(),
+            transaction_kernel_mast_hash_path: BlockKernelLeaf::Body(BlockBodyField::Transaction)
+                .path(block_kernel),
+            timestamp: block_kernel.header.timestamp,
+            timestamp_path: BlockKernelLeaf::Header(BlockHeaderField::Timestamp).path(block_kernel),
+            transaction_timestamp: transaction_kernel.timestamp,
+            transaction_timestamp_path: BlockKernelLeaf::TransactionKernel(
+                TransactionKernelField::Timestamp,
+            )
+            .path(block_kernel),
+        }
+    }
 }
 
 impl SecretWitness for TransactionIsValidWitness {
     fn nondeterminism(&self) -> NonDeterminism<BFieldElement> {
-        todo!()
+        let mut memory = HashMap::default();
+        encode_to_memory(
+            &mut memory,
+            FIRST_NON_DETERMINISTICALLY_INITIALIZED_MEMORY_ADDRESS,
+            self.clone(),
+        );
+        NonDeterminism::default().with_ram(memory)
     }
 
     fn subprogram(&self) -> Program {
-        todo!()
+        TransactionIsValid::program()
     }
 }
 
 #[derive(Debug, Clone, BFieldCodec, GetSize, PartialEq, Eq, Serialize, Deserialize)]
 pub struct TransactionIsValid {
-    supported_claim: SupportedClaim<TransactionIsValidWitness>,
+    pub supported_claim: SupportedClaim<TransactionIsValidWitness>,
+}
+
+impl ValidationLogic<TransactionIsValidWitness> for TransactionIsValid {
+    type PrimitiveWitness = BlockPrimitiveWitness;
+
+    type Kernel = BlockKernel;
+
+    fn new_from_primitive_witness(
+        primitive_witness: &BlockPrimitiveWitness,
+        block_kernel: &BlockKernel,
+    ) -> Self {
+        let transaction_is_valid_witness =
+            TransactionIsValidWitness::new(primitive_witness, block_kernel);
+        let claim = Claim {
+            input: block_claim_input(block_kernel),
+            output: transaction_is_valid_witness
+                .transaction_kernel_mast_hash
+                .values()
+                .to_vec(),
+            program_digest: Hash::hash_varlen(&Self::program().encode()),
+        };
+        let supported_claim = SupportedClaim {
+            claim,
+            support: ClaimSupport::SecretWitness(transaction_is_valid_witness),
+        };
+        Self { supported_claim }
+    }
+
+    fn upgrade(&mut self, proof: Proof) {
+        self.supported_claim.support = ClaimSupport::Proof(proof);
+    }
+
+    fn subprogram(&self) -> Program {
+        Self::program()
+    }
+
+    fn support(&self) -> ClaimSupport<TransactionIsValidWitness> {
+        self.supported_claim.support.clone()
+    }
+
+    fn claim(&self) -> Claim {
+        self.supported_claim.claim.clone()
+    }
 }
@@ -3,24 +3,27 @@ use crate::prelude::{triton_vm, twenty_first};
 
 pub mod native_coin;
 pub mod neptune_coins;
+pub mod timelock;
+pub mod token;
+pub mod transaction_details;
 pub mod transaction_kernel;
 pub mod utxo;
 pub mod validity;
 
-use crate::models::consensus::Witness;
-use anyhow::Result;
+use crate::models::consensus::{ClaimSupport, ValidationLogic, Witness};
+use anyhow::{bail, Result};
 use get_size::GetSize;
 use itertools::Itertools;
 use num_bigint::BigInt;
 use num_rational::BigRational;
 use serde::{Deserialize, Serialize};
-use std::cmp::max;
 use std::hash::{Hash as StdHash, Hasher as StdHasher};
 use std::time::SystemTime;
 use tracing::{debug, error, warn};
 use triton_vm::prelude::NonDeterminism;
 use twenty_first::shared_math::b_field_element::BFieldElement;
 use twenty_first::shared_math::bfield_codec::BFieldCodec;
+use twenty_first::shared_math::tip5::Digest;
 use twenty_first::util_types::algebraic_hasher::AlgebraicHasher;
 use twenty_first::util_types::emojihash_trait::Emojihash;
 
@@ -28,16 +31,20 @@ use self::native_coin::native_coin_program;
 use self::neptune_coins::NeptuneCoins;
 use self::transaction_kernel::TransactionKernel;
 use self::utxo::{LockScript, TypeScript, Utxo};
-use self::validity::TransactionValidationLogic;
+use self::validity::merge::{Merge, MergeWitness};
+use self::validity::typescripts_halt::TypeScriptUtxos;
+use self::validity::update::{Update, UpdateWitness};
+use self::validity::{TransactionDerivation, TransactionValidationLogic};
 use super::block::Block;
 use super::shared::Hash;
 use crate::util_types::mutator_set::addition_record::AdditionRecord;
 use crate::util_types::mutator_set::ms_membership_proof::MsMembershipProof;
 use crate::util_types::mutator_set::mutator_set_accumulator::MutatorSetAccumulator;
-use crate::util_types::mutator_set::mutator_set_trait::MutatorSet;
+use crate::util_types::mutator_set::mutator_set_trait::{commit, MutatorSet};
 use crate::util_types::mutator_set::removal_record::RemovalRecord;
 
-pub type TransactionWitness = Witness<TransactionPrimitiveWitness, TransactionValidationLogic>;
+pub type TransactionWitness =
+    Witness<TransactionPrimitiveWitness, TransactionValidationLogic, TransactionDerivation>;
 
 #[derive(Clone, Debug, Serialize, Deserialize, PartialEq, Eq, GetSize, BFieldCodec, Default)]
 pub struct PublicAnnouncement {
@@ -60,10 +67,33 @@ pub struct TransactionPrimitiveWitness {
     pub lock_script_witnesses: Vec<Vec<BFieldElement>>,
     pub input_membership_proofs: Vec<MsMembershipProof>,
     pub output_utxos: Vec<Utxo>,
+    pub output_sender_randomnesses: Vec<Digest>,
+    pub output_receiver_digests: Vec<Digest>,
     pub public_announcements: Vec<PublicAnnouncement>,
     pub mutator_set_accumulator: MutatorSetAccumulator,
 }
 
+impl TransactionPrimitiveWitness {
+    /// The salt that hides the input UTXOs in the claims that are linked by their hash.
+    /// Derived from the sender randomnesses of the inputs, which only the parties to the
+    /// inputs know.
+    pub fn input_utxos_salt(&self) -> Digest {
+        let sender_randomnesses = self
+            .input_membership_proofs
+            .iter()
+            .map(|msmp| msmp.sender_randomness)
+            .collect_vec();
+        Hash::hash(&sender_randomnesses)
+    }
+
+    /// The salt that hides the output UTXOs in the claims that are linked by their hash.
+    /// Derived from the sender randomnesses of the outputs, which only the sender and the
+    /// respective receivers know.
+    pub fn output_utxos_salt(&self) -> Digest {
+        Hash::hash(&self.output_sender_randomnesses)
+    }
+}
+
 #[derive(Clone, Debug, Serialize, Deserialize, PartialEq, Eq, GetSize, BFieldCodec)]
 pub struct Transaction {
     pub kernel: TransactionKernel,
@@ -83,18 +113,52 @@ impl StdHash for Transaction {
 }
 
 impl Transaction {
+    /// Turn the primitive witness of a transaction into the witness that is broadcast. The
+    /// claims that the lock scripts halt are always proven, as their witnesses contain
+    /// spending keys. With `prove_all`, all other claims are proven as well, which hides
+    /// the remaining secret data of the transaction.
+    pub fn prove_from_primitive_witness(
+        primitive_witness: &TransactionPrimitiveWitness,
+        kernel: TransactionKernel,
+        prove_all: bool,
+    ) -> Transaction {
+        let mut transaction_validity_logic =
+            TransactionValidationLogic::new_from_primitive_witness(primitive_witness, &kernel);
+
+        if prove_all {
+            transaction_validity_logic
+                .prove()
+                .expect("Proof generation must work when creating a new transaction");
+        } else {
+            transaction_validity_logic.lock_scripts_halt.prove().expect(
+                "Proof generation must work when unlocking owned UTXOs for a new transaction.",
+            );
+        }
+
+        Transaction {
+            kernel,
+            witness: Witness::ValidationLogic(transaction_validity_logic),
+        }
+    }
+
     /// Update mutator set data in a transaction to update its
-    /// compatibility with a new block. Note that for SingleProof witnesses, this will
-    /// invalidate the proof, requiring an update. For LinkedProofs or PrimitiveWitness
-    /// witnesses the witness data can be and is updated.
+    /// compatibility with a new block. Primitive witnesses are updated along with the
+    /// removal records. Proven transactions are supported by an update claim instead,
+    /// which shows that the new kernel is the old one updated to the new mutator set, and
+    /// which keeps the original transaction so that its proof is verified as well.
     pub fn update_mutator_set_records(
         &mut self,
         previous_mutator_set_accumulator: &MutatorSetAccumulator,
         block: &Block,
     ) -> Result<()> {
+        let old_kernel = self.kernel.clone();
         let mut msa_state: MutatorSetAccumulator = previous_mutator_set_accumulator.clone();
         let block_addition_records: Vec<AdditionRecord> =
             block.kernel.body.transaction.kernel.outputs.clone();
+        let aocl_leafs = block_addition_records
+            .iter()
+            .map(|addition_record| addition_record.canonical_commitment)
+            .collect_vec();
         let mut transaction_removal_records: Vec<RemovalRecord> = self.kernel.inputs.clone();
         let mut transaction_removal_records: Vec<&mut RemovalRecord> =
             transaction_removal_records.iter_mut().collect();
@@ -177,6 +241,42 @@ impl Transaction {
         // Update the claimed mutator set hash
         self.kernel.mutator_set_hash = block_msa_hash;
 
+        // Support a proven transaction by the claim that it was updated correctly. A
+        // transaction that was updated before is updated from its original, so that
+        // update claims do not nest with every block.
+        let update_witness = UpdateWitness::new(
+            &old_kernel,
+            &self.kernel,
+            previous_mutator_set_accumulator,
+            &block.kernel.body.mutator_set_accumulator,
+            aocl_leafs,
+        );
+        match &self.witness {
+            Witness::Derivation(TransactionDerivation::Update(update)) => {
+                let ClaimSupport::SecretWitness(earlier_update_witness) = update.support() else {
+                    bail!("Cannot extend an update claim that was already proven");
+                };
+                let [original] = &update.operands[..] else {
+                    bail!("Update must carry the transaction that was updated");
+                };
+                self.witness = Witness::Derivation(TransactionDerivation::Update(Update::new(
+                    &earlier_update_witness.extend(&update_witness),
+                    original.clone(),
+                )));
+            }
+            Witness::ValidationLogic(_) | Witness::Derivation(_) => {
+                let original = Transaction {
+                    kernel: old_kernel,
+                    witness: self.witness.clone(),
+                };
+                self.witness = Witness::Derivation(TransactionDerivation::Update(Update::new(
+                    &update_witness,
+                    original,
+                )));
+            }
+            Witness::Primitive(_) | Witness::SingleProof(_) | Witness::Faith => (),
+        }
+
         Ok(())
     }
 
@@ -190,116 +290,58 @@ impl Transaction {
     /// isolation, without the context of the canonical chain.
     pub fn is_valid(&self) -> bool {
         match &self.witness {
-            Witness::ValidationLogic(validity_logic) => validity_logic.verify(),
+            Witness::ValidationLogic(validity_logic) => validity_logic.verify(&self.kernel),
             Witness::Primitive(primitive_witness) => {
                 warn!("Verifying transaction by raw witness; unlock key might be exposed!");
                 self.validate_primitive_witness(primitive_witness)
             }
-            Witness::SingleProof(_) => true,
+            Witness::SingleProof(_) => {
+                warn!("Single proofs cannot be verified until recursion is available");
+                false
+            }
+            Witness::Derivation(derivation) => derivation.verify(&self.kernel),
             // TODO: All validation of `Faith` should panic as only the genesis block
             // should have a Faith witness once all validation logic is implemented.
             Witness::Faith => true,
         }
     }
 
-    /// Merge two transactions. Both input transactions must have a
-    /// valid SingleProof witness for this operation to work.
+    /// This transaction and, recursively, the transactions it was merged or updated from,
+    /// which its witness carries.
+    pub fn constituents(&self) -> Vec<&Transaction> {
+        let operands: &[Transaction] = match &self.witness {
+            Witness::Derivation(TransactionDerivation::Merge(merge)) => &merge.operands,
+            Witness::Derivation(TransactionDerivation::Update(update)) => &update.operands,
+            _ => &[],
+        };
+        let mut constituents = vec![self];
+        for operand in operands {
+            constituents.extend(operand.constituents());
+        }
+        constituents
+    }
+
+    /// Merge two transactions into one whose inputs, outputs and public announcements
+    /// are shuffled deterministically, so that the merger does not reveal which of them
+    /// belonged together. The merged transaction is supported by a merge claim and
+    /// carries the two transactions, whose witnesses stay bound to their own kernels.
     /// The mutator sets are assumed to be identical; this is the responsibility of the caller.
     pub fn merge_with(self, other: Transaction) -> Transaction {
         assert_eq!(
             self.kernel.mutator_set_hash, other.kernel.mutator_set_hash,
             "Mutator sets must be equal for transaction merger."
         );
-        let timestamp = BFieldElement::new(max(
-            self.kernel.timestamp.value(),
-            other.kernel.timestamp.value(),
-        ));
-
-        let merged_coinbase = match self.kernel.coinbase {
-            Some(_) => match other.kernel.coinbase {
-                Some(_) => {
-                    error!("Cannot merge two transactions with non-empty coinbase fields.");
-                    return self;
-                }
-                None => self.kernel.coinbase,
-            },
-            None => other.kernel.coinbase,
-        };
-
-        let merged_kernel = TransactionKernel {
-            inputs: [self.kernel.inputs, other.kernel.inputs].concat(),
-            outputs: [self.kernel.outputs, other.kernel.outputs].concat(),
-            public_announcements: [
-                self.kernel.public_announcements,
-                other.kernel.public_announcements,
-            ]
-            .concat(),
-            fee: self.kernel.fee + other.kernel.fee,
-            coinbase: merged_coinbase,
-            timestamp,
-            mutator_set_hash: self.kernel.mutator_set_hash,
-        };
-
-        let merged_witness = match (&self.witness, &other.witness) {
-            (Witness::Primitive(self_witness), Witness::Primitive(other_witness)) => {
-                Witness::Primitive(TransactionPrimitiveWitness {
-                    input_utxos: [
-                        self_witness.input_utxos.clone(),
-                        other_witness.input_utxos.clone(),
-                    ]
-                    .concat(),
-                    input_lock_scripts: [
-                        self_witness.input_lock_scripts.clone(),
-                        other_witness.input_lock_scripts.clone(),
-                    ]
-                    .concat(),
-                    type_scripts: self_witness
-                        .type_scripts
-                        .iter()
-                        .cloned()
-                        .chain(other_witness.type_scripts.iter().cloned())
-                        .unique()
-                        .collect_vec(),
-                    lock_script_witnesses: [
-                        self_witness.lock_script_witnesses.clone(),
-                        other_witness.lock_script_witnesses.clone(),
-                    ]
-                    .concat(),
-                    input_membership_proofs: [
-                        self_witness.input_membership_proofs.clone(),
-                        other_witness.input_membership_proofs.clone(),
-                    ]
-                    .concat(),
-                    output_utxos: [
-                        self_witness.output_utxos.clone(),
-                        other_witness.output_utxos.clone(),
-                    ]
-                    .concat(),
-                    public_announcements: [
-                        self_witness.public_announcements.clone(),
-                        other_witness.public_announcements.clone(),
-                    ]
-                    .concat(),
-                    mutator_set_accumulator: self_witness.mutator_set_accumulator.clone(),
-                })
-            }
+        if self.kernel.coinbase.is_some() && other.kernel.coinbase.is_some() {
+            error!("Cannot merge two transactions with non-empty coinbase fields.");
+            return self;
+        }
 
-            // TODO: Merge with recursion
-            (Witness::ValidationLogic(_self_vl), Witness::ValidationLogic(_other_vl)) => {
-                Witness::Faith
-            }
-            (Witness::Faith, _) => Witness::Faith,
-            (_, Witness::Faith) => Witness::Faith,
-            _ => {
-                let self_type = std::mem::discriminant(&self.witness);
-                let other_type = std::mem::discriminant(&other.witness);
-                todo!("Can only merge primitive witnesses for now. Got: self: {self_type:?}; other: {other_type:?}");
-            }
-        };
+        let merge_witness = MergeWitness::new(&self.kernel, &other.kernel);
+        let merge = Merge::new(&merge_witness, self, other);
 
         Transaction {
-            kernel: merged_kernel,
-            witness: merged_witness,
+            kernel: merge_witness.new_kernel,
+            witness: Witness::Derivation(TransactionDerivation::Merge(merge)),
         }
     }
 
@@ -327,7 +369,21 @@ impl Transaction {
     }
 
     fn validate_primitive_witness(&self, primitive_witness: &TransactionPrimitiveWitness) -> bool {
-        // verify lock scripts
+        // verify that the lock scripts are those the inputs are locked to
+        if primitive_witness.input_lock_scripts.len() != primitive_witness.input_utxos.len()
+            || primitive_witness
+                .input_lock_scripts
+                .iter()
+                .zip(primitive_witness.input_utxos.iter())
+                .any(|(lock_script, utxo)| lock_script.hash() != utxo.lock_script_hash)
+        {
+            warn!("Lock scripts of transaction do not match the lock script hashes of its inputs.");
+            return false;
+        }
+
+        // verify lock scripts, which read the kernel MAST hash from public input, as in
+        // the claims of `LockScriptsHalt`
+        let public_input = self.kernel.mast_hash().values().to_vec();
         for (lock_script, secret_input) in primitive_witness
             .input_lock_scripts
             .iter()
@@ -335,10 +391,8 @@ impl Transaction {
         {
             // The lock script is satisfied if it halts gracefully (i.e.,
             // without crashing). We do not care about the output.
-            let public_input = Hash::hash(&self.kernel).reversed().encode();
-
             match lock_script.program.run(
-                public_input.into(),
+                public_input.clone().into(),
                 NonDeterminism::new(secret_input.to_vec()),
             ) {
                 Ok(_) => (),
@@ -382,34 +436,45 @@ impl Transaction {
 
         // collect type scripts
         let type_scripts = primitive_witness
-            .output_utxos
+            .input_utxos
             .iter()
+            .chain(primitive_witness.output_utxos.iter())
             .flat_map(|utxo| utxo.coins.iter().map(|coin| coin.type_script_hash))
             .sorted_by_key(|d| d.values().map(|b| b.value()))
             .dedup()
             .collect_vec();
 
         // verify type scripts
+        let utxos = TypeScriptUtxos::new(primitive_witness);
         for type_script_hash in type_scripts {
-            let type_script = if type_script_hash != native_coin_program().hash::<Hash>() {
-                warn!("Observed non-native type script: {} Non-native type scripts are not supported yet.", type_script_hash.emojihash());
-                continue;
-            } else {
-                native_coin_program()
-            };
-
-            let public_input = self.kernel.mast_hash().encode();
-            let secret_input = self
-                .kernel
-                .mast_sequences()
-                .into_iter()
-                .flatten()
-                .collect_vec();
+            let (type_script, nondeterminism) =
+                if type_script_hash == native_coin_program().hash::<Hash>() {
+                    let secret_input = self
+                        .kernel
+                        .mast_sequences()
+                        .into_iter()
+                        .flatten()
+                        .collect_vec();
+                    (native_coin_program(), NonDeterminism::new(secret_input))
+                } else {
+                    let Some(type_script) = primitive_witness
+                        .type_scripts
+                        .iter()
+                        .find(|type_script| type_script.hash() == type_script_hash)
+                    else {
+                        warn!(
+                            "Type script {} of transaction is missing from its witness.",
+                            type_script_hash.emojihash()
+                        );
+                        return false;
+                    };
+                    (type_script.program.clone(), utxos.nondeterminism())
+                };
 
             // The type script is satisfied if it halts gracefully, i.e.,
             // without panicking. So we don't care about the output
-            if let Err(e) = type_script.run(public_input.into(), NonDeterminism::new(secret_input))
-            {
+            let public_input = utxos.public_input(self.kernel.mast_hash(), type_script_hash);
+            if let Err(e) = type_script.run(public_input.into(), nondeterminism) {
                 warn!(
                     "Type script {} not satisfied for transaction: {}",
                     type_script_hash.emojihash(),
@@ -481,6 +546,29 @@ impl Transaction {
             return false;
         }
 
+        // Verify that the addition records listed in the transaction kernel commit to the
+        // output UTXOs of the primitive witness.
+        let output_count = self.kernel.outputs.len();
+        if primitive_witness.output_utxos.len() != output_count
+            || primitive_witness.output_sender_randomnesses.len() != output_count
+            || primitive_witness.output_receiver_digests.len() != output_count
+            || itertools::izip!(
+                primitive_witness.output_utxos.iter(),
+                primitive_witness.output_sender_randomnesses.iter(),
+                primitive_witness.output_receiver_digests.iter(),
+                self.kernel.outputs.iter()
+            )
+            .any(
+                |(utxo, sender_randomness, receiver_digest, addition_record)| {
+                    commit(Hash::hash(utxo), *sender_randomness, *receiver_digest)
+                        != *addition_record
+                },
+            )
+        {
+            warn!("Addition records as generated from witness do not match with those listed as outputs in transaction kernel.");
+            return false;
+        }
+
         // in regards to public announcements: there isn't anything to verify
 
         true
@@ -500,6 +588,8 @@ mod witness_tests {
             lock_script_witnesses: vec![],
             input_membership_proofs: vec![],
             output_utxos: vec![],
+            output_sender_randomnesses: vec![],
+            output_receiver_digests: vec![],
             public_announcements: vec![],
             mutator_set_accumulator: MutatorSetAccumulator::new(),
         };
@@ -513,6 +603,7 @@ mod witness_tests {
 #[cfg(test)]
 mod transaction_tests {
     use rand::random;
+    use std::collections::HashSet;
     use std::time::Duration;
     use tracing_test::traced_test;
 
@@ -549,6 +640,64 @@ mod transaction_tests {
         assert_eq!(empty_tx, decoded);
     }
 
+    #[test]
+    fn merged_transaction_is_shuffled_and_valid() {
+        let addition_records = |count: usize| {
+            (0..count)
+                .map(|_| commit(random(), random(), random()))
+                .collect_vec()
+        };
+        let transaction_1 = make_mock_transaction(vec![], addition_records(3));
+        let mut transaction_2 = make_mock_transaction(vec![], addition_records(2));
+        transaction_2.kernel.mutator_set_hash = transaction_1.kernel.mutator_set_hash;
+
+        let merged = transaction_1.clone().merge_with(transaction_2.clone());
+        assert!(merged.is_valid());
+        assert_eq!(
+            merged.kernel,
+            transaction_2
+                .clone()
+                .merge_with(transaction_1.clone())
+                .kernel,
+            "The order of the merged transactions must not show"
+        );
+        assert_eq!(
+            HashSet::<AdditionRecord>::from_iter(merged.kernel.outputs.iter().copied()),
+            HashSet::from_iter(
+                [transaction_1.kernel.outputs, transaction_2.kernel.outputs]
+                    .concat()
+                    .into_iter()
+            )
+        );
+        assert_eq!(NeptuneCoins::new(2), merged.kernel.fee);
+
+        let mut tampered = merged.clone();
+        tampered.kernel.fee = NeptuneCoins::new(1);
+        assert!(!tampered.is_valid());
+
+        let Witness::Derivation(TransactionDerivation::Merge(merge)) = &merged.witness else {
+            panic!("Merged transaction must be supported by a merge claim");
+        };
+        let mut tampered_merge = merge.clone();
+        tampered_merge.operands[1].kernel.fee = NeptuneCoins::new(0);
+        let tampered = Transaction {
+            kernel: merged.kernel.clone(),
+            witness: Witness::Derivation(TransactionDerivation::Merge(tampered_merge)),
+        };
+        assert!(
+            !tampered.is_valid(),
+            "The merged transactions must be the ones the merge claim is about"
+        );
+
+        let mut tampered_merge = merge.clone();
+        tampered_merge.operands.pop();
+        let tampered = Transaction {
+            kernel: merged.kernel.clone(),
+            witness: Witness::Derivation(TransactionDerivation::Merge(tampered_merge)),
+        };
+        assert!(!tampered.is_valid());
+    }
+
     // #[traced_test]
     // #[test]
     // fn merged_transaction_is_devnet_valid_test() {
@@ -0,0 +1,337 @@
+//! Time locks for lock scripts. A time-locked lock script only halts for transactions
+//! whose timestamp is at or after a given release time, or whose minimum block height is
+//! at or above a given release height.
+//!
+//! The timestamp of a transaction cannot exceed the timestamp of the block that confirms
+//! it, so UTXOs that are locked until a point in time cannot be spent before it (up to the
+//! clock skew that blocks may have). Likewise, a transaction can only be confirmed by blocks
+//! of at least its minimum block height, so UTXOs that are locked until a block height cannot
+//! be spent in earlier blocks.
+
+use crate::models::consensus::mast_hash::MastHash;
+use crate::prelude::{triton_vm, twenty_first};
+
+use itertools::Itertools;
+use serde::{Deserialize, Serialize};
+use std::fmt;
+use triton_vm::instruction::LabelledInstruction;
+use triton_vm::triton_asm;
+use twenty_first::shared_math::b_field_element::BFieldElement;
+use twenty_first::shared_math::digest::Digest;
+
+use super::transaction_kernel::{TransactionKernel, TransactionKernelField};
+
+/// The condition under which a time-locked UTXO is released.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash, Serialize, Deserialize)]
+pub enum Timelock {
+    /// Released from this time on, in milliseconds since the UNIX epoch
+    Time(u64),
+
+    /// Released from the block of this height on
+    Height(u64),
+}
+
+impl Timelock {
+    /// Return the instructions that check that the lock is released for the transaction
+    /// kernel whose MAST hash is given on standard input.
+    pub fn check(&self) -> Vec<LabelledInstruction> {
+        match self {
+            Timelock::Time(release_time) => release_time_check(*release_time),
+            Timelock::Height(release_height) => release_height_check(*release_height),
+        }
+    }
+
+    /// Return the secret input that the instructions of [`check`](Self::check) need.
+    pub fn witness(&self, kernel: &TransactionKernel) -> Vec<BFieldElement> {
+        match self {
+            Timelock::Time(_) => release_time_witness(kernel),
+            Timelock::Height(_) => release_height_witness(kernel),
+        }
+    }
+
+    /// Return true iff a transaction with the given timestamp, in milliseconds since the
+    /// UNIX epoch, and the given minimum block height can spend UTXOs with this lock. This
+    /// is the reference for [`check`](Self::check).
+    pub fn is_released(&self, timestamp: u64, minimum_block_height: u64) -> bool {
+        match self {
+            Timelock::Time(release_time) => is_released(*release_time, timestamp),
+            Timelock::Height(release_height) => minimum_block_height >= *release_height,
+        }
+    }
+}
+
+impl fmt::Display for Timelock {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Timelock::Time(release_time) => write!(f, "until {release_time} ms after the epoch"),
+            Timelock::Height(release_height) => write!(f, "until block height {release_height}"),
+        }
+    }
+}
+
+/// Return the instructions that read a field of the transaction kernel from secret input,
+/// assert that it is at least `threshold`, and hash it into its leaf of the kernel's Merkle
+/// tree.
+///
+/// The field `f` must satisfy `f - threshold < 2^63` in the field, which holds for all values
+/// from the threshold up to 2^63 more, and for none below it.
+fn divine_leaf_at_least(threshold: u64) -> Vec<LabelledInstruction> {
+    let minus_threshold = (-BFieldElement::new(threshold)).value();
+
+    triton_asm!(
+        // read the field, padded for hashing as a variable-length sequence
+        push 0 push 0 push 0 push 0 push 0 push 0 push 0 push 0
+        push 1
+        divine 1                    // _ 0 0 0 0 0 0 0 0 1 value
+
+        // assert that the value is not below the threshold
+        dup 0
+        push {minus_threshold}
+        add
+        split                       // _ 0 0 0 0 0 0 0 0 1 value hi lo
+        pop 1
+        push 2147483648
+        and
+        push 0
+        eq
+        assert                      // _ 0 0 0 0 0 0 0 0 1 value
+
+        // hash the value into the leaf digest
+        sponge_init
+        sponge_absorb               // _
+        sponge_squeeze              // _ s9 s8 s7 s6 s5 [leaf]
+        swap 5 pop 1
+        swap 5 pop 1
+        swap 5 pop 1
+        swap 5 pop 1
+        swap 5 pop 1                // _ [leaf]
+    )
+}
+
+/// Return the instructions that compare the root on top of the stack with the kernel MAST
+/// hash on standard input.
+fn assert_kernel_mast_hash() -> Vec<LabelledInstruction> {
+    triton_asm!(
+        read_io 5                   // _ [root] k4 k3 k2 k1 k0
+        swap 4
+        swap 1
+        swap 3
+        swap 1                      // _ [root] [kernel_mast_hash]
+        assert_vector
+        pop 5                       // _
+    )
+}
+
+/// Digests are reversed in secret input, such that their zeroth element ends up on top of
+/// the stack.
+fn reversed(digest: &Digest) -> Vec<BFieldElement> {
+    digest.values().into_iter().rev().collect_vec()
+}
+
+/// Return the instructions that check that the transaction kernel, whose MAST hash is
+/// given on standard input, has a timestamp of at least `release_time` milliseconds since
+/// the UNIX epoch. The timestamp and its authentication path are read from secret input,
+/// as produced by [`release_time_witness`].
+pub fn release_time_check(release_time: u64) -> Vec<LabelledInstruction> {
+    // The timestamp is leaf 5 of the 8 leaves of the kernel's Merkle tree, so it is node 13
+    // and its authentication path consists of nodes 12, 7 and 2. Nodes 12 and 2 are left
+    // siblings, node 7 is a right sibling.
+    triton_asm!(
+        divine 5                    // _ [sibling_7]
+        {&divine_leaf_at_least(release_time)}
+                                    // _ [sibling_7] [leaf]
+
+        // walk the authentication path up to the root
+        divine 5                    // _ [sibling_7] [leaf] [sibling_12]
+        hash                        // _ [sibling_7] [node_6]
+        hash                        // _ [node_3]
+        divine 5                    // _ [node_3] [sibling_2]
+        hash                        // _ [root]
+
+        {&assert_kernel_mast_hash()}
+    )
+}
+
+/// Return the secret input that the instructions of [`release_time_check`] need to verify
+/// the timestamp of the given kernel.
+pub fn release_time_witness(kernel: &TransactionKernel) -> Vec<BFieldElement> {
+    let authentication_path = kernel.mast_path(TransactionKernelField::Timestamp);
+
+    [
+        reversed(&authentication_path[1]),
+        vec![kernel.timestamp],
+        reversed(&authentication_path[0]),
+        reversed(&authentication_path[2]),
+    ]
+    .concat()
+}
+
+/// Return the instructions that check that the transaction kernel, whose MAST hash is
+/// given on standard input, has a minimum block height of at least `release_height`. The
+/// minimum block height and its authentication path are read from secret input, as
+/// produced by [`release_height_witness`].
+pub fn release_height_check(release_height: u64) -> Vec<LabelledInstruction> {
+    // The minimum block height is leaf 7 of the 8 leaves of the kernel's Merkle tree, so it
+    // is node 15 and its authentication path consists of nodes 14, 6 and 2, which are all
+    // left siblings.
+    triton_asm!(
+        {&divine_leaf_at_least(release_height)}
+                                    // _ [leaf]
+
+        // walk the authentication path up to the root
+        divine 5                    // _ [leaf] [sibling_14]
+        hash                        // _ [node_7]
+        divine 5                    // _ [node_7] [sibling_6]
+        hash                        // _ [node_3]
+        divine 5                    // _ [node_3] [sibling_2]
+        hash                        // _ [root]
+
+        {&assert_kernel_mast_hash()}
+    )
+}
+
+/// Return the secret input that the instructions of [`release_height_check`] need to
+/// verify the minimum block height of the given kernel.
+pub fn release_height_witness(kernel: &TransactionKernel) -> Vec<BFieldElement> {
+    let authentication_path = kernel.mast_path(TransactionKernelField::MinimumBlockHeight);
+
+    [
+        vec![kernel.minimum_block_height],
+        reversed(&authentication_path[0]),
+        reversed(&authentication_path[1]),
+        reversed(&authentication_path[2]),
+    ]
+    .concat()
+}
+
+/// Return true iff a UTXO that is locked until `release_time` can be spent by a transaction
+/// with the given timestamp, both in milliseconds since the UNIX epoch. This is the
+/// reference for [`release_time_check`].
+pub fn is_released(release_time: u64, timestamp: u64) -> bool {
+    timestamp >= release_time
+}
+
+#[cfg(test)]
+mod timelock_tests {
+    use rand::random;
+    use triton_vm::prelude::{NonDeterminism, Program, PublicInput};
+
+    use super::*;
+    use crate::models::blockchain::transaction::transaction_kernel::pseudorandom_transaction_kernel;
+
+    fn run_release_time_check(release_time: u64, kernel: &TransactionKernel) -> bool {
+        let program = Program::new(&[release_time_check(release_time), triton_asm!(halt)].concat());
+        program
+            .run(
+                PublicInput::new(kernel.mast_hash().values().to_vec()),
+                NonDeterminism::new(release_time_witness(kernel)),
+            )
+            .is_ok()
+    }
+
+    #[test]
+    fn release_time_check_agrees_with_reference() {
+        let mut kernel = pseudorandom_transaction_kernel(random(), 2, 2, 1);
+        kernel.timestamp = BFieldElement::new(1_700_000_000_000);
+        let timestamp = kernel.timestamp.value();
+
+        for release_time in [
+            0,
+            timestamp - 1,
+            timestamp,
+            timestamp + 1,
+            u64::from(u32::MAX),
+        ] {
+            assert_eq!(
+                is_released(release_time, timestamp),
+                run_release_time_check(release_time, &kernel),
+                "release time {release_time}"
+            );
+        }
+    }
+
+    fn run_timelock_check(timelock: Timelock, kernel: &TransactionKernel) -> bool {
+        let program = Program::new(&[timelock.check(), triton_asm!(halt)].concat());
+        program
+            .run(
+                PublicInput::new(kernel.mast_hash().values().to_vec()),
+                NonDeterminism::new(timelock.witness(kernel)),
+            )
+            .is_ok()
+    }
+
+    #[test]
+    fn release_height_check_agrees_with_reference() {
+        let mut kernel = pseudorandom_transaction_kernel(random(), 2, 2, 1);
+        kernel.minimum_block_height = BFieldElement::new(1000);
+        let timestamp = kernel.timestamp.value();
+        let minimum_block_height = kernel.minimum_block_height.value();
+
+        for release_height in [0, 999, 1000, 1001, u64::from(u32::MAX)] {
+            let timelock = Timelock::Height(release_height);
+            assert_eq!(
+                timelock.is_released(timestamp, minimum_block_height),
+                run_timelock_check(timelock, &kernel),
+                "release height {release_height}"
+            );
+        }
+    }
+
+    #[test]
+    fn release_height_check_rejects_height_of_other_kernel() {
+        let mut kernel = pseudorandom_transaction_kernel(random(), 2, 2, 1);
+        kernel.minimum_block_height = BFieldElement::new(1000);
+        let mut later_kernel = kernel.clone();
+        later_kernel.minimum_block_height = BFieldElement::new(2000);
+
+        let timelock = Timelock::Height(1500);
+        assert!(run_timelock_check(timelock, &later_kernel));
+        let program = Program::new(&[timelock.check(), triton_asm!(halt)].concat());
+        assert!(program
+            .run(
+                PublicInput::new(kernel.mast_hash().values().to_vec()),
+                NonDeterminism::new(timelock.witness(&later_kernel)),
+            )
+            .is_err());
+    }
+
+    #[test]
+    fn time_and_height_locks_check_their_own_field() {
+        let mut kernel = pseudorandom_transaction_kernel(random(), 2, 2, 1);
+        kernel.timestamp = BFieldElement::new(1_700_000_000_000);
+        kernel.minimum_block_height = BFieldElement::new(1000);
+
+        // A height lock is not released by a late timestamp, nor a time lock by a high
+        // minimum block height
+        assert!(!run_timelock_check(
+            Timelock::Height(1_600_000_000_000),
+            &kernel
+        ));
+        assert!(!run_timelock_check(
+            Timelock::Time(1_800_000_000_000),
+            &kernel
+        ));
+        assert!(run_timelock_check(Timelock::Height(1000), &kernel));
+        assert!(run_timelock_check(
+            Timelock::Time(1_700_000_000_000),
+            &kernel
+        ));
+    }
+
+    #[test]
+    fn release_time_check_rejects_timestamp_of_other_kernel() {
+        let mut kernel = pseudorandom_transaction_kernel(random(), 2, 2, 1);
+        kernel.timestamp = BFieldElement::new(1_700_000_000_000);
+        let mut later_kernel = kernel.clone();
+        later_kernel.timestamp = BFieldElement::new(1_800_000_000_000);
+
+        let program =
+            Program::new(&[release_time_check(1_750_000_000_000), triton_asm!(halt)].concat());
+        assert!(program
+            .run(
+                PublicInput::new(kernel.mast_hash().values().to_vec()),
+                NonDeterminism::new(release_time_witness(&later_kernel)),
+            )
+            .is_err());
+    }
+}
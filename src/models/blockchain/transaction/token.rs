@@ -0,0 +1,588 @@
+//! User-defined tokens. A token is a coin whose type script is generated from a
+//! [`TokenDefinition`], and whose state is the amount of tokens it holds.
+//!
+//! The token type script asserts that the transaction does not create tokens, unless one of
+//! its inputs is locked to the issuer's lock script. So the issuer can create new tokens
+//! by spending any UTXO of the issuing address, and everyone else can only pass tokens on.
+
+use crate::prelude::{triton_vm, twenty_first};
+
+use anyhow::{bail, Result};
+use bech32::{FromBase32, ToBase32, Variant};
+use serde::{Deserialize, Serialize};
+use tasm_lib::data_type::DataType;
+use tasm_lib::library::Library;
+use tasm_lib::list::contiguous_list::get_pointer_list::GetPointerList;
+use tasm_lib::list::ListType;
+use tasm_lib::memory::push_ram_to_stack::PushRamToStack;
+use tasm_lib::structure::tasm_object::TasmObject;
+use triton_vm::triton_asm;
+use twenty_first::shared_math::b_field_element::BFieldElement;
+use twenty_first::shared_math::tip5::Digest;
+
+use super::utxo::{Coin, TypeScript, Utxo};
+use super::validity::typescripts_halt::{read_type_script_utxos, TypeScriptUtxos};
+use crate::config_models::network::Network;
+use crate::models::state::wallet::address::generation_address::network_byte;
+
+/// The largest amount a token coin can hold. Bounding the amounts lets the type script sum
+/// them without wrapping around the field modulus.
+pub const MAX_TOKEN_AMOUNT: u64 = (1 << 48) - 1;
+
+/// Everything that determines the type script of a token: the lock script hash of the
+/// issuer, whose UTXOs must be spent to issue tokens, and a nonce that distinguishes the
+/// tokens of the same issuer.
+#[derive(Clone, Copy, Debug, Serialize, Deserialize, PartialEq, Eq)]
+pub struct TokenDefinition {
+    pub issuer_lock_script_hash: Digest,
+    pub nonce: Digest,
+}
+
+impl TokenDefinition {
+    pub fn new(issuer_lock_script_hash: Digest, nonce: Digest) -> Self {
+        Self {
+            issuer_lock_script_hash,
+            nonce,
+        }
+    }
+
+    /// Generate the type script. It learns the UTXOs of the transaction through
+    /// [`read_type_script_utxos`], which binds them to the salted hashes in the public
+    /// input, and recognizes the token coins by its own hash, which follows them.
+    pub fn type_script(&self) -> TypeScript {
+        let [nonce_0, nonce_1, nonce_2, nonce_3, nonce_4] =
+            self.nonce.values().map(|element| element.value());
+        let [issuer_0, issuer_1, issuer_2, issuer_3, issuer_4] = self
+            .issuer_lock_script_hash
+            .values()
+            .map(|element| element.value());
+
+        let mut library = Library::new();
+        let read_utxos = read_type_script_utxos(&mut library);
+        let read_digest = library.import(Box::new(PushRamToStack {
+            data_type: DataType::Digest,
+        }));
+        let get_pointer_list = library.import(Box::new(GetPointerList {
+            output_list_type: ListType::Unsafe,
+        }));
+
+        // field getters
+        let utxos_to_input_utxos = tasm_lib::field!(TypeScriptUtxos::input_utxos);
+        let utxos_to_output_utxos = tasm_lib::field!(TypeScriptUtxos::output_utxos);
+        let utxo_to_lock_script_hash = tasm_lib::field!(Utxo::lock_script_hash);
+        let utxo_to_coins = tasm_lib::field!(Utxo::coins);
+        let coin_to_type_script_hash = tasm_lib::field!(Coin::type_script_hash);
+        let coin_to_state = tasm_lib::field!(Coin::state);
+
+        let code = triton_asm!(
+            // the nonce only serves to make the type script unique
+            push {nonce_0} push {nonce_1} push {nonce_2} push {nonce_3} push {nonce_4}
+            pop 5
+
+            {&read_utxos}               // _ [kernel_mast_hash] *utxos
+            swap 5
+            pop 5                       // _ *utxos
+            read_io 5                   // _ *utxos [token]
+
+            // sum the token amounts of the inputs, and count the inputs of the issuer
+            dup 5
+            {&utxos_to_input_utxos}
+            call {get_pointer_list}     // _ *utxos [token] *[*utxo]
+            dup 0 read_mem 1 pop 1
+            push 0 push 0 push 0        // _ *utxos [token] *[*utxo] num_utxos 0 0 0
+            call token_utxos            // _ *utxos [token] *[*utxo] num_utxos num_utxos input_sum issuer_input_count
+            swap 4 pop 1 swap 2 pop 2   // _ *utxos [token] issuer_input_count input_sum
+
+            // sum the token amounts of the outputs
+            dup 6 dup 6 dup 6 dup 6 dup 6
+                                        // _ *utxos [token] issuer_input_count input_sum [token]
+            dup 12
+            {&utxos_to_output_utxos}
+            call {get_pointer_list}     // _ *utxos [token] issuer_input_count input_sum [token] *[*utxo]
+            dup 0 read_mem 1 pop 1
+            push 0 push 0 push 0
+            call token_utxos
+            swap 4 pop 1 swap 2 pop 2   // _ *utxos [token] issuer_input_count input_sum [token] issuer_output_count output_sum
+            swap 1 pop 1
+            swap 5 pop 5                // _ *utxos [token] issuer_input_count input_sum output_sum
+
+            // both sums are less than 2^62, so the difference is less than 2^62 iff the
+            // outputs do not exceed the inputs
+            push -1
+            mul
+            add
+            split
+            pop 1
+            push 3221225472
+            and
+            push 0
+            eq                          // _ *utxos [token] issuer_input_count is_conserved
+
+            // assert that the amount is conserved or that the issuer signed
+            add
+            push 0
+            eq
+            push 0
+            eq
+            assert
+            halt
+
+            // BEFORE: _ [token] *[*utxo] num_utxos i sum issuer_count
+            // AFTER:  _ [token] *[*utxo] num_utxos num_utxos sum' issuer_count'
+            token_utxos:
+                dup 3 dup 3 eq
+                skiz
+                    return
+                dup 4 push 1 add
+                dup 3 add
+                read_mem 1 pop 1        // _ [token] *[*utxo] num_utxos i sum issuer_count *utxo
+
+                dup 0
+                {&utxo_to_lock_script_hash}
+                call token_is_issuer    // _ [token] *[*utxo] num_utxos i sum issuer_count *utxo is_issuer
+                swap 1 swap 2 add       // _ [token] *[*utxo] num_utxos i sum *utxo issuer_count'
+                swap 2                  // _ [token] *[*utxo] num_utxos i issuer_count' *utxo sum
+
+                call token_utxo         // _ [token] *[*utxo] num_utxos i issuer_count' *utxo sum'
+                swap 1 pop 1
+                swap 1                  // _ [token] *[*utxo] num_utxos i sum' issuer_count'
+
+                swap 2 push 1 add swap 2
+                recurse
+
+            // BEFORE: _ *lock_script_hash
+            // AFTER:  _ is_issuer
+            token_is_issuer:
+                dup 0 read_mem 1 pop 1 push {issuer_0} eq
+                dup 1 push 1 add read_mem 1 pop 1 push {issuer_1} eq mul
+                dup 1 push 2 add read_mem 1 pop 1 push {issuer_2} eq mul
+                dup 1 push 3 add read_mem 1 pop 1 push {issuer_3} eq mul
+                dup 1 push 4 add read_mem 1 pop 1 push {issuer_4} eq mul
+                swap 1
+                pop 1
+                return
+
+            // BEFORE: _ [token] *[*utxo] num_utxos i issuer_count *utxo sum
+            // AFTER:  _ [token] *[*utxo] num_utxos i issuer_count *utxo sum'
+            token_utxo:
+                dup 1
+                {&utxo_to_coins}
+                call {get_pointer_list} // _ [token] *[*utxo] num_utxos i issuer_count *utxo sum *[*coin]
+                swap 1
+                dup 1 read_mem 1 pop 1
+                swap 1
+                push 0
+                swap 1                  // _ [token] *[*utxo] num_utxos i issuer_count *utxo *[*coin] num_coins 0 sum
+                call token_coins        // _ [token] *[*utxo] num_utxos i issuer_count *utxo *[*coin] num_coins num_coins sum'
+                swap 3
+                pop 3
+                return
+
+            // BEFORE: _ [token] *[*utxo] num_utxos i issuer_count *utxo *[*coin] num_coins j sum
+            // AFTER:  _ [token] *[*utxo] num_utxos i issuer_count *utxo *[*coin] num_coins num_coins sum'
+            token_coins:
+                dup 2 dup 2 eq
+                skiz
+                    return
+                dup 3 push 1 add
+                dup 2 add
+                read_mem 1 pop 1        // _ [token] ... *[*coin] num_coins j sum *coin
+
+                // compare the type script hash of the coin with the token's
+                dup 0
+                {&coin_to_type_script_hash}
+                call {read_digest}      // _ [token] ... *[*coin] num_coins j sum *coin [coin_type_script_hash]
+                dup 15 eq
+                swap 1 dup 16 eq mul
+                swap 1 dup 16 eq mul
+                swap 1 dup 16 eq mul
+                swap 1 dup 16 eq mul    // _ [token] ... *[*coin] num_coins j sum *coin is_token
+                skiz
+                    call token_amount   // _ [token] ... *[*coin] num_coins j sum' *coin
+                pop 1
+
+                swap 1 push 1 add swap 1
+                recurse
+
+            // BEFORE: _ sum *coin
+            // AFTER:  _ sum' *coin
+            token_amount:
+                // the state of a token coin is its amount
+                dup 0
+                {&coin_to_state}        // _ sum *coin *state
+                dup 0 read_mem 1 pop 1
+                push 1
+                eq
+                assert
+                push 1 add
+                read_mem 1 pop 1        // _ sum *coin amount
+
+                // assert that the amount is less than 2^48
+                dup 0
+                split
+                pop 1
+                push 4294901760
+                and
+                push 0
+                eq
+                assert
+
+                // assert that the sum stays below 2^62
+                swap 1 swap 2
+                add                     // _ *coin sum'
+                dup 0
+                split
+                pop 1
+                push 3221225472
+                and
+                push 0
+                eq
+                assert
+                swap 1
+                return
+        );
+
+        [code, library.all_imports()].concat().into()
+    }
+
+    pub fn type_script_hash(&self) -> Digest {
+        self.type_script().hash()
+    }
+
+    /// A coin holding the given amount of this token.
+    pub fn coin(&self, amount: u64) -> Result<Coin> {
+        if amount > MAX_TOKEN_AMOUNT {
+            bail!("Token amount {amount} exceeds the maximum of {MAX_TOKEN_AMOUNT}");
+        }
+
+        Ok(token_coin(self.type_script_hash(), amount))
+    }
+
+    fn get_hrp(network: Network) -> String {
+        // NTOKEN: Neptune token definition
+        let mut hrp = "ntoken".to_string();
+        hrp.push(network_byte(network));
+        hrp
+    }
+
+    pub fn to_bech32m(&self, network: Network) -> Result<String> {
+        let hrp = Self::get_hrp(network);
+        let payload = bincode::serialize(self)?;
+        match bech32::encode(&hrp, payload.to_base32(), Variant::Bech32m) {
+            Ok(enc) => Ok(enc),
+            Err(e) => bail!("Could not encode token definition as bech32m because error: {e}"),
+        }
+    }
+
+    pub fn from_bech32m(encoded: &str, network: Network) -> Result<Self> {
+        let (hrp, data, variant) = bech32::decode(encoded)?;
+
+        if variant != Variant::Bech32m {
+            bail!("Can only decode bech32m token definitions.");
+        }
+
+        if hrp != Self::get_hrp(network) {
+            bail!("Could not decode bech32m token definition because of invalid prefix");
+        }
+
+        let payload = Vec::<u8>::from_base32(&data)?;
+        match bincode::deserialize(&payload) {
+            Ok(definition) => Ok(definition),
+            Err(e) => bail!("Could not decode bech32m token definition because of error: {e}"),
+        }
+    }
+}
+
+/// A coin holding the given amount of the token with the given type script hash. The
+/// amount must not exceed [`MAX_TOKEN_AMOUNT`].
+pub fn token_coin(type_script_hash: Digest, amount: u64) -> Coin {
+    Coin {
+        type_script_hash,
+        state: vec![BFieldElement::new(amount)],
+    }
+}
+
+/// The amount of the token with the given type script hash that the UTXO holds.
+pub fn token_amount(utxo: &Utxo, type_script_hash: Digest) -> u64 {
+    utxo.coins
+        .iter()
+        .filter(|coin| coin.type_script_hash == type_script_hash)
+        .map(|coin| match coin.state[..] {
+            [amount] => amount.value(),
+            _ => 0,
+        })
+        .sum()
+}
+
+/// Return true iff the token type script of the given definition halts for a transaction
+/// with the given inputs and outputs. This is the reference for
+/// [`TokenDefinition::type_script`].
+pub fn is_valid_token_transfer(
+    definition: &TokenDefinition,
+    input_utxos: &[Utxo],
+    output_utxos: &[Utxo],
+) -> bool {
+    let type_script_hash = definition.type_script_hash();
+    let coins = |utxos: &[Utxo]| {
+        utxos
+            .iter()
+            .flat_map(|utxo| utxo.coins.clone())
+            .filter(|coin| coin.type_script_hash == type_script_hash)
+            .collect::<Vec<_>>()
+    };
+    let sum = |coins: Vec<Coin>| {
+        let mut sum = 0u64;
+        for coin in coins {
+            let [amount] = coin.state[..] else {
+                return None;
+            };
+            if amount.value() > MAX_TOKEN_AMOUNT {
+                return None;
+            }
+            sum += amount.value();
+            if sum >= 1 << 62 {
+                return None;
+            }
+        }
+        Some(sum)
+    };
+
+    let (Some(input_sum), Some(output_sum)) = (sum(coins(input_utxos)), sum(coins(output_utxos)))
+    else {
+        return false;
+    };
+    let is_issuance = input_utxos
+        .iter()
+        .any(|utxo| utxo.lock_script_hash == definition.issuer_lock_script_hash);
+
+    is_issuance || output_sum <= input_sum
+}
+
+#[cfg(test)]
+mod token_tests {
+    use rand::random;
+    use triton_vm::prelude::PublicInput;
+
+    use super::*;
+
+    fn halts(
+        definition: &TokenDefinition,
+        utxos: &TypeScriptUtxos,
+        public_input: &[BFieldElement],
+    ) -> bool {
+        definition
+            .type_script()
+            .program
+            .run(
+                PublicInput::new(public_input.to_vec()),
+                utxos.nondeterminism(),
+            )
+            .is_ok()
+    }
+
+    fn type_script_utxos(input_utxos: &[Utxo], output_utxos: &[Utxo]) -> TypeScriptUtxos {
+        TypeScriptUtxos {
+            input_utxos: input_utxos.to_vec(),
+            input_utxos_salt: random(),
+            output_utxos: output_utxos.to_vec(),
+            output_utxos_salt: random(),
+        }
+    }
+
+    fn type_script_halts(
+        definition: &TokenDefinition,
+        input_utxos: &[Utxo],
+        output_utxos: &[Utxo],
+    ) -> bool {
+        let utxos = type_script_utxos(input_utxos, output_utxos);
+        let public_input = utxos.public_input(random(), definition.type_script_hash());
+        halts(definition, &utxos, &public_input)
+    }
+
+    fn token_utxo(lock_script_hash: Digest, coins: Vec<Coin>) -> Utxo {
+        Utxo {
+            lock_script_hash,
+            coins,
+        }
+    }
+
+    #[test]
+    fn token_type_script_agrees_with_reference() {
+        let definition = TokenDefinition::new(random(), random());
+        let other_token = TokenDefinition::new(random(), random());
+        let issuer = definition.issuer_lock_script_hash;
+        let coin = |amount| token_coin(definition.type_script_hash(), amount);
+        let holder: Digest = random();
+
+        let cases = vec![
+            // transfers that conserve or burn tokens
+            (vec![], vec![]),
+            (
+                vec![token_utxo(holder, vec![coin(10)])],
+                vec![
+                    token_utxo(random(), vec![coin(7)]),
+                    token_utxo(holder, vec![coin(3)]),
+                ],
+            ),
+            (vec![token_utxo(holder, vec![coin(10), coin(5)])], vec![]),
+            // transfers that create tokens
+            (
+                vec![token_utxo(holder, vec![coin(10)])],
+                vec![token_utxo(random(), vec![coin(11)])],
+            ),
+            (vec![], vec![token_utxo(random(), vec![coin(1)])]),
+            // other tokens do not count
+            (
+                vec![token_utxo(
+                    holder,
+                    vec![token_coin(other_token.type_script_hash(), 100)],
+                )],
+                vec![token_utxo(random(), vec![coin(1)])],
+            ),
+            // issuance
+            (
+                vec![token_utxo(issuer, vec![])],
+                vec![token_utxo(random(), vec![coin(MAX_TOKEN_AMOUNT)])],
+            ),
+            (
+                vec![
+                    token_utxo(holder, vec![coin(1)]),
+                    token_utxo(issuer, vec![]),
+                ],
+                vec![token_utxo(random(), vec![coin(1000)])],
+            ),
+            // amounts out of range
+            (
+                vec![token_utxo(holder, vec![coin(MAX_TOKEN_AMOUNT + 1)])],
+                vec![],
+            ),
+            (
+                vec![token_utxo(issuer, vec![])],
+                vec![token_utxo(random(), vec![coin(MAX_TOKEN_AMOUNT + 1)])],
+            ),
+        ];
+
+        for (index, (input_utxos, output_utxos)) in cases.into_iter().enumerate() {
+            assert_eq!(
+                is_valid_token_transfer(&definition, &input_utxos, &output_utxos),
+                type_script_halts(&definition, &input_utxos, &output_utxos),
+                "case {index}"
+            );
+        }
+    }
+
+    #[test]
+    fn token_type_script_rejects_malformed_coins() {
+        let definition = TokenDefinition::new(random(), random());
+        let malformed_coin = Coin {
+            type_script_hash: definition.type_script_hash(),
+            state: vec![BFieldElement::new(5), BFieldElement::new(5)],
+        };
+        let input_utxos = vec![token_utxo(random(), vec![malformed_coin])];
+        assert!(!is_valid_token_transfer(&definition, &input_utxos, &[]));
+        assert!(!type_script_halts(&definition, &input_utxos, &[]));
+    }
+
+    #[test]
+    fn token_type_script_rejects_utxos_of_another_transaction() {
+        let definition = TokenDefinition::new(random(), random());
+        let coin = |amount| token_coin(definition.type_script_hash(), amount);
+        let holder: Digest = random();
+        let input_utxos = vec![token_utxo(holder, vec![coin(10)])];
+        let output_utxos = vec![token_utxo(random(), vec![coin(10)])];
+        let utxos = type_script_utxos(&input_utxos, &output_utxos);
+        let public_input = utxos.public_input(random(), definition.type_script_hash());
+        assert!(halts(&definition, &utxos, &public_input));
+
+        // an input of the issuer that the transaction does not spend
+        let mut forged_issuer_input = utxos.clone();
+        forged_issuer_input
+            .input_utxos
+            .push(token_utxo(definition.issuer_lock_script_hash, vec![]));
+        forged_issuer_input.output_utxos[0] = token_utxo(random(), vec![coin(1000)]);
+        assert!(is_valid_token_transfer(
+            &definition,
+            &forged_issuer_input.input_utxos,
+            &forged_issuer_input.output_utxos
+        ));
+        assert!(!halts(&definition, &forged_issuer_input, &public_input));
+
+        // an output that holds fewer tokens than the transaction creates
+        let mut hidden_inflation = utxos.clone();
+        hidden_inflation.output_utxos[0] = token_utxo(random(), vec![coin(1)]);
+        let inflated_utxos =
+            type_script_utxos(&input_utxos, &[token_utxo(random(), vec![coin(11)])]);
+        let inflated_public_input =
+            inflated_utxos.public_input(random(), definition.type_script_hash());
+        assert!(!halts(&definition, &inflated_utxos, &inflated_public_input));
+        assert!(!halts(
+            &definition,
+            &hidden_inflation,
+            &inflated_public_input
+        ));
+    }
+
+    #[test]
+    fn token_type_script_rejects_amounts_above_maximum() {
+        let definition = TokenDefinition::new(random(), random());
+        let issuer_input = token_utxo(definition.issuer_lock_script_hash, vec![]);
+        let output = |amount| {
+            token_utxo(
+                random(),
+                vec![token_coin(definition.type_script_hash(), amount)],
+            )
+        };
+
+        assert!(type_script_halts(
+            &definition,
+            &[issuer_input.clone()],
+            &[output(MAX_TOKEN_AMOUNT)]
+        ));
+        assert!(!type_script_halts(
+            &definition,
+            &[issuer_input],
+            &[output(MAX_TOKEN_AMOUNT + 1)]
+        ));
+    }
+
+    #[test]
+    fn token_type_script_only_counts_its_own_coins() {
+        let definition = TokenDefinition::new(random(), random());
+        let holder: Digest = random();
+        let input_utxos = vec![token_utxo(holder, vec![token_coin(random(), 100)])];
+        let output_utxos = vec![token_utxo(
+            random(),
+            vec![token_coin(definition.type_script_hash(), 1)],
+        )];
+        let utxos = type_script_utxos(&input_utxos, &output_utxos);
+        assert!(!halts(
+            &definition,
+            &utxos,
+            &utxos.public_input(random(), definition.type_script_hash())
+        ));
+
+        // passing the coins on is fine
+        let utxos = type_script_utxos(&output_utxos, &output_utxos);
+        assert!(halts(
+            &definition,
+            &utxos,
+            &utxos.public_input(random(), definition.type_script_hash())
+        ));
+    }
+
+    #[test]
+    fn token_definitions_are_distinct_and_survive_bech32m() {
+        let issuer: Digest = random();
+        let definition = TokenDefinition::new(issuer, random());
+        let other = TokenDefinition::new(issuer, random());
+        assert_ne!(definition.type_script_hash(), other.type_script_hash());
+        assert!(definition.coin(MAX_TOKEN_AMOUNT + 1).is_err());
+
+        let encoded = definition.to_bech32m(Network::Alpha).unwrap();
+        assert_eq!(
+            definition,
+            TokenDefinition::from_bech32m(&encoded, Network::Alpha).unwrap()
+        );
+        assert!(TokenDefinition::from_bech32m(&encoded, Network::Testnet).is_err());
+    }
+}
@@ -0,0 +1,220 @@
+use crate::models::consensus::mast_hash::MastHash;
+use crate::prelude::twenty_first;
+
+use get_size::GetSize;
+use itertools::Itertools;
+use serde::{Deserialize, Serialize};
+use std::fmt;
+use twenty_first::shared_math::b_field_element::BFieldElement;
+use twenty_first::shared_math::digest::Digest;
+use twenty_first::util_types::algebraic_hasher::AlgebraicHasher;
+
+use super::neptune_coins::NeptuneCoins;
+use super::validity::TransactionDerivation;
+use super::{PublicAnnouncement, Transaction};
+use crate::models::blockchain::shared::Hash;
+use crate::models::consensus::Witness;
+use crate::util_types::mutator_set::removal_record::AbsoluteIndexSet;
+
+/// What supports the validity of a transaction.
+#[derive(Clone, Copy, Debug, Serialize, Deserialize, PartialEq, Eq)]
+pub enum TransactionWitnessType {
+    /// The secret data of the transaction, which only its creator should hold
+    Primitive,
+
+    /// The claims of the base case, supported by proofs or secret witnesses
+    ValidationLogic,
+
+    /// A single proof of the validity of the transaction
+    SingleProof,
+
+    /// A claim that the transaction is the merger of two others
+    Merge,
+
+    /// A claim that the transaction is another one updated to a later mutator set
+    Update,
+
+    /// Nothing; the transaction is taken on faith
+    Faith,
+}
+
+impl fmt::Display for TransactionWitnessType {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            TransactionWitnessType::Primitive => write!(f, "primitive witness"),
+            TransactionWitnessType::ValidationLogic => write!(f, "validation logic"),
+            TransactionWitnessType::SingleProof => write!(f, "single proof"),
+            TransactionWitnessType::Merge => write!(f, "merge claim"),
+            TransactionWitnessType::Update => write!(f, "update claim"),
+            TransactionWitnessType::Faith => write!(f, "faith"),
+        }
+    }
+}
+
+/// The sizes in bytes of the parts of a transaction, as measured by `GetSize`.
+#[derive(Clone, Copy, Debug, Serialize, Deserialize, PartialEq, Eq)]
+pub struct TransactionSizes {
+    pub total: usize,
+    pub kernel: usize,
+    pub inputs: usize,
+    pub outputs: usize,
+    pub public_announcements: usize,
+    pub witness: usize,
+}
+
+/// The fields of a transaction in readable form, for inspecting transactions without a
+/// debugger.
+#[derive(Clone, Debug, Serialize, Deserialize, PartialEq, Eq)]
+pub struct TransactionDetails {
+    pub txid: Digest,
+    pub kernel_hash: Digest,
+
+    /// The absolute index sets of the removal records of the inputs
+    pub inputs: Vec<AbsoluteIndexSet>,
+
+    /// The canonical commitments of the addition records of the outputs
+    pub outputs: Vec<Digest>,
+
+    pub fee: NeptuneCoins,
+    pub coinbase: Option<NeptuneCoins>,
+
+    // number of milliseconds since unix epoch
+    pub timestamp: BFieldElement,
+
+    pub public_announcements: Vec<PublicAnnouncement>,
+    pub mutator_set_hash: Digest,
+    pub witness_type: TransactionWitnessType,
+    pub sizes: TransactionSizes,
+}
+
+impl TransactionDetails {
+    pub fn new(transaction: &Transaction) -> Self {
+        let kernel = &transaction.kernel;
+        let witness_type = match &transaction.witness {
+            Witness::Primitive(_) => TransactionWitnessType::Primitive,
+            Witness::ValidationLogic(_) => TransactionWitnessType::ValidationLogic,
+            Witness::SingleProof(_) => TransactionWitnessType::SingleProof,
+            Witness::Derivation(TransactionDerivation::Merge(_)) => TransactionWitnessType::Merge,
+            Witness::Derivation(TransactionDerivation::Update(_)) => TransactionWitnessType::Update,
+            Witness::Faith => TransactionWitnessType::Faith,
+        };
+        let sizes = TransactionSizes {
+            total: transaction.get_size(),
+            kernel: kernel.get_size(),
+            inputs: kernel.inputs.get_size(),
+            outputs: kernel.outputs.get_size(),
+            public_announcements: kernel.public_announcements.get_size(),
+            witness: transaction.witness.get_size(),
+        };
+
+        Self {
+            txid: Hash::hash(transaction),
+            kernel_hash: kernel.mast_hash(),
+            inputs: kernel
+                .inputs
+                .iter()
+                .map(|removal_record| removal_record.absolute_indices.clone())
+                .collect_vec(),
+            outputs: kernel
+                .outputs
+                .iter()
+                .map(|addition_record| addition_record.canonical_commitment)
+                .collect_vec(),
+            fee: kernel.fee,
+            coinbase: kernel.coinbase,
+            timestamp: kernel.timestamp,
+            public_announcements: kernel.public_announcements.clone(),
+            mutator_set_hash: kernel.mutator_set_hash,
+            witness_type,
+            sizes,
+        }
+    }
+}
+
+impl fmt::Display for TransactionDetails {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        writeln!(f, "Transaction {}", self.txid)?;
+        writeln!(f, "Kernel hash: {}", self.kernel_hash)?;
+        writeln!(f, "Inputs: {}", self.inputs.len())?;
+        for (i, absolute_indices) in self.inputs.iter().enumerate() {
+            writeln!(
+                f,
+                "  {i}: absolute indices {}",
+                absolute_indices.to_array().iter().join(", ")
+            )?;
+        }
+        writeln!(f, "Outputs: {}", self.outputs.len())?;
+        for (i, canonical_commitment) in self.outputs.iter().enumerate() {
+            writeln!(f, "  {i}: canonical commitment {canonical_commitment}")?;
+        }
+        writeln!(f, "Fee: {}", self.fee)?;
+        match self.coinbase {
+            Some(coinbase) => writeln!(f, "Coinbase: {coinbase}")?,
+            None => writeln!(f, "Coinbase: none")?,
+        }
+        writeln!(f, "Timestamp: {} ms since the UNIX epoch", self.timestamp)?;
+        writeln!(
+            f,
+            "Public announcements: {}",
+            self.public_announcements.len()
+        )?;
+        for (i, public_announcement) in self.public_announcements.iter().enumerate() {
+            writeln!(
+                f,
+                "  {i}: [{}]",
+                public_announcement.message.iter().join(", ")
+            )?;
+        }
+        writeln!(f, "Mutator set hash: {}", self.mutator_set_hash)?;
+        writeln!(f, "Witness: {}", self.witness_type)?;
+        writeln!(f, "Size: {} bytes", self.sizes.total)?;
+        writeln!(f, "  kernel: {} bytes", self.sizes.kernel)?;
+        writeln!(f, "    inputs: {} bytes", self.sizes.inputs)?;
+        writeln!(f, "    outputs: {} bytes", self.sizes.outputs)?;
+        writeln!(
+            f,
+            "    public announcements: {} bytes",
+            self.sizes.public_announcements
+        )?;
+        write!(f, "  witness: {} bytes", self.sizes.witness)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::tests::shared::make_mock_transaction;
+    use crate::util_types::mutator_set::addition_record::pseudorandom_addition_record;
+    use crate::util_types::mutator_set::removal_record::pseudorandom_removal_record;
+    use rand::random;
+
+    #[test]
+    fn details_describe_the_kernel_and_survive_encoding() {
+        let transaction = make_mock_transaction(
+            vec![pseudorandom_removal_record(random())],
+            vec![
+                pseudorandom_addition_record(random()),
+                pseudorandom_addition_record(random()),
+            ],
+        );
+        let details = TransactionDetails::new(&transaction);
+
+        assert_eq!(Hash::hash(&transaction), details.txid);
+        assert_eq!(
+            transaction.kernel.inputs[0].absolute_indices,
+            details.inputs[0]
+        );
+        assert_eq!(
+            transaction.kernel.outputs[1].canonical_commitment,
+            details.outputs[1]
+        );
+        assert_eq!(TransactionWitnessType::Faith, details.witness_type);
+        assert!(details.sizes.kernel >= details.sizes.inputs + details.sizes.outputs);
+        assert!(details.sizes.total >= details.sizes.kernel);
+
+        // transactions are decoded from the bytes that peers send
+        let bytes = bincode::serialize(&transaction).unwrap();
+        let decoded: Transaction = bincode::deserialize(&bytes).unwrap();
+        assert_eq!(details, TransactionDetails::new(&decoded));
+    }
+}
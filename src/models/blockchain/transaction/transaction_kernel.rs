@@ -40,6 +40,9 @@ pub struct TransactionKernel {
     pub timestamp: BFieldElement,
 
     pub mutator_set_hash: Digest,
+
+    // the transaction is only valid in blocks of at least this height
+    pub minimum_block_height: BFieldElement,
 }
 
 #[derive(Debug, Clone)]
@@ -51,6 +54,7 @@ pub enum TransactionKernelField {
     Coinbase,
     Timestamp,
     MutatorSetHash,
+    MinimumBlockHeight,
 }
 
 impl HasDiscriminant for TransactionKernelField {
@@ -78,6 +82,8 @@ impl MastHash for TransactionKernel {
 
         let mutator_set_hash_sequence = self.mutator_set_hash.encode();
 
+        let minimum_block_height_sequence = self.minimum_block_height.encode();
+
         vec![
             input_utxos_sequence,
             output_utxos_sequence,
@@ -86,6 +92,7 @@ impl MastHash for TransactionKernel {
             coinbase_sequence,
             timestamp_sequence,
             mutator_set_hash_sequence,
+            minimum_block_height_sequence,
         ]
     }
 }
@@ -119,6 +126,7 @@ pub fn pseudorandom_transaction_kernel(
     let coinbase = pseudorandom_option(rng.gen(), pseudorandom_amount(rng.gen::<[u8; 32]>()));
     let timestamp: BFieldElement = rng.gen();
     let mutator_set_hash: Digest = rng.gen();
+    let minimum_block_height = BFieldElement::new(rng.gen::<u32>().into());
 
     TransactionKernel {
         inputs,
@@ -128,6 +136,7 @@ pub fn pseudorandom_transaction_kernel(
         coinbase,
         timestamp,
         mutator_set_hash,
+        minimum_block_height,
     }
 }
 
@@ -191,6 +200,7 @@ pub mod transaction_kernel_tests {
             coinbase: None,
             timestamp: Default::default(),
             mutator_set_hash: rng.gen::<Digest>(),
+            minimum_block_height: Default::default(),
         };
         let encoded = kernel.encode();
         println!(
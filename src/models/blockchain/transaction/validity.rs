@@ -1,27 +1,39 @@
-use crate::prelude::twenty_first;
+use crate::models::consensus::mast_hash::MastHash;
+use crate::prelude::{triton_vm, twenty_first};
 
 pub mod kernel_to_lock_scripts;
 pub mod kernel_to_type_scripts;
 pub mod lockscripts_halt;
+pub mod merge;
 pub mod removal_records_integrity;
 pub mod tasm;
 pub mod typescripts_halt;
+pub mod update;
 
 use anyhow::{Ok, Result};
 use get_size::GetSize;
+use itertools::Itertools;
 use serde::{Deserialize, Serialize};
-use tracing::info;
+use tasm_lib::traits::compiled_program::CompiledProgram;
+use tracing::warn;
+use triton_vm::prelude::BFieldElement;
 use twenty_first::shared_math::bfield_codec::BFieldCodec;
+use twenty_first::shared_math::tip5::{Digest, DIGEST_LENGTH};
+use twenty_first::util_types::algebraic_hasher::AlgebraicHasher;
 
+use crate::models::blockchain::shared::Hash;
 use crate::models::consensus::ValidationLogic;
 
 use self::lockscripts_halt::LockScriptsHalt;
+use self::merge::Merge;
 use self::removal_records_integrity::RemovalRecordsIntegrity;
+use self::update::Update;
 use self::{
     kernel_to_lock_scripts::KernelToLockScripts, kernel_to_type_scripts::KernelToTypeScripts,
     typescripts_halt::TypeScriptsHalt,
 };
 use super::transaction_kernel::TransactionKernel;
+use super::utxo::Utxo;
 use super::TransactionPrimitiveWitness;
 
 /// The validity of a transaction, in the base case, decomposes into
@@ -31,19 +43,39 @@ pub struct TransactionValidationLogic {
     // programs: [lock_script], input: transaction kernel mast hash, witness: secret spending key, output: []
     pub lock_scripts_halt: LockScriptsHalt,
 
-    // program: todo, input: transaction kernel mast hash, witness: input utxos, utxo mast auth path, output: hashes of lock scripts
+    // program: output lock script hashes, input: salted input utxos hash, witness: input utxos + salt, output: hashes of lock scripts
     pub kernel_to_lock_scripts: KernelToLockScripts,
 
-    // program: recompute swbf indices, input: transaction kernel mast hash, witness: inputs + mutator set accumulator, output: []
+    // program: recompute swbf indices, input: transaction kernel mast hash, witness: inputs + mutator set accumulator, output: salted input utxos hash
     pub removal_records_integrity: RemovalRecordsIntegrity,
 
-    // program: todo, input: transaction kernel mast hash, witness: outputs + kernel mast auth path + coins, output: hashes of type scripts
+    // program: check coins against listed type scripts, input: transaction kernel mast hash + salted input utxos hash + salted output utxos hash, witness: inputs + outputs + kernel, output: hashes of type scripts
     pub kernel_to_typescripts: KernelToTypeScripts,
 
-    // programs: [type script], input: transaction kernel mast hash, witness: inputs + outputs + any, output: []
+    // programs: [type script], input: same as kernel to type scripts + type script hash, witness: inputs + outputs + salts, output: []
     pub type_scripts_halt: TypeScriptsHalt,
 }
 
+/// The hash that links the claims about the same list of UTXOs. The salt keeps the claims
+/// from revealing the UTXOs, whose contents are otherwise easy to guess.
+pub fn salted_utxos_hash(utxos: &[Utxo], salt: Digest) -> Digest {
+    Hash::hash_pair(Hash::hash(&utxos.to_vec()), salt)
+}
+
+/// Split the output of a claim into digests, if it is a list of digests.
+fn output_digests(output: &[BFieldElement]) -> Option<Vec<Digest>> {
+    if output.len() % DIGEST_LENGTH != 0 {
+        return None;
+    }
+
+    Some(
+        output
+            .chunks(DIGEST_LENGTH)
+            .map(|chunk| Digest::new(chunk.try_into().unwrap()))
+            .collect_vec(),
+    )
+}
+
 impl TransactionValidationLogic {
     pub fn new_from_primitive_witness(
         primitive_witness: &TransactionPrimitiveWitness,
@@ -69,23 +101,147 @@ impl TransactionValidationLogic {
     }
 
     pub fn prove(&mut self) -> Result<()> {
-        self.lock_scripts_halt.prove()?;
-
+        self.lock_scripts_halt.prove_claims()?;
+        self.kernel_to_lock_scripts.prove()?;
         self.removal_records_integrity.prove()?;
+        self.kernel_to_typescripts.prove()?;
+        self.type_scripts_halt.prove_claims()?;
 
-        // not supported yet:
-        // self.kernel_to_lock_scripts.prove()?;
-        // self.kernel_to_typescripts.prove()?;
-        // self.type_scripts_halt.prove()?;
         Ok(())
     }
 
-    pub fn verify(&self) -> bool {
-        info!("validity logic for 'kernel_to_lock_scripts', 'kernel_to_type_scripts', 'type_scripts_halt' not implemented yet.");
+    /// Verify that the subclaims are about the given kernel, that they link up, and that
+    /// each of them holds.
+    ///
+    /// Removal records integrity outputs the salted hash of the input UTXOs, from which
+    /// kernel-to-lock-scripts derives the lock scripts that must halt, and which together
+    /// with the kernel's outputs determines the type scripts that must halt. The type
+    /// scripts get the same salted UTXO hashes as the claim that lists them.
+    pub fn verify(&self, kernel: &TransactionKernel) -> bool {
+        let kernel_hash = kernel.mast_hash();
+        let reversed = |digest: Digest| digest.reversed().values().to_vec();
+
+        let program_digest =
+            |program: triton_vm::prelude::Program| Hash::hash_varlen(&program.encode());
+        if self.removal_records_integrity.claim().program_digest
+            != program_digest(RemovalRecordsIntegrity::program())
+            || self.kernel_to_lock_scripts.claim().program_digest
+                != program_digest(KernelToLockScripts::program())
+            || self.kernel_to_typescripts.claim().program_digest
+                != program_digest(KernelToTypeScripts::program())
+        {
+            warn!("Transaction validity claims are about the wrong programs");
+            return false;
+        }
+
+        // removal records integrity links the kernel to the salted input utxos hash
+        let removal_records_integrity_claim = self.removal_records_integrity.claim();
+        if removal_records_integrity_claim.input != reversed(kernel_hash) {
+            warn!("Removal records integrity claim is not about the transaction kernel");
+            return false;
+        }
+        let salted_input_utxos_hash =
+            match output_digests(&removal_records_integrity_claim.output).as_deref() {
+                Some(&[salted_input_utxos_hash]) => salted_input_utxos_hash,
+                _ => {
+                    warn!("Removal records integrity claim must output one digest");
+                    return false;
+                }
+            };
+
+        // the lock scripts that must halt are those of the input utxos
+        let kernel_to_lock_scripts_claim = self.kernel_to_lock_scripts.claim();
+        if kernel_to_lock_scripts_claim.input != reversed(salted_input_utxos_hash) {
+            warn!("Lock scripts claim is not about the input utxos of the transaction");
+            return false;
+        }
+        let lock_script_hashes = self
+            .lock_scripts_halt
+            .supported_claims
+            .iter()
+            .map(|supported_claim| supported_claim.claim.program_digest)
+            .collect_vec();
+        if output_digests(&kernel_to_lock_scripts_claim.output) != Some(lock_script_hashes)
+            || self
+                .lock_scripts_halt
+                .supported_claims
+                .iter()
+                .any(|supported_claim| supported_claim.claim.input != kernel_hash.values())
+        {
+            warn!("Lock script claims do not match the lock scripts of the input utxos");
+            return false;
+        }
+
+        // every type script of the inputs and outputs must halt
+        let kernel_to_type_scripts_claim = self.kernel_to_typescripts.claim();
+        let transaction_digests =
+            [reversed(kernel_hash), reversed(salted_input_utxos_hash)].concat();
+        if kernel_to_type_scripts_claim.input.len() != transaction_digests.len() + DIGEST_LENGTH
+            || !kernel_to_type_scripts_claim
+                .input
+                .starts_with(&transaction_digests)
+        {
+            warn!("Type scripts claim is not about the transaction");
+            return false;
+        }
+        let Some(type_script_hashes) = output_digests(&kernel_to_type_scripts_claim.output) else {
+            warn!("Type scripts claim must output a list of digests");
+            return false;
+        };
+        if type_script_hashes.iter().any(|type_script_hash| {
+            !self
+                .type_scripts_halt
+                .supported_claims
+                .iter()
+                .any(|supported_claim| {
+                    supported_claim.claim.program_digest == *type_script_hash
+                        && supported_claim.claim.input
+                            == [
+                                kernel_to_type_scripts_claim.input.clone(),
+                                reversed(*type_script_hash),
+                            ]
+                            .concat()
+                })
+        }) {
+            warn!("Type script claims do not cover the type scripts of the transaction");
+            return false;
+        }
+
         self.lock_scripts_halt.verify()
-            // && self.kernel_to_lock_scripts.verify()
+            && self.kernel_to_lock_scripts.verify()
             && self.removal_records_integrity.verify()
-        // && self.kernel_to_typescripts.verify()
-        // && self.type_scripts_halt.verify()
+            && self.kernel_to_typescripts.verify()
+            && self.type_scripts_halt.verify()
+    }
+}
+
+/// A claim that a transaction was derived from others, in place of the subclaims of the
+/// base case. The claim cannot verify the transactions it was derived from, so these are
+/// carried along and verified on their own. This is not proof aggregation: the witness
+/// grows with every transaction that is merged or updated.
+#[derive(Clone, Debug, Serialize, Deserialize, PartialEq, Eq, GetSize, BFieldCodec)]
+pub enum TransactionDerivation {
+    /// The transaction is the merger of two others.
+    Merge(Merge),
+
+    /// The transaction is another one updated to a later mutator set.
+    Update(Update),
+}
+
+impl TransactionDerivation {
+    pub fn prove(&mut self) -> Result<()> {
+        match self {
+            TransactionDerivation::Merge(merge) => merge.prove(),
+            TransactionDerivation::Update(update) => update.prove(),
+        }
+    }
+
+    /// Verify that the claim is about the given kernel, that it holds, and that the
+    /// transactions it was derived from are valid.
+    pub fn verify(&self, kernel: &TransactionKernel) -> bool {
+        match self {
+            TransactionDerivation::Merge(merge) => merge.verify_merger(kernel),
+            TransactionDerivation::Update(update) => update.verify_update(kernel),
+        }
     }
 }
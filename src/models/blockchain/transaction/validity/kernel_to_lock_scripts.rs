@@ -1,35 +1,55 @@
-use crate::models::consensus::mast_hash::MastHash;
 use crate::prelude::{triton_vm, twenty_first};
 
-use crate::models::blockchain::transaction::TransactionPrimitiveWitness;
-use crate::models::blockchain::transaction::{
-    transaction_kernel::{TransactionKernel, TransactionKernelField},
-    utxo::Utxo,
-};
-
-use crate::models::consensus::{ClaimSupport, SupportedClaim};
-use crate::models::consensus::{SecretWitness, ValidationLogic};
+use field_count::FieldCount;
 use get_size::GetSize;
 use itertools::Itertools;
 use serde::{Deserialize, Serialize};
-use tasm_lib::library::Library;
+use std::collections::HashMap;
+use tasm_lib::memory::{encode_to_memory, FIRST_NON_DETERMINISTICALLY_INITIALIZED_MEMORY_ADDRESS};
+use tasm_lib::structure::tasm_object::TasmObject;
 use tasm_lib::traits::compiled_program::CompiledProgram;
-use triton_vm::prelude::{BFieldElement, Claim, Digest, NonDeterminism, Program, PublicInput};
+use triton_vm::prelude::{BFieldElement, Claim, NonDeterminism, Program, Proof};
 use twenty_first::shared_math::bfield_codec::BFieldCodec;
+use twenty_first::shared_math::tip5::Digest;
+use twenty_first::util_types::algebraic_hasher::AlgebraicHasher;
 
-#[derive(Clone, Debug, Serialize, Deserialize, PartialEq, Eq, GetSize, BFieldCodec)]
+use super::salted_utxos_hash;
+use crate::models::blockchain::shared::Hash;
+use crate::models::blockchain::transaction::TransactionPrimitiveWitness;
+use crate::models::blockchain::transaction::{transaction_kernel::TransactionKernel, utxo::Utxo};
+use crate::models::consensus::{ClaimSupport, SupportedClaim};
+use crate::models::consensus::{SecretWitness, ValidationLogic};
+
+#[derive(
+    Clone,
+    Debug,
+    Serialize,
+    Deserialize,
+    PartialEq,
+    Eq,
+    GetSize,
+    BFieldCodec,
+    FieldCount,
+    TasmObject,
+)]
 pub struct KernelToLockScriptsWitness {
-    input_utxos: Vec<Utxo>,
-    mast_path: Vec<Digest>,
+    pub input_utxos: Vec<Utxo>,
+    pub input_utxos_salt: Digest,
 }
 
 impl SecretWitness for KernelToLockScriptsWitness {
     fn nondeterminism(&self) -> NonDeterminism<BFieldElement> {
-        todo!()
+        let mut memory = HashMap::default();
+        encode_to_memory(
+            &mut memory,
+            FIRST_NON_DETERMINISTICALLY_INITIALIZED_MEMORY_ADDRESS,
+            self.clone(),
+        );
+        NonDeterminism::default().with_ram(memory)
     }
 
     fn subprogram(&self) -> Program {
-        todo!()
+        KernelToLockScripts::program()
     }
 }
 
@@ -38,15 +58,6 @@ pub struct KernelToLockScripts {
     pub supported_claim: SupportedClaim<KernelToLockScriptsWitness>,
 }
 
-impl KernelToLockScripts {
-    // TODO: Remove after implementing this struct
-    pub fn dummy() -> Self {
-        Self {
-            supported_claim: SupportedClaim::dummy(),
-        }
-    }
-}
-
 impl ValidationLogic<KernelToLockScriptsWitness> for KernelToLockScripts {
     type PrimitiveWitness = TransactionPrimitiveWitness;
 
@@ -54,32 +65,40 @@ impl ValidationLogic<KernelToLockScriptsWitness> for KernelToLockScripts {
 
     fn new_from_primitive_witness(
         primitive_witness: &TransactionPrimitiveWitness,
-        tx_kernel: &TransactionKernel,
+        _tx_kernel: &TransactionKernel,
     ) -> Self {
+        let kernel_to_lock_scripts_witness = KernelToLockScriptsWitness {
+            input_utxos: primitive_witness.input_utxos.clone(),
+            input_utxos_salt: primitive_witness.input_utxos_salt(),
+        };
         let claim = Claim {
-            input: tx_kernel.mast_hash().into(),
-            output: primitive_witness
-                .input_lock_scripts
+            input: salted_utxos_hash(
+                &kernel_to_lock_scripts_witness.input_utxos,
+                kernel_to_lock_scripts_witness.input_utxos_salt,
+            )
+            .reversed()
+            .values()
+            .to_vec(),
+            output: kernel_to_lock_scripts_witness
+                .input_utxos
                 .iter()
-                .flat_map(|ls| ls.hash().values().to_vec())
+                .flat_map(|utxo| utxo.lock_script_hash.values())
                 .collect_vec(),
-            // program_digest: Self::program().hash::<Hash>(),
-            program_digest: Digest::default(),
-        };
-        let _kernel_to_lock_scripts_witness = KernelToLockScriptsWitness {
-            input_utxos: primitive_witness.input_utxos.clone(),
-            mast_path: tx_kernel.mast_path(TransactionKernelField::InputUtxos),
+            program_digest: Hash::hash_varlen(&Self::program().encode()),
         };
         let supported_claim = SupportedClaim {
             claim,
-            // support: ClaimSupport::SecretWitness(kernel_to_lock_scripts_witness),
-            support: ClaimSupport::DummySupport,
+            support: ClaimSupport::SecretWitness(kernel_to_lock_scripts_witness),
         };
         Self { supported_claim }
     }
 
+    fn upgrade(&mut self, proof: Proof) {
+        self.supported_claim.support = ClaimSupport::Proof(proof);
+    }
+
     fn subprogram(&self) -> Program {
-        todo!()
+        Self::program()
     }
 
     fn support(&self) -> ClaimSupport<KernelToLockScriptsWitness> {
@@ -90,20 +109,3 @@ impl ValidationLogic<KernelToLockScriptsWitness> for KernelToLockScripts {
         self.supported_claim.claim.clone()
     }
 }
-
-impl CompiledProgram for KernelToLockScripts {
-    fn rust_shadow(
-        _public_input: &PublicInput,
-        _secret_input: &NonDeterminism<BFieldElement>,
-    ) -> anyhow::Result<Vec<BFieldElement>> {
-        todo!()
-    }
-
-    fn crash_conditions() -> Vec<String> {
-        todo!()
-    }
-
-    fn code() -> (Vec<triton_vm::instruction::LabelledInstruction>, Library) {
-        todo!()
-    }
-}
@@ -1,97 +1,153 @@
-use crate::models::blockchain::transaction::transaction_kernel::TransactionKernel;
 use crate::models::consensus::mast_hash::MastHash;
 use crate::prelude::{triton_vm, twenty_first};
 
-use crate::models::blockchain::transaction::utxo::TypeScript;
-use crate::models::blockchain::transaction::TransactionPrimitiveWitness;
-use crate::models::consensus::{ClaimSupport, SecretWitness, SupportedClaim, ValidationLogic};
-
+use field_count::FieldCount;
 use get_size::GetSize;
 use itertools::Itertools;
 use serde::{Deserialize, Serialize};
-use tasm_lib::library::Library;
+use std::collections::HashMap;
+use tasm_lib::memory::{encode_to_memory, FIRST_NON_DETERMINISTICALLY_INITIALIZED_MEMORY_ADDRESS};
+use tasm_lib::structure::tasm_object::TasmObject;
 use tasm_lib::traits::compiled_program::CompiledProgram;
-use triton_vm::instruction::LabelledInstruction;
-use triton_vm::prelude::{BFieldElement, Claim, Digest, NonDeterminism, Program, PublicInput};
+use triton_vm::prelude::{BFieldElement, Claim, NonDeterminism, Program, Proof};
 use twenty_first::shared_math::bfield_codec::BFieldCodec;
+use twenty_first::shared_math::tip5::Digest;
+use twenty_first::util_types::algebraic_hasher::AlgebraicHasher;
 
-#[derive(Clone, Debug, Serialize, Deserialize, PartialEq, Eq, GetSize, BFieldCodec)]
+use super::salted_utxos_hash;
+use crate::models::blockchain::shared::Hash;
+use crate::models::blockchain::transaction::transaction_kernel::TransactionKernel;
+use crate::models::blockchain::transaction::utxo::Utxo;
+use crate::models::blockchain::transaction::TransactionPrimitiveWitness;
+use crate::models::consensus::{ClaimSupport, SecretWitness, SupportedClaim, ValidationLogic};
+
+/// Everything needed to list the type scripts of a transaction: the input UTXOs, which
+/// are vouched for by their salted hash, and the output UTXOs, which are vouched for by
+/// the addition records in the kernel. The program also attests to the salted hash of the
+/// output UTXOs, which the type scripts get as public input.
+#[derive(
+    Clone,
+    Debug,
+    Serialize,
+    Deserialize,
+    PartialEq,
+    Eq,
+    GetSize,
+    BFieldCodec,
+    FieldCount,
+    TasmObject,
+)]
 pub struct KernelToTypeScriptsWitness {
-    type_scripts: Vec<TypeScript>,
-    mast_path: Vec<Digest>,
+    pub input_utxos: Vec<Utxo>,
+    pub input_utxos_salt: Digest,
+    pub output_utxos: Vec<Utxo>,
+    pub output_utxos_salt: Digest,
+    pub output_sender_randomnesses: Vec<Digest>,
+    pub output_receiver_digests: Vec<Digest>,
+    pub kernel: TransactionKernel,
+    pub type_script_hashes: Vec<Digest>,
+}
+
+impl KernelToTypeScriptsWitness {
+    pub fn new(
+        primitive_witness: &TransactionPrimitiveWitness,
+        tx_kernel: &TransactionKernel,
+    ) -> Self {
+        let mut type_script_hashes = primitive_witness
+            .input_utxos
+            .iter()
+            .chain(primitive_witness.output_utxos.iter())
+            .flat_map(|utxo| {
+                utxo.coins
+                    .iter()
+                    .map(|coin| coin.type_script_hash)
+                    .collect_vec()
+            })
+            .collect_vec();
+        type_script_hashes.sort();
+        type_script_hashes.dedup();
+
+        Self {
+            input_utxos: primitive_witness.input_utxos.clone(),
+            input_utxos_salt: primitive_witness.input_utxos_salt(),
+            output_utxos: primitive_witness.output_utxos.clone(),
+            output_utxos_salt: primitive_witness.output_utxos_salt(),
+            output_sender_randomnesses: primitive_witness.output_sender_randomnesses.clone(),
+            output_receiver_digests: primitive_witness.output_receiver_digests.clone(),
+            kernel: tx_kernel.to_owned(),
+            type_script_hashes,
+        }
+    }
+
+    /// The public input of the program: the kernel's MAST hash, followed by the salted
+    /// hashes of the input and of the output UTXOs, all reversed.
+    pub fn public_input(&self) -> Vec<BFieldElement> {
+        let salted_input_utxos_hash = salted_utxos_hash(&self.input_utxos, self.input_utxos_salt);
+        let salted_output_utxos_hash =
+            salted_utxos_hash(&self.output_utxos, self.output_utxos_salt);
+        [
+            self.kernel.mast_hash().reversed().values(),
+            salted_input_utxos_hash.reversed().values(),
+            salted_output_utxos_hash.reversed().values(),
+        ]
+        .concat()
+    }
 }
 
 impl SecretWitness for KernelToTypeScriptsWitness {
     fn nondeterminism(&self) -> NonDeterminism<BFieldElement> {
-        todo!()
+        let mut memory = HashMap::default();
+        encode_to_memory(
+            &mut memory,
+            FIRST_NON_DETERMINISTICALLY_INITIALIZED_MEMORY_ADDRESS,
+            self.clone(),
+        );
+        NonDeterminism::default().with_ram(memory)
     }
 
     fn subprogram(&self) -> Program {
-        todo!()
+        KernelToTypeScripts::program()
     }
 }
+
 #[derive(Clone, Debug, Serialize, Deserialize, PartialEq, Eq, GetSize, BFieldCodec)]
 pub struct KernelToTypeScripts {
     pub supported_claim: SupportedClaim<KernelToTypeScriptsWitness>,
 }
 
-impl KernelToTypeScripts {
-    // TODO: Remove after implementing this struct
-    pub fn dummy() -> Self {
-        Self {
-            supported_claim: SupportedClaim::dummy(),
-        }
-    }
-}
-
 impl ValidationLogic<KernelToTypeScriptsWitness> for KernelToTypeScripts {
     type PrimitiveWitness = TransactionPrimitiveWitness;
 
     type Kernel = TransactionKernel;
 
     fn new_from_primitive_witness(
-        primitive_witness: &crate::models::blockchain::transaction::TransactionPrimitiveWitness,
-        tx_kernel: &crate::models::blockchain::transaction::transaction_kernel::TransactionKernel,
+        primitive_witness: &TransactionPrimitiveWitness,
+        tx_kernel: &TransactionKernel,
     ) -> Self {
-        let mut type_script_digests = primitive_witness
-            .input_utxos
-            .iter()
-            .chain(primitive_witness.output_utxos.iter())
-            .flat_map(|utxo| {
-                utxo.coins
-                    .iter()
-                    .map(|coin| coin.type_script_hash)
-                    .collect_vec()
-            })
-            .collect_vec();
-        type_script_digests.sort();
-        type_script_digests.dedup();
+        let kernel_to_type_scripts_witness =
+            KernelToTypeScriptsWitness::new(primitive_witness, tx_kernel);
         let claim = Claim {
-            input: tx_kernel.mast_hash().values().to_vec(),
-            output: type_script_digests
-                .into_iter()
-                .flat_map(|d| d.values().to_vec())
+            input: kernel_to_type_scripts_witness.public_input(),
+            output: kernel_to_type_scripts_witness
+                .type_script_hashes
+                .iter()
+                .flat_map(|type_script_hash| type_script_hash.values())
                 .collect_vec(),
-            // program_hash: Self::program(),
-            program_digest: Digest::default(),
+            program_digest: Hash::hash_varlen(&Self::program().encode()),
         };
         let supported_claim = SupportedClaim {
             claim,
-            support: ClaimSupport::DummySupport,
+            support: ClaimSupport::SecretWitness(kernel_to_type_scripts_witness),
         };
         Self { supported_claim }
     }
 
-    fn prove(&mut self) -> anyhow::Result<()> {
-        todo!()
-    }
-
-    fn verify(&self) -> bool {
-        todo!()
+    fn upgrade(&mut self, proof: Proof) {
+        self.supported_claim.support = ClaimSupport::Proof(proof);
     }
 
     fn subprogram(&self) -> Program {
-        todo!()
+        Self::program()
     }
 
     fn support(&self) -> ClaimSupport<KernelToTypeScriptsWitness> {
@@ -102,20 +158,3 @@ impl ValidationLogic<KernelToTypeScriptsWitness> for KernelToTypeScripts {
         self.supported_claim.claim.clone()
     }
 }
-
-impl CompiledProgram for KernelToTypeScripts {
-    fn rust_shadow(
-        _public_input: &PublicInput,
-        _secret_input: &NonDeterminism<BFieldElement>,
-    ) -> anyhow::Result<Vec<BFieldElement>> {
-        todo!()
-    }
-
-    fn code() -> (Vec<LabelledInstruction>, Library) {
-        todo!()
-    }
-
-    fn crash_conditions() -> Vec<String> {
-        todo!()
-    }
-}
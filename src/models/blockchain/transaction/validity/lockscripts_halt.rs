@@ -3,10 +3,14 @@ use crate::{
     prelude::{triton_vm, twenty_first},
 };
 
+use anyhow::Result;
 use get_size::GetSize;
 use itertools::Itertools;
 use serde::{Deserialize, Serialize};
-use triton_vm::prelude::{BFieldElement, Claim, NonDeterminism, Program};
+use tracing::warn;
+use triton_vm::prelude::{
+    BFieldElement, Claim, NonDeterminism, Program, PublicInput, StarkParameters,
+};
 use twenty_first::shared_math::bfield_codec::BFieldCodec;
 
 use crate::models::{
@@ -37,6 +41,27 @@ pub struct LockScriptsHalt {
     pub supported_claims: Vec<SupportedClaim<LockScriptHaltsWitness>>,
 }
 
+impl LockScriptsHalt {
+    /// Replace the secret witness of every lock script claim by a proof, such that the
+    /// claims can be verified without the unlock keys.
+    pub fn prove_claims(&mut self) -> Result<()> {
+        for supported_claim in self.supported_claims.iter_mut() {
+            let ClaimSupport::SecretWitness(witness) = &supported_claim.support else {
+                continue;
+            };
+            let proof = triton_vm::prove(
+                StarkParameters::default(),
+                &supported_claim.claim,
+                &witness.subprogram(),
+                witness.nondeterminism(),
+            )?;
+            supported_claim.support = ClaimSupport::Proof(proof);
+        }
+
+        Ok(())
+    }
+}
+
 impl ValidationLogic<LockScriptHaltsWitness> for LockScriptsHalt {
     type PrimitiveWitness = TransactionPrimitiveWitness;
 
@@ -76,21 +101,31 @@ impl ValidationLogic<LockScriptHaltsWitness> for LockScriptsHalt {
         todo!()
     }
 
+    /// The secret witnesses of the lock script claims that are not proven yet. Proven
+    /// claims need no further support.
     fn support(&self) -> ClaimSupport<LockScriptHaltsWitness> {
         ClaimSupport::MultipleSupports(
             self.supported_claims
-                .clone()
-                .into_iter()
-                .map(|sc| match sc.support {
-                    ClaimSupport::Proof(_) => todo!(),
-                    ClaimSupport::MultipleSupports(_) => todo!(),
-                    ClaimSupport::SecretWitness(sw) => sw.to_owned(),
-                    ClaimSupport::DummySupport => todo!(),
+                .iter()
+                .filter_map(|sc| match &sc.support {
+                    ClaimSupport::Proof(_) => None,
+                    ClaimSupport::SecretWitness(sw) => Some(sw.to_owned()),
+                    ClaimSupport::MultipleSupports(_) | ClaimSupport::DummySupport => {
+                        unreachable!(
+                            "lock script claims are supported by a proof or a secret witness"
+                        )
+                    }
                 })
                 .collect(),
         )
     }
 
+    /// Prove every lock script claim on its own, as each lock script gets the kernel MAST
+    /// hash as public input.
+    fn prove(&mut self) -> Result<()> {
+        self.prove_claims()
+    }
+
     // fn support(&self) -> ClaimSupport<LockScriptHaltsWitness> {
     // let supports = self
     //     .supported_claims
@@ -100,6 +135,29 @@ impl ValidationLogic<LockScriptHaltsWitness> for LockScriptsHalt {
     // ClaimSupport::MultipleSupports(supports)
     // }
 
+    /// Verify every lock script claim on its own, as each lock script gets the kernel MAST
+    /// hash as public input.
+    fn verify(&self) -> bool {
+        self.supported_claims
+            .iter()
+            .all(|supported_claim| match &supported_claim.support {
+                ClaimSupport::Proof(proof) => {
+                    triton_vm::verify(StarkParameters::default(), &supported_claim.claim, proof)
+                }
+                ClaimSupport::SecretWitness(witness) => {
+                    let vm_result = witness.subprogram().run(
+                        PublicInput::new(supported_claim.claim.input.clone()),
+                        witness.nondeterminism(),
+                    );
+                    if let Err(err) = &vm_result {
+                        warn!("Lock script failed to halt gracefully: {err}");
+                    }
+                    vm_result.is_ok()
+                }
+                ClaimSupport::MultipleSupports(_) | ClaimSupport::DummySupport => false,
+            })
+    }
+
     fn claim(&self) -> Claim {
         let input = self
             .supported_claims
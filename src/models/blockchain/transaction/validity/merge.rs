@@ -0,0 +1,278 @@
+use crate::models::consensus::mast_hash::MastHash;
+use crate::prelude::{triton_vm, twenty_first};
+
+use field_count::FieldCount;
+use get_size::GetSize;
+use itertools::Itertools;
+use serde::{Deserialize, Serialize};
+use std::cmp::max;
+use std::collections::HashMap;
+use tasm_lib::memory::{encode_to_memory, FIRST_NON_DETERMINISTICALLY_INITIALIZED_MEMORY_ADDRESS};
+use tasm_lib::structure::tasm_object::TasmObject;
+use tasm_lib::traits::compiled_program::CompiledProgram;
+use tracing::warn;
+use triton_vm::prelude::{BFieldElement, Claim, NonDeterminism, Program, Proof};
+use twenty_first::shared_math::bfield_codec::BFieldCodec;
+use twenty_first::util_types::algebraic_hasher::AlgebraicHasher;
+
+use crate::models::blockchain::shared::Hash;
+use crate::models::blockchain::transaction::transaction_kernel::TransactionKernel;
+use crate::models::blockchain::transaction::Transaction;
+use crate::models::consensus::{ClaimSupport, SupportedClaim};
+use crate::models::consensus::{SecretWitness, ValidationLogic};
+
+/// The data that shows that a transaction kernel is the merger of two others: its inputs,
+/// outputs and public announcements are those of the two, shuffled; its fee is the sum
+/// of theirs; it has the coinbase of at most one of them; it has the later of their
+/// timestamps; and all three are about the same mutator set.
+#[derive(
+    Clone,
+    Debug,
+    Serialize,
+    Deserialize,
+    PartialEq,
+    Eq,
+    GetSize,
+    BFieldCodec,
+    FieldCount,
+    TasmObject,
+)]
+pub struct MergeWitness {
+    pub left_kernel: TransactionKernel,
+    pub right_kernel: TransactionKernel,
+    pub new_kernel: TransactionKernel,
+
+    // For every item of the new kernel, the index of that item in the list of the left
+    // kernel followed by the list of the right kernel.
+    pub input_permutation: Vec<BFieldElement>,
+    pub output_permutation: Vec<BFieldElement>,
+    pub public_announcement_permutation: Vec<BFieldElement>,
+}
+
+/// Order the items by their hash, so that the order of the items of a merged transaction
+/// does not reveal which transaction they came from. Returns the permutation that maps
+/// every position in the shuffled list to the index of its item in the given one.
+fn shuffle<T: BFieldCodec>(items: &[T]) -> Vec<BFieldElement> {
+    (0..items.len())
+        .sorted_by_key(|&index| Hash::hash(&items[index]).values().map(|e| e.value()))
+        .map(|index| BFieldElement::new(index as u64))
+        .collect_vec()
+}
+
+/// Apply a permutation produced by `shuffle` to the left list followed by the right one.
+pub fn permute<T: Clone>(left: &[T], right: &[T], permutation: &[BFieldElement]) -> Vec<T> {
+    let concatenation = [left, right].concat();
+    permutation
+        .iter()
+        .map(|index| concatenation[index.value() as usize].clone())
+        .collect_vec()
+}
+
+impl MergeWitness {
+    /// Merge two transaction kernels. The caller is responsible for checking that at most
+    /// one of them has a coinbase and that both are about the same mutator set.
+    pub fn new(left_kernel: &TransactionKernel, right_kernel: &TransactionKernel) -> Self {
+        let input_permutation =
+            shuffle(&[left_kernel.inputs.clone(), right_kernel.inputs.clone()].concat());
+        let output_permutation =
+            shuffle(&[left_kernel.outputs.clone(), right_kernel.outputs.clone()].concat());
+        let public_announcement_permutation = shuffle(
+            &[
+                left_kernel.public_announcements.clone(),
+                right_kernel.public_announcements.clone(),
+            ]
+            .concat(),
+        );
+
+        let new_kernel = TransactionKernel {
+            inputs: permute(
+                &left_kernel.inputs,
+                &right_kernel.inputs,
+                &input_permutation,
+            ),
+            outputs: permute(
+                &left_kernel.outputs,
+                &right_kernel.outputs,
+                &output_permutation,
+            ),
+            public_announcements: permute(
+                &left_kernel.public_announcements,
+                &right_kernel.public_announcements,
+                &public_announcement_permutation,
+            ),
+            fee: left_kernel.fee + right_kernel.fee,
+            coinbase: left_kernel.coinbase.or(right_kernel.coinbase),
+            timestamp: BFieldElement::new(max(
+                left_kernel.timestamp.value(),
+                right_kernel.timestamp.value(),
+            )),
+            mutator_set_hash: left_kernel.mutator_set_hash,
+            minimum_block_height: BFieldElement::new(max(
+                left_kernel.minimum_block_height.value(),
+                right_kernel.minimum_block_height.value(),
+            )),
+        };
+
+        Self {
+            left_kernel: left_kernel.clone(),
+            right_kernel: right_kernel.clone(),
+            new_kernel,
+            input_permutation,
+            output_permutation,
+            public_announcement_permutation,
+        }
+    }
+
+    pub fn public_input(&self) -> Vec<BFieldElement> {
+        self.new_kernel.mast_hash().reversed().values().to_vec()
+    }
+
+    /// The hashes of the kernels that were merged, which is what the merge program
+    /// outputs.
+    pub fn output(&self) -> Vec<BFieldElement> {
+        [
+            self.left_kernel.mast_hash().values(),
+            self.right_kernel.mast_hash().values(),
+        ]
+        .concat()
+    }
+}
+
+impl SecretWitness for MergeWitness {
+    fn nondeterminism(&self) -> NonDeterminism<BFieldElement> {
+        let mut memory = HashMap::default();
+        encode_to_memory(
+            &mut memory,
+            FIRST_NON_DETERMINISTICALLY_INITIALIZED_MEMORY_ADDRESS,
+            self.clone(),
+        );
+        NonDeterminism::default().with_ram(memory)
+    }
+
+    fn subprogram(&self) -> Program {
+        Merge::program()
+    }
+}
+
+/// The claim that a transaction is the merger of two others, together with those two
+/// transactions. The merge program only shows how the kernels relate; it cannot verify
+/// the witnesses of the transactions that were merged. So the merger carries them, and
+/// every one of them is verified against its own kernel, whose hash the merge claim
+/// outputs. The lock scripts of the merged transactions thus keep running on the kernels
+/// they were unlocked for.
+#[derive(Clone, Debug, Serialize, Deserialize, PartialEq, Eq, GetSize, BFieldCodec)]
+pub struct Merge {
+    pub supported_claim: SupportedClaim<MergeWitness>,
+
+    // the left and the right transaction that were merged
+    pub operands: Vec<Transaction>,
+}
+
+impl ValidationLogic<MergeWitness> for Merge {
+    type PrimitiveWitness = MergeWitness;
+
+    type Kernel = TransactionKernel;
+
+    fn new_from_primitive_witness(
+        merge_witness: &MergeWitness,
+        _tx_kernel: &TransactionKernel,
+    ) -> Self {
+        let claim = Claim {
+            input: merge_witness.public_input(),
+            output: merge_witness.output(),
+            program_digest: Hash::hash_varlen(&Self::program().encode()),
+        };
+        let supported_claim = SupportedClaim {
+            claim,
+            support: ClaimSupport::SecretWitness(merge_witness.clone()),
+        };
+        Self {
+            supported_claim,
+            operands: vec![],
+        }
+    }
+
+    fn upgrade(&mut self, proof: Proof) {
+        self.supported_claim.support = ClaimSupport::Proof(proof);
+    }
+
+    fn subprogram(&self) -> Program {
+        Self::program()
+    }
+
+    fn support(&self) -> ClaimSupport<MergeWitness> {
+        self.supported_claim.support.clone()
+    }
+
+    fn claim(&self) -> Claim {
+        self.supported_claim.claim.clone()
+    }
+}
+
+impl Merge {
+    /// The merger of the given transactions, whose kernels the merge witness merges.
+    pub fn new(merge_witness: &MergeWitness, left: Transaction, right: Transaction) -> Self {
+        Self {
+            operands: vec![left, right],
+            ..Self::new_from_primitive_witness(merge_witness, &merge_witness.new_kernel)
+        }
+    }
+
+    /// Verify that the merge claim is about the given kernel and the kernels of the
+    /// transactions that were merged, that it holds, and that those transactions are
+    /// valid.
+    pub fn verify_merger(&self, kernel: &TransactionKernel) -> bool {
+        let claim = self.claim();
+        if claim.program_digest != Hash::hash_varlen(&Self::program().encode()) {
+            warn!("Merge claim is about the wrong program");
+            return false;
+        }
+        if claim.input != kernel.mast_hash().reversed().values().to_vec() {
+            warn!("Merge claim is not about the transaction kernel");
+            return false;
+        }
+        let [left, right] = &self.operands[..] else {
+            warn!("Merger must carry the two transactions that were merged");
+            return false;
+        };
+        if claim.output
+            != [
+                left.kernel.mast_hash().values(),
+                right.kernel.mast_hash().values(),
+            ]
+            .concat()
+        {
+            warn!("Merge claim is not about the transactions that were merged");
+            return false;
+        }
+        if !left.is_valid() || !right.is_valid() {
+            warn!("Merger of invalid transactions");
+            return false;
+        }
+
+        self.verify()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::models::blockchain::transaction::neptune_coins::NeptuneCoins;
+    use crate::models::blockchain::transaction::transaction_kernel::pseudorandom_transaction_kernel;
+    use rand::{thread_rng, Rng};
+
+    #[test]
+    fn merger_is_independent_of_the_order_of_the_operands() {
+        let mut rng = thread_rng();
+        let mut left_kernel = pseudorandom_transaction_kernel(rng.gen(), 2, 3, 1);
+        let mut right_kernel = pseudorandom_transaction_kernel(rng.gen(), 3, 2, 2);
+        left_kernel.fee = NeptuneCoins::new(1);
+        right_kernel.fee = NeptuneCoins::new(2);
+        right_kernel.coinbase = None;
+        right_kernel.mutator_set_hash = left_kernel.mutator_set_hash;
+
+        let left_then_right = MergeWitness::new(&left_kernel, &right_kernel);
+        let right_then_left = MergeWitness::new(&right_kernel, &left_kernel);
+        assert_eq!(left_then_right.new_kernel, right_then_left.new_kernel);
+    }
+}
@@ -9,12 +9,13 @@ use std::collections::HashMap;
 use tasm_lib::memory::{encode_to_memory, FIRST_NON_DETERMINISTICALLY_INITIALIZED_MEMORY_ADDRESS};
 use tasm_lib::structure::tasm_object::TasmObject;
 use tasm_lib::traits::compiled_program::CompiledProgram;
-use triton_vm::prelude::{BFieldElement, Claim, NonDeterminism, Program};
+use triton_vm::prelude::{BFieldElement, Claim, NonDeterminism, Program, Proof};
 use twenty_first::{
     shared_math::{bfield_codec::BFieldCodec, tip5::Digest},
     util_types::{algebraic_hasher::AlgebraicHasher, mmr::mmr_accumulator::MmrAccumulator},
 };
 
+use super::salted_utxos_hash;
 use crate::models::consensus::{ClaimSupport, SecretWitness, SupportedClaim, ValidationLogic};
 use crate::{
     models::blockchain::{
@@ -40,6 +41,7 @@ use crate::{
 )]
 pub struct RemovalRecordsIntegrityWitness {
     pub input_utxos: Vec<Utxo>,
+    pub input_utxos_salt: Digest,
     pub membership_proofs: Vec<MsMembershipProof>,
     pub aocl: MmrAccumulator<Hash>,
     pub swbfi: MmrAccumulator<Hash>,
@@ -54,6 +56,7 @@ impl RemovalRecordsIntegrityWitness {
     ) -> Self {
         Self {
             input_utxos: primitive_witness.input_utxos.clone(),
+            input_utxos_salt: primitive_witness.input_utxos_salt(),
             membership_proofs: primitive_witness.input_membership_proofs.clone(),
             kernel: tx_kernel.to_owned(),
             aocl: primitive_witness
@@ -114,13 +117,22 @@ impl ValidationLogic<RemovalRecordsIntegrityWitness> for RemovalRecordsIntegrity
                         .into_iter()
                         .rev()
                         .collect_vec(),
-                    output: vec![],
+                    output: salted_utxos_hash(
+                        &removal_records_integrity_witness.input_utxos,
+                        removal_records_integrity_witness.input_utxos_salt,
+                    )
+                    .values()
+                    .to_vec(),
                 },
                 support: ClaimSupport::SecretWitness(removal_records_integrity_witness),
             },
         }
     }
 
+    fn upgrade(&mut self, proof: Proof) {
+        self.supported_claim.support = ClaimSupport::Proof(proof);
+    }
+
     fn subprogram(&self) -> Program {
         Self::program()
     }
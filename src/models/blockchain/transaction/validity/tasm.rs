@@ -3,6 +3,10 @@ mod compute_indices;
 mod hash_index_list;
 mod hash_removal_record_indices;
 mod hash_utxo;
+pub mod kernel_to_lock_scripts;
+pub mod kernel_to_type_scripts;
+pub mod merge;
 pub mod removal_records_integrity;
 pub mod transaction_kernel_mast_hash;
+pub mod update;
 mod verify_aocl_membership;
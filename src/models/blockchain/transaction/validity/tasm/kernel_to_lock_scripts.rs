@@ -0,0 +1,248 @@
+use crate::prelude::{triton_vm, twenty_first};
+
+use itertools::Itertools;
+use tasm_lib::data_type::DataType;
+use tasm_lib::hashing::hash_varlen::HashVarlen;
+use tasm_lib::library::Library;
+use tasm_lib::list::contiguous_list::get_pointer_list::GetPointerList;
+use tasm_lib::list::ListType;
+use tasm_lib::memory::push_ram_to_stack::PushRamToStack;
+use tasm_lib::memory::FIRST_NON_DETERMINISTICALLY_INITIALIZED_MEMORY_ADDRESS;
+use tasm_lib::structure::tasm_object::TasmObject;
+use tasm_lib::traits::compiled_program::CompiledProgram;
+use tasm_lib::DIGEST_LENGTH;
+use triton_vm::instruction::LabelledInstruction;
+use triton_vm::prelude::{triton_asm, BFieldElement, NonDeterminism, PublicInput};
+use twenty_first::shared_math::{bfield_codec::BFieldCodec, tip5::Digest};
+
+use crate::models::blockchain::transaction::utxo::Utxo;
+use crate::models::blockchain::transaction::validity::kernel_to_lock_scripts::{
+    KernelToLockScripts, KernelToLockScriptsWitness,
+};
+use crate::models::blockchain::transaction::validity::salted_utxos_hash;
+
+impl CompiledProgram for KernelToLockScripts {
+    fn rust_shadow(
+        public_input: &PublicInput,
+        nondeterminism: &NonDeterminism<BFieldElement>,
+    ) -> anyhow::Result<Vec<BFieldElement>> {
+        let salted_input_utxos_hash = *Digest::decode(
+            &public_input
+                .individual_tokens
+                .iter()
+                .copied()
+                .take(DIGEST_LENGTH)
+                .rev()
+                .collect_vec(),
+        )
+        .expect("Could not decode public input in Kernel To Lock Scripts");
+
+        // 1. read witness data
+        let kernel_to_lock_scripts_witness = *KernelToLockScriptsWitness::decode_from_memory(
+            &nondeterminism.ram,
+            FIRST_NON_DETERMINISTICALLY_INITIALIZED_MEMORY_ADDRESS,
+        )
+        .unwrap();
+
+        // 2. assert that the input utxos from the witness match the salted hash in the
+        // public input; now we can trust the input utxos
+        assert_eq!(
+            salted_input_utxos_hash,
+            salted_utxos_hash(
+                &kernel_to_lock_scripts_witness.input_utxos,
+                kernel_to_lock_scripts_witness.input_utxos_salt
+            ),
+        );
+
+        // 3. output the lock script hashes of the input utxos
+        Ok(kernel_to_lock_scripts_witness
+            .input_utxos
+            .iter()
+            .flat_map(|utxo| utxo.lock_script_hash.values())
+            .collect_vec())
+    }
+
+    fn code() -> (Vec<LabelledInstruction>, Library) {
+        let mut library = Library::new();
+        let hash_varlen = library.import(Box::new(HashVarlen));
+        let read_digest = library.import(Box::new(PushRamToStack {
+            data_type: DataType::Digest,
+        }));
+        let get_pointer_list = library.import(Box::new(GetPointerList {
+            output_list_type: ListType::Unsafe,
+        }));
+
+        // field getters
+        let witness_to_utxos = tasm_lib::field!(KernelToLockScriptsWitness::input_utxos);
+        let witness_to_utxos_with_size =
+            tasm_lib::field_with_size!(KernelToLockScriptsWitness::input_utxos);
+        let witness_to_utxos_salt = tasm_lib::field!(KernelToLockScriptsWitness::input_utxos_salt);
+        let utxo_to_lock_script_hash = tasm_lib::field!(Utxo::lock_script_hash);
+
+        let output_lock_script_hashes =
+            "tasm_neptune_transaction_kernel_to_lock_scripts_output_lock_script_hashes".to_string();
+
+        let code = triton_asm! {
+
+        // 1. Witness was already loaded into memory, just point to it
+        push {FIRST_NON_DETERMINISTICALLY_INITIALIZED_MEMORY_ADDRESS} // _ *witness
+
+        // 2. assert that the salted hash of the witness utxos == public input
+        dup 0                           // _ *witness *witness
+        {&witness_to_utxos_salt}        // _ *witness *salt
+        call {read_digest}              // _ *witness [salt]
+        dup 5                           // _ *witness [salt] *witness
+        {&witness_to_utxos_with_size}   // _ *witness [salt] *utxos utxos_size
+        call {hash_varlen}              // _ *witness [salt] [utxos_hash]
+        hash                            // _ *witness [witness_salted_utxos_hash]
+        read_io 5                       // _ *witness [witness_salted_utxos_hash] [input_salted_utxos_hash]
+        assert_vector                   // _ *witness [witness_salted_utxos_hash]
+        pop 5                           // _ *witness
+
+        // 3. output the lock script hash of every input utxo
+        {&witness_to_utxos}             // _ *utxos
+        call {get_pointer_list}         // _ *[*utxo]
+        dup 0                           // _ *[*utxo] *[*utxo]
+        read_mem 1                      // _ *[*utxo] num_utxos (*[*utxo] - 1)
+        pop 1                           // _ *[*utxo] num_utxos
+        push 0                          // _ *[*utxo] num_utxos 0
+        call {output_lock_script_hashes}
+                                        // _ *[*utxo] num_utxos num_utxos
+        pop 3                           // _
+
+        halt
+
+        // BEFORE: _ *[*utxo] num_utxos i
+        // AFTER: _ *[*utxo] num_utxos num_utxos
+        {output_lock_script_hashes}:
+            dup 1 dup 1 eq              // _ *[*utxo] num_utxos i (num_utxos == i)
+            skiz return                 // _ *[*utxo] num_utxos i
+
+            dup 2 push 1 add            // _ *[*utxo] num_utxos i (*[*utxo] + 1)
+            dup 1 add                   // _ *[*utxo] num_utxos i *[*utxo][i]
+            read_mem 1                  // _ *[*utxo] num_utxos i *utxo (*[*utxo][i] - 1)
+            pop 1                       // _ *[*utxo] num_utxos i *utxo
+            {&utxo_to_lock_script_hash} // _ *[*utxo] num_utxos i *lock_script_hash
+            call {read_digest}          // _ *[*utxo] num_utxos i [lock_script_hash]
+            write_io 5                  // _ *[*utxo] num_utxos i
+
+            push 1 add                  // _ *[*utxo] num_utxos (i + 1)
+            recurse
+        };
+
+        (code, library)
+    }
+
+    fn crash_conditions() -> Vec<String> {
+        vec!["the salted hash of the input utxos does not match the public input".to_string()]
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::models::blockchain::shared::Hash;
+    use crate::models::consensus::SecretWitness;
+    use crate::tests::shared::pseudorandom_kernel_to_lock_scripts_witness;
+    use rand::{rngs::StdRng, Rng, SeedableRng};
+    use tasm_lib::traits::compiled_program::test_rust_shadow;
+    use triton_vm::prelude::{Claim, StarkParameters};
+
+    fn public_input(witness: &KernelToLockScriptsWitness) -> PublicInput {
+        PublicInput::new(
+            salted_utxos_hash(&witness.input_utxos, witness.input_utxos_salt)
+                .reversed()
+                .values()
+                .to_vec(),
+        )
+    }
+
+    fn halts(witness: &KernelToLockScriptsWitness, public_input: PublicInput) -> bool {
+        KernelToLockScripts::program()
+            .run(public_input, witness.nondeterminism())
+            .is_ok()
+    }
+
+    #[test]
+    fn test_graceful_halt() {
+        let mut seed = [0u8; 32];
+        seed[0] = 0xa1;
+        seed[1] = 0x5c;
+        let mut rng: StdRng = SeedableRng::from_seed(seed);
+        let witness = pseudorandom_kernel_to_lock_scripts_witness(rng.gen());
+        let expected_output = witness
+            .input_utxos
+            .iter()
+            .flat_map(|utxo| utxo.lock_script_hash.values())
+            .collect_vec();
+
+        let program = KernelToLockScripts::program();
+        let output = match program.run(public_input(&witness), witness.nondeterminism()) {
+            Ok(output) => output,
+            Err(err) => panic!("Failed:\n last state was:\n{err}"),
+        };
+        assert_eq!(expected_output, output);
+
+        if std::env::var("DYING_TO_PROVE").is_ok() {
+            let claim: Claim = Claim {
+                program_digest: program.hash::<Hash>(),
+                input: public_input(&witness).individual_tokens,
+                output,
+            };
+            let maybe_proof = triton_vm::prove(
+                StarkParameters::default(),
+                &claim,
+                &program,
+                witness.nondeterminism(),
+            );
+            assert!(maybe_proof.is_ok());
+
+            assert!(triton_vm::verify(
+                StarkParameters::default(),
+                &claim,
+                &maybe_proof.unwrap()
+            ));
+        }
+    }
+
+    #[test]
+    fn program_is_deterministic() {
+        let program = KernelToLockScripts::program();
+        let other_program = KernelToLockScripts::program();
+        assert_eq!(program, other_program);
+    }
+
+    #[test]
+    fn tasm_matches_rust() {
+        let mut seed = [0u8; 32];
+        seed[0] = 0xa1;
+        seed[1] = 0x5d;
+        let mut rng: StdRng = SeedableRng::from_seed(seed);
+        let witness = pseudorandom_kernel_to_lock_scripts_witness(rng.gen());
+
+        test_rust_shadow::<KernelToLockScripts>(&public_input(&witness), &witness.nondeterminism());
+    }
+
+    #[test]
+    fn crashes_on_utxos_that_do_not_match_salted_hash() {
+        let mut seed = [0u8; 32];
+        seed[0] = 0xa1;
+        seed[1] = 0x5e;
+        let mut rng: StdRng = SeedableRng::from_seed(seed);
+        let witness = pseudorandom_kernel_to_lock_scripts_witness(rng.gen());
+        let public_input = public_input(&witness);
+        assert!(halts(&witness, public_input.clone()));
+
+        let mut wrong_salt = witness.clone();
+        wrong_salt.input_utxos_salt = rng.gen();
+        assert!(!halts(&wrong_salt, public_input.clone()));
+
+        let mut swapped_lock_script = witness.clone();
+        swapped_lock_script.input_utxos[0].lock_script_hash = rng.gen();
+        assert!(!halts(&swapped_lock_script, public_input.clone()));
+
+        let mut dropped_utxo = witness;
+        dropped_utxo.input_utxos.pop();
+        assert!(!halts(&dropped_utxo, public_input));
+    }
+}
@@ -0,0 +1,537 @@
+use crate::models::consensus::mast_hash::MastHash;
+use crate::prelude::{triton_vm, twenty_first};
+
+use itertools::Itertools;
+use tasm_lib::data_type::DataType;
+use tasm_lib::hashing::hash_varlen::HashVarlen;
+use tasm_lib::library::Library;
+use tasm_lib::list::contiguous_list::get_pointer_list::GetPointerList;
+use tasm_lib::list::ListType;
+use tasm_lib::memory::push_ram_to_stack::PushRamToStack;
+use tasm_lib::memory::FIRST_NON_DETERMINISTICALLY_INITIALIZED_MEMORY_ADDRESS;
+use tasm_lib::structure::tasm_object::TasmObject;
+use tasm_lib::traits::compiled_program::CompiledProgram;
+use tasm_lib::DIGEST_LENGTH;
+use triton_vm::instruction::LabelledInstruction;
+use triton_vm::prelude::{triton_asm, BFieldElement, NonDeterminism, PublicInput};
+use twenty_first::shared_math::{bfield_codec::BFieldCodec, tip5::Digest};
+use twenty_first::util_types::algebraic_hasher::AlgebraicHasher;
+
+use super::hash_utxo::HashUtxo;
+use super::transaction_kernel_mast_hash::TransactionKernelMastHash;
+use crate::models::blockchain::shared::Hash;
+use crate::models::blockchain::transaction::transaction_kernel::TransactionKernel;
+use crate::models::blockchain::transaction::utxo::{Coin, Utxo};
+use crate::models::blockchain::transaction::validity::kernel_to_type_scripts::{
+    KernelToTypeScripts, KernelToTypeScriptsWitness,
+};
+use crate::models::blockchain::transaction::validity::salted_utxos_hash;
+use crate::util_types::mutator_set::mutator_set_trait::commit;
+
+impl CompiledProgram for KernelToTypeScripts {
+    fn rust_shadow(
+        public_input: &PublicInput,
+        nondeterminism: &NonDeterminism<BFieldElement>,
+    ) -> anyhow::Result<Vec<BFieldElement>> {
+        let read_digest = |index: usize| {
+            *Digest::decode(
+                &public_input
+                    .individual_tokens
+                    .iter()
+                    .copied()
+                    .skip(index * DIGEST_LENGTH)
+                    .take(DIGEST_LENGTH)
+                    .rev()
+                    .collect_vec(),
+            )
+            .expect("Could not decode public input in Kernel To Type Scripts")
+        };
+        let hash_of_kernel = read_digest(0);
+        let salted_input_utxos_hash = read_digest(1);
+        let salted_output_utxos_hash = read_digest(2);
+
+        // 1. read witness data
+        let witness = *KernelToTypeScriptsWitness::decode_from_memory(
+            &nondeterminism.ram,
+            FIRST_NON_DETERMINISTICALLY_INITIALIZED_MEMORY_ADDRESS,
+        )
+        .unwrap();
+
+        // 2. assert that the kernel from the witness matches the hash in the public input
+        assert_eq!(hash_of_kernel, witness.kernel.mast_hash());
+
+        // 3. assert that the input and output utxos from the witness match the salted
+        // hashes in the public input
+        assert_eq!(
+            salted_input_utxos_hash,
+            salted_utxos_hash(&witness.input_utxos, witness.input_utxos_salt),
+        );
+        assert_eq!(
+            salted_output_utxos_hash,
+            salted_utxos_hash(&witness.output_utxos, witness.output_utxos_salt),
+        );
+
+        // 4. assert that the addition records in the kernel commit to the output utxos
+        let output_count = witness.kernel.outputs.len();
+        assert_eq!(output_count, witness.output_utxos.len());
+        assert_eq!(output_count, witness.output_sender_randomnesses.len());
+        assert_eq!(output_count, witness.output_receiver_digests.len());
+        for (((utxo, sender_randomness), receiver_digest), addition_record) in witness
+            .output_utxos
+            .iter()
+            .zip(witness.output_sender_randomnesses.iter())
+            .zip(witness.output_receiver_digests.iter())
+            .zip(witness.kernel.outputs.iter())
+        {
+            assert_eq!(
+                *addition_record,
+                commit(Hash::hash(utxo), *sender_randomness, *receiver_digest)
+            );
+        }
+
+        // 5. assert that the type script of every coin is listed
+        assert!(witness
+            .input_utxos
+            .iter()
+            .chain(witness.output_utxos.iter())
+            .flat_map(|utxo| utxo.coins.iter())
+            .all(|coin| witness.type_script_hashes.contains(&coin.type_script_hash)));
+
+        // 6. output the listed type script hashes
+        Ok(witness
+            .type_script_hashes
+            .iter()
+            .flat_map(|type_script_hash| type_script_hash.values())
+            .collect_vec())
+    }
+
+    fn code() -> (Vec<LabelledInstruction>, Library) {
+        let mut library = Library::new();
+        let transaction_kernel_mast_hash = library.import(Box::new(TransactionKernelMastHash));
+        let hash_varlen = library.import(Box::new(HashVarlen));
+        let hash_utxo = library.import(Box::new(HashUtxo));
+        let read_digest = library.import(Box::new(PushRamToStack {
+            data_type: DataType::Digest,
+        }));
+        let get_pointer_list = library.import(Box::new(GetPointerList {
+            output_list_type: ListType::Unsafe,
+        }));
+
+        // field getters
+        let witness_to_kernel = tasm_lib::field!(KernelToTypeScriptsWitness::kernel);
+        let witness_to_input_utxos = tasm_lib::field!(KernelToTypeScriptsWitness::input_utxos);
+        let witness_to_input_utxos_with_size =
+            tasm_lib::field_with_size!(KernelToTypeScriptsWitness::input_utxos);
+        let witness_to_input_utxos_salt =
+            tasm_lib::field!(KernelToTypeScriptsWitness::input_utxos_salt);
+        let witness_to_output_utxos = tasm_lib::field!(KernelToTypeScriptsWitness::output_utxos);
+        let witness_to_output_utxos_with_size =
+            tasm_lib::field_with_size!(KernelToTypeScriptsWitness::output_utxos);
+        let witness_to_output_utxos_salt =
+            tasm_lib::field!(KernelToTypeScriptsWitness::output_utxos_salt);
+        let witness_to_sender_randomnesses =
+            tasm_lib::field!(KernelToTypeScriptsWitness::output_sender_randomnesses);
+        let witness_to_receiver_digests =
+            tasm_lib::field!(KernelToTypeScriptsWitness::output_receiver_digests);
+        let witness_to_type_script_hashes =
+            tasm_lib::field!(KernelToTypeScriptsWitness::type_script_hashes);
+        let kernel_to_outputs = tasm_lib::field!(TransactionKernel::outputs);
+        let utxo_to_coins = tasm_lib::field!(Utxo::coins);
+        let coin_to_type_script_hash = tasm_lib::field!(Coin::type_script_hash);
+
+        let label = |name: &str| format!("tasm_neptune_transaction_kernel_to_type_scripts_{name}");
+        let verify_outputs = label("verify_outputs");
+        let check_utxos = label("check_utxos");
+        let check_utxos_loop = label("check_utxos_loop");
+        let check_coins = label("check_coins");
+        let check_coins_loop = label("check_coins_loop");
+        let assert_listed = label("assert_listed");
+        let output_digests = label("output_digests");
+
+        let code = triton_asm! {
+
+        // 1. Witness was already loaded into memory, just point to it
+        push {FIRST_NON_DETERMINISTICALLY_INITIALIZED_MEMORY_ADDRESS} // _ *witness
+
+        // 2. assert that witness kernel hash == public input
+        dup 0                               // _ *witness *witness
+        {&witness_to_kernel}                // _ *witness *kernel
+        call {transaction_kernel_mast_hash} // _ *witness [witness_kernel_digest]
+        read_io 5                           // _ *witness [witness_kernel_digest] [input_kernel_digest]
+        assert_vector                       // _ *witness [witness_kernel_digest]
+        pop 5                               // _ *witness
+
+        // 3. assert that the salted hashes of the witness input and output utxos == public input
+        dup 0                               // _ *witness *witness
+        {&witness_to_input_utxos_salt}      // _ *witness *salt
+        call {read_digest}                  // _ *witness [salt]
+        dup 5                               // _ *witness [salt] *witness
+        {&witness_to_input_utxos_with_size} // _ *witness [salt] *utxos utxos_size
+        call {hash_varlen}                  // _ *witness [salt] [utxos_hash]
+        hash                                // _ *witness [witness_salted_utxos_hash]
+        read_io 5                           // _ *witness [witness_salted_utxos_hash] [input_salted_utxos_hash]
+        assert_vector                       // _ *witness [witness_salted_utxos_hash]
+        pop 5                               // _ *witness
+
+        dup 0                               // _ *witness *witness
+        {&witness_to_output_utxos_salt}     // _ *witness *salt
+        call {read_digest}                  // _ *witness [salt]
+        dup 5                               // _ *witness [salt] *witness
+        {&witness_to_output_utxos_with_size}
+                                            // _ *witness [salt] *utxos utxos_size
+        call {hash_varlen}                  // _ *witness [salt] [utxos_hash]
+        hash                                // _ *witness [witness_salted_utxos_hash]
+        read_io 5                           // _ *witness [witness_salted_utxos_hash] [output_salted_utxos_hash]
+        assert_vector                       // _ *witness [witness_salted_utxos_hash]
+        pop 5                               // _ *witness
+
+        // 4. assert that the addition records in the kernel commit to the output utxos
+        dup 0                               // _ *witness *witness
+        {&witness_to_output_utxos}          // _ *witness *utxos
+        call {get_pointer_list}             // _ *witness *[*utxo]
+        dup 1                               // _ *witness *[*utxo] *witness
+        {&witness_to_sender_randomnesses}   // _ *witness *[*utxo] *[sender_randomness]
+        dup 2                               // _ *witness *[*utxo] *[sender_randomness] *witness
+        {&witness_to_receiver_digests}      // _ *witness *[*utxo] *[sender_randomness] *[receiver_digest]
+        dup 3                               // _ *witness *[*utxo] *[sender_randomness] *[receiver_digest] *witness
+        {&witness_to_kernel}                // _ *witness *[*utxo] *[sender_randomness] *[receiver_digest] *kernel
+        {&kernel_to_outputs}                // _ *witness *[*utxo] *[sr] *[rd] *[addition_record]
+
+        dup 0 read_mem 1 pop 1              // _ *witness *[*utxo] *[sr] *[rd] *[ar] num_outputs
+        dup 2 read_mem 1 pop 1 dup 1 eq assert
+        dup 3 read_mem 1 pop 1 dup 1 eq assert
+        dup 4 read_mem 1 pop 1 dup 1 eq assert
+                                            // _ *witness *[*utxo] *[sr] *[rd] *[ar] num_outputs
+        push 0                              // _ *witness *[*utxo] *[sr] *[rd] *[ar] num_outputs 0
+        call {verify_outputs}               // _ *witness *[*utxo] *[sr] *[rd] *[ar] num_outputs num_outputs
+        pop 5
+        pop 1                               // _ *witness
+
+        // 5. assert that the type script of every coin is listed
+        dup 0                               // _ *witness *witness
+        {&witness_to_type_script_hashes}    // _ *witness *[type_script_hash]
+        dup 1                               // _ *witness *[type_script_hash] *witness
+        {&witness_to_input_utxos}           // _ *witness *[type_script_hash] *input_utxos
+        call {get_pointer_list}             // _ *witness *[type_script_hash] *[*utxo]
+        call {check_utxos}                  // _ *witness *[type_script_hash] *[*utxo]
+        pop 1                               // _ *witness *[type_script_hash]
+        dup 1                               // _ *witness *[type_script_hash] *witness
+        {&witness_to_output_utxos}          // _ *witness *[type_script_hash] *output_utxos
+        call {get_pointer_list}             // _ *witness *[type_script_hash] *[*utxo]
+        call {check_utxos}                  // _ *witness *[type_script_hash] *[*utxo]
+        pop 1                               // _ *witness *[type_script_hash]
+
+        // 6. output the listed type script hashes
+        dup 0 read_mem 1 pop 1              // _ *witness *[type_script_hash] num_hashes
+        push 0                              // _ *witness *[type_script_hash] num_hashes 0
+        call {output_digests}               // _ *witness *[type_script_hash] num_hashes num_hashes
+        pop 4                               // _
+
+        halt
+
+        // BEFORE: _ *[*utxo] *[sr] *[rd] *[ar] num_outputs i
+        // AFTER: _ *[*utxo] *[sr] *[rd] *[ar] num_outputs num_outputs
+        {verify_outputs}:
+            dup 1 dup 1 eq                  // _ *[*utxo] *[sr] *[rd] *[ar] num_outputs i (num_outputs == i)
+            skiz return                     // _ *[*utxo] *[sr] *[rd] *[ar] num_outputs i
+
+            dup 3 push 1 add                // _ *[*utxo] *[sr] *[rd] *[ar] num_outputs i (*[rd] + 1)
+            dup 1 push 5 mul add            // _ *[*utxo] *[sr] *[rd] *[ar] num_outputs i *rd
+            call {read_digest}              // _ *[*utxo] *[sr] *[rd] *[ar] num_outputs i [rd]
+
+            dup 9 push 1 add                // _ *[*utxo] *[sr] *[rd] *[ar] num_outputs i [rd] (*[sr] + 1)
+            dup 6 push 5 mul add            // _ *[*utxo] *[sr] *[rd] *[ar] num_outputs i [rd] *sr
+            call {read_digest}              // _ *[*utxo] *[sr] *[rd] *[ar] num_outputs i [rd] [sr]
+
+            dup 15 push 1 add               // _ *[*utxo] *[sr] *[rd] *[ar] num_outputs i [rd] [sr] (*[*utxo] + 1)
+            dup 11 add                      // _ *[*utxo] *[sr] *[rd] *[ar] num_outputs i [rd] [sr] *[*utxo][i]
+            read_mem 1 pop 1                // _ *[*utxo] *[sr] *[rd] *[ar] num_outputs i [rd] [sr] *utxo
+            call {hash_utxo}                // _ *[*utxo] *[sr] *[rd] *[ar] num_outputs i [rd] [sr] [item]
+
+            hash                            // _ *[*utxo] *[sr] *[rd] *[ar] num_outputs i [rd] [H(item||sr)]
+            hash                            // _ *[*utxo] *[sr] *[rd] *[ar] num_outputs i [canonical_commitment]
+
+            dup 7 push 1 add                // _ *[*utxo] *[sr] *[rd] *[ar] num_outputs i [cc] (*[ar] + 1)
+            dup 6 push 5 mul add            // _ *[*utxo] *[sr] *[rd] *[ar] num_outputs i [cc] *ar
+            call {read_digest}              // _ *[*utxo] *[sr] *[rd] *[ar] num_outputs i [cc] [ar]
+            assert_vector                   // _ *[*utxo] *[sr] *[rd] *[ar] num_outputs i [cc]
+            pop 5                           // _ *[*utxo] *[sr] *[rd] *[ar] num_outputs i
+
+            push 1 add                      // _ *[*utxo] *[sr] *[rd] *[ar] num_outputs (i + 1)
+            recurse
+
+        // BEFORE: _ *[type_script_hash] *[*utxo]
+        // AFTER: _ *[type_script_hash] *[*utxo]
+        {check_utxos}:
+            dup 0 read_mem 1 pop 1          // _ *[type_script_hash] *[*utxo] num_utxos
+            push 0                          // _ *[type_script_hash] *[*utxo] num_utxos 0
+            call {check_utxos_loop}         // _ *[type_script_hash] *[*utxo] num_utxos num_utxos
+            pop 2                           // _ *[type_script_hash] *[*utxo]
+            return
+
+        // BEFORE: _ *[type_script_hash] *[*utxo] num_utxos i
+        // AFTER: _ *[type_script_hash] *[*utxo] num_utxos num_utxos
+        {check_utxos_loop}:
+            dup 1 dup 1 eq                  // _ *[tsh] *[*utxo] num_utxos i (num_utxos == i)
+            skiz return                     // _ *[tsh] *[*utxo] num_utxos i
+
+            dup 2 push 1 add                // _ *[tsh] *[*utxo] num_utxos i (*[*utxo] + 1)
+            dup 1 add                       // _ *[tsh] *[*utxo] num_utxos i *[*utxo][i]
+            read_mem 1 pop 1                // _ *[tsh] *[*utxo] num_utxos i *utxo
+            {&utxo_to_coins}                // _ *[tsh] *[*utxo] num_utxos i *coins
+            call {get_pointer_list}         // _ *[tsh] *[*utxo] num_utxos i *[*coin]
+            dup 4                           // _ *[tsh] *[*utxo] num_utxos i *[*coin] *[tsh]
+            swap 1                          // _ *[tsh] *[*utxo] num_utxos i *[tsh] *[*coin]
+            call {check_coins}              // _ *[tsh] *[*utxo] num_utxos i *[tsh] *[*coin]
+            pop 2                           // _ *[tsh] *[*utxo] num_utxos i
+
+            push 1 add                      // _ *[tsh] *[*utxo] num_utxos (i + 1)
+            recurse
+
+        // BEFORE: _ *[type_script_hash] *[*coin]
+        // AFTER: _ *[type_script_hash] *[*coin]
+        {check_coins}:
+            dup 0 read_mem 1 pop 1          // _ *[type_script_hash] *[*coin] num_coins
+            push 0                          // _ *[type_script_hash] *[*coin] num_coins 0
+            call {check_coins_loop}         // _ *[type_script_hash] *[*coin] num_coins num_coins
+            pop 2                           // _ *[type_script_hash] *[*coin]
+            return
+
+        // BEFORE: _ *[type_script_hash] *[*coin] num_coins j
+        // AFTER: _ *[type_script_hash] *[*coin] num_coins num_coins
+        {check_coins_loop}:
+            dup 1 dup 1 eq                  // _ *[tsh] *[*coin] num_coins j (num_coins == j)
+            skiz return                     // _ *[tsh] *[*coin] num_coins j
+
+            dup 2 push 1 add                // _ *[tsh] *[*coin] num_coins j (*[*coin] + 1)
+            dup 1 add                       // _ *[tsh] *[*coin] num_coins j *[*coin][j]
+            read_mem 1 pop 1                // _ *[tsh] *[*coin] num_coins j *coin
+            {&coin_to_type_script_hash}     // _ *[tsh] *[*coin] num_coins j *coin_type_script_hash
+            call {read_digest}              // _ *[tsh] *[*coin] num_coins j [coin_tsh]
+
+            dup 8                           // _ *[tsh] *[*coin] num_coins j [coin_tsh] *[tsh]
+            dup 0 read_mem 1 pop 1          // _ *[tsh] *[*coin] num_coins j [coin_tsh] *[tsh] num_hashes
+            push 0                          // _ *[tsh] *[*coin] num_coins j [coin_tsh] *[tsh] num_hashes 0
+            call {assert_listed}            // _ *[tsh] *[*coin] num_coins j [coin_tsh] *[tsh] num_hashes k
+            pop 3                           // _ *[tsh] *[*coin] num_coins j [coin_tsh]
+            pop 5                           // _ *[tsh] *[*coin] num_coins j
+
+            push 1 add                      // _ *[tsh] *[*coin] num_coins (j + 1)
+            recurse
+
+        // Crashes if the digest is not in the list.
+        // BEFORE: _ [digest] *[type_script_hash] num_hashes k
+        // AFTER: _ [digest] *[type_script_hash] num_hashes k', where list[k'] == digest
+        {assert_listed}:
+            dup 1 dup 1 eq                  // _ [d] *[tsh] num_hashes k (num_hashes == k)
+            push 0 eq assert                // _ [d] *[tsh] num_hashes k
+
+            dup 2 push 1 add                // _ [d] *[tsh] num_hashes k (*[tsh] + 1)
+            dup 1 push 5 mul add            // _ [d] *[tsh] num_hashes k *tsh
+            call {read_digest}              // _ [d] *[tsh] num_hashes k [tsh]
+            dup 12 dup 12 dup 12 dup 12 dup 12
+                                            // _ [d] *[tsh] num_hashes k [tsh] [d]
+
+            // compare [tsh] and [d] element by element
+            dup 5 eq                        // _ [d] *[tsh] num_hashes k [tsh] d4 d3 d2 d1 (d0 == tsh0)
+            swap 1 dup 6 eq mul             // _ [d] *[tsh] num_hashes k [tsh] d4 d3 d2 (d0 == tsh0)*(d1 == tsh1)
+            swap 1 dup 6 eq mul             // _ [d] *[tsh] num_hashes k [tsh] d4 d3 eq_so_far
+            swap 1 dup 6 eq mul             // _ [d] *[tsh] num_hashes k [tsh] d4 eq_so_far
+            swap 1 dup 6 eq mul             // _ [d] *[tsh] num_hashes k [tsh] (d == tsh)
+            swap 5 pop 5                    // _ [d] *[tsh] num_hashes k (d == tsh)
+            skiz return                     // _ [d] *[tsh] num_hashes k
+
+            push 1 add                      // _ [d] *[tsh] num_hashes (k + 1)
+            recurse
+
+        // BEFORE: _ *[digest] num_digests i
+        // AFTER: _ *[digest] num_digests num_digests
+        {output_digests}:
+            dup 1 dup 1 eq                  // _ *[digest] num_digests i (num_digests == i)
+            skiz return                     // _ *[digest] num_digests i
+
+            dup 2 push 1 add                // _ *[digest] num_digests i (*[digest] + 1)
+            dup 1 push 5 mul add            // _ *[digest] num_digests i *digest
+            call {read_digest}              // _ *[digest] num_digests i [digest]
+            write_io 5                      // _ *[digest] num_digests i
+
+            push 1 add                      // _ *[digest] num_digests (i + 1)
+            recurse
+        };
+
+        (code, library)
+    }
+
+    fn crash_conditions() -> Vec<String> {
+        vec![
+            "the kernel from the witness does not match the hash in the public input".to_string(),
+            "the salted hash of the input utxos does not match the public input".to_string(),
+            "the salted hash of the output utxos does not match the public input".to_string(),
+            "the addition records in the kernel do not commit to the output utxos".to_string(),
+            "the type script of some coin is not listed".to_string(),
+        ]
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::models::consensus::SecretWitness;
+    use crate::tests::shared::pseudorandom_kernel_to_type_scripts_witness;
+    use rand::{rngs::StdRng, Rng, SeedableRng};
+    use tasm_lib::traits::compiled_program::test_rust_shadow;
+    use triton_vm::prelude::{Claim, StarkParameters};
+
+    fn halts(witness: &KernelToTypeScriptsWitness, public_input: &[BFieldElement]) -> bool {
+        KernelToTypeScripts::program()
+            .run(
+                PublicInput::new(public_input.to_vec()),
+                witness.nondeterminism(),
+            )
+            .is_ok()
+    }
+
+    fn pseudorandom_witness(seed_byte: u8) -> KernelToTypeScriptsWitness {
+        let mut seed = [0u8; 32];
+        seed[0] = 0xb7;
+        seed[1] = seed_byte;
+        let mut rng: StdRng = SeedableRng::from_seed(seed);
+        pseudorandom_kernel_to_type_scripts_witness(rng.gen())
+    }
+
+    #[test]
+    fn test_graceful_halt() {
+        let witness = pseudorandom_witness(0x01);
+        let expected_output = witness
+            .type_script_hashes
+            .iter()
+            .flat_map(|type_script_hash| type_script_hash.values())
+            .collect_vec();
+
+        let program = KernelToTypeScripts::program();
+        let public_input = PublicInput::new(witness.public_input());
+        let output = match program.run(public_input.clone(), witness.nondeterminism()) {
+            Ok(output) => output,
+            Err(err) => panic!("Failed:\n last state was:\n{err}"),
+        };
+        assert_eq!(expected_output, output);
+
+        if std::env::var("DYING_TO_PROVE").is_ok() {
+            let claim: Claim = Claim {
+                program_digest: program.hash::<Hash>(),
+                input: public_input.individual_tokens,
+                output,
+            };
+            let maybe_proof = triton_vm::prove(
+                StarkParameters::default(),
+                &claim,
+                &program,
+                witness.nondeterminism(),
+            );
+            assert!(maybe_proof.is_ok());
+
+            assert!(triton_vm::verify(
+                StarkParameters::default(),
+                &claim,
+                &maybe_proof.unwrap()
+            ));
+        }
+    }
+
+    #[test]
+    fn program_is_deterministic() {
+        let program = KernelToTypeScripts::program();
+        let other_program = KernelToTypeScripts::program();
+        assert_eq!(program, other_program);
+    }
+
+    #[test]
+    fn tasm_matches_rust() {
+        let witness = pseudorandom_witness(0x02);
+        let public_input = PublicInput::new(witness.public_input());
+
+        test_rust_shadow::<KernelToTypeScripts>(&public_input, &witness.nondeterminism());
+    }
+
+    #[test]
+    fn crashes_on_kernel_that_does_not_match_public_input() {
+        let witness = pseudorandom_witness(0x03);
+        let public_input = witness.public_input();
+        assert!(halts(&witness, &public_input));
+
+        let mut other_kernel = witness.clone();
+        other_kernel.kernel.timestamp += BFieldElement::new(1);
+        assert!(!halts(&other_kernel, &public_input));
+    }
+
+    #[test]
+    fn crashes_on_input_utxos_that_do_not_match_salted_hash() {
+        let witness = pseudorandom_witness(0x04);
+        let public_input = witness.public_input();
+
+        let mut wrong_salt = witness.clone();
+        wrong_salt.input_utxos_salt = Digest::default();
+        assert!(!halts(&wrong_salt, &public_input));
+
+        let mut dropped_input = witness;
+        dropped_input.input_utxos.pop();
+        assert!(!halts(&dropped_input, &public_input));
+    }
+
+    #[test]
+    fn crashes_on_output_utxos_that_do_not_match_salted_hash() {
+        let witness = pseudorandom_witness(0x07);
+        let public_input = witness.public_input();
+
+        let mut wrong_salt = witness.clone();
+        wrong_salt.output_utxos_salt = Digest::default();
+        assert!(!halts(&wrong_salt, &public_input));
+
+        // outputs that match the addition records but not the salted hash in the public input
+        let mut other_public_input = public_input.clone();
+        other_public_input[DIGEST_LENGTH * 2] += BFieldElement::new(1);
+        assert!(!halts(&witness, &other_public_input));
+    }
+
+    #[test]
+    fn crashes_on_outputs_that_do_not_match_addition_records() {
+        let witness = pseudorandom_witness(0x05);
+        let public_input = witness.public_input();
+
+        let mut wrong_randomness = witness.clone();
+        wrong_randomness.output_sender_randomnesses[0] = Digest::default();
+        assert!(!halts(&wrong_randomness, &public_input));
+
+        let mut wrong_receiver = witness.clone();
+        wrong_receiver.output_receiver_digests[1] = Digest::default();
+        assert!(!halts(&wrong_receiver, &public_input));
+
+        let mut inflated_output = witness.clone();
+        inflated_output.output_utxos[0] = inflated_output.input_utxos[0].clone();
+        assert!(!halts(&inflated_output, &public_input));
+
+        let mut dropped_output = witness;
+        dropped_output.output_utxos.pop();
+        dropped_output.output_sender_randomnesses.pop();
+        dropped_output.output_receiver_digests.pop();
+        assert!(!halts(&dropped_output, &public_input));
+    }
+
+    #[test]
+    fn crashes_on_unlisted_type_script() {
+        let witness = pseudorandom_witness(0x06);
+        let public_input = witness.public_input();
+
+        for index in 0..witness.type_script_hashes.len() {
+            let mut unlisted = witness.clone();
+            unlisted.type_script_hashes.remove(index);
+            assert!(!halts(&unlisted, &public_input));
+        }
+
+        // Listing more type scripts than the coins have is allowed; the extra type scripts
+        // must halt as well.
+        let mut superfluous = witness;
+        superfluous.type_script_hashes.push(Digest::default());
+        assert!(halts(&superfluous, &public_input));
+    }
+}
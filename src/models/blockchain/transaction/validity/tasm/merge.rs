@@ -0,0 +1,582 @@
+use crate::models::consensus::mast_hash::MastHash;
+use crate::prelude::{triton_vm, twenty_first};
+
+use itertools::Itertools;
+use tasm_lib::data_type::DataType;
+use tasm_lib::hashing::hash_varlen::HashVarlen;
+use tasm_lib::library::Library;
+use tasm_lib::list::contiguous_list::get_pointer_list::GetPointerList;
+use tasm_lib::list::ListType;
+use tasm_lib::memory::push_ram_to_stack::PushRamToStack;
+use tasm_lib::memory::FIRST_NON_DETERMINISTICALLY_INITIALIZED_MEMORY_ADDRESS;
+use tasm_lib::structure::tasm_object::TasmObject;
+use tasm_lib::traits::compiled_program::CompiledProgram;
+use tasm_lib::DIGEST_LENGTH;
+use triton_vm::instruction::LabelledInstruction;
+use triton_vm::prelude::{triton_asm, BFieldElement, NonDeterminism, PublicInput};
+use twenty_first::shared_math::{bfield_codec::BFieldCodec, tip5::Digest};
+use twenty_first::util_types::algebraic_hasher::AlgebraicHasher;
+
+use super::transaction_kernel_mast_hash::TransactionKernelMastHash;
+use crate::models::blockchain::shared::Hash;
+use crate::models::blockchain::transaction::transaction_kernel::TransactionKernel;
+use crate::models::blockchain::transaction::validity::merge::{Merge, MergeWitness};
+
+/// Assert that the new list is a permutation of the left list followed by the right one.
+fn assert_permutation<T: BFieldCodec>(
+    left: &[T],
+    right: &[T],
+    new: &[T],
+    permutation: &[BFieldElement],
+) {
+    let concatenation = left.iter().chain(right.iter()).collect_vec();
+    assert_eq!(concatenation.len(), new.len());
+    assert_eq!(concatenation.len(), permutation.len());
+    assert!(permutation.iter().all_unique());
+    for (item, index) in new.iter().zip_eq(permutation.iter()) {
+        let index = index.value() as usize;
+        assert!(index < concatenation.len());
+        assert_eq!(Hash::hash(concatenation[index]), Hash::hash(item));
+    }
+}
+
+impl CompiledProgram for Merge {
+    fn rust_shadow(
+        public_input: &PublicInput,
+        nondeterminism: &NonDeterminism<BFieldElement>,
+    ) -> anyhow::Result<Vec<BFieldElement>> {
+        let hash_of_kernel = *Digest::decode(
+            &public_input
+                .individual_tokens
+                .iter()
+                .copied()
+                .take(DIGEST_LENGTH)
+                .rev()
+                .collect_vec(),
+        )
+        .expect("Could not decode public input in Merge");
+
+        // 1. read witness data
+        let witness = *MergeWitness::decode_from_memory(
+            &nondeterminism.ram,
+            FIRST_NON_DETERMINISTICALLY_INITIALIZED_MEMORY_ADDRESS,
+        )
+        .unwrap();
+        let left = &witness.left_kernel;
+        let right = &witness.right_kernel;
+        let new = &witness.new_kernel;
+
+        // 2. assert that the new kernel matches the hash in the public input
+        assert_eq!(hash_of_kernel, new.mast_hash());
+
+        // 3. assert that the inputs, outputs and public announcements are shuffled
+        assert_permutation(
+            &left.inputs,
+            &right.inputs,
+            &new.inputs,
+            &witness.input_permutation,
+        );
+        assert_permutation(
+            &left.outputs,
+            &right.outputs,
+            &new.outputs,
+            &witness.output_permutation,
+        );
+        assert_permutation(
+            &left.public_announcements,
+            &right.public_announcements,
+            &new.public_announcements,
+            &witness.public_announcement_permutation,
+        );
+
+        // 4. assert that the fee is the sum of the fees
+        assert_eq!(new.fee, left.fee + right.fee);
+
+        // 5. assert that there is at most one coinbase, and that it is kept
+        assert!(left.coinbase.is_none() || right.coinbase.is_none());
+        assert_eq!(new.coinbase, left.coinbase.or(right.coinbase));
+
+        // 6. assert that the timestamp is the later of the two
+        assert_eq!(
+            new.timestamp.value(),
+            left.timestamp.value().max(right.timestamp.value())
+        );
+
+        // 7. assert that all three are about the same mutator set
+        assert_eq!(new.mutator_set_hash, left.mutator_set_hash);
+        assert_eq!(new.mutator_set_hash, right.mutator_set_hash);
+
+        // 8. assert that the minimum block height is the higher of the two
+        assert_eq!(
+            new.minimum_block_height.value(),
+            left.minimum_block_height
+                .value()
+                .max(right.minimum_block_height.value())
+        );
+
+        // 9. output the hashes of the kernels that were merged
+        Ok([left.mast_hash().values(), right.mast_hash().values()].concat())
+    }
+
+    fn code() -> (Vec<LabelledInstruction>, Library) {
+        let mut library = Library::new();
+        let transaction_kernel_mast_hash = library.import(Box::new(TransactionKernelMastHash));
+        let hash_varlen = library.import(Box::new(HashVarlen));
+        let read_digest = library.import(Box::new(PushRamToStack {
+            data_type: DataType::Digest,
+        }));
+        let get_pointer_list = library.import(Box::new(GetPointerList {
+            output_list_type: ListType::Unsafe,
+        }));
+
+        // field getters
+        type W = MergeWitness;
+        let witness_to_left_kernel = tasm_lib::field!(W::left_kernel);
+        let witness_to_right_kernel = tasm_lib::field!(W::right_kernel);
+        let witness_to_new_kernel = tasm_lib::field!(W::new_kernel);
+        let witness_to_input_permutation = tasm_lib::field!(W::input_permutation);
+        let witness_to_output_permutation = tasm_lib::field!(W::output_permutation);
+        let witness_to_public_announcement_permutation =
+            tasm_lib::field!(W::public_announcement_permutation);
+        let kernel_to_inputs = tasm_lib::field!(TransactionKernel::inputs);
+        let kernel_to_outputs = tasm_lib::field!(TransactionKernel::outputs);
+        let kernel_to_public_announcements =
+            tasm_lib::field!(TransactionKernel::public_announcements);
+        let kernel_to_fee = tasm_lib::field!(TransactionKernel::fee);
+        let kernel_to_coinbase = tasm_lib::field!(TransactionKernel::coinbase);
+        let kernel_to_coinbase_with_size = tasm_lib::field_with_size!(TransactionKernel::coinbase);
+        let kernel_to_timestamp = tasm_lib::field!(TransactionKernel::timestamp);
+        let kernel_to_mutator_set_hash = tasm_lib::field!(TransactionKernel::mutator_set_hash);
+        let kernel_to_minimum_block_height =
+            tasm_lib::field!(TransactionKernel::minimum_block_height);
+
+        let label = |name: &str| format!("tasm_neptune_transaction_merge_{name}");
+        let left_coinbase = label("left_coinbase");
+        let right_coinbase = label("right_coinbase");
+
+        // Subroutines that assert that the new list is a permutation of the left list
+        // followed by the right one. The permutation maps every item of the new list to
+        // an index into the concatenation; its indices must be distinct and in range, and
+        // the items they point to must hash to the same digests.
+        // BEFORE: _ *left *right *new *permutation
+        // AFTER: _ *left *right *new *permutation
+        let check_permutation = |name: &str, item: Vec<LabelledInstruction>| {
+            let check = label(&format!("check_{name}"));
+            let check_loop = label(&format!("check_{name}_loop"));
+            let distinct = label(&format!("check_{name}_distinct"));
+            let code = triton_asm! {
+                {check}:
+                    dup 3 read_mem 1 pop 1      // _ *l *r *n *p left_len
+                    dup 3 read_mem 1 pop 1      // _ *l *r *n *p left_len right_len
+                    add                         // _ *l *r *n *p total
+                    dup 2 read_mem 1 pop 1
+                    dup 1 eq assert
+                    dup 1 read_mem 1 pop 1
+                    dup 1 eq assert             // _ *l *r *n *p total
+                    push 0                      // _ *l *r *n *p total 0
+                    call {check_loop}           // _ *l *r *n *p total total
+                    pop 2                       // _ *l *r *n *p
+                    return
+
+                // BEFORE: _ *l *r *n *p total j
+                // AFTER: _ *l *r *n *p total total
+                {check_loop}:
+                    dup 1 dup 1 eq              // _ *l *r *n *p total j (total == j)
+                    skiz return                 // _ *l *r *n *p total j
+
+                    dup 2 push 1 add
+                    dup 1 add
+                    read_mem 1 pop 1            // _ *l *r *n *p total j k
+
+                    // the index is in range
+                    dup 0 split pop 1 push 0 eq assert
+                    dup 2 push -1 add
+                    dup 1 push -1 mul add
+                    split pop 1 push 0 eq assert
+
+                    // no later index is the same
+                    dup 1 push 1 add            // _ *l *r *n *p total j k (j + 1)
+                    call {distinct}             // _ *l *r *n *p total j k total
+                    pop 1                       // _ *l *r *n *p total j k
+
+                    // find the item in the left or the right list
+                    dup 6 read_mem 1 pop 1      // _ *l *r *n *p total j k left_len
+                    dup 1 dup 1 push -1 mul add
+                    split pop 1 push 0 eq       // _ *l *r *n *p total j k left_len is_right
+                    swap 1 dup 1 mul            // _ *l *r *n *p total j k is_right (is_right * left_len)
+                    push -1 mul dup 2 add       // _ *l *r *n *p total j k is_right index
+                    swap 2 pop 1                // _ *l *r *n *p total j index is_right
+                    dup 6 dup 8 push -1 mul add
+                    mul dup 7 add               // _ *l *r *n *p total j index *list
+                    swap 1                      // _ *l *r *n *p total j *list index
+                    {&item}                     // _ *l *r *n *p total j *item item_size
+                    call {hash_varlen}          // _ *l *r *n *p total j [item_digest]
+
+                    // it hashes to the same digest as the new item
+                    dup 8 dup 6                 // _ *l *r *n *p total j [item_digest] *n j
+                    {&item}                     // _ *l *r *n *p total j [item_digest] *new_item new_item_size
+                    call {hash_varlen}          // _ *l *r *n *p total j [item_digest] [new_item_digest]
+                    assert_vector
+                    pop 5                       // _ *l *r *n *p total j
+
+                    push 1 add                  // _ *l *r *n *p total (j + 1)
+                    recurse
+
+                // BEFORE: _ *l *r *n *p total j k i
+                // AFTER: _ *l *r *n *p total j k total
+                {distinct}:
+                    dup 3 dup 1 eq              // _ *l *r *n *p total j k i (total == i)
+                    skiz return                 // _ *l *r *n *p total j k i
+
+                    dup 4 push 1 add
+                    dup 1 add
+                    read_mem 1 pop 1            // _ *l *r *n *p total j k i permutation[i]
+                    dup 2 eq
+                    push 0 eq assert            // _ *l *r *n *p total j k i
+
+                    push 1 add                  // _ *l *r *n *p total j k (i + 1)
+                    recurse
+            };
+            (check, code)
+        };
+
+        // BEFORE: _ *[*item] index
+        // AFTER: _ *item item_size
+        let dynamically_sized_item = triton_asm! {
+            push 1 add add
+            read_mem 1 pop 1
+            dup 0 push -1 add
+            read_mem 1 pop 1
+        };
+
+        // BEFORE: _ *[item] index
+        // AFTER: _ *item item_size
+        let digest_item = triton_asm! {
+            push {DIGEST_LENGTH} mul
+            push 1 add add
+            push {DIGEST_LENGTH}
+        };
+
+        let (check_inputs, check_inputs_code) =
+            check_permutation("inputs", dynamically_sized_item.clone());
+        let (check_outputs, check_outputs_code) = check_permutation("outputs", digest_item);
+        let (check_public_announcements, check_public_announcements_code) =
+            check_permutation("public_announcements", dynamically_sized_item);
+
+        // fee limbs, least significant first, are added with carry
+        // BEFORE: _ *witness *left_fee *right_fee *new_fee 0
+        // AFTER: _ *witness *left_fee *right_fee *new_fee carry
+        let mut add_fees = vec![];
+        for i in 0..4 {
+            add_fees.extend(triton_asm! {
+                dup 3 push {i} add read_mem 1 pop 1
+                dup 0 split pop 1 push 0 eq assert
+                dup 3 push {i} add read_mem 1 pop 1
+                dup 0 split pop 1 push 0 eq assert
+                add add
+                split                   // _ *witness *lf *rf *nf carry sum_i
+                dup 2 push {i} add read_mem 1 pop 1
+                eq assert               // _ *witness *lf *rf *nf carry
+            });
+        }
+
+        let code = triton_asm! {
+
+        // 1. Witness was already loaded into memory, just point to it
+        push {FIRST_NON_DETERMINISTICALLY_INITIALIZED_MEMORY_ADDRESS} // _ *witness
+
+        // 2. assert that the new kernel hash == public input
+        dup 0                               // _ *witness *witness
+        {&witness_to_new_kernel}            // _ *witness *new_kernel
+        call {transaction_kernel_mast_hash} // _ *witness [new_kernel_digest]
+        read_io 5                           // _ *witness [new_kernel_digest] [input_kernel_digest]
+        assert_vector                       // _ *witness [new_kernel_digest]
+        pop 5                               // _ *witness
+
+        // 3. assert that the inputs, outputs and public announcements are shuffled
+        dup 0 {&witness_to_left_kernel} {&kernel_to_inputs} call {get_pointer_list}
+        dup 1 {&witness_to_right_kernel} {&kernel_to_inputs} call {get_pointer_list}
+        dup 2 {&witness_to_new_kernel} {&kernel_to_inputs} call {get_pointer_list}
+        dup 3 {&witness_to_input_permutation}
+                                            // _ *witness *left_inputs *right_inputs *new_inputs *permutation
+        call {check_inputs}
+        pop 4                               // _ *witness
+
+        dup 0 {&witness_to_left_kernel} {&kernel_to_outputs}
+        dup 1 {&witness_to_right_kernel} {&kernel_to_outputs}
+        dup 2 {&witness_to_new_kernel} {&kernel_to_outputs}
+        dup 3 {&witness_to_output_permutation}
+                                            // _ *witness *left_outputs *right_outputs *new_outputs *permutation
+        call {check_outputs}
+        pop 4                               // _ *witness
+
+        dup 0 {&witness_to_left_kernel} {&kernel_to_public_announcements} call {get_pointer_list}
+        dup 1 {&witness_to_right_kernel} {&kernel_to_public_announcements} call {get_pointer_list}
+        dup 2 {&witness_to_new_kernel} {&kernel_to_public_announcements} call {get_pointer_list}
+        dup 3 {&witness_to_public_announcement_permutation}
+                                            // _ *witness *left_pas *right_pas *new_pas *permutation
+        call {check_public_announcements}
+        pop 4                               // _ *witness
+
+        // 4. assert that the fee is the sum of the fees
+        dup 0 {&witness_to_left_kernel} {&kernel_to_fee}
+        dup 1 {&witness_to_right_kernel} {&kernel_to_fee}
+        dup 2 {&witness_to_new_kernel} {&kernel_to_fee}
+        push 0                              // _ *witness *left_fee *right_fee *new_fee 0
+        {&add_fees}                         // _ *witness *left_fee *right_fee *new_fee carry
+        push 0 eq assert
+        pop 3                               // _ *witness
+
+        // 5. assert that there is at most one coinbase, and that it is kept
+        dup 0 {&witness_to_left_kernel} {&kernel_to_coinbase} read_mem 1 pop 1
+        dup 1 {&witness_to_right_kernel} {&kernel_to_coinbase} read_mem 1 pop 1
+                                            // _ *witness left_is_some right_is_some
+        dup 1 mul push 0 eq assert          // _ *witness left_is_some
+        dup 0 skiz call {left_coinbase}     // _ *witness left_is_some
+        push 0 eq skiz call {right_coinbase}
+                                            // _ *witness
+
+        // 6. assert that the timestamp is the later of the two, that is, that it is one
+        // of them and that it is less than 2^63 after both in the field
+        dup 0 {&witness_to_new_kernel} {&kernel_to_timestamp} read_mem 1 pop 1
+                                            // _ *witness timestamp
+        dup 1 {&witness_to_left_kernel} {&kernel_to_timestamp} read_mem 1 pop 1
+        push -1 mul dup 1 add               // _ *witness timestamp (timestamp - left_timestamp)
+        dup 2 {&witness_to_right_kernel} {&kernel_to_timestamp} read_mem 1 pop 1
+        push -1 mul dup 2 add               // _ *witness timestamp left_delta right_delta
+        dup 1 split pop 1 push 2147483648 and push 0 eq assert
+        dup 0 split pop 1 push 2147483648 and push 0 eq assert
+        mul push 0 eq assert
+        pop 1                               // _ *witness
+
+        // 7. assert that all three are about the same mutator set
+        dup 0 {&witness_to_new_kernel} {&kernel_to_mutator_set_hash} call {read_digest}
+        dup 5 {&witness_to_left_kernel} {&kernel_to_mutator_set_hash} call {read_digest}
+        assert_vector                       // _ *witness [mutator_set_hash]
+        dup 5 {&witness_to_right_kernel} {&kernel_to_mutator_set_hash} call {read_digest}
+        assert_vector
+        pop 5                               // _ *witness
+
+        // 8. assert that the minimum block height is the higher of the two, in the same way
+        // as the timestamp
+        dup 0 {&witness_to_new_kernel} {&kernel_to_minimum_block_height} read_mem 1 pop 1
+                                            // _ *witness height
+        dup 1 {&witness_to_left_kernel} {&kernel_to_minimum_block_height} read_mem 1 pop 1
+        push -1 mul dup 1 add               // _ *witness height (height - left_height)
+        dup 2 {&witness_to_right_kernel} {&kernel_to_minimum_block_height} read_mem 1 pop 1
+        push -1 mul dup 2 add               // _ *witness height left_delta right_delta
+        dup 1 split pop 1 push 2147483648 and push 0 eq assert
+        dup 0 split pop 1 push 2147483648 and push 0 eq assert
+        mul push 0 eq assert
+        pop 1                               // _ *witness
+
+        // 9. output the hashes of the kernels that were merged
+        dup 0 {&witness_to_left_kernel}
+        call {transaction_kernel_mast_hash} // _ *witness [left_kernel_digest]
+        write_io 5                          // _ *witness
+        {&witness_to_right_kernel}
+        call {transaction_kernel_mast_hash} // _ [right_kernel_digest]
+        write_io 5                          // _
+
+        halt
+
+        // BEFORE: _ *witness left_is_some
+        // AFTER: _ *witness left_is_some
+        {left_coinbase}:
+            dup 1 {&witness_to_left_kernel} {&kernel_to_coinbase_with_size}
+            call {hash_varlen}              // _ *witness left_is_some [left_coinbase_digest]
+            dup 6 {&witness_to_new_kernel} {&kernel_to_coinbase_with_size}
+            call {hash_varlen}              // _ *witness left_is_some [left_coinbase_digest] [new_coinbase_digest]
+            assert_vector
+            pop 5
+            return
+
+        // BEFORE: _ *witness
+        // AFTER: _ *witness
+        {right_coinbase}:
+            dup 0 {&witness_to_right_kernel} {&kernel_to_coinbase_with_size}
+            call {hash_varlen}              // _ *witness [right_coinbase_digest]
+            dup 5 {&witness_to_new_kernel} {&kernel_to_coinbase_with_size}
+            call {hash_varlen}              // _ *witness [right_coinbase_digest] [new_coinbase_digest]
+            assert_vector
+            pop 5
+            return
+
+        {&check_inputs_code}
+        {&check_outputs_code}
+        {&check_public_announcements_code}
+        };
+
+        (code, library)
+    }
+
+    fn crash_conditions() -> Vec<String> {
+        vec![
+            "the new kernel does not match the hash in the public input".to_string(),
+            "the inputs, outputs or public announcements are not shuffled".to_string(),
+            "the fee is not the sum of the fees".to_string(),
+            "both kernels have a coinbase, or the coinbase is not kept".to_string(),
+            "the timestamp is not the later of the two".to_string(),
+            "the kernels are not about the same mutator set".to_string(),
+            "the minimum block height is not the higher of the two".to_string(),
+        ]
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::models::blockchain::transaction::neptune_coins::NeptuneCoins;
+    use crate::models::blockchain::transaction::transaction_kernel::pseudorandom_transaction_kernel;
+    use crate::models::consensus::SecretWitness;
+    use rand::{rngs::StdRng, Rng, SeedableRng};
+    use tasm_lib::traits::compiled_program::test_rust_shadow;
+
+    fn halts(witness: &MergeWitness) -> bool {
+        Merge::program()
+            .run(
+                PublicInput::new(witness.public_input()),
+                witness.nondeterminism(),
+            )
+            .is_ok()
+    }
+
+    fn pseudorandom_witness(seed_byte: u8) -> MergeWitness {
+        let mut seed = [0u8; 32];
+        seed[0] = 0x3e;
+        seed[1] = seed_byte;
+        let mut rng: StdRng = SeedableRng::from_seed(seed);
+        let mut left_kernel = pseudorandom_transaction_kernel(rng.gen(), 2, 3, 1);
+        let mut right_kernel = pseudorandom_transaction_kernel(rng.gen(), 3, 2, 2);
+        left_kernel.fee = NeptuneCoins::new(rng.gen_range(0..1000));
+        right_kernel.fee = NeptuneCoins::new(rng.gen_range(0..1000));
+        left_kernel.coinbase = Some(NeptuneCoins::new(100));
+        right_kernel.coinbase = None;
+        left_kernel.timestamp = BFieldElement::new(rng.gen_range(0..1 << 40));
+        right_kernel.timestamp = BFieldElement::new(rng.gen_range(0..1 << 40));
+        right_kernel.mutator_set_hash = left_kernel.mutator_set_hash;
+        MergeWitness::new(&left_kernel, &right_kernel)
+    }
+
+    #[test]
+    fn test_graceful_halt() {
+        let witness = pseudorandom_witness(0x01);
+        let output = Merge::program()
+            .run(
+                PublicInput::new(witness.public_input()),
+                witness.nondeterminism(),
+            )
+            .unwrap();
+        assert_eq!(witness.output(), output);
+    }
+
+    #[test]
+    fn program_is_deterministic() {
+        let program = Merge::program();
+        let other_program = Merge::program();
+        assert_eq!(program, other_program);
+    }
+
+    #[test]
+    fn tasm_matches_rust() {
+        let witness = pseudorandom_witness(0x02);
+        test_rust_shadow::<Merge>(
+            &PublicInput::new(witness.public_input()),
+            &witness.nondeterminism(),
+        );
+    }
+
+    #[test]
+    fn halts_on_any_coinbase_and_timestamp_order() {
+        let witness = pseudorandom_witness(0x03);
+        let (left_kernel, mut right_kernel) = (witness.left_kernel, witness.right_kernel);
+        assert!(halts(&MergeWitness::new(&left_kernel, &right_kernel)));
+        assert!(halts(&MergeWitness::new(&right_kernel, &left_kernel)));
+
+        right_kernel.timestamp = left_kernel.timestamp;
+        assert!(halts(&MergeWitness::new(&left_kernel, &right_kernel)));
+
+        let mut no_coinbase = left_kernel;
+        no_coinbase.coinbase = None;
+        assert!(halts(&MergeWitness::new(&no_coinbase, &right_kernel)));
+    }
+
+    #[test]
+    fn crashes_on_items_that_are_not_shuffled() {
+        let witness = pseudorandom_witness(0x04);
+        assert!(halts(&witness));
+
+        let mut dropped_input = witness.clone();
+        dropped_input.new_kernel.inputs.pop();
+        dropped_input.input_permutation.pop();
+        assert!(!halts(&dropped_input));
+
+        let mut repeated_output = witness.clone();
+        repeated_output.new_kernel.outputs[1] = repeated_output.new_kernel.outputs[0];
+        repeated_output.output_permutation[1] = repeated_output.output_permutation[0];
+        assert!(!halts(&repeated_output));
+
+        let mut out_of_range = witness.clone();
+        let total = out_of_range.output_permutation.len() as u64;
+        out_of_range.output_permutation[0] = BFieldElement::new(total);
+        assert!(!halts(&out_of_range));
+
+        let mut foreign_announcement = witness;
+        foreign_announcement.new_kernel.public_announcements[0]
+            .message
+            .push(BFieldElement::new(1));
+        assert!(!halts(&foreign_announcement));
+    }
+
+    #[test]
+    fn crashes_on_wrong_fee_coinbase_timestamp_or_mutator_set() {
+        let witness = pseudorandom_witness(0x05);
+
+        let mut wrong_fee = witness.clone();
+        wrong_fee.new_kernel.fee = wrong_fee.left_kernel.fee;
+        assert!(!halts(&wrong_fee));
+
+        let mut two_coinbases = witness.clone();
+        two_coinbases.right_kernel.coinbase = Some(NeptuneCoins::new(1));
+        assert!(!halts(&two_coinbases));
+
+        let mut dropped_coinbase = witness.clone();
+        dropped_coinbase.new_kernel.coinbase = None;
+        assert!(!halts(&dropped_coinbase));
+
+        let mut early_timestamp = witness.clone();
+        early_timestamp.new_kernel.timestamp = BFieldElement::new(
+            early_timestamp
+                .left_kernel
+                .timestamp
+                .value()
+                .min(early_timestamp.right_kernel.timestamp.value()),
+        );
+        if early_timestamp.left_kernel.timestamp != early_timestamp.right_kernel.timestamp {
+            assert!(!halts(&early_timestamp));
+        }
+
+        let mut low_minimum_block_height = witness.clone();
+        low_minimum_block_height.new_kernel.minimum_block_height = BFieldElement::new(
+            low_minimum_block_height
+                .left_kernel
+                .minimum_block_height
+                .value()
+                .min(
+                    low_minimum_block_height
+                        .right_kernel
+                        .minimum_block_height
+                        .value(),
+                ),
+        );
+        if low_minimum_block_height.left_kernel.minimum_block_height
+            != low_minimum_block_height.right_kernel.minimum_block_height
+        {
+            assert!(!halts(&low_minimum_block_height));
+        }
+
+        let mut other_mutator_set = witness;
+        other_mutator_set.right_kernel.mutator_set_hash = Digest::default();
+        assert!(!halts(&other_mutator_set));
+    }
+}
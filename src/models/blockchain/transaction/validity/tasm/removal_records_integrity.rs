@@ -5,6 +5,7 @@ use std::collections::HashSet;
 
 use itertools::Itertools;
 use tasm_lib::data_type::DataType;
+use tasm_lib::hashing::hash_varlen::HashVarlen;
 use tasm_lib::library::Library;
 use tasm_lib::memory::FIRST_NON_DETERMINISTICALLY_INITIALIZED_MEMORY_ADDRESS;
 use tasm_lib::structure::tasm_object::TasmObject;
@@ -33,6 +34,7 @@ use twenty_first::{
 use crate::models::blockchain::transaction::validity::removal_records_integrity::{
     RemovalRecordsIntegrity, RemovalRecordsIntegrityWitness,
 };
+use crate::models::blockchain::transaction::validity::salted_utxos_hash;
 use crate::{
     models::blockchain::{
         shared::Hash,
@@ -174,7 +176,14 @@ impl CompiledProgram for RemovalRecordsIntegrity {
                 .0
             }));
 
-        Ok(vec![])
+        // 6. output the salted hash of the input utxos, which links this claim to the
+        // claims about the input utxos
+        Ok(salted_utxos_hash(
+            &removal_record_integrity_witness.input_utxos,
+            removal_record_integrity_witness.input_utxos_salt,
+        )
+        .values()
+        .to_vec())
     }
 
     fn code() -> (Vec<LabelledInstruction>, Library) {
@@ -223,6 +232,7 @@ impl CompiledProgram for RemovalRecordsIntegrity {
             data_type: DataType::Digest,
         }));
         let _compute_indices = library.import(Box::new(ComputeIndices));
+        let hash_varlen = library.import(Box::new(HashVarlen));
 
         // field getters
         let witness_to_kernel = tasm_lib::field!(RemovalRecordsIntegrityWitness::kernel);
@@ -233,6 +243,10 @@ impl CompiledProgram for RemovalRecordsIntegrity {
         let witness_to_aocl = tasm_lib::field!(RemovalRecordsIntegrityWitness::aocl);
         let kernel_to_mutator_set_hash = tasm_lib::field!(TransactionKernel::mutator_set_hash);
         let witness_to_utxos = tasm_lib::field!(RemovalRecordsIntegrityWitness::input_utxos);
+        let witness_to_utxos_with_size =
+            tasm_lib::field_with_size!(RemovalRecordsIntegrityWitness::input_utxos);
+        let witness_to_utxos_salt =
+            tasm_lib::field!(RemovalRecordsIntegrityWitness::input_utxos_salt);
         let witness_to_mps = tasm_lib::field!(RemovalRecordsIntegrityWitness::membership_proofs);
         let kernel_to_inputs = tasm_lib::field!(TransactionKernel::inputs);
         let aocl_to_leaf_count = tasm_lib::field!(MmraH::leaf_count);
@@ -346,6 +360,17 @@ impl CompiledProgram for RemovalRecordsIntegrity {
 
         assert
 
+        // 6. output the salted hash of the input utxos
+        push {FIRST_NON_DETERMINISTICALLY_INITIALIZED_MEMORY_ADDRESS} // _ *peaks leaf_count_hi leaf_count_lo *witness
+        dup 0                              // _ *peaks leaf_count_hi leaf_count_lo *witness *witness
+        {&witness_to_utxos_salt}           // _ *peaks leaf_count_hi leaf_count_lo *witness *salt
+        call {read_digest}                 // _ *peaks leaf_count_hi leaf_count_lo *witness [salt]
+        dup 5                              // _ *peaks leaf_count_hi leaf_count_lo *witness [salt] *witness
+        {&witness_to_utxos_with_size}      // _ *peaks leaf_count_hi leaf_count_lo *witness [salt] *utxos utxos_size
+        call {hash_varlen}                 // _ *peaks leaf_count_hi leaf_count_lo *witness [salt] [utxos_hash]
+        hash                               // _ *peaks leaf_count_hi leaf_count_lo *witness [salted_utxos_hash]
+        write_io 5                         // _ *peaks leaf_count_hi leaf_count_lo *witness
+
         halt
         };
 
@@ -412,6 +437,12 @@ mod tests {
             .reversed()
             .values()
             .to_vec();
+        let expected_output = salted_utxos_hash(
+            &removal_record_integrity_witness.input_utxos,
+            removal_record_integrity_witness.input_utxos_salt,
+        )
+        .values()
+        .to_vec();
 
         let mut memory = HashMap::default();
         encode_to_memory(
@@ -422,16 +453,17 @@ mod tests {
         let nondeterminism = NonDeterminism::new(vec![]).with_ram(memory);
         let program = RemovalRecordsIntegrity::program();
         let run_res = program.run(PublicInput::new(stdin.clone()), nondeterminism.clone());
-        match run_res {
-            Ok(_) => println!("Run successful."),
+        let output = match run_res {
+            Ok(output) => output,
             Err(err) => panic!("Failed:\n last state was:\n{err}"),
         };
+        assert_eq!(expected_output, output);
 
         if std::env::var("DYING_TO_PROVE").is_ok() {
             let claim: Claim = Claim {
                 program_digest: program.hash::<Hash>(),
                 input: stdin,
-                output: vec![],
+                output,
             };
             let maybe_proof =
                 triton_vm::prove(StarkParameters::default(), &claim, &program, nondeterminism);
@@ -99,6 +99,8 @@ impl BasicSnippet for TransactionKernelMastHash {
             tasm_lib::field_with_size!(TransactionKernel::timestamp);
         let kernel_to_mutator_set_hash_with_size =
             tasm_lib::field_with_size!(TransactionKernel::mutator_set_hash);
+        let kernel_to_minimum_block_height_with_size =
+            tasm_lib::field_with_size!(TransactionKernel::minimum_block_height);
 
         let hash_varlen = library.import(Box::new(HashVarlen));
 
@@ -167,8 +169,11 @@ impl BasicSnippet for TransactionKernelMastHash {
             dup 5 push 14               // _ *kernel *list d4 d3 d2 d1 d0 *list 14
             call {set_element}          // _ *kernel *list
 
-            // populate list[15] with default digest
-            push 0 push 0 push 0 push 0 push 0
+            // populate list[15] with minimum block height digest
+            dup 1                       // _ *kernel *list *kernel
+            {&kernel_to_minimum_block_height_with_size}
+                                        // _ *kernel *list *minimum_block_height minimum_block_height_size
+            call {hash_varlen}          // _ *kernel *list d4 d3 d2 d1 d0
             dup 5 push 15               // _ *kernel *list d4 d3 d2 d1 d0 *list 15
             call {set_element}          // _ *kernel *list
 
@@ -369,7 +374,11 @@ impl Function for TransactionKernelMastHash {
         let mutator_set_hash_hash = Hash::hash_varlen(&mutator_set_hash_encoded);
         // address += BFieldElement::one() + BFieldElement::new(mutator_set_hash_size as u64);
 
-        // padding
+        // minimum_block_height
+        let minimum_block_height = kernel.minimum_block_height;
+        let minimum_block_height_encoded = minimum_block_height.encode();
+        let minimum_block_height_hash = Hash::hash_varlen(&minimum_block_height_encoded);
+
         let zero = Digest::default();
 
         // Merkleize
@@ -381,7 +390,7 @@ impl Function for TransactionKernelMastHash {
             coinbase_hash,
             timestamp_hash,
             mutator_set_hash_hash,
-            zero,
+            minimum_block_height_hash,
         ];
         let mut nodes = [[zero; 8], leafs].concat();
         for i in (1..=7).rev() {
@@ -0,0 +1,510 @@
+use crate::models::consensus::mast_hash::MastHash;
+use crate::prelude::{triton_vm, twenty_first};
+
+use itertools::Itertools;
+use tasm_lib::data_type::DataType;
+use tasm_lib::hashing::hash_varlen::HashVarlen;
+use tasm_lib::library::Library;
+use tasm_lib::list::contiguous_list::get_pointer_list::GetPointerList;
+use tasm_lib::list::ListType;
+use tasm_lib::memory::push_ram_to_stack::PushRamToStack;
+use tasm_lib::memory::FIRST_NON_DETERMINISTICALLY_INITIALIZED_MEMORY_ADDRESS;
+use tasm_lib::mmr::bag_peaks::BagPeaks;
+use tasm_lib::structure::tasm_object::TasmObject;
+use tasm_lib::traits::compiled_program::CompiledProgram;
+use tasm_lib::DIGEST_LENGTH;
+use triton_vm::instruction::LabelledInstruction;
+use triton_vm::prelude::{triton_asm, BFieldElement, NonDeterminism, PublicInput};
+use twenty_first::shared_math::{bfield_codec::BFieldCodec, tip5::Digest};
+use twenty_first::util_types::algebraic_hasher::AlgebraicHasher;
+use twenty_first::util_types::mmr::{mmr_accumulator::MmrAccumulator, mmr_trait::Mmr};
+
+use super::hash_removal_record_indices::HashRemovalRecordIndices;
+use super::transaction_kernel_mast_hash::TransactionKernelMastHash;
+use crate::models::blockchain::block::validity::tasm::verify_mmr_append::VerifyMmrAppend;
+use crate::models::blockchain::shared::Hash;
+use crate::models::blockchain::transaction::transaction_kernel::TransactionKernel;
+use crate::models::blockchain::transaction::validity::update::{Update, UpdateWitness};
+
+/// The hash of a mutator set accumulator with the given AOCL, inactive part of the sliding
+/// window Bloom filter, and hash of its active part.
+fn mutator_set_hash(
+    aocl: &MmrAccumulator<Hash>,
+    swbfi: &MmrAccumulator<Hash>,
+    swbfa_hash: Digest,
+) -> Digest {
+    Hash::hash_pair(
+        Hash::hash_pair(aocl.bag_peaks(), swbfi.bag_peaks()),
+        Hash::hash_pair(swbfa_hash, Digest::default()),
+    )
+}
+
+impl CompiledProgram for Update {
+    fn rust_shadow(
+        public_input: &PublicInput,
+        nondeterminism: &NonDeterminism<BFieldElement>,
+    ) -> anyhow::Result<Vec<BFieldElement>> {
+        let hash_of_kernel = *Digest::decode(
+            &public_input
+                .individual_tokens
+                .iter()
+                .copied()
+                .take(DIGEST_LENGTH)
+                .rev()
+                .collect_vec(),
+        )
+        .expect("Could not decode public input in Update");
+
+        // 1. read witness data
+        let witness = *UpdateWitness::decode_from_memory(
+            &nondeterminism.ram,
+            FIRST_NON_DETERMINISTICALLY_INITIALIZED_MEMORY_ADDRESS,
+        )
+        .unwrap();
+        let old = &witness.old_kernel;
+        let new = &witness.new_kernel;
+
+        // 2. assert that the new kernel matches the hash in the public input
+        assert_eq!(hash_of_kernel, new.mast_hash());
+
+        // 3. assert that the AOCL accumulators grow one leaf at a time
+        let aocl_accumulators = &witness.aocl_accumulators;
+        assert_eq!(witness.aocl_leafs.len() + 1, aocl_accumulators.len());
+        for (i, leaf) in witness.aocl_leafs.iter().enumerate() {
+            let mut aocl = aocl_accumulators[i].clone();
+            aocl.append(*leaf);
+            assert_eq!(aocl, aocl_accumulators[i + 1]);
+        }
+
+        // 4. assert that the kernels are about the first and the last of them
+        assert_eq!(
+            old.mutator_set_hash,
+            mutator_set_hash(
+                &aocl_accumulators[0],
+                &witness.old_swbfi,
+                witness.old_swbfa_hash
+            )
+        );
+        assert_eq!(
+            new.mutator_set_hash,
+            mutator_set_hash(
+                &aocl_accumulators[witness.aocl_leafs.len()],
+                &witness.new_swbfi,
+                witness.new_swbfa_hash
+            )
+        );
+
+        // 5. assert that the outputs, public announcements, fee, coinbase and timestamp
+        // are unchanged
+        assert_eq!(old.outputs, new.outputs);
+        assert_eq!(old.public_announcements, new.public_announcements);
+        assert_eq!(old.fee, new.fee);
+        assert_eq!(old.coinbase, new.coinbase);
+        assert_eq!(old.timestamp, new.timestamp);
+
+        // 6. assert that the inputs remove the same items
+        assert_eq!(old.inputs.len(), new.inputs.len());
+        for (old_input, new_input) in old.inputs.iter().zip_eq(new.inputs.iter()) {
+            assert_eq!(old_input.absolute_indices, new_input.absolute_indices);
+        }
+
+        // 7. output the hash of the kernel that was updated
+        Ok(old.mast_hash().values().to_vec())
+    }
+
+    fn code() -> (Vec<LabelledInstruction>, Library) {
+        let mut library = Library::new();
+        let transaction_kernel_mast_hash = library.import(Box::new(TransactionKernelMastHash));
+        let hash_varlen = library.import(Box::new(HashVarlen));
+        let bag_peaks = library.import(Box::new(BagPeaks));
+        let read_digest = library.import(Box::new(PushRamToStack {
+            data_type: DataType::Digest,
+        }));
+        let get_pointer_list = library.import(Box::new(GetPointerList {
+            output_list_type: ListType::Unsafe,
+        }));
+        let verify_mmr_append = library.import(Box::new(VerifyMmrAppend));
+        let hash_removal_record_indices = library.import(Box::new(HashRemovalRecordIndices));
+
+        // field getters
+        type W = UpdateWitness;
+        type MmraH = MmrAccumulator<Hash>;
+        let witness_to_old_kernel = tasm_lib::field!(W::old_kernel);
+        let witness_to_new_kernel = tasm_lib::field!(W::new_kernel);
+        let witness_to_old_swbfi = tasm_lib::field!(W::old_swbfi);
+        let witness_to_old_swbfa_hash = tasm_lib::field!(W::old_swbfa_hash);
+        let witness_to_new_swbfi = tasm_lib::field!(W::new_swbfi);
+        let witness_to_new_swbfa_hash = tasm_lib::field!(W::new_swbfa_hash);
+        let witness_to_aocl_accumulators = tasm_lib::field!(W::aocl_accumulators);
+        let witness_to_aocl_leafs = tasm_lib::field!(W::aocl_leafs);
+        let mmra_to_peaks = tasm_lib::field!(MmraH::peaks);
+        let kernel_to_inputs = tasm_lib::field!(TransactionKernel::inputs);
+        let kernel_to_mutator_set_hash = tasm_lib::field!(TransactionKernel::mutator_set_hash);
+
+        let label = |name: &str| format!("tasm_neptune_transaction_update_{name}");
+        let append_leafs = label("append_leafs");
+        let compare_inputs = label("compare_inputs");
+
+        // Assert that a kernel is about the mutator set with the given inactive and active
+        // parts of the sliding window Bloom filter, and the AOCL accumulator that the
+        // index instructions pick from the list.
+        // BEFORE: _ *witness *[*aocl] num_leafs
+        // AFTER: _ *witness *[*aocl] num_leafs
+        let assert_mutator_set_hash =
+            |witness_to_swbfi: &[LabelledInstruction],
+             witness_to_swbfa_hash: &[LabelledInstruction],
+             aocl_index: Vec<LabelledInstruction>,
+             witness_to_kernel: &[LabelledInstruction]| {
+                triton_asm! {
+                    push 0 push 0 push 0 push 0 push 0
+                    dup 7 {&witness_to_swbfa_hash}
+                    call {read_digest}
+                    hash                            // _ *witness *[*aocl] num_leafs [H(swbfa_hash||0^5)]
+                    dup 7 {&witness_to_swbfi} {&mmra_to_peaks}
+                    call {bag_peaks}                // _ *witness *[*aocl] num_leafs [H(swbfa_hash||0^5)] [swbfi_hash]
+                    dup 11 push 1 add
+                    {&aocl_index}
+                    read_mem 1 pop 1 {&mmra_to_peaks}
+                    call {bag_peaks}                // _ *witness *[*aocl] num_leafs [H(swbfa_hash||0^5)] [swbfi_hash] [aocl_hash]
+                    hash
+                    hash                            // _ *witness *[*aocl] num_leafs [mutator_set_hash]
+                    dup 7 {&witness_to_kernel} {&kernel_to_mutator_set_hash}
+                    call {read_digest}              // _ *witness *[*aocl] num_leafs [mutator_set_hash] [kernel_mutator_set_hash]
+                    assert_vector
+                    pop 5                           // _ *witness *[*aocl] num_leafs
+                }
+            };
+        let assert_old_mutator_set_hash = assert_mutator_set_hash(
+            &witness_to_old_swbfi,
+            &witness_to_old_swbfa_hash,
+            vec![],
+            &witness_to_old_kernel,
+        );
+        let assert_new_mutator_set_hash = assert_mutator_set_hash(
+            &witness_to_new_swbfi,
+            &witness_to_new_swbfa_hash,
+            triton_asm!(dup 11 add),
+            &witness_to_new_kernel,
+        );
+
+        // Assert that a field is the same in both kernels.
+        // BEFORE: _ *witness
+        // AFTER: _ *witness
+        let assert_unchanged = |kernel_to_field_with_size: Vec<LabelledInstruction>| {
+            triton_asm! {
+                dup 0 {&witness_to_old_kernel} {&kernel_to_field_with_size}
+                call {hash_varlen}              // _ *witness [old_field_digest]
+                dup 5 {&witness_to_new_kernel} {&kernel_to_field_with_size}
+                call {hash_varlen}              // _ *witness [old_field_digest] [new_field_digest]
+                assert_vector
+                pop 5                           // _ *witness
+            }
+        };
+        let assert_same_outputs =
+            assert_unchanged(tasm_lib::field_with_size!(TransactionKernel::outputs));
+        let assert_same_public_announcements = assert_unchanged(tasm_lib::field_with_size!(
+            TransactionKernel::public_announcements
+        ));
+        let assert_same_fee = assert_unchanged(tasm_lib::field_with_size!(TransactionKernel::fee));
+        let assert_same_coinbase =
+            assert_unchanged(tasm_lib::field_with_size!(TransactionKernel::coinbase));
+        let assert_same_timestamp =
+            assert_unchanged(tasm_lib::field_with_size!(TransactionKernel::timestamp));
+
+        let code = triton_asm! {
+
+        // 1. Witness was already loaded into memory, just point to it
+        push {FIRST_NON_DETERMINISTICALLY_INITIALIZED_MEMORY_ADDRESS} // _ *witness
+
+        // 2. assert that the new kernel hash == public input
+        dup 0                               // _ *witness *witness
+        {&witness_to_new_kernel}            // _ *witness *new_kernel
+        call {transaction_kernel_mast_hash} // _ *witness [new_kernel_digest]
+        read_io 5                           // _ *witness [new_kernel_digest] [input_kernel_digest]
+        assert_vector                       // _ *witness [new_kernel_digest]
+        pop 5                               // _ *witness
+
+        // 3. assert that there is one AOCL accumulator more than there are leafs, and
+        // that every one follows from the previous one by adding the next leaf
+        dup 0 {&witness_to_aocl_accumulators}
+        call {get_pointer_list}             // _ *witness *[*aocl]
+        dup 1 {&witness_to_aocl_leafs}      // _ *witness *[*aocl] *leafs
+        dup 1 read_mem 1 pop 1              // _ *witness *[*aocl] *leafs num_aocls
+        dup 1 read_mem 1 pop 1              // _ *witness *[*aocl] *leafs num_aocls num_leafs
+        swap 1
+        push -1
+        add
+        dup 1
+        eq
+        assert                              // _ *witness *[*aocl] *leafs num_leafs
+        push 0                              // _ *witness *[*aocl] *leafs num_leafs 0
+        call {append_leafs}                 // _ *witness *[*aocl] *leafs num_leafs num_leafs
+        pop 1
+        swap 1
+        pop 1                               // _ *witness *[*aocl] num_leafs
+
+        // 4. assert that the old kernel is about the mutator set with the first AOCL
+        // accumulator, and the new kernel about the one with the last
+        {&assert_old_mutator_set_hash}      // _ *witness *[*aocl] num_leafs
+        {&assert_new_mutator_set_hash}      // _ *witness *[*aocl] num_leafs
+        pop 2                               // _ *witness
+
+        // 5. assert that the outputs, public announcements, fee, coinbase and timestamp
+        // are unchanged
+        {&assert_same_outputs}
+        {&assert_same_public_announcements}
+        {&assert_same_fee}
+        {&assert_same_coinbase}
+        {&assert_same_timestamp}            // _ *witness
+
+        // 6. assert that the inputs remove the same items
+        dup 0 {&witness_to_old_kernel} {&kernel_to_inputs} call {get_pointer_list}
+        dup 1 {&witness_to_new_kernel} {&kernel_to_inputs} call {get_pointer_list}
+                                            // _ *witness *old_inputs *new_inputs
+        dup 1 read_mem 1 pop 1
+        dup 1 read_mem 1 pop 1
+        dup 1 eq assert                     // _ *witness *old_inputs *new_inputs num_inputs
+        push 0                              // _ *witness *old_inputs *new_inputs num_inputs 0
+        call {compare_inputs}               // _ *witness *old_inputs *new_inputs num_inputs num_inputs
+        pop 4                               // _ *witness
+
+        // 7. output the hash of the kernel that was updated
+        {&witness_to_old_kernel}
+        call {transaction_kernel_mast_hash} // _ [old_kernel_digest]
+        write_io 5                          // _
+
+        halt
+
+        // BEFORE: _ *[*aocl] *leafs num_leafs i
+        // AFTER: _ *[*aocl] *leafs num_leafs num_leafs
+        {append_leafs}:
+            dup 1 dup 1 eq              // _ *[*aocl] *leafs num_leafs i (num_leafs == i)
+            skiz return                 // _ *[*aocl] *leafs num_leafs i
+
+            dup 2
+            push {DIGEST_LENGTH} add
+            dup 1
+            push {DIGEST_LENGTH} mul
+            add
+            read_mem 5
+            pop 1                       // _ *[*aocl] *leafs num_leafs i [leaf]
+
+            dup 8
+            push 1 add
+            dup 6
+            add
+            read_mem 1
+            pop 1                       // _ *[*aocl] *leafs num_leafs i [leaf] *aocl_i
+            dup 9
+            push 2 add
+            dup 7
+            add
+            read_mem 1
+            pop 1                       // _ *[*aocl] *leafs num_leafs i [leaf] *aocl_i *aocl_{i+1}
+            call {verify_mmr_append}    // _ *[*aocl] *leafs num_leafs i
+
+            push 1 add                  // _ *[*aocl] *leafs num_leafs (i + 1)
+            recurse
+
+        // BEFORE: _ *[*old_input] *[*new_input] num_inputs i
+        // AFTER: _ *[*old_input] *[*new_input] num_inputs num_inputs
+        {compare_inputs}:
+            dup 1 dup 1 eq              // _ *[*old_input] *[*new_input] num_inputs i (num_inputs == i)
+            skiz return                 // _ *[*old_input] *[*new_input] num_inputs i
+
+            dup 3 push 1 add
+            dup 1 add
+            read_mem 1 pop 1            // _ *[*old_input] *[*new_input] num_inputs i *old_input
+            call {hash_removal_record_indices}
+                                        // _ *[*old_input] *[*new_input] num_inputs i [old_indices_digest]
+            dup 7 push 1 add
+            dup 6 add
+            read_mem 1 pop 1            // _ *[*old_input] *[*new_input] num_inputs i [old_indices_digest] *new_input
+            call {hash_removal_record_indices}
+                                        // _ *[*old_input] *[*new_input] num_inputs i [old_indices_digest] [new_indices_digest]
+            assert_vector
+            pop 5                       // _ *[*old_input] *[*new_input] num_inputs i
+
+            push 1 add                  // _ *[*old_input] *[*new_input] num_inputs (i + 1)
+            recurse
+        };
+
+        (code, library)
+    }
+
+    fn crash_conditions() -> Vec<String> {
+        vec![
+            "the new kernel does not match the hash in the public input".to_string(),
+            "an AOCL accumulator does not follow from the previous one and the next leaf"
+                .to_string(),
+            "a kernel does not match its mutator set".to_string(),
+            "the outputs, public announcements, fee, coinbase or timestamp changed".to_string(),
+            "the inputs do not remove the same items".to_string(),
+        ]
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::models::blockchain::transaction::neptune_coins::NeptuneCoins;
+    use crate::models::blockchain::transaction::transaction_kernel::pseudorandom_transaction_kernel;
+    use crate::models::consensus::SecretWitness;
+    use crate::util_types::mutator_set::addition_record::AdditionRecord;
+    use crate::util_types::mutator_set::mutator_set_accumulator::MutatorSetAccumulator;
+    use crate::util_types::mutator_set::mutator_set_trait::MutatorSet;
+    use crate::util_types::mutator_set::removal_record::pseudorandom_removal_record;
+    use rand::{random, rngs::StdRng, Rng, SeedableRng};
+    use tasm_lib::traits::compiled_program::test_rust_shadow;
+
+    fn halts(witness: &UpdateWitness) -> bool {
+        Update::program()
+            .run(
+                PublicInput::new(witness.public_input()),
+                witness.nondeterminism(),
+            )
+            .is_ok()
+    }
+
+    fn pseudorandom_witness(seed_byte: u8) -> UpdateWitness {
+        let mut seed = [0u8; 32];
+        seed[0] = 0x7a;
+        seed[1] = seed_byte;
+        let mut rng: StdRng = SeedableRng::from_seed(seed);
+
+        let mut old_mutator_set_accumulator = MutatorSetAccumulator::default();
+        for _ in 0..rng.gen_range(0..10) {
+            old_mutator_set_accumulator.add(&AdditionRecord::new(rng.gen()));
+        }
+        let aocl_leafs: Vec<Digest> = (0..3).map(|_| rng.gen()).collect_vec();
+        let mut new_mutator_set_accumulator = old_mutator_set_accumulator.clone();
+        for leaf in aocl_leafs.iter() {
+            new_mutator_set_accumulator.add(&AdditionRecord::new(*leaf));
+        }
+
+        let mut old_kernel = pseudorandom_transaction_kernel(rng.gen(), 2, 2, 1);
+        old_kernel.mutator_set_hash = old_mutator_set_accumulator.hash();
+
+        // only the target chunks of the removal records change
+        let mut new_kernel = old_kernel.clone();
+        new_kernel.mutator_set_hash = new_mutator_set_accumulator.hash();
+        new_kernel.inputs[0].target_chunks = pseudorandom_removal_record(rng.gen()).target_chunks;
+
+        UpdateWitness::new(
+            &old_kernel,
+            &new_kernel,
+            &old_mutator_set_accumulator,
+            &new_mutator_set_accumulator,
+            aocl_leafs,
+        )
+    }
+
+    #[test]
+    fn test_graceful_halt() {
+        let witness = pseudorandom_witness(0x01);
+        let output = Update::program()
+            .run(
+                PublicInput::new(witness.public_input()),
+                witness.nondeterminism(),
+            )
+            .unwrap();
+        assert_eq!(witness.output(), output);
+    }
+
+    #[test]
+    fn program_is_deterministic() {
+        let program = Update::program();
+        let other_program = Update::program();
+        assert_eq!(program, other_program);
+    }
+
+    #[test]
+    fn tasm_matches_rust() {
+        let witness = pseudorandom_witness(0x02);
+        test_rust_shadow::<Update>(
+            &PublicInput::new(witness.public_input()),
+            &witness.nondeterminism(),
+        );
+    }
+
+    #[test]
+    fn crashes_on_wrong_aocl() {
+        let witness = pseudorandom_witness(0x03);
+        assert!(halts(&witness));
+
+        // an AOCL accumulator skipped
+        let mut skipped = witness.clone();
+        skipped.aocl_accumulators[1] = skipped.aocl_accumulators[0].clone();
+        assert!(!halts(&skipped));
+
+        // an AOCL accumulator missing
+        let mut missing = witness.clone();
+        missing.aocl_accumulators.pop();
+        assert!(!halts(&missing));
+
+        // a leaf that was not added
+        let mut wrong_leaf = witness.clone();
+        wrong_leaf.aocl_leafs[0] = random();
+        assert!(!halts(&wrong_leaf));
+    }
+
+    #[test]
+    fn crashes_on_wrong_mutator_set() {
+        let witness = pseudorandom_witness(0x04);
+        assert!(halts(&witness));
+
+        let mut wrong_old_mutator_set = witness.clone();
+        wrong_old_mutator_set.old_kernel.mutator_set_hash = random();
+        assert!(!halts(&wrong_old_mutator_set));
+
+        let mut wrong_new_mutator_set = witness.clone();
+        wrong_new_mutator_set.new_kernel.mutator_set_hash = random();
+        assert!(!halts(&wrong_new_mutator_set));
+
+        let mut wrong_active_window = witness.clone();
+        wrong_active_window.new_swbfa_hash = random();
+        assert!(!halts(&wrong_active_window));
+    }
+
+    #[test]
+    fn crashes_on_changed_kernel() {
+        let witness = pseudorandom_witness(0x05);
+        assert!(halts(&witness));
+
+        let mut other_item = witness.clone();
+        other_item.new_kernel.inputs[1] = pseudorandom_removal_record(random());
+        assert!(!halts(&other_item));
+
+        let mut extra_input = witness.clone();
+        extra_input
+            .new_kernel
+            .inputs
+            .push(pseudorandom_removal_record(random()));
+        assert!(!halts(&extra_input));
+
+        let mut other_output = witness.clone();
+        other_output.new_kernel.outputs[0] = AdditionRecord::new(random());
+        assert!(!halts(&other_output));
+
+        let mut other_public_announcements = witness.clone();
+        other_public_announcements
+            .new_kernel
+            .public_announcements
+            .clear();
+        assert!(!halts(&other_public_announcements));
+
+        let mut other_fee = witness.clone();
+        other_fee.old_kernel.fee = NeptuneCoins::new(1);
+        other_fee.new_kernel.fee = NeptuneCoins::new(2);
+        assert!(!halts(&other_fee));
+
+        let mut other_coinbase = witness.clone();
+        other_coinbase.new_kernel.coinbase = None;
+        other_coinbase.old_kernel.coinbase = Some(NeptuneCoins::new(1));
+        assert!(!halts(&other_coinbase));
+
+        let mut other_timestamp = witness.clone();
+        other_timestamp.new_kernel.timestamp += BFieldElement::new(1);
+        assert!(!halts(&other_timestamp));
+    }
+}
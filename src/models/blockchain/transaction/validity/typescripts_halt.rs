@@ -3,12 +3,28 @@ use crate::{
     prelude::{triton_vm, twenty_first},
 };
 
+use anyhow::Result;
+use field_count::FieldCount;
 use get_size::GetSize;
 use itertools::Itertools;
 use serde::{Deserialize, Serialize};
-use triton_vm::prelude::{BFieldElement, Claim, NonDeterminism, Program};
+use std::collections::HashMap;
+use tasm_lib::data_type::DataType;
+use tasm_lib::hashing::hash_varlen::HashVarlen;
+use tasm_lib::library::Library;
+use tasm_lib::memory::push_ram_to_stack::PushRamToStack;
+use tasm_lib::memory::{encode_to_memory, FIRST_NON_DETERMINISTICALLY_INITIALIZED_MEMORY_ADDRESS};
+use tasm_lib::structure::tasm_object::TasmObject;
+use tracing::warn;
+use triton_vm::instruction::LabelledInstruction;
+use triton_vm::prelude::{
+    triton_asm, BFieldElement, Claim, NonDeterminism, Program, PublicInput, StarkParameters,
+};
 use twenty_first::shared_math::bfield_codec::BFieldCodec;
+use twenty_first::shared_math::tip5::Digest;
+use twenty_first::util_types::emojihash_trait::Emojihash;
 
+use super::salted_utxos_hash;
 use crate::models::{
     blockchain::transaction::{
         transaction_kernel::TransactionKernel,
@@ -18,16 +34,140 @@ use crate::models::{
     consensus::{ClaimSupport, SecretWitness, SupportedClaim, ValidationLogic},
 };
 
+/// The UTXOs of a transaction as its type scripts see them. A type script gets the kernel
+/// MAST hash and the salted hashes of the input and of the output UTXOs as public input,
+/// the same digests that the claim listing the type scripts is about, followed by its own
+/// hash, by which it recognizes its coins. To learn the UTXOs, it finds this struct
+/// encoded in memory, at the first nondeterministically initialized address, and must
+/// check the UTXOs against the salted hashes.
+#[derive(
+    Clone,
+    Debug,
+    Serialize,
+    Deserialize,
+    PartialEq,
+    Eq,
+    GetSize,
+    BFieldCodec,
+    FieldCount,
+    TasmObject,
+)]
+pub struct TypeScriptUtxos {
+    pub input_utxos: Vec<Utxo>,
+    pub input_utxos_salt: Digest,
+    pub output_utxos: Vec<Utxo>,
+    pub output_utxos_salt: Digest,
+}
+
+impl TypeScriptUtxos {
+    pub fn new(primitive_witness: &TransactionPrimitiveWitness) -> Self {
+        Self {
+            input_utxos: primitive_witness.input_utxos.clone(),
+            input_utxos_salt: primitive_witness.input_utxos_salt(),
+            output_utxos: primitive_witness.output_utxos.clone(),
+            output_utxos_salt: primitive_witness.output_utxos_salt(),
+        }
+    }
+
+    /// The public input of the type script with the given hash about the kernel with the
+    /// given MAST hash: the kernel MAST hash, followed by the salted hashes of the input
+    /// and of the output UTXOs, followed by the hash of the type script, all reversed.
+    pub fn public_input(
+        &self,
+        kernel_hash: Digest,
+        type_script_hash: Digest,
+    ) -> Vec<BFieldElement> {
+        let salted_input_utxos_hash = salted_utxos_hash(&self.input_utxos, self.input_utxos_salt);
+        let salted_output_utxos_hash =
+            salted_utxos_hash(&self.output_utxos, self.output_utxos_salt);
+        [
+            kernel_hash.reversed().values(),
+            salted_input_utxos_hash.reversed().values(),
+            salted_output_utxos_hash.reversed().values(),
+            type_script_hash.reversed().values(),
+        ]
+        .concat()
+    }
+
+    /// The nondeterminism of a type script: the UTXOs, encoded in memory.
+    pub fn nondeterminism(&self) -> NonDeterminism<BFieldElement> {
+        let mut memory = HashMap::default();
+        encode_to_memory(
+            &mut memory,
+            FIRST_NON_DETERMINISTICALLY_INITIALIZED_MEMORY_ADDRESS,
+            self.clone(),
+        );
+        NonDeterminism::default().with_ram(memory)
+    }
+}
+
+/// The code a type script starts with to learn the UTXOs of the transaction. It reads the
+/// public input up to the type script's own hash and asserts that the [`TypeScriptUtxos`]
+/// in memory match the salted hashes in it, so a type script that uses this code cannot be
+/// fed UTXOs other than those of the transaction it is about.
+///
+/// BEFORE: _
+/// AFTER: _ [kernel_mast_hash] *type_script_utxos
+pub fn read_type_script_utxos(library: &mut Library) -> Vec<LabelledInstruction> {
+    let hash_varlen = library.import(Box::new(HashVarlen));
+    let read_digest = library.import(Box::new(PushRamToStack {
+        data_type: DataType::Digest,
+    }));
+
+    let utxos_to_input_utxos_with_size = tasm_lib::field_with_size!(TypeScriptUtxos::input_utxos);
+    let utxos_to_input_utxos_salt = tasm_lib::field!(TypeScriptUtxos::input_utxos_salt);
+    let utxos_to_output_utxos_with_size = tasm_lib::field_with_size!(TypeScriptUtxos::output_utxos);
+    let utxos_to_output_utxos_salt = tasm_lib::field!(TypeScriptUtxos::output_utxos_salt);
+
+    triton_asm! {
+        read_io 5                           // _ [kernel_mast_hash]
+        push {FIRST_NON_DETERMINISTICALLY_INITIALIZED_MEMORY_ADDRESS}
+                                            // _ [kernel_mast_hash] *utxos
+
+        dup 0                               // _ [kernel_mast_hash] *utxos *utxos
+        {&utxos_to_input_utxos_salt}        // _ [kernel_mast_hash] *utxos *salt
+        call {read_digest}                  // _ [kernel_mast_hash] *utxos [salt]
+        dup 5                               // _ [kernel_mast_hash] *utxos [salt] *utxos
+        {&utxos_to_input_utxos_with_size}   // _ [kernel_mast_hash] *utxos [salt] *input_utxos size
+        call {hash_varlen}                  // _ [kernel_mast_hash] *utxos [salt] [input_utxos_hash]
+        hash                                // _ [kernel_mast_hash] *utxos [salted_input_utxos_hash]
+        read_io 5                           // _ [kernel_mast_hash] *utxos [salted_input_utxos_hash] [public_salted_input_utxos_hash]
+        assert_vector
+        pop 5                               // _ [kernel_mast_hash] *utxos
+
+        dup 0                               // _ [kernel_mast_hash] *utxos *utxos
+        {&utxos_to_output_utxos_salt}       // _ [kernel_mast_hash] *utxos *salt
+        call {read_digest}                  // _ [kernel_mast_hash] *utxos [salt]
+        dup 5                               // _ [kernel_mast_hash] *utxos [salt] *utxos
+        {&utxos_to_output_utxos_with_size}  // _ [kernel_mast_hash] *utxos [salt] *output_utxos size
+        call {hash_varlen}                  // _ [kernel_mast_hash] *utxos [salt] [output_utxos_hash]
+        hash                                // _ [kernel_mast_hash] *utxos [salted_output_utxos_hash]
+        read_io 5                           // _ [kernel_mast_hash] *utxos [salted_output_utxos_hash] [public_salted_output_utxos_hash]
+        assert_vector
+        pop 5                               // _ [kernel_mast_hash] *utxos
+    }
+}
+
 #[derive(Clone, Debug, Serialize, Deserialize, PartialEq, Eq, GetSize, BFieldCodec)]
 pub struct TypeScriptHaltsWitness {
     type_script: TypeScript,
-    input_utxos: Vec<Utxo>,
-    output_utxos: Vec<Utxo>,
+    utxos: TypeScriptUtxos,
+}
+
+impl TypeScriptHaltsWitness {
+    /// The hashes of the type scripts of all coins in the transaction.
+    fn coin_type_script_hashes(&self) -> impl Iterator<Item = Digest> + '_ {
+        self.utxos
+            .input_utxos
+            .iter()
+            .chain(self.utxos.output_utxos.iter())
+            .flat_map(|utxo| utxo.coins.iter().map(|coin| coin.type_script_hash))
+    }
 }
 
 impl SecretWitness for TypeScriptHaltsWitness {
     fn nondeterminism(&self) -> NonDeterminism<BFieldElement> {
-        NonDeterminism::default()
+        self.utxos.nondeterminism()
     }
 
     fn subprogram(&self) -> Program {
@@ -41,11 +181,23 @@ pub struct TypeScriptsHalt {
 }
 
 impl TypeScriptsHalt {
-    // TODO: Remove after implementing this struct
-    pub fn dummy() -> Self {
-        Self {
-            supported_claims: vec![SupportedClaim::dummy()],
+    /// Replace the secret witness of every type script claim by a proof, such that the
+    /// claims can be verified without the UTXOs of the transaction.
+    pub fn prove_claims(&mut self) -> Result<()> {
+        for supported_claim in self.supported_claims.iter_mut() {
+            let ClaimSupport::SecretWitness(witness) = &supported_claim.support else {
+                continue;
+            };
+            let proof = triton_vm::prove(
+                StarkParameters::default(),
+                &supported_claim.claim,
+                &witness.subprogram(),
+                witness.nondeterminism(),
+            )?;
+            supported_claim.support = ClaimSupport::Proof(proof);
         }
+
+        Ok(())
     }
 }
 
@@ -58,44 +210,97 @@ impl ValidationLogic<TypeScriptHaltsWitness> for TypeScriptsHalt {
         primitive_witness: &TransactionPrimitiveWitness,
         tx_kernel: &TransactionKernel,
     ) -> Self {
-        let claim = Claim {
-            input: tx_kernel.mast_hash().values().to_vec(),
-            output: vec![],
-            program_digest: TypeScript::native_coin().hash(),
-        };
-        let witness = TypeScriptHaltsWitness {
-            type_script: TypeScript::native_coin(),
-            input_utxos: primitive_witness.input_utxos.clone(),
-            output_utxos: primitive_witness.output_utxos.clone(),
-        };
-        let amount_logic: SupportedClaim<TypeScriptHaltsWitness> = SupportedClaim {
-            claim,
-            support: ClaimSupport::SecretWitness(witness),
-        };
-        Self {
-            supported_claims: vec![amount_logic],
-        }
+        let utxos = TypeScriptUtxos::new(primitive_witness);
+        let tx_kernel_mast_hash = tx_kernel.mast_hash();
+        let supported_claims = primitive_witness
+            .type_scripts
+            .iter()
+            .unique_by(|type_script| type_script.hash())
+            .map(|type_script| SupportedClaim {
+                claim: Claim {
+                    input: utxos.public_input(tx_kernel_mast_hash, type_script.hash()),
+                    output: vec![],
+                    program_digest: type_script.hash(),
+                },
+                support: ClaimSupport::SecretWitness(TypeScriptHaltsWitness {
+                    type_script: type_script.to_owned(),
+                    utxos: utxos.clone(),
+                }),
+            })
+            .collect();
+
+        Self { supported_claims }
     }
 
+    /// There is no single program; every type script is proven and verified on its own.
     fn subprogram(&self) -> Program {
-        todo!()
+        unreachable!("type script claims are proven one by one, each with its own program")
     }
 
+    /// The secret witnesses of the type script claims that are not proven yet. Proven
+    /// claims need no further support.
     fn support(&self) -> ClaimSupport<TypeScriptHaltsWitness> {
         ClaimSupport::MultipleSupports(
             self.supported_claims
-                .clone()
-                .into_iter()
-                .map(|sc| match sc.support {
-                    ClaimSupport::Proof(_) => todo!(),
-                    ClaimSupport::MultipleSupports(_) => todo!(),
-                    ClaimSupport::SecretWitness(sw) => sw.to_owned(),
-                    ClaimSupport::DummySupport => todo!(),
+                .iter()
+                .filter_map(|sc| match &sc.support {
+                    ClaimSupport::Proof(_) => None,
+                    ClaimSupport::SecretWitness(sw) => Some(sw.to_owned()),
+                    ClaimSupport::MultipleSupports(_) | ClaimSupport::DummySupport => {
+                        unreachable!(
+                            "type script claims are supported by a proof or a secret witness"
+                        )
+                    }
                 })
                 .collect(),
         )
     }
 
+    /// Prove every type script claim on its own, as each type script is a program of its
+    /// own.
+    fn prove(&mut self) -> Result<()> {
+        self.prove_claims()
+    }
+
+    /// Verify every type script claim on its own. Where the coins of the transaction are
+    /// known, also check that every type script they name has a claim.
+    fn verify(&self) -> bool {
+        let claimed_type_scripts = self
+            .supported_claims
+            .iter()
+            .map(|supported_claim| supported_claim.claim.program_digest)
+            .collect_vec();
+
+        self.supported_claims
+            .iter()
+            .all(|supported_claim| match &supported_claim.support {
+                ClaimSupport::Proof(proof) => {
+                    triton_vm::verify(StarkParameters::default(), &supported_claim.claim, proof)
+                }
+                ClaimSupport::SecretWitness(witness) => {
+                    if let Some(type_script_hash) = witness
+                        .coin_type_script_hashes()
+                        .find(|hash| !claimed_type_scripts.contains(hash))
+                    {
+                        warn!(
+                            "Missing claim for type script {}",
+                            type_script_hash.emojihash()
+                        );
+                        return false;
+                    }
+                    let vm_result = witness.subprogram().run(
+                        PublicInput::new(supported_claim.claim.input.clone()),
+                        witness.nondeterminism(),
+                    );
+                    if let Err(err) = &vm_result {
+                        warn!("Type script failed to halt gracefully: {err}");
+                    }
+                    vm_result.is_ok()
+                }
+                ClaimSupport::MultipleSupports(_) | ClaimSupport::DummySupport => false,
+            })
+    }
+
     fn claim(&self) -> Claim {
         let input = self
             .supported_claims
@@ -112,3 +317,110 @@ impl ValidationLogic<TypeScriptHaltsWitness> for TypeScriptsHalt {
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use rand::random;
+
+    use super::*;
+    use crate::models::blockchain::transaction::utxo::Coin;
+
+    fn type_script(code: Vec<LabelledInstruction>, library: Library) -> TypeScript {
+        [code, library.all_imports()].concat().into()
+    }
+
+    /// A type script that only checks that it is given the UTXOs of the transaction.
+    fn utxo_checking_type_script() -> TypeScript {
+        let mut library = Library::new();
+        let read_utxos = read_type_script_utxos(&mut library);
+        let code = triton_asm! {
+            {&read_utxos}
+            pop 5
+            pop 1
+            halt
+        };
+        type_script(code, library)
+    }
+
+    /// UTXOs that each hold a coin of the given type script.
+    fn pseudorandom_utxos(type_script_hash: Digest) -> TypeScriptUtxos {
+        let utxo = |_| Utxo {
+            lock_script_hash: random(),
+            coins: vec![Coin {
+                type_script_hash,
+                state: vec![random()],
+            }],
+        };
+        TypeScriptUtxos {
+            input_utxos: (0..2).map(utxo).collect_vec(),
+            input_utxos_salt: random(),
+            output_utxos: (0..2).map(utxo).collect_vec(),
+            output_utxos_salt: random(),
+        }
+    }
+
+    fn type_scripts_halt(
+        type_script: &TypeScript,
+        utxos: &TypeScriptUtxos,
+        input: Vec<BFieldElement>,
+    ) -> TypeScriptsHalt {
+        TypeScriptsHalt {
+            supported_claims: vec![SupportedClaim {
+                claim: Claim {
+                    input,
+                    output: vec![],
+                    program_digest: type_script.hash(),
+                },
+                support: ClaimSupport::SecretWitness(TypeScriptHaltsWitness {
+                    type_script: type_script.to_owned(),
+                    utxos: utxos.to_owned(),
+                }),
+            }],
+        }
+    }
+
+    #[test]
+    fn type_script_that_crashes_fails_verification() {
+        let halting: TypeScript = triton_asm!(halt).into();
+        let utxos = pseudorandom_utxos(halting.hash());
+        let input = utxos.public_input(random(), halting.hash());
+        assert!(type_scripts_halt(&halting, &utxos, input).verify());
+
+        let crashing: TypeScript = triton_asm!(push 0 assert halt).into();
+        let utxos = pseudorandom_utxos(crashing.hash());
+        let input = utxos.public_input(random(), crashing.hash());
+        assert!(!type_scripts_halt(&crashing, &utxos, input).verify());
+    }
+
+    #[test]
+    fn coin_without_type_script_claim_fails_verification() {
+        let halting: TypeScript = triton_asm!(halt).into();
+        let mut utxos = pseudorandom_utxos(halting.hash());
+        utxos.output_utxos[0].coins.push(Coin {
+            type_script_hash: random(),
+            state: vec![],
+        });
+        let input = utxos.public_input(random(), halting.hash());
+        assert!(!type_scripts_halt(&halting, &utxos, input).verify());
+    }
+
+    #[test]
+    fn type_script_fails_on_utxos_of_another_transaction() {
+        let type_script = utxo_checking_type_script();
+        let utxos = pseudorandom_utxos(type_script.hash());
+        let input = utxos.public_input(random(), type_script.hash());
+        assert!(type_scripts_halt(&type_script, &utxos, input.clone()).verify());
+
+        let mut other_inputs = utxos.clone();
+        other_inputs.input_utxos.pop();
+        assert!(!type_scripts_halt(&type_script, &other_inputs, input.clone()).verify());
+
+        let mut other_outputs = utxos.clone();
+        other_outputs.output_utxos[1] = other_outputs.input_utxos[0].clone();
+        assert!(!type_scripts_halt(&type_script, &other_outputs, input.clone()).verify());
+
+        let mut other_salt = utxos;
+        other_salt.output_utxos_salt = random();
+        assert!(!type_scripts_halt(&type_script, &other_salt, input).verify());
+    }
+}
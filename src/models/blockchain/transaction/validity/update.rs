@@ -0,0 +1,227 @@
+use crate::models::consensus::mast_hash::MastHash;
+use crate::prelude::{triton_vm, twenty_first};
+
+use field_count::FieldCount;
+use get_size::GetSize;
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use tasm_lib::memory::{encode_to_memory, FIRST_NON_DETERMINISTICALLY_INITIALIZED_MEMORY_ADDRESS};
+use tasm_lib::structure::tasm_object::TasmObject;
+use tasm_lib::traits::compiled_program::CompiledProgram;
+use tracing::warn;
+use triton_vm::prelude::{BFieldElement, Claim, NonDeterminism, Program, Proof};
+use twenty_first::shared_math::bfield_codec::BFieldCodec;
+use twenty_first::shared_math::tip5::Digest;
+use twenty_first::util_types::algebraic_hasher::AlgebraicHasher;
+use twenty_first::util_types::mmr::mmr_accumulator::MmrAccumulator;
+use twenty_first::util_types::mmr::mmr_trait::Mmr;
+
+use crate::models::blockchain::shared::Hash;
+use crate::models::blockchain::transaction::transaction_kernel::TransactionKernel;
+use crate::models::blockchain::transaction::Transaction;
+use crate::models::consensus::{ClaimSupport, SupportedClaim};
+use crate::models::consensus::{SecretWitness, ValidationLogic};
+use crate::util_types::mutator_set::mutator_set_accumulator::MutatorSetAccumulator;
+
+/// The data that shows that a transaction kernel is another one updated to a later
+/// mutator set: both mutator sets are given, the AOCL of the later one follows from that
+/// of the earlier one by adding leafs, and the kernels differ only in the removal
+/// records of their inputs, whose absolute indices are the same, and in the mutator set
+/// hash.
+#[derive(
+    Clone,
+    Debug,
+    Serialize,
+    Deserialize,
+    PartialEq,
+    Eq,
+    GetSize,
+    BFieldCodec,
+    FieldCount,
+    TasmObject,
+)]
+pub struct UpdateWitness {
+    pub old_kernel: TransactionKernel,
+    pub new_kernel: TransactionKernel,
+    pub old_swbfi: MmrAccumulator<Hash>,
+    pub old_swbfa_hash: Digest,
+    pub new_swbfi: MmrAccumulator<Hash>,
+    pub new_swbfa_hash: Digest,
+
+    // The AOCL accumulators from that of the old mutator set to that of the new one,
+    // adding one leaf at a time.
+    pub aocl_accumulators: Vec<MmrAccumulator<Hash>>,
+    pub aocl_leafs: Vec<Digest>,
+}
+
+impl UpdateWitness {
+    /// Collect the data that shows that the new kernel is the old one updated from the
+    /// old mutator set to the new one, which adds the given AOCL leafs.
+    pub fn new(
+        old_kernel: &TransactionKernel,
+        new_kernel: &TransactionKernel,
+        old_mutator_set_accumulator: &MutatorSetAccumulator,
+        new_mutator_set_accumulator: &MutatorSetAccumulator,
+        aocl_leafs: Vec<Digest>,
+    ) -> Self {
+        let mut aocl = old_mutator_set_accumulator.kernel.aocl.clone();
+        let mut aocl_accumulators = vec![aocl.clone()];
+        for leaf in aocl_leafs.iter() {
+            aocl.append(*leaf);
+            aocl_accumulators.push(aocl.clone());
+        }
+
+        Self {
+            old_kernel: old_kernel.clone(),
+            new_kernel: new_kernel.clone(),
+            old_swbfi: old_mutator_set_accumulator.kernel.swbf_inactive.clone(),
+            old_swbfa_hash: Hash::hash(&old_mutator_set_accumulator.kernel.swbf_active),
+            new_swbfi: new_mutator_set_accumulator.kernel.swbf_inactive.clone(),
+            new_swbfa_hash: Hash::hash(&new_mutator_set_accumulator.kernel.swbf_active),
+            aocl_accumulators,
+            aocl_leafs,
+        }
+    }
+
+    /// Extend this update by the next one, which updates the new kernel of this one to a
+    /// later mutator set. The result updates the old kernel of this one to that mutator
+    /// set in one step.
+    pub fn extend(&self, next: &UpdateWitness) -> Self {
+        Self {
+            old_kernel: self.old_kernel.clone(),
+            new_kernel: next.new_kernel.clone(),
+            old_swbfi: self.old_swbfi.clone(),
+            old_swbfa_hash: self.old_swbfa_hash,
+            new_swbfi: next.new_swbfi.clone(),
+            new_swbfa_hash: next.new_swbfa_hash,
+            aocl_accumulators: self
+                .aocl_accumulators
+                .iter()
+                .chain(next.aocl_accumulators.iter().skip(1))
+                .cloned()
+                .collect(),
+            aocl_leafs: [self.aocl_leafs.clone(), next.aocl_leafs.clone()].concat(),
+        }
+    }
+
+    pub fn public_input(&self) -> Vec<BFieldElement> {
+        self.new_kernel.mast_hash().reversed().values().to_vec()
+    }
+
+    /// The hash of the kernel that was updated, which is what the update program
+    /// outputs.
+    pub fn output(&self) -> Vec<BFieldElement> {
+        self.old_kernel.mast_hash().values().to_vec()
+    }
+}
+
+impl SecretWitness for UpdateWitness {
+    fn nondeterminism(&self) -> NonDeterminism<BFieldElement> {
+        let mut memory = HashMap::default();
+        encode_to_memory(
+            &mut memory,
+            FIRST_NON_DETERMINISTICALLY_INITIALIZED_MEMORY_ADDRESS,
+            self.clone(),
+        );
+        NonDeterminism::default().with_ram(memory)
+    }
+
+    fn subprogram(&self) -> Program {
+        Update::program()
+    }
+}
+
+/// The claim that a transaction is another one updated to a later mutator set, together
+/// with that transaction. As with [`Merge`](super::merge::Merge), the update program
+/// cannot verify the witness of the transaction that was updated, so the update carries
+/// it, and it is verified against its own kernel, whose hash the update claim outputs.
+/// Without recursion, the update cannot replace that witness by a proof of its own. So
+/// an updated transaction that is updated again gets a new update claim for the whole
+/// range of mutator sets, about the same original transaction, rather than an update
+/// of the update.
+#[derive(Clone, Debug, Serialize, Deserialize, PartialEq, Eq, GetSize, BFieldCodec)]
+pub struct Update {
+    pub supported_claim: SupportedClaim<UpdateWitness>,
+
+    // the transaction that was updated, as the sole element
+    pub operands: Vec<Transaction>,
+}
+
+impl ValidationLogic<UpdateWitness> for Update {
+    type PrimitiveWitness = UpdateWitness;
+
+    type Kernel = TransactionKernel;
+
+    fn new_from_primitive_witness(
+        update_witness: &UpdateWitness,
+        _tx_kernel: &TransactionKernel,
+    ) -> Self {
+        let claim = Claim {
+            input: update_witness.public_input(),
+            output: update_witness.output(),
+            program_digest: Hash::hash_varlen(&Self::program().encode()),
+        };
+        let supported_claim = SupportedClaim {
+            claim,
+            support: ClaimSupport::SecretWitness(update_witness.clone()),
+        };
+        Self {
+            supported_claim,
+            operands: vec![],
+        }
+    }
+
+    fn upgrade(&mut self, proof: Proof) {
+        self.supported_claim.support = ClaimSupport::Proof(proof);
+    }
+
+    fn subprogram(&self) -> Program {
+        Self::program()
+    }
+
+    fn support(&self) -> ClaimSupport<UpdateWitness> {
+        self.supported_claim.support.clone()
+    }
+
+    fn claim(&self) -> Claim {
+        self.supported_claim.claim.clone()
+    }
+}
+
+impl Update {
+    /// The update of the given transaction, whose kernel the update witness updates.
+    pub fn new(update_witness: &UpdateWitness, original: Transaction) -> Self {
+        Self {
+            operands: vec![original],
+            ..Self::new_from_primitive_witness(update_witness, &update_witness.new_kernel)
+        }
+    }
+
+    /// Verify that the update claim is about the given kernel and the kernel of the
+    /// transaction that was updated, that it holds, and that this transaction is valid.
+    pub fn verify_update(&self, kernel: &TransactionKernel) -> bool {
+        let claim = self.claim();
+        if claim.program_digest != Hash::hash_varlen(&Self::program().encode()) {
+            warn!("Update claim is about the wrong program");
+            return false;
+        }
+        if claim.input != kernel.mast_hash().reversed().values().to_vec() {
+            warn!("Update claim is not about the transaction kernel");
+            return false;
+        }
+        let [original] = &self.operands[..] else {
+            warn!("Update must carry the transaction that was updated");
+            return false;
+        };
+        if claim.output != original.kernel.mast_hash().values().to_vec() {
+            warn!("Update claim is not about the transaction that was updated");
+            return false;
+        }
+        if !original.is_valid() {
+            warn!("Update of an invalid transaction");
+            return false;
+        }
+
+        self.verify()
+    }
+}
@@ -6,6 +6,7 @@ use twenty_first::amount::u32s::U32s;
 use twenty_first::shared_math::digest::Digest;
 
 use super::blockchain::block::block_header::PROOF_OF_WORK_COUNT_U32_SIZE;
+use super::blockchain::block::compact_block::BlockComposition;
 use super::blockchain::block::{block_height::BlockHeight, Block};
 use super::blockchain::transaction::Transaction;
 use super::peer::TransactionNotification;
@@ -31,6 +32,7 @@ pub enum MainToMiner {
 pub struct NewBlockFound {
     pub block: Box<Block>,
     pub coinbase_utxo_info: Box<ExpectedUtxo>,
+    pub composition: Box<BlockComposition>,
 }
 
 #[derive(Clone, Debug)]
@@ -1,4 +1,4 @@
-use anyhow::Result;
+use anyhow::{bail, Result};
 use get_size::GetSize;
 use serde::Deserialize;
 use serde::Serialize;
@@ -21,7 +21,7 @@ pub mod mast_hash;
 /// A Witness is any data that supports the truth of a claim, typically related to the
 /// validity of a block or transaction.
 #[derive(Clone, Debug, Serialize, Deserialize, PartialEq, Eq, GetSize, BFieldCodec)]
-pub enum Witness<PrimitiveWitness, ValidationLogic> {
+pub enum Witness<PrimitiveWitness, ValidationLogic, Derivation> {
     /// All the first-order witness data that supports the validity claim, including
     /// sensitive secrets.
     Primitive(PrimitiveWitness),
@@ -30,6 +30,11 @@ pub enum Witness<PrimitiveWitness, ValidationLogic> {
     ValidationLogic(ValidationLogic),
     /// A single proof for the entire claim, typically produced via recursion.
     SingleProof(SingleProof),
+    /// A claim that the object was derived from others, which are carried along with it
+    /// and for which the validity claim holds in turn. This stands in for single proofs
+    /// until recursion is available: it is not succinct, as it grows with every object
+    /// that the object was derived from, and it hides nothing that their witnesses expose.
+    Derivation(Derivation),
     /// As we do not have recursion yet, sometimes we just need to take things on faith.
     /// This must be depracated before mainnet launch!
     Faith,
@@ -135,20 +140,19 @@ pub trait ValidationLogic<T: SecretWitness> {
             }
             ClaimSupport::SecretWitness(witness) => {
                 // Run program before proving
-                self.subprogram()
-                    .run(
-                        self.claim().public_input().into(),
-                        witness.nondeterminism().clone(),
-                    )
-                    .expect("Program execution prior to proving must succeed");
+                if let Err(err) = self.subprogram().run(
+                    self.claim().public_input().into(),
+                    witness.nondeterminism().clone(),
+                ) {
+                    bail!("Program execution prior to proving failed: {err}");
+                }
 
                 let proof = triton_vm::prove(
                     StarkParameters::default(),
                     &self.claim(),
                     &self.subprogram(),
                     witness.nondeterminism().clone(),
-                )
-                .expect("Proving integrity of removal records must succeed.");
+                )?;
                 self.upgrade(proof);
                 Ok(())
             }
@@ -1,13 +1,14 @@
 use crate::prelude::twenty_first;
 
 use serde::{Deserialize, Serialize};
-use std::{fmt, net::IpAddr};
+use std::fmt;
+use std::net::{IpAddr, SocketAddr};
 use twenty_first::shared_math::b_field_element::BFieldElement;
 use twenty_first::shared_math::digest::Digest;
 
 use super::blockchain::block::block_header::BlockHeader;
 use super::blockchain::block::block_height::BlockHeight;
-use super::peer::PeerStanding;
+use super::peer::{PeerAddressBookEntry, PeerStanding};
 use crate::database::NeptuneLevelDb;
 
 pub const DATABASE_DIRECTORY_ROOT_NAME: &str = "databases";
@@ -85,6 +86,7 @@ pub enum BlockIndexKey {
     Height(BlockHeight), // Maps from block height to list of blocks
     LastFile,            // points to last file used
     BlockTipDigest,      // points to block digest of most canonical block known
+    Transaction(Digest), // maps from transaction ID to list of blocks containing it
 }
 
 #[derive(Clone, Debug, Serialize, Deserialize)]
@@ -94,6 +96,7 @@ pub enum BlockIndexValue {
     Height(Vec<Digest>),
     LastFile(LastFileRecord),
     BlockTipDigest(Digest),
+    Transaction(Vec<Digest>),
 }
 
 impl BlockIndexValue {
@@ -131,11 +134,21 @@ impl BlockIndexValue {
             _ => panic!("Requested BlockTipDigest, found {:?}", self),
         }
     }
+
+    pub fn as_transaction_record(&self) -> Vec<Digest> {
+        match self {
+            BlockIndexValue::Transaction(rec) => rec.to_owned(),
+            _ => panic!("Requested Transaction, found {:?}", self),
+        }
+    }
 }
 
 #[derive(Clone)]
 pub struct PeerDatabases {
     pub peer_standings: NeptuneLevelDb<IpAddr, PeerStanding>,
+
+    // Every address that a peer has been seen listening on
+    pub peer_address_book: NeptuneLevelDb<SocketAddr, PeerAddressBookEntry>,
 }
 
 impl fmt::Debug for PeerDatabases {
@@ -1,9 +1,13 @@
 use crate::prelude::twenty_first;
 
+use serde::de::{self, Deserializer, SeqAccess, Visitor};
 use serde::{Deserialize, Serialize};
-use std::fmt::Display;
+use std::collections::HashMap;
+use std::fmt::{self, Display};
+use std::marker::PhantomData;
 use std::net::SocketAddr;
-use std::time::SystemTime;
+use std::time::{Duration, Instant, SystemTime};
+use strum::{EnumDiscriminants, EnumIter};
 use twenty_first::shared_math::digest::Digest;
 
 use twenty_first::amount::u32s::U32s;
@@ -11,11 +15,13 @@ use twenty_first::util_types::algebraic_hasher::AlgebraicHasher;
 
 use super::blockchain::block::block_header::{BlockHeader, PROOF_OF_WORK_COUNT_U32_SIZE};
 use super::blockchain::block::block_height::BlockHeight;
+use super::blockchain::block::compact_block::CompactBlock;
 use super::blockchain::block::transfer_block::TransferBlock;
 use super::blockchain::block::Block;
 use super::blockchain::shared::Hash;
 use super::blockchain::transaction::Transaction;
 use crate::config_models::network::Network;
+use crate::peer_transport::PeerStaticKey;
 
 const BAD_BLOCK_BATCH_REQUEST_SEVERITY: u16 = 10;
 const INVALID_BLOCK_SEVERITY: u16 = 10;
@@ -28,6 +34,23 @@ const UNKNOWN_BLOCK_HEIGHT: u16 = 1;
 const INVALID_TRANSACTION: u16 = 10;
 const UNCONFIRMABLE_TRANSACTION: u16 = 2;
 const NO_STANDING_FOUND_MAYBE_CRASH: u16 = 10;
+const OVERSIZED_MESSAGE_SEVERITY: u16 = 10;
+const RATE_LIMIT_EXCEEDED_SEVERITY: u16 = 2;
+
+const KIB: usize = 1024;
+const MIB: usize = 1024 * KIB;
+
+/// The largest number of digests in a block request batch
+pub const MAX_BLOCK_REQUEST_BATCH_LENGTH: usize = 200;
+
+/// The largest number of positions in a request for the transactions of a compact block
+pub const MAX_COMPACT_BLOCK_TRANSACTIONS_REQUEST_LENGTH: usize = 8 * 1024;
+
+const RECONNECTION_BACKOFF_BASE_IN_SECONDS: u64 = 60;
+const RECONNECTION_BACKOFF_MAX_IN_SECONDS: u64 = 24 * 60 * 60; // 1 day
+const SUCCESSFUL_CONNECTION_SCORE: i64 = 10;
+const FAILED_CONNECTION_SCORE: i64 = 2;
+const MAX_CONSECUTIVE_CONNECTION_FAILURES: u32 = 12;
 
 pub type InstanceId = u128;
 
@@ -71,6 +94,8 @@ pub enum PeerSanctionReason {
     UnconfirmableTransaction,
 
     NoStandingFoundMaybeCrash,
+    OversizedMessage(PeerMessageType),
+    RateLimitExceeded(PeerMessageType),
 }
 
 impl Display for PeerSanctionReason {
@@ -99,6 +124,8 @@ impl Display for PeerSanctionReason {
             PeerSanctionReason::NoStandingFoundMaybeCrash => {
                 "No standing found in map. Did peer thread crash?"
             }
+            PeerSanctionReason::OversizedMessage(_) => "oversized message",
+            PeerSanctionReason::RateLimitExceeded(_) => "rate limit exceeded",
         };
         write!(f, "{string}")
     }
@@ -149,6 +176,8 @@ impl PeerSanctionReason {
             PeerSanctionReason::UnconfirmableTransaction => UNCONFIRMABLE_TRANSACTION,
             PeerSanctionReason::NonMinedTransactionHasCoinbase => INVALID_TRANSACTION,
             PeerSanctionReason::NoStandingFoundMaybeCrash => NO_STANDING_FOUND_MAYBE_CRASH,
+            PeerSanctionReason::OversizedMessage(_) => OVERSIZED_MESSAGE_SEVERITY,
+            PeerSanctionReason::RateLimitExceeded(_) => RATE_LIMIT_EXCEEDED_SEVERITY,
         }
     }
 }
@@ -189,6 +218,84 @@ impl PeerStanding {
     }
 }
 
+/// This is the object that gets stored in the address book database for every
+/// address that a peer has been seen listening on. It outlives restarts, such that
+/// outgoing connections can be made before peer discovery has found anyone.
+#[derive(Clone, Copy, Debug, Serialize, Deserialize, PartialEq, Eq)]
+pub struct PeerAddressBookEntry {
+    pub first_seen: SystemTime,
+    pub last_success: Option<SystemTime>,
+    pub last_failure: Option<SystemTime>,
+
+    // Number of failed connection attempts since the last successful one
+    pub consecutive_failures: u32,
+
+    // The static key that the peer presented in the first encrypted connection to
+    // this address
+    pub static_key: Option<PeerStaticKey>,
+}
+
+impl PeerAddressBookEntry {
+    pub fn new(now: SystemTime) -> Self {
+        Self {
+            first_seen: now,
+            last_success: None,
+            last_failure: None,
+            consecutive_failures: 0,
+            static_key: None,
+        }
+    }
+
+    pub fn record_success(&mut self, now: SystemTime) {
+        self.last_success = Some(now);
+        self.consecutive_failures = 0;
+    }
+
+    pub fn record_failure(&mut self, now: SystemTime) {
+        self.last_failure = Some(now);
+        self.consecutive_failures = self.consecutive_failures.saturating_add(1);
+    }
+
+    /// The time to wait after the latest failure before trying again. Doubles with
+    /// every consecutive failure, up to a maximum.
+    pub fn backoff(&self) -> Duration {
+        if self.consecutive_failures == 0 {
+            return Duration::ZERO;
+        }
+
+        let doublings = (self.consecutive_failures - 1).min(u64::BITS - 1);
+        let backoff = RECONNECTION_BACKOFF_BASE_IN_SECONDS.saturating_mul(1 << doublings);
+        Duration::from_secs(backoff.min(RECONNECTION_BACKOFF_MAX_IN_SECONDS))
+    }
+
+    /// Return true if the backoff after the latest failure has passed.
+    pub fn is_due(&self, now: SystemTime) -> bool {
+        match self.last_failure {
+            Some(last_failure) => last_failure + self.backoff() <= now,
+            None => true,
+        }
+    }
+
+    /// Return true if connecting to this address has failed for so long that it is not
+    /// worth keeping. With the backoff doubling, this is after a couple of days.
+    pub fn is_abandoned(&self) -> bool {
+        self.consecutive_failures >= MAX_CONSECUTIVE_CONNECTION_FAILURES
+    }
+
+    /// How promising this address is as an outgoing connection. Higher is better.
+    /// Derived from the standing of the peer's IP, if any, and from the outcome of
+    /// previous connection attempts.
+    pub fn score(&self, standing: Option<PeerStanding>) -> i64 {
+        let standing = standing.map(|s| s.standing as i64).unwrap_or_default();
+        let success = match self.last_success {
+            Some(_) => SUCCESSFUL_CONNECTION_SCORE,
+            None => 0,
+        };
+
+        standing + success - FAILED_CONNECTION_SCORE * self.consecutive_failures as i64
+    }
+}
+
 #[derive(Clone, Debug, Serialize, Deserialize, PartialEq, Eq)]
 pub struct HandshakeData {
     pub tip_header: BlockHeader,
@@ -272,7 +379,8 @@ impl From<Transaction> for TransactionNotification {
     }
 }
 
-#[derive(Clone, Debug, Serialize, Deserialize, PartialEq, Eq)]
+#[derive(Clone, Debug, Serialize, Deserialize, PartialEq, Eq, EnumDiscriminants)]
+#[strum_discriminants(name(PeerMessageType), derive(Hash, Serialize, Deserialize, EnumIter))]
 pub enum PeerMessage {
     Handshake(Box<(Vec<u8>, HandshakeData)>),
     Block(Box<TransferBlock>),
@@ -280,8 +388,14 @@ pub enum PeerMessage {
     BlockNotification(PeerBlockNotification),
     BlockRequestByHeight(BlockHeight),
     BlockRequestByHash(Digest),
-    BlockRequestBatch(Vec<Digest>, usize), // TODO: Consider restricting this in size
-    BlockResponseBatch(Vec<TransferBlock>), // TODO: Consider restricting this in size
+    /// Restricted in size by [`PeerMessageType::max_size`], and in length by
+    /// [`MAX_BLOCK_REQUEST_BATCH_LENGTH`]
+    BlockRequestBatch(
+        #[serde(deserialize_with = "deserialize_block_request_batch")] Vec<Digest>,
+        usize,
+    ),
+    /// Restricted in size by [`PeerMessageType::max_size`]
+    BlockResponseBatch(Vec<TransferBlock>),
     /// Send a full transaction object to a peer.
     Transaction(Box<Transaction>),
     /// Send a notification to a peer, informing it that this node stores the
@@ -297,6 +411,18 @@ pub enum PeerMessage {
     /// Inform peer that we are disconnecting them.
     Bye,
     ConnectionStatus(ConnectionStatus),
+    /// Request a block as a compact block, which is answered with a full block if the
+    /// peer cannot describe it compactly.
+    CompactBlockRequest(Digest),
+    CompactBlock(Box<CompactBlock>),
+    /// Request the transactions of a compact block at the given positions, because
+    /// they are not in the mempool. At most
+    /// [`MAX_COMPACT_BLOCK_TRANSACTIONS_REQUEST_LENGTH`] positions.
+    CompactBlockTransactionsRequest(
+        Digest,
+        #[serde(deserialize_with = "deserialize_compact_block_transactions_request")] Vec<u32>,
+    ),
+    CompactBlockTransactions(Digest, Vec<Transaction>),
 }
 
 impl PeerMessage {
@@ -317,6 +443,12 @@ impl PeerMessage {
             PeerMessage::PeerListResponse(_) => "peer list resp".to_string(),
             PeerMessage::Bye => "bye".to_string(),
             PeerMessage::ConnectionStatus(_) => "connection status".to_string(),
+            PeerMessage::CompactBlockRequest(_) => "compact block req".to_string(),
+            PeerMessage::CompactBlock(_) => "compact block".to_string(),
+            PeerMessage::CompactBlockTransactionsRequest(_, _) => {
+                "compact block transactions req".to_string()
+            }
+            PeerMessage::CompactBlockTransactions(_, _) => "compact block transactions".to_string(),
         }
     }
 
@@ -337,6 +469,10 @@ impl PeerMessage {
             PeerMessage::PeerListResponse(_) => false,
             PeerMessage::Bye => false,
             PeerMessage::ConnectionStatus(_) => false,
+            PeerMessage::CompactBlockRequest(_) => false,
+            PeerMessage::CompactBlock(_) => false,
+            PeerMessage::CompactBlockTransactionsRequest(_, _) => false,
+            PeerMessage::CompactBlockTransactions(_, _) => false,
         }
     }
 
@@ -358,15 +494,218 @@ impl PeerMessage {
             PeerMessage::PeerListResponse(_) => false,
             PeerMessage::Bye => false,
             PeerMessage::ConnectionStatus(_) => false,
+            PeerMessage::CompactBlockRequest(_) => false,
+            PeerMessage::CompactBlock(_) => true,
+            PeerMessage::CompactBlockTransactionsRequest(_, _) => false,
+            PeerMessage::CompactBlockTransactions(_, _) => true,
         }
     }
 }
 
+/// Deserialize a vector of at most `max_length` elements. Fails before reading any
+/// element if the encoded length exceeds the bound.
+fn deserialize_bounded_vec<'de, D, T>(
+    deserializer: D,
+    max_length: usize,
+) -> Result<Vec<T>, D::Error>
+where
+    D: Deserializer<'de>,
+    T: Deserialize<'de>,
+{
+    struct BoundedVecVisitor<T> {
+        max_length: usize,
+        element: PhantomData<T>,
+    }
+
+    impl<'de, T: Deserialize<'de>> Visitor<'de> for BoundedVecVisitor<T> {
+        type Value = Vec<T>;
+
+        fn expecting(&self, formatter: &mut fmt::Formatter) -> fmt::Result {
+            write!(
+                formatter,
+                "a sequence of at most {} elements",
+                self.max_length
+            )
+        }
+
+        fn visit_seq<A: SeqAccess<'de>>(self, mut seq: A) -> Result<Vec<T>, A::Error> {
+            let length = seq.size_hint().unwrap_or(0);
+            if length > self.max_length {
+                return Err(de::Error::invalid_length(length, &self));
+            }
+
+            let mut elements = Vec::with_capacity(length);
+            while let Some(element) = seq.next_element()? {
+                if elements.len() == self.max_length {
+                    return Err(de::Error::invalid_length(self.max_length + 1, &self));
+                }
+                elements.push(element);
+            }
+            Ok(elements)
+        }
+    }
+
+    deserializer.deserialize_seq(BoundedVecVisitor {
+        max_length,
+        element: PhantomData,
+    })
+}
+
+fn deserialize_block_request_batch<'de, D: Deserializer<'de>>(
+    deserializer: D,
+) -> Result<Vec<Digest>, D::Error> {
+    deserialize_bounded_vec(deserializer, MAX_BLOCK_REQUEST_BATCH_LENGTH)
+}
+
+fn deserialize_compact_block_transactions_request<'de, D: Deserializer<'de>>(
+    deserializer: D,
+) -> Result<Vec<u32>, D::Error> {
+    deserialize_bounded_vec(deserializer, MAX_COMPACT_BLOCK_TRANSACTIONS_REQUEST_LENGTH)
+}
+
+impl PeerMessageType {
+    /// The largest number of bytes that a message of this type may take up when
+    /// encoded. Larger messages are rejected before they are decoded, and their
+    /// senders are sanctioned. The largest of these is the max frame length of the
+    /// connection.
+    pub fn max_size(&self) -> usize {
+        match self {
+            PeerMessageType::Handshake => 64 * KIB,
+            PeerMessageType::Block => 128 * MIB,
+            PeerMessageType::BlockNotificationRequest => KIB,
+            PeerMessageType::BlockNotification => KIB,
+            PeerMessageType::BlockRequestByHeight => KIB,
+            PeerMessageType::BlockRequestByHash => KIB,
+            // Room for `MAX_BLOCK_REQUEST_BATCH_LENGTH` digests, each of which is looked up
+            // in the database
+            PeerMessageType::BlockRequestBatch => 8 * KIB,
+            // Room for two blocks of maximal size, the smallest batch a peer may respond with
+            PeerMessageType::BlockResponseBatch => 2 * PeerMessageType::Block.max_size() + KIB,
+            PeerMessageType::Transaction => 64 * MIB,
+            PeerMessageType::TransactionNotification => KIB,
+            PeerMessageType::TransactionRequest => KIB,
+            PeerMessageType::PeerListRequest => KIB,
+            PeerMessageType::PeerListResponse => 4 * KIB,
+            PeerMessageType::Bye => KIB,
+            PeerMessageType::ConnectionStatus => KIB,
+            PeerMessageType::CompactBlockRequest => KIB,
+            PeerMessageType::CompactBlock => 16 * MIB,
+            PeerMessageType::CompactBlockTransactionsRequest => 64 * KIB,
+            PeerMessageType::CompactBlockTransactions => 128 * MIB,
+        }
+    }
+
+    /// The number of messages of this type that a peer may send in a burst, and the
+    /// number per second that it may send after that. `None` if unlimited.
+    pub fn rate_limit(&self) -> Option<(f64, f64)> {
+        match self {
+            PeerMessageType::Handshake => Some((2.0, 0.1)),
+            PeerMessageType::Block => Some((20.0, 1.0)),
+            PeerMessageType::BlockNotificationRequest => Some((5.0, 0.1)),
+            PeerMessageType::BlockNotification => Some((20.0, 1.0)),
+            PeerMessageType::BlockRequestByHeight => Some((100.0, 10.0)),
+            PeerMessageType::BlockRequestByHash => Some((100.0, 10.0)),
+            // Syncing peers request a batch every few seconds
+            PeerMessageType::BlockRequestBatch => Some((10.0, 0.5)),
+            PeerMessageType::BlockResponseBatch => Some((10.0, 0.5)),
+            PeerMessageType::Transaction => Some((100.0, 10.0)),
+            PeerMessageType::TransactionNotification => Some((100.0, 10.0)),
+            PeerMessageType::TransactionRequest => Some((100.0, 10.0)),
+            PeerMessageType::PeerListRequest => Some((5.0, 1.0 / 30.0)),
+            PeerMessageType::PeerListResponse => Some((5.0, 1.0 / 30.0)),
+            PeerMessageType::Bye => None,
+            PeerMessageType::ConnectionStatus => Some((2.0, 0.1)),
+            PeerMessageType::CompactBlockRequest => Some((100.0, 10.0)),
+            PeerMessageType::CompactBlock => Some((20.0, 1.0)),
+            PeerMessageType::CompactBlockTransactionsRequest => Some((20.0, 1.0)),
+            PeerMessageType::CompactBlockTransactions => Some((20.0, 1.0)),
+        }
+    }
+}
+
+/// A bucket that holds up to `capacity` tokens and is refilled at a constant rate.
+/// Taking a token fails if the bucket is empty.
+#[derive(Clone, Copy, Debug)]
+pub struct TokenBucket {
+    capacity: f64,
+    refill_per_second: f64,
+    tokens: f64,
+    last_refill: Instant,
+}
+
+impl TokenBucket {
+    /// A full bucket
+    pub fn new(capacity: f64, refill_per_second: f64, now: Instant) -> Self {
+        Self {
+            capacity,
+            refill_per_second,
+            tokens: capacity,
+            last_refill: now,
+        }
+    }
+
+    pub fn try_take(&mut self, now: Instant) -> bool {
+        let elapsed = now
+            .saturating_duration_since(self.last_refill)
+            .as_secs_f64();
+        self.tokens = (self.tokens + elapsed * self.refill_per_second).min(self.capacity);
+        self.last_refill = now;
+
+        if self.tokens < 1.0 {
+            return false;
+        }
+        self.tokens -= 1.0;
+        true
+    }
+}
+
+/// Limits the rate at which a peer may send messages of each type
+#[derive(Clone, Debug, Default)]
+pub struct PeerMessageRateLimiter {
+    buckets: HashMap<PeerMessageType, TokenBucket>,
+}
+
+impl PeerMessageRateLimiter {
+    /// Return true if the peer may send a message of this type now, and count it.
+    pub fn allow(&mut self, message_type: PeerMessageType, now: Instant) -> bool {
+        let Some((capacity, refill_per_second)) = message_type.rate_limit() else {
+            return true;
+        };
+
+        self.buckets
+            .entry(message_type)
+            .or_insert_with(|| TokenBucket::new(capacity, refill_per_second, now))
+            .try_take(now)
+    }
+}
+
+/// The largest number of compact blocks that may be pending from a peer at a time.
+/// Further blocks are requested in full.
+pub const MAX_PENDING_COMPACT_BLOCKS: usize = 8;
+
+/// A compact block that was requested from the peer, and has not been rebuilt yet.
+/// Pending compact blocks are keyed by the hash of the requested block.
+#[derive(Clone, Debug)]
+pub enum PendingCompactBlock {
+    /// The compact block was requested
+    Requested,
+
+    /// Transactions of the compact block were missing from the mempool and were
+    /// requested. Holds the transactions found so far.
+    MissingTransactions(Box<CompactBlock>, Vec<Option<Transaction>>),
+}
+
 /// `MutablePeerState` contains the part of the peer-loop's state that is mutable
 #[derive(Clone, Debug)]
 pub struct MutablePeerState {
     pub highest_shared_block_height: BlockHeight,
     pub fork_reconciliation_blocks: Vec<Block>,
+    pub rate_limiter: PeerMessageRateLimiter,
+    pub pending_compact_blocks: HashMap<Digest, PendingCompactBlock>,
+
+    // The number of blocks requested from the peer that it has not sent yet. Blocks
+    // and compact blocks that answer these requests are exempt from the rate limit.
+    pub outstanding_block_requests: usize,
 }
 
 impl MutablePeerState {
@@ -374,6 +713,168 @@ impl MutablePeerState {
         Self {
             highest_shared_block_height: block_height,
             fork_reconciliation_blocks: vec![],
+            rate_limiter: PeerMessageRateLimiter::default(),
+            pending_compact_blocks: HashMap::new(),
+            outstanding_block_requests: 0,
+        }
+    }
+}
+
+#[cfg(test)]
+mod peer_address_book_tests {
+    use super::*;
+
+    #[test]
+    fn backoff_doubles_until_max() {
+        let now = SystemTime::now();
+        let mut entry = PeerAddressBookEntry::new(now);
+        assert_eq!(Duration::ZERO, entry.backoff());
+        assert!(entry.is_due(now));
+
+        entry.record_failure(now);
+        assert_eq!(
+            Duration::from_secs(RECONNECTION_BACKOFF_BASE_IN_SECONDS),
+            entry.backoff()
+        );
+        entry.record_failure(now);
+        assert_eq!(
+            Duration::from_secs(2 * RECONNECTION_BACKOFF_BASE_IN_SECONDS),
+            entry.backoff()
+        );
+        assert!(!entry.is_due(now));
+        assert!(entry.is_due(now + entry.backoff()));
+
+        for _ in 0..100 {
+            entry.record_failure(now);
         }
+        assert_eq!(
+            Duration::from_secs(RECONNECTION_BACKOFF_MAX_IN_SECONDS),
+            entry.backoff()
+        );
+
+        entry.record_success(now);
+        assert_eq!(Duration::ZERO, entry.backoff());
+        assert!(entry.is_due(now));
+    }
+
+    #[test]
+    fn score_follows_standing_and_connection_outcomes() {
+        let now = SystemTime::now();
+        let fresh = PeerAddressBookEntry::new(now);
+        let mut succeeded = fresh;
+        succeeded.record_success(now);
+        let mut failed = fresh;
+        failed.record_failure(now);
+
+        assert!(succeeded.score(None) > fresh.score(None));
+        assert!(fresh.score(None) > failed.score(None));
+
+        let mut bad_standing = PeerStanding::default();
+        bad_standing.sanction(PeerSanctionReason::InvalidBlock((
+            BlockHeight::genesis(),
+            Digest::default(),
+        )));
+        assert!(succeeded.score(Some(bad_standing)) < succeeded.score(None));
+    }
+}
+
+#[cfg(test)]
+mod peer_message_limit_tests {
+    use super::*;
+    use crate::connect_to_peers::MAX_PEER_FRAME_LENGTH_IN_BYTES;
+    use strum::IntoEnumIterator;
+
+    #[test]
+    fn token_bucket_allows_bursts_then_refills() {
+        let start = Instant::now();
+        let mut bucket = TokenBucket::new(3.0, 0.5, start);
+        assert!((0..3).all(|_| bucket.try_take(start)));
+        assert!(!bucket.try_take(start));
+
+        // Half a token after a second, one after two
+        assert!(!bucket.try_take(start + Duration::from_secs(1)));
+        assert!(bucket.try_take(start + Duration::from_secs(2)));
+        assert!(!bucket.try_take(start + Duration::from_secs(2)));
+
+        // Never more than the capacity
+        let much_later = start + Duration::from_secs(3600);
+        assert!((0..3).all(|_| bucket.try_take(much_later)));
+        assert!(!bucket.try_take(much_later));
+    }
+
+    #[test]
+    fn rate_limiter_counts_message_types_separately() {
+        let now = Instant::now();
+        let mut rate_limiter = PeerMessageRateLimiter::default();
+        let (burst, _) = PeerMessageType::BlockRequestBatch.rate_limit().unwrap();
+        for _ in 0..burst as usize {
+            assert!(rate_limiter.allow(PeerMessageType::BlockRequestBatch, now));
+        }
+        assert!(!rate_limiter.allow(PeerMessageType::BlockRequestBatch, now));
+        assert!(rate_limiter.allow(PeerMessageType::BlockRequestByHeight, now));
+        assert!((0..1000).all(|_| rate_limiter.allow(PeerMessageType::Bye, now)));
+    }
+
+    #[test]
+    fn message_type_matches_variant_and_sizes_fit_in_frames() {
+        assert_eq!(
+            PeerMessageType::BlockRequestBatch,
+            PeerMessageType::from(&PeerMessage::BlockRequestBatch(vec![], 1))
+        );
+
+        let max_batch_request = PeerMessage::BlockRequestBatch(
+            vec![Digest::default(); MAX_BLOCK_REQUEST_BATCH_LENGTH],
+            50,
+        );
+        assert!(
+            bincode::serialized_size(&max_batch_request).unwrap() as usize
+                <= PeerMessageType::BlockRequestBatch.max_size()
+        );
+        let max_transactions_request = PeerMessage::CompactBlockTransactionsRequest(
+            Digest::default(),
+            vec![0; MAX_COMPACT_BLOCK_TRANSACTIONS_REQUEST_LENGTH],
+        );
+        assert!(
+            bincode::serialized_size(&max_transactions_request).unwrap() as usize
+                <= PeerMessageType::CompactBlockTransactionsRequest.max_size()
+        );
+
+        let largest_max_size = PeerMessageType::iter()
+            .map(|message_type| message_type.max_size())
+            .max()
+            .unwrap();
+        assert_eq!(MAX_PEER_FRAME_LENGTH_IN_BYTES, largest_max_size);
+    }
+
+    #[test]
+    fn vector_lengths_are_bounded_when_decoding() {
+        let max_batch_request = PeerMessage::BlockRequestBatch(
+            vec![Digest::default(); MAX_BLOCK_REQUEST_BATCH_LENGTH],
+            50,
+        );
+        let encoded = bincode::serialize(&max_batch_request).unwrap();
+        assert_eq!(
+            max_batch_request,
+            bincode::deserialize::<PeerMessage>(&encoded).unwrap()
+        );
+
+        let long_batch_request = PeerMessage::BlockRequestBatch(
+            vec![Digest::default(); MAX_BLOCK_REQUEST_BATCH_LENGTH + 1],
+            50,
+        );
+        let encoded = bincode::serialize(&long_batch_request).unwrap();
+        assert!(bincode::deserialize::<PeerMessage>(&encoded).is_err());
+
+        // Only the announced length is read, not the elements
+        let mut announced_only = bincode::serialize(&PeerMessageType::BlockRequestBatch).unwrap();
+        announced_only.extend_from_slice(&u64::MAX.to_le_bytes());
+        assert!(bincode::deserialize::<PeerMessage>(&announced_only).is_err());
+
+        let long_transactions_request = PeerMessage::CompactBlockTransactionsRequest(
+            Digest::default(),
+            vec![0; MAX_COMPACT_BLOCK_TRANSACTIONS_REQUEST_LENGTH + 1],
+        );
+        let encoded = bincode::serialize(&long_transactions_request).unwrap();
+        assert!(bincode::deserialize::<PeerMessage>(&encoded).is_err());
     }
 }
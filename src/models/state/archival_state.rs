@@ -1,6 +1,7 @@
 use crate::prelude::twenty_first;
 
-use anyhow::Result;
+use anyhow::{bail, Result};
+use itertools::Itertools;
 use memmap2::MmapOptions;
 use num_traits::Zero;
 use std::ops::DerefMut;
@@ -13,6 +14,7 @@ use tracing::{debug, warn};
 use twenty_first::amount::u32s::U32s;
 use twenty_first::shared_math::digest::Digest;
 use twenty_first::storage::level_db::DB;
+use twenty_first::util_types::algebraic_hasher::AlgebraicHasher;
 use twenty_first::util_types::mmr::mmr_trait::Mmr;
 use twenty_first::util_types::storage_schema::traits::*;
 
@@ -21,6 +23,8 @@ use crate::config_models::data_directory::DataDirectory;
 use crate::database::{create_db_if_missing, NeptuneLevelDb};
 use crate::models::blockchain::block::block_header::{BlockHeader, PROOF_OF_WORK_COUNT_U32_SIZE};
 use crate::models::blockchain::block::{block_height::BlockHeight, Block};
+use crate::models::blockchain::shared::Hash;
+use crate::models::blockchain::transaction::Transaction;
 use crate::models::database::{
     BlockFileLocation, BlockIndexKey, BlockIndexValue, BlockRecord, FileRecord, LastFileRecord,
 };
@@ -49,9 +53,10 @@ pub struct ArchivalState {
     ///   Height(BlockHeight)  -> Height(Vec<Digest>)
     ///   LastFile             -> LastFile(LastFileRecord)
     ///   BlockTipDigest       -> BlockTipDigest(Digest)
+    ///   Transaction(Digest)  -> Transaction(Vec<Digest>)
     /// ```
     ///
-    /// So this is effectively 5 logical indexes.
+    /// So this is effectively 6 logical indexes.
     pub block_index_db: NeptuneLevelDb<BlockIndexKey, BlockIndexValue>,
 
     // The genesis block is stored on the heap, as we would otherwise get stack overflows whenever we instantiate
@@ -332,6 +337,33 @@ impl ArchivalState {
             BlockIndexValue::Height(blocks_at_same_height),
         ));
 
+        // Index the block's transaction and the transactions it was merged from
+        let transaction_ids = new_block
+            .kernel
+            .body
+            .transaction
+            .constituents()
+            .into_iter()
+            .map(Hash::hash)
+            .unique()
+            .collect_vec();
+        for transaction_id in transaction_ids {
+            let transaction_record_key = BlockIndexKey::Transaction(transaction_id);
+            let mut blocks_with_transaction: Vec<Digest> = match self
+                .block_index_db
+                .get(transaction_record_key.clone())
+                .await
+            {
+                Some(rec) => rec.as_transaction_record(),
+                None => vec![],
+            };
+            blocks_with_transaction.push(new_block.hash());
+            block_index_entries.push((
+                transaction_record_key,
+                BlockIndexValue::Transaction(blocks_with_transaction),
+            ));
+        }
+
         // Mark block as tip if its PoW family is larger than current most canonical
         if current_max_pow_family.is_none()
             || current_max_pow_family.unwrap() < new_block.kernel.header.proof_of_work_family
@@ -609,6 +641,120 @@ impl ArchivalState {
         backwards.is_empty()
     }
 
+    /// Return the block at the given height that belongs to the canonical chain ending in
+    /// `tip_digest`, if one is known.
+    pub async fn get_canonical_block_at_height(
+        &self,
+        block_height: BlockHeight,
+        tip_digest: Digest,
+    ) -> Result<Option<Block>> {
+        for block_digest in self.block_height_to_block_digests(block_height).await {
+            if self
+                .block_belongs_to_canonical_chain(block_digest, tip_digest)
+                .await
+            {
+                return self.get_block(block_digest).await;
+            }
+        }
+
+        // The genesis block is not stored in the block index database
+        if block_height == BlockHeight::genesis() {
+            return Ok(Some(*self.genesis_block.clone()));
+        }
+
+        Ok(None)
+    }
+
+    /// Return the transaction with the given ID if a block on the canonical chain ending in
+    /// `tip_digest` contains it, either as the block's transaction or as one of the
+    /// transactions that were merged into it.
+    pub async fn get_canonical_transaction(
+        &self,
+        transaction_id: Digest,
+        tip_digest: Digest,
+    ) -> Result<Option<Transaction>> {
+        let find_in = |block: &Block| {
+            block
+                .kernel
+                .body
+                .transaction
+                .constituents()
+                .into_iter()
+                .find(|transaction| Hash::hash(*transaction) == transaction_id)
+                .cloned()
+        };
+
+        let block_digests = self
+            .block_index_db
+            .get(BlockIndexKey::Transaction(transaction_id))
+            .await
+            .map(|x| x.as_transaction_record())
+            .unwrap_or_else(Vec::new);
+        for block_digest in block_digests {
+            if self
+                .block_belongs_to_canonical_chain(block_digest, tip_digest)
+                .await
+            {
+                let Some(block) = self.get_block(block_digest).await? else {
+                    bail!("Could not find indexed block {block_digest} in archival state");
+                };
+                return Ok(find_in(&block));
+            }
+        }
+
+        // The genesis block is not stored in the block index database
+        Ok(find_in(&self.genesis_block))
+    }
+
+    /// Return the block on the canonical chain ending in `tip_digest` whose transaction
+    /// added the AOCL leaf with the given index to the mutator set. Returns `None` if the
+    /// mutator set of the tip has no such leaf.
+    pub async fn find_canonical_block_with_aocl_index(
+        &self,
+        aocl_index: u64,
+        tip_digest: Digest,
+    ) -> Result<Option<Block>> {
+        let tip_header = match self.get_block_header(tip_digest).await {
+            Some(header) => header,
+            None => bail!("Unknown tip digest {tip_digest}"),
+        };
+
+        // Binary search for the lowest block whose AOCL contains the leaf
+        let mut min_height: u64 = 0;
+        let mut max_height: u64 = tip_header.height.into();
+        let mut found_block = None;
+        while min_height <= max_height {
+            let height = min_height + (max_height - min_height) / 2;
+            let block = match self
+                .get_canonical_block_at_height(height.into(), tip_digest)
+                .await?
+            {
+                Some(block) => block,
+                None => bail!("Canonical chain is missing a block at height {height}"),
+            };
+
+            if block
+                .kernel
+                .body
+                .mutator_set_accumulator
+                .kernel
+                .aocl
+                .count_leaves()
+                > aocl_index
+            {
+                found_block = Some(block);
+                if height == 0 {
+                    break;
+                }
+                max_height = height - 1;
+            } else {
+                min_height = height + 1;
+            }
+        }
+
+        Ok(found_block)
+    }
+
     /// Return a list of digests of the ancestors to the requested digest. Does not include the input
     /// digest. If no ancestors can be found, returns the empty list. The count is the maximum length
     /// of the returned list. E.g. if the input digest corresponds to height 2 and count is 5, the
@@ -888,7 +1034,8 @@ mod archival_state_tests {
             &archival_state.genesis_block,
             None,
             genesis_wallet_state
-                .wallet_secret
+                .spending_secret()
+                .unwrap()
                 .nth_generation_spending_key(0)
                 .to_address(),
         );
@@ -918,7 +1065,7 @@ mod archival_state_tests {
 
         let network = Network::Alpha;
         let genesis_wallet_state = get_mock_wallet_state(None, network).await;
-        let wallet = genesis_wallet_state.wallet_secret;
+        let wallet = genesis_wallet_state.wallet_secret.unwrap();
         let own_receiving_address = wallet.nth_generation_spending_key(0).to_address();
         let genesis_receiver_global_state_lock =
             get_mock_global_state(network, 0, Some(wallet)).await;
@@ -1069,7 +1216,7 @@ mod archival_state_tests {
         let (mut archival_state, _peer_db_lock, _data_dir) =
             make_unit_test_archival_state(network).await;
         let genesis_wallet_state = get_mock_wallet_state(None, network).await;
-        let genesis_wallet = genesis_wallet_state.wallet_secret;
+        let genesis_wallet = genesis_wallet_state.wallet_secret.unwrap();
         let own_receiving_address = genesis_wallet.nth_generation_spending_key(0).to_address();
         let global_state_lock =
             get_mock_global_state(Network::RegTest, 42, Some(genesis_wallet)).await;
@@ -1189,7 +1336,7 @@ mod archival_state_tests {
         // blocks, also when there are many blocks that push the active window of the
         // mutator set forwards.
         let genesis_wallet_state = get_mock_wallet_state(None, Network::Alpha).await;
-        let genesis_wallet = genesis_wallet_state.wallet_secret;
+        let genesis_wallet = genesis_wallet_state.wallet_secret.unwrap();
         let own_receiving_address = genesis_wallet.nth_generation_spending_key(0).to_address();
         let global_state_lock =
             get_mock_global_state(Network::RegTest, 42, Some(genesis_wallet)).await;
@@ -1350,7 +1497,7 @@ mod archival_state_tests {
     async fn allow_consumption_of_genesis_output_test() -> Result<()> {
         let network = Network::RegTest;
         let genesis_wallet_state = get_mock_wallet_state(None, network).await;
-        let genesis_wallet = genesis_wallet_state.wallet_secret;
+        let genesis_wallet = genesis_wallet_state.wallet_secret.unwrap();
         let own_receiving_address = genesis_wallet.nth_generation_spending_key(0).to_address();
         let genesis_block = Block::genesis_block();
         let (mut block_1_a, _, _) =
@@ -1397,10 +1544,11 @@ mod archival_state_tests {
         let network = Network::Alpha;
         let genesis_wallet_state = get_mock_wallet_state(None, network).await;
         let genesis_spending_key = genesis_wallet_state
-            .wallet_secret
+            .spending_secret()
+            .unwrap()
             .nth_generation_spending_key(0);
         let genesis_state_lock =
-            get_mock_global_state(network, 3, Some(genesis_wallet_state.wallet_secret)).await;
+            get_mock_global_state(network, 3, genesis_wallet_state.wallet_secret).await;
 
         let wallet_secret_alice = WalletSecret::new_random();
         let alice_spending_key = wallet_secret_alice.nth_generation_spending_key(0);
@@ -2572,6 +2720,131 @@ mod archival_state_tests {
             .is_empty());
     }
 
+    #[traced_test]
+    #[tokio::test]
+    async fn find_canonical_block_with_aocl_index_test() -> Result<()> {
+        let mut archival_state = make_test_archival_state(Network::Alpha).await;
+        let genesis = *archival_state.genesis_block.clone();
+        let own_receiving_address = WalletSecret::new_random()
+            .nth_generation_spending_key(0)
+            .to_address();
+
+        // Build a chain of three blocks, and a fork at height 2, with one output each
+        let (mock_block_1, _, _) =
+            make_mock_block_with_valid_pow(&genesis.clone(), None, own_receiving_address);
+        add_block_to_archival_state(&mut archival_state, mock_block_1.clone()).await?;
+        let (mock_block_2_a, _, _) =
+            make_mock_block_with_valid_pow(&mock_block_1.clone(), None, own_receiving_address);
+        add_block_to_archival_state(&mut archival_state, mock_block_2_a.clone()).await?;
+        let (mock_block_2_b, _, _) =
+            make_mock_block_with_valid_pow(&mock_block_1.clone(), None, own_receiving_address);
+        add_block_to_archival_state(&mut archival_state, mock_block_2_b.clone()).await?;
+        let (mock_block_3_a, _, _) =
+            make_mock_block_with_valid_pow(&mock_block_2_a.clone(), None, own_receiving_address);
+        add_block_to_archival_state(&mut archival_state, mock_block_3_a.clone()).await?;
+
+        let tip_digest = mock_block_3_a.hash();
+        let genesis_output_count = genesis.kernel.body.transaction.kernel.outputs.len() as u64;
+        let expected_blocks = [
+            (0, Some(genesis.hash())),
+            (genesis_output_count - 1, Some(genesis.hash())),
+            (genesis_output_count, Some(mock_block_1.hash())),
+            (genesis_output_count + 1, Some(mock_block_2_a.hash())),
+            (genesis_output_count + 2, Some(mock_block_3_a.hash())),
+            (genesis_output_count + 3, None),
+        ];
+        for (aocl_index, expected_block_digest) in expected_blocks {
+            let found_block = archival_state
+                .find_canonical_block_with_aocl_index(aocl_index, tip_digest)
+                .await?;
+            assert_eq!(
+                expected_block_digest,
+                found_block.map(|block| block.hash()),
+                "Wrong block for AOCL index {aocl_index}"
+            );
+        }
+
+        // Relative to the fork, the block at height 2 is a different one
+        let found_block = archival_state
+            .find_canonical_block_with_aocl_index(genesis_output_count + 1, mock_block_2_b.hash())
+            .await?;
+        assert_eq!(
+            Some(mock_block_2_b.hash()),
+            found_block.map(|block| block.hash())
+        );
+
+        Ok(())
+    }
+
+    #[traced_test]
+    #[tokio::test]
+    async fn get_canonical_transaction_test() -> Result<()> {
+        let network = Network::RegTest;
+        let mut archival_state = make_test_archival_state(network).await;
+        let genesis = *archival_state.genesis_block.clone();
+        let genesis_wallet = get_mock_wallet_state(None, network)
+            .await
+            .wallet_secret
+            .unwrap();
+        let own_receiving_address = genesis_wallet.nth_generation_spending_key(0).to_address();
+        let global_state_lock = get_mock_global_state(network, 42, Some(genesis_wallet)).await;
+
+        // Confirm a transaction in block 1a, and fork at height 1 without it
+        let receiver_data = UtxoReceiverData {
+            public_announcement: PublicAnnouncement::default(),
+            receiver_privacy_digest: random(),
+            sender_randomness: random(),
+            utxo: Utxo {
+                coins: NeptuneCoins::new(1).to_native_coins(),
+                lock_script_hash: LockScript::anyone_can_spend().hash(),
+            },
+        };
+        let sender_tx = global_state_lock
+            .lock_guard_mut()
+            .await
+            .create_transaction(vec![receiver_data], NeptuneCoins::new(1))
+            .await?;
+        let (mut block_1_a, _, _) =
+            make_mock_block_with_valid_pow(&genesis, None, own_receiving_address);
+        block_1_a.accumulate_transaction(
+            sender_tx.clone(),
+            &genesis.kernel.body.mutator_set_accumulator,
+        );
+        add_block_to_archival_state(&mut archival_state, block_1_a.clone()).await?;
+        let (block_1_b, _, _) =
+            make_mock_block_with_valid_pow(&genesis, None, own_receiving_address);
+        add_block_to_archival_state(&mut archival_state, block_1_b.clone()).await?;
+
+        // Both the block's transaction and the one merged into it are found
+        let tip_digest = block_1_a.hash();
+        for transaction in [&block_1_a.kernel.body.transaction, &sender_tx] {
+            assert_eq!(
+                Some(transaction.clone()),
+                archival_state
+                    .get_canonical_transaction(Hash::hash(transaction), tip_digest)
+                    .await?
+            );
+        }
+        assert_eq!(
+            Some(genesis.kernel.body.transaction.clone()),
+            archival_state
+                .get_canonical_transaction(Hash::hash(&genesis.kernel.body.transaction), tip_digest)
+                .await?
+        );
+        assert!(archival_state
+            .get_canonical_transaction(random(), tip_digest)
+            .await?
+            .is_none());
+
+        // Relative to the fork, the transaction is not confirmed
+        assert!(archival_state
+            .get_canonical_transaction(Hash::hash(&sender_tx), block_1_b.hash())
+            .await?
+            .is_none());
+
+        Ok(())
+    }
+
     #[traced_test]
     #[tokio::test]
     async fn write_block_db_test() -> Result<()> {
@@ -0,0 +1,143 @@
+//! Estimation of the fee that a transaction must pay in order to be mined within a given
+//! number of blocks. The estimate combines two sources: the fee densities of the
+//! transactions that are currently waiting in the mempool, and how full the recent blocks
+//! were and which fee densities they confirmed.
+
+use num_bigint::BigInt;
+use num_rational::BigRational;
+use num_traits::Zero;
+
+use crate::models::blockchain::transaction::neptune_coins::NeptuneCoins;
+
+/// The number of recent blocks whose fill is taken into account.
+pub const FEE_ESTIMATION_BLOCK_COUNT: usize = 10;
+
+/// The number of blocks within which a transaction is expected to be mined when the sender
+/// does not specify a target, e.g. with `--fee auto`.
+pub const DEFAULT_FEE_ESTIMATION_TARGET_BLOCKS: usize = 3;
+
+/// The transaction size that fees are estimated for when the size of the transaction is not
+/// known, and there are no transactions in the mempool to take the average size from.
+pub const DEFAULT_TRANSACTION_SIZE_FOR_FEE_ESTIMATION: usize = 100_000;
+
+/// The number of times a transaction is built to find a fee that covers its own size. A
+/// higher fee can require another input, which makes the transaction larger.
+pub const MAX_FEE_ESTIMATION_ROUNDS: usize = 3;
+
+/// A block whose transaction takes up at least this percentage of the block capacity is
+/// considered full. Full blocks indicate that transactions compete for block space.
+const FULL_BLOCK_FILL_PERCENTAGE: usize = 90;
+
+/// The size and the fee density of the transaction of a mined block.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct BlockFill {
+    pub transaction_size: usize,
+    pub fee_density: BigRational,
+}
+
+/// Estimate the fee density that a transaction needs to be mined soon.
+///
+/// `mempool_fee_density` is the fee density that must be exceeded to fit within the block
+/// space of the targeted number of blocks, as returned by
+/// [`Mempool::fee_density_to_fit_within`](super::mempool::Mempool::fee_density_to_fit_within).
+/// `recent_blocks` describes the fill of recent blocks. If most of these were full, the
+/// median fee density of the full blocks is a lower bound on the estimate, since block space
+/// is contended even if the mempool is momentarily empty.
+pub fn estimate_fee_density(
+    mempool_fee_density: Option<BigRational>,
+    recent_blocks: &[BlockFill],
+    block_capacity: usize,
+) -> BigRational {
+    let mut full_block_fee_densities = recent_blocks
+        .iter()
+        .filter(|block| {
+            block.transaction_size.saturating_mul(100)
+                >= block_capacity.saturating_mul(FULL_BLOCK_FILL_PERCENTAGE)
+        })
+        .map(|block| block.fee_density.clone())
+        .collect::<Vec<_>>();
+    full_block_fee_densities.sort();
+
+    let historical_fee_density = if 2 * full_block_fee_densities.len() > recent_blocks.len() {
+        full_block_fee_densities[full_block_fee_densities.len() / 2].clone()
+    } else {
+        BigRational::zero()
+    };
+
+    let mempool_fee_density = mempool_fee_density.unwrap_or_else(BigRational::zero);
+
+    std::cmp::max(mempool_fee_density, historical_fee_density)
+}
+
+/// The fee that a transaction of the given size must pay to reach the given fee density.
+/// Rounds up, such that the fee density is reached. Returns `None` if the fee cannot be
+/// represented as an amount of coins.
+pub fn fee_for_size(fee_density: &BigRational, transaction_size: usize) -> Option<NeptuneCoins> {
+    let transaction_size = BigRational::from_integer(BigInt::from(transaction_size));
+    let fee_in_nau = (fee_density * transaction_size).ceil().to_integer();
+    NeptuneCoins::from_nau(fee_in_nau)
+}
+
+#[cfg(test)]
+mod fee_estimation_tests {
+    use super::*;
+
+    fn density(fee: i64, size: i64) -> BigRational {
+        BigRational::new(BigInt::from(fee), BigInt::from(size))
+    }
+
+    fn block(transaction_size: usize, fee_density: BigRational) -> BlockFill {
+        BlockFill {
+            transaction_size,
+            fee_density,
+        }
+    }
+
+    #[test]
+    fn empty_mempool_and_empty_blocks_need_no_fee() {
+        let recent_blocks = vec![block(10, density(5, 1)), block(20, density(7, 1))];
+        assert!(estimate_fee_density(None, &recent_blocks, 1000).is_zero());
+        assert!(estimate_fee_density(None, &[], 1000).is_zero());
+    }
+
+    #[test]
+    fn mempool_fee_density_is_used() {
+        let recent_blocks = vec![block(10, density(5, 1))];
+        assert_eq!(
+            density(3, 2),
+            estimate_fee_density(Some(density(3, 2)), &recent_blocks, 1000)
+        );
+    }
+
+    #[test]
+    fn full_recent_blocks_set_a_lower_bound() {
+        let recent_blocks = vec![
+            block(950, density(4, 1)),
+            block(1000, density(2, 1)),
+            block(10, density(100, 1)),
+            block(990, density(3, 1)),
+        ];
+        assert_eq!(
+            density(3, 1),
+            estimate_fee_density(None, &recent_blocks, 1000)
+        );
+        assert_eq!(
+            density(3, 1),
+            estimate_fee_density(Some(density(1, 1)), &recent_blocks, 1000)
+        );
+        assert_eq!(
+            density(5, 1),
+            estimate_fee_density(Some(density(5, 1)), &recent_blocks, 1000)
+        );
+
+        // If only half of the blocks are full, block space is not contended
+        assert!(estimate_fee_density(None, &recent_blocks[2..], 1000).is_zero());
+    }
+
+    #[test]
+    fn fee_for_size_rounds_up() {
+        let fee = fee_for_size(&density(1, 3), 10).unwrap();
+        assert_eq!(BigInt::from(4), fee.to_nau());
+        assert!(fee_for_size(&BigRational::zero(), 10).unwrap().is_zero());
+    }
+}
@@ -14,6 +14,7 @@ use crate::{
 
 use bytesize::ByteSize;
 use get_size::GetSize;
+use num_bigint::BigInt;
 use num_traits::Zero;
 use priority_queue::{double_priority_queue::iterators::IntoSortedIter, DoublePriorityQueue};
 use std::{
@@ -21,9 +22,11 @@ use std::{
     iter::Rev,
     time::{Duration, SystemTime, UNIX_EPOCH},
 };
+use tracing::warn;
 use twenty_first::shared_math::digest::Digest;
 use twenty_first::util_types::algebraic_hasher::AlgebraicHasher;
 
+use crate::models::blockchain::block::compact_block::ShortTransactionId;
 use crate::models::blockchain::block::Block;
 use crate::models::blockchain::shared::Hash;
 use crate::models::blockchain::transaction::{neptune_coins::NeptuneCoins, Transaction};
@@ -54,6 +57,10 @@ pub const MEMPOOL_IGNORE_TRANSACTIONS_THIS_MANY_SECS_AHEAD: u64 = 5 * 60;
 
 pub const TRANSACTION_NOTIFICATION_AGE_LIMIT_IN_SECS: u64 = 60 * 60 * 24;
 
+/// The percentage by which a transaction must raise the fee of a conflicting transaction in
+/// the mempool in order to replace it.
+pub const MEMPOOL_REPLACEMENT_MIN_FEE_INCREASE_PERCENT: u32 = 10;
+
 type LookupItem<'a> = (Digest, &'a Transaction);
 
 /// Timestamp of 'now' encoded as the duration since epoch.
@@ -100,6 +107,33 @@ impl Mempool {
         self.tx_dictionary.get(&transaction_id)
     }
 
+    /// get the transactions with the given short IDs, computed with the given salt, from
+    /// the mempool, `None` for those that are not there
+    ///
+    /// Computes in O(N)
+    pub fn get_by_short_ids(
+        &self,
+        short_ids: &[ShortTransactionId],
+        salt: Digest,
+    ) -> Vec<Option<Transaction>> {
+        let by_short_id: HashMap<ShortTransactionId, &Transaction> = self
+            .tx_dictionary
+            .iter()
+            .map(|(transaction_id, transaction)| {
+                (ShortTransactionId::new(*transaction_id, salt), transaction)
+            })
+            .collect();
+
+        short_ids
+            .iter()
+            .map(|short_id| {
+                by_short_id
+                    .get(short_id)
+                    .map(|&transaction| transaction.clone())
+            })
+            .collect()
+    }
+
     /// Returns `Some(txid, transaction)` iff a transcation conflicts with a block that's already in
     /// the mempool. Returns `None` otherwise.
     fn transaction_conflicts_with(
@@ -125,6 +159,38 @@ impl Mempool {
         None
     }
 
+    /// The lowest fee with which a transaction can replace the given, conflicting
+    /// transaction: the fee must be raised by at least
+    /// [`MEMPOOL_REPLACEMENT_MIN_FEE_INCREASE_PERCENT`] percent, and by at least one nau.
+    /// Returns `None` if the fee of the given transaction is negative, or too large to be
+    /// raised, in which case it cannot be replaced.
+    pub fn minimum_replacement_fee(replaced: &Transaction) -> Option<NeptuneCoins> {
+        if replaced.kernel.fee.is_negative() {
+            return None;
+        }
+
+        let replaced_fee = replaced.kernel.fee.to_nau();
+        let percentage = BigInt::from(100 + MEMPOOL_REPLACEMENT_MIN_FEE_INCREASE_PERCENT);
+        let increased_fee = (replaced_fee.clone() * percentage + BigInt::from(99)) / 100;
+        let minimum_fee = std::cmp::max(increased_fee, replaced_fee + 1);
+        NeptuneCoins::from_nau(minimum_fee).filter(|fee| !fee.is_negative())
+    }
+
+    /// A transaction replaces a conflicting transaction iff it pays at least the
+    /// [minimum replacement fee](Self::minimum_replacement_fee) and has a higher fee density.
+    /// Requiring both prevents replacements that pay less in total by being smaller, and
+    /// makes every replacement cost something, which bounds how often it can happen.
+    /// Transactions whose fees cannot be compared this way are not replaced.
+    pub fn is_valid_replacement(replaced: &Transaction, replacement: &Transaction) -> bool {
+        let Some(minimum_fee) = Self::minimum_replacement_fee(replaced) else {
+            return false;
+        };
+
+        !replacement.kernel.fee.is_negative()
+            && replacement.kernel.fee >= minimum_fee
+            && replacement.fee_density() > replaced.fee_density()
+    }
+
     /// Insert a transaction into the mempool. It is the caller's responsibility to verify
     /// that the transaction is valid and confirmable.
     pub fn insert(&mut self, transaction: &Transaction) -> Option<Digest> {
@@ -143,15 +209,14 @@ impl Mempool {
         // is valid.
 
         // If transaction to be inserted conflicts with a transaction that's already
-        // in the mempool we preserve only the one with the highest fee density.
+        // in the mempool we preserve the old one, unless the new one is a valid replacement.
         if let Some((txid, tx)) = self.transaction_conflicts_with(transaction) {
-            if tx.fee_density() < transaction.fee_density() {
-                // If new transaction has a higher fee density than the one previously seen
+            if Self::is_valid_replacement(&tx, transaction) {
+                // If new transaction pays sufficiently more than the one previously seen
                 // remove the old one.
                 self.remove(txid);
             } else {
-                // If new transaction has a lower fee density than the one previous seen,
-                // ignore it. Stop execution here.
+                // Otherwise, ignore the new transaction. Stop execution here.
                 return Some(txid);
             }
         };
@@ -230,6 +295,24 @@ impl Mempool {
         transactions
     }
 
+    /// Return the fee density that a transaction must exceed to be among the transactions
+    /// that fill the first `storage` bytes of block space, when these are picked in order of
+    /// descending fee density. Returns `None` if all transactions in the mempool fit within
+    /// `storage` bytes, in which case any fee density suffices.
+    pub fn fee_density_to_fit_within(&self, storage: usize) -> Option<FeeDensity> {
+        let mut used_storage = 0usize;
+        for (transaction_digest, fee_density) in self.get_sorted_iter() {
+            if let Some(transaction) = self.get(transaction_digest) {
+                used_storage = used_storage.saturating_add(transaction.get_size());
+                if used_storage > storage {
+                    return Some(fee_density);
+                }
+            }
+        }
+
+        None
+    }
+
     /// Computes in θ(lg N)
     #[allow(dead_code)]
     pub fn pop_max(&mut self) -> Option<(Transaction, FeeDensity)> {
@@ -329,10 +412,19 @@ impl Mempool {
         // Remove the transactions that become invalid with this block
         self.retain(keep);
 
-        // Update the remaining transactions so their mutator set data is still valid
-        for tx in self.tx_dictionary.values_mut() {
-            tx.update_mutator_set_records(&previous_mutator_set_accumulator, block)
-                .expect("Updating mempool transaction must succeed");
+        // Update the remaining transactions so their mutator set data is still valid, and
+        // drop those that cannot be updated
+        let mut failed_updates = vec![];
+        for (transaction_id, tx) in self.tx_dictionary.iter_mut() {
+            if let Err(err) =
+                tx.update_mutator_set_records(&previous_mutator_set_accumulator, block)
+            {
+                warn!("Dropping mempool transaction that could not be updated: {err}");
+                failed_updates.push(*transaction_id);
+            }
+        }
+        for transaction_id in failed_updates {
+            self.remove(transaction_id);
         }
 
         // Maintaining the mutator set data could have increased the size of the
@@ -394,9 +486,12 @@ mod tests {
             blockchain::{
                 block::block_height::BlockHeight,
                 transaction::{
-                    neptune_coins::NeptuneCoins, utxo::Utxo, PublicAnnouncement, Transaction,
+                    neptune_coins::NeptuneCoins, transaction_kernel::TransactionKernel, utxo::Utxo,
+                    validity::TransactionDerivation, PublicAnnouncement, Transaction,
+                    TransactionPrimitiveWitness,
                 },
             },
+            consensus::Witness,
             shared::SIZE_20MB_IN_BYTES,
             state::{
                 wallet::{utxo_notification_pool::UtxoNotifier, WalletSecret},
@@ -410,7 +505,7 @@ mod tests {
         util_types::mutator_set::mutator_set_trait::MutatorSet,
     };
     use anyhow::Result;
-    use num_bigint::BigInt;
+    use itertools::Itertools;
     use num_traits::Zero;
     use rand::random;
     use tracing::debug;
@@ -497,6 +592,32 @@ mod tests {
         assert!(!mempool.is_empty())
     }
 
+    #[traced_test]
+    #[tokio::test]
+    async fn fee_density_to_fit_within() {
+        // Verify that the returned fee density is that of the first transaction that does
+        // not fit within the given storage
+        let mempool = setup(10, Network::Alpha).await;
+        assert!(mempool.fee_density_to_fit_within(usize::MAX).is_none());
+        assert!(mempool.fee_density_to_fit_within(0).is_some());
+
+        let sorted = mempool
+            .get_sorted_iter()
+            .map(|(transaction_id, fee_density)| {
+                (mempool.get(transaction_id).unwrap().get_size(), fee_density)
+            })
+            .collect_vec();
+        let storage_for_three = sorted[0].0 + sorted[1].0 + sorted[2].0;
+        assert_eq!(
+            Some(sorted[3].1.clone()),
+            mempool.fee_density_to_fit_within(storage_for_three)
+        );
+        assert_eq!(
+            Some(sorted[2].1.clone()),
+            mempool.fee_density_to_fit_within(storage_for_three - 1)
+        );
+    }
+
     #[traced_test]
     #[tokio::test]
     async fn prune_stale_transactions() {
@@ -545,7 +666,10 @@ mod tests {
             get_mock_global_state(Network::Alpha, 2, None).await;
         let mut premine_receiver_global_state =
             premine_receiver_global_state_lock.lock_guard_mut().await;
-        let premine_wallet_secret = &premine_receiver_global_state.wallet_state.wallet_secret;
+        let premine_wallet_secret = premine_receiver_global_state
+            .wallet_state
+            .spending_secret()
+            .unwrap();
         let premine_receiver_spending_key = premine_wallet_secret.nth_generation_spending_key(0);
         let premine_receiver_address = premine_receiver_spending_key.to_address();
         let other_wallet_secret = WalletSecret::new_random();
@@ -653,6 +777,48 @@ mod tests {
         // valid mutator set data
         let mut tx_by_other_updated: Transaction =
             mempool.get_transactions_for_block(usize::MAX)[0].clone();
+        assert!(
+            matches!(
+                tx_by_other_updated.witness,
+                Witness::Derivation(TransactionDerivation::Update(_))
+            ),
+            "Proven transaction must be supported by an update claim after block 2"
+        );
+        assert!(
+            tx_by_other_updated.is_valid(),
+            "Updated transaction must be valid"
+        );
+        let Witness::Derivation(TransactionDerivation::Update(update)) =
+            &tx_by_other_updated.witness
+        else {
+            unreachable!()
+        };
+        assert_eq!(
+            vec![tx_by_other_original.clone()],
+            update.operands,
+            "Update claim must keep the original transaction and its proof"
+        );
+        let mut tampered_update = update.clone();
+        tampered_update.operands[0].witness = Witness::Primitive(TransactionPrimitiveWitness {
+            input_utxos: vec![],
+            input_lock_scripts: vec![],
+            type_scripts: vec![],
+            lock_script_witnesses: vec![],
+            input_membership_proofs: vec![],
+            output_utxos: vec![],
+            output_sender_randomnesses: vec![],
+            output_receiver_digests: vec![],
+            public_announcements: vec![],
+            mutator_set_accumulator: MutatorSetAccumulator::new(),
+        });
+        let tampered = Transaction {
+            kernel: tx_by_other_updated.kernel.clone(),
+            witness: Witness::Derivation(TransactionDerivation::Update(tampered_update)),
+        };
+        assert!(
+            !tampered.is_valid(),
+            "Update of a transaction whose proof does not hold must be invalid"
+        );
 
         let (block_3_with_no_input, _, _) =
             make_mock_block(&block_2, None, premine_receiver_address);
@@ -693,6 +859,10 @@ mod tests {
         // Mine 10 more blocks without including the transaction but while still keeping the
         // mempool updated. After these 10 blocks are mined, the transaction must still be
         // valid.
+        mempool.update_with_block(
+            block_2.kernel.body.mutator_set_accumulator.clone(),
+            &block_3_with_no_input,
+        );
         let mut previous_block = block_3_with_no_input;
         for _ in 0..10 {
             let (next_block, _, _) = make_mock_block(&previous_block, None, other_receiver_address);
@@ -706,6 +876,20 @@ mod tests {
         let (mut block_14, _, _) = make_mock_block(&previous_block, None, other_receiver_address);
         assert_eq!(Into::<BlockHeight>::into(14), block_14.kernel.header.height);
         tx_by_other_updated = mempool.get_transactions_for_block(usize::MAX)[0].clone();
+        let Witness::Derivation(TransactionDerivation::Update(update)) =
+            &tx_by_other_updated.witness
+        else {
+            panic!("Proven transaction must be supported by an update claim after block 13");
+        };
+        assert_eq!(
+            vec![tx_by_other_original.clone()],
+            update.operands,
+            "Later updates must replace the update claim rather than nest it"
+        );
+        assert!(
+            tx_by_other_updated.is_valid(),
+            "Transaction updated over several blocks must be valid"
+        );
         block_14.accumulate_transaction(
             tx_by_other_updated,
             &previous_block.kernel.body.mutator_set_accumulator,
@@ -734,7 +918,7 @@ mod tests {
         // Create a global state object, controlled by a preminer who receives a premine-UTXO.
         let preminer_state_lock = get_mock_global_state(Network::Alpha, 2, None).await;
         let mut preminer_state = preminer_state_lock.lock_guard_mut().await;
-        let premine_wallet_secret = &preminer_state.wallet_state.wallet_secret;
+        let premine_wallet_secret = preminer_state.wallet_state.spending_secret().unwrap();
         let premine_spending_key = premine_wallet_secret.nth_generation_spending_key(0);
         let premine_address = premine_spending_key.to_address();
 
@@ -798,6 +982,92 @@ mod tests {
         Ok(())
     }
 
+    #[traced_test]
+    #[tokio::test]
+    async fn replacement_requires_minimum_fee_increase() -> Result<()> {
+        let preminer_state_lock = get_mock_global_state(Network::Alpha, 2, None).await;
+        let mut preminer_state = preminer_state_lock.lock_guard_mut().await;
+        let premine_address = preminer_state
+            .wallet_state
+            .spending_secret()
+            .unwrap()
+            .nth_generation_spending_key(0)
+            .to_address();
+        let receiver_data = UtxoReceiverData {
+            utxo: Utxo {
+                coins: NeptuneCoins::new(1).to_native_coins(),
+                lock_script_hash: premine_address.lock_script().hash(),
+            },
+            receiver_privacy_digest: premine_address.privacy_digest,
+            sender_randomness: random(),
+            public_announcement: PublicAnnouncement::default(),
+        };
+
+        let original = preminer_state
+            .create_transaction(vec![receiver_data.clone()], NeptuneCoins::new(10))
+            .await?;
+        assert_eq!(
+            Some(NeptuneCoins::new(11)),
+            Mempool::minimum_replacement_fee(&original)
+        );
+        preminer_state.mempool.insert(&original);
+
+        // Paying the same fee does not suffice to replace a transaction
+        let same_fee = preminer_state
+            .create_transaction(vec![receiver_data.clone()], NeptuneCoins::new(10))
+            .await?;
+        assert!(!Mempool::is_valid_replacement(&original, &same_fee));
+        preminer_state.mempool.insert(&same_fee);
+        assert_eq!(1, preminer_state.mempool.len());
+        assert!(preminer_state.mempool.contains(Hash::hash(&original)));
+
+        // Paying the minimum increase does
+        let increased_fee = preminer_state
+            .create_transaction(vec![receiver_data], NeptuneCoins::new(11))
+            .await?;
+        assert!(Mempool::is_valid_replacement(&original, &increased_fee));
+        preminer_state.mempool.insert(&increased_fee);
+        assert_eq!(1, preminer_state.mempool.len());
+        assert!(preminer_state.mempool.contains(Hash::hash(&increased_fee)));
+
+        // A transaction without fee is replaced by any fee
+        let no_fee = Transaction {
+            kernel: TransactionKernel {
+                fee: NeptuneCoins::zero(),
+                ..original.kernel.clone()
+            },
+            witness: original.witness.clone(),
+        };
+        assert_eq!(
+            NeptuneCoins::from_nau(1.into()),
+            Mempool::minimum_replacement_fee(&no_fee)
+        );
+
+        // A transaction with a negative fee is neither replaced nor a replacement, and
+        // inserting it next to a conflicting transaction does not panic
+        let negative_fee = Transaction {
+            kernel: TransactionKernel {
+                fee: -NeptuneCoins::new(1),
+                ..original.kernel.clone()
+            },
+            witness: original.witness.clone(),
+        };
+        assert_eq!(None, Mempool::minimum_replacement_fee(&negative_fee));
+        assert!(!Mempool::is_valid_replacement(
+            &negative_fee,
+            &increased_fee
+        ));
+        assert!(!Mempool::is_valid_replacement(
+            &increased_fee,
+            &negative_fee
+        ));
+        preminer_state.mempool.insert(&negative_fee);
+        assert_eq!(1, preminer_state.mempool.len());
+        assert!(preminer_state.mempool.contains(Hash::hash(&increased_fee)));
+
+        Ok(())
+    }
+
     #[traced_test]
     #[tokio::test]
     async fn get_mempool_size() {
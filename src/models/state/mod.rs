@@ -1,14 +1,18 @@
 use crate::models::consensus::mast_hash::MastHash;
-use crate::prelude::twenty_first;
+use crate::prelude::{triton_vm, twenty_first};
 use crate::util_types::mutator_set::mutator_set_accumulator::MutatorSetAccumulator;
 
 use anyhow::{bail, Result};
+use get_size::GetSize;
 use itertools::Itertools;
 use num_traits::{CheckedSub, Zero};
+use serde::{Deserialize, Serialize};
 use std::cmp::max;
+use std::collections::HashSet;
 use std::ops::{Deref, DerefMut};
 use std::time::{Duration, SystemTime, UNIX_EPOCH};
 use tracing::{debug, info, warn};
+use triton_vm::prelude::{NonDeterminism, PublicInput};
 use twenty_first::shared_math::b_field_element::BFieldElement;
 use twenty_first::shared_math::bfield_codec::BFieldCodec;
 use twenty_first::shared_math::digest::Digest;
@@ -19,21 +23,33 @@ use twenty_first::util_types::emojihash_trait::Emojihash;
 use twenty_first::util_types::mmr::mmr_trait::Mmr;
 
 use self::blockchain_state::BlockchainState;
+use self::fee_estimation::{
+    estimate_fee_density, fee_for_size, BlockFill, DEFAULT_TRANSACTION_SIZE_FOR_FEE_ESTIMATION,
+    FEE_ESTIMATION_BLOCK_COUNT, MAX_FEE_ESTIMATION_ROUNDS,
+};
 use self::mempool::Mempool;
 use self::networking_state::NetworkingState;
+use self::wallet::address::generation_address;
+use self::wallet::address::multisig_address::MultisigAddress;
+use self::wallet::coin_selection::CoinSelectionStrategy;
+use self::wallet::multisig::{MultisigSetup, PartiallySignedTransaction, MULTISIG_UPDATE_DEPTH};
+use self::wallet::unconfirmed_send::UnconfirmedSend;
+use self::wallet::unsigned_transaction::{SignedTransaction, UnsignedInput, UnsignedTransaction};
 use self::wallet::utxo_notification_pool::UtxoNotifier;
+use self::wallet::utxo_transfer::{UtxoClaimStatus, UtxoTransferNotification};
 use self::wallet::wallet_state::WalletState;
 use self::wallet::wallet_status::WalletStatus;
 use super::blockchain::block::block_height::BlockHeight;
 use super::blockchain::block::Block;
+use super::blockchain::transaction::native_coin::NATIVE_COIN_TYPESCRIPT_DIGEST;
+use super::blockchain::transaction::timelock::Timelock;
+use super::blockchain::transaction::token;
 use super::blockchain::transaction::transaction_kernel::TransactionKernel;
 use super::blockchain::transaction::utxo::{LockScript, TypeScript, Utxo};
-use super::blockchain::transaction::validity::TransactionValidationLogic;
 use super::blockchain::transaction::{neptune_coins::NeptuneCoins, Transaction};
 use super::blockchain::transaction::{PublicAnnouncement, TransactionPrimitiveWitness};
-use super::consensus::ValidationLogic;
+use super::shared::SIZE_20MB_IN_BYTES;
 use crate::config_models::cli_args;
-use crate::models::consensus::Witness;
 use crate::models::peer::HandshakeData;
 use crate::models::state::wallet::monitored_utxo::MonitoredUtxo;
 use crate::models::state::wallet::utxo_notification_pool::ExpectedUtxo;
@@ -48,6 +64,7 @@ use crate::{Hash, VERSION};
 
 pub mod archival_state;
 pub mod blockchain_state;
+pub mod fee_estimation;
 pub mod light_state;
 pub mod mempool;
 pub mod networking_state;
@@ -238,7 +255,7 @@ pub struct GlobalState {
     pub mining: bool,
 }
 
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct UtxoReceiverData {
     pub utxo: Utxo,
     pub sender_randomness: Digest,
@@ -246,6 +263,19 @@ pub struct UtxoReceiverData {
     pub public_announcement: PublicAnnouncement,
 }
 
+/// How the receiver of a transaction output learns about the output.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub enum UtxoNotificationMedium {
+    /// The notification is encrypted to the receiver and included in the transaction as a
+    /// public announcement.
+    #[default]
+    OnChain,
+
+    /// The notification is encrypted to the receiver but left out of the transaction. The
+    /// sender must deliver it to the receiver, which saves space on the blockchain.
+    OffChain,
+}
+
 impl GlobalState {
     pub fn new(
         wallet_state: WalletState,
@@ -374,6 +404,124 @@ impl GlobalState {
         history
     }
 
+    /// Estimate the fee that a transaction of the given size must pay to be mined within
+    /// `target_blocks` blocks. If the size is not given, the average size of the
+    /// transactions in the mempool is used.
+    pub async fn estimate_fee(
+        &self,
+        target_blocks: usize,
+        transaction_size: Option<usize>,
+    ) -> Result<NeptuneCoins> {
+        if target_blocks == 0 {
+            bail!("Cannot estimate the fee for a target of zero blocks");
+        }
+
+        let block_capacity = SIZE_20MB_IN_BYTES;
+        let mempool_fee_density = self
+            .mempool
+            .fee_density_to_fit_within(block_capacity.saturating_mul(target_blocks));
+
+        let tip_digest = self.chain.light_state().hash();
+        let mut recent_block_digests = self
+            .chain
+            .archival_state()
+            .get_ancestor_block_digests(tip_digest, FEE_ESTIMATION_BLOCK_COUNT - 1)
+            .await;
+        recent_block_digests.push(tip_digest);
+        let mut recent_blocks = vec![];
+        for block_digest in recent_block_digests {
+            let Some(block) = self.chain.archival_state().get_block(block_digest).await? else {
+                bail!("Could not find canonical block {block_digest} in archival state");
+            };
+            recent_blocks.push(BlockFill {
+                transaction_size: block.kernel.body.transaction.get_size(),
+                fee_density: block.kernel.body.transaction.fee_density(),
+            });
+        }
+
+        let fee_density = estimate_fee_density(mempool_fee_density, &recent_blocks, block_capacity);
+
+        let transaction_size = match transaction_size {
+            Some(size) => size,
+            None if self.mempool.is_empty() => DEFAULT_TRANSACTION_SIZE_FOR_FEE_ESTIMATION,
+            None => {
+                let total_size: usize = self
+                    .mempool
+                    .get_sorted_iter()
+                    .filter_map(|(transaction_id, _)| self.mempool.get(transaction_id))
+                    .map(|transaction| transaction.get_size())
+                    .sum();
+                total_size / self.mempool.len()
+            }
+        };
+
+        match fee_for_size(&fee_density, transaction_size) {
+            Some(fee) => Ok(fee),
+            None => bail!("Estimated fee for {transaction_size} bytes exceeds the maximum amount"),
+        }
+    }
+
+    /// Estimate the fee for a transaction that pays the given outputs from this wallet, to
+    /// be mined within `target_blocks` blocks. The transaction is built and proven as it
+    /// would be sent, without changing the wallet, and the fee is estimated for its size.
+    /// As a higher fee can require more inputs, the transaction is built again with the
+    /// estimated fee until the fee covers the size of the transaction that pays it.
+    pub async fn estimate_fee_for_outputs(
+        &self,
+        receiver_data: Vec<UtxoReceiverData>,
+        notification_media: &[UtxoNotificationMedium],
+        coin_selection: CoinSelectionStrategy,
+        target_blocks: usize,
+    ) -> Result<NeptuneCoins> {
+        let output_amount = receiver_data
+            .iter()
+            .map(|rd| rd.utxo.get_native_coin_amount())
+            .sum::<NeptuneCoins>();
+        let tip_digest = self.chain.light_state().hash();
+
+        let mut fee = NeptuneCoins::zero();
+        for _ in 0..MAX_FEE_ESTIMATION_ROUNDS {
+            let inputs = self
+                .wallet_state
+                .allocate_sufficient_input_funds_from_lock(
+                    output_amount + fee,
+                    tip_digest,
+                    coin_selection,
+                )
+                .await?;
+            let (transaction, _expected_change) = self.build_transaction_from_inputs(
+                inputs,
+                receiver_data.clone(),
+                notification_media,
+                fee,
+            )?;
+            let fee_for_size = self
+                .estimate_fee(target_blocks, Some(transaction.get_size()))
+                .await?;
+            if fee_for_size <= fee {
+                return Ok(fee);
+            }
+            fee = fee_for_size;
+        }
+
+        Ok(fee)
+    }
+
+    /// Return the transaction with the given ID from the mempool, or from a block of the
+    /// canonical chain. Blocks are found through the archival state's index of the
+    /// transactions they contain, which includes the transactions merged into a block's.
+    pub async fn get_transaction(&self, txid: Digest) -> Result<Option<Transaction>> {
+        if let Some(transaction) = self.mempool.get(txid) {
+            return Ok(Some(transaction.clone()));
+        }
+
+        let tip_digest = self.chain.light_state().hash();
+        self.chain
+            .archival_state()
+            .get_canonical_transaction(txid, tip_digest)
+            .await
+    }
+
     /// Create a transaction that sends coins to the given
     /// `recipient_utxos` from some selection of owned UTXOs.
     /// A change UTXO will be added if needed; the caller
@@ -388,6 +536,35 @@ impl GlobalState {
         receiver_data: Vec<UtxoReceiverData>,
         fee: NeptuneCoins,
     ) -> Result<Transaction> {
+        let notification_media = vec![UtxoNotificationMedium::OnChain; receiver_data.len()];
+        self.create_transaction_with_notification_media(
+            receiver_data,
+            &notification_media,
+            CoinSelectionStrategy::default(),
+            fee,
+        )
+        .await
+    }
+
+    /// Like [`Self::create_transaction`], but the public announcement of an output is
+    /// only included in the transaction if the output's notification medium is on-chain.
+    /// The receivers of the other outputs must be notified by the caller. The inputs are
+    /// selected with the given coin selection strategy.
+    pub async fn create_transaction_with_notification_media(
+        &mut self,
+        receiver_data: Vec<UtxoReceiverData>,
+        notification_media: &[UtxoNotificationMedium],
+        coin_selection: CoinSelectionStrategy,
+        fee: NeptuneCoins,
+    ) -> Result<Transaction> {
+        if receiver_data.len() != notification_media.len() {
+            bail!(
+                "Got {} outputs but {} notification media.",
+                receiver_data.len(),
+                notification_media.len()
+            );
+        }
+
         // Get the block tip as the transaction is made relative to it
         let bc_tip = self.chain.light_state();
 
@@ -401,8 +578,354 @@ impl GlobalState {
         // todo: accomodate a future change whereby this function also returns the matching spending keys
         let spendable_utxos_and_mps: Vec<(Utxo, LockScript, MsMembershipProof)> = self
             .wallet_state
-            .allocate_sufficient_input_funds_from_lock(total_spend, bc_tip.hash())
+            .allocate_sufficient_input_funds_from_lock(total_spend, bc_tip.hash(), coin_selection)
+            .await?;
+
+        self.create_transaction_from_inputs(
+            spendable_utxos_and_mps,
+            receiver_data,
+            notification_media,
+            fee,
+        )
+    }
+
+    /// Create a transaction that sends tokens of the registered token with the given name
+    /// to the receivers. The tokens are taken from this wallet's token UTXOs, or, if `issue`
+    /// is set, created anew, which requires that this wallet is the token's issuer. The fee
+    /// and any native coins of the outputs are paid from UTXOs without tokens.
+    pub async fn create_token_transaction(
+        &mut self,
+        token_name: &str,
+        receiver_data: Vec<UtxoReceiverData>,
+        issue: bool,
+        fee: NeptuneCoins,
+    ) -> Result<Transaction> {
+        let Some(known_token) = self.wallet_state.find_token(token_name) else {
+            bail!("No token named {token_name} is registered");
+        };
+        let definition = known_token.definition;
+        let type_script_hash = definition.type_script_hash();
+        let tip_digest = self.chain.light_state().hash();
+
+        let mut spendable_utxos_and_mps = match issue {
+            true => vec![],
+            false => {
+                let token_amount = receiver_data
+                    .iter()
+                    .map(|rd| token::token_amount(&rd.utxo, type_script_hash))
+                    .sum();
+                self.wallet_state.allocate_token_inputs(
+                    type_script_hash,
+                    token_amount,
+                    tip_digest,
+                )?
+            }
+        };
+
+        // Pay the fee and the native coins of the outputs
+        let native_spend = receiver_data
+            .iter()
+            .map(|rd| rd.utxo.get_native_coin_amount())
+            .sum::<NeptuneCoins>()
+            + fee;
+        let native_input = spendable_utxos_and_mps
+            .iter()
+            .map(|(utxo, _lock_script, _mp)| utxo.get_native_coin_amount())
+            .sum::<NeptuneCoins>();
+        if let Some(missing_amount) = native_spend.checked_sub(&native_input) {
+            if !missing_amount.is_zero() {
+                let native_inputs = self
+                    .wallet_state
+                    .allocate_sufficient_input_funds_from_lock(
+                        missing_amount,
+                        tip_digest,
+                        CoinSelectionStrategy::default(),
+                    )
+                    .await?;
+                spendable_utxos_and_mps.extend(native_inputs);
+            }
+        }
+
+        // Issuance is authorized by spending a UTXO of the issuer
+        if issue
+            && !spendable_utxos_and_mps
+                .iter()
+                .any(|(utxo, _lock_script, _mp)| {
+                    utxo.lock_script_hash == definition.issuer_lock_script_hash
+                })
+        {
+            let excluded = spendable_utxos_and_mps
+                .iter()
+                .map(|(utxo, _lock_script, _mp)| utxo.clone())
+                .collect_vec();
+            let issuer_input = self.wallet_state.find_issuer_input(
+                definition.issuer_lock_script_hash,
+                tip_digest,
+                &excluded,
+            )?;
+            spendable_utxos_and_mps.push(issuer_input);
+        }
+
+        let notification_media = vec![UtxoNotificationMedium::OnChain; receiver_data.len()];
+        self.create_transaction_from_inputs(
+            spendable_utxos_and_mps,
+            receiver_data,
+            &notification_media,
+            fee,
+        )
+    }
+
+    /// Rebuild the unconfirmed transaction with the given ID, spending the same inputs and
+    /// paying the same receivers, but with a higher fee that is taken from the change. The
+    /// new fee must be high enough for the new transaction to replace the old one in the
+    /// mempool.
+    pub async fn create_fee_bump_transaction(
+        &mut self,
+        txid: Digest,
+        new_fee: NeptuneCoins,
+    ) -> Result<UnconfirmedSend> {
+        let Some(unconfirmed_send) = self.wallet_state.get_unconfirmed_send(txid) else {
+            bail!("Transaction {txid} is not an unconfirmed transaction sent from this wallet");
+        };
+        let Some(minimum_fee) = Mempool::minimum_replacement_fee(&unconfirmed_send.transaction)
+        else {
+            bail!("Transaction {txid} cannot be replaced, as its fee cannot be raised");
+        };
+        if new_fee < minimum_fee {
+            bail!("Fee {new_fee} is too low to replace transaction {txid}; must be at least {minimum_fee}");
+        }
+
+        let tip_digest = self.chain.light_state().hash();
+        let inputs = self
+            .wallet_state
+            .inputs_of_own_transaction(&unconfirmed_send.transaction, tip_digest)?;
+        let input_amount = inputs
+            .iter()
+            .map(|(utxo, _lock_script, _mp)| utxo.get_native_coin_amount())
+            .sum::<NeptuneCoins>();
+        if input_amount < unconfirmed_send.amount() + new_fee {
+            bail!("Fee {new_fee} exceeds what the inputs of transaction {txid} leave as change");
+        }
+
+        let transaction = self.create_transaction_from_inputs(
+            inputs,
+            unconfirmed_send.receiver_data.clone(),
+            &unconfirmed_send.notification_media,
+            new_fee,
+        )?;
+
+        Ok(UnconfirmedSend {
+            transaction,
+            ..unconfirmed_send
+        })
+    }
+
+    /// Create a transaction that pays the recipients from this wallet's UTXOs, but leave out
+    /// the lock script witnesses, such that a wallet secret that is kept offline can sign
+    /// it. Works for watch-only wallets. All outputs are announced on chain.
+    pub async fn create_unsigned_transaction(
+        &mut self,
+        recipients: Vec<(generation_address::ReceivingAddress, NeptuneCoins)>,
+        coin_selection: CoinSelectionStrategy,
+        fee: NeptuneCoins,
+    ) -> Result<UnsignedTransaction> {
+        let mut receiver_data = vec![];
+        for (address, amount) in recipients.iter() {
+            let utxo = Utxo::new(address.lock_script(), amount.to_native_coins());
+            let sender_randomness: Digest = rand::random();
+            let public_announcement =
+                address.generate_public_announcement(&utxo, sender_randomness)?;
+            receiver_data.push(UtxoReceiverData {
+                utxo,
+                sender_randomness,
+                receiver_privacy_digest: address.privacy_digest,
+                public_announcement,
+            });
+        }
+        let notification_media = vec![UtxoNotificationMedium::OnChain; receiver_data.len()];
+
+        let total_spend = recipients
+            .iter()
+            .map(|(_address, amount)| *amount)
+            .sum::<NeptuneCoins>()
+            + fee;
+        let tip_digest = self.chain.light_state().hash();
+        let spendable_utxos_and_mps = self
+            .wallet_state
+            .allocate_sufficient_input_funds_from_lock(total_spend, tip_digest, coin_selection)
             .await?;
+        let mut inputs = vec![];
+        for (utxo, _lock_script, _mp) in spendable_utxos_and_mps.iter() {
+            let Some(generation_key_index) = self.wallet_state.generation_key_index_for_utxo(utxo)
+            else {
+                bail!("Could not find generation key for input UTXO");
+            };
+            let timelock = self
+                .wallet_state
+                .find_timelock(utxo.lock_script_hash)
+                .map(|known_timelock| known_timelock.timelock);
+            inputs.push(UnsignedInput {
+                generation_key_index,
+                timelock,
+            });
+        }
+
+        let (kernel, primitive_witness, expected_change) = self.assemble_transaction_from_inputs(
+            spendable_utxos_and_mps,
+            receiver_data,
+            &notification_media,
+            fee,
+        )?;
+        self.expect_change(expected_change);
+
+        Ok(UnsignedTransaction::new(
+            self.cli().network,
+            kernel,
+            primitive_witness,
+            inputs,
+            recipients,
+        ))
+    }
+
+    /// Check that a transaction that was signed offline spends UTXOs that are unspent at
+    /// the tip, and that it is valid. Returns the transaction for broadcasting.
+    pub fn verify_signed_transaction(
+        &self,
+        signed_transaction: SignedTransaction,
+    ) -> Result<Transaction> {
+        let network = self.cli().network;
+        if signed_transaction.network != network {
+            bail!(
+                "Transaction is for network {}, but this node runs on {network}",
+                signed_transaction.network
+            );
+        }
+        let transaction = signed_transaction.transaction;
+        if transaction.kernel.mutator_set_hash
+            != self
+                .chain
+                .light_state()
+                .body()
+                .mutator_set_accumulator
+                .hash()
+        {
+            bail!("Transaction was created for another tip; create and sign it again");
+        }
+        if !transaction.is_valid() {
+            bail!("Transaction is invalid");
+        }
+
+        Ok(transaction)
+    }
+
+    /// Create a transaction that spends exactly the given inputs, which must have membership
+    /// proofs relative to the block tip. A change UTXO is added if the inputs exceed the
+    /// outputs and the fee, and the wallet expects it.
+    fn create_transaction_from_inputs(
+        &mut self,
+        spendable_utxos_and_mps: Vec<(Utxo, LockScript, MsMembershipProof)>,
+        receiver_data: Vec<UtxoReceiverData>,
+        notification_media: &[UtxoNotificationMedium],
+        fee: NeptuneCoins,
+    ) -> Result<Transaction> {
+        let (transaction, expected_change) = self.build_transaction_from_inputs(
+            spendable_utxos_and_mps,
+            receiver_data,
+            notification_media,
+            fee,
+        )?;
+        self.expect_change(expected_change);
+
+        Ok(transaction)
+    }
+
+    /// Add the change UTXO of a transaction made by this wallet to the expected UTXOs.
+    fn expect_change(&mut self, expected_change: Option<ExpectedUtxo>) {
+        if let Some(change) = expected_change {
+            let _change_addition_record = self
+                .wallet_state
+                .expected_utxos
+                .add_expected_utxo(
+                    change.utxo,
+                    change.sender_randomness,
+                    change.receiver_preimage,
+                    change.received_from,
+                )
+                .expect("Adding change UTXO to UTXO notification pool must succeed");
+        }
+    }
+
+    /// Build and prove a transaction that spends exactly the given inputs, without changing
+    /// the wallet. Returns the transaction and its change UTXO, if any.
+    fn build_transaction_from_inputs(
+        &self,
+        spendable_utxos_and_mps: Vec<(Utxo, LockScript, MsMembershipProof)>,
+        receiver_data: Vec<UtxoReceiverData>,
+        notification_media: &[UtxoNotificationMedium],
+        fee: NeptuneCoins,
+    ) -> Result<(Transaction, Option<ExpectedUtxo>)> {
+        if self.wallet_state.is_watch_only() {
+            bail!("A watch-only wallet cannot create transactions");
+        }
+
+        let (kernel, mut primitive_witness, expected_change) = self
+            .assemble_transaction_from_inputs(
+                spendable_utxos_and_mps,
+                receiver_data,
+                notification_media,
+                fee,
+            )?;
+
+        // The spending key can be different for each input UTXO, as the UTXOs can be
+        // locked to different generation addresses of this wallet.
+        // When reading a digest from secret and standard-in, the digest's
+        // zeroth element must be on top of the stack. So the secret-in
+        // is here the spending key reversed.
+        let mut lock_script_witnesses = vec![];
+        for utxo in primitive_witness.input_utxos.iter() {
+            let spending_key = match self.wallet_state.find_spending_key_for_utxo(utxo) {
+                Some(spending_key) => spending_key,
+                None => bail!("Could not find spending key for input UTXO"),
+            };
+            let mut secret_input = spending_key.unlock_key.encode();
+            secret_input.reverse();
+
+            // Time-locked lock scripts additionally read the timestamp or the minimum block
+            // height of the kernel.
+            if let Some(known_timelock) = self.wallet_state.find_timelock(utxo.lock_script_hash) {
+                secret_input.extend(known_timelock.timelock.witness(&kernel));
+            }
+            lock_script_witnesses.push(secret_input);
+        }
+        primitive_witness.lock_script_witnesses = lock_script_witnesses;
+
+        Ok((
+            self.prove_transaction(primitive_witness, kernel),
+            expected_change,
+        ))
+    }
+
+    /// Build the kernel and the primitive witness of a transaction that spends exactly the
+    /// given inputs, leaving out the lock script witnesses. A change UTXO is added if the
+    /// inputs exceed the outputs and the fee. The change UTXO is returned for the caller
+    /// to expect, unless the wallet is watch-only and announces it on chain instead.
+    fn assemble_transaction_from_inputs(
+        &self,
+        spendable_utxos_and_mps: Vec<(Utxo, LockScript, MsMembershipProof)>,
+        receiver_data: Vec<UtxoReceiverData>,
+        notification_media: &[UtxoNotificationMedium],
+        fee: NeptuneCoins,
+    ) -> Result<(
+        TransactionKernel,
+        TransactionPrimitiveWitness,
+        Option<ExpectedUtxo>,
+    )> {
+        let bc_tip = self.chain.light_state();
+        let total_spend: NeptuneCoins = receiver_data
+            .iter()
+            .map(|x| x.utxo.get_native_coin_amount())
+            .sum::<NeptuneCoins>()
+            + fee;
 
         // Create all removal records. These must be relative to the block tip.
         let msa_tip = &bc_tip.kernel.body.mutator_set_accumulator;
@@ -417,6 +940,8 @@ impl GlobalState {
 
         let mut transaction_outputs: Vec<AdditionRecord> = vec![];
         let mut output_utxos: Vec<Utxo> = vec![];
+        let mut output_sender_randomnesses: Vec<Digest> = vec![];
+        let mut output_receiver_digests: Vec<Digest> = vec![];
         for rd in receiver_data.iter() {
             let addition_record = commit(
                 Hash::hash(&rd.utxo),
@@ -425,6 +950,8 @@ impl GlobalState {
             );
             transaction_outputs.push(addition_record);
             output_utxos.push(rd.utxo.to_owned());
+            output_sender_randomnesses.push(rd.sender_randomness);
+            output_receiver_digests.push(rd.receiver_privacy_digest);
         }
 
         // Send remaining amount back to self
@@ -435,24 +962,50 @@ impl GlobalState {
             }
         };
 
+        // Tokens that the inputs hold in excess of the outputs go back to self as well
+        let token_type_script_hashes = spendable_utxos_and_mps
+            .iter()
+            .flat_map(|(utxo, _lock_script, _mp)| utxo.coins.iter())
+            .map(|coin| coin.type_script_hash)
+            .filter(|type_script_hash| *type_script_hash != NATIVE_COIN_TYPESCRIPT_DIGEST)
+            .unique()
+            .collect_vec();
+        let mut token_change_coins = vec![];
+        for type_script_hash in token_type_script_hashes {
+            let input_token_amount: u64 = spendable_utxos_and_mps
+                .iter()
+                .map(|(utxo, _lock_script, _mp)| token::token_amount(utxo, type_script_hash))
+                .sum();
+            let output_token_amount: u64 = output_utxos
+                .iter()
+                .map(|utxo| token::token_amount(utxo, type_script_hash))
+                .sum();
+            if input_token_amount > output_token_amount {
+                token_change_coins.push(token::token_coin(
+                    type_script_hash,
+                    input_token_amount - output_token_amount,
+                ));
+            }
+        }
+
         // add change UTXO if necessary
-        if input_amount > total_spend {
-            let own_spending_key_for_change = self
-                .wallet_state
-                .wallet_secret
-                .nth_generation_spending_key(0);
-            let own_receiving_address = own_spending_key_for_change.to_address();
+        let mut change_public_announcement = None;
+        let mut expected_change = None;
+        if input_amount > total_spend || !token_change_coins.is_empty() {
+            let own_view_key_for_change = self.wallet_state.known_view_keys()[0];
+            let own_receiving_address = own_view_key_for_change.receiving_address;
             let lock_script = own_receiving_address.lock_script();
             let lock_script_hash = lock_script.hash();
             let change_utxo = Utxo {
-                coins: change_amount.to_native_coins(),
+                coins: [change_amount.to_native_coins(), token_change_coins].concat(),
                 lock_script_hash,
             };
             let receiver_digest = own_receiving_address.privacy_digest;
-            let change_sender_randomness = self
-                .wallet_state
-                .wallet_secret
-                .generate_sender_randomness(bc_tip.kernel.header.height, receiver_digest);
+            let change_sender_randomness = match &self.wallet_state.wallet_secret {
+                Some(wallet_secret) => wallet_secret
+                    .generate_sender_randomness(bc_tip.kernel.header.height, receiver_digest),
+                None => rand::random(),
+            };
             let change_addition_record = commit(
                 Hash::hash(&change_utxo),
                 change_sender_randomness,
@@ -460,24 +1013,33 @@ impl GlobalState {
             );
             transaction_outputs.push(change_addition_record);
             output_utxos.push(change_utxo.clone());
-
-            // Add change UTXO to pool of expected incoming UTXOs
-            let receiver_preimage = own_spending_key_for_change.privacy_preimage;
-            let _change_addition_record = self
-                .wallet_state
-                .expected_utxos
-                .add_expected_utxo(
+            output_sender_randomnesses.push(change_sender_randomness);
+            output_receiver_digests.push(receiver_digest);
+
+            if self.wallet_state.is_watch_only() {
+                // A watch-only wallet cannot rederive the sender randomness of its change,
+                // so the change is announced on chain, where the wallet always finds it.
+                change_public_announcement = Some(
+                    own_receiving_address
+                        .generate_public_announcement(&change_utxo, change_sender_randomness)?,
+                );
+            } else {
+                // The change UTXO is expected once the transaction is made
+                expected_change = Some(ExpectedUtxo::new(
                     change_utxo,
                     change_sender_randomness,
-                    receiver_preimage,
+                    own_view_key_for_change.privacy_preimage,
                     UtxoNotifier::Myself,
-                )
-                .expect("Adding change UTXO to UTXO notification pool must succeed");
+                ));
+            }
         }
 
         let public_announcements = receiver_data
             .iter()
-            .map(|x| x.public_announcement.clone())
+            .zip(notification_media.iter())
+            .filter(|(_rd, medium)| **medium == UtxoNotificationMedium::OnChain)
+            .map(|(rd, _medium)| rd.public_announcement.clone())
+            .chain(change_public_announcement)
             .collect_vec();
         let timestamp = SystemTime::now()
             .duration_since(UNIX_EPOCH)
@@ -492,25 +1054,32 @@ impl GlobalState {
             timestamp: BFieldElement::new(timestamp.try_into().unwrap()),
             coinbase: None,
             mutator_set_hash: msa_tip.hash(),
+            minimum_block_height: bc_tip.kernel.header.height.next().into(),
         };
 
-        // TODO: The spending key can be different for each UTXO, and therefore must be supplied by `spendable_utxos_and_mps`.
-        let spending_key = self
-            .wallet_state
-            .wallet_secret
-            .nth_generation_spending_key(0);
         let input_utxos = spendable_utxos_and_mps
             .iter()
             .map(|(utxo, _lock_script, _mp)| utxo)
             .cloned()
             .collect_vec();
 
-        // Right now we only have one type script, namely that for the native Neptune coin.
-        // Down the line we can and will have other type scripts and these will have to be
-        // stored and managed in some non-hardcoded database. See issue #92 [1].
-        //
-        // [1]: https://github.com/Neptune-Crypto/neptune-core/issues/92
-        let type_scripts = vec![TypeScript::native_coin()];
+        // Besides the native coin's, the type scripts are those of registered tokens
+        let mut type_scripts = vec![TypeScript::native_coin()];
+        for type_script_hash in input_utxos
+            .iter()
+            .chain(output_utxos.iter())
+            .flat_map(|utxo| utxo.coins.iter().map(|coin| coin.type_script_hash))
+            .unique()
+            .filter(|type_script_hash| *type_script_hash != NATIVE_COIN_TYPESCRIPT_DIGEST)
+        {
+            let Some(type_script) = self.wallet_state.find_type_script(type_script_hash) else {
+                bail!(
+                    "Transaction holds a token with unknown type script {}; register the token first",
+                    type_script_hash.emojihash()
+                );
+            };
+            type_scripts.push(type_script);
+        }
         let input_lock_scripts = spendable_utxos_and_mps
             .iter()
             .map(|(_utxo, lock_script, _mp)| lock_script.to_owned())
@@ -543,44 +1112,29 @@ impl GlobalState {
             .mutator_set_accumulator
             .clone();
 
-        // When reading a digest from secret and standard-in, the digest's
-        // zeroth element must be on top of the stack. So the secret-in
-        // is here the spending key reversed.
-        let mut secret_input = spending_key.unlock_key.encode();
-        secret_input.reverse();
-        let mut primitive_witness = TransactionPrimitiveWitness {
+        let primitive_witness = TransactionPrimitiveWitness {
             input_utxos,
             input_lock_scripts,
             type_scripts,
-            lock_script_witnesses: vec![secret_input; spendable_utxos_and_mps.len()],
+            lock_script_witnesses: vec![],
             input_membership_proofs,
             output_utxos: output_utxos.clone(),
+            output_sender_randomnesses,
+            output_receiver_digests,
             public_announcements,
             mutator_set_accumulator,
         };
 
-        // Convert the secret-supported claim to a proof, several proofs, or
-        // at the very least hide sensitive data.
-        let mut transaction_validity_logic =
-            TransactionValidationLogic::new_from_primitive_witness(&primitive_witness, &kernel);
-
-        if self.cli().privacy {
-            transaction_validity_logic
-                .prove()
-                .expect("Proof generation must work when creating a new transaction");
-        } else {
-            transaction_validity_logic.lock_scripts_halt.prove().expect(
-                "Proof generation must work when unlocking owned UTXOs for a new transaction.",
-            );
-        }
-
-        // Remove lock script witness from primitive witness to not leak spending keys
-        primitive_witness.lock_script_witnesses = vec![];
+        Ok((kernel, primitive_witness, expected_change))
+    }
 
-        Ok(Transaction {
-            kernel,
-            witness: Witness::ValidationLogic(transaction_validity_logic),
-        })
+    /// Turn the primitive witness of a transaction into the witness that is broadcast.
+    fn prove_transaction(
+        &self,
+        primitive_witness: TransactionPrimitiveWitness,
+        kernel: TransactionKernel,
+    ) -> Transaction {
+        Transaction::prove_from_primitive_witness(&primitive_witness, kernel, self.cli().privacy)
     }
 
     pub async fn get_own_handshakedata(&self) -> HandshakeData {
@@ -597,20 +1151,22 @@ impl GlobalState {
     }
 
     /// In case the wallet database is corrupted or deleted, this method will restore
-    /// monitored UTXO data structures from recovery data. This method should only be
-    /// called on startup, not while the program is running, since it will only restore
-    /// a wallet state, if the monitored UTXOs have been deleted. Not merely if they
-    /// are not synced with a valid mutator set membership proof. And this corruption
-    /// can only happen if the wallet database is deleted or corrupted.
-    pub(crate) async fn restore_monitored_utxos_from_recovery_data(&mut self) -> Result<()> {
+    /// monitored UTXO data structures from recovery data. It will only restore a
+    /// monitored UTXO if it has been deleted. Not merely if it is not synced with a valid
+    /// mutator set membership proof. And this corruption can only happen if the wallet
+    /// database is deleted or corrupted. Restored UTXOs are marked as confirmed in the
+    /// block that added them to the mutator set, and as spent if the canonical chain
+    /// spends them.
+    ///
+    /// Returns the number of monitored UTXOs that were restored.
+    pub(crate) async fn restore_monitored_utxos_from_recovery_data(&mut self) -> Result<usize> {
         let tip_hash = self.chain.light_state().hash();
+        let tip_height = self.chain.light_state().kernel.header.height;
         let ams_ref = &self.chain.archival_state().archival_mutator_set;
 
-        assert_eq!(
-            tip_hash,
-            ams_ref.get_sync_label(),
-            "Archival mutator set must be synced to tip for successful MUTXO recovery"
-        );
+        if tip_hash != ams_ref.get_sync_label() {
+            bail!("Archival mutator set must be synced to tip for successful MUTXO recovery");
+        }
 
         // Fetch all incoming UTXOs from recovery data
         let incoming_utxos = self.wallet_state.read_utxo_ms_recovery_data().await?;
@@ -647,7 +1203,7 @@ impl GlobalState {
             info!(
                 "No missing monitored UTXOs found in wallet database. Wallet database looks good."
             );
-            return Ok(());
+            return Ok(0);
         }
 
         // For all recovery data where we did not find a matching monitored UTXO,
@@ -658,7 +1214,7 @@ impl GlobalState {
             recovery_data_for_missing_mutxos.len()
         );
         let current_aocl_leaf_count = ams_ref.ams().kernel.aocl.count_leaves();
-        let mut restored_mutxos = 0;
+        let mut restored_mutxos = vec![];
         for incoming_utxo in recovery_data_for_missing_mutxos {
             // If the referenced UTXO is in the future from our tip, do not attempt to recover it. Instead: warn the user of this.
             if current_aocl_leaf_count <= incoming_utxo.aocl_index {
@@ -666,50 +1222,648 @@ impl GlobalState {
                 continue;
             }
             let ms_item = Hash::hash(&incoming_utxo.utxo);
-            let restored_msmp_res = ams_ref.ams().restore_membership_proof(
+            let restored_msmp = match ams_ref.ams().restore_membership_proof(
                 ms_item,
                 incoming_utxo.sender_randomness,
                 incoming_utxo.receiver_preimage,
                 incoming_utxo.aocl_index,
-            );
-            let restored_msmp = match restored_msmp_res {
-                Ok(msmp) => {
-                    // Verify that the restored MSMP is valid
-                    if !ams_ref.ams().verify(ms_item, &msmp) {
-                        warn!("Restored MSMP is invalid. Skipping restoration of UTXO with AOCL index {}. Maybe this UTXO is on an abandoned chain?", incoming_utxo.aocl_index);
-                        continue;
-                    }
-
-                    msmp
-                }
+            ) {
+                Ok(msmp) => msmp,
                 Err(err) => bail!("Could not restore MS membership proof. Got: {err}"),
             };
 
+            // The UTXO must have been added by the block that added its AOCL leaf on the
+            // canonical chain. Otherwise it was received on an abandoned chain.
+            let confirming_block = match self
+                .chain
+                .archival_state()
+                .find_canonical_block_with_aocl_index(incoming_utxo.aocl_index, tip_hash)
+                .await?
+            {
+                Some(block) => block,
+                None => bail!(
+                    "Could not find the block that added AOCL index {}",
+                    incoming_utxo.aocl_index
+                ),
+            };
+            let addition_record = commit(
+                ms_item,
+                incoming_utxo.sender_randomness,
+                incoming_utxo.receiver_preimage.hash::<Hash>(),
+            );
+            if !confirming_block
+                .kernel
+                .body
+                .transaction
+                .kernel
+                .outputs
+                .contains(&addition_record)
+            {
+                warn!("Skipping restoration of UTXO with AOCL index {} as it is not on the canonical chain. Maybe this UTXO is on an abandoned chain?", incoming_utxo.aocl_index);
+                continue;
+            }
+
             let mut restored_mutxo =
                 MonitoredUtxo::new(incoming_utxo.utxo, self.wallet_state.number_of_mps_per_utxo);
+            restored_mutxo.confirmed_in_block = Some((
+                confirming_block.hash(),
+                Duration::from_millis(confirming_block.kernel.header.timestamp.value()),
+                confirming_block.kernel.header.height,
+            ));
+            let is_spent = !ams_ref.ams().verify(ms_item, &restored_msmp);
             restored_mutxo.add_membership_proof_for_tip(tip_hash, restored_msmp);
-
-            self.wallet_state
-                .wallet_db
-                .monitored_utxos_mut()
-                .push(restored_mutxo);
-            restored_mutxos += 1;
+            restored_mutxos.push((restored_mutxo, is_spent));
         }
 
-        self.wallet_state.wallet_db.persist();
-        info!("Successfully restored {restored_mutxos} monitored UTXOs to wallet database");
-
-        Ok(())
-    }
-
-    ///  Locking:
-    ///   * acquires `monitored_utxos_lock` for write
-    pub async fn resync_membership_proofs_from_stored_blocks(
-        &mut self,
-        tip_hash: Digest,
-    ) -> Result<()> {
-        // loop over all monitored utxos
-        let monitored_utxos = self.wallet_state.wallet_db.monitored_utxos_mut();
+        // A restored membership proof that is invalid for a UTXO on the canonical chain
+        // means that the UTXO was spent. Find the spending blocks in one pass over the
+        // blocks that follow the earliest of these UTXOs.
+        let spent_mutxo_confirmation_heights = restored_mutxos
+            .iter()
+            .filter(|(_mutxo, is_spent)| *is_spent)
+            .filter_map(|(mutxo, _is_spent)| mutxo.confirmed_in_block)
+            .map(|(_block_hash, _timestamp, block_height)| block_height);
+        if let Some(min_confirmation_height) = spent_mutxo_confirmation_heights.min() {
+            let mut block_height = min_confirmation_height.next();
+            while block_height <= tip_height {
+                let block = match self
+                    .chain
+                    .archival_state()
+                    .get_canonical_block_at_height(block_height, tip_hash)
+                    .await?
+                {
+                    Some(block) => block,
+                    None => bail!("Canonical chain is missing a block at height {block_height}"),
+                };
+                for (mutxo, is_spent) in restored_mutxos.iter_mut() {
+                    if !*is_spent || mutxo.spent_in_block.is_some() {
+                        continue;
+                    }
+                    let (_block_hash, msmp) = mutxo
+                        .get_latest_membership_proof_entry()
+                        .expect("Restored monitored UTXO must have a membership proof");
+                    let absolute_indices = msmp.compute_indices(Hash::hash(&mutxo.utxo));
+                    if block
+                        .kernel
+                        .body
+                        .transaction
+                        .kernel
+                        .inputs
+                        .iter()
+                        .any(|removal_record| removal_record.absolute_indices == absolute_indices)
+                    {
+                        mutxo.spent_in_block = Some((
+                            block.hash(),
+                            Duration::from_millis(block.kernel.header.timestamp.value()),
+                            block.kernel.header.height,
+                        ));
+                    }
+                }
+                block_height = block_height.next();
+            }
+        }
+
+        let restored_mutxo_count = restored_mutxos.len();
+        for (restored_mutxo, _is_spent) in restored_mutxos {
+            self.wallet_state
+                .wallet_db
+                .monitored_utxos_mut()
+                .push(restored_mutxo);
+        }
+
+        self.wallet_state.wallet_db.persist();
+        info!("Successfully restored {restored_mutxo_count} monitored UTXOs to wallet database");
+
+        Ok(restored_mutxo_count)
+    }
+
+    /// Rebuild the monitored UTXOs of the wallet by walking the canonical chain from the
+    /// given height to the tip. UTXOs that belong to this wallet are added with a membership
+    /// proof restored from the archival mutator set, and marked as spent if a later block
+    /// spends them. UTXOs that are already monitored are left untouched. This is needed when
+    /// a wallet is restored from its seed phrase on a node that has already synced.
+    ///
+    /// Returns the number of monitored UTXOs that were added to the wallet database.
+    pub async fn rescan_from_height(&mut self, from_height: BlockHeight) -> Result<usize> {
+        let tip_hash = self.chain.light_state().hash();
+        let tip_height = self.chain.light_state().kernel.header.height;
+        if from_height > tip_height {
+            bail!("Cannot rescan from height {from_height}, as the tip is at height {tip_height}");
+        }
+        let ams_sync_label = self
+            .chain
+            .archival_state()
+            .archival_mutator_set
+            .get_sync_label();
+        if tip_hash != ams_sync_label {
+            bail!("Archival mutator set must be synced to tip before the wallet can be rescanned");
+        }
+
+        // Collect the canonical blocks from the requested height to the tip, oldest first
+        let number_of_ancestors = (tip_height - from_height) as usize;
+        let mut block_digests = self
+            .chain
+            .archival_state()
+            .get_ancestor_block_digests(tip_hash, number_of_ancestors)
+            .await;
+        block_digests.reverse();
+        block_digests.push(tip_hash);
+        info!(
+            "Rescanning {} blocks from height {from_height} for own UTXOs",
+            block_digests.len()
+        );
+
+        // Find the outputs that belong to us, and remember all spent absolute index sets,
+        // such that spent UTXOs can be recognized. Finding a UTXO on a generation address
+        // can move the scanning window along, so the blocks are scanned again until no new
+        // generation keys are derived, as earlier blocks may pay the new keys.
+        let (recovered_utxos, spent_index_sets) = loop {
+            let known_key_count = self.wallet_state.known_view_keys().len();
+            let mut recovered_utxos = vec![];
+            let mut spent_index_sets = vec![];
+            for block_digest in block_digests.iter().copied() {
+                let block = match self.chain.archival_state().get_block(block_digest).await? {
+                    Some(block) => block,
+                    None => {
+                        bail!("Could not find canonical block {block_digest} in archival state")
+                    }
+                };
+                let block_info = (
+                    block.hash(),
+                    Duration::from_millis(block.kernel.header.timestamp.value()),
+                    block.kernel.header.height,
+                );
+                for removal_record in block.kernel.body.transaction.kernel.inputs.iter() {
+                    spent_index_sets.push((removal_record.absolute_indices.clone(), block_info));
+                }
+                for recovery_data in self.wallet_state.scan_block_for_own_utxos(&block) {
+                    recovered_utxos.push((recovery_data, block_info));
+                }
+            }
+
+            if self.wallet_state.known_view_keys().len() == known_key_count {
+                break (recovered_utxos, spent_index_sets);
+            }
+            debug!("Scanning window moved during rescan; scanning the blocks again");
+        };
+
+        // Skip UTXOs that are already monitored, identified by their UTXO digest and their
+        // AOCL index.
+        let monitored_utxo_keys = self
+            .wallet_state
+            .wallet_db
+            .monitored_utxos()
+            .get_all()
+            .into_iter()
+            .filter_map(|mutxo| {
+                mutxo
+                    .get_latest_membership_proof_entry()
+                    .map(|(_block_hash, msmp)| {
+                        (Hash::hash(&mutxo.utxo), msmp.auth_path_aocl.leaf_index)
+                    })
+            })
+            .collect::<HashSet<_>>();
+
+        let ams_ref = &self.chain.archival_state().archival_mutator_set;
+        let mut restored_mutxos = vec![];
+        for (recovery_data, confirmed_in_block) in recovered_utxos {
+            let ms_item = Hash::hash(&recovery_data.utxo);
+            if monitored_utxo_keys.contains(&(ms_item, recovery_data.aocl_index)) {
+                continue;
+            }
+
+            let restored_msmp = match ams_ref.ams().restore_membership_proof(
+                ms_item,
+                recovery_data.sender_randomness,
+                recovery_data.receiver_preimage,
+                recovery_data.aocl_index,
+            ) {
+                Ok(msmp) => msmp,
+                Err(err) => bail!("Could not restore MS membership proof. Got: {err}"),
+            };
+
+            let absolute_indices = restored_msmp.compute_indices(ms_item);
+            let spent_in_block = spent_index_sets
+                .iter()
+                .find(|(spent_indices, _block_info)| *spent_indices == absolute_indices)
+                .map(|(_spent_indices, block_info)| *block_info);
+            if spent_in_block.is_none() && !ams_ref.ams().verify(ms_item, &restored_msmp) {
+                warn!(
+                    "Restored MSMP is invalid. Skipping restoration of UTXO with AOCL index {}.",
+                    recovery_data.aocl_index
+                );
+                continue;
+            }
+
+            let mut restored_mutxo = MonitoredUtxo::new(
+                recovery_data.utxo.clone(),
+                self.wallet_state.number_of_mps_per_utxo,
+            );
+            restored_mutxo.add_membership_proof_for_tip(tip_hash, restored_msmp);
+            restored_mutxo.confirmed_in_block = Some(confirmed_in_block);
+            restored_mutxo.spent_in_block = spent_in_block;
+            restored_mutxos.push((restored_mutxo, recovery_data));
+        }
+
+        // Add the restored UTXOs to the wallet database and to the file with incoming
+        // randomness, unless that file already knows about them.
+        let stored_recovery_data = self
+            .wallet_state
+            .read_utxo_ms_recovery_data()
+            .await
+            .unwrap_or_default()
+            .into_iter()
+            .map(|recovery_data| (Hash::hash(&recovery_data.utxo), recovery_data.aocl_index))
+            .collect::<HashSet<_>>();
+        let restored_mutxo_count = restored_mutxos.len();
+        for (restored_mutxo, recovery_data) in restored_mutxos {
+            let recovery_key = (Hash::hash(&recovery_data.utxo), recovery_data.aocl_index);
+            if !stored_recovery_data.contains(&recovery_key) {
+                self.wallet_state
+                    .store_utxo_ms_recovery_data(recovery_data)
+                    .await?;
+            }
+            self.wallet_state
+                .wallet_db
+                .monitored_utxos_mut()
+                .push(restored_mutxo);
+        }
+
+        self.wallet_state.wallet_db.persist();
+        info!("Rescan added {restored_mutxo_count} monitored UTXOs to wallet database");
+
+        Ok(restored_mutxo_count)
+    }
+
+    /// Claim a UTXO that was sent to this wallet without an on-chain notification. The
+    /// notification must be for this network, commit to the UTXO, and pay an address of
+    /// this wallet. If it names the block that confirmed the UTXO, that block must be
+    /// canonical and contain the UTXO, and the wallet is rescanned from that block.
+    /// Otherwise the UTXO is expected, and picked up from the block that confirms it.
+    pub async fn claim_utxo(
+        &mut self,
+        notification: UtxoTransferNotification,
+    ) -> Result<UtxoClaimStatus> {
+        let network = self.cli().network;
+        if notification.network != network {
+            bail!(
+                "Notification is for network {}, but this node runs on {network}",
+                notification.network
+            );
+        }
+        if !notification.has_valid_commitment() {
+            bail!("Addition record of notification does not commit to its UTXO");
+        }
+        let Some(view_key) = self
+            .wallet_state
+            .find_view_key_for_privacy_digest(notification.receiver_privacy_digest)
+        else {
+            bail!("Notification is not for an address of this wallet");
+        };
+        if view_key.receiving_address.lock_script().hash() != notification.utxo.lock_script_hash {
+            bail!("UTXO of notification is not locked to an address of this wallet");
+        }
+        let receiver_preimage = view_key.privacy_preimage;
+
+        let confirming_block = match notification.block_hint {
+            Some(block_digest) => {
+                let Some(block) = self.chain.archival_state().get_block(block_digest).await? else {
+                    bail!("Block {block_digest} of notification is unknown");
+                };
+                let tip_digest = self.chain.light_state().hash();
+                if !self
+                    .chain
+                    .archival_state()
+                    .block_belongs_to_canonical_chain(block_digest, tip_digest)
+                    .await
+                {
+                    bail!("Block {block_digest} of notification is not canonical");
+                }
+                if !block
+                    .kernel
+                    .body
+                    .transaction
+                    .kernel
+                    .outputs
+                    .contains(&notification.addition_record)
+                {
+                    bail!("Block {block_digest} of notification does not contain its UTXO");
+                }
+                Some(block)
+            }
+            None => None,
+        };
+
+        let Some(block) = confirming_block else {
+            self.wallet_state
+                .add_pending_utxo_claim(notification, receiver_preimage)?;
+            return Ok(UtxoClaimStatus::Pending);
+        };
+        self.wallet_state
+            .forget_pending_utxo_claim(notification.addition_record);
+        self.wallet_state.expected_utxos.add_expected_utxo(
+            notification.utxo,
+            notification.sender_randomness,
+            receiver_preimage,
+            UtxoNotifier::Cli,
+        )?;
+        let block_height = block.kernel.header.height;
+        self.rescan_from_height(block_height).await?;
+
+        Ok(UtxoClaimStatus::Confirmed(block.hash(), block_height))
+    }
+
+    /// Start tracking a multisig address that this wallet participates in, and find the
+    /// UTXOs that the canonical chain sent to it. Returns the number of unspent UTXOs of
+    /// the address.
+    pub async fn import_multisig_setup(&mut self, setup: MultisigSetup) -> Result<usize> {
+        self.wallet_state.add_multisig_setup(setup.clone())?;
+
+        let tip_hash = self.chain.light_state().hash();
+        let tip_height = self.chain.light_state().kernel.header.height;
+        let number_of_ancestors = (tip_height - BlockHeight::genesis()) as usize;
+        let mut block_digests = self
+            .chain
+            .archival_state()
+            .get_ancestor_block_digests(tip_hash, number_of_ancestors)
+            .await;
+        block_digests.reverse();
+        block_digests.push(tip_hash);
+        for block_digest in block_digests {
+            let block = match self.chain.archival_state().get_block(block_digest).await? {
+                Some(block) => block,
+                None => bail!("Could not find canonical block {block_digest} in archival state"),
+            };
+            self.wallet_state
+                .scan_block_for_multisig_utxos(&block, std::slice::from_ref(&setup));
+        }
+
+        Ok(self
+            .wallet_state
+            .unspent_multisig_utxos(&setup.address)
+            .len())
+    }
+
+    /// Create a transaction that spends the UTXOs of a multisig address, for its
+    /// participants to sign. All unspent UTXOs of the address are spent, since signing
+    /// reveals the unlock keys of the signers to whoever combines the signatures. If the
+    /// inputs exceed the outputs and the fee, the difference goes to the change address,
+    /// which should be a multisig address with fresh keys.
+    pub fn create_multisig_transaction(
+        &self,
+        address: &MultisigAddress,
+        receiver_data: Vec<UtxoReceiverData>,
+        change_address: Option<MultisigAddress>,
+        fee: NeptuneCoins,
+    ) -> Result<PartiallySignedTransaction> {
+        let Some(setup) = self
+            .wallet_state
+            .multisig_setups()
+            .into_iter()
+            .find(|setup| setup.address == *address)
+        else {
+            bail!("Multisig address was not imported into this wallet");
+        };
+        let tip_hash = self.chain.light_state().hash();
+        let ams_ref = &self.chain.archival_state().archival_mutator_set;
+        if ams_ref.get_sync_label() != tip_hash {
+            bail!("Archival mutator set must be synced to tip to spend multisig UTXOs");
+        }
+        let multisig_utxos = self.wallet_state.unspent_multisig_utxos(address);
+        if multisig_utxos.is_empty() {
+            bail!("Multisig address has no unspent UTXOs");
+        }
+
+        // Create all removal records. These must be relative to the block tip.
+        let msa_tip = self
+            .chain
+            .light_state()
+            .body()
+            .mutator_set_accumulator
+            .clone();
+        let receiver_preimage = setup.view_key().privacy_preimage;
+        let mut inputs = vec![];
+        let mut input_utxos = vec![];
+        let mut input_membership_proofs = vec![];
+        let mut input_amount = NeptuneCoins::zero();
+        for multisig_utxo in multisig_utxos {
+            let item = Hash::hash(&multisig_utxo.utxo);
+            let membership_proof = match ams_ref.ams().restore_membership_proof(
+                item,
+                multisig_utxo.sender_randomness,
+                receiver_preimage,
+                multisig_utxo.aocl_index,
+            ) {
+                Ok(msmp) => msmp,
+                Err(err) => bail!("Could not restore MS membership proof. Got: {err}"),
+            };
+            if !msa_tip.verify(item, &membership_proof) {
+                bail!(
+                    "Multisig UTXO with AOCL index {} is not unspent at the tip",
+                    multisig_utxo.aocl_index
+                );
+            }
+            inputs.push(msa_tip.kernel.drop(item, &membership_proof));
+            input_amount = input_amount + multisig_utxo.utxo.get_native_coin_amount();
+            input_utxos.push(multisig_utxo.utxo);
+            input_membership_proofs.push(membership_proof);
+        }
+
+        let mut receiver_data = receiver_data;
+        let total_spend = receiver_data
+            .iter()
+            .map(|rd| rd.utxo.get_native_coin_amount())
+            .sum::<NeptuneCoins>()
+            + fee;
+        let Some(change_amount) = input_amount.checked_sub(&total_spend) else {
+            bail!("Multisig address holds {input_amount}, which does not cover {total_spend}");
+        };
+        if change_amount > NeptuneCoins::zero() {
+            let Some(change_address) = change_address else {
+                bail!("The multisig UTXOs exceed the outputs and the fee by {change_amount}, but no change address was given");
+            };
+            let utxo = Utxo::new(
+                change_address.lock_script(),
+                change_amount.to_native_coins(),
+            );
+            let sender_randomness: Digest = rand::random();
+            let public_announcement = change_address
+                .receiving_address
+                .generate_public_announcement(&utxo, sender_randomness)?;
+            receiver_data.push(UtxoReceiverData {
+                utxo,
+                sender_randomness,
+                receiver_privacy_digest: change_address.receiving_address.privacy_digest,
+                public_announcement,
+            });
+        }
+
+        let outputs = receiver_data
+            .iter()
+            .map(|rd| {
+                commit(
+                    Hash::hash(&rd.utxo),
+                    rd.sender_randomness,
+                    rd.receiver_privacy_digest,
+                )
+            })
+            .collect_vec();
+        let public_announcements = receiver_data
+            .iter()
+            .map(|rd| rd.public_announcement.clone())
+            .collect_vec();
+        let timestamp = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .unwrap()
+            .as_millis();
+        let kernel = TransactionKernel {
+            inputs,
+            outputs,
+            public_announcements: public_announcements.clone(),
+            fee,
+            timestamp: BFieldElement::new(timestamp.try_into().unwrap()),
+            coinbase: None,
+            mutator_set_hash: msa_tip.hash(),
+            minimum_block_height: self.chain.light_state().kernel.header.height.next().into(),
+        };
+        let primitive_witness = TransactionPrimitiveWitness {
+            input_lock_scripts: vec![address.lock_script(); input_utxos.len()],
+            input_utxos,
+            type_scripts: vec![TypeScript::native_coin()],
+            lock_script_witnesses: vec![],
+            input_membership_proofs,
+            output_sender_randomnesses: receiver_data
+                .iter()
+                .map(|rd| rd.sender_randomness)
+                .collect(),
+            output_receiver_digests: receiver_data
+                .iter()
+                .map(|rd| rd.receiver_privacy_digest)
+                .collect(),
+            output_utxos: receiver_data.into_iter().map(|rd| rd.utxo).collect(),
+            public_announcements,
+            mutator_set_accumulator: msa_tip,
+        };
+
+        Ok(PartiallySignedTransaction::new(
+            self.cli().network,
+            address.clone(),
+            kernel,
+            primitive_witness,
+        ))
+    }
+
+    /// Add this wallet's signature to a multisig transaction.
+    pub fn sign_multisig_transaction(
+        &mut self,
+        mut transaction: PartiallySignedTransaction,
+    ) -> Result<PartiallySignedTransaction> {
+        let network = self.cli().network;
+        if transaction.network != network {
+            bail!(
+                "Transaction is for network {}, but this node runs on {network}",
+                transaction.network
+            );
+        }
+        self.wallet_state
+            .sign_multisig_transaction(&mut transaction)?;
+
+        Ok(transaction)
+    }
+
+    /// Turn a multisig transaction with enough signatures into a transaction that can be
+    /// broadcast. The signatures are bound to the kernel, so the transaction is proven as
+    /// it was created, and then updated with the blocks that the canonical chain added
+    /// since. Fails if it was created more than [`MULTISIG_UPDATE_DEPTH`] blocks ago.
+    pub async fn finalize_multisig_transaction(
+        &self,
+        transaction: PartiallySignedTransaction,
+    ) -> Result<Transaction> {
+        let network = self.cli().network;
+        if transaction.network != network {
+            bail!(
+                "Transaction is for network {}, but this node runs on {network}",
+                transaction.network
+            );
+        }
+        if !transaction.is_complete() {
+            bail!(
+                "Transaction has {} of the {} required signatures",
+                transaction.signature_count(),
+                transaction.address.threshold
+            );
+        }
+        if transaction.kernel.mutator_set_hash
+            != transaction.primitive_witness.mutator_set_accumulator.hash()
+        {
+            bail!("The kernel and the witness of the transaction are for different mutator sets");
+        }
+
+        // Collect the blocks since the one whose mutator set the transaction was created for
+        let mut blocks_since_creation = vec![];
+        let mut block = self.chain.light_state().clone();
+        while block.kernel.body.mutator_set_accumulator.hash()
+            != transaction.kernel.mutator_set_hash
+        {
+            if blocks_since_creation.len() >= MULTISIG_UPDATE_DEPTH {
+                bail!("The transaction was not created in the last {MULTISIG_UPDATE_DEPTH} blocks of the canonical chain. Create it again.");
+            }
+            let parent_digest = block.kernel.header.prev_block_digest;
+            let Some(parent) = self.chain.archival_state().get_block(parent_digest).await? else {
+                bail!("The transaction was not created on the canonical chain. Create it again.");
+            };
+            blocks_since_creation.push(block);
+            block = parent;
+        }
+
+        // Check the signatures before proving, as proving fails hard on invalid witnesses
+        let primitive_witness = transaction.signed_primitive_witness()?;
+        let lock_script = transaction.address.lock_script();
+        let kernel_hash = transaction.kernel.mast_hash();
+        for (input_utxo, (input_lock_script, lock_script_witness)) in
+            primitive_witness.input_utxos.iter().zip(
+                primitive_witness
+                    .input_lock_scripts
+                    .iter()
+                    .zip(primitive_witness.lock_script_witnesses.iter()),
+            )
+        {
+            if *input_lock_script != lock_script
+                || input_utxo.lock_script_hash != lock_script.hash()
+            {
+                bail!("Transaction spends a UTXO that is not locked to its multisig address");
+            }
+            if input_lock_script
+                .program
+                .run(
+                    PublicInput::new(kernel_hash.values().to_vec()),
+                    NonDeterminism::new(lock_script_witness.clone()),
+                )
+                .is_err()
+            {
+                bail!("Signatures do not unlock the multisig UTXOs");
+            }
+        }
+
+        let mut proven_transaction = self.prove_transaction(primitive_witness, transaction.kernel);
+        let mut previous_mutator_set_accumulator = block.kernel.body.mutator_set_accumulator;
+        for block in blocks_since_creation.into_iter().rev() {
+            proven_transaction
+                .update_mutator_set_records(&previous_mutator_set_accumulator, &block)?;
+            previous_mutator_set_accumulator = block.kernel.body.mutator_set_accumulator;
+        }
+
+        Ok(proven_transaction)
+    }
+
+    ///  Locking:
+    ///   * acquires `monitored_utxos_lock` for write
+    pub async fn resync_membership_proofs_from_stored_blocks(
+        &mut self,
+        tip_hash: Digest,
+    ) -> Result<()> {
+        // loop over all monitored utxos
+        let monitored_utxos = self.wallet_state.wallet_db.monitored_utxos_mut();
 
         // note: iter_mut_lock holds a write-lock, so it should be dropped
         // immediately after use.
@@ -876,6 +2030,11 @@ impl GlobalState {
 
         // Update sync label and persist
         self.wallet_state.wallet_db.set_sync_label(tip_hash);
+        if let Some(tip_header) = self.chain.archival_state().get_block_header(tip_hash).await {
+            self.wallet_state
+                .wallet_db
+                .set_sync_height(tip_header.height);
+        }
         self.wallet_state.wallet_db.persist();
 
         Ok(())
@@ -983,6 +2142,9 @@ impl GlobalState {
         // flush peer_standings
         self.net.peer_databases.peer_standings.flush().await;
 
+        // flush peer address book
+        self.net.peer_databases.peer_address_book.flush().await;
+
         debug!("Flushed all databases");
 
         Ok(())
@@ -1010,6 +2172,7 @@ impl GlobalState {
     ) -> Result<()> {
         // get proof_of_work_family for tip
         let tip_proof_of_work_family = self.chain.light_state().kernel.header.proof_of_work_family;
+        let previous_tip_digest = self.chain.light_state().hash();
         let previous_mutator_set_accumulator = self
             .chain
             .light_state()
@@ -1044,10 +2207,33 @@ impl GlobalState {
                 .expect("UTXO notification from miner must be accepted");
         }
 
+        // If the new block is on another chain, the blocks of the old chain no longer
+        // confirm the outgoing transactions that they settled.
+        let new_block_parent_digest = new_block.kernel.header.prev_block_digest;
+        if new_block_parent_digest != previous_tip_digest {
+            let (reverted_block_digests, _luca, applied_block_digests) = self
+                .chain
+                .archival_state()
+                .find_path(previous_tip_digest, new_block_parent_digest)
+                .await;
+            let mut applied_blocks = vec![];
+            for block_digest in applied_block_digests {
+                let Some(block) = self.chain.archival_state().get_block(block_digest).await? else {
+                    bail!("Could not find block {block_digest} of the new chain in archival state");
+                };
+                applied_blocks.push(block);
+            }
+            self.wallet_state
+                .reorganize_outgoing_transaction_statuses(&reverted_block_digests, &applied_blocks);
+        }
+
         // update wallet state with relevant UTXOs from this block
+        let known_generation_key_count = self.wallet_state.known_view_keys().len();
         self.wallet_state
             .update_wallet_state_with_new_block(&previous_mutator_set_accumulator, &new_block)
             .await?;
+        let scanning_window_moved =
+            self.wallet_state.known_view_keys().len() > known_generation_key_count;
 
         // Update mempool with UTXOs from this block. This is done by removing all transaction
         // that became invalid/was mined by this block.
@@ -1056,6 +2242,15 @@ impl GlobalState {
 
         self.chain.light_state_mut().set_block(new_block);
 
+        // Generation keys that were derived because the scanning window moved may have been
+        // paid in earlier blocks, for instance by another node that runs the same wallet.
+        if scanning_window_moved {
+            info!("Rescanning the chain for UTXOs on newly derived generation addresses");
+            if let Err(err) = self.rescan_from_height(BlockHeight::genesis()).await {
+                warn!("Could not rescan for newly derived generation addresses: {err}");
+            }
+        }
+
         // Flush databases
         self.flush_databases().await?;
 
@@ -1099,15 +2294,28 @@ impl GlobalState {
 
 #[cfg(test)]
 mod global_state_tests {
+    use crate::models::consensus::{ClaimSupport, Witness};
     use crate::{
         config_models::network::Network,
         models::{blockchain::block::Block, state::wallet::utxo_notification_pool::UtxoNotifier},
-        tests::shared::{add_block_to_light_state, get_mock_global_state, make_mock_block},
+        tests::shared::{
+            add_block_to_light_state, get_mock_global_state, make_mock_block,
+            random_addition_record, unit_test_data_directory,
+        },
+        util_types::test_shared::mutator_set::random_removal_record,
     };
     use num_traits::One;
+    use rand::random;
     use tracing_test::traced_test;
 
-    use super::{wallet::WalletSecret, *};
+    use super::{
+        wallet::{
+            outgoing_transaction::{OffChainOutput, OutgoingTransactionStatus},
+            wallet_state::GENERATION_ADDRESS_GAP_LIMIT,
+            WalletSecret,
+        },
+        *,
+    };
 
     async fn wallet_state_has_all_valid_mps_for(
         wallet_state: &WalletState,
@@ -1137,91 +2345,1083 @@ mod global_state_tests {
 
     #[traced_test]
     #[tokio::test]
-    async fn premine_recipient_can_spend_genesis_block_output() {
+    async fn premine_recipient_can_spend_genesis_block_output() {
+        let network = Network::Alpha;
+        let other_wallet = WalletSecret::new_random();
+        let global_state_lock = get_mock_global_state(network, 2, None).await;
+        let twenty_amount: NeptuneCoins = NeptuneCoins::new(20);
+        let twenty_coins = twenty_amount.to_native_coins();
+        let recipient_address = other_wallet.nth_generation_spending_key(0).to_address();
+        let main_lock_script = recipient_address.lock_script();
+        let output_utxo = Utxo {
+            coins: twenty_coins,
+            lock_script_hash: main_lock_script.hash(),
+        };
+        let sender_randomness = Digest::default();
+        let receiver_privacy_digest = recipient_address.privacy_digest;
+        let public_announcement = recipient_address
+            .generate_public_announcement(&output_utxo, sender_randomness)
+            .unwrap();
+        let receiver_data = vec![UtxoReceiverData {
+            utxo: output_utxo.clone(),
+            sender_randomness,
+            receiver_privacy_digest,
+            public_announcement,
+        }];
+        let tx: Transaction = global_state_lock
+            .lock_guard_mut()
+            .await
+            .create_transaction(receiver_data, NeptuneCoins::new(1))
+            .await
+            .unwrap();
+
+        assert!(tx.is_valid());
+        assert_eq!(
+            2,
+            tx.kernel.outputs.len(),
+            "tx must have a send output and a change output"
+        );
+        assert_eq!(
+            1,
+            tx.kernel.inputs.len(),
+            "tx must have exactly one input, a genesis UTXO"
+        );
+
+        // Test with a transaction with three outputs and one (premine) input
+        let mut other_receiver_data = vec![];
+        let mut output_utxos: Vec<Utxo> = vec![];
+        for i in 2..5 {
+            let amount: NeptuneCoins = NeptuneCoins::new(i);
+            let that_many_coins = amount.to_native_coins();
+            let receiving_address = other_wallet.nth_generation_spending_key(0).to_address();
+            let lock_script = receiving_address.lock_script();
+            let utxo = Utxo {
+                coins: that_many_coins,
+                lock_script_hash: lock_script.hash(),
+            };
+            let other_sender_randomness = Digest::default();
+            let other_receiver_digest = receiving_address.privacy_digest;
+            let other_public_announcement = receiving_address
+                .generate_public_announcement(&utxo, other_sender_randomness)
+                .unwrap();
+            output_utxos.push(utxo.clone());
+            other_receiver_data.push(UtxoReceiverData {
+                utxo,
+                sender_randomness: other_sender_randomness,
+                receiver_privacy_digest: other_receiver_digest,
+                public_announcement: other_public_announcement,
+            });
+        }
+
+        let new_tx: Transaction = global_state_lock
+            .lock_guard_mut()
+            .await
+            .create_transaction(other_receiver_data, NeptuneCoins::new(1))
+            .await
+            .unwrap();
+        assert!(new_tx.is_valid());
+        assert_eq!(
+            4,
+            new_tx.kernel.outputs.len(),
+            "tx must have three send outputs and a change output"
+        );
+        assert_eq!(
+            1,
+            new_tx.kernel.inputs.len(),
+            "tx must have exactly one input, a genesis UTXO"
+        );
+    }
+
+    #[traced_test]
+    #[tokio::test]
+    async fn off_chain_outputs_are_not_announced() {
+        let network = Network::Alpha;
+        let global_state_lock = get_mock_global_state(network, 2, None).await;
+        let other_wallet = WalletSecret::new_random();
+        let mut receiver_data = vec![];
+        for i in 0..3u16 {
+            let recipient_address = other_wallet.nth_generation_spending_key(i).to_address();
+            let utxo = Utxo::new(
+                recipient_address.lock_script(),
+                NeptuneCoins::new(u32::from(i) + 1).to_native_coins(),
+            );
+            let sender_randomness: Digest = random();
+            let public_announcement = recipient_address
+                .generate_public_announcement(&utxo, sender_randomness)
+                .unwrap();
+            receiver_data.push(UtxoReceiverData {
+                utxo,
+                sender_randomness,
+                receiver_privacy_digest: recipient_address.privacy_digest,
+                public_announcement,
+            });
+        }
+        let notification_media = [
+            UtxoNotificationMedium::OnChain,
+            UtxoNotificationMedium::OffChain,
+            UtxoNotificationMedium::OnChain,
+        ];
+
+        let mut global_state = global_state_lock.lock_guard_mut().await;
+        assert!(
+            global_state
+                .create_transaction_with_notification_media(
+                    receiver_data.clone(),
+                    &notification_media[0..2],
+                    CoinSelectionStrategy::default(),
+                    NeptuneCoins::new(1),
+                )
+                .await
+                .is_err(),
+            "Each output must have a notification medium"
+        );
+
+        let tx = global_state
+            .create_transaction_with_notification_media(
+                receiver_data.clone(),
+                &notification_media,
+                CoinSelectionStrategy::default(),
+                NeptuneCoins::new(1),
+            )
+            .await
+            .unwrap();
+        assert!(tx.is_valid());
+        assert_eq!(
+            4,
+            tx.kernel.outputs.len(),
+            "tx must have three send outputs and a change output"
+        );
+        assert_eq!(
+            vec![
+                receiver_data[0].public_announcement.clone(),
+                receiver_data[2].public_announcement.clone()
+            ],
+            tx.kernel.public_announcements,
+            "Only the outputs with on-chain notification must be announced"
+        );
+    }
+
+    #[traced_test]
+    #[tokio::test]
+    async fn bump_fee_replaces_unconfirmed_send_test() {
+        let network = Network::Alpha;
+        let global_state_lock = get_mock_global_state(network, 2, None).await;
+        let recipient_address = WalletSecret::new_random()
+            .nth_generation_spending_key(0)
+            .to_address();
+        let utxo = Utxo::new(
+            recipient_address.lock_script(),
+            NeptuneCoins::new(5).to_native_coins(),
+        );
+        let sender_randomness: Digest = random();
+        let receiver_data = vec![UtxoReceiverData {
+            public_announcement: recipient_address
+                .generate_public_announcement(&utxo, sender_randomness)
+                .unwrap(),
+            utxo,
+            sender_randomness,
+            receiver_privacy_digest: recipient_address.privacy_digest,
+        }];
+        let notification_media = vec![UtxoNotificationMedium::OnChain];
+
+        let mut global_state = global_state_lock.lock_guard_mut().await;
+        let transaction = global_state
+            .create_transaction_with_notification_media(
+                receiver_data.clone(),
+                &notification_media,
+                CoinSelectionStrategy::default(),
+                NeptuneCoins::new(1),
+            )
+            .await
+            .unwrap();
+        let txid = Hash::hash(&transaction);
+        assert!(
+            global_state
+                .create_fee_bump_transaction(txid, NeptuneCoins::new(2))
+                .await
+                .is_err(),
+            "Only tracked sends can be bumped"
+        );
+
+        global_state.mempool.insert(&transaction);
+        global_state
+            .wallet_state
+            .add_unconfirmed_send(UnconfirmedSend {
+                transaction: transaction.clone(),
+                receiver_data: receiver_data.clone(),
+                notification_media,
+            });
+        assert!(
+            global_state
+                .create_fee_bump_transaction(txid, NeptuneCoins::new(1))
+                .await
+                .is_err(),
+            "Fee must be raised by the minimum increase"
+        );
+
+        let replacement = global_state
+            .create_fee_bump_transaction(txid, NeptuneCoins::new(2))
+            .await
+            .unwrap();
+        assert!(replacement.transaction.is_valid());
+        assert_eq!(NeptuneCoins::new(2), replacement.fee());
+        assert_eq!(
+            transaction
+                .kernel
+                .inputs
+                .iter()
+                .map(|input| input.absolute_indices.clone())
+                .collect_vec(),
+            replacement
+                .transaction
+                .kernel
+                .inputs
+                .iter()
+                .map(|input| input.absolute_indices.clone())
+                .collect_vec(),
+            "Replacement must spend the same inputs"
+        );
+        assert_eq!(
+            transaction.kernel.outputs[0], replacement.transaction.kernel.outputs[0],
+            "Replacement must pay the same receiver"
+        );
+
+        // The replacement evicts the original from the mempool, and is tracked instead of it
+        global_state.mempool.insert(&replacement.transaction);
+        assert_eq!(1, global_state.mempool.len());
+        assert!(global_state.mempool.contains(replacement.txid()));
+        let replacement_txid = replacement.txid();
+        let original_change = transaction.kernel.outputs[1];
+        let replacement_change = replacement.transaction.kernel.outputs[1];
+        let expected_addition_records = |global_state: &GlobalState| {
+            global_state
+                .wallet_state
+                .expected_utxos
+                .get_all_expected_utxos()
+                .into_iter()
+                .map(|expected_utxo| expected_utxo.addition_record)
+                .collect_vec()
+        };
+        assert!(expected_addition_records(&global_state).contains(&original_change));
+        global_state.wallet_state.add_unconfirmed_send(replacement);
+        assert_eq!(1, global_state.wallet_state.unconfirmed_sends().len());
+        assert!(global_state
+            .wallet_state
+            .get_unconfirmed_send(replacement_txid)
+            .is_some());
+        assert!(global_state
+            .wallet_state
+            .get_unconfirmed_send(txid)
+            .is_none());
+
+        // Only the change of the replacement is expected from now on
+        let expected = expected_addition_records(&global_state);
+        assert!(!expected.contains(&original_change));
+        assert!(expected.contains(&replacement_change));
+
+        // Unconfirmed sends are kept in the wallet database
+        global_state.wallet_state.wallet_db.persist();
+        assert_eq!(
+            vec![replacement_txid],
+            global_state
+                .wallet_state
+                .wallet_db
+                .get_unconfirmed_sends()
+                .iter()
+                .map(UnconfirmedSend::txid)
+                .collect_vec()
+        );
+    }
+
+    #[traced_test]
+    #[tokio::test]
+    async fn outgoing_transactions_are_recorded_and_settled_test() {
+        let network = Network::Alpha;
+        let global_state_lock = get_mock_global_state(network, 2, None).await;
+        let genesis_block = Block::genesis_block();
+        let recipient_address = WalletSecret::new_random()
+            .nth_generation_spending_key(0)
+            .to_address();
+        let make_receiver_data = |amount: u32| {
+            let utxo = Utxo::new(
+                recipient_address.lock_script(),
+                NeptuneCoins::new(amount).to_native_coins(),
+            );
+            let sender_randomness: Digest = random();
+            UtxoReceiverData {
+                public_announcement: recipient_address
+                    .generate_public_announcement(&utxo, sender_randomness)
+                    .unwrap(),
+                utxo,
+                sender_randomness,
+                receiver_privacy_digest: recipient_address.privacy_digest,
+            }
+        };
+        let notification_media = vec![UtxoNotificationMedium::OnChain];
+
+        let mut global_state = global_state_lock.lock_guard_mut().await;
+        let input_amount = global_state
+            .get_wallet_status_for_tip()
+            .await
+            .synced_unspent_amount;
+
+        // Record a payment and label it
+        let receiver_data = vec![make_receiver_data(5)];
+        let transaction = global_state
+            .create_transaction_with_notification_media(
+                receiver_data.clone(),
+                &notification_media,
+                CoinSelectionStrategy::default(),
+                NeptuneCoins::new(1),
+            )
+            .await
+            .unwrap();
+        let txid = Hash::hash(&transaction);
+        global_state
+            .wallet_state
+            .record_outgoing_transaction(
+                &transaction,
+                vec![(recipient_address, NeptuneCoins::new(5))],
+                vec![],
+            )
+            .unwrap();
+        global_state
+            .wallet_state
+            .set_outgoing_transaction_label(txid, "rent".to_string())
+            .unwrap();
+        assert!(global_state
+            .wallet_state
+            .set_outgoing_transaction_label(random(), "unknown".to_string())
+            .is_err());
+
+        let records = global_state.wallet_state.outgoing_transactions();
+        assert_eq!(1, records.len());
+        assert_eq!(txid, records[0].txid);
+        assert_eq!(NeptuneCoins::new(5), records[0].amount());
+        assert_eq!(NeptuneCoins::new(1), records[0].fee);
+        assert_eq!(
+            input_amount.checked_sub(&NeptuneCoins::new(6)).unwrap(),
+            records[0].change
+        );
+        assert_eq!("rent", records[0].label);
+        assert_eq!(OutgoingTransactionStatus::Pending, records[0].status);
+
+        // Bumping the fee updates the record and takes the extra fee from the change
+        global_state
+            .wallet_state
+            .add_unconfirmed_send(UnconfirmedSend {
+                transaction,
+                receiver_data,
+                notification_media: notification_media.clone(),
+            });
+        let replacement = global_state
+            .create_fee_bump_transaction(txid, NeptuneCoins::new(2))
+            .await
+            .unwrap();
+        global_state
+            .wallet_state
+            .record_fee_bump(txid, &replacement.transaction)
+            .unwrap();
+        let records = global_state.wallet_state.outgoing_transactions();
+        assert_eq!(1, records.len());
+        assert_eq!(replacement.txid(), records[0].txid);
+        assert_eq!(NeptuneCoins::new(2), records[0].fee);
+        assert_eq!(
+            input_amount.checked_sub(&NeptuneCoins::new(7)).unwrap(),
+            records[0].change
+        );
+        assert_eq!("rent", records[0].label);
+
+        // Record a conflicting payment that spends the same input
+        let conflicting_transaction = global_state
+            .create_transaction_with_notification_media(
+                vec![make_receiver_data(3)],
+                &notification_media,
+                CoinSelectionStrategy::default(),
+                NeptuneCoins::new(1),
+            )
+            .await
+            .unwrap();
+        global_state
+            .wallet_state
+            .record_outgoing_transaction(
+                &conflicting_transaction,
+                vec![(recipient_address, NeptuneCoins::new(3))],
+                vec![],
+            )
+            .unwrap();
+
+        // A block that confirms the replacement abandons the conflicting payment
+        let (mut block_1, _, _) = make_mock_block(&genesis_block, None, recipient_address);
+        block_1.accumulate_transaction(
+            replacement.transaction,
+            &genesis_block.kernel.body.mutator_set_accumulator,
+        );
+        global_state
+            .wallet_state
+            .update_wallet_state_with_new_block(
+                &genesis_block.kernel.body.mutator_set_accumulator,
+                &block_1,
+            )
+            .await
+            .unwrap();
+        let records = global_state.wallet_state.outgoing_transactions();
+        assert_eq!(
+            OutgoingTransactionStatus::Confirmed(block_1.hash(), 1u64.into()),
+            records[0].status
+        );
+        assert_eq!(
+            OutgoingTransactionStatus::Abandoned(block_1.hash()),
+            records[1].status
+        );
+
+        // Reverting the block makes both payments pending again, and a block of the new
+        // chain that spends the same input settles them again.
+        global_state
+            .wallet_state
+            .reorganize_outgoing_transaction_statuses(&[block_1.hash()], &[]);
+        let records = global_state.wallet_state.outgoing_transactions();
+        assert_eq!(OutgoingTransactionStatus::Pending, records[0].status);
+        assert_eq!(OutgoingTransactionStatus::Pending, records[1].status);
+
+        global_state
+            .wallet_state
+            .reorganize_outgoing_transaction_statuses(&[], &[block_1.clone()]);
+        let records = global_state.wallet_state.outgoing_transactions();
+        assert_eq!(
+            OutgoingTransactionStatus::Confirmed(block_1.hash(), 1u64.into()),
+            records[0].status
+        );
+        assert_eq!(
+            OutgoingTransactionStatus::Abandoned(block_1.hash()),
+            records[1].status
+        );
+    }
+
+    #[traced_test]
+    #[tokio::test]
+    async fn claim_utxo_from_transfer_notification_test() {
+        let network = Network::Alpha;
+        let sender_state_lock = get_mock_global_state(network, 2, None).await;
+        let receiver_state_lock =
+            get_mock_global_state(network, 2, Some(WalletSecret::new_random())).await;
+        let other_state_lock =
+            get_mock_global_state(network, 2, Some(WalletSecret::new_random())).await;
+        let genesis_block = Block::genesis_block();
+        let mut receiver_state = receiver_state_lock.lock_guard_mut().await;
+        let receiver_address = receiver_state.wallet_state.own_receiving_address();
+
+        // Pay the receiver without publishing the notification
+        let utxo = Utxo::new(
+            receiver_address.lock_script(),
+            NeptuneCoins::new(5).to_native_coins(),
+        );
+        let sender_randomness: Digest = random();
+        let receiver_data = UtxoReceiverData {
+            public_announcement: receiver_address
+                .generate_public_announcement(&utxo, sender_randomness)
+                .unwrap(),
+            utxo: utxo.clone(),
+            sender_randomness,
+            receiver_privacy_digest: receiver_address.privacy_digest,
+        };
+        let transaction = sender_state_lock
+            .lock_guard_mut()
+            .await
+            .create_transaction_with_notification_media(
+                vec![receiver_data],
+                &[UtxoNotificationMedium::OffChain],
+                CoinSelectionStrategy::default(),
+                NeptuneCoins::new(1),
+            )
+            .await
+            .unwrap();
+        let off_chain_output = OffChainOutput {
+            utxo: utxo.clone(),
+            sender_randomness,
+            receiver_privacy_digest: receiver_address.privacy_digest,
+        };
+        let notification = UtxoTransferNotification::new(
+            network,
+            Hash::hash(&transaction),
+            &off_chain_output,
+            None,
+        );
+
+        // Reject notifications for other networks, with a broken commitment, or for
+        // another wallet
+        let mut wrong_network = notification.clone();
+        wrong_network.network = Network::Testnet;
+        assert!(receiver_state.claim_utxo(wrong_network).await.is_err());
+        let mut tampered = notification.clone();
+        tampered.sender_randomness = random();
+        assert!(receiver_state.claim_utxo(tampered).await.is_err());
+        assert!(other_state_lock
+            .lock_guard_mut()
+            .await
+            .claim_utxo(notification.clone())
+            .await
+            .is_err());
+
+        // Without a block hint, the UTXO is expected and the claim is stored in the wallet
+        assert_eq!(
+            UtxoClaimStatus::Pending,
+            receiver_state
+                .claim_utxo(notification.clone())
+                .await
+                .unwrap()
+        );
+        assert_eq!(
+            vec![notification.clone()],
+            receiver_state.wallet_state.pending_utxo_claims()
+        );
+
+        // Confirm the transaction in block 1
+        let other_address = WalletSecret::new_random()
+            .nth_generation_spending_key(0)
+            .to_address();
+        let (mut block_1, _, _) = make_mock_block(&genesis_block, None, other_address);
+        block_1.accumulate_transaction(
+            transaction,
+            &genesis_block.kernel.body.mutator_set_accumulator,
+        );
+        crate::tests::shared::add_block_to_archival_state(
+            receiver_state.chain.archival_state_mut(),
+            block_1.clone(),
+        )
+        .await
+        .unwrap();
+        add_block_to_light_state(receiver_state.chain.light_state_mut(), block_1.clone())
+            .await
+            .unwrap();
+
+        // Block hints must name a known block that contains the UTXO
+        let mut unknown_block = notification.clone();
+        unknown_block.block_hint = Some(random());
+        assert!(receiver_state.claim_utxo(unknown_block).await.is_err());
+        let mut wrong_block = notification.clone();
+        wrong_block.block_hint = Some(genesis_block.hash());
+        assert!(receiver_state.claim_utxo(wrong_block).await.is_err());
+
+        // With the right block hint, the UTXO is added to the wallet
+        let mut confirmed = notification;
+        confirmed.block_hint = Some(block_1.hash());
+        assert_eq!(
+            UtxoClaimStatus::Confirmed(block_1.hash(), 1u64.into()),
+            receiver_state.claim_utxo(confirmed).await.unwrap()
+        );
+        assert!(receiver_state
+            .wallet_state
+            .wallet_db
+            .monitored_utxos()
+            .get_all()
+            .iter()
+            .any(|mutxo| mutxo.utxo == utxo));
+        assert!(receiver_state.wallet_state.pending_utxo_claims().is_empty());
+    }
+
+    #[traced_test]
+    #[tokio::test]
+    async fn time_locked_utxos_can_only_be_spent_once_released_test() {
+        let network = Network::Alpha;
+        let sender_state_lock = get_mock_global_state(network, 2, None).await;
+        let receiver_state_lock =
+            get_mock_global_state(network, 2, Some(WalletSecret::new_random())).await;
+        let genesis_block = Block::genesis_block();
+        let mut receiver_state = receiver_state_lock.lock_guard_mut().await;
+        let receiver_address = receiver_state.wallet_state.own_receiving_address();
+
+        // Send UTXOs that are locked until a day ago and until a day from now, and UTXOs that
+        // are locked until the next block and until a much later block
+        let now = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .unwrap()
+            .as_millis() as u64;
+        let one_day = 24 * 60 * 60 * 1000;
+        let timelocked_receiver_data = |amount: u32, timelock: Timelock| {
+            let utxo = Utxo::new(
+                receiver_address.timelocked_lock_script(timelock),
+                NeptuneCoins::new(amount).to_native_coins(),
+            );
+            let sender_randomness: Digest = random();
+            UtxoReceiverData {
+                public_announcement: receiver_address
+                    .generate_timelocked_public_announcement(&utxo, sender_randomness, timelock)
+                    .unwrap(),
+                utxo,
+                sender_randomness,
+                receiver_privacy_digest: receiver_address.privacy_digest,
+            }
+        };
+        let released = timelocked_receiver_data(3, Timelock::Time(now - one_day));
+        let locked = timelocked_receiver_data(5, Timelock::Time(now + one_day));
+        let released_by_height = timelocked_receiver_data(2, Timelock::Height(2));
+        let locked_by_height = timelocked_receiver_data(7, Timelock::Height(100));
+        let transaction = sender_state_lock
+            .lock_guard_mut()
+            .await
+            .create_transaction(
+                vec![
+                    released.clone(),
+                    locked.clone(),
+                    released_by_height.clone(),
+                    locked_by_height.clone(),
+                ],
+                NeptuneCoins::new(1),
+            )
+            .await
+            .unwrap();
+
+        let other_address = WalletSecret::new_random()
+            .nth_generation_spending_key(0)
+            .to_address();
+        let (mut block_1, _, _) = make_mock_block(&genesis_block, None, other_address);
+        block_1.accumulate_transaction(
+            transaction,
+            &genesis_block.kernel.body.mutator_set_accumulator,
+        );
+        crate::tests::shared::add_block_to_archival_state(
+            receiver_state.chain.archival_state_mut(),
+            block_1.clone(),
+        )
+        .await
+        .unwrap();
+        add_block_to_light_state(receiver_state.chain.light_state_mut(), block_1.clone())
+            .await
+            .unwrap();
+        receiver_state
+            .wallet_state
+            .update_wallet_state_with_new_block(
+                &genesis_block.kernel.body.mutator_set_accumulator,
+                &block_1,
+            )
+            .await
+            .unwrap();
+
+        // All UTXOs are recognized as own, but only the released ones can be spent in the
+        // next block, of height 2
+        for receiver_data in [&released, &locked, &released_by_height, &locked_by_height] {
+            assert!(receiver_state
+                .wallet_state
+                .find_timelock(receiver_data.utxo.lock_script_hash)
+                .is_some());
+        }
+        let wallet_state = &receiver_state.wallet_state;
+        assert!(wallet_state.is_released(&released.utxo, now, 2));
+        assert!(!wallet_state.is_released(&locked.utxo, now, 2));
+        assert!(wallet_state.is_released(&released_by_height.utxo, now, 2));
+        assert!(!wallet_state.is_released(&locked_by_height.utxo, now, 2));
+        assert!(wallet_state.is_released(&locked_by_height.utxo, now, 100));
+        assert_eq!(
+            NeptuneCoins::new(17),
+            wallet_state
+                .get_wallet_status_from_lock(block_1.hash())
+                .synced_unspent_amount
+        );
+        assert!(wallet_state
+            .allocate_sufficient_input_funds_from_lock(
+                NeptuneCoins::new(6),
+                block_1.hash(),
+                CoinSelectionStrategy::default(),
+            )
+            .await
+            .is_err());
+
+        // Spending both released UTXOs satisfies their time-locked lock scripts
+        let payment = Utxo::new(
+            other_address.lock_script(),
+            NeptuneCoins::new(4).to_native_coins(),
+        );
+        let sender_randomness: Digest = random();
+        let receiver_data = UtxoReceiverData {
+            public_announcement: other_address
+                .generate_public_announcement(&payment, sender_randomness)
+                .unwrap(),
+            utxo: payment,
+            sender_randomness,
+            receiver_privacy_digest: other_address.privacy_digest,
+        };
+        let spending_transaction = receiver_state
+            .create_transaction(vec![receiver_data], NeptuneCoins::new(1))
+            .await
+            .unwrap();
+        assert_eq!(2, spending_transaction.kernel.inputs.len());
+        assert_eq!(
+            BFieldElement::new(2),
+            spending_transaction.kernel.minimum_block_height
+        );
+        assert!(spending_transaction.is_valid());
+    }
+
+    #[traced_test]
+    #[tokio::test]
+    async fn tokens_can_be_issued_and_sent_test() {
         let network = Network::Alpha;
-        let other_wallet = WalletSecret::new_random();
-        let global_state_lock = get_mock_global_state(network, 2, None).await;
-        let twenty_amount: NeptuneCoins = NeptuneCoins::new(20);
-        let twenty_coins = twenty_amount.to_native_coins();
-        let recipient_address = other_wallet.nth_generation_spending_key(0).to_address();
-        let main_lock_script = recipient_address.lock_script();
-        let output_utxo = Utxo {
-            coins: twenty_coins,
-            lock_script_hash: main_lock_script.hash(),
+        let issuer_state_lock = get_mock_global_state(network, 2, None).await;
+        let receiver_state_lock =
+            get_mock_global_state(network, 2, Some(WalletSecret::new_random())).await;
+        let genesis_block = Block::genesis_block();
+        let mut issuer_state = issuer_state_lock.lock_guard_mut().await;
+        let mut receiver_state = receiver_state_lock.lock_guard_mut().await;
+        let receiver_address = receiver_state.wallet_state.own_receiving_address();
+
+        // The premine recipient issues 1000 tokens, together with some native coins
+        let definition = issuer_state
+            .wallet_state
+            .create_token("gold".to_string())
+            .unwrap()
+            .definition;
+        let token_receiver_data = |address: generation_address::ReceivingAddress,
+                                   native_amount: u32,
+                                   token_amount: u64| {
+            let utxo = Utxo::new(
+                address.lock_script(),
+                NeptuneCoins::new(native_amount)
+                    .to_native_coins()
+                    .into_iter()
+                    .chain((token_amount > 0).then(|| definition.coin(token_amount).unwrap()))
+                    .collect(),
+            );
+            let sender_randomness: Digest = random();
+            UtxoReceiverData {
+                public_announcement: address
+                    .generate_public_announcement(&utxo, sender_randomness)
+                    .unwrap(),
+                utxo,
+                sender_randomness,
+                receiver_privacy_digest: address.privacy_digest,
+            }
         };
-        let sender_randomness = Digest::default();
-        let receiver_privacy_digest = recipient_address.privacy_digest;
-        let public_announcement = recipient_address
-            .generate_public_announcement(&output_utxo, sender_randomness)
+        let issuance = issuer_state
+            .create_token_transaction(
+                "gold",
+                vec![token_receiver_data(receiver_address, 5, 1000)],
+                true,
+                NeptuneCoins::new(1),
+            )
+            .await
             .unwrap();
-        let receiver_data = vec![UtxoReceiverData {
-            utxo: output_utxo.clone(),
-            sender_randomness,
-            receiver_privacy_digest,
-            public_announcement,
-        }];
-        let tx: Transaction = global_state_lock
-            .lock_guard_mut()
+        assert!(issuance.is_valid());
+
+        let other_address = WalletSecret::new_random()
+            .nth_generation_spending_key(0)
+            .to_address();
+        let (mut block_1, _, _) = make_mock_block(&genesis_block, None, other_address);
+        block_1
+            .accumulate_transaction(issuance, &genesis_block.kernel.body.mutator_set_accumulator);
+        crate::tests::shared::add_block_to_archival_state(
+            receiver_state.chain.archival_state_mut(),
+            block_1.clone(),
+        )
+        .await
+        .unwrap();
+        add_block_to_light_state(receiver_state.chain.light_state_mut(), block_1.clone())
             .await
-            .create_transaction(receiver_data, NeptuneCoins::new(1))
+            .unwrap();
+        receiver_state
+            .wallet_state
+            .update_wallet_state_with_new_block(
+                &genesis_block.kernel.body.mutator_set_accumulator,
+                &block_1,
+            )
             .await
             .unwrap();
 
-        assert!(tx.is_valid());
+        // Tokens are counted once the token is registered
+        let wallet_status = receiver_state
+            .wallet_state
+            .get_wallet_status_from_lock(block_1.hash());
+        assert!(wallet_status.synced_unspent_token_amounts.is_empty());
+        assert_eq!(NeptuneCoins::new(5), wallet_status.synced_unspent_amount);
+        receiver_state
+            .wallet_state
+            .register_token("gold".to_string(), definition)
+            .unwrap();
+        assert!(receiver_state
+            .wallet_state
+            .register_token("silver".to_string(), definition)
+            .is_err());
         assert_eq!(
-            2,
-            tx.kernel.outputs.len(),
-            "tx must have a send output and a change output"
+            vec![("gold".to_string(), 1000)],
+            receiver_state
+                .wallet_state
+                .get_wallet_status_from_lock(block_1.hash())
+                .synced_unspent_token_amounts
         );
+
+        // Only the issuer can issue, and native payments leave the token UTXO alone
+        assert!(receiver_state
+            .create_token_transaction(
+                "gold",
+                vec![token_receiver_data(other_address, 0, 1)],
+                true,
+                NeptuneCoins::new(1),
+            )
+            .await
+            .is_err());
+        assert!(receiver_state
+            .create_transaction(
+                vec![token_receiver_data(other_address, 1, 0)],
+                NeptuneCoins::new(1)
+            )
+            .await
+            .is_err());
+        assert!(receiver_state
+            .create_token_transaction(
+                "gold",
+                vec![token_receiver_data(other_address, 0, 1001)],
+                false,
+                NeptuneCoins::new(1),
+            )
+            .await
+            .is_err());
+
+        // Sending tokens returns the remaining tokens and native coins as change
+        let transfer = receiver_state
+            .create_token_transaction(
+                "gold",
+                vec![token_receiver_data(other_address, 0, 300)],
+                false,
+                NeptuneCoins::new(1),
+            )
+            .await
+            .unwrap();
+        assert!(transfer.is_valid());
+        let change = receiver_state
+            .wallet_state
+            .expected_utxos
+            .get_all_expected_utxos()
+            .into_iter()
+            .map(|expected_utxo| expected_utxo.utxo)
+            .find(|utxo| token::token_amount(utxo, definition.type_script_hash()) > 0)
+            .unwrap();
         assert_eq!(
-            1,
-            tx.kernel.inputs.len(),
-            "tx must have exactly one input, a genesis UTXO"
+            700,
+            token::token_amount(&change, definition.type_script_hash())
         );
+        assert_eq!(NeptuneCoins::new(4), change.get_native_coin_amount());
+    }
 
-        // Test with a transaction with three outputs and one (premine) input
-        let mut other_receiver_data = vec![];
-        let mut output_utxos: Vec<Utxo> = vec![];
-        for i in 2..5 {
-            let amount: NeptuneCoins = NeptuneCoins::new(i);
-            let that_many_coins = amount.to_native_coins();
-            let receiving_address = other_wallet.nth_generation_spending_key(0).to_address();
-            let lock_script = receiving_address.lock_script();
-            let utxo = Utxo {
-                coins: that_many_coins,
-                lock_script_hash: lock_script.hash(),
-            };
-            let other_sender_randomness = Digest::default();
-            let other_receiver_digest = receiving_address.privacy_digest;
-            let other_public_announcement = receiving_address
-                .generate_public_announcement(&utxo, other_sender_randomness)
-                .unwrap();
-            output_utxos.push(utxo.clone());
-            other_receiver_data.push(UtxoReceiverData {
-                utxo,
-                sender_randomness: other_sender_randomness,
-                receiver_privacy_digest: other_receiver_digest,
-                public_announcement: other_public_announcement,
-            });
-        }
+    #[traced_test]
+    #[tokio::test]
+    async fn multisig_utxos_can_be_spent_with_threshold_signatures_test() {
+        let network = Network::Alpha;
+        let sender_state_lock = get_mock_global_state(network, 2, None).await;
+        let receiver_wallet = WalletSecret::new_random();
+        let receiver_state_lock =
+            get_mock_global_state(network, 2, Some(receiver_wallet.clone())).await;
+        let genesis_block = Block::genesis_block();
+        let mut receiver_state = receiver_state_lock.lock_guard_mut().await;
 
-        let new_tx: Transaction = global_state_lock
+        // Set up a 2-of-2 multisig address of the receiver and another participant
+        let own_key = receiver_wallet.nth_multisig_key(0);
+        let other_key = WalletSecret::new_random().nth_multisig_key(0);
+        let setup = MultisigSetup::new(
+            2,
+            vec![own_key.public_key(), other_key.public_key()],
+            random(),
+        )
+        .unwrap();
+        let multisig_address = setup.address.clone();
+
+        // Fund the multisig address
+        let utxo = Utxo::new(
+            multisig_address.lock_script(),
+            NeptuneCoins::new(5).to_native_coins(),
+        );
+        let sender_randomness: Digest = random();
+        let receiver_data = UtxoReceiverData {
+            public_announcement: multisig_address
+                .receiving_address
+                .generate_public_announcement(&utxo, sender_randomness)
+                .unwrap(),
+            utxo,
+            sender_randomness,
+            receiver_privacy_digest: multisig_address.receiving_address.privacy_digest,
+        };
+        let transaction = sender_state_lock
             .lock_guard_mut()
             .await
-            .create_transaction(other_receiver_data, NeptuneCoins::new(1))
+            .create_transaction(vec![receiver_data], NeptuneCoins::new(1))
             .await
             .unwrap();
-        assert!(new_tx.is_valid());
-        assert_eq!(
-            4,
-            new_tx.kernel.outputs.len(),
-            "tx must have three send outputs and a change output"
+
+        let other_address = WalletSecret::new_random()
+            .nth_generation_spending_key(0)
+            .to_address();
+        let (mut block_1, _, _) = make_mock_block(&genesis_block, None, other_address);
+        block_1.accumulate_transaction(
+            transaction,
+            &genesis_block.kernel.body.mutator_set_accumulator,
         );
+        crate::tests::shared::add_block_to_archival_state(
+            receiver_state.chain.archival_state_mut(),
+            block_1.clone(),
+        )
+        .await
+        .unwrap();
+        add_block_to_light_state(receiver_state.chain.light_state_mut(), block_1.clone())
+            .await
+            .unwrap();
+
+        // Importing the setup finds the UTXO that was sent before the import
         assert_eq!(
             1,
-            new_tx.kernel.inputs.len(),
-            "tx must have exactly one input, a genesis UTXO"
+            receiver_state
+                .import_multisig_setup(setup.clone())
+                .await
+                .unwrap()
+        );
+        assert!(receiver_state.import_multisig_setup(setup).await.is_err());
+
+        // The UTXO can only be spent once both participants have signed
+        let payment = Utxo::new(
+            other_address.lock_script(),
+            NeptuneCoins::new(4).to_native_coins(),
+        );
+        let sender_randomness: Digest = random();
+        let receiver_data = UtxoReceiverData {
+            public_announcement: other_address
+                .generate_public_announcement(&payment, sender_randomness)
+                .unwrap(),
+            utxo: payment,
+            sender_randomness,
+            receiver_privacy_digest: other_address.privacy_digest,
+        };
+        let other_receiver_data = UtxoReceiverData {
+            sender_randomness: random(),
+            ..receiver_data.clone()
+        };
+        let unsigned = receiver_state
+            .create_multisig_transaction(
+                &multisig_address,
+                vec![receiver_data],
+                None,
+                NeptuneCoins::new(1),
+            )
+            .unwrap();
+        let signed_once = receiver_state
+            .sign_multisig_transaction(unsigned.clone())
+            .unwrap();
+        assert!(!signed_once.is_complete());
+        assert!(receiver_state
+            .finalize_multisig_transaction(signed_once.clone())
+            .await
+            .is_err());
+
+        // The one-time key of the wallet signs the same transaction again, but no other
+        assert_eq!(
+            signed_once,
+            receiver_state
+                .sign_multisig_transaction(unsigned.clone())
+                .unwrap()
         );
+        let other_unsigned = receiver_state
+            .create_multisig_transaction(
+                &multisig_address,
+                vec![other_receiver_data],
+                None,
+                NeptuneCoins::new(1),
+            )
+            .unwrap();
+        assert!(receiver_state
+            .sign_multisig_transaction(other_unsigned)
+            .is_err());
+
+        let mut signed_by_other = unsigned;
+        signed_by_other.sign(&other_key).unwrap();
+        let mut signed_twice = signed_once;
+        signed_twice.combine(&signed_by_other).unwrap();
+        assert!(signed_twice.is_complete());
+
+        // The signatures do not sign a transaction with another kernel
+        let mut tampered = signed_twice.clone();
+        tampered.kernel.fee = NeptuneCoins::new(2);
+        assert!(tampered.signed_primitive_witness().is_err());
+
+        let spending_transaction = receiver_state
+            .finalize_multisig_transaction(signed_twice)
+            .await
+            .unwrap();
+        assert!(spending_transaction.is_valid());
+    }
+
+    #[traced_test]
+    #[tokio::test]
+    async fn watch_only_node_creates_transactions_for_offline_signing_test() {
+        let network = Network::Alpha;
+        let global_state_lock = get_mock_global_state(network, 2, None).await;
+        let mut global_state = global_state_lock.lock_guard_mut().await;
+
+        // Replace the wallet with a watch-only wallet of the same addresses
+        let wallet_secret = WalletSecret::devnet_wallet();
+        let view_keys = (0..=GENERATION_ADDRESS_GAP_LIMIT)
+            .map(|index| {
+                wallet_secret
+                    .nth_generation_spending_key(index)
+                    .to_view_key(index)
+            })
+            .collect_vec();
+        let data_dir = unit_test_data_directory(network).unwrap();
+        let cli_args = global_state.cli().clone();
+        global_state.wallet_state =
+            WalletState::new_watch_only(&data_dir, view_keys, &cli_args).await;
+
+        let recipient = WalletSecret::new_random()
+            .nth_generation_spending_key(0)
+            .to_address();
+        let unsigned_transaction = global_state
+            .create_unsigned_transaction(
+                vec![(recipient, NeptuneCoins::new(2))],
+                CoinSelectionStrategy::default(),
+                NeptuneCoins::new(1),
+            )
+            .await
+            .unwrap();
+        assert!(unsigned_transaction
+            .primitive_witness
+            .lock_script_witnesses
+            .is_empty());
+
+        // Only the wallet secret of the watched addresses can sign, and only for the
+        // listed recipients
+        assert!(unsigned_transaction
+            .sign(&WalletSecret::new_random())
+            .is_err());
+        let mut redirected = unsigned_transaction.clone();
+        redirected.recipients[0].0 = WalletSecret::new_random()
+            .nth_generation_spending_key(0)
+            .to_address();
+        assert!(redirected.sign(&wallet_secret).is_err());
+        let mut redirected_privacy = unsigned_transaction.clone();
+        redirected_privacy.primitive_witness.output_receiver_digests[0] = random();
+        assert!(redirected_privacy.sign(&wallet_secret).is_err());
+
+        // The kernel that the lock scripts sign must add and spend exactly the listed UTXOs
+        let mut other_outputs = unsigned_transaction.clone();
+        other_outputs.kernel.outputs[0] = random_addition_record();
+        assert!(other_outputs.sign(&wallet_secret).is_err());
+        let mut other_inputs = unsigned_transaction.clone();
+        other_inputs.kernel.inputs[0] = random_removal_record();
+        assert!(other_inputs.sign(&wallet_secret).is_err());
+
+        let signed_transaction =
+            SignedTransaction::new(&unsigned_transaction, &wallet_secret, false).unwrap();
+        let transaction = global_state
+            .verify_signed_transaction(signed_transaction)
+            .unwrap();
+        assert_eq!(unsigned_transaction.kernel, transaction.kernel);
+        let Witness::ValidationLogic(validation_logic) = &transaction.witness else {
+            panic!("Signed transaction must have a validation logic witness");
+        };
+        assert!(validation_logic
+            .lock_scripts_halt
+            .supported_claims
+            .iter()
+            .all(|supported_claim| matches!(supported_claim.support, ClaimSupport::Proof(_))));
+
+        // The watch-only wallet announces its change, as it cannot rederive its randomness
+        assert_eq!(2, transaction.kernel.public_announcements.len());
     }
 
     #[traced_test]
@@ -1235,15 +3435,22 @@ mod global_state_tests {
             .to_address();
         let genesis_block = Block::genesis_block();
         let (mock_block_1, _, _) = make_mock_block(&genesis_block, None, other_receiver_address);
+        add_block_to_light_state(global_state.chain.light_state_mut(), mock_block_1.clone())
+            .await
+            .unwrap();
+
+        // Recovery fails while the archival mutator set lags behind the tip
+        assert!(global_state
+            .restore_monitored_utxos_from_recovery_data()
+            .await
+            .is_err());
+
         crate::tests::shared::add_block_to_archival_state(
             global_state.chain.archival_state_mut(),
             mock_block_1.clone(),
         )
         .await
         .unwrap();
-        add_block_to_light_state(global_state.chain.light_state_mut(), mock_block_1.clone())
-            .await
-            .unwrap();
 
         // Delete everything from monitored UTXO (the premined UTXO)
         {
@@ -1295,7 +3502,91 @@ mod global_state_tests {
                     .0,
                 "MUTXO must have the correct latest block digest value"
             );
+            assert_eq!(
+                Some(genesis_block.hash()),
+                own_premine_mutxo
+                    .confirmed_in_block
+                    .map(|(block_hash, _timestamp, _height)| block_hash),
+                "Restored MUTXO must be confirmed in the genesis block"
+            );
+            assert!(own_premine_mutxo.spent_in_block.is_none());
         }
+
+        // Nothing is missing anymore
+        assert!(global_state
+            .restore_monitored_utxos_from_recovery_data()
+            .await
+            .unwrap()
+            .is_zero());
+    }
+
+    #[traced_test]
+    #[tokio::test]
+    async fn rescan_from_height_restores_monitored_utxos_test() {
+        let network = Network::Alpha;
+        let global_state_lock = get_mock_global_state(network, 2, None).await;
+        let mut global_state = global_state_lock.lock_guard_mut().await;
+        let other_receiver_address = WalletSecret::new_random()
+            .nth_generation_spending_key(0)
+            .to_address();
+        let genesis_block = Block::genesis_block();
+        let (mock_block_1, _, _) = make_mock_block(&genesis_block, None, other_receiver_address);
+        crate::tests::shared::add_block_to_archival_state(
+            global_state.chain.archival_state_mut(),
+            mock_block_1.clone(),
+        )
+        .await
+        .unwrap();
+        add_block_to_light_state(global_state.chain.light_state_mut(), mock_block_1.clone())
+            .await
+            .unwrap();
+
+        // Nothing is missing, so a rescan must not add anything
+        assert!(global_state
+            .rescan_from_height(BlockHeight::genesis())
+            .await
+            .unwrap()
+            .is_zero());
+
+        // Delete the premined UTXO from the wallet database, as if the wallet was restored
+        // from its seed phrase
+        global_state
+            .wallet_state
+            .wallet_db
+            .monitored_utxos_mut()
+            .pop();
+
+        // A rescan that starts after the block with the premine does not find it
+        assert!(global_state
+            .rescan_from_height(1u64.into())
+            .await
+            .unwrap()
+            .is_zero());
+        assert!(global_state.rescan_from_height(2u64.into()).await.is_err());
+
+        assert!(global_state
+            .rescan_from_height(BlockHeight::genesis())
+            .await
+            .unwrap()
+            .is_one());
+        let monitored_utxos = global_state.wallet_state.wallet_db.monitored_utxos();
+        assert!(monitored_utxos.len().is_one());
+        let own_premine_mutxo = monitored_utxos.get(0);
+        let (block_digest, msmp) = own_premine_mutxo
+            .get_latest_membership_proof_entry()
+            .unwrap();
+        assert_eq!(mock_block_1.hash(), block_digest);
+        assert!(mock_block_1
+            .body()
+            .mutator_set_accumulator
+            .verify(Hash::hash(&own_premine_mutxo.utxo), &msmp));
+        assert_eq!(
+            Some(genesis_block.hash()),
+            own_premine_mutxo
+                .confirmed_in_block
+                .map(|(block_hash, _timestamp, _height)| block_hash)
+        );
+        assert!(own_premine_mutxo.spent_in_block.is_none());
     }
 
     #[traced_test]
@@ -1372,7 +3663,8 @@ mod global_state_tests {
         let mut global_state = global_state_lock.lock_guard_mut().await;
         let own_spending_key = global_state
             .wallet_state
-            .wallet_secret
+            .spending_secret()
+            .unwrap()
             .nth_generation_spending_key(0);
         let own_receiving_address = own_spending_key.to_address();
 
@@ -1486,7 +3778,7 @@ mod global_state_tests {
         let network = Network::RegTest;
         let global_state_lock = get_mock_global_state(network, 2, None).await;
         let mut global_state = global_state_lock.lock_guard_mut().await;
-        let wallet_secret = global_state.wallet_state.wallet_secret.clone();
+        let wallet_secret = global_state.wallet_state.wallet_secret.clone().unwrap();
         let own_spending_key = wallet_secret.nth_generation_spending_key(0);
         let own_receiving_address = own_spending_key.to_address();
         let other_wallet_secret = WalletSecret::new_random();
@@ -1693,4 +3985,83 @@ mod global_state_tests {
 
         Ok(())
     }
+
+    #[traced_test]
+    #[tokio::test]
+    async fn estimate_fee_on_idle_network_test() {
+        let global_state_lock = get_mock_global_state(Network::RegTest, 0, None).await;
+        let global_state = global_state_lock.lock_guard().await;
+
+        assert!(global_state.estimate_fee(0, Some(1000)).await.is_err());
+
+        // With an empty mempool and empty blocks, no fee is needed to get mined
+        for target_blocks in [1, 3, 10] {
+            assert!(global_state
+                .estimate_fee(target_blocks, Some(1000))
+                .await
+                .unwrap()
+                .is_zero());
+            assert!(global_state
+                .estimate_fee(target_blocks, None)
+                .await
+                .unwrap()
+                .is_zero());
+        }
+    }
+
+    #[traced_test]
+    #[tokio::test]
+    async fn estimate_fee_for_outputs_leaves_wallet_unchanged_test() {
+        let global_state_lock = get_mock_global_state(Network::Alpha, 2, None).await;
+        let global_state = global_state_lock.lock_guard().await;
+        let recipient_address = WalletSecret::new_random()
+            .nth_generation_spending_key(0)
+            .to_address();
+        let utxo = Utxo::new(
+            recipient_address.lock_script(),
+            NeptuneCoins::new(20).to_native_coins(),
+        );
+        let sender_randomness: Digest = random();
+        let receiver_data = vec![UtxoReceiverData {
+            public_announcement: recipient_address
+                .generate_public_announcement(&utxo, sender_randomness)
+                .unwrap(),
+            utxo,
+            sender_randomness,
+            receiver_privacy_digest: recipient_address.privacy_digest,
+        }];
+
+        let expected_utxo_count = global_state.wallet_state.expected_utxos.len();
+        let fee = global_state
+            .estimate_fee_for_outputs(
+                receiver_data.clone(),
+                &[UtxoNotificationMedium::OnChain],
+                CoinSelectionStrategy::default(),
+                3,
+            )
+            .await
+            .unwrap();
+        assert!(fee.is_zero());
+        assert_eq!(
+            expected_utxo_count,
+            global_state.wallet_state.expected_utxos.len(),
+            "The change of the draft transaction must not be expected"
+        );
+
+        // The balance must cover the outputs
+        let mut too_much = receiver_data;
+        too_much[0].utxo = Utxo::new(
+            recipient_address.lock_script(),
+            NeptuneCoins::new(1_000_000_000).to_native_coins(),
+        );
+        assert!(global_state
+            .estimate_fee_for_outputs(
+                too_much,
+                &[UtxoNotificationMedium::OnChain],
+                CoinSelectionStrategy::default(),
+                3,
+            )
+            .await
+            .is_err());
+    }
 }
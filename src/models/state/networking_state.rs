@@ -1,12 +1,31 @@
+use crate::prelude::twenty_first;
+
 use crate::config_models::data_directory::DataDirectory;
 use crate::database::{create_db_if_missing, NeptuneLevelDb};
+use crate::models::blockchain::block::compact_block::BlockComposition;
 use crate::models::database::PeerDatabases;
-use crate::models::peer::{self, PeerStanding};
-use anyhow::Result;
+use crate::models::peer::{self, PeerAddressBookEntry, PeerStanding};
+use crate::peer_transport::{PeerStaticKey, PeerTransportKey};
+use anyhow::{bail, Result};
+use itertools::Itertools;
+use std::collections::VecDeque;
 use std::net::IpAddr;
+use std::time::SystemTime;
 use std::{collections::HashMap, net::SocketAddr};
+use twenty_first::shared_math::digest::Digest;
 
 pub const BANNED_IPS_DB_NAME: &str = "banned_ips";
+pub const PEER_ADDRESS_BOOK_DB_NAME: &str = "peer_address_book";
+
+/// The number of recent blocks whose composition is kept for compact block relay
+pub const MAX_STORED_BLOCK_COMPOSITIONS: usize = 10;
+
+/// The number of addresses kept in the peer address book
+pub const MAX_PEER_ADDRESS_BOOK_SIZE: usize = 1000;
+
+/// The number of addresses from a single peer list response that are added to the
+/// address book, such that one peer cannot fill it
+pub const MAX_NEW_PEER_ADDRESSES_PER_RESPONSE: usize = 10;
 
 type PeerMap = HashMap<SocketAddr, peer::PeerInfo>;
 
@@ -18,30 +37,75 @@ pub struct NetworkingState {
     // Peer threads may update their own entries into this map.
     pub peer_map: PeerMap,
 
-    // `peer_databases` are used to persist IPs with their standing.
+    // `peer_databases` are used to persist IPs with their standing, and the
+    // addresses that peers listen on.
     // The peer threads may update their own entries into this map.
     pub peer_databases: PeerDatabases,
 
+    // In-memory copy of the address book database, from which candidates for
+    // outgoing connections are picked. Every change is written through to the
+    // database.
+    peer_address_book: HashMap<SocketAddr, PeerAddressBookEntry>,
+
     // This value is only true if instance is running an archival node
     // that is currently downloading blocks to catch up.
     // Only the main thread may update this flag
     pub syncing: bool,
 
-    // Read-only value set during startup
+    // Read-only value set during startup, bound to the transport key
     pub instance_id: u128,
+
+    // Read-only value set during startup. The static key with which this node
+    // authenticates itself in encrypted connections.
+    pub transport_key: PeerTransportKey,
+
+    // The compositions of the most recent blocks that this node mined or rebuilt
+    // from compact blocks, newest last. Peer threads use these to relay blocks as
+    // compact blocks.
+    pub block_compositions: VecDeque<(Digest, BlockComposition)>,
 }
 
 impl NetworkingState {
-    pub fn new(peer_map: PeerMap, peer_databases: PeerDatabases, syncing: bool) -> Self {
+    /// The networking state of a node that authenticates itself with the given static key
+    /// in encrypted connections, and uses the instance ID bound to it.
+    pub fn new(
+        peer_map: PeerMap,
+        peer_databases: PeerDatabases,
+        syncing: bool,
+        transport_key: PeerTransportKey,
+    ) -> Self {
+        let peer_address_book = peer_databases.peer_address_book.iter().collect();
         Self {
             peer_map,
             peer_databases,
+            peer_address_book,
             syncing,
-            instance_id: rand::random(),
+            instance_id: transport_key.instance_id(),
+            transport_key,
+            block_compositions: VecDeque::new(),
+        }
+    }
+
+    /// Keep the composition of a block, so that it can be relayed as a compact block.
+    /// Only the most recent compositions are kept.
+    pub fn store_block_composition(&mut self, block_hash: Digest, composition: BlockComposition) {
+        if self.block_composition(block_hash).is_some() {
+            return;
+        }
+        if self.block_compositions.len() == MAX_STORED_BLOCK_COMPOSITIONS {
+            self.block_compositions.pop_front();
         }
+        self.block_compositions.push_back((block_hash, composition));
     }
 
-    /// Create databases for peer standings
+    pub fn block_composition(&self, block_hash: Digest) -> Option<&BlockComposition> {
+        self.block_compositions
+            .iter()
+            .find(|(hash, _)| *hash == block_hash)
+            .map(|(_, composition)| composition)
+    }
+
+    /// Create databases for peer standings and the peer address book
     pub async fn initialize_peer_databases(data_dir: &DataDirectory) -> Result<PeerDatabases> {
         let database_dir_path = data_dir.database_dir_path();
         DataDirectory::create_dir_if_not_exists(&database_dir_path).await?;
@@ -52,7 +116,16 @@ impl NetworkingState {
         )
         .await?;
 
-        Ok(PeerDatabases { peer_standings })
+        let peer_address_book = NeptuneLevelDb::<SocketAddr, PeerAddressBookEntry>::new(
+            &data_dir.peer_address_book_database_dir_path(),
+            &create_db_if_missing(),
+        )
+        .await?;
+
+        Ok(PeerDatabases {
+            peer_standings,
+            peer_address_book,
+        })
     }
 
     /// Return a list of peer sanctions stored in the database.
@@ -115,4 +188,318 @@ impl NetworkingState {
                 .await
         }
     }
+
+    /// Add an address that a peer listens on to the address book, unless it is
+    /// already there. Returns true if the address was added.
+    pub async fn record_peer_address_seen(&mut self, listen_address: SocketAddr) -> bool {
+        if self.peer_address_book.contains_key(&listen_address) {
+            return false;
+        }
+
+        self.insert_peer_address(listen_address, PeerAddressBookEntry::new(SystemTime::now()))
+            .await
+    }
+
+    /// Add the addresses from a peer's answer to a peer list request to the address
+    /// book. At most [`MAX_NEW_PEER_ADDRESSES_PER_RESPONSE`] of them are added.
+    pub async fn record_peer_addresses_reported(&mut self, listen_addresses: &[SocketAddr]) {
+        let mut added_count = 0;
+        for &listen_address in listen_addresses {
+            if added_count == MAX_NEW_PEER_ADDRESSES_PER_RESPONSE {
+                break;
+            }
+            if self.record_peer_address_seen(listen_address).await {
+                added_count += 1;
+            }
+        }
+    }
+
+    /// Record in the address book that a connection to a peer listening on this
+    /// address was established.
+    pub async fn record_peer_connection_success(&mut self, listen_address: SocketAddr) {
+        let now = SystemTime::now();
+        let mut entry = self
+            .peer_address_book
+            .get(&listen_address)
+            .copied()
+            .unwrap_or_else(|| PeerAddressBookEntry::new(now));
+        entry.record_success(now);
+        self.insert_peer_address(listen_address, entry).await;
+    }
+
+    /// Trust the static key that the peer listening on this address presents in an
+    /// encrypted connection on first use: record it in the address book if no key is
+    /// recorded for the address yet, and fail if another key is.
+    ///
+    /// This only applies to outgoing connections, where the address is the one that was
+    /// dialed. An incoming peer only claims the address it listens on, so its key is not
+    /// checked against the address book, and it is not recorded either.
+    pub async fn check_peer_static_key(
+        &mut self,
+        listen_address: SocketAddr,
+        static_key: PeerStaticKey,
+    ) -> Result<()> {
+        let mut entry = self
+            .peer_address_book
+            .get(&listen_address)
+            .copied()
+            .unwrap_or_else(|| PeerAddressBookEntry::new(SystemTime::now()));
+        match entry.static_key {
+            Some(recorded_key) if recorded_key == static_key => Ok(()),
+            Some(_) => bail!(
+                "Peer at {listen_address} presented another static key than the one recorded on first use"
+            ),
+            None => {
+                entry.static_key = Some(static_key);
+                self.insert_peer_address(listen_address, entry).await;
+                Ok(())
+            }
+        }
+    }
+
+    /// Record in the address book that an attempt to connect to this address failed.
+    /// Addresses that have failed for too long are removed.
+    pub async fn record_peer_connection_failure(&mut self, listen_address: SocketAddr) {
+        let Some(entry) = self.peer_address_book.get_mut(&listen_address) else {
+            return;
+        };
+        entry.record_failure(SystemTime::now());
+
+        if entry.is_abandoned() {
+            self.remove_peer_address(listen_address).await;
+        } else {
+            let entry = *entry;
+            self.peer_databases
+                .peer_address_book
+                .put(listen_address, entry)
+                .await
+        }
+    }
+
+    /// Put an entry into the address book. A new address only makes it into a full
+    /// address book if it replaces the lowest-scored entry, and only entries with
+    /// failed connection attempts score lower than a new one. Returns true if the
+    /// entry was stored.
+    async fn insert_peer_address(
+        &mut self,
+        listen_address: SocketAddr,
+        entry: PeerAddressBookEntry,
+    ) -> bool {
+        if !self.peer_address_book.contains_key(&listen_address)
+            && self.peer_address_book.len() >= MAX_PEER_ADDRESS_BOOK_SIZE
+        {
+            let lowest_scored = self
+                .peer_address_book
+                .iter()
+                .min_by_key(|(_, entry)| entry.score(None))
+                .map(|(address, entry)| (*address, entry.score(None)));
+            match lowest_scored {
+                Some((address, score)) if score < entry.score(None) => {
+                    self.remove_peer_address(address).await
+                }
+                _ => return false,
+            }
+        }
+
+        self.peer_address_book.insert(listen_address, entry);
+        self.peer_databases
+            .peer_address_book
+            .put(listen_address, entry)
+            .await;
+        true
+    }
+
+    async fn remove_peer_address(&mut self, listen_address: SocketAddr) {
+        self.peer_address_book.remove(&listen_address);
+        self.peer_databases
+            .peer_address_book
+            .delete(listen_address)
+            .await;
+    }
+
+    /// Return the addresses from the address book that may be connected to now,
+    /// best score first. Addresses that are connected to, whose backoff has not
+    /// passed, or whose IP is in worse standing than `peer_tolerance` allows, are
+    /// left out.
+    pub async fn peer_address_book_candidates(&self, peer_tolerance: u16) -> Vec<SocketAddr> {
+        let now = SystemTime::now();
+        let connected = self
+            .peer_map
+            .values()
+            .flat_map(|peer| [Some(peer.connected_address), peer.listen_address()])
+            .flatten()
+            .collect_vec();
+
+        let due_entries = self
+            .peer_address_book
+            .iter()
+            .filter(|(address, entry)| entry.is_due(now) && !connected.contains(address))
+            .collect_vec();
+
+        let mut candidates = vec![];
+        for (&address, entry) in due_entries {
+            let standing = self.get_peer_standing_from_database(address.ip()).await;
+            if standing.is_some_and(|s| s.standing < -(peer_tolerance as i32)) {
+                continue;
+            }
+            candidates.push((address, entry.score(standing)));
+        }
+
+        candidates
+            .into_iter()
+            .sorted_by_key(|(_, score)| -score)
+            .map(|(address, _)| address)
+            .collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::config_models::network::Network;
+    use crate::tests::shared::{get_dummy_socket_address, get_peer_map, unit_test_databases};
+    use rand::random;
+
+    #[tokio::test]
+    async fn address_book_candidates_respect_backoff_and_standing() -> Result<()> {
+        let (_block_db, peer_databases, _data_dir) = unit_test_databases(Network::Alpha).await?;
+        let mut net = NetworkingState::new(
+            get_peer_map(),
+            peer_databases,
+            false,
+            PeerTransportKey::generate(),
+        );
+        let reliable = get_dummy_socket_address(1);
+        let new = get_dummy_socket_address(2);
+        let failing = get_dummy_socket_address(3);
+        let banned = get_dummy_socket_address(4);
+
+        net.record_peer_address_seen(new).await;
+        net.record_peer_connection_success(reliable).await;
+        net.record_peer_address_seen(failing).await;
+        net.record_peer_connection_failure(failing).await;
+        net.record_peer_address_seen(banned).await;
+        net.write_peer_standing_on_decrease(
+            banned.ip(),
+            PeerStanding {
+                standing: -1000,
+                ..Default::default()
+            },
+        )
+        .await;
+
+        assert_eq!(
+            vec![reliable, new],
+            net.peer_address_book_candidates(100).await
+        );
+
+        // Seeing an address again does not reset its history
+        net.record_peer_address_seen(failing).await;
+        assert_eq!(
+            1,
+            net.peer_databases
+                .peer_address_book
+                .get(failing)
+                .await
+                .unwrap()
+                .consecutive_failures
+        );
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn address_book_is_bounded() -> Result<()> {
+        let (_block_db, peer_databases, _data_dir) = unit_test_databases(Network::Alpha).await?;
+        let mut net = NetworkingState::new(
+            get_peer_map(),
+            peer_databases,
+            false,
+            PeerTransportKey::generate(),
+        );
+        let address = |i: usize| SocketAddr::from(([10, 0, (i >> 8) as u8, i as u8], 8080));
+
+        // A single peer list response only adds a few addresses
+        let reported = (0..MAX_PEER_ADDRESS_BOOK_SIZE).map(address).collect_vec();
+        net.record_peer_addresses_reported(&reported).await;
+        assert_eq!(
+            MAX_NEW_PEER_ADDRESSES_PER_RESPONSE,
+            net.peer_address_book.len()
+        );
+
+        // A full address book only takes new addresses in place of failing ones
+        for i in 0..MAX_PEER_ADDRESS_BOOK_SIZE {
+            net.record_peer_address_seen(address(i)).await;
+        }
+        assert_eq!(MAX_PEER_ADDRESS_BOOK_SIZE, net.peer_address_book.len());
+        let newcomer = address(MAX_PEER_ADDRESS_BOOK_SIZE);
+        assert!(!net.record_peer_address_seen(newcomer).await);
+
+        let failing = address(7);
+        net.record_peer_connection_failure(failing).await;
+        assert!(net.record_peer_address_seen(newcomer).await);
+        assert_eq!(MAX_PEER_ADDRESS_BOOK_SIZE, net.peer_address_book.len());
+        assert!(!net.peer_address_book.contains_key(&failing));
+        assert!(net
+            .peer_databases
+            .peer_address_book
+            .get(failing)
+            .await
+            .is_none());
+
+        // Addresses that keep failing are dropped, also from the database
+        while net.peer_address_book.contains_key(&newcomer) {
+            net.record_peer_connection_failure(newcomer).await;
+        }
+        assert!(net
+            .peer_databases
+            .peer_address_book
+            .get(newcomer)
+            .await
+            .is_none());
+
+        // The address book is restored from the database
+        let restored = NetworkingState::new(
+            get_peer_map(),
+            net.peer_databases.clone(),
+            false,
+            PeerTransportKey::generate(),
+        );
+        assert_eq!(net.peer_address_book, restored.peer_address_book);
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn peer_static_key_is_trusted_on_first_use() -> Result<()> {
+        let (_block_db, peer_databases, _data_dir) = unit_test_databases(Network::Alpha).await?;
+        let mut net = NetworkingState::new(
+            get_peer_map(),
+            peer_databases,
+            false,
+            PeerTransportKey::generate(),
+        );
+        let address = SocketAddr::from(([10, 0, 0, 8], 8080));
+        let (key, other_key) = (random(), random());
+
+        net.check_peer_static_key(address, key).await?;
+        net.check_peer_static_key(address, key).await?;
+        assert!(net.check_peer_static_key(address, other_key).await.is_err());
+
+        // Connection outcomes do not change the recorded key, which is persisted
+        net.record_peer_connection_success(address).await;
+        net.record_peer_connection_failure(address).await;
+        assert_eq!(Some(key), net.peer_address_book[&address].static_key);
+        assert_eq!(
+            Some(key),
+            net.peer_databases
+                .peer_address_book
+                .get(address)
+                .await
+                .unwrap()
+                .static_key
+        );
+
+        Ok(())
+    }
 }
@@ -1 +1,2 @@
 pub mod generation_address;
+pub mod multisig_address;
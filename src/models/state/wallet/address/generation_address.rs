@@ -13,6 +13,7 @@ use rand::thread_rng;
 use rand::Rng;
 use serde_derive::Deserialize;
 use serde_derive::Serialize;
+use triton_vm::instruction::LabelledInstruction;
 use triton_vm::triton_asm;
 use triton_vm::triton_instr;
 use twenty_first::shared_math::lattice::kem::CIPHERTEXT_SIZE_IN_BFES;
@@ -24,6 +25,7 @@ use twenty_first::{
 
 use crate::config_models::network::Network;
 use crate::models::blockchain::shared::Hash;
+use crate::models::blockchain::transaction::timelock::Timelock;
 use crate::models::blockchain::transaction::utxo::LockScript;
 use crate::models::blockchain::transaction::utxo::Utxo;
 use crate::models::blockchain::transaction::PublicAnnouncement;
@@ -42,6 +44,18 @@ pub struct SpendingKey {
     pub seed: Digest,
 }
 
+/// The part of a [`SpendingKey`] that suffices to recognize and decrypt the UTXOs sent to
+/// its address, but not to unlock them. Watch-only wallets hold view keys instead of the
+/// wallet secret. The derivation index of the spending key is kept, such that a wallet
+/// secret kept elsewhere can derive the spending key for the UTXOs that the view key finds.
+#[derive(Clone, Debug, Copy, Serialize, Deserialize, PartialEq, Eq)]
+pub struct ViewKey {
+    pub decryption_key: lattice::kem::SecretKey,
+    pub privacy_preimage: Digest,
+    pub receiving_address: ReceivingAddress,
+    pub derivation_index: u16,
+}
+
 #[derive(Debug, Clone, Copy, Serialize, Deserialize, PartialEq, Eq)]
 pub struct ReceivingAddress {
     pub receiver_identifier: BFieldElement,
@@ -56,6 +70,16 @@ fn public_announcement_is_marked(announcement: &PublicAnnouncement) -> bool {
     matches!(announcement.message.first(), Some(&GENERATION_FLAG))
 }
 
+/// The last character of the human-readable part of bech32m encodings, which identifies
+/// the network.
+pub(crate) fn network_byte(network: Network) -> char {
+    match network {
+        Network::Alpha => 'm',
+        Network::Testnet => 't',
+        Network::RegTest => 'r',
+    }
+}
+
 fn derive_receiver_id(seed: Digest) -> BFieldElement {
     Hash::hash_varlen(&[seed.values().to_vec(), vec![BFieldElement::new(2)]].concat()).values()[0]
 }
@@ -141,6 +165,110 @@ pub fn std_lockscript_reference_verify_unlock(
     spending_lock == Digest::new(witness_data).hash::<Hash>()
 }
 
+/// Return the UTXOs announced to the generation address with the given receiver identifier,
+/// as a list of (addition record, utxo, sender randomness, receiver preimage).
+fn scan_for_announced_utxos(
+    receiver_identifier: BFieldElement,
+    decryption_key: lattice::kem::SecretKey,
+    privacy_preimage: Digest,
+    transaction: &Transaction,
+) -> Vec<(AdditionRecord, Utxo, Digest, Digest)> {
+    decrypt_announcements(receiver_identifier, decryption_key, transaction)
+        .into_iter()
+        .map(|(utxo, sender_randomness, _timelock)| {
+            // join those with the receiver digest to get a commitment
+            // Note: the commitment is computed in the same way as in the mutator set.
+            let receiver_preimage = privacy_preimage;
+            let receiver_digest = receiver_preimage.hash::<Hash>();
+            let addition_record = commit(Hash::hash(&utxo), sender_randomness, receiver_digest);
+            (addition_record, utxo, sender_randomness, receiver_preimage)
+        })
+        .collect()
+}
+
+/// Decrypt the announcements to the generation address with the given receiver identifier,
+/// as a list of (utxo, sender randomness, time lock if time-locked).
+fn decrypt_announcements(
+    receiver_identifier: BFieldElement,
+    decryption_key: lattice::kem::SecretKey,
+    transaction: &Transaction,
+) -> Vec<(Utxo, Digest, Option<Timelock>)> {
+    let mut decrypted_announcements = vec![];
+
+    // for all public scripts that contain a ciphertext for me,
+    for matching_announcement in transaction
+        .kernel
+        .public_announcements
+        .iter()
+        .filter(|pa| public_announcement_is_marked(pa))
+        .filter(|pa| {
+            let receiver_id = receiver_identifier_from_public_announcement(pa);
+            match receiver_id {
+                Ok(recid) => recid == receiver_identifier,
+                Err(_) => false,
+            }
+        })
+    {
+        // decrypt it to obtain the utxo and sender randomness
+        let ciphertext = ciphertext_from_public_announcement(matching_announcement);
+        let decryption_result = match ciphertext {
+            Ok(ctxt) => decrypt(decryption_key, &ctxt),
+            _ => {
+                continue;
+            }
+        };
+        match decryption_result {
+            Ok(triple) => decrypted_announcements.push(triple),
+            _ => {
+                continue;
+            }
+        };
+    }
+
+    decrypted_announcements
+}
+
+/// Decrypt a Generation Address ciphertext into the UTXO, the sender randomness and, if the
+/// UTXO is time-locked, its time lock
+fn decrypt(
+    decryption_key: lattice::kem::SecretKey,
+    ciphertext: &[BFieldElement],
+) -> Result<(Utxo, Digest, Option<Timelock>)> {
+    // parse ciphertext
+    if ciphertext.len() <= CIPHERTEXT_SIZE_IN_BFES {
+        bail!("Ciphertext does not have nonce.");
+    }
+    let (kem_ctxt, remainder_ctxt) = ciphertext.split_at(CIPHERTEXT_SIZE_IN_BFES);
+    if remainder_ctxt.len() <= 1 {
+        bail!("Ciphertext does not have payload.")
+    }
+    let (nonce_ctxt, dem_ctxt) = remainder_ctxt.split_at(1);
+    let kem_ctxt_array: [BFieldElement; CIPHERTEXT_SIZE_IN_BFES] = kem_ctxt.try_into().unwrap();
+
+    // decrypt
+    let shared_key = match lattice::kem::dec(decryption_key, kem_ctxt_array.into()) {
+        Some(sk) => sk,
+        None => bail!("Could not establish shared secret key."),
+    };
+    let cipher = Aes256Gcm::new(&shared_key.into());
+    let nonce_as_bytes = [nonce_ctxt[0].value().to_be_bytes().to_vec(), vec![0u8; 4]].concat();
+    let nonce = Nonce::from_slice(&nonce_as_bytes); // almost 64 bits; unique per message
+    let ciphertext_bytes = bfes_to_bytes(dem_ctxt)?;
+    let plaintext = match cipher.decrypt(nonce, ciphertext_bytes.as_ref()) {
+        Ok(ptxt) => ptxt,
+        Err(_) => bail!("Failed to decrypt symmetric payload."),
+    };
+
+    // convert plaintext to utxo and digest, followed by the time lock if time-locked
+    if let Ok((utxo, sender_randomness, timelock)) =
+        bincode::deserialize::<(Utxo, Digest, Timelock)>(&plaintext)
+    {
+        return Ok((utxo, sender_randomness, Some(timelock)));
+    }
+    let (utxo, sender_randomness) = bincode::deserialize(&plaintext)?;
+    Ok((utxo, sender_randomness, None))
+}
+
 impl SpendingKey {
     pub fn to_address(&self) -> ReceivingAddress {
         let randomness: [u8; 32] = shake256(&bincode::serialize(&self.seed).unwrap(), 32)
@@ -156,58 +284,28 @@ impl SpendingKey {
         }
     }
 
+    /// Return the view key of this spending key, which can be handed to a watch-only wallet.
+    /// The derivation index is the one with which the wallet secret derived this key.
+    pub fn to_view_key(&self, derivation_index: u16) -> ViewKey {
+        ViewKey {
+            decryption_key: self.decryption_key,
+            privacy_preimage: self.privacy_preimage,
+            receiving_address: self.to_address(),
+            derivation_index,
+        }
+    }
+
     /// Return announces a list of (addition record, utxo, sender randomness, receiver preimage)
     pub fn scan_for_announced_utxos(
         &self,
         transaction: &Transaction,
     ) -> Vec<(AdditionRecord, Utxo, Digest, Digest)> {
-        let mut received_utxos_with_randomnesses = vec![];
-
-        // for all public scripts that contain a ciphertext for me,
-        for matching_announcement in transaction
-            .kernel
-            .public_announcements
-            .iter()
-            .filter(|pa| public_announcement_is_marked(pa))
-            .filter(|pa| {
-                let receiver_id = receiver_identifier_from_public_announcement(pa);
-                match receiver_id {
-                    Ok(recid) => recid == self.receiver_identifier,
-                    Err(_) => false,
-                }
-            })
-        {
-            // decrypt it to obtain the utxo and sender randomness
-            let ciphertext = ciphertext_from_public_announcement(matching_announcement);
-            let decryption_result = match ciphertext {
-                Ok(ctxt) => self.decrypt(&ctxt),
-                _ => {
-                    continue;
-                }
-            };
-            let (utxo, sender_randomness) = match decryption_result {
-                Ok(tuple) => tuple,
-                _ => {
-                    continue;
-                }
-            };
-
-            // and join those with the receiver digest to get a commitment
-            // Note: the commitment is computed in the same way as in the mutator set.
-            let receiver_preimage = self.privacy_preimage;
-            let receiver_digest = receiver_preimage.hash::<Hash>();
-            let addition_record = commit(Hash::hash(&utxo), sender_randomness, receiver_digest);
-
-            // push to list
-            received_utxos_with_randomnesses.push((
-                addition_record,
-                utxo,
-                sender_randomness,
-                receiver_preimage,
-            ));
-        }
-
-        received_utxos_with_randomnesses
+        scan_for_announced_utxos(
+            self.receiver_identifier,
+            self.decryption_key,
+            self.privacy_preimage,
+            transaction,
+        )
     }
 
     pub fn derive_from_seed(seed: Digest) -> Self {
@@ -243,37 +341,6 @@ impl SpendingKey {
         spending_key
     }
 
-    /// Decrypt a Generation Address ciphertext
-    fn decrypt(&self, ciphertext: &[BFieldElement]) -> Result<(Utxo, Digest)> {
-        // parse ciphertext
-        if ciphertext.len() <= CIPHERTEXT_SIZE_IN_BFES {
-            bail!("Ciphertext does not have nonce.");
-        }
-        let (kem_ctxt, remainder_ctxt) = ciphertext.split_at(CIPHERTEXT_SIZE_IN_BFES);
-        if remainder_ctxt.len() <= 1 {
-            bail!("Ciphertext does not have payload.")
-        }
-        let (nonce_ctxt, dem_ctxt) = remainder_ctxt.split_at(1);
-        let kem_ctxt_array: [BFieldElement; CIPHERTEXT_SIZE_IN_BFES] = kem_ctxt.try_into().unwrap();
-
-        // decrypt
-        let shared_key = match lattice::kem::dec(self.decryption_key, kem_ctxt_array.into()) {
-            Some(sk) => sk,
-            None => bail!("Could not establish shared secret key."),
-        };
-        let cipher = Aes256Gcm::new(&shared_key.into());
-        let nonce_as_bytes = [nonce_ctxt[0].value().to_be_bytes().to_vec(), vec![0u8; 4]].concat();
-        let nonce = Nonce::from_slice(&nonce_as_bytes); // almost 64 bits; unique per message
-        let ciphertext_bytes = bfes_to_bytes(dem_ctxt)?;
-        let plaintext = match cipher.decrypt(nonce, ciphertext_bytes.as_ref()) {
-            Ok(ptxt) => ptxt,
-            Err(_) => bail!("Failed to decrypt symmetric payload."),
-        };
-
-        // convert plaintext to utxo and digest
-        Ok(bincode::deserialize(&plaintext)?)
-    }
-
     fn generate_spending_lock(&self) -> Digest {
         self.unlock_key.hash::<Hash>()
     }
@@ -286,6 +353,87 @@ impl SpendingKey {
     }
 }
 
+impl ViewKey {
+    /// Return announces a list of (addition record, utxo, sender randomness, receiver preimage)
+    pub fn scan_for_announced_utxos(
+        &self,
+        transaction: &Transaction,
+    ) -> Vec<(AdditionRecord, Utxo, Digest, Digest)> {
+        scan_for_announced_utxos(
+            self.receiving_address.receiver_identifier,
+            self.decryption_key,
+            self.privacy_preimage,
+            transaction,
+        )
+    }
+
+    /// Return the time locks of the time-locked UTXOs announced to this key's address, as a
+    /// list of (lock script hash, time lock). Announcements whose UTXO is not locked with
+    /// the time-locked lock script of this address are ignored.
+    pub fn scan_for_announced_timelocks(
+        &self,
+        transaction: &Transaction,
+    ) -> Vec<(Digest, Timelock)> {
+        decrypt_announcements(
+            self.receiving_address.receiver_identifier,
+            self.decryption_key,
+            transaction,
+        )
+        .into_iter()
+        .filter_map(|(utxo, _sender_randomness, timelock)| {
+            let timelock = timelock?;
+            let lock_script_hash = self
+                .receiving_address
+                .timelocked_lock_script(timelock)
+                .hash();
+            (utxo.lock_script_hash == lock_script_hash).then_some((lock_script_hash, timelock))
+        })
+        .collect()
+    }
+
+    fn get_hrp(network: Network) -> String {
+        // NOLGV: Neptune lattice-based generation view key
+        let mut hrp = "nolgv".to_string();
+        hrp.push(network_byte(network));
+        hrp
+    }
+
+    pub fn to_bech32m(&self, network: Network) -> Result<String> {
+        let hrp = Self::get_hrp(network);
+        let payload = bincode::serialize(self)?;
+        match bech32::encode(&hrp, payload.to_base32(), Variant::Bech32m) {
+            Ok(enc) => Ok(enc),
+            Err(e) => bail!("Could not encode view key as bech32m because error: {e}"),
+        }
+    }
+
+    pub fn from_bech32m(encoded: &str, network: Network) -> Result<Self> {
+        let (hrp, data, variant) = bech32::decode(encoded)?;
+
+        if variant != Variant::Bech32m {
+            bail!("Can only decode bech32m view keys.");
+        }
+
+        if hrp != Self::get_hrp(network) {
+            bail!("Could not decode bech32m view key because of invalid prefix");
+        }
+
+        let payload = Vec::<u8>::from_base32(&data)?;
+        let view_key: Self = match bincode::deserialize(&payload) {
+            Ok(view_key) => view_key,
+            Err(e) => bail!("Could not decode bech32m view key because of error: {e}"),
+        };
+
+        // A privacy preimage that does not belong to the address would make the wallet
+        // compute the wrong commitments, and miss the UTXOs sent to it.
+        if view_key.privacy_preimage.hash::<Hash>() != view_key.receiving_address.privacy_digest {
+            bail!("View key does not match its receiving address");
+        }
+
+        Ok(view_key)
+    }
+}
+
 impl ReceivingAddress {
     pub fn from_spending_key(spending_key: &SpendingKey) -> Self {
         let seed = spending_key.seed;
@@ -309,6 +457,21 @@ impl ReceivingAddress {
     }
 
     pub fn encrypt(&self, utxo: &Utxo, sender_randomness: Digest) -> Result<Vec<BFieldElement>> {
+        self.encrypt_plaintext(&bincode::serialize(&(utxo, sender_randomness))?)
+    }
+
+    /// Encrypt a time-locked UTXO. The time lock is needed to recognize and unlock the
+    /// UTXO, as it is part of the UTXO's lock script.
+    pub fn encrypt_timelocked(
+        &self,
+        utxo: &Utxo,
+        sender_randomness: Digest,
+        timelock: Timelock,
+    ) -> Result<Vec<BFieldElement>> {
+        self.encrypt_plaintext(&bincode::serialize(&(utxo, sender_randomness, timelock))?)
+    }
+
+    fn encrypt_plaintext(&self, plaintext: &[u8]) -> Result<Vec<BFieldElement>> {
         // derive shared key
         let mut randomness = [0u8; 32];
         let mut rng = thread_rng();
@@ -318,9 +481,6 @@ impl ReceivingAddress {
         // sample nonce
         let nonce_bfe: BFieldElement = rng.gen();
 
-        // convert payload to bytes
-        let plaintext = bincode::serialize(&(utxo, sender_randomness))?;
-
         // generate symmetric ciphertext
         let cipher = Aes256Gcm::new(&shared_key.into());
         let nonce_as_bytes = [nonce_bfe.value().to_be_bytes().to_vec(), vec![0u8; 4]].concat();
@@ -354,37 +514,67 @@ impl ReceivingAddress {
         Ok(PublicAnnouncement::new(ciphertext))
     }
 
+    /// Generate a public announcement of a UTXO that is locked with
+    /// [`timelocked_lock_script`](Self::timelocked_lock_script).
+    pub fn generate_timelocked_public_announcement(
+        &self,
+        utxo: &Utxo,
+        sender_randomness: Digest,
+        timelock: Timelock,
+    ) -> Result<PublicAnnouncement> {
+        let mut ciphertext = vec![GENERATION_FLAG, self.receiver_identifier];
+        ciphertext.append(&mut self.encrypt_timelocked(utxo, sender_randomness, timelock)?);
+
+        Ok(PublicAnnouncement::new(ciphertext))
+    }
+
     /// Generate a lock script from the spending lock. Satisfaction
     /// of this lock script establishes the UTXO owner's assent to
     /// the transaction. The logic contained in here should be
     /// identical to `verify_unlock`.
     pub fn lock_script(&self) -> LockScript {
+        let instructions = triton_asm!(
+            {&self.spending_lock_check()}
+            read_io 5
+            halt
+        );
+
+        instructions.into()
+    }
+
+    /// Generate a lock script that, besides the owner's assent, requires the time lock to
+    /// be released for the transaction. The secret input is that of
+    /// [`lock_script`](Self::lock_script), followed by the
+    /// [`witness`](Timelock::witness) of the time lock for the transaction kernel.
+    pub fn timelocked_lock_script(&self, timelock: Timelock) -> LockScript {
+        let instructions = triton_asm!(
+            {&self.spending_lock_check()}
+            {&timelock.check()}
+            halt
+        );
+
+        instructions.into()
+    }
+
+    /// The instructions that verify that the secret input starts with the unlock key.
+    fn spending_lock_check(&self) -> Vec<LabelledInstruction> {
         let mut push_spending_lock_digest_to_stack = vec![];
         for elem in self.spending_lock.values().iter().rev() {
             push_spending_lock_digest_to_stack.push(triton_instr!(push elem.value()));
         }
 
-        let instructions = triton_asm!(
+        triton_asm!(
             divine 5
             hash
             {&push_spending_lock_digest_to_stack}
             assert_vector
-            read_io 5
-            halt
-        );
-
-        instructions.into()
+        )
     }
 
     fn get_hrp(network: Network) -> String {
         // NOLGA: Neptune lattice-based generation address
         let mut hrp = "nolga".to_string();
-        let network_byte: char = match network {
-            Network::Alpha => 'm',
-            Network::Testnet => 't',
-            Network::RegTest => 'r',
-        };
-        hrp.push(network_byte);
+        hrp.push(network_byte(network));
         hrp
     }
 
@@ -549,11 +739,74 @@ mod test_generation_addresses {
         let ciphertext = receiving_address.encrypt(&utxo, sender_randomness).unwrap();
         println!("ciphertext.get_size() = {}", ciphertext.len() * 8);
 
-        let (utxo_again, sender_randomness_again) = spending_key.decrypt(&ciphertext).unwrap();
+        let (utxo_again, sender_randomness_again, timelock) =
+            decrypt(spending_key.decryption_key, &ciphertext).unwrap();
 
         assert_eq!(utxo, utxo_again);
 
         assert_eq!(sender_randomness, sender_randomness_again);
+        assert!(timelock.is_none());
+
+        for timelock in [Timelock::Time(1_700_000_000_000), Timelock::Height(1000)] {
+            let ciphertext = receiving_address
+                .encrypt_timelocked(&utxo, sender_randomness, timelock)
+                .unwrap();
+            assert_eq!(
+                (utxo.clone(), sender_randomness, Some(timelock)),
+                decrypt(spending_key.decryption_key, &ciphertext).unwrap()
+            );
+        }
+    }
+
+    #[test]
+    fn timelocked_utxos_are_recognized_with_their_timelock_test() {
+        let spending_key = SpendingKey::derive_from_seed(random());
+        let view_key = spending_key.to_view_key(0);
+        let receiving_address = spending_key.to_address();
+        let release_time = 1_700_000_000_000;
+        let timelock = Timelock::Time(release_time);
+        let timelocked_lock_script = receiving_address.timelocked_lock_script(timelock);
+        assert_ne!(receiving_address.lock_script(), timelocked_lock_script);
+        assert_ne!(
+            timelocked_lock_script,
+            receiving_address.timelocked_lock_script(Timelock::Time(release_time + 1))
+        );
+        assert_ne!(
+            timelocked_lock_script,
+            receiving_address.timelocked_lock_script(Timelock::Height(release_time))
+        );
+
+        let timelocked_utxo = Utxo::new(
+            timelocked_lock_script.clone(),
+            NeptuneCoins::new(10).to_native_coins(),
+        );
+        let plain_utxo = Utxo::new(
+            receiving_address.lock_script(),
+            NeptuneCoins::new(10).to_native_coins(),
+        );
+        let mut mock_tx = make_mock_transaction(vec![], vec![]);
+        mock_tx.kernel.public_announcements = vec![
+            receiving_address
+                .generate_timelocked_public_announcement(&timelocked_utxo, random(), timelock)
+                .unwrap(),
+            receiving_address
+                .generate_public_announcement(&plain_utxo, random())
+                .unwrap(),
+            // A time lock that does not match the lock script is ignored
+            receiving_address
+                .generate_timelocked_public_announcement(
+                    &timelocked_utxo,
+                    random(),
+                    Timelock::Height(release_time),
+                )
+                .unwrap(),
+        ];
+
+        assert_eq!(3, view_key.scan_for_announced_utxos(&mock_tx).len());
+        assert_eq!(
+            vec![(timelocked_lock_script.hash(), timelock)],
+            view_key.scan_for_announced_timelocks(&mock_tx)
+        );
     }
 
     #[test]
@@ -600,4 +853,36 @@ mod test_generation_addresses {
         assert_eq!(sender_randomness, read_sender_randomness);
         assert_eq!(returned_receiver_preimage, spending_key.privacy_preimage);
     }
+
+    #[test]
+    fn view_key_recognizes_announced_utxos_test() {
+        let seed: Digest = thread_rng().gen();
+        let spending_key = SpendingKey::derive_from_seed(seed);
+        let view_key = spending_key.to_view_key(5);
+        assert_eq!(spending_key.to_address(), view_key.receiving_address);
+
+        let encoded = view_key.to_bech32m(Network::Testnet).unwrap();
+        let view_key = ViewKey::from_bech32m(&encoded, Network::Testnet).unwrap();
+        assert!(ViewKey::from_bech32m(&encoded, Network::Alpha).is_err());
+        assert_eq!(5, view_key.derivation_index);
+
+        let utxo = Utxo {
+            lock_script_hash: view_key.receiving_address.lock_script().hash(),
+            coins: NeptuneCoins::new(10).to_native_coins(),
+        };
+        let sender_randomness: Digest = random();
+        let mut mock_tx = make_mock_transaction(vec![], vec![]);
+        mock_tx.kernel.public_announcements.push(
+            view_key
+                .receiving_address
+                .generate_public_announcement(&utxo, sender_randomness)
+                .unwrap(),
+        );
+
+        assert_eq!(
+            spending_key.scan_for_announced_utxos(&mock_tx),
+            view_key.scan_for_announced_utxos(&mock_tx)
+        );
+        assert_eq!(1, view_key.scan_for_announced_utxos(&mock_tx).len());
+    }
 }
@@ -0,0 +1,580 @@
+//! Multi-signature addresses. UTXOs sent to an m-of-n multisig address can only be spent
+//! with the assent of m of its n participants.
+//!
+//! The participants sign the MAST hash of the transaction kernel with Winternitz one-time
+//! signatures over Tip5, which the lock script verifies against the kernel hash on its
+//! public input. A signature reveals nothing that lets others sign a different kernel, but
+//! a key that signs two different kernels is broken, so every key signs one transaction
+//! only.
+
+use crate::prelude::{triton_vm, twenty_first};
+
+use anyhow::{bail, Result};
+use bech32::{FromBase32, ToBase32, Variant};
+use itertools::Itertools;
+use serde_derive::{Deserialize, Serialize};
+use triton_vm::instruction::LabelledInstruction;
+use triton_vm::triton_asm;
+use twenty_first::shared_math::b_field_element::BFieldElement;
+use twenty_first::shared_math::bfield_codec::BFieldCodec;
+use twenty_first::shared_math::tip5::{Digest, DIGEST_LENGTH};
+use twenty_first::util_types::algebraic_hasher::AlgebraicHasher;
+
+use super::generation_address::{self, network_byte};
+use crate::config_models::network::Network;
+use crate::models::blockchain::shared::Hash;
+use crate::models::blockchain::transaction::utxo::LockScript;
+
+pub const MULTISIG_FLAG: BFieldElement = BFieldElement::new(77);
+
+/// The maximum number of participants of a multisig address. Bounds the size of the lock
+/// script, which grows linearly in the number of participants.
+pub const MAX_MULTISIG_PARTICIPANTS: usize = 16;
+
+/// The largest digit of the base-16 encoding of signed messages, which is also the length
+/// of the hash chains of signatures.
+const WOTS_MAX_DIGIT: u64 = 15;
+
+/// The number of base-16 digits of a signed message: 16 for each element of a digest.
+const WOTS_MESSAGE_DIGITS: usize = 80;
+
+/// The number of base-16 digits of the checksum of the message digits, which is at most
+/// 80 * 15 = 1200.
+const WOTS_CHECKSUM_DIGITS: usize = 3;
+
+/// The number of hash chains of a one-time key, one for each message and checksum digit.
+pub const WOTS_CHAIN_COUNT: usize = WOTS_MESSAGE_DIGITS + WOTS_CHECKSUM_DIGITS;
+
+/// The secret of one participant of multisig addresses, derived from the wallet secret.
+/// It is a one-time key: it must not sign more than one transaction kernel.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct MultisigKey {
+    pub seed: Digest,
+}
+
+impl MultisigKey {
+    fn chain_start(&self, chain: usize) -> Digest {
+        Hash::hash_varlen(&[self.seed.encode(), vec![BFieldElement::new(chain as u64)]].concat())
+    }
+
+    /// The public key, which commits to the ends of all hash chains of the key.
+    pub fn public_key(&self) -> Digest {
+        public_key_from_chain_ends(
+            (0..WOTS_CHAIN_COUNT).map(|chain| hash_chain(self.chain_start(chain), WOTS_MAX_DIGIT)),
+        )
+    }
+
+    /// Sign the given message, which is the MAST hash of a transaction kernel.
+    pub fn sign(&self, message: Digest) -> MultisigSignature {
+        let chain_elements = wots_digits(message)
+            .into_iter()
+            .enumerate()
+            .map(|(chain, digit)| hash_chain(self.chain_start(chain), digit))
+            .collect();
+
+        MultisigSignature(chain_elements)
+    }
+}
+
+/// A Winternitz one-time signature: for every digit of the message and its checksum, the
+/// element of the digit's hash chain at the position of the digit.
+#[derive(Clone, Debug, Serialize, Deserialize, PartialEq, Eq)]
+pub struct MultisigSignature(Vec<Digest>);
+
+impl MultisigSignature {
+    pub fn verify(&self, public_key: Digest, message: Digest) -> bool {
+        if self.0.len() != WOTS_CHAIN_COUNT {
+            return false;
+        }
+        let chain_ends = wots_digits(message)
+            .into_iter()
+            .zip(&self.0)
+            .map(|(digit, element)| hash_chain(*element, WOTS_MAX_DIGIT - digit));
+
+        public_key_from_chain_ends(chain_ends) == public_key
+    }
+}
+
+/// The base-16 digits of the elements of the message, most significant first, followed by
+/// the digits of their checksum. The checksum decreases when any message digit increases,
+/// so a signature of one message cannot be hashed forward into a signature of another.
+fn wots_digits(message: Digest) -> Vec<u64> {
+    let message_digits = message
+        .values()
+        .iter()
+        .flat_map(|element| {
+            let value = element.value();
+            (0..16)
+                .rev()
+                .map(move |position| (value >> (4 * position)) & 15)
+        })
+        .collect_vec();
+    let checksum: u64 = message_digits
+        .iter()
+        .map(|digit| WOTS_MAX_DIGIT - digit)
+        .sum();
+    let checksum_digits = (0..WOTS_CHECKSUM_DIGITS)
+        .rev()
+        .map(|position| (checksum >> (4 * position)) & 15);
+
+    message_digits.into_iter().chain(checksum_digits).collect()
+}
+
+/// Hash the digest the given number of times. One step is the hash of the zero digest and
+/// the digest, which the `hash` instruction computes from the digest with five zeros on
+/// top.
+fn hash_chain(start: Digest, steps: u64) -> Digest {
+    (0..steps).fold(start, |digest, _| {
+        Hash::hash_pair(Digest::default(), digest)
+    })
+}
+
+fn public_key_from_chain_ends(chain_ends: impl Iterator<Item = Digest>) -> Digest {
+    chain_ends.fold(Digest::default(), |public_key, chain_end| {
+        Hash::hash_pair(chain_end, public_key)
+    })
+}
+
+/// An m-of-n multisig address. Besides the public keys of the participants, it holds a
+/// generation receiving address, whose view key the participants share, for the
+/// notifications and the privacy digest of the UTXOs sent to it.
+#[derive(Clone, Debug, Serialize, Deserialize, PartialEq, Eq)]
+pub struct MultisigAddress {
+    pub threshold: u8,
+    pub public_keys: Vec<Digest>,
+    pub receiving_address: generation_address::ReceivingAddress,
+}
+
+impl MultisigAddress {
+    pub fn new(
+        threshold: u8,
+        public_keys: Vec<Digest>,
+        receiving_address: generation_address::ReceivingAddress,
+    ) -> Result<Self> {
+        let address = Self {
+            threshold,
+            public_keys,
+            receiving_address,
+        };
+        address.validate()?;
+
+        Ok(address)
+    }
+
+    fn validate(&self) -> Result<()> {
+        let participant_count = self.public_keys.len();
+        if participant_count > MAX_MULTISIG_PARTICIPANTS {
+            bail!("A multisig address can have at most {MAX_MULTISIG_PARTICIPANTS} participants, got {participant_count}");
+        }
+        if self.threshold == 0 || self.threshold as usize > participant_count {
+            bail!(
+                "Threshold must be between 1 and the number of participants {participant_count}, got {}",
+                self.threshold
+            );
+        }
+        if !self.public_keys.iter().all_unique() {
+            bail!("The public keys of a multisig address must be distinct");
+        }
+
+        Ok(())
+    }
+
+    /// The index of the participant with the given public key.
+    pub fn participant_index(&self, public_key: Digest) -> Option<usize> {
+        self.public_keys.iter().position(|key| *key == public_key)
+    }
+
+    /// Generate the lock script. For each participant, the secret input holds a flag that
+    /// says whether the participant signs, followed by the participant's signature of the
+    /// kernel MAST hash on the public input if the flag is set. Exactly `threshold` flags
+    /// must be set.
+    pub fn lock_script(&self) -> LockScript {
+        let participant_checks = (0..self.public_keys.len())
+            .map(Self::participant_check)
+            .concat();
+        let signature_checks = self
+            .public_keys
+            .iter()
+            .enumerate()
+            .map(|(index, public_key)| Self::signature_check(index, public_key))
+            .concat();
+        let threshold = self.threshold;
+        let instructions = triton_asm!(
+            read_io 5               // _ [kernel_hash]
+            push 0                  // _ [kernel_hash] signature_count
+            {&participant_checks}
+            push {threshold}
+            eq
+            assert
+            halt
+
+            {&signature_checks}
+            {&Self::wots_subroutines()}
+        );
+
+        instructions.into()
+    }
+
+    /// The instructions that add a participant's flag to the signature count, after
+    /// asserting that the flag is a bit, and verifying the participant's signature if the
+    /// flag is set.
+    fn participant_check(index: usize) -> Vec<LabelledInstruction> {
+        let signature_check = format!("multisig_signature_of_participant_{index}");
+        triton_asm!(
+            divine 1                // _ [k] count flag
+            dup 0
+            dup 0
+            mul
+            dup 1
+            eq
+            assert                  // _ [k] count flag
+
+            dup 0
+            skiz
+            call {signature_check}
+            add                     // _ [k] count'
+        )
+    }
+
+    /// The subroutine that verifies the signature of the participant with the given public
+    /// key. Every message digit is recomposed into the element of the kernel hash it is a
+    /// digit of, and the checksum digits into the checksum of the message digits.
+    fn signature_check(index: usize, public_key: &Digest) -> Vec<LabelledInstruction> {
+        let [key_0, key_1, key_2, key_3, key_4] =
+            public_key.values().map(|element| element.value());
+        let message_element_checks = (0..DIGEST_LENGTH)
+            .map(|element| {
+                // `read_io` leaves the last element of the kernel hash on top, so with a
+                // copy of the recomposed element on top, element i is at depth 14 - i
+                let element_depth = 14 - element;
+                let digit_checks = vec![triton_asm!(call multisig_wots_message_digit); 16].concat();
+                triton_asm!(
+                    {&digit_checks}     // _ [k] count flag checksum element [acc]
+                    dup 5
+                    dup {element_depth}
+                    eq
+                    assert
+
+                    // start the recomposition of the next element
+                    swap 5
+                    pop 1
+                    push 0
+                    swap 5
+                )
+            })
+            .concat();
+        let checksum_digit_checks =
+            vec![triton_asm!(call multisig_wots_checksum_digit); WOTS_CHECKSUM_DIGITS].concat();
+        let label = format!("multisig_signature_of_participant_{index}");
+        triton_asm!(
+            {label}:
+                push 0              // _ [k] count flag checksum
+                push 0              // _ [k] count flag checksum element
+                push 0 push 0 push 0 push 0 push 0
+                                    // _ [k] count flag checksum element [acc]
+                {&message_element_checks}
+                {&checksum_digit_checks}
+                dup 5
+                dup 7
+                eq
+                assert
+
+                // the chain ends must hash to the public key
+                push {key_4} push {key_3} push {key_2} push {key_1} push {key_0}
+                assert_vector
+                pop 5
+                pop 2               // _ [k] count flag
+                return
+        )
+    }
+
+    /// The subroutines that verify one digit of a signature. Each takes the stack
+    /// `_ checksum element [acc]`, reads a digit and its chain element from secret input,
+    /// appends the digit to `element`, hashes the chain element to the end of its chain,
+    /// and folds the chain end into `acc`. Message digits also add to the checksum.
+    fn wots_subroutines() -> Vec<LabelledInstruction> {
+        let digit_range_check = triton_asm!(
+            dup 0
+            split                   // _ digit hi lo
+            push 4294967280
+            and
+            push 0
+            eq
+            assert
+            push 0
+            eq
+            assert                  // _ digit
+        );
+
+        triton_asm!(
+            multisig_wots_message_digit:
+                divine 1            // _ checksum element [acc] digit
+                {&digit_range_check}
+                dup 0
+                push -1
+                mul
+                push {WOTS_MAX_DIGIT}
+                add                 // _ checksum element [acc] digit (15 - digit)
+                dup 8
+                add
+                swap 8
+                pop 1               // _ checksum' element [acc] digit
+                call multisig_wots_chain
+                return
+
+            multisig_wots_checksum_digit:
+                divine 1            // _ checksum element [acc] digit
+                {&digit_range_check}
+                call multisig_wots_chain
+                return
+
+            multisig_wots_chain:
+                dup 0
+                dup 7
+                push 16
+                mul
+                add                 // _ checksum element [acc] digit element'
+                swap 7
+                pop 1               // _ checksum element' [acc] digit
+                push -1
+                mul
+                push {WOTS_MAX_DIGIT}
+                add                 // _ checksum element' [acc] steps
+                divine 5            // _ checksum element' [acc] steps [chain_element]
+                call multisig_hash_chain
+                                    // _ checksum element' [acc] 0 [chain_end]
+                swap 1
+                swap 2
+                swap 3
+                swap 4
+                swap 5
+                pop 1               // _ checksum element' [acc] [chain_end]
+                hash                // _ checksum element' [acc']
+                return
+
+            // _ steps [digest] -> _ 0 [digest']
+            multisig_hash_chain:
+                dup 5
+                push 0
+                eq
+                skiz
+                return
+                push 0 push 0 push 0 push 0 push 0
+                hash
+                swap 5
+                push -1
+                add
+                swap 5
+                recurse
+        )
+    }
+
+    /// Return the secret input for the lock script from the signatures of the participants
+    /// of the given kernel hash, in the order of their public keys. Only the first
+    /// `threshold` signatures are used.
+    pub fn lock_script_witness(
+        &self,
+        kernel_hash: Digest,
+        signatures: &[Option<MultisigSignature>],
+    ) -> Result<Vec<BFieldElement>> {
+        if signatures.len() != self.public_keys.len() {
+            bail!(
+                "Expected {} signature slots, got {}",
+                self.public_keys.len(),
+                signatures.len()
+            );
+        }
+
+        let digits = wots_digits(kernel_hash);
+        let mut used_signatures = 0;
+        let mut witness = vec![];
+        for signature in signatures {
+            match signature {
+                Some(signature) if used_signatures < self.threshold => {
+                    if signature.0.len() != WOTS_CHAIN_COUNT {
+                        bail!("Signature has the wrong number of chain elements");
+                    }
+                    used_signatures += 1;
+                    witness.push(BFieldElement::new(1));
+                    for (digit, chain_element) in digits.iter().zip(&signature.0) {
+                        let mut reversed_chain_element = chain_element.encode();
+                        reversed_chain_element.reverse();
+                        witness.push(BFieldElement::new(*digit));
+                        witness.append(&mut reversed_chain_element);
+                    }
+                }
+                _ => witness.push(BFieldElement::new(0)),
+            }
+        }
+        if used_signatures < self.threshold {
+            bail!("Need {} signatures, got {used_signatures}", self.threshold);
+        }
+
+        Ok(witness)
+    }
+
+    fn get_hrp(network: Network) -> String {
+        // NMSIG: Neptune multisig address
+        let mut hrp = "nmsig".to_string();
+        hrp.push(network_byte(network));
+        hrp
+    }
+
+    pub fn to_bech32m(&self, network: Network) -> Result<String> {
+        let hrp = Self::get_hrp(network);
+        let payload = bincode::serialize(self)?;
+        match bech32::encode(&hrp, payload.to_base32(), Variant::Bech32m) {
+            Ok(enc) => Ok(enc),
+            Err(e) => bail!("Could not encode multisig address as bech32m because error: {e}"),
+        }
+    }
+
+    pub fn from_bech32m(encoded: &str, network: Network) -> Result<Self> {
+        let (hrp, data, variant) = bech32::decode(encoded)?;
+
+        if variant != Variant::Bech32m {
+            bail!("Can only decode bech32m multisig addresses.");
+        }
+
+        if hrp != Self::get_hrp(network) {
+            bail!("Could not decode bech32m multisig address because of invalid prefix");
+        }
+
+        let payload = Vec::<u8>::from_base32(&data)?;
+        let address: Self = match bincode::deserialize(&payload) {
+            Ok(address) => address,
+            Err(e) => bail!("Could not decode bech32m multisig address because of error: {e}"),
+        };
+        address.validate()?;
+
+        Ok(address)
+    }
+}
+
+#[cfg(test)]
+mod multisig_address_tests {
+    use rand::random;
+    use triton_vm::prelude::{NonDeterminism, PublicInput};
+
+    use super::*;
+    use crate::models::state::wallet::WalletSecret;
+
+    fn lock_script_halts(
+        address: &MultisigAddress,
+        kernel_hash: Digest,
+        witness: Vec<BFieldElement>,
+    ) -> bool {
+        address
+            .lock_script()
+            .program
+            .run(
+                PublicInput::new(kernel_hash.values().to_vec()),
+                NonDeterminism::new(witness),
+            )
+            .is_ok()
+    }
+
+    #[test]
+    fn multisig_lock_script_requires_threshold_signatures_test() {
+        let keys = (0..3)
+            .map(|index| WalletSecret::new_random().nth_multisig_key(index))
+            .collect_vec();
+        let receiving_address = WalletSecret::new_random()
+            .nth_generation_spending_key(0)
+            .to_address();
+        let address = MultisigAddress::new(
+            2,
+            keys.iter().map(|key| key.public_key()).collect(),
+            receiving_address,
+        )
+        .unwrap();
+
+        let kernel_hash: Digest = random();
+        let signature = |index: usize| Some(keys[index].sign(kernel_hash));
+        for signatures in [
+            [signature(0), signature(1), None],
+            [None, signature(1), signature(2)],
+            [signature(0), signature(1), signature(2)],
+        ] {
+            let witness = address
+                .lock_script_witness(kernel_hash, &signatures)
+                .unwrap();
+            assert!(lock_script_halts(&address, kernel_hash, witness));
+        }
+        assert!(address
+            .lock_script_witness(kernel_hash, &[signature(0), None, None])
+            .is_err());
+
+        // Signatures are bound to the kernel they sign
+        let witness = address
+            .lock_script_witness(kernel_hash, &[signature(0), signature(1), None])
+            .unwrap();
+        let other_kernel_hash: Digest = random();
+        assert!(!lock_script_halts(
+            &address,
+            other_kernel_hash,
+            witness.clone()
+        ));
+
+        // A wrong chain element, a wrong digit, or too few signatures make the lock
+        // script crash
+        let mut wrong_chain_element = witness.clone();
+        wrong_chain_element[2] += BFieldElement::new(1);
+        assert!(!lock_script_halts(
+            &address,
+            kernel_hash,
+            wrong_chain_element
+        ));
+        let mut wrong_digit = witness.clone();
+        wrong_digit[1] += BFieldElement::new(1);
+        assert!(!lock_script_halts(&address, kernel_hash, wrong_digit));
+        let mut too_few = witness.clone();
+        too_few.truncate(1 + WOTS_CHAIN_COUNT * 6);
+        too_few.append(&mut vec![BFieldElement::new(0); 2]);
+        assert!(!lock_script_halts(&address, kernel_hash, too_few));
+
+        // Flags must be bits
+        let mut no_bit = witness;
+        no_bit[0] = BFieldElement::new(2);
+        assert!(!lock_script_halts(&address, kernel_hash, no_bit));
+    }
+
+    #[test]
+    fn multisig_signatures_verify_only_for_their_key_and_message_test() {
+        let key = WalletSecret::new_random().nth_multisig_key(0);
+        let other_key = WalletSecret::new_random().nth_multisig_key(0);
+        let message: Digest = random();
+        let other_message: Digest = random();
+
+        let signature = key.sign(message);
+        assert!(signature.verify(key.public_key(), message));
+        assert!(!signature.verify(key.public_key(), other_message));
+        assert!(!signature.verify(other_key.public_key(), message));
+
+        let digits = wots_digits(message);
+        assert_eq!(WOTS_CHAIN_COUNT, digits.len());
+        assert!(digits.iter().all(|digit| *digit <= WOTS_MAX_DIGIT));
+    }
+
+    #[test]
+    fn multisig_address_validation_and_bech32m_test() {
+        let public_keys: Vec<Digest> = vec![random(), random(), random()];
+        let receiving_address = WalletSecret::new_random()
+            .nth_generation_spending_key(0)
+            .to_address();
+        assert!(MultisigAddress::new(0, public_keys.clone(), receiving_address).is_err());
+        assert!(MultisigAddress::new(4, public_keys.clone(), receiving_address).is_err());
+        assert!(
+            MultisigAddress::new(1, vec![public_keys[0], public_keys[0]], receiving_address)
+                .is_err()
+        );
+
+        let address = MultisigAddress::new(2, public_keys, receiving_address).unwrap();
+        let encoded = address.to_bech32m(Network::Alpha).unwrap();
+        assert_eq!(
+            address,
+            MultisigAddress::from_bech32m(&encoded, Network::Alpha).unwrap()
+        );
+        assert!(MultisigAddress::from_bech32m(&encoded, Network::Testnet).is_err());
+    }
+}
@@ -0,0 +1,295 @@
+use itertools::Itertools;
+use num_traits::Zero;
+use rand::seq::SliceRandom;
+use rand::thread_rng;
+use serde::{Deserialize, Serialize};
+use std::cmp::Reverse;
+use std::fmt;
+use std::str::FromStr;
+use strum::EnumIter;
+
+use crate::models::blockchain::transaction::neptune_coins::NeptuneCoins;
+
+/// Maximum number of branches that branch-and-bound selection explores before it gives up
+/// on finding an exact match.
+const BRANCH_AND_BOUND_MAX_TRIES: usize = 100_000;
+
+/// A strategy for selecting which UTXOs fund a transaction.
+pub trait CoinSelector {
+    /// Select UTXOs whose amounts add up to at least `target`. `amounts` holds the amounts
+    /// of the spendable UTXOs. Returns the indices into `amounts` of the selected UTXOs, or
+    /// `None` if the amounts do not suffice.
+    fn select(&self, amounts: &[NeptuneCoins], target: NeptuneCoins) -> Option<Vec<usize>>;
+}
+
+/// Spend the largest UTXOs first. This minimizes the number of inputs and thereby the size
+/// of the transaction.
+pub struct LargestFirst;
+
+/// Spend the smallest UTXOs first. This consolidates many small UTXOs into fewer ones.
+pub struct SmallestFirst;
+
+/// Search for a set of UTXOs that adds up to exactly the target, such that no change output
+/// is needed. Falls back to [`LargestFirst`] if no exact match is found.
+pub struct BranchAndBound;
+
+/// Spend UTXOs in random order. This makes it harder for observers to link the UTXOs of a
+/// wallet through the way they are spent together.
+pub struct RandomSelection;
+
+/// Add UTXOs in the given order until the target is reached.
+fn select_in_order(
+    amounts: &[NeptuneCoins],
+    order: impl IntoIterator<Item = usize>,
+    target: NeptuneCoins,
+) -> Option<Vec<usize>> {
+    let mut selected = vec![];
+    let mut total = NeptuneCoins::zero();
+    for index in order {
+        if total >= target {
+            break;
+        }
+        selected.push(index);
+        total = total + amounts[index];
+    }
+
+    if total >= target {
+        Some(selected)
+    } else {
+        None
+    }
+}
+
+impl CoinSelector for LargestFirst {
+    fn select(&self, amounts: &[NeptuneCoins], target: NeptuneCoins) -> Option<Vec<usize>> {
+        let order = (0..amounts.len()).sorted_by_key(|index| Reverse(amounts[*index]));
+        select_in_order(amounts, order, target)
+    }
+}
+
+impl CoinSelector for SmallestFirst {
+    fn select(&self, amounts: &[NeptuneCoins], target: NeptuneCoins) -> Option<Vec<usize>> {
+        let order = (0..amounts.len()).sorted_by_key(|index| amounts[*index]);
+        select_in_order(amounts, order, target)
+    }
+}
+
+impl CoinSelector for RandomSelection {
+    fn select(&self, amounts: &[NeptuneCoins], target: NeptuneCoins) -> Option<Vec<usize>> {
+        let mut order = (0..amounts.len()).collect_vec();
+        order.shuffle(&mut thread_rng());
+        select_in_order(amounts, order, target)
+    }
+}
+
+impl BranchAndBound {
+    /// Depth-first search over the UTXOs in `order`, where each UTXO is either included or
+    /// excluded. `remaining[depth]` is the total amount of the UTXOs that are not decided
+    /// on yet, which bounds the search.
+    #[allow(clippy::too_many_arguments)]
+    fn search(
+        amounts: &[NeptuneCoins],
+        order: &[usize],
+        remaining: &[NeptuneCoins],
+        target: NeptuneCoins,
+        depth: usize,
+        total: NeptuneCoins,
+        selected: &mut Vec<usize>,
+        tries: &mut usize,
+    ) -> bool {
+        if total == target {
+            return true;
+        }
+        if depth == order.len()
+            || total > target
+            || total + remaining[depth] < target
+            || *tries >= BRANCH_AND_BOUND_MAX_TRIES
+        {
+            return false;
+        }
+        *tries += 1;
+
+        let index = order[depth];
+        selected.push(index);
+        if Self::search(
+            amounts,
+            order,
+            remaining,
+            target,
+            depth + 1,
+            total + amounts[index],
+            selected,
+            tries,
+        ) {
+            return true;
+        }
+        selected.pop();
+
+        Self::search(
+            amounts,
+            order,
+            remaining,
+            target,
+            depth + 1,
+            total,
+            selected,
+            tries,
+        )
+    }
+}
+
+impl CoinSelector for BranchAndBound {
+    fn select(&self, amounts: &[NeptuneCoins], target: NeptuneCoins) -> Option<Vec<usize>> {
+        // Trying large UTXOs first finds a match, or rules one out, faster
+        let order = (0..amounts.len())
+            .sorted_by_key(|index| Reverse(amounts[*index]))
+            .collect_vec();
+        let mut remaining = vec![NeptuneCoins::zero(); order.len() + 1];
+        for depth in (0..order.len()).rev() {
+            remaining[depth] = remaining[depth + 1] + amounts[order[depth]];
+        }
+
+        let mut selected = vec![];
+        let mut tries = 0;
+        let found_exact_match = Self::search(
+            amounts,
+            &order,
+            &remaining,
+            target,
+            0,
+            NeptuneCoins::zero(),
+            &mut selected,
+            &mut tries,
+        );
+        if found_exact_match {
+            return Some(selected);
+        }
+
+        LargestFirst.select(amounts, target)
+    }
+}
+
+/// The coin selection strategies that can be chosen when sending.
+#[derive(Clone, Copy, Debug, Serialize, Deserialize, PartialEq, Eq, Default, EnumIter)]
+pub enum CoinSelectionStrategy {
+    #[default]
+    LargestFirst,
+    SmallestFirst,
+    BranchAndBound,
+    Random,
+}
+
+impl CoinSelectionStrategy {
+    pub fn selector(&self) -> &'static dyn CoinSelector {
+        match self {
+            CoinSelectionStrategy::LargestFirst => &LargestFirst,
+            CoinSelectionStrategy::SmallestFirst => &SmallestFirst,
+            CoinSelectionStrategy::BranchAndBound => &BranchAndBound,
+            CoinSelectionStrategy::Random => &RandomSelection,
+        }
+    }
+}
+
+impl fmt::Display for CoinSelectionStrategy {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let string = match self {
+            CoinSelectionStrategy::LargestFirst => "largest-first",
+            CoinSelectionStrategy::SmallestFirst => "smallest-first",
+            CoinSelectionStrategy::BranchAndBound => "branch-and-bound",
+            CoinSelectionStrategy::Random => "random",
+        };
+        write!(f, "{}", string)
+    }
+}
+
+impl FromStr for CoinSelectionStrategy {
+    type Err = String;
+    fn from_str(input: &str) -> Result<CoinSelectionStrategy, Self::Err> {
+        match input {
+            "largest-first" => Ok(CoinSelectionStrategy::LargestFirst),
+            "smallest-first" => Ok(CoinSelectionStrategy::SmallestFirst),
+            "branch-and-bound" => Ok(CoinSelectionStrategy::BranchAndBound),
+            "random" => Ok(CoinSelectionStrategy::Random),
+            _ => Err(format!(
+                "Failed to parse {} as coin selection strategy",
+                input
+            )),
+        }
+    }
+}
+
+#[cfg(test)]
+mod coin_selection_tests {
+    use strum::IntoEnumIterator;
+
+    use super::*;
+
+    fn to_amounts(coins: &[u32]) -> Vec<NeptuneCoins> {
+        coins.iter().map(|c| NeptuneCoins::new(*c)).collect_vec()
+    }
+
+    fn selected_total(amounts: &[NeptuneCoins], selected: &[usize]) -> NeptuneCoins {
+        selected.iter().map(|index| amounts[*index]).sum()
+    }
+
+    #[test]
+    fn all_strategies_reach_target_or_fail() {
+        let amounts = to_amounts(&[5, 1, 20, 3, 8]);
+        for strategy in CoinSelectionStrategy::iter() {
+            for target in [0, 1, 7, 12, 37] {
+                let target = NeptuneCoins::new(target);
+                let selected = strategy.selector().select(&amounts, target).unwrap();
+                assert!(selected_total(&amounts, &selected) >= target);
+                assert!(selected.iter().all_unique());
+            }
+            assert!(strategy
+                .selector()
+                .select(&amounts, NeptuneCoins::new(38))
+                .is_none());
+        }
+    }
+
+    #[test]
+    fn largest_and_smallest_first_order() {
+        let amounts = to_amounts(&[5, 1, 20, 3, 8]);
+        assert_eq!(
+            vec![2],
+            LargestFirst
+                .select(&amounts, NeptuneCoins::new(10))
+                .unwrap()
+        );
+        assert_eq!(
+            vec![1, 3, 0],
+            SmallestFirst
+                .select(&amounts, NeptuneCoins::new(7))
+                .unwrap()
+        );
+    }
+
+    #[test]
+    fn branch_and_bound_finds_exact_match() {
+        let amounts = to_amounts(&[5, 1, 20, 3, 8]);
+        for target in [4, 9, 16, 29, 37] {
+            let target = NeptuneCoins::new(target);
+            let selected = BranchAndBound.select(&amounts, target).unwrap();
+            assert_eq!(target, selected_total(&amounts, &selected));
+        }
+
+        // Without an exact match, fall back to largest-first
+        let amounts = to_amounts(&[10, 10, 10]);
+        assert_eq!(
+            LargestFirst.select(&amounts, NeptuneCoins::new(15)),
+            BranchAndBound.select(&amounts, NeptuneCoins::new(15))
+        );
+    }
+
+    #[test]
+    fn strategy_string_roundtrip() {
+        for strategy in CoinSelectionStrategy::iter() {
+            assert_eq!(
+                strategy,
+                CoinSelectionStrategy::from_str(&strategy.to_string()).unwrap()
+            );
+        }
+    }
+}
@@ -0,0 +1,334 @@
+use aead::Aead;
+use aead::KeyInit;
+use aes_gcm::Aes256Gcm;
+use aes_gcm::Nonce;
+use anyhow::{anyhow, bail, ensure, Result};
+use argon2::{Algorithm, Argon2, Params, Version};
+use crossterm::event::{self, Event, KeyCode, KeyEventKind, KeyModifiers};
+use crossterm::terminal;
+use rand::{thread_rng, Rng};
+use serde::{Deserialize, Serialize};
+use std::io::{self, IsTerminal, Write};
+use zeroize::Zeroize;
+
+use super::WalletSecret;
+
+/// Version of the wallet file format in which the wallet secret is stored as plain JSON.
+pub const PLAINTEXT_WALLET_FILE_VERSION: u8 = 0;
+
+/// Version of the wallet file format in which the wallet secret is encrypted under a key
+/// derived from a user passphrase.
+pub const ENCRYPTED_WALLET_FILE_VERSION: u8 = 1;
+
+/// Argon2id parameters used for newly encrypted wallet files. Stored in the file so that
+/// they can be raised later without breaking existing wallets.
+const DEFAULT_KDF_MEMORY_COST_IN_KIB: u32 = 64 * 1024;
+const DEFAULT_KDF_ITERATIONS: u32 = 3;
+const DEFAULT_KDF_PARALLELISM: u32 = 1;
+
+/// Bounds on the Argon2id parameters read from wallet files. Weaker parameters would
+/// make guessing the passphrase cheap, and stronger ones could stall the node.
+const MIN_KDF_MEMORY_COST_IN_KIB: u32 = 8 * 1024;
+const MAX_KDF_MEMORY_COST_IN_KIB: u32 = 4 * 1024 * 1024;
+const MAX_KDF_ITERATIONS: u32 = 64;
+const MAX_KDF_PARALLELISM: u32 = 16;
+
+const SALT_LENGTH: usize = 32;
+const NONCE_LENGTH: usize = 12;
+const KEY_LENGTH: usize = 32;
+
+/// Only used to read the format version of a wallet file before deciding how to decode it.
+/// Both the plaintext `WalletSecret` and the `EncryptedWalletSecret` have a `version` field.
+#[derive(Deserialize)]
+pub(super) struct WalletFileHeader {
+    pub version: u8,
+}
+
+/// The parameters of the Argon2id key derivation of an encrypted wallet file
+#[derive(Clone, Copy, Debug, Serialize, Deserialize, PartialEq, Eq)]
+pub struct KeyDerivationParameters {
+    pub memory_cost_in_kib: u32,
+    pub iterations: u32,
+    pub parallelism: u32,
+}
+
+impl Default for KeyDerivationParameters {
+    fn default() -> Self {
+        Self {
+            memory_cost_in_kib: DEFAULT_KDF_MEMORY_COST_IN_KIB,
+            iterations: DEFAULT_KDF_ITERATIONS,
+            parallelism: DEFAULT_KDF_PARALLELISM,
+        }
+    }
+}
+
+impl KeyDerivationParameters {
+    fn validate(&self) -> Result<()> {
+        ensure!(
+            (MIN_KDF_MEMORY_COST_IN_KIB..=MAX_KDF_MEMORY_COST_IN_KIB)
+                .contains(&self.memory_cost_in_kib),
+            "Key derivation memory cost of {} KiB is outside the allowed range of {} to {} KiB.",
+            self.memory_cost_in_kib,
+            MIN_KDF_MEMORY_COST_IN_KIB,
+            MAX_KDF_MEMORY_COST_IN_KIB
+        );
+        ensure!(
+            (1..=MAX_KDF_ITERATIONS).contains(&self.iterations),
+            "Key derivation iteration count {} is outside the allowed range of 1 to {}.",
+            self.iterations,
+            MAX_KDF_ITERATIONS
+        );
+        ensure!(
+            (1..=MAX_KDF_PARALLELISM).contains(&self.parallelism),
+            "Key derivation parallelism {} is outside the allowed range of 1 to {}.",
+            self.parallelism,
+            MAX_KDF_PARALLELISM
+        );
+        Ok(())
+    }
+}
+
+/// The encrypted envelope of a [`WalletSecret`] as it is stored on disk.
+///
+/// The key is derived from the passphrase and a random salt with Argon2id, and the
+/// JSON-encoded wallet secret is encrypted and authenticated with AES-256-GCM. A wrong
+/// passphrase or a tampered file makes decryption fail.
+#[derive(Clone, Debug, Serialize, Deserialize, PartialEq, Eq)]
+pub struct EncryptedWalletSecret {
+    version: u8,
+    key_derivation: KeyDerivationParameters,
+    salt: Vec<u8>,
+    nonce: Vec<u8>,
+    ciphertext: Vec<u8>,
+}
+
+impl EncryptedWalletSecret {
+    /// Encrypt the wallet secret under a key derived from `passphrase`.
+    pub fn encrypt(wallet_secret: &WalletSecret, passphrase: &str) -> Result<Self> {
+        if passphrase.is_empty() {
+            bail!("Cannot encrypt wallet with an empty passphrase.");
+        }
+
+        let mut rng = thread_rng();
+        let salt: [u8; SALT_LENGTH] = rng.gen();
+        let nonce: [u8; NONCE_LENGTH] = rng.gen();
+        let key_derivation = KeyDerivationParameters::default();
+
+        let mut key = derive_key(passphrase, &salt, &key_derivation)?;
+        let cipher = Aes256Gcm::new(&key.into());
+        key.zeroize();
+
+        let mut plaintext = serde_json::to_vec(wallet_secret)?;
+        let ciphertext = cipher.encrypt(Nonce::from_slice(&nonce), plaintext.as_ref());
+        plaintext.zeroize();
+
+        let ciphertext = match ciphertext {
+            Ok(ctxt) => ctxt,
+            Err(_) => bail!("Could not encrypt wallet secret."),
+        };
+
+        Ok(Self {
+            version: ENCRYPTED_WALLET_FILE_VERSION,
+            key_derivation,
+            salt: salt.to_vec(),
+            nonce: nonce.to_vec(),
+            ciphertext,
+        })
+    }
+
+    /// Decrypt the wallet secret. Fails if the passphrase is wrong or if the file was
+    /// modified.
+    pub fn decrypt(&self, passphrase: &str) -> Result<WalletSecret> {
+        if self.version != ENCRYPTED_WALLET_FILE_VERSION {
+            bail!(
+                "Unsupported encrypted wallet file version {}. Expected version {}.",
+                self.version,
+                ENCRYPTED_WALLET_FILE_VERSION
+            );
+        }
+        if self.nonce.len() != NONCE_LENGTH {
+            bail!("Encrypted wallet file has a nonce of invalid length.");
+        }
+        if self.salt.len() != SALT_LENGTH {
+            bail!("Encrypted wallet file has a salt of invalid length.");
+        }
+
+        let mut key = derive_key(passphrase, &self.salt, &self.key_derivation)?;
+        let cipher = Aes256Gcm::new(&key.into());
+        key.zeroize();
+
+        let mut plaintext =
+            match cipher.decrypt(Nonce::from_slice(&self.nonce), self.ciphertext.as_ref()) {
+                Ok(ptxt) => ptxt,
+                Err(_) => bail!("Could not decrypt wallet. Wrong passphrase?"),
+            };
+        let wallet_secret = serde_json::from_slice::<WalletSecret>(&plaintext);
+        plaintext.zeroize();
+
+        Ok(wallet_secret?)
+    }
+}
+
+/// Derive a symmetric key from a passphrase with Argon2id. The salt prevents
+/// precomputation across wallets, and the memory and time costs make guessing
+/// passphrases expensive.
+fn derive_key(
+    passphrase: &str,
+    salt: &[u8],
+    parameters: &KeyDerivationParameters,
+) -> Result<[u8; KEY_LENGTH]> {
+    parameters.validate()?;
+    let params = Params::new(
+        parameters.memory_cost_in_kib,
+        parameters.iterations,
+        parameters.parallelism,
+        Some(KEY_LENGTH),
+    )
+    .map_err(|err| anyhow!("Invalid key derivation parameters: {err}"))?;
+
+    let mut key = [0u8; KEY_LENGTH];
+    Argon2::new(Algorithm::Argon2id, Version::V0x13, params)
+        .hash_password_into(passphrase.as_bytes(), salt, &mut key)
+        .map_err(|err| anyhow!("Key derivation failed: {err}"))?;
+    Ok(key)
+}
+
+/// Ask the user for a passphrase on the terminal without echoing it. If standard-in is
+/// not a terminal, the passphrase is read as a single line from standard-in.
+pub fn prompt_for_passphrase(prompt: &str) -> Result<String> {
+    print!("{prompt}");
+    io::stdout().flush()?;
+
+    let mut passphrase = String::new();
+    if !io::stdin().is_terminal() {
+        io::stdin().read_line(&mut passphrase)?;
+        let trimmed_length = passphrase.trim_end_matches(['\r', '\n']).len();
+        passphrase.truncate(trimmed_length);
+        return Ok(passphrase);
+    }
+
+    terminal::enable_raw_mode()?;
+    let read_result = loop {
+        match event::read() {
+            Ok(Event::Key(key)) if key.kind == KeyEventKind::Press => match key.code {
+                KeyCode::Enter => break Ok(()),
+                KeyCode::Backspace => {
+                    passphrase.pop();
+                }
+                KeyCode::Char('c') if key.modifiers.contains(KeyModifiers::CONTROL) => {
+                    break Err(anyhow!("Passphrase entry aborted."))
+                }
+                KeyCode::Char(c) => passphrase.push(c),
+                _ => {}
+            },
+            Ok(_) => {}
+            Err(err) => break Err(err.into()),
+        }
+    };
+    terminal::disable_raw_mode()?;
+    println!();
+
+    match read_result {
+        Ok(()) => Ok(passphrase),
+        Err(err) => {
+            passphrase.zeroize();
+            Err(err)
+        }
+    }
+}
+
+/// Ask the user for a new passphrase twice and return it if both entries match.
+pub fn prompt_for_new_passphrase() -> Result<String> {
+    let mut passphrase = prompt_for_passphrase("Enter new wallet passphrase: ")?;
+    let mut repeated = prompt_for_passphrase("Repeat new wallet passphrase: ")?;
+    let matches = passphrase == repeated;
+    repeated.zeroize();
+
+    if !matches {
+        passphrase.zeroize();
+        bail!("Passphrases do not match.");
+    }
+    if passphrase.is_empty() {
+        bail!("Passphrase cannot be empty.");
+    }
+
+    Ok(passphrase)
+}
+
+#[cfg(test)]
+mod encrypted_secret_tests {
+    use super::*;
+
+    #[test]
+    fn encrypt_decrypt_roundtrip() {
+        let wallet_secret = WalletSecret::new_random();
+        let encrypted = EncryptedWalletSecret::encrypt(&wallet_secret, "hunter2").unwrap();
+        let decrypted = encrypted.decrypt("hunter2").unwrap();
+        assert_eq!(wallet_secret, decrypted);
+    }
+
+    #[test]
+    fn wrong_passphrase_fails() {
+        let wallet_secret = WalletSecret::new_random();
+        let encrypted = EncryptedWalletSecret::encrypt(&wallet_secret, "hunter2").unwrap();
+        assert!(encrypted.decrypt("hunter3").is_err());
+        assert!(encrypted.decrypt("").is_err());
+    }
+
+    #[test]
+    fn tampered_ciphertext_fails() {
+        let wallet_secret = WalletSecret::new_random();
+        let mut encrypted = EncryptedWalletSecret::encrypt(&wallet_secret, "hunter2").unwrap();
+        encrypted.ciphertext[0] ^= 1;
+        assert!(encrypted.decrypt("hunter2").is_err());
+    }
+
+    #[test]
+    fn weak_or_excessive_key_derivation_parameters_are_rejected() {
+        let wallet_secret = WalletSecret::new_random();
+        let encrypted = EncryptedWalletSecret::encrypt(&wallet_secret, "hunter2").unwrap();
+
+        for key_derivation in [
+            KeyDerivationParameters {
+                iterations: 0,
+                ..Default::default()
+            },
+            KeyDerivationParameters {
+                iterations: u32::MAX,
+                ..Default::default()
+            },
+            KeyDerivationParameters {
+                memory_cost_in_kib: 1,
+                ..Default::default()
+            },
+            KeyDerivationParameters {
+                memory_cost_in_kib: u32::MAX,
+                ..Default::default()
+            },
+            KeyDerivationParameters {
+                parallelism: 0,
+                ..Default::default()
+            },
+        ] {
+            let tampered = EncryptedWalletSecret {
+                key_derivation,
+                ..encrypted.clone()
+            };
+            assert!(tampered.decrypt("hunter2").is_err());
+        }
+    }
+
+    #[test]
+    fn empty_passphrase_is_rejected() {
+        let wallet_secret = WalletSecret::new_random();
+        assert!(EncryptedWalletSecret::encrypt(&wallet_secret, "").is_err());
+    }
+
+    #[test]
+    fn ciphertext_does_not_contain_secret_seed() {
+        let wallet_secret = WalletSecret::new_random();
+        let encrypted = EncryptedWalletSecret::encrypt(&wallet_secret, "hunter2").unwrap();
+        let as_json = serde_json::to_string(&encrypted).unwrap();
+        assert!(!as_json.contains("secret_seed"));
+    }
+}
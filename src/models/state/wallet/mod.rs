@@ -1,11 +1,19 @@
 use crate::prelude::twenty_first;
 
 pub mod address;
+pub mod coin_selection;
+pub mod encrypted_secret;
 pub mod monitored_utxo;
+pub mod multisig;
+pub mod outgoing_transaction;
 pub mod rusty_wallet_database;
+pub mod unconfirmed_send;
+pub mod unsigned_transaction;
 pub mod utxo_notification_pool;
+pub mod utxo_transfer;
 pub mod wallet_state;
 pub mod wallet_status;
+pub mod watch_only;
 
 use anyhow::{bail, Context, Result};
 use bip39::Mnemonic;
@@ -27,14 +35,20 @@ use twenty_first::shared_math::b_field_element::BFieldElement;
 
 use crate::models::blockchain::block::block_height::BlockHeight;
 
+use crate::util_types::secret_file::write_secret_file;
 use crate::Hash;
 
-use self::address::generation_address;
+use self::address::{generation_address, multisig_address};
+use self::encrypted_secret::{
+    EncryptedWalletSecret, WalletFileHeader, ENCRYPTED_WALLET_FILE_VERSION,
+    PLAINTEXT_WALLET_FILE_VERSION,
+};
 
 pub const WALLET_DIRECTORY: &str = "wallet";
 pub const WALLET_SECRET_FILE_NAME: &str = "wallet.dat";
 pub const WALLET_OUTGOING_SECRETS_FILE_NAME: &str = "outgoing_randomness.dat";
 pub const WALLET_INCOMING_SECRETS_FILE_NAME: &str = "incoming_randomness.dat";
+pub const WALLET_VIEW_KEYS_FILE_NAME: &str = "view_keys.json";
 const STANDARD_WALLET_NAME: &str = "standard_wallet";
 const STANDARD_WALLET_VERSION: u8 = 0;
 pub const WALLET_DB_NAME: &str = "wallet";
@@ -59,6 +73,16 @@ pub struct WalletSecret {
     version: u8,
 }
 
+/// The on-disk format of the wallet secret file.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum WalletFileFormat {
+    /// Version 0: the wallet secret is stored as plain JSON.
+    Plaintext,
+
+    /// Version 1: the wallet secret is encrypted under a passphrase.
+    Encrypted,
+}
+
 /// Struct for containing file paths for secrets. To be communicated to user upon
 /// wallet creation or wallet opening.
 pub struct WalletSecretFileLocations {
@@ -117,12 +141,14 @@ impl WalletSecret {
     }
 
     /// Read wallet from `wallet_file` if the file exists, or, if none exists, create new wallet
-    /// and save it to `wallet_file`.
+    /// and save it to `wallet_file`. If a passphrase is given, a newly created wallet is
+    /// encrypted under it, and an existing encrypted wallet is unlocked with it.
     /// Also create files for incoming and outgoing randomness which should be appended to
     /// on each incoming and outgoing transaction.
     /// Returns an instance of self and the path in which the wallet secret was stored.
     pub fn read_from_file_or_create(
         wallet_directory_path: &Path,
+        passphrase: Option<&str>,
     ) -> Result<(Self, WalletSecretFileLocations)> {
         let wallet_secret_path = Self::wallet_secret_path(wallet_directory_path);
         let wallet = if wallet_secret_path.exists() {
@@ -130,14 +156,19 @@ impl WalletSecret {
                 "***** Reading wallet from {} *****\n\n\n",
                 wallet_secret_path.display()
             );
-            Self::read_from_file(&wallet_secret_path)?
+            Self::read_from_file_with_passphrase(&wallet_secret_path, passphrase)?
         } else {
             info!(
                 "***** Creating new wallet in {} *****\n\n\n",
                 wallet_secret_path.display()
             );
             let new_wallet: WalletSecret = WalletSecret::new_random();
-            new_wallet.save_to_disk(&wallet_secret_path)?;
+            match passphrase {
+                Some(passphrase) => {
+                    new_wallet.save_to_disk_encrypted(&wallet_secret_path, passphrase)?
+                }
+                None => new_wallet.save_to_disk(&wallet_secret_path)?,
+            }
             new_wallet
         };
 
@@ -186,11 +217,6 @@ impl WalletSecret {
     }
 
     pub fn nth_generation_spending_key(&self, counter: u16) -> generation_address::SpendingKey {
-        assert!(
-            counter.is_zero(),
-            "For now we only support one generation address per wallet"
-        );
-
         // We keep n between 0 and 2^16 as this makes it possible to scan all possible addresses
         // in case you don't know with what counter you made the address
         let key_seed = Hash::hash_varlen(
@@ -206,6 +232,22 @@ impl WalletSecret {
         generation_address::SpendingKey::derive_from_seed(key_seed)
     }
 
+    /// Return this wallet's secret for the multisig addresses that it participates in. The
+    /// public key of the returned key identifies the wallet to the other participants.
+    pub fn nth_multisig_key(&self, counter: u16) -> multisig_address::MultisigKey {
+        let seed = Hash::hash_varlen(
+            &[
+                self.secret_seed.0.encode(),
+                vec![
+                    multisig_address::MULTISIG_FLAG,
+                    BFieldElement::new(counter.into()),
+                ],
+            ]
+            .concat(),
+        );
+        multisig_address::MultisigKey { seed }
+    }
+
     /// Return the secret key that is used to deterministically generate commitment pseudo-randomness
     /// for the mutator set.
     pub fn generate_sender_randomness(
@@ -227,77 +269,131 @@ impl WalletSecret {
         )
     }
 
-    /// Read Wallet from file as JSON
-    pub fn read_from_file(wallet_file: &Path) -> Result<Self> {
-        let wallet_file_content: String = fs::read_to_string(wallet_file).with_context(|| {
-            format!(
-                "Failed to read wallet from {}",
+    /// Determine whether the wallet file holds a plaintext or an encrypted wallet secret.
+    pub fn wallet_file_format(wallet_file: &Path) -> Result<WalletFileFormat> {
+        let wallet_file_content = Self::read_wallet_file_content(wallet_file)?;
+        Self::wallet_file_format_from_content(&wallet_file_content, wallet_file)
+    }
+
+    fn wallet_file_format_from_content(
+        wallet_file_content: &str,
+        wallet_file: &Path,
+    ) -> Result<WalletFileFormat> {
+        let header =
+            serde_json::from_str::<WalletFileHeader>(wallet_file_content).with_context(|| {
+                format!(
+                    "Failed to decode wallet file version from {}",
+                    wallet_file.to_string_lossy(),
+                )
+            })?;
+
+        match header.version {
+            PLAINTEXT_WALLET_FILE_VERSION => Ok(WalletFileFormat::Plaintext),
+            ENCRYPTED_WALLET_FILE_VERSION => Ok(WalletFileFormat::Encrypted),
+            version => bail!(
+                "Unknown wallet file version {version} in {}",
                 wallet_file.to_string_lossy(),
-            )
-        })?;
+            ),
+        }
+    }
 
-        serde_json::from_str::<WalletSecret>(&wallet_file_content).with_context(|| {
+    fn read_wallet_file_content(wallet_file: &Path) -> Result<String> {
+        fs::read_to_string(wallet_file).with_context(|| {
             format!(
-                "Failed to decode wallet from {}",
+                "Failed to read wallet from {}",
                 wallet_file.to_string_lossy(),
             )
         })
     }
 
-    /// Used to generate both the file for incoming and outgoing randomness
-    fn create_empty_wallet_randomness_file(file_path: &Path) -> Result<()> {
-        let init_value: String = String::default();
+    /// Read Wallet from file as JSON. Fails if the wallet file is encrypted.
+    pub fn read_from_file(wallet_file: &Path) -> Result<Self> {
+        Self::read_from_file_with_passphrase(wallet_file, None)
+    }
 
-        #[cfg(unix)]
-        {
-            Self::create_wallet_file_unix(&file_path.to_path_buf(), init_value)
-        }
-        #[cfg(not(unix))]
-        {
-            Self::create_wallet_file_windows(&file_path.to_path_buf(), init_value)
+    /// Read Wallet from file, decrypting it with `passphrase` if the file is encrypted.
+    /// The passphrase is ignored for plaintext wallet files.
+    pub fn read_from_file_with_passphrase(
+        wallet_file: &Path,
+        passphrase: Option<&str>,
+    ) -> Result<Self> {
+        let wallet_file_content = Self::read_wallet_file_content(wallet_file)?;
+
+        match Self::wallet_file_format_from_content(&wallet_file_content, wallet_file)? {
+            WalletFileFormat::Plaintext => {
+                serde_json::from_str::<WalletSecret>(&wallet_file_content).with_context(|| {
+                    format!(
+                        "Failed to decode wallet from {}",
+                        wallet_file.to_string_lossy(),
+                    )
+                })
+            }
+            WalletFileFormat::Encrypted => {
+                let passphrase = match passphrase {
+                    Some(passphrase) => passphrase,
+                    None => bail!(
+                        "Wallet file {} is encrypted. A passphrase is required to unlock it.",
+                        wallet_file.to_string_lossy(),
+                    ),
+                };
+                let encrypted = serde_json::from_str::<EncryptedWalletSecret>(&wallet_file_content)
+                    .with_context(|| {
+                        format!(
+                            "Failed to decode encrypted wallet from {}",
+                            wallet_file.to_string_lossy(),
+                        )
+                    })?;
+                encrypted.decrypt(passphrase).with_context(|| {
+                    format!(
+                        "Failed to unlock wallet from {}",
+                        wallet_file.to_string_lossy(),
+                    )
+                })
+            }
         }
     }
 
+    /// Used to generate both the file for incoming and outgoing randomness
+    fn create_empty_wallet_randomness_file(file_path: &Path) -> Result<()> {
+        Self::create_wallet_file(file_path, String::default())
+    }
+
     /// Save this wallet to disk. If necessary, create the file (with restrictive permissions).
     pub fn save_to_disk(&self, wallet_file: &Path) -> Result<()> {
         let wallet_secret_as_json: String = serde_json::to_string(self).unwrap();
+        Self::create_wallet_file(wallet_file, wallet_secret_as_json)
+    }
 
-        #[cfg(unix)]
-        {
-            Self::create_wallet_file_unix(&wallet_file.to_path_buf(), wallet_secret_as_json)
-        }
-        #[cfg(not(unix))]
-        {
-            Self::create_wallet_file_windows(&wallet_file.to_path_buf(), wallet_secret_as_json)
-        }
+    /// Save this wallet to disk, encrypted under `passphrase`. If necessary, create the
+    /// file (with restrictive permissions).
+    pub fn save_to_disk_encrypted(&self, wallet_file: &Path, passphrase: &str) -> Result<()> {
+        let encrypted = EncryptedWalletSecret::encrypt(self, passphrase)?;
+        let encrypted_as_json: String = serde_json::to_string(&encrypted).unwrap();
+        Self::create_wallet_file(wallet_file, encrypted_as_json)
     }
 
-    #[cfg(unix)]
-    /// Create a wallet file, and set restrictive permissions
-    fn create_wallet_file_unix(path: &PathBuf, file_content: String) -> Result<()> {
-        // On Unix/Linux we set the file permissions to 600, to disallow
-        // other users on the same machine to access the secrets.
-        // I don't think the `std::os::unix` library can be imported on a Windows machine,
-        // so this function and the below import is only compiled on Unix machines.
-        use std::os::unix::prelude::OpenOptionsExt;
-        fs::OpenOptions::new()
-            .create(true)
-            .write(true)
-            .mode(0o600)
-            .open(path)
-            .unwrap();
-        fs::write(path.clone(), file_content).context("Failed to write wallet file to disk")
+    /// Replace an existing wallet file with this wallet, encrypted under `passphrase`.
+    /// The new file is written next to the old one and then moved into place, such that
+    /// a crash cannot leave behind a half-written wallet file. This is also how version-0
+    /// (plaintext) wallet files are migrated to the encrypted format.
+    pub fn replace_on_disk_encrypted(&self, wallet_file: &Path, passphrase: &str) -> Result<()> {
+        let tmp_wallet_file = wallet_file.with_extension("dat.new");
+        if tmp_wallet_file.exists() {
+            fs::remove_file(&tmp_wallet_file)?;
+        }
+        self.save_to_disk_encrypted(&tmp_wallet_file, passphrase)?;
+        fs::rename(&tmp_wallet_file, wallet_file).with_context(|| {
+            format!(
+                "Failed to move new wallet file into place at {}",
+                wallet_file.to_string_lossy(),
+            )
+        })
     }
 
-    #[cfg(not(unix))]
-    /// Create a wallet file, without setting restrictive UNIX permissions
-    fn create_wallet_file_windows(path: &PathBuf, wallet_as_json: String) -> Result<()> {
-        fs::OpenOptions::new()
-            .create(true)
-            .write(true)
-            .open(path)
-            .unwrap();
-        fs::write(path.clone(), wallet_as_json).context("Failed to write wallet file to disk")
+    /// Create or overwrite a wallet file, with restrictive permissions
+    fn create_wallet_file(wallet_file: &Path, file_content: String) -> Result<()> {
+        write_secret_file(wallet_file, file_content.as_bytes())
+            .context("Failed to write wallet file to disk")
     }
 
     /// Convert the wallet secret into a BIP-39 phrase consisting of 18 words (for 192
@@ -365,7 +461,7 @@ mod wallet_tests {
     use crate::models::state::UtxoReceiverData;
     use crate::tests::shared::{
         add_block, get_mock_global_state, get_mock_wallet_state, make_mock_block,
-        make_mock_transaction_with_generation_key,
+        make_mock_transaction_with_generation_key, unit_test_data_directory,
     };
     use crate::util_types::mutator_set::mutator_set_trait::MutatorSet;
 
@@ -389,7 +485,8 @@ mod wallet_tests {
         );
 
         let premine_receiver_spending_key = wallet_state_premine_recipient
-            .wallet_secret
+            .spending_secret()
+            .unwrap()
             .nth_generation_spending_key(0);
         let premine_receiver_address = premine_receiver_spending_key.to_address();
         let expected_premine_utxo = Utxo {
@@ -587,7 +684,8 @@ mod wallet_tests {
         let network = Network::Testnet;
         let mut own_wallet_state = get_mock_wallet_state(Some(own_wallet_secret), network).await;
         let own_spending_key = own_wallet_state
-            .wallet_secret
+            .spending_secret()
+            .unwrap()
             .nth_generation_spending_key(0);
         let genesis_block = Block::genesis_block();
         let (block_1, cb_utxo, cb_output_randomness) =
@@ -791,11 +889,15 @@ mod wallet_tests {
         let own_wallet_secret = WalletSecret::new_random();
         let mut own_wallet_state = get_mock_wallet_state(Some(own_wallet_secret), network).await;
         let own_spending_key = own_wallet_state
-            .wallet_secret
+            .spending_secret()
+            .unwrap()
             .nth_generation_spending_key(0);
         let own_address = own_spending_key.to_address();
         let genesis_block = Block::genesis_block();
-        let premine_wallet = get_mock_wallet_state(None, network).await.wallet_secret;
+        let premine_wallet = get_mock_wallet_state(None, network)
+            .await
+            .wallet_secret
+            .unwrap();
         let premine_receiver_global_state_lock =
             get_mock_global_state(Network::Alpha, 2, Some(premine_wallet)).await;
         let mut premine_receiver_global_state =
@@ -817,7 +919,8 @@ mod wallet_tests {
             receiver_privacy_digest: own_address.privacy_digest,
             sender_randomness: premine_receiver_global_state
                 .wallet_state
-                .wallet_secret
+                .spending_secret()
+                .unwrap()
                 .generate_sender_randomness(
                     genesis_block.kernel.header.height,
                     own_address.privacy_digest,
@@ -832,7 +935,8 @@ mod wallet_tests {
             receiver_privacy_digest: own_address.privacy_digest,
             sender_randomness: premine_receiver_global_state
                 .wallet_state
-                .wallet_secret
+                .spending_secret()
+                .unwrap()
                 .generate_sender_randomness(
                     genesis_block.kernel.header.height,
                     own_address.privacy_digest,
@@ -998,7 +1102,8 @@ mod wallet_tests {
         // verify that membership proofs are valid after forks
         let premine_wallet_spending_key = premine_receiver_global_state
             .wallet_state
-            .wallet_secret
+            .spending_secret()
+            .unwrap()
             .nth_generation_spending_key(0);
         let (block_2_b, _, _) =
             make_mock_block(&block_1, None, premine_wallet_spending_key.to_address());
@@ -1264,4 +1369,61 @@ mod wallet_tests {
         phrase[0] = "bbb".to_string();
         assert!(WalletSecret::from_phrase(&phrase[0..phrase.len() - 1]).is_err());
     }
+
+    #[test]
+    fn plaintext_wallet_file_can_be_migrated_to_encrypted() -> Result<()> {
+        let data_dir = unit_test_data_directory(Network::Alpha)?;
+        let wallet_dir = data_dir.wallet_directory_path();
+        fs::create_dir_all(&wallet_dir)?;
+        let wallet_file = WalletSecret::wallet_secret_path(&wallet_dir);
+
+        let wallet_secret = WalletSecret::new_random();
+        wallet_secret.save_to_disk(&wallet_file)?;
+        assert_eq!(
+            WalletFileFormat::Plaintext,
+            WalletSecret::wallet_file_format(&wallet_file)?
+        );
+        assert_eq!(wallet_secret, WalletSecret::read_from_file(&wallet_file)?);
+
+        wallet_secret.replace_on_disk_encrypted(&wallet_file, "correct horse")?;
+        assert_eq!(
+            WalletFileFormat::Encrypted,
+            WalletSecret::wallet_file_format(&wallet_file)?
+        );
+        assert!(
+            WalletSecret::read_from_file(&wallet_file).is_err(),
+            "Encrypted wallet file cannot be read without passphrase"
+        );
+        assert!(
+            WalletSecret::read_from_file_with_passphrase(&wallet_file, Some("wrong")).is_err(),
+            "Encrypted wallet file cannot be read with wrong passphrase"
+        );
+        assert_eq!(
+            wallet_secret,
+            WalletSecret::read_from_file_with_passphrase(&wallet_file, Some("correct horse"))?
+        );
+
+        Ok(())
+    }
+
+    #[test]
+    fn new_wallet_is_encrypted_when_passphrase_is_given() -> Result<()> {
+        let data_dir = unit_test_data_directory(Network::Alpha)?;
+        let wallet_dir = data_dir.wallet_directory_path();
+        fs::create_dir_all(&wallet_dir)?;
+
+        let (wallet_secret, file_locations) =
+            WalletSecret::read_from_file_or_create(&wallet_dir, Some("correct horse"))?;
+        assert_eq!(
+            WalletFileFormat::Encrypted,
+            WalletSecret::wallet_file_format(&file_locations.wallet_secret_path)?
+        );
+        assert!(WalletSecret::read_from_file_or_create(&wallet_dir, None).is_err());
+
+        let (wallet_secret_again, _) =
+            WalletSecret::read_from_file_or_create(&wallet_dir, Some("correct horse"))?;
+        assert_eq!(wallet_secret, wallet_secret_again);
+
+        Ok(())
+    }
 }
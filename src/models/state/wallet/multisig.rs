@@ -0,0 +1,242 @@
+use crate::prelude::twenty_first;
+
+use anyhow::{bail, Context, Result};
+use serde::{Deserialize, Serialize};
+use std::fs;
+use std::path::Path;
+use twenty_first::shared_math::digest::Digest;
+
+use super::address::generation_address;
+use super::address::multisig_address::{MultisigAddress, MultisigKey, MultisigSignature};
+use crate::config_models::network::Network;
+use crate::models::blockchain::block::block_height::BlockHeight;
+use crate::models::blockchain::transaction::transaction_kernel::TransactionKernel;
+use crate::models::blockchain::transaction::utxo::Utxo;
+use crate::models::blockchain::transaction::TransactionPrimitiveWitness;
+use crate::models::consensus::mast_hash::MastHash;
+use crate::util_types::secret_file::create_secret_file;
+
+/// The version of the format of partially signed transactions.
+pub const PARTIALLY_SIGNED_TRANSACTION_VERSION: u8 = 1;
+
+/// The number of multisig keys of a wallet that are searched for the wallet's own key in
+/// a multisig address.
+pub const MULTISIG_KEY_SEARCH_DEPTH: u16 = 256;
+
+/// The number of blocks by which the tip may have moved since a multisig transaction was
+/// created, for it to still be broadcast.
+pub const MULTISIG_UPDATE_DEPTH: usize = 100;
+
+/// Everything the participants of a multisig address share: the address, and the seed of
+/// the view key of its receiving address. The seed lets every participant recognize and
+/// spend the UTXOs sent to the address, but the participants still need each other's
+/// signatures to spend them.
+#[derive(Clone, Debug, Serialize, Deserialize, PartialEq, Eq)]
+pub struct MultisigSetup {
+    pub address: MultisigAddress,
+    view_seed: Digest,
+}
+
+impl MultisigSetup {
+    pub fn new(threshold: u8, public_keys: Vec<Digest>, view_seed: Digest) -> Result<Self> {
+        let receiving_address =
+            generation_address::SpendingKey::derive_from_seed(view_seed).to_address();
+        let address = MultisigAddress::new(threshold, public_keys, receiving_address)?;
+
+        Ok(Self { address, view_seed })
+    }
+
+    pub fn view_key(&self) -> generation_address::ViewKey {
+        // The key is derived from the shared view seed, not from a wallet secret
+        generation_address::SpendingKey::derive_from_seed(self.view_seed).to_view_key(0)
+    }
+
+    /// Write the setup to a new file. Refuses to overwrite existing files. The file lets
+    /// its holder see the UTXOs of the multisig address, so it should only be given to the
+    /// participants.
+    pub fn save_to_file(&self, path: &Path) -> Result<()> {
+        if path.exists() {
+            bail!("File {} already exists", path.to_string_lossy());
+        }
+        let as_json = serde_json::to_string_pretty(self)?;
+        create_secret_file(path, as_json.as_bytes())
+            .with_context(|| format!("Failed to write {}", path.to_string_lossy()))
+    }
+
+    pub fn read_from_file(path: &Path) -> Result<Self> {
+        let as_json = fs::read_to_string(path)
+            .with_context(|| format!("Failed to read {}", path.to_string_lossy()))?;
+        let setup: Self = serde_json::from_str(&as_json).with_context(|| {
+            format!(
+                "Failed to decode multisig setup from {}",
+                path.to_string_lossy()
+            )
+        })?;
+
+        // Re-derive the address, such that a tampered file is rejected
+        MultisigSetup::new(
+            setup.address.threshold,
+            setup.address.public_keys.clone(),
+            setup.view_seed,
+        )
+        .ok()
+        .filter(|derived| *derived == setup)
+        .with_context(|| {
+            format!(
+                "Multisig setup in {} is inconsistent",
+                path.to_string_lossy()
+            )
+        })
+    }
+}
+
+/// A UTXO of a multisig address that this wallet participates in.
+#[derive(Clone, Debug, Serialize, Deserialize, PartialEq, Eq)]
+pub struct MultisigUtxo {
+    pub address: MultisigAddress,
+    pub utxo: Utxo,
+    pub sender_randomness: Digest,
+    pub aocl_index: u64,
+    pub confirmed_in_block: (Digest, BlockHeight),
+    pub spent_in_block: Option<(Digest, BlockHeight)>,
+}
+
+/// A transaction spending UTXOs of a multisig address, which the participants pass around
+/// to collect signatures. The kernel is fixed when the transaction is created, so all
+/// participants sign the same transaction: the MAST hash of its kernel.
+#[derive(Clone, Debug, Serialize, Deserialize, PartialEq, Eq)]
+pub struct PartiallySignedTransaction {
+    pub version: u8,
+    pub network: Network,
+    pub address: MultisigAddress,
+    pub kernel: TransactionKernel,
+
+    /// Everything needed to prove the transaction, except the lock script witnesses
+    pub primitive_witness: TransactionPrimitiveWitness,
+
+    /// The signatures of the participants, in the order of their public keys
+    pub signatures: Vec<Option<MultisigSignature>>,
+}
+
+impl PartiallySignedTransaction {
+    pub fn new(
+        network: Network,
+        address: MultisigAddress,
+        kernel: TransactionKernel,
+        primitive_witness: TransactionPrimitiveWitness,
+    ) -> Self {
+        let signatures = vec![None; address.public_keys.len()];
+        Self {
+            version: PARTIALLY_SIGNED_TRANSACTION_VERSION,
+            network,
+            address,
+            kernel,
+            primitive_witness,
+            signatures,
+        }
+    }
+
+    /// The hash of the kernel, which identifies the transaction to its signers.
+    pub fn kernel_hash(&self) -> Digest {
+        self.kernel.mast_hash()
+    }
+
+    pub fn signature_count(&self) -> usize {
+        self.signatures.iter().flatten().count()
+    }
+
+    pub fn is_complete(&self) -> bool {
+        self.signature_count() >= self.address.threshold as usize
+    }
+
+    /// Add the signature of the participant with the given key. Keys are one-time keys, so
+    /// the key must not sign any other transaction.
+    pub fn sign(&mut self, key: &MultisigKey) -> Result<()> {
+        let Some(index) = self.address.participant_index(key.public_key()) else {
+            bail!("Key is not a participant of the multisig address");
+        };
+        self.signatures[index] = Some(key.sign(self.kernel_hash()));
+
+        Ok(())
+    }
+
+    /// Add the signatures of another copy of the same transaction.
+    pub fn combine(&mut self, other: &Self) -> Result<()> {
+        if self.network != other.network
+            || self.address != other.address
+            || self.kernel != other.kernel
+            || self.primitive_witness != other.primitive_witness
+        {
+            bail!("Cannot combine signatures of different transactions");
+        }
+        if other.signatures.len() != self.signatures.len() {
+            bail!("Partially signed transaction has the wrong number of signature slots");
+        }
+
+        let kernel_hash = self.kernel_hash();
+        for (index, signature) in other.signatures.iter().enumerate() {
+            let Some(signature) = signature else {
+                continue;
+            };
+            if !signature.verify(self.address.public_keys[index], kernel_hash) {
+                bail!("Invalid signature of participant {index}");
+            }
+            self.signatures[index] = Some(signature.clone());
+        }
+
+        Ok(())
+    }
+
+    /// Return the primitive witness with the lock script witnesses filled in from the
+    /// signatures.
+    pub fn signed_primitive_witness(&self) -> Result<TransactionPrimitiveWitness> {
+        let kernel_hash = self.kernel_hash();
+        for (signature, public_key) in self.signatures.iter().zip(&self.address.public_keys) {
+            if signature
+                .as_ref()
+                .is_some_and(|signature| !signature.verify(*public_key, kernel_hash))
+            {
+                bail!("Partially signed transaction contains an invalid signature");
+            }
+        }
+        let lock_script_witness = self
+            .address
+            .lock_script_witness(kernel_hash, &self.signatures)?;
+        let mut primitive_witness = self.primitive_witness.clone();
+        primitive_witness.lock_script_witnesses =
+            vec![lock_script_witness; primitive_witness.input_utxos.len()];
+
+        Ok(primitive_witness)
+    }
+
+    /// Write the transaction to a file that only the owner may read, replacing the file if
+    /// it exists.
+    pub fn write_to_file(&self, path: &Path) -> Result<()> {
+        let as_json = serde_json::to_string_pretty(self)?;
+        if path.exists() {
+            fs::remove_file(path)
+                .with_context(|| format!("Failed to replace {}", path.to_string_lossy()))?;
+        }
+        create_secret_file(path, as_json.as_bytes())
+            .with_context(|| format!("Failed to write {}", path.to_string_lossy()))
+    }
+
+    pub fn read_from_file(path: &Path) -> Result<Self> {
+        let as_json = fs::read_to_string(path)
+            .with_context(|| format!("Failed to read {}", path.to_string_lossy()))?;
+        let transaction: Self = serde_json::from_str(&as_json).with_context(|| {
+            format!(
+                "Failed to decode partially signed transaction from {}",
+                path.to_string_lossy()
+            )
+        })?;
+        if transaction.version != PARTIALLY_SIGNED_TRANSACTION_VERSION {
+            bail!(
+                "Unsupported partially signed transaction version {}",
+                transaction.version
+            );
+        }
+
+        Ok(transaction)
+    }
+}
@@ -0,0 +1,91 @@
+use crate::prelude::twenty_first;
+
+use serde::{Deserialize, Serialize};
+use std::fmt;
+use std::time::Duration;
+use twenty_first::shared_math::digest::Digest;
+
+use super::address::generation_address;
+use crate::models::blockchain::block::block_height::BlockHeight;
+use crate::models::blockchain::transaction::neptune_coins::NeptuneCoins;
+use crate::models::blockchain::transaction::utxo::Utxo;
+use crate::util_types::mutator_set::addition_record::AdditionRecord;
+
+/// Where an outgoing transaction is in its life cycle.
+#[derive(Clone, Copy, Debug, Serialize, Deserialize, PartialEq, Eq)]
+pub enum OutgoingTransactionStatus {
+    /// Sent, but not confirmed in a block yet
+    Pending,
+
+    /// Confirmed in the given block
+    Confirmed(Digest, BlockHeight),
+
+    /// The inputs were spent by the given block without paying the receivers of this
+    /// transaction, so this transaction can never be confirmed unless that block is
+    /// reverted.
+    Abandoned(Digest),
+}
+
+impl fmt::Display for OutgoingTransactionStatus {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            OutgoingTransactionStatus::Pending => write!(f, "pending"),
+            OutgoingTransactionStatus::Confirmed(_, height) => {
+                write!(f, "confirmed at height {height}")
+            }
+            OutgoingTransactionStatus::Abandoned(_) => write!(f, "abandoned"),
+        }
+    }
+}
+
+/// The wallet's record of a transaction that it sent. Unlike the balance history, which is
+/// derived from the monitored UTXOs, this records what the payment was for: who was paid
+/// how much, and at what fee.
+#[derive(Clone, Debug, Serialize, Deserialize, PartialEq, Eq)]
+pub struct OutgoingTransaction {
+    /// The ID of the transaction. Replaced when the fee of the transaction is bumped.
+    pub txid: Digest,
+
+    /// The time the transaction was created, as duration since the UNIX epoch
+    pub timestamp: Duration,
+
+    pub recipients: Vec<(generation_address::ReceivingAddress, NeptuneCoins)>,
+    pub fee: NeptuneCoins,
+    pub change: NeptuneCoins,
+
+    /// A note kept only in this wallet, e.g. what the payment was for
+    pub label: String,
+
+    pub status: OutgoingTransactionStatus,
+
+    /// Indices into the wallet's list of monitored UTXOs of the UTXOs that this transaction
+    /// spends. Used to recognize the block that confirms, or abandons, the transaction.
+    pub input_monitored_utxo_indices: Vec<u64>,
+
+    /// The addition records of the outputs to the recipients, in the order of the
+    /// recipients. These are the same for all versions of a fee-bumped transaction.
+    pub recipient_addition_records: Vec<AdditionRecord>,
+
+    /// The outputs whose notification was not published in the transaction. Their
+    /// receivers can only claim them with a notification exported from this wallet.
+    pub off_chain_outputs: Vec<OffChainOutput>,
+}
+
+/// An output that was sent without an on-chain notification, with the secrets that its
+/// receiver needs to claim it.
+#[derive(Clone, Debug, Serialize, Deserialize, PartialEq, Eq)]
+pub struct OffChainOutput {
+    pub utxo: Utxo,
+    pub sender_randomness: Digest,
+    pub receiver_privacy_digest: Digest,
+}
+
+impl OutgoingTransaction {
+    /// The total amount paid to the recipients, excluding change and fee.
+    pub fn amount(&self) -> NeptuneCoins {
+        self.recipients
+            .iter()
+            .map(|(_address, amount)| *amount)
+            .sum()
+    }
+}
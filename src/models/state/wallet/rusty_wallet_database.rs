@@ -7,6 +7,12 @@ use twenty_first::{
 };
 
 use super::monitored_utxo::MonitoredUtxo;
+use super::multisig::{MultisigSetup, MultisigUtxo};
+use super::outgoing_transaction::OutgoingTransaction;
+use super::unconfirmed_send::UnconfirmedSend;
+use super::utxo_transfer::UtxoTransferNotification;
+use super::wallet_state::{KnownTimelock, KnownToken};
+use crate::models::blockchain::block::block_height::BlockHeight;
 
 pub struct RustyWalletDatabase {
     storage: SimpleRustyStorage,
@@ -16,8 +22,36 @@ pub struct RustyWalletDatabase {
     // records which block the database is synced to
     sync_label: DbtSingleton<Digest>,
 
+    // height of the block the database is synced to
+    sync_height: DbtSingleton<BlockHeight>,
+
     // counts the number of output UTXOs generated by this wallet
     counter: DbtSingleton<u64>,
+
+    // highest derivation index of a generation address handed out by this wallet
+    generation_key_counter: DbtSingleton<u64>,
+
+    // transactions sent by this wallet, oldest first
+    outgoing_transactions: DbtVec<OutgoingTransaction>,
+
+    // transactions sent by this wallet that are not confirmed yet
+    unconfirmed_sends: DbtSingleton<Vec<UnconfirmedSend>>,
+
+    // UTXOs claimed from transfer notifications that are not confirmed yet
+    pending_utxo_claims: DbtSingleton<Vec<UtxoTransferNotification>>,
+
+    // time-locked lock scripts of UTXOs announced to this wallet
+    timelocks: DbtVec<KnownTimelock>,
+
+    // multisig addresses this wallet participates in, and their UTXOs
+    multisig_setups: DbtVec<MultisigSetup>,
+    multisig_utxos: DbtVec<MultisigUtxo>,
+
+    // kernel MAST hashes signed by this wallet's one-time multisig keys, by public key
+    multisig_signed_kernels: DbtVec<(Digest, Digest)>,
+
+    // tokens registered with this wallet, by name
+    tokens: DbtVec<KnownToken>,
 }
 
 impl RustyWalletDatabase {
@@ -30,7 +64,27 @@ impl RustyWalletDatabase {
 
         let monitored_utxos_storage = storage.schema.new_vec::<MonitoredUtxo>("monitored_utxos");
         let sync_label_storage = storage.schema.new_singleton::<Digest>("sync_label");
+        let sync_height_storage = storage.schema.new_singleton::<BlockHeight>("sync_height");
         let counter_storage = storage.schema.new_singleton::<u64>("counter");
+        let generation_key_counter_storage = storage
+            .schema
+            .new_singleton::<u64>("generation_key_counter");
+        let outgoing_transactions_storage = storage
+            .schema
+            .new_vec::<OutgoingTransaction>("outgoing_transactions");
+        let unconfirmed_sends_storage = storage
+            .schema
+            .new_singleton::<Vec<UnconfirmedSend>>("unconfirmed_sends");
+        let pending_utxo_claims_storage = storage
+            .schema
+            .new_singleton::<Vec<UtxoTransferNotification>>("pending_utxo_claims");
+        let timelocks_storage = storage.schema.new_vec::<KnownTimelock>("timelocks");
+        let multisig_setups_storage = storage.schema.new_vec::<MultisigSetup>("multisig_setups");
+        let multisig_utxos_storage = storage.schema.new_vec::<MultisigUtxo>("multisig_utxos");
+        let multisig_signed_kernels_storage = storage
+            .schema
+            .new_vec::<(Digest, Digest)>("multisig_signed_kernels");
+        let tokens_storage = storage.schema.new_vec::<KnownToken>("tokens");
 
         storage.restore_or_new();
 
@@ -38,7 +92,17 @@ impl RustyWalletDatabase {
             storage,
             monitored_utxos: monitored_utxos_storage,
             sync_label: sync_label_storage,
+            sync_height: sync_height_storage,
             counter: counter_storage,
+            generation_key_counter: generation_key_counter_storage,
+            outgoing_transactions: outgoing_transactions_storage,
+            unconfirmed_sends: unconfirmed_sends_storage,
+            pending_utxo_claims: pending_utxo_claims_storage,
+            timelocks: timelocks_storage,
+            multisig_setups: multisig_setups_storage,
+            multisig_utxos: multisig_utxos_storage,
+            multisig_signed_kernels: multisig_signed_kernels_storage,
+            tokens: tokens_storage,
         }
     }
 
@@ -52,6 +116,66 @@ impl RustyWalletDatabase {
         &mut self.monitored_utxos
     }
 
+    /// get outgoing_transactions.
+    pub fn outgoing_transactions(&self) -> &DbtVec<OutgoingTransaction> {
+        &self.outgoing_transactions
+    }
+
+    /// get mutable outgoing_transactions.
+    pub fn outgoing_transactions_mut(&mut self) -> &mut DbtVec<OutgoingTransaction> {
+        &mut self.outgoing_transactions
+    }
+
+    /// get timelocks.
+    pub fn timelocks(&self) -> &DbtVec<KnownTimelock> {
+        &self.timelocks
+    }
+
+    /// get mutable timelocks.
+    pub fn timelocks_mut(&mut self) -> &mut DbtVec<KnownTimelock> {
+        &mut self.timelocks
+    }
+
+    /// get multisig setups.
+    pub fn multisig_setups(&self) -> &DbtVec<MultisigSetup> {
+        &self.multisig_setups
+    }
+
+    /// get mutable multisig setups.
+    pub fn multisig_setups_mut(&mut self) -> &mut DbtVec<MultisigSetup> {
+        &mut self.multisig_setups
+    }
+
+    /// get multisig UTXOs.
+    pub fn multisig_utxos(&self) -> &DbtVec<MultisigUtxo> {
+        &self.multisig_utxos
+    }
+
+    /// get mutable multisig UTXOs.
+    pub fn multisig_utxos_mut(&mut self) -> &mut DbtVec<MultisigUtxo> {
+        &mut self.multisig_utxos
+    }
+
+    /// get the kernels signed by multisig keys.
+    pub fn multisig_signed_kernels(&self) -> &DbtVec<(Digest, Digest)> {
+        &self.multisig_signed_kernels
+    }
+
+    /// get mutable kernels signed by multisig keys.
+    pub fn multisig_signed_kernels_mut(&mut self) -> &mut DbtVec<(Digest, Digest)> {
+        &mut self.multisig_signed_kernels
+    }
+
+    /// get tokens.
+    pub fn tokens(&self) -> &DbtVec<KnownToken> {
+        &self.tokens
+    }
+
+    /// get mutable tokens.
+    pub fn tokens_mut(&mut self) -> &mut DbtVec<KnownToken> {
+        &mut self.tokens
+    }
+
     pub fn get_sync_label(&self) -> Digest {
         self.sync_label.get()
     }
@@ -60,6 +184,14 @@ impl RustyWalletDatabase {
         self.sync_label.set(sync_label);
     }
 
+    pub fn get_sync_height(&self) -> BlockHeight {
+        self.sync_height.get()
+    }
+
+    pub fn set_sync_height(&mut self, sync_height: BlockHeight) {
+        self.sync_height.set(sync_height);
+    }
+
     pub fn get_counter(&self) -> u64 {
         self.counter.get()
     }
@@ -67,6 +199,32 @@ impl RustyWalletDatabase {
     pub fn set_counter(&mut self, counter: u64) {
         self.counter.set(counter);
     }
+
+    /// Get the highest derivation index of a generation address that this wallet has
+    /// handed out. Index 0 is always in use, so the next unused index is this value plus one.
+    pub fn get_generation_key_counter(&self) -> u64 {
+        self.generation_key_counter.get()
+    }
+
+    pub fn set_generation_key_counter(&mut self, generation_key_counter: u64) {
+        self.generation_key_counter.set(generation_key_counter);
+    }
+
+    pub fn get_unconfirmed_sends(&self) -> Vec<UnconfirmedSend> {
+        self.unconfirmed_sends.get()
+    }
+
+    pub fn set_unconfirmed_sends(&mut self, unconfirmed_sends: Vec<UnconfirmedSend>) {
+        self.unconfirmed_sends.set(unconfirmed_sends);
+    }
+
+    pub fn get_pending_utxo_claims(&self) -> Vec<UtxoTransferNotification> {
+        self.pending_utxo_claims.get()
+    }
+
+    pub fn set_pending_utxo_claims(&mut self, pending_utxo_claims: Vec<UtxoTransferNotification>) {
+        self.pending_utxo_claims.set(pending_utxo_claims);
+    }
 }
 
 impl StorageWriter for RustyWalletDatabase {
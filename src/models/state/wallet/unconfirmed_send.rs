@@ -0,0 +1,50 @@
+use crate::prelude::twenty_first;
+
+use serde::{Deserialize, Serialize};
+use twenty_first::shared_math::digest::Digest;
+use twenty_first::util_types::algebraic_hasher::AlgebraicHasher;
+
+use crate::models::blockchain::transaction::neptune_coins::NeptuneCoins;
+use crate::models::blockchain::transaction::Transaction;
+use crate::models::state::{UtxoNotificationMedium, UtxoReceiverData};
+use crate::Hash;
+
+/// A transaction that was sent from this wallet but is not confirmed in a block yet.
+/// Besides the transaction, the outputs it was built from are kept, such that the
+/// transaction can be rebuilt with a higher fee while spending the same inputs.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct UnconfirmedSend {
+    pub transaction: Transaction,
+    pub receiver_data: Vec<UtxoReceiverData>,
+    pub notification_media: Vec<UtxoNotificationMedium>,
+}
+
+impl UnconfirmedSend {
+    pub fn txid(&self) -> Digest {
+        Hash::hash(&self.transaction)
+    }
+
+    pub fn fee(&self) -> NeptuneCoins {
+        self.transaction.kernel.fee
+    }
+
+    /// The total amount sent to the receivers, excluding change and fee.
+    pub fn amount(&self) -> NeptuneCoins {
+        self.receiver_data
+            .iter()
+            .map(|rd| rd.utxo.get_native_coin_amount())
+            .sum()
+    }
+
+    /// Return true iff the given transaction spends any of the inputs of this send. This
+    /// is the case for a replacement of this send, and for a block that confirms it.
+    pub fn shares_inputs_with(&self, transaction: &Transaction) -> bool {
+        self.transaction.kernel.inputs.iter().any(|own_input| {
+            transaction
+                .kernel
+                .inputs
+                .iter()
+                .any(|input| input.absolute_indices == own_input.absolute_indices)
+        })
+    }
+}
@@ -0,0 +1,321 @@
+//! Transactions that are built by a node without the wallet secret, such as a watch-only
+//! node, and signed offline by a machine that holds the wallet secret but no chain state.
+//!
+//! The online node exports an [`UnsignedTransaction`], which holds everything needed to
+//! prove the transaction except the spending keys. The offline signer checks it against
+//! its wallet secret, proves it, and exports a [`SignedTransaction`] for the online node
+//! to broadcast. The spending keys never leave the offline machine, as only proofs of the
+//! lock scripts are exported.
+
+use crate::prelude::twenty_first;
+
+use anyhow::{bail, Context, Result};
+use num_traits::Zero;
+use serde::{Deserialize, Serialize};
+use std::fs;
+use std::path::Path;
+use twenty_first::shared_math::bfield_codec::BFieldCodec;
+use twenty_first::shared_math::digest::Digest;
+use twenty_first::util_types::algebraic_hasher::AlgebraicHasher;
+
+use super::address::generation_address;
+use super::WalletSecret;
+use crate::config_models::network::Network;
+use crate::models::blockchain::transaction::neptune_coins::NeptuneCoins;
+use crate::models::blockchain::transaction::timelock::Timelock;
+use crate::models::blockchain::transaction::transaction_kernel::TransactionKernel;
+use crate::models::blockchain::transaction::{Transaction, TransactionPrimitiveWitness};
+use crate::models::consensus::mast_hash::MastHash;
+use crate::models::consensus::{ClaimSupport, Witness};
+use crate::util_types::mutator_set::mutator_set_trait::{commit, MutatorSet};
+use crate::Hash;
+
+/// The version of the format of unsigned and signed transaction files.
+pub const UNSIGNED_TRANSACTION_VERSION: u8 = 0;
+
+/// What the offline signer needs to know to unlock an input: the derivation index of the
+/// generation key that the input is locked to, and the time lock if the input is
+/// time-locked.
+#[derive(Clone, Copy, Debug, Serialize, Deserialize, PartialEq, Eq)]
+pub struct UnsignedInput {
+    pub generation_key_index: u16,
+    pub timelock: Option<Timelock>,
+}
+
+/// A transaction whose lock script witnesses are missing. The outputs are the recipients,
+/// in order, followed by the change if there is any. The change is always locked to the
+/// wallet's generation address with derivation index 0.
+#[derive(Clone, Debug, Serialize, Deserialize, PartialEq, Eq)]
+pub struct UnsignedTransaction {
+    pub version: u8,
+    pub network: Network,
+    pub kernel: TransactionKernel,
+
+    /// Everything needed to prove the transaction, except the lock script witnesses
+    pub primitive_witness: TransactionPrimitiveWitness,
+
+    /// The keys that unlock the inputs, in the order of the inputs
+    pub inputs: Vec<UnsignedInput>,
+
+    pub recipients: Vec<(generation_address::ReceivingAddress, NeptuneCoins)>,
+}
+
+impl UnsignedTransaction {
+    pub fn new(
+        network: Network,
+        kernel: TransactionKernel,
+        primitive_witness: TransactionPrimitiveWitness,
+        inputs: Vec<UnsignedInput>,
+        recipients: Vec<(generation_address::ReceivingAddress, NeptuneCoins)>,
+    ) -> Self {
+        Self {
+            version: UNSIGNED_TRANSACTION_VERSION,
+            network,
+            kernel,
+            primitive_witness,
+            inputs,
+            recipients,
+        }
+    }
+
+    /// The hash of the kernel, which identifies the transaction to the signer.
+    pub fn kernel_hash(&self) -> Digest {
+        self.kernel.mast_hash()
+    }
+
+    pub fn input_amount(&self) -> NeptuneCoins {
+        self.primitive_witness
+            .input_utxos
+            .iter()
+            .map(|utxo| utxo.get_native_coin_amount())
+            .sum()
+    }
+
+    /// The amount that goes back to the wallet, which is zero if there is no change output.
+    pub fn change_amount(&self) -> NeptuneCoins {
+        self.primitive_witness
+            .output_utxos
+            .get(self.recipients.len())
+            .map(|utxo| utxo.get_native_coin_amount())
+            .unwrap_or(NeptuneCoins::zero())
+    }
+
+    /// Check that the transaction spends UTXOs of the given wallet, pays exactly the
+    /// listed recipients and the change to the wallet, and return the primitive witness with
+    /// the lock script witnesses filled in. The kernel, which the lock scripts sign, is
+    /// checked against the witness, such that the signer signs what it checked.
+    pub fn sign(&self, wallet_secret: &WalletSecret) -> Result<TransactionPrimitiveWitness> {
+        let primitive_witness = &self.primitive_witness;
+        if self.inputs.len() != primitive_witness.input_utxos.len()
+            || self.inputs.len() != primitive_witness.input_lock_scripts.len()
+            || self.inputs.len() != primitive_witness.input_membership_proofs.len()
+            || self.inputs.len() != self.kernel.inputs.len()
+        {
+            bail!("Unsigned transaction has inconsistent inputs");
+        }
+
+        // The kernel must spend the listed input UTXOs
+        let mutator_set_accumulator = &primitive_witness.mutator_set_accumulator;
+        if mutator_set_accumulator.hash() != self.kernel.mutator_set_hash {
+            bail!("Kernel and witness of unsigned transaction are for different mutator sets");
+        }
+        for (input_index, (utxo, membership_proof)) in primitive_witness
+            .input_utxos
+            .iter()
+            .zip(primitive_witness.input_membership_proofs.iter())
+            .enumerate()
+        {
+            let item = Hash::hash(utxo);
+            if !mutator_set_accumulator.verify(item, membership_proof)
+                || mutator_set_accumulator.kernel.drop(item, membership_proof)
+                    != self.kernel.inputs[input_index]
+            {
+                bail!("Input {input_index} of the kernel does not spend the listed UTXO");
+            }
+        }
+
+        let mut lock_script_witnesses = vec![];
+        for (input_index, input) in self.inputs.iter().enumerate() {
+            let spending_key =
+                wallet_secret.nth_generation_spending_key(input.generation_key_index);
+            let address = spending_key.to_address();
+            let lock_script = match input.timelock {
+                Some(timelock) => address.timelocked_lock_script(timelock),
+                None => address.lock_script(),
+            };
+            if lock_script != primitive_witness.input_lock_scripts[input_index]
+                || lock_script.hash() != primitive_witness.input_utxos[input_index].lock_script_hash
+            {
+                bail!("Input {input_index} is not locked to this wallet");
+            }
+
+            let mut secret_input = spending_key.unlock_key.encode();
+            secret_input.reverse();
+            if let Some(timelock) = input.timelock {
+                secret_input.extend(timelock.witness(&self.kernel));
+            }
+            lock_script_witnesses.push(secret_input);
+        }
+
+        let output_utxos = &primitive_witness.output_utxos;
+        if output_utxos.len() != primitive_witness.output_sender_randomnesses.len()
+            || output_utxos.len() != primitive_witness.output_receiver_digests.len()
+            || output_utxos.len() != self.kernel.outputs.len()
+        {
+            bail!("Unsigned transaction has inconsistent outputs");
+        }
+        let has_change = match output_utxos.len().checked_sub(self.recipients.len()) {
+            Some(0) => false,
+            Some(1) => true,
+            _ => bail!("Outputs of unsigned transaction do not match its recipients"),
+        };
+        let change_address = wallet_secret.nth_generation_spending_key(0).to_address();
+        let receivers = self
+            .recipients
+            .iter()
+            .copied()
+            .chain(has_change.then_some((change_address, self.change_amount())));
+        for (output_index, (address, amount)) in receivers.enumerate() {
+            let utxo = &output_utxos[output_index];
+            if utxo.lock_script_hash != address.lock_script().hash()
+                || utxo.get_native_coin_amount() != amount
+                || primitive_witness.output_receiver_digests[output_index] != address.privacy_digest
+            {
+                if output_index == self.recipients.len() {
+                    bail!("Change of unsigned transaction does not go to this wallet");
+                }
+                bail!("Outputs of unsigned transaction do not match its recipients");
+            }
+
+            // The kernel must add the checked outputs to the mutator set
+            let addition_record = commit(
+                Hash::hash(utxo),
+                primitive_witness.output_sender_randomnesses[output_index],
+                primitive_witness.output_receiver_digests[output_index],
+            );
+            if addition_record != self.kernel.outputs[output_index] {
+                bail!("Output {output_index} of the kernel does not add the listed UTXO");
+            }
+        }
+        let output_amount = output_utxos
+            .iter()
+            .map(|utxo| utxo.get_native_coin_amount())
+            .sum::<NeptuneCoins>();
+        if output_amount + self.kernel.fee != self.input_amount() {
+            bail!("Inputs of unsigned transaction do not equal its outputs and fee");
+        }
+
+        let mut primitive_witness = primitive_witness.clone();
+        primitive_witness.lock_script_witnesses = lock_script_witnesses;
+
+        Ok(primitive_witness)
+    }
+
+    /// Write the transaction to a new file. Refuses to overwrite existing files.
+    pub fn save_to_file(&self, path: &Path) -> Result<()> {
+        if path.exists() {
+            bail!("File {} already exists", path.to_string_lossy());
+        }
+        let as_json = serde_json::to_string_pretty(self)?;
+        fs::write(path, as_json)
+            .with_context(|| format!("Failed to write {}", path.to_string_lossy()))
+    }
+
+    pub fn read_from_file(path: &Path) -> Result<Self> {
+        let as_json = fs::read_to_string(path)
+            .with_context(|| format!("Failed to read {}", path.to_string_lossy()))?;
+        let transaction: Self = serde_json::from_str(&as_json).with_context(|| {
+            format!(
+                "Failed to decode unsigned transaction from {}",
+                path.to_string_lossy()
+            )
+        })?;
+        if transaction.version != UNSIGNED_TRANSACTION_VERSION {
+            bail!(
+                "Unsupported unsigned transaction version {}",
+                transaction.version
+            );
+        }
+
+        Ok(transaction)
+    }
+}
+
+/// A proven transaction, as exported by the offline signer for the online node to
+/// broadcast. It contains no spending keys.
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct SignedTransaction {
+    pub version: u8,
+    pub network: Network,
+    pub transaction: Transaction,
+}
+
+impl SignedTransaction {
+    /// Sign and prove the unsigned transaction with the given wallet secret. With
+    /// `prove_all`, all claims of the transaction are proven, not only its lock scripts.
+    pub fn new(
+        unsigned_transaction: &UnsignedTransaction,
+        wallet_secret: &WalletSecret,
+        prove_all: bool,
+    ) -> Result<Self> {
+        let primitive_witness = unsigned_transaction.sign(wallet_secret)?;
+
+        // The lock script witnesses are the unlock keys, which must not leave this machine.
+        // Proving always replaces them by proofs.
+        let transaction = Transaction::prove_from_primitive_witness(
+            &primitive_witness,
+            unsigned_transaction.kernel.clone(),
+            prove_all,
+        );
+        let Witness::ValidationLogic(validation_logic) = &transaction.witness else {
+            bail!("Proven transaction must have a validation logic witness");
+        };
+        if validation_logic
+            .lock_scripts_halt
+            .supported_claims
+            .iter()
+            .any(|claim| !matches!(claim.support, ClaimSupport::Proof(_)))
+        {
+            bail!("Proven transaction must not contain lock script witnesses");
+        }
+
+        Ok(Self {
+            version: UNSIGNED_TRANSACTION_VERSION,
+            network: unsigned_transaction.network,
+            transaction,
+        })
+    }
+
+    pub fn txid(&self) -> Digest {
+        Hash::hash(&self.transaction)
+    }
+
+    /// Write the transaction to a new file. Refuses to overwrite existing files.
+    pub fn save_to_file(&self, path: &Path) -> Result<()> {
+        if path.exists() {
+            bail!("File {} already exists", path.to_string_lossy());
+        }
+        let as_json = serde_json::to_string_pretty(self)?;
+        fs::write(path, as_json)
+            .with_context(|| format!("Failed to write {}", path.to_string_lossy()))
+    }
+
+    pub fn read_from_file(path: &Path) -> Result<Self> {
+        let as_json = fs::read_to_string(path)
+            .with_context(|| format!("Failed to read {}", path.to_string_lossy()))?;
+        let transaction: Self = serde_json::from_str(&as_json).with_context(|| {
+            format!(
+                "Failed to decode signed transaction from {}",
+                path.to_string_lossy()
+            )
+        })?;
+        if transaction.version != UNSIGNED_TRANSACTION_VERSION {
+            bail!(
+                "Unsupported signed transaction version {}",
+                transaction.version
+            );
+        }
+
+        Ok(transaction)
+    }
+}
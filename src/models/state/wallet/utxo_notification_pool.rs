@@ -281,6 +281,32 @@ impl UtxoNotificationPool {
         Ok(addition_record)
     }
 
+    /// Delete the expected UTXOs that a transaction of this wallet would have paid back to
+    /// it, when the transaction is replaced by another one and can no longer be mined.
+    /// Outputs that the replacement pays as well, and UTXOs that were mined, are kept.
+    pub fn drop_unmined_own_outputs(
+        &mut self,
+        replaced_transaction: &Transaction,
+        replacement: &Transaction,
+    ) {
+        let dropped_addition_records = replaced_transaction
+            .kernel
+            .outputs
+            .iter()
+            .filter(|output| !replacement.kernel.outputs.contains(output))
+            .filter(|output| {
+                self.notifications.get(output).is_some_and(|expected_utxo| {
+                    expected_utxo.received_from == UtxoNotifier::Myself
+                        && expected_utxo.mined_in_block.is_none()
+                })
+            })
+            .copied()
+            .collect_vec();
+        for addition_record in dropped_addition_records {
+            self.drop_expected_utxo(addition_record);
+        }
+    }
+
     /// Mark an expected incoming UTXO as received
     pub fn mark_as_received(
         &mut self,
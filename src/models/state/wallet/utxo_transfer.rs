@@ -0,0 +1,187 @@
+use crate::prelude::twenty_first;
+
+use anyhow::{bail, Context, Result};
+use serde::{Deserialize, Serialize};
+use std::fmt;
+use std::fs;
+use std::path::Path;
+use twenty_first::shared_math::digest::Digest;
+use twenty_first::util_types::algebraic_hasher::AlgebraicHasher;
+
+use super::outgoing_transaction::OffChainOutput;
+use crate::config_models::network::Network;
+use crate::models::blockchain::block::block_height::BlockHeight;
+use crate::models::blockchain::transaction::utxo::Utxo;
+use crate::util_types::mutator_set::addition_record::AdditionRecord;
+use crate::util_types::mutator_set::mutator_set_trait::commit;
+use crate::util_types::secret_file::create_secret_file;
+use crate::Hash;
+
+/// The version of the format of UTXO transfer notifications.
+pub const UTXO_TRANSFER_NOTIFICATION_VERSION: u8 = 0;
+
+/// Everything the receiver of a UTXO needs to claim it, for outputs whose notification
+/// was not published in the transaction. The sender exports it to a file and hands it to
+/// the receiver out of band.
+#[derive(Clone, Debug, Serialize, Deserialize, PartialEq, Eq)]
+pub struct UtxoTransferNotification {
+    pub version: u8,
+    pub network: Network,
+
+    /// The ID of the transaction that created the UTXO, for reference only
+    pub txid: Digest,
+
+    pub utxo: Utxo,
+    pub sender_randomness: Digest,
+    pub receiver_privacy_digest: Digest,
+    pub addition_record: AdditionRecord,
+
+    /// The block that confirmed the transaction, if it was confirmed when the notification
+    /// was exported
+    pub block_hint: Option<Digest>,
+}
+
+impl UtxoTransferNotification {
+    pub fn new(
+        network: Network,
+        txid: Digest,
+        off_chain_output: &OffChainOutput,
+        block_hint: Option<Digest>,
+    ) -> Self {
+        let addition_record = commit(
+            Hash::hash(&off_chain_output.utxo),
+            off_chain_output.sender_randomness,
+            off_chain_output.receiver_privacy_digest,
+        );
+        Self {
+            version: UTXO_TRANSFER_NOTIFICATION_VERSION,
+            network,
+            txid,
+            utxo: off_chain_output.utxo.clone(),
+            sender_randomness: off_chain_output.sender_randomness,
+            receiver_privacy_digest: off_chain_output.receiver_privacy_digest,
+            addition_record,
+            block_hint,
+        }
+    }
+
+    /// Return true iff the addition record is the commitment to the UTXO and the secrets
+    /// in this notification.
+    pub fn has_valid_commitment(&self) -> bool {
+        self.addition_record
+            == commit(
+                Hash::hash(&self.utxo),
+                self.sender_randomness,
+                self.receiver_privacy_digest,
+            )
+    }
+
+    /// Write the notification to a new file. Refuses to overwrite existing files. Anyone
+    /// holding the notification learns the amount and can link it to the receiver, so
+    /// only the owner may read the file.
+    pub fn save_to_file(&self, path: &Path) -> Result<()> {
+        if path.exists() {
+            bail!("File {} already exists", path.to_string_lossy());
+        }
+        let as_json = serde_json::to_string_pretty(self)?;
+        create_secret_file(path, as_json.as_bytes())
+            .with_context(|| format!("Failed to write {}", path.to_string_lossy()))
+    }
+
+    pub fn read_from_file(path: &Path) -> Result<Self> {
+        let as_json = fs::read_to_string(path)
+            .with_context(|| format!("Failed to read {}", path.to_string_lossy()))?;
+        let notification: Self = serde_json::from_str(&as_json).with_context(|| {
+            format!(
+                "Failed to decode UTXO transfer notification from {}",
+                path.to_string_lossy()
+            )
+        })?;
+        if notification.version != UTXO_TRANSFER_NOTIFICATION_VERSION {
+            bail!(
+                "Unsupported UTXO transfer notification version {}",
+                notification.version
+            );
+        }
+
+        Ok(notification)
+    }
+}
+
+/// The result of claiming a UTXO from a transfer notification.
+#[derive(Clone, Copy, Debug, Serialize, Deserialize, PartialEq, Eq)]
+pub enum UtxoClaimStatus {
+    /// The UTXO was found in the given block and added to the wallet
+    Confirmed(Digest, BlockHeight),
+
+    /// The UTXO is not confirmed yet. The wallet picks it up from the block that confirms
+    /// it, as long as the node is not restarted before then.
+    Pending,
+}
+
+impl fmt::Display for UtxoClaimStatus {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            UtxoClaimStatus::Confirmed(_, height) => write!(f, "confirmed at height {height}"),
+            UtxoClaimStatus::Pending => write!(f, "pending"),
+        }
+    }
+}
+
+#[cfg(test)]
+mod utxo_transfer_tests {
+    use rand::random;
+
+    use crate::models::blockchain::transaction::neptune_coins::NeptuneCoins;
+    use crate::models::state::wallet::WalletSecret;
+    use crate::tests::shared::unit_test_data_directory;
+
+    use super::*;
+
+    #[test]
+    fn utxo_transfer_notification_file_roundtrip_test() {
+        let network = Network::RegTest;
+        let address = WalletSecret::new_random()
+            .nth_generation_spending_key(0)
+            .to_address();
+        let off_chain_output = OffChainOutput {
+            utxo: Utxo::new(
+                address.lock_script(),
+                NeptuneCoins::new(3).to_native_coins(),
+            ),
+            sender_randomness: random(),
+            receiver_privacy_digest: address.privacy_digest,
+        };
+        let notification =
+            UtxoTransferNotification::new(network, random(), &off_chain_output, None);
+        assert!(notification.has_valid_commitment());
+
+        let mut tampered = notification.clone();
+        tampered.sender_randomness = random();
+        assert!(!tampered.has_valid_commitment());
+
+        let data_dir = unit_test_data_directory(network).unwrap();
+        fs::create_dir_all(data_dir.root_dir_path()).unwrap();
+        let path = data_dir.root_dir_path().join("utxo.json");
+        notification.save_to_file(&path).unwrap();
+        assert!(
+            notification.save_to_file(&path).is_err(),
+            "Existing notification files must not be overwritten"
+        );
+        assert_eq!(
+            notification,
+            UtxoTransferNotification::read_from_file(&path).unwrap()
+        );
+
+        #[cfg(unix)]
+        {
+            use std::os::unix::fs::PermissionsExt;
+            let mode = fs::metadata(&path).unwrap().permissions().mode();
+            assert_eq!(
+                0o600,
+                mode & 0o777,
+                "Only the owner may read the notification"
+            );
+        }
+    }
+}
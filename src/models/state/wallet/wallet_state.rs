@@ -2,13 +2,13 @@ use crate::prelude::twenty_first;
 
 use anyhow::{bail, Result};
 use itertools::Itertools;
-use num_traits::Zero;
+use num_traits::CheckedSub;
 use serde_derive::{Deserialize, Serialize};
 use std::collections::HashMap;
 use std::error::Error;
 use std::fmt::Debug;
 use std::path::PathBuf;
-use std::time::Duration;
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
 use tokio::fs::OpenOptions;
 use tokio::io::{AsyncBufReadExt, AsyncWriteExt, BufReader, BufWriter};
 use tracing::{debug, error, info, warn};
@@ -17,30 +17,52 @@ use twenty_first::shared_math::digest::Digest;
 use twenty_first::storage::level_db::DB;
 use twenty_first::util_types::algebraic_hasher::AlgebraicHasher;
 use twenty_first::util_types::emojihash_trait::Emojihash;
+use twenty_first::util_types::mmr::mmr_trait::Mmr;
 use twenty_first::util_types::storage_schema::traits::*;
 use twenty_first::util_types::storage_vec::traits::*;
 
+use super::address::generation_address;
+use super::address::multisig_address::{MultisigAddress, MultisigKey};
+use super::coin_selection::CoinSelectionStrategy;
+use super::multisig::{
+    MultisigSetup, MultisigUtxo, PartiallySignedTransaction, MULTISIG_KEY_SEARCH_DEPTH,
+};
+use super::outgoing_transaction::{OffChainOutput, OutgoingTransaction, OutgoingTransactionStatus};
 use super::rusty_wallet_database::RustyWalletDatabase;
+use super::unconfirmed_send::UnconfirmedSend;
 use super::utxo_notification_pool::{UtxoNotificationPool, UtxoNotifier};
+use super::utxo_transfer::UtxoTransferNotification;
 use super::wallet_status::{WalletStatus, WalletStatusElement};
 use super::{WalletSecret, WALLET_INCOMING_SECRETS_FILE_NAME};
 use crate::config_models::cli_args::Args;
 use crate::config_models::data_directory::DataDirectory;
+use crate::config_models::network::Network;
 use crate::models::blockchain::block::Block;
 use crate::models::blockchain::transaction::native_coin::NATIVE_COIN_TYPESCRIPT_DIGEST;
-use crate::models::blockchain::transaction::utxo::{LockScript, Utxo};
+use crate::models::blockchain::transaction::timelock::Timelock;
+use crate::models::blockchain::transaction::token::{self, TokenDefinition};
+use crate::models::blockchain::transaction::utxo::{LockScript, TypeScript, Utxo};
 use crate::models::blockchain::transaction::{neptune_coins::NeptuneCoins, Transaction};
 use crate::models::state::wallet::monitored_utxo::MonitoredUtxo;
 use crate::util_types::mutator_set::addition_record::AdditionRecord;
 use crate::util_types::mutator_set::ms_membership_proof::MsMembershipProof;
 use crate::util_types::mutator_set::mutator_set_accumulator::MutatorSetAccumulator;
+use crate::util_types::mutator_set::mutator_set_kernel::get_swbf_indices;
 use crate::util_types::mutator_set::mutator_set_trait::MutatorSet;
 use crate::util_types::mutator_set::removal_record::{AbsoluteIndexSet, RemovalRecord};
 use crate::Hash;
 
+/// Number of generation addresses beyond the highest handed-out derivation index that
+/// the wallet scans incoming public announcements for. This covers addresses that were
+/// handed out but not recorded, e.g. when a wallet is restored from its seed phrase.
+pub const GENERATION_ADDRESS_GAP_LIMIT: u16 = 20;
+
 pub struct WalletState {
     pub wallet_db: RustyWalletDatabase,
-    pub wallet_secret: WalletSecret,
+
+    /// The secret that the keys of this wallet are derived from. `None` for watch-only
+    /// wallets, which can see incoming UTXOs but cannot spend them.
+    pub wallet_secret: Option<WalletSecret>,
     pub number_of_mps_per_utxo: usize,
 
     // Any thread may read from expected_utxos, only main thread may write
@@ -48,6 +70,11 @@ pub struct WalletState {
 
     /// Path to directory containing wallet files
     wallet_directory_path: PathBuf,
+
+    /// View keys of the generation addresses that are scanned for incoming UTXOs, ordered by
+    /// their derivation index. Derivation is expensive, so the keys are derived once and
+    /// cached here. Watch-only wallets hold the imported keys only, which can skip indices.
+    known_generation_keys: Vec<generation_address::ViewKey>,
 }
 
 /// Contains the cryptographic (non-public) data that is needed to recover the mutator set
@@ -60,6 +87,23 @@ pub(crate) struct IncomingUtxoRecoveryData {
     pub aocl_index: u64,
 }
 
+/// A time-locked lock script of one of this wallet's generation addresses, learned from the
+/// announcement of a UTXO that is locked with it.
+#[derive(Clone, Copy, Debug, Serialize, Deserialize, PartialEq, Eq)]
+pub struct KnownTimelock {
+    pub lock_script_hash: Digest,
+    pub generation_key_index: u16,
+    pub timelock: Timelock,
+}
+
+/// A token registered with this wallet under a name of the user's choosing. Only UTXOs of
+/// registered tokens can be spent, as spending requires the token's type script.
+#[derive(Clone, Debug, Serialize, Deserialize, PartialEq, Eq)]
+pub struct KnownToken {
+    pub name: String,
+    pub definition: TokenDefinition,
+}
+
 #[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
 struct StrongUtxoKey {
     utxo_digest: Digest,
@@ -82,6 +126,10 @@ impl Debug for WalletState {
             .field("number_of_mps_per_utxo", &self.number_of_mps_per_utxo)
             .field("expected_utxos", &self.expected_utxos)
             .field("wallet_directory_path", &self.wallet_directory_path)
+            .field(
+                "known_generation_keys_count",
+                &self.known_generation_keys.len(),
+            )
             .finish()
     }
 }
@@ -96,7 +144,7 @@ impl WalletState {
     /// the wallet database is deleted.
     ///
     /// Uses non-blocking I/O via tokio.
-    async fn store_utxo_ms_recovery_data(
+    pub(crate) async fn store_utxo_ms_recovery_data(
         &self,
         utxo_ms_recovery_data: IncomingUtxoRecoveryData,
     ) -> Result<()> {
@@ -158,6 +206,27 @@ impl WalletState {
         wallet_secret: WalletSecret,
         cli_args: &Args,
     ) -> Self {
+        Self::new(data_dir, Some(wallet_secret), vec![], cli_args).await
+    }
+
+    /// Create a wallet that monitors the addresses of the given view keys, but cannot spend
+    /// from them. The view keys must have distinct derivation indices.
+    pub async fn new_watch_only(
+        data_dir: &DataDirectory,
+        view_keys: Vec<generation_address::ViewKey>,
+        cli_args: &Args,
+    ) -> Self {
+        Self::new(data_dir, None, view_keys, cli_args).await
+    }
+
+    async fn new(
+        data_dir: &DataDirectory,
+        wallet_secret: Option<WalletSecret>,
+        mut view_keys: Vec<generation_address::ViewKey>,
+        cli_args: &Args,
+    ) -> Self {
+        view_keys.sort_by_key(|view_key| view_key.derivation_index);
+
         // Create or connect to wallet block DB
 
         DataDirectory::create_dir_if_not_exists(&data_dir.wallet_database_dir_path())
@@ -189,7 +258,10 @@ impl WalletState {
                 cli_args.max_unconfirmed_utxo_notification_count_per_peer,
             ),
             wallet_directory_path: data_dir.wallet_directory_path(),
+            known_generation_keys: view_keys,
         };
+        ret.derive_known_generation_keys();
+        ret.restore_pending_utxo_claims();
 
         // Wallet state has to be initialized with the genesis block, otherwise the outputs
         // from genesis would be unspendable. This should only be done *once* though.
@@ -198,8 +270,8 @@ impl WalletState {
         // outputs.
         if sync_label == Digest::default() {
             // Check if we are premine recipients
-            let own_spending_key = ret.wallet_secret.nth_generation_spending_key(0);
-            let own_receiving_address = own_spending_key.to_address();
+            let own_view_key = ret.known_generation_keys[0];
+            let own_receiving_address = own_view_key.receiving_address;
             for (premine_receiving_address, amount) in Block::premine_distribution() {
                 if premine_receiving_address == own_receiving_address {
                     let coins = amount.to_native_coins();
@@ -210,7 +282,7 @@ impl WalletState {
                         .add_expected_utxo(
                             utxo,
                             Digest::default(),
-                            own_spending_key.privacy_preimage,
+                            own_view_key.privacy_preimage,
                             UtxoNotifier::Premine,
                         )
                         .unwrap();
@@ -228,6 +300,204 @@ impl WalletState {
         ret
     }
 
+    /// Return the highest derivation index of a generation address handed out by this wallet.
+    pub fn generation_key_counter(&self) -> u16 {
+        self.wallet_db
+            .get_generation_key_counter()
+            .try_into()
+            .expect("Generation key counter must fit in a u16")
+    }
+
+    /// Return true iff this wallet holds view keys only, and therefore cannot spend.
+    pub fn is_watch_only(&self) -> bool {
+        self.wallet_secret.is_none()
+    }
+
+    /// Return the wallet secret, or an error if this is a watch-only wallet.
+    pub fn spending_secret(&self) -> Result<&WalletSecret> {
+        match &self.wallet_secret {
+            Some(wallet_secret) => Ok(wallet_secret),
+            None => bail!("A watch-only wallet cannot spend or create new addresses"),
+        }
+    }
+
+    /// Return the generation address with the lowest known derivation index, which receives
+    /// change and mining rewards. This is index 0 unless a watch-only wallet left it out.
+    pub fn own_receiving_address(&self) -> generation_address::ReceivingAddress {
+        self.known_generation_keys[0].receiving_address
+    }
+
+    /// Return the view keys of the generation addresses that this wallet scans, in the order
+    /// of their derivation indices. These can be imported into a watch-only wallet.
+    pub fn known_view_keys(&self) -> &[generation_address::ViewKey] {
+        &self.known_generation_keys
+    }
+
+    /// Derive and cache all generation keys up to the gap limit beyond the highest handed-out
+    /// derivation index. Watch-only wallets cannot derive keys, so they keep the imported
+    /// ones.
+    fn derive_known_generation_keys(&mut self) {
+        let Some(wallet_secret) = &self.wallet_secret else {
+            return;
+        };
+        let last_index_to_scan = self
+            .generation_key_counter()
+            .saturating_add(GENERATION_ADDRESS_GAP_LIMIT);
+        while self.known_generation_keys.len() <= last_index_to_scan as usize {
+            let index = self.known_generation_keys.len() as u16;
+            let view_key = wallet_secret
+                .nth_generation_spending_key(index)
+                .to_view_key(index);
+            self.known_generation_keys.push(view_key);
+        }
+    }
+
+    /// Record that the generation address with the given derivation index is in use. This
+    /// moves the scanning window along if the index is beyond the highest one handed out.
+    /// The newly derived keys are only scanned for in later blocks, so the caller must
+    /// rescan the chain to find UTXOs that earlier blocks sent to them.
+    fn mark_generation_key_as_used(&mut self, index: u16) {
+        if index <= self.generation_key_counter() {
+            return;
+        }
+
+        info!("Generation address with derivation index {index} is in use");
+        self.wallet_db.set_generation_key_counter(index.into());
+        self.derive_known_generation_keys();
+    }
+
+    /// Hand out the spending key of a generation address that was not handed out before,
+    /// and persist the new derivation index such that the address is scanned for after a
+    /// restart.
+    pub fn next_unused_generation_spending_key(
+        &mut self,
+    ) -> Result<generation_address::SpendingKey> {
+        let next_index = match self.generation_key_counter().checked_add(1) {
+            Some(index) => index,
+            None => bail!("All generation address derivation indices are in use"),
+        };
+        let spending_key = self
+            .spending_secret()?
+            .nth_generation_spending_key(next_index);
+        self.mark_generation_key_as_used(next_index);
+        self.wallet_db.persist();
+
+        Ok(spending_key)
+    }
+
+    /// Return the derivation index and the view key of the generation address that the UTXO
+    /// is locked to, if the address belongs to this wallet.
+    fn find_generation_key_for_utxo(
+        &self,
+        utxo: &Utxo,
+    ) -> Option<(u16, &generation_address::ViewKey)> {
+        if let Some(timelock) = self.find_timelock(utxo.lock_script_hash) {
+            return self
+                .known_generation_keys
+                .iter()
+                .find(|view_key| view_key.derivation_index == timelock.generation_key_index)
+                .map(|view_key| (view_key.derivation_index, view_key));
+        }
+
+        self.known_generation_keys
+            .iter()
+            .find(|view_key| {
+                view_key.receiving_address.lock_script().hash() == utxo.lock_script_hash
+            })
+            .map(|view_key| (view_key.derivation_index, view_key))
+    }
+
+    /// Return the lock script of a UTXO of this wallet, which is either the standard or a
+    /// time-locked lock script of one of its generation addresses.
+    fn find_lock_script_for_utxo(&self, utxo: &Utxo) -> Option<LockScript> {
+        let (_index, view_key) = self.find_generation_key_for_utxo(utxo)?;
+        let lock_script = match self.find_timelock(utxo.lock_script_hash) {
+            Some(known_timelock) => view_key
+                .receiving_address
+                .timelocked_lock_script(known_timelock.timelock),
+            None => view_key.receiving_address.lock_script(),
+        };
+
+        Some(lock_script)
+    }
+
+    /// Return the time lock of UTXOs with the given lock script hash, if this is the hash of
+    /// a known time-locked lock script of this wallet.
+    pub fn find_timelock(&self, lock_script_hash: Digest) -> Option<KnownTimelock> {
+        self.wallet_db
+            .timelocks()
+            .iter()
+            .map(|(_index, timelock)| timelock)
+            .find(|timelock| timelock.lock_script_hash == lock_script_hash)
+    }
+
+    /// Return true iff the UTXO can be spent by a transaction with the given timestamp, in
+    /// milliseconds since the UNIX epoch, and the given minimum block height. Only
+    /// time-locked UTXOs can be unreleased.
+    pub fn is_released(&self, utxo: &Utxo, timestamp: u64, minimum_block_height: u64) -> bool {
+        self.find_timelock(utxo.lock_script_hash)
+            .map_or(true, |known_timelock| {
+                known_timelock
+                    .timelock
+                    .is_released(timestamp, minimum_block_height)
+            })
+    }
+
+    /// Remember the time-locked lock scripts of the UTXOs that the transaction announces to
+    /// this wallet's generation addresses, such that these UTXOs are recognized as own and
+    /// can be unlocked once released.
+    fn register_announced_timelocks(&mut self, transaction: &Transaction) {
+        let announced_timelocks = self
+            .known_generation_keys
+            .iter()
+            .flat_map(|view_key| {
+                view_key
+                    .scan_for_announced_timelocks(transaction)
+                    .into_iter()
+                    .map(move |(lock_script_hash, timelock)| KnownTimelock {
+                        lock_script_hash,
+                        generation_key_index: view_key.derivation_index,
+                        timelock,
+                    })
+            })
+            .collect_vec();
+        for timelock in announced_timelocks {
+            if self.find_timelock(timelock.lock_script_hash).is_none() {
+                self.wallet_db.timelocks_mut().push(timelock);
+            }
+        }
+    }
+
+    /// Return the spending key of the generation address that the UTXO is locked to, if the
+    /// address belongs to this wallet and this wallet is not watch-only.
+    pub fn find_spending_key_for_utxo(
+        &self,
+        utxo: &Utxo,
+    ) -> Option<generation_address::SpendingKey> {
+        let (index, _view_key) = self.find_generation_key_for_utxo(utxo)?;
+        self.wallet_secret
+            .as_ref()
+            .map(|wallet_secret| wallet_secret.nth_generation_spending_key(index))
+    }
+
+    /// Return the derivation index of the generation key that unlocks the UTXO, if the UTXO
+    /// belongs to this wallet. Works for watch-only wallets too.
+    pub fn generation_key_index_for_utxo(&self, utxo: &Utxo) -> Option<u16> {
+        self.find_generation_key_for_utxo(utxo)
+            .map(|(index, _view_key)| index)
+    }
+
+    /// Return the derivation index of the generation key with the given receiver preimage.
+    fn generation_key_index_from_receiver_preimage(
+        &self,
+        receiver_preimage: Digest,
+    ) -> Option<u16> {
+        self.known_generation_keys
+            .iter()
+            .find(|view_key| view_key.privacy_preimage == receiver_preimage)
+            .map(|view_key| view_key.derivation_index)
+    }
+
     /// Return a list of UTXOs spent by this wallet in the transaction
     fn scan_for_spent_utxos(
         &self,
@@ -263,15 +533,11 @@ impl WalletState {
         &self,
         transaction: &Transaction,
     ) -> Vec<(AdditionRecord, Utxo, Digest, Digest)> {
-        // TODO: These spending keys should probably be derived dynamically from some
-        // state in the wallet. And we should allow for other types than just generation
-        // addresses.
-        let spending_keys = [self.wallet_secret.nth_generation_spending_key(0)];
-
-        // get recognized UTXOs
-        let recognized_utxos = spending_keys
+        // TODO: We should allow for other types than just generation addresses.
+        let recognized_utxos = self
+            .known_generation_keys
             .iter()
-            .map(|spending_key| spending_key.scan_for_announced_utxos(transaction))
+            .map(|view_key| view_key.scan_for_announced_utxos(transaction))
             .collect_vec()
             .concat();
 
@@ -285,6 +551,59 @@ impl WalletState {
             .collect_vec()
     }
 
+    /// Return the recovery data of all outputs of the block that belong to this wallet,
+    /// either because they were announced to one of its generation addresses or because
+    /// they were expected. Generation addresses that are found to be in use move the
+    /// scanning window along. Used to rebuild the monitored UTXOs from historical blocks.
+    pub(crate) fn scan_block_for_own_utxos(
+        &mut self,
+        block: &Block,
+    ) -> Vec<IncomingUtxoRecoveryData> {
+        let transaction = &block.kernel.body.transaction;
+        self.register_announced_timelocks(transaction);
+        let mut own_outputs = self.scan_for_announced_utxos(transaction);
+        let used_generation_key_indices = own_outputs
+            .iter()
+            .filter_map(|(_ar, _utxo, _sr, receiver_preimage)| {
+                self.generation_key_index_from_receiver_preimage(*receiver_preimage)
+            })
+            .collect_vec();
+        for index in used_generation_key_indices {
+            self.mark_generation_key_as_used(index);
+        }
+        own_outputs.append(&mut self.expected_utxos.scan_for_expected_utxos(transaction));
+
+        // The outputs of a block are added to the AOCL in order, and the mutator set
+        // accumulator of the block includes all of them.
+        let outputs = &transaction.kernel.outputs;
+        let first_aocl_index = block
+            .kernel
+            .body
+            .mutator_set_accumulator
+            .kernel
+            .aocl
+            .count_leaves()
+            - outputs.len() as u64;
+
+        own_outputs
+            .into_iter()
+            .unique_by(|(addition_record, _utxo, _sr, _rp)| *addition_record)
+            .filter_map(
+                |(addition_record, utxo, sender_randomness, receiver_preimage)| {
+                    outputs
+                        .iter()
+                        .position(|output| *output == addition_record)
+                        .map(|position| IncomingUtxoRecoveryData {
+                            utxo,
+                            sender_randomness,
+                            receiver_preimage,
+                            aocl_index: first_aocl_index + position as u64,
+                        })
+                },
+            )
+            .collect_vec()
+    }
+
     /// Update wallet state with new block. Assumes the given block
     /// is valid and that the wallet state is not up to date yet.
     pub async fn update_wallet_state_with_new_block(
@@ -294,8 +613,27 @@ impl WalletState {
     ) -> Result<()> {
         let transaction: Transaction = new_block.kernel.body.transaction.clone();
 
+        // Sends whose inputs are spent by this block were confirmed, either as sent or in a
+        // replaced form.
+        let mut unconfirmed_sends = self.wallet_db.get_unconfirmed_sends();
+        let unconfirmed_send_count = unconfirmed_sends.len();
+        unconfirmed_sends.retain(|send| !send.shares_inputs_with(&transaction));
+        if unconfirmed_sends.len() != unconfirmed_send_count {
+            self.wallet_db.set_unconfirmed_sends(unconfirmed_sends);
+        }
+
+        // Claimed UTXOs that this block confirms are picked up as expected UTXOs below
+        for addition_record in transaction.kernel.outputs.iter() {
+            self.forget_pending_utxo_claim(*addition_record);
+        }
+
         let spent_inputs: Vec<(Utxo, AbsoluteIndexSet, u64)> =
             self.scan_for_spent_utxos(&transaction);
+        self.update_outgoing_transaction_statuses(&spent_inputs, new_block);
+
+        self.register_announced_timelocks(&transaction);
+        let multisig_setups = self.multisig_setups();
+        self.scan_block_for_multisig_utxos(new_block, &multisig_setups);
 
         // utxo, sender randomness, receiver preimage, addition record
         let mut received_outputs: Vec<(AdditionRecord, Utxo, Digest, Digest)> = vec![];
@@ -304,6 +642,19 @@ impl WalletState {
             "received_outputs as announced outputs = {}",
             received_outputs.len()
         );
+
+        // Move the scanning window along if UTXOs were received on generation addresses
+        // beyond the highest derivation index known to be in use.
+        let used_generation_key_indices = received_outputs
+            .iter()
+            .filter_map(|(_ar, _utxo, _sr, receiver_preimage)| {
+                self.generation_key_index_from_receiver_preimage(*receiver_preimage)
+            })
+            .collect_vec();
+        for index in used_generation_key_indices {
+            self.mark_generation_key_as_used(index);
+        }
+
         let expected_utxos_in_this_block =
             self.expected_utxos.scan_for_expected_utxos(&transaction);
         received_outputs.append(&mut expected_utxos_in_this_block.clone());
@@ -582,6 +933,8 @@ impl WalletState {
         }
 
         self.wallet_db.set_sync_label(new_block.hash());
+        self.wallet_db
+            .set_sync_height(new_block.kernel.header.height);
         self.wallet_db.persist();
 
         // Mark all expected UTXOs that were received in this block as received
@@ -596,6 +949,562 @@ impl WalletState {
         Ok(())
     }
 
+    /// Return the transactions sent from this wallet that are not confirmed yet, oldest
+    /// first. A send is forgotten when a block spends any of its inputs.
+    pub fn unconfirmed_sends(&self) -> Vec<UnconfirmedSend> {
+        self.wallet_db.get_unconfirmed_sends()
+    }
+
+    pub fn get_unconfirmed_send(&self, txid: Digest) -> Option<UnconfirmedSend> {
+        self.wallet_db
+            .get_unconfirmed_sends()
+            .into_iter()
+            .find(|send| send.txid() == txid)
+    }
+
+    /// Start tracking a transaction sent from this wallet. Sends that this transaction
+    /// replaces, because it spends some of their inputs, are no longer tracked, and the
+    /// change they would have returned to this wallet is no longer expected.
+    pub fn add_unconfirmed_send(&mut self, unconfirmed_send: UnconfirmedSend) {
+        let (mut unconfirmed_sends, replaced_sends): (Vec<_>, Vec<_>) = self
+            .wallet_db
+            .get_unconfirmed_sends()
+            .into_iter()
+            .partition(|send| !send.shares_inputs_with(&unconfirmed_send.transaction));
+        for replaced_send in replaced_sends {
+            self.expected_utxos.drop_unmined_own_outputs(
+                &replaced_send.transaction,
+                &unconfirmed_send.transaction,
+            );
+        }
+        unconfirmed_sends.push(unconfirmed_send);
+        self.wallet_db.set_unconfirmed_sends(unconfirmed_sends);
+    }
+
+    /// Record a transaction sent from this wallet. The first outputs of the transaction must
+    /// pay the given recipients, in order. The secrets of the outputs whose notification is
+    /// not published in the transaction are kept, such that they can be exported later.
+    pub fn record_outgoing_transaction(
+        &mut self,
+        transaction: &Transaction,
+        recipients: Vec<(generation_address::ReceivingAddress, NeptuneCoins)>,
+        off_chain_outputs: Vec<OffChainOutput>,
+    ) -> Result<()> {
+        if transaction.kernel.outputs.len() < recipients.len() {
+            bail!(
+                "Transaction has {} outputs but pays {} recipients",
+                transaction.kernel.outputs.len(),
+                recipients.len()
+            );
+        }
+
+        let spent_inputs = self.scan_for_spent_utxos(transaction);
+        let input_amount = spent_inputs
+            .iter()
+            .map(|(utxo, _absolute_indices, _mutxo_index)| utxo.get_native_coin_amount())
+            .sum::<NeptuneCoins>();
+        let amount = recipients
+            .iter()
+            .map(|(_address, amount)| *amount)
+            .sum::<NeptuneCoins>();
+        let Some(change) = input_amount.checked_sub(&(amount + transaction.kernel.fee)) else {
+            bail!("Inputs of transaction do not cover the amount sent and the fee");
+        };
+
+        let recipient_addition_records = transaction.kernel.outputs[..recipients.len()].to_vec();
+        self.wallet_db
+            .outgoing_transactions_mut()
+            .push(OutgoingTransaction {
+                txid: Hash::hash(transaction),
+                timestamp: Duration::from_millis(transaction.kernel.timestamp.value()),
+                recipients,
+                fee: transaction.kernel.fee,
+                change,
+                label: String::default(),
+                status: OutgoingTransactionStatus::Pending,
+                input_monitored_utxo_indices: spent_inputs
+                    .into_iter()
+                    .map(|(_utxo, _absolute_indices, mutxo_index)| mutxo_index)
+                    .collect(),
+                recipient_addition_records,
+                off_chain_outputs,
+            });
+
+        Ok(())
+    }
+
+    /// Update the record of a sent transaction that was replaced by a transaction with a
+    /// higher fee. The difference in fee is taken from the change.
+    pub fn record_fee_bump(
+        &mut self,
+        replaced_txid: Digest,
+        replacement: &Transaction,
+    ) -> Result<()> {
+        let Some((index, mut record)) = self
+            .wallet_db
+            .outgoing_transactions()
+            .iter()
+            .find(|(_index, record)| record.txid == replaced_txid)
+        else {
+            bail!("No outgoing transaction with ID {replaced_txid}");
+        };
+        let Some(change) = (record.change + record.fee).checked_sub(&replacement.kernel.fee) else {
+            bail!("Fee of replacement exceeds the change of transaction {replaced_txid}");
+        };
+
+        record.txid = Hash::hash(replacement);
+        record.fee = replacement.kernel.fee;
+        record.change = change;
+        self.wallet_db
+            .outgoing_transactions_mut()
+            .set(index, record);
+
+        Ok(())
+    }
+
+    /// Return the transactions sent from this wallet, oldest first.
+    pub fn outgoing_transactions(&self) -> Vec<OutgoingTransaction> {
+        self.wallet_db
+            .outgoing_transactions()
+            .iter()
+            .map(|(_index, record)| record)
+            .collect()
+    }
+
+    /// Return notifications for the outputs of a sent transaction whose notification was not
+    /// published in the transaction. If the transaction is confirmed, the notifications
+    /// point the receiver to the confirming block.
+    pub fn utxo_transfer_notifications(
+        &self,
+        txid: Digest,
+        network: Network,
+    ) -> Result<Vec<UtxoTransferNotification>> {
+        let Some((_index, record)) = self
+            .wallet_db
+            .outgoing_transactions()
+            .iter()
+            .find(|(_index, record)| record.txid == txid)
+        else {
+            bail!("No outgoing transaction with ID {txid}");
+        };
+        let block_hint = match record.status {
+            OutgoingTransactionStatus::Pending => None,
+            OutgoingTransactionStatus::Confirmed(block_digest, _height) => Some(block_digest),
+            OutgoingTransactionStatus::Abandoned(_) => {
+                bail!("Transaction {txid} was abandoned, so its outputs cannot be claimed")
+            }
+        };
+
+        Ok(record
+            .off_chain_outputs
+            .iter()
+            .map(|output| UtxoTransferNotification::new(network, txid, output, block_hint))
+            .collect())
+    }
+
+    /// Expect the UTXO of a transfer notification that is not confirmed yet. The claim is
+    /// stored in the wallet database, so the UTXO is still expected after a restart, until a
+    /// block confirms it.
+    pub fn add_pending_utxo_claim(
+        &mut self,
+        notification: UtxoTransferNotification,
+        receiver_preimage: Digest,
+    ) -> Result<()> {
+        self.expected_utxos.add_expected_utxo(
+            notification.utxo.clone(),
+            notification.sender_randomness,
+            receiver_preimage,
+            UtxoNotifier::Cli,
+        )?;
+
+        let mut pending_utxo_claims = self.wallet_db.get_pending_utxo_claims();
+        if !pending_utxo_claims
+            .iter()
+            .any(|claim| claim.addition_record == notification.addition_record)
+        {
+            pending_utxo_claims.push(notification);
+            self.wallet_db.set_pending_utxo_claims(pending_utxo_claims);
+        }
+
+        Ok(())
+    }
+
+    /// Stop tracking the claim of the UTXO with the given addition record, as it was found
+    /// in a block.
+    pub fn forget_pending_utxo_claim(&mut self, addition_record: AdditionRecord) {
+        let mut pending_utxo_claims = self.wallet_db.get_pending_utxo_claims();
+        let pending_utxo_claim_count = pending_utxo_claims.len();
+        pending_utxo_claims.retain(|claim| claim.addition_record != addition_record);
+        if pending_utxo_claims.len() != pending_utxo_claim_count {
+            self.wallet_db.set_pending_utxo_claims(pending_utxo_claims);
+        }
+    }
+
+    /// Return the claimed UTXOs that no block has confirmed yet.
+    pub fn pending_utxo_claims(&self) -> Vec<UtxoTransferNotification> {
+        self.wallet_db.get_pending_utxo_claims()
+    }
+
+    /// Expect the UTXOs of the claims stored in the wallet database again, as the pool of
+    /// expected UTXOs does not survive a restart.
+    fn restore_pending_utxo_claims(&mut self) {
+        for claim in self.wallet_db.get_pending_utxo_claims() {
+            let Some(view_key) =
+                self.find_view_key_for_privacy_digest(claim.receiver_privacy_digest)
+            else {
+                warn!(
+                    "Pending UTXO claim {} is not for an address of this wallet",
+                    claim.addition_record.canonical_commitment.emojihash()
+                );
+                continue;
+            };
+            let receiver_preimage = view_key.privacy_preimage;
+            if let Err(err) = self.expected_utxos.add_expected_utxo(
+                claim.utxo,
+                claim.sender_randomness,
+                receiver_preimage,
+                UtxoNotifier::Cli,
+            ) {
+                warn!("Could not restore pending UTXO claim: {err}");
+            }
+        }
+    }
+
+    /// Return the view key of the generation address with the given privacy digest, if the
+    /// address belongs to this wallet.
+    pub fn find_view_key_for_privacy_digest(
+        &self,
+        receiver_privacy_digest: Digest,
+    ) -> Option<&generation_address::ViewKey> {
+        self.known_generation_keys
+            .iter()
+            .find(|view_key| view_key.receiving_address.privacy_digest == receiver_privacy_digest)
+    }
+
+    /// Set the label of a transaction sent from this wallet.
+    pub fn set_outgoing_transaction_label(&mut self, txid: Digest, label: String) -> Result<()> {
+        let Some((index, mut record)) = self
+            .wallet_db
+            .outgoing_transactions()
+            .iter()
+            .find(|(_index, record)| record.txid == txid)
+        else {
+            bail!("No outgoing transaction with ID {txid}");
+        };
+
+        record.label = label;
+        self.wallet_db
+            .outgoing_transactions_mut()
+            .set(index, record);
+
+        Ok(())
+    }
+
+    /// Mark pending outgoing transactions whose inputs are spent in the given block as
+    /// confirmed if the block pays their recipients, and as abandoned otherwise.
+    fn update_outgoing_transaction_statuses(
+        &mut self,
+        spent_inputs: &[(Utxo, AbsoluteIndexSet, u64)],
+        block: &Block,
+    ) {
+        let spent_mutxo_indices = spent_inputs
+            .iter()
+            .map(|(_utxo, _absolute_indices, mutxo_index)| *mutxo_index)
+            .collect_vec();
+        let block_outputs = &block.kernel.body.transaction.kernel.outputs;
+        let settled_records = self
+            .wallet_db
+            .outgoing_transactions()
+            .iter()
+            .filter(|(_index, record)| {
+                record.status == OutgoingTransactionStatus::Pending
+                    && record
+                        .input_monitored_utxo_indices
+                        .iter()
+                        .any(|mutxo_index| spent_mutxo_indices.contains(mutxo_index))
+            })
+            .collect_vec();
+
+        for (index, mut record) in settled_records {
+            let pays_recipients = record
+                .recipient_addition_records
+                .iter()
+                .all(|addition_record| block_outputs.contains(addition_record));
+            record.status = if pays_recipients {
+                OutgoingTransactionStatus::Confirmed(block.hash(), block.kernel.header.height)
+            } else {
+                warn!("Outgoing transaction {} was abandoned", record.txid);
+                OutgoingTransactionStatus::Abandoned(block.hash())
+            };
+            self.wallet_db
+                .outgoing_transactions_mut()
+                .set(index, record);
+        }
+    }
+
+    /// Set outgoing transactions that were settled in blocks that are no longer canonical
+    /// back to pending, and settle them again by the blocks of the new chain, which are
+    /// given oldest first. Used when the tip moves to a block on another chain.
+    pub(crate) fn reorganize_outgoing_transaction_statuses(
+        &mut self,
+        reverted_block_digests: &[Digest],
+        applied_blocks: &[Block],
+    ) {
+        let reverted_records = self
+            .wallet_db
+            .outgoing_transactions()
+            .iter()
+            .filter(|(_index, record)| match record.status {
+                OutgoingTransactionStatus::Pending => false,
+                OutgoingTransactionStatus::Confirmed(block_digest, _height)
+                | OutgoingTransactionStatus::Abandoned(block_digest) => {
+                    reverted_block_digests.contains(&block_digest)
+                }
+            })
+            .collect_vec();
+        for (index, mut record) in reverted_records {
+            info!(
+                "Outgoing transaction {} is pending again, as the block that settled it was reverted",
+                record.txid
+            );
+            record.status = OutgoingTransactionStatus::Pending;
+            self.wallet_db
+                .outgoing_transactions_mut()
+                .set(index, record);
+        }
+
+        for block in applied_blocks {
+            let spent_inputs = self.scan_for_spent_utxos(&block.kernel.body.transaction);
+            self.update_outgoing_transaction_statuses(&spent_inputs, block);
+        }
+    }
+
+    /// Start tracking the UTXOs of a multisig address that this wallet participates in.
+    /// UTXOs that were sent to the address before are found by
+    /// [`scan_block_for_multisig_utxos`](Self::scan_block_for_multisig_utxos).
+    pub fn add_multisig_setup(&mut self, setup: MultisigSetup) -> Result<()> {
+        if self.own_multisig_key(&setup.address)?.is_none() {
+            bail!("This wallet is not a participant of the multisig address");
+        }
+        if self
+            .multisig_setups()
+            .iter()
+            .any(|known| known.address == setup.address)
+        {
+            bail!("Multisig address was already imported");
+        }
+        self.wallet_db.multisig_setups_mut().push(setup);
+
+        Ok(())
+    }
+
+    pub fn multisig_setups(&self) -> Vec<MultisigSetup> {
+        self.wallet_db.multisig_setups().get_all()
+    }
+
+    /// Return this wallet's key for the given multisig address, if it participates in it.
+    /// Fails for watch-only wallets.
+    pub fn own_multisig_key(&self, address: &MultisigAddress) -> Result<Option<MultisigKey>> {
+        let wallet_secret = self.spending_secret()?;
+        let own_key = (0..MULTISIG_KEY_SEARCH_DEPTH)
+            .map(|index| wallet_secret.nth_multisig_key(index))
+            .find(|key| address.participant_index(key.public_key()).is_some());
+
+        Ok(own_key)
+    }
+
+    /// Add this wallet's signature to a multisig transaction. Multisig keys are one-time
+    /// keys, so a key that signed one kernel refuses to sign another. The signed kernel is
+    /// stored before the signature is handed out.
+    pub fn sign_multisig_transaction(
+        &mut self,
+        transaction: &mut PartiallySignedTransaction,
+    ) -> Result<()> {
+        let Some(key) = self.own_multisig_key(&transaction.address)? else {
+            bail!("This wallet is not a participant of the multisig address");
+        };
+        let public_key = key.public_key();
+        let kernel_hash = transaction.kernel_hash();
+        let signed_kernel = self
+            .wallet_db
+            .multisig_signed_kernels()
+            .get_all()
+            .into_iter()
+            .find(|(signer, _)| *signer == public_key);
+        match signed_kernel {
+            Some((_, signed_kernel_hash)) if signed_kernel_hash != kernel_hash => bail!(
+                "This wallet's key for the multisig address already signed transaction {signed_kernel_hash}, and signing another would reveal it"
+            ),
+            Some(_) => {}
+            None => {
+                self.wallet_db
+                    .multisig_signed_kernels_mut()
+                    .push((public_key, kernel_hash));
+                self.wallet_db.persist();
+            }
+        }
+
+        transaction.sign(&key)
+    }
+
+    /// Return the unspent UTXOs of the given multisig address.
+    pub fn unspent_multisig_utxos(&self, address: &MultisigAddress) -> Vec<MultisigUtxo> {
+        self.wallet_db
+            .multisig_utxos()
+            .get_all()
+            .into_iter()
+            .filter(|multisig_utxo| {
+                multisig_utxo.address == *address && multisig_utxo.spent_in_block.is_none()
+            })
+            .collect()
+    }
+
+    /// Record the UTXOs of the given multisig addresses that the block spends or receives.
+    pub(crate) fn scan_block_for_multisig_utxos(
+        &mut self,
+        block: &Block,
+        setups: &[MultisigSetup],
+    ) {
+        let transaction = &block.kernel.body.transaction;
+        let block_info = (block.hash(), block.kernel.header.height);
+
+        let spent_index_sets = transaction
+            .kernel
+            .inputs
+            .iter()
+            .map(|removal_record| removal_record.absolute_indices.clone())
+            .collect_vec();
+        let newly_spent = self
+            .wallet_db
+            .multisig_utxos()
+            .iter()
+            .filter(|(_index, multisig_utxo)| multisig_utxo.spent_in_block.is_none())
+            .filter_map(|(index, mut multisig_utxo)| {
+                let setup = setups
+                    .iter()
+                    .find(|setup| setup.address == multisig_utxo.address)?;
+                let absolute_indices = AbsoluteIndexSet::new(&get_swbf_indices(
+                    Hash::hash(&multisig_utxo.utxo),
+                    multisig_utxo.sender_randomness,
+                    setup.view_key().privacy_preimage,
+                    multisig_utxo.aocl_index,
+                ));
+                spent_index_sets.contains(&absolute_indices).then(|| {
+                    multisig_utxo.spent_in_block = Some(block_info);
+                    (index, multisig_utxo)
+                })
+            })
+            .collect_vec();
+        for (index, multisig_utxo) in newly_spent {
+            self.wallet_db
+                .multisig_utxos_mut()
+                .set(index, multisig_utxo);
+        }
+
+        // The outputs of the block are the last leaves of its AOCL
+        let outputs = &transaction.kernel.outputs;
+        let first_aocl_index = block
+            .kernel
+            .body
+            .mutator_set_accumulator
+            .kernel
+            .aocl
+            .count_leaves()
+            - outputs.len() as u64;
+        for setup in setups {
+            let lock_script_hash = setup.address.lock_script().hash();
+            for (addition_record, utxo, sender_randomness, _receiver_preimage) in setup
+                .view_key()
+                .scan_for_announced_utxos(transaction)
+                .into_iter()
+                .filter(|(_ar, utxo, _sr, _rp)| utxo.lock_script_hash == lock_script_hash)
+            {
+                let Some(position) = outputs.iter().position(|output| *output == addition_record)
+                else {
+                    continue;
+                };
+                let aocl_index = first_aocl_index + position as u64;
+                let already_known =
+                    self.wallet_db
+                        .multisig_utxos()
+                        .iter()
+                        .any(|(_index, known)| {
+                            known.address == setup.address && known.aocl_index == aocl_index
+                        });
+                if already_known {
+                    continue;
+                }
+                info!(
+                    "Received UTXO of multisig address in block {}, height {}: value = {}",
+                    block.hash().emojihash(),
+                    block.kernel.header.height,
+                    utxo.get_native_coin_amount()
+                );
+                self.wallet_db.multisig_utxos_mut().push(MultisigUtxo {
+                    address: setup.address.clone(),
+                    utxo,
+                    sender_randomness,
+                    aocl_index,
+                    confirmed_in_block: block_info,
+                    spent_in_block: None,
+                });
+            }
+        }
+    }
+
+    /// Register a token under the given name, such that its UTXOs are counted and can be
+    /// spent. Names and definitions must be unique within the wallet.
+    pub fn register_token(&mut self, name: String, definition: TokenDefinition) -> Result<()> {
+        if name.is_empty() {
+            bail!("Token name must not be empty");
+        }
+        for known_token in self.known_tokens() {
+            if known_token.name == name {
+                bail!("A token named {name} is already registered");
+            }
+            if known_token.definition == definition {
+                bail!("Token is already registered as {}", known_token.name);
+            }
+        }
+        self.wallet_db
+            .tokens_mut()
+            .push(KnownToken { name, definition });
+
+        Ok(())
+    }
+
+    /// Define and register a new token that this wallet issues. UTXOs of the wallet's
+    /// generation address with derivation index 0 authorize the issuance.
+    pub fn create_token(&mut self, name: String) -> Result<KnownToken> {
+        let issuer_lock_script_hash = self.own_receiving_address().lock_script().hash();
+        let definition = TokenDefinition::new(issuer_lock_script_hash, rand::random());
+        self.register_token(name.clone(), definition)?;
+
+        Ok(KnownToken { name, definition })
+    }
+
+    pub fn known_tokens(&self) -> Vec<KnownToken> {
+        self.wallet_db.tokens().get_all()
+    }
+
+    pub fn find_token(&self, name: &str) -> Option<KnownToken> {
+        self.known_tokens()
+            .into_iter()
+            .find(|known_token| known_token.name == name)
+    }
+
+    /// Return the type script with the given hash if it is the native coin's or that of a
+    /// registered token.
+    pub fn find_type_script(&self, type_script_hash: Digest) -> Option<TypeScript> {
+        if type_script_hash == NATIVE_COIN_TYPESCRIPT_DIGEST {
+            return Some(TypeScript::native_coin());
+        }
+        self.known_tokens()
+            .into_iter()
+            .map(|known_token| known_token.definition.type_script())
+            .find(|type_script| type_script.hash() == type_script_hash)
+    }
+
     pub async fn is_synced_to(&self, tip_hash: Digest) -> bool {
         let db_sync_digest = self.wallet_db.get_sync_label();
         if db_sync_digest != tip_hash {
@@ -641,6 +1550,18 @@ impl WalletState {
                 }
             }
         }
+        let synced_unspent_token_amounts = self
+            .known_tokens()
+            .into_iter()
+            .map(|known_token| {
+                let type_script_hash = known_token.definition.type_script_hash();
+                let amount = synced_unspent
+                    .iter()
+                    .map(|x| token::token_amount(&x.0 .1, type_script_hash))
+                    .sum();
+                (known_token.name, amount)
+            })
+            .collect();
         WalletStatus {
             synced_unspent_amount: synced_unspent
                 .iter()
@@ -662,6 +1583,7 @@ impl WalletState {
                 .map(|x| x.1.get_native_coin_amount())
                 .sum(),
             unsynced_spent,
+            synced_unspent_token_amounts,
         }
     }
 
@@ -669,6 +1591,7 @@ impl WalletState {
         &self,
         requested_amount: NeptuneCoins,
         tip_digest: Digest,
+        coin_selection: CoinSelectionStrategy,
     ) -> Result<Vec<(Utxo, LockScript, MsMembershipProof)>> {
         // TODO: Should return the correct spending keys associated with the UTXOs
         // We only attempt to generate a transaction using those UTXOs that have up-to-date
@@ -685,51 +1608,377 @@ impl WalletState {
                 tip_digest.emojihash());
         }
 
+        // UTXOs that hold tokens are only spent when sending tokens, such that their tokens
+        // are not moved by accident
+        let released_unspent = self
+            .released_unspent(wallet_status.synced_unspent)
+            .into_iter()
+            .filter(|(wallet_status_element, _membership_proof)| {
+                wallet_status_element
+                    .1
+                    .coins
+                    .iter()
+                    .all(|coin| coin.type_script_hash == NATIVE_COIN_TYPESCRIPT_DIGEST)
+            })
+            .collect_vec();
+        let amounts = released_unspent
+            .iter()
+            .map(|(wallet_status_element, _membership_proof)| {
+                wallet_status_element.1.get_native_coin_amount()
+            })
+            .collect_vec();
+        if amounts.iter().copied().sum::<NeptuneCoins>() < requested_amount {
+            bail!(
+                "Insufficient released amount to create transaction. Requested: {requested_amount}, but part of the synced unspent amount {} is time-locked or held in UTXOs with tokens.",
+                wallet_status.synced_unspent_amount
+            );
+        }
+        let selected_indices = match coin_selection.selector().select(&amounts, requested_amount) {
+            Some(indices) => indices,
+            None => {
+                bail!("Coin selection strategy {coin_selection} could not cover {requested_amount}")
+            }
+        };
+
         let mut ret: Vec<(Utxo, LockScript, MsMembershipProof)> = vec![];
-        let mut allocated_amount = NeptuneCoins::zero();
-        let lock_script = self
-            .wallet_secret
-            .nth_generation_spending_key(0)
-            .to_address()
-            .lock_script();
-        while allocated_amount < requested_amount {
-            let (wallet_status_element, membership_proof) =
-                wallet_status.synced_unspent[ret.len()].clone();
-            allocated_amount = allocated_amount + wallet_status_element.1.get_native_coin_amount();
-            ret.push((
-                wallet_status_element.1,
-                lock_script.clone(),
-                membership_proof,
-            ));
+        for index in selected_indices {
+            ret.push(self.with_lock_script(released_unspent[index].clone())?);
+        }
+
+        Ok(ret)
+    }
+
+    /// Time-locked UTXOs can only be spent once they are released. Return the UTXOs of the
+    /// given ones that a transaction created now can spend, which is only valid from the
+    /// block after the one the wallet is synced to.
+    fn released_unspent(
+        &self,
+        synced_unspent: Vec<(WalletStatusElement, MsMembershipProof)>,
+    ) -> Vec<(WalletStatusElement, MsMembershipProof)> {
+        let now = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .unwrap()
+            .as_millis() as u64;
+        let next_block_height = self.wallet_db.get_sync_height().next();
+        synced_unspent
+            .into_iter()
+            .filter(|(wallet_status_element, _membership_proof)| {
+                self.is_released(&wallet_status_element.1, now, next_block_height.into())
+            })
+            .collect_vec()
+    }
+
+    fn with_lock_script(
+        &self,
+        (wallet_status_element, membership_proof): (WalletStatusElement, MsMembershipProof),
+    ) -> Result<(Utxo, LockScript, MsMembershipProof)> {
+        let Some(lock_script) = self.find_lock_script_for_utxo(&wallet_status_element.1) else {
+            bail!(
+                "Could not find spending key for monitored UTXO with AOCL index {}",
+                wallet_status_element.0
+            );
+        };
+
+        Ok((wallet_status_element.1, lock_script, membership_proof))
+    }
+
+    /// Allocate UTXOs that hold at least the requested amount of the token with the given
+    /// type script hash, largest amounts first.
+    pub fn allocate_token_inputs(
+        &self,
+        type_script_hash: Digest,
+        requested_amount: u64,
+        tip_digest: Digest,
+    ) -> Result<Vec<(Utxo, LockScript, MsMembershipProof)>> {
+        let wallet_status = self.get_wallet_status_from_lock(tip_digest);
+        let token_unspent = self
+            .released_unspent(wallet_status.synced_unspent)
+            .into_iter()
+            .map(|element| {
+                (
+                    token::token_amount(&element.0 .1, type_script_hash),
+                    element,
+                )
+            })
+            .filter(|(amount, _element)| *amount > 0)
+            .sorted_by_key(|(amount, _element)| std::cmp::Reverse(*amount))
+            .collect_vec();
+
+        let mut allocated_amount = 0;
+        let mut ret = vec![];
+        for (amount, element) in token_unspent {
+            if allocated_amount >= requested_amount {
+                break;
+            }
+            allocated_amount += amount;
+            ret.push(self.with_lock_script(element)?);
+        }
+        if allocated_amount < requested_amount {
+            bail!("Insufficient synced and released token amount. Requested: {requested_amount}, available: {allocated_amount}");
+        }
+
+        Ok(ret)
+    }
+
+    /// Return a released UTXO that is locked to the given lock script hash, holds no tokens,
+    /// and is not among the excluded ones. Spending it authorizes the issuance of tokens
+    /// whose issuer has this lock script hash.
+    pub fn find_issuer_input(
+        &self,
+        issuer_lock_script_hash: Digest,
+        tip_digest: Digest,
+        excluded: &[Utxo],
+    ) -> Result<(Utxo, LockScript, MsMembershipProof)> {
+        let wallet_status = self.get_wallet_status_from_lock(tip_digest);
+        let Some(element) = self
+            .released_unspent(wallet_status.synced_unspent)
+            .into_iter()
+            .find(|(wallet_status_element, _membership_proof)| {
+                let utxo = &wallet_status_element.1;
+                utxo.lock_script_hash == issuer_lock_script_hash
+                    && utxo
+                        .coins
+                        .iter()
+                        .all(|coin| coin.type_script_hash == NATIVE_COIN_TYPESCRIPT_DIGEST)
+                    && !excluded.contains(utxo)
+            })
+        else {
+            bail!("Issuing tokens requires a synced and released UTXO of the issuing address, which this wallet does not have");
+        };
+
+        self.with_lock_script(element)
+    }
+
+    /// Return the inputs of a transaction sent from this wallet, with their lock scripts and
+    /// their membership proofs relative to the given tip, such that a transaction spending
+    /// the same inputs can be built. Fails if any input is not an unspent UTXO of this
+    /// wallet with a membership proof for the tip.
+    pub fn inputs_of_own_transaction(
+        &self,
+        transaction: &Transaction,
+        tip_digest: Digest,
+    ) -> Result<Vec<(Utxo, LockScript, MsMembershipProof)>> {
+        let own_inputs = self.scan_for_spent_utxos(transaction);
+        if own_inputs.len() != transaction.kernel.inputs.len() {
+            bail!(
+                "Only {} of the {} inputs of the transaction belong to this wallet",
+                own_inputs.len(),
+                transaction.kernel.inputs.len()
+            );
+        }
+
+        let monitored_utxos = self.wallet_db.monitored_utxos();
+        let mut ret = vec![];
+        for (utxo, _absolute_indices, mutxo_index) in own_inputs {
+            let monitored_utxo = monitored_utxos.get(mutxo_index);
+            if monitored_utxo.spent_in_block.is_some() {
+                bail!("Input with monitored UTXO index {mutxo_index} was already spent");
+            }
+            let Some(membership_proof) = monitored_utxo.get_membership_proof_for_block(tip_digest)
+            else {
+                bail!("Input with monitored UTXO index {mutxo_index} is not synced to the tip");
+            };
+            let lock_script = match self.find_lock_script_for_utxo(&utxo) {
+                Some(lock_script) => lock_script,
+                None => {
+                    bail!("Could not find spending key for monitored UTXO with index {mutxo_index}")
+                }
+            };
+            ret.push((utxo, lock_script, membership_proof));
         }
 
         Ok(ret)
     }
 
     // Allocate sufficient UTXOs to generate a transaction. `amount` must include fees that are
-    // paid in the transaction.
+    // paid in the transaction. UTXOs are selected with the default coin selection strategy.
     pub async fn allocate_sufficient_input_funds(
         &self,
         requested_amount: NeptuneCoins,
         tip_digest: Digest,
     ) -> Result<Vec<(Utxo, LockScript, MsMembershipProof)>> {
-        self.allocate_sufficient_input_funds_from_lock(requested_amount, tip_digest)
-            .await
+        self.allocate_sufficient_input_funds_from_lock(
+            requested_amount,
+            tip_digest,
+            CoinSelectionStrategy::default(),
+        )
+        .await
     }
 }
 
 #[cfg(test)]
 mod tests {
-    use num_traits::One;
+    use num_traits::{One, Zero};
     use tracing_test::traced_test;
 
+    use rand::random;
+
     use crate::{
         config_models::network::Network,
-        tests::shared::{get_mock_global_state, make_mock_block},
+        tests::shared::{
+            get_mock_global_state, get_mock_wallet_state, make_mock_block, make_mock_transaction,
+            unit_test_data_directory,
+        },
     };
 
     use super::*;
 
+    #[tokio::test]
+    async fn next_unused_generation_spending_key_moves_scanning_window() {
+        let wallet_secret = WalletSecret::new_random();
+        let mut wallet_state =
+            get_mock_wallet_state(Some(wallet_secret.clone()), Network::Alpha).await;
+        assert!(wallet_state.generation_key_counter().is_zero());
+        assert_eq!(
+            GENERATION_ADDRESS_GAP_LIMIT as usize + 1,
+            wallet_state.known_generation_keys.len()
+        );
+
+        let first_key = wallet_state.next_unused_generation_spending_key().unwrap();
+        let second_key = wallet_state.next_unused_generation_spending_key().unwrap();
+        assert_eq!(2, wallet_state.generation_key_counter());
+        assert_eq!(
+            wallet_secret.nth_generation_spending_key(1).to_address(),
+            first_key.to_address()
+        );
+        assert_eq!(
+            wallet_secret.nth_generation_spending_key(2).to_address(),
+            second_key.to_address()
+        );
+        assert_eq!(
+            GENERATION_ADDRESS_GAP_LIMIT as usize + 3,
+            wallet_state.known_generation_keys.len()
+        );
+    }
+
+    #[tokio::test]
+    async fn announced_utxos_are_recognized_up_to_gap_limit() {
+        let wallet_secret = WalletSecret::new_random();
+        let mut wallet_state =
+            get_mock_wallet_state(Some(wallet_secret.clone()), Network::Alpha).await;
+
+        let announce_to = |index: u16| {
+            let receiving_address = wallet_secret
+                .nth_generation_spending_key(index)
+                .to_address();
+            let utxo = Utxo::new(
+                receiving_address.lock_script(),
+                NeptuneCoins::new(10).to_native_coins(),
+            );
+            let public_announcement = receiving_address
+                .generate_public_announcement(&utxo, random())
+                .unwrap();
+            let mut transaction = make_mock_transaction(vec![], vec![]);
+            transaction
+                .kernel
+                .public_announcements
+                .push(public_announcement);
+            transaction
+        };
+
+        // Announcements to addresses that were never handed out, but are within the gap
+        // limit, are recognized. Announcements beyond the gap limit are not.
+        let within_gap = announce_to(GENERATION_ADDRESS_GAP_LIMIT);
+        let beyond_gap = announce_to(GENERATION_ADDRESS_GAP_LIMIT + 1);
+        assert_eq!(
+            1,
+            wallet_state
+                .known_generation_keys
+                .iter()
+                .map(|view_key| view_key.scan_for_announced_utxos(&within_gap).len())
+                .sum::<usize>()
+        );
+        assert!(wallet_state
+            .known_generation_keys
+            .iter()
+            .all(|view_key| view_key.scan_for_announced_utxos(&beyond_gap).is_empty()));
+
+        // Once the address at the edge of the window is in use, the window moves along
+        wallet_state.mark_generation_key_as_used(GENERATION_ADDRESS_GAP_LIMIT);
+        assert!(wallet_state
+            .known_generation_keys
+            .iter()
+            .any(|view_key| !view_key.scan_for_announced_utxos(&beyond_gap).is_empty()));
+    }
+
+    #[tokio::test]
+    async fn watch_only_wallet_sees_but_cannot_spend_test() {
+        let network = Network::Alpha;
+        let premine_wallet_secret = WalletSecret::devnet_wallet();
+        // The view keys may come in any order and skip indices
+        let view_keys = (0..=GENERATION_ADDRESS_GAP_LIMIT)
+            .rev()
+            .filter(|index| *index != 3)
+            .map(|index| {
+                premine_wallet_secret
+                    .nth_generation_spending_key(index)
+                    .to_view_key(index)
+            })
+            .collect_vec();
+        let cli_args = Args {
+            number_of_mps_per_utxo: 30,
+            ..Default::default()
+        };
+        let data_dir = unit_test_data_directory(network).unwrap();
+        let mut watch_only_wallet =
+            WalletState::new_watch_only(&data_dir, view_keys, &cli_args).await;
+        let spending_wallet = get_mock_wallet_state(Some(premine_wallet_secret), network).await;
+        assert!(watch_only_wallet.is_watch_only());
+        assert!(!spending_wallet.is_watch_only());
+        assert_eq!(
+            spending_wallet.own_receiving_address(),
+            watch_only_wallet.own_receiving_address()
+        );
+
+        // The watch-only wallet sees the premine, but holds no key to unlock it
+        let genesis_digest = Block::genesis_block().hash();
+        let watched_balance = watch_only_wallet
+            .get_wallet_status_from_lock(genesis_digest)
+            .synced_unspent_amount;
+        assert!(!watched_balance.is_zero());
+        assert_eq!(
+            spending_wallet
+                .get_wallet_status_from_lock(genesis_digest)
+                .synced_unspent_amount,
+            watched_balance
+        );
+        let premine_utxo = watch_only_wallet.wallet_db.monitored_utxos().get(0).utxo;
+        assert!(spending_wallet
+            .find_spending_key_for_utxo(&premine_utxo)
+            .is_some());
+        assert!(watch_only_wallet
+            .find_spending_key_for_utxo(&premine_utxo)
+            .is_none());
+        assert!(watch_only_wallet.spending_secret().is_err());
+        assert!(watch_only_wallet
+            .next_unused_generation_spending_key()
+            .is_err());
+        assert!(watch_only_wallet.generation_key_counter().is_zero());
+
+        // UTXOs are attributed to the derivation index of their key, not to its position
+        assert_eq!(
+            Some(0),
+            watch_only_wallet.generation_key_index_for_utxo(&premine_utxo)
+        );
+        let utxo_of_index = |index: u16| {
+            let receiving_address = WalletSecret::devnet_wallet()
+                .nth_generation_spending_key(index)
+                .to_address();
+            Utxo::new(
+                receiving_address.lock_script(),
+                NeptuneCoins::new(1).to_native_coins(),
+            )
+        };
+        assert_eq!(
+            Some(7),
+            watch_only_wallet.generation_key_index_for_utxo(&utxo_of_index(7))
+        );
+        assert_eq!(
+            None,
+            watch_only_wallet.generation_key_index_for_utxo(&utxo_of_index(3))
+        );
+    }
+
     #[tokio::test]
     #[traced_test]
     async fn wallet_state_prune_abandoned_mutxos() {
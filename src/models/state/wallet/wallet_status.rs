@@ -26,6 +26,9 @@ pub struct WalletStatus {
     pub synced_spent: Vec<WalletStatusElement>,
     pub unsynced_spent_amount: NeptuneCoins,
     pub unsynced_spent: Vec<WalletStatusElement>,
+
+    /// The synced, unspent amount of each registered token, by token name
+    pub synced_unspent_token_amounts: Vec<(String, u64)>,
 }
 
 impl Display for WalletStatus {
@@ -64,10 +67,17 @@ impl Display for WalletStatus {
             self.unsynced_spent_amount,
             self.unsynced_spent.iter().map(|x| x.to_string()).join(",")
         );
+        let synced_unspent_tokens: String = format!(
+            "synced, unspent tokens:\n[{}]",
+            self.synced_unspent_token_amounts
+                .iter()
+                .map(|(name, amount)| format!("{name}: {amount}"))
+                .join(",")
+        );
         write!(
             f,
-            "{}\n\n{}\n\n{}\n\n{}",
-            synced_unspent, unsynced_unspent, synced_spent, unsynced_spent
+            "{}\n\n{}\n\n{}\n\n{}\n\n{}",
+            synced_unspent, unsynced_unspent, synced_spent, unsynced_spent, synced_unspent_tokens
         )
     }
 }
@@ -0,0 +1,121 @@
+use anyhow::{bail, Context, Result};
+use itertools::Itertools;
+use std::fs;
+use std::path::{Path, PathBuf};
+
+use super::address::generation_address::ViewKey;
+use super::{WalletSecret, WALLET_VIEW_KEYS_FILE_NAME};
+
+/// The file in the wallet directory that holds the view keys of a watch-only wallet.
+pub fn view_keys_path(wallet_directory_path: &Path) -> PathBuf {
+    wallet_directory_path.join(WALLET_VIEW_KEYS_FILE_NAME)
+}
+
+/// Check that no two view keys claim the same derivation index.
+fn ensure_distinct_derivation_indices(view_keys: &[ViewKey]) -> Result<()> {
+    if let Some(duplicate_index) = view_keys
+        .iter()
+        .map(|view_key| view_key.derivation_index)
+        .duplicates()
+        .next()
+    {
+        bail!("Got more than one view key with derivation index {duplicate_index}");
+    }
+
+    Ok(())
+}
+
+/// Store the view keys of a watch-only wallet. Each view key carries the derivation index of
+/// its address, so they can be in any order, but the indices must be distinct. Refuses to
+/// overwrite existing view keys. View keys reveal the balance and the history of the
+/// wallet, so the file gets the same restrictive permissions as the wallet secret.
+pub fn save_view_keys(wallet_directory_path: &Path, view_keys: &[ViewKey]) -> Result<()> {
+    ensure_distinct_derivation_indices(view_keys)?;
+    let view_keys_file = view_keys_path(wallet_directory_path);
+    if view_keys_file.exists() {
+        bail!(
+            "View keys file {} already exists. Move it to another location to import other view keys.",
+            view_keys_file.to_string_lossy()
+        );
+    }
+
+    let view_keys_as_json = serde_json::to_string(view_keys)?;
+    WalletSecret::create_wallet_file(&view_keys_file, view_keys_as_json)
+}
+
+/// Read the view keys of a watch-only wallet. Also create the file of incoming randomness
+/// if it does not exist yet, as it is needed to restore the wallet database.
+pub fn read_view_keys(wallet_directory_path: &Path) -> Result<Vec<ViewKey>> {
+    let view_keys_file = view_keys_path(wallet_directory_path);
+    let view_keys_as_json = fs::read_to_string(&view_keys_file).with_context(|| {
+        format!(
+            "Failed to read view keys from {}. Import view keys with `neptune-cli import-view-keys`.",
+            view_keys_file.to_string_lossy(),
+        )
+    })?;
+    let view_keys: Vec<ViewKey> = serde_json::from_str(&view_keys_as_json).with_context(|| {
+        format!(
+            "Failed to decode view keys from {}",
+            view_keys_file.to_string_lossy(),
+        )
+    })?;
+    if view_keys.is_empty() {
+        bail!(
+            "View keys file {} contains no view keys",
+            view_keys_file.to_string_lossy()
+        );
+    }
+    ensure_distinct_derivation_indices(&view_keys)?;
+
+    let incoming_randomness_file =
+        WalletSecret::wallet_incoming_secrets_path(wallet_directory_path);
+    if !incoming_randomness_file.exists() {
+        WalletSecret::create_empty_wallet_randomness_file(&incoming_randomness_file)?;
+    }
+
+    Ok(view_keys)
+}
+
+#[cfg(test)]
+mod watch_only_tests {
+    use crate::config_models::network::Network;
+    use crate::tests::shared::unit_test_data_directory;
+
+    use super::*;
+
+    #[test]
+    fn view_keys_roundtrip_test() {
+        let data_dir = unit_test_data_directory(Network::Alpha).unwrap();
+        let wallet_dir = data_dir.wallet_directory_path();
+        fs::create_dir_all(&wallet_dir).unwrap();
+        assert!(read_view_keys(&wallet_dir).is_err());
+        assert!(save_view_keys(&wallet_dir, &[]).is_ok());
+        assert!(
+            read_view_keys(&wallet_dir).is_err(),
+            "Wallet without view keys must be rejected"
+        );
+        fs::remove_file(view_keys_path(&wallet_dir)).unwrap();
+
+        let wallet_secret = WalletSecret::new_random();
+        let view_keys = (0..3)
+            .map(|index| {
+                wallet_secret
+                    .nth_generation_spending_key(index)
+                    .to_view_key(index)
+            })
+            .collect::<Vec<_>>();
+        save_view_keys(&wallet_dir, &view_keys).unwrap();
+        assert!(
+            save_view_keys(&wallet_dir, &view_keys).is_err(),
+            "Existing view keys must not be overwritten"
+        );
+        assert_eq!(view_keys, read_view_keys(&wallet_dir).unwrap());
+        assert!(WalletSecret::wallet_incoming_secrets_path(&wallet_dir).exists());
+
+        // Two view keys for the same derivation index are rejected
+        fs::remove_file(view_keys_path(&wallet_dir)).unwrap();
+        let mut conflicting_view_keys = view_keys.clone();
+        conflicting_view_keys[2].derivation_index = 0;
+        assert!(save_view_keys(&wallet_dir, &conflicting_view_keys).is_err());
+    }
+}
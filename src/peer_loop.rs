@@ -1,13 +1,16 @@
 use crate::models::consensus::mast_hash::MastHash;
 use crate::prelude::twenty_first;
 
-use crate::connect_to_peers::close_peer_connected_callback;
+use crate::connect_to_peers::{close_peer_connected_callback, OversizedPeerMessage};
 use crate::models::blockchain::block::block_height::BlockHeight;
+use crate::models::blockchain::block::compact_block::{BlockComposition, CompactBlock};
 use crate::models::blockchain::block::transfer_block::TransferBlock;
 use crate::models::blockchain::block::Block;
+use crate::models::blockchain::transaction::Transaction;
 use crate::models::channel::{MainToPeerThread, PeerThreadToMain, PeerThreadToMainTransaction};
 use crate::models::peer::{
-    HandshakeData, MutablePeerState, PeerInfo, PeerMessage, PeerSanctionReason, PeerStanding,
+    HandshakeData, MutablePeerState, PeerInfo, PeerMessage, PeerMessageType, PeerSanctionReason,
+    PeerStanding, PendingCompactBlock, MAX_PENDING_COMPACT_BLOCKS,
 };
 use crate::models::state::mempool::{
     MEMPOOL_IGNORE_TRANSACTIONS_THIS_MANY_SECS_AHEAD, MEMPOOL_TX_THRESHOLD_AGE_IN_SECS,
@@ -17,10 +20,11 @@ use anyhow::{bail, Result};
 use futures::sink::{Sink, SinkExt};
 use futures::stream::{TryStream, TryStreamExt};
 use itertools::Itertools;
+use rand::random;
 use std::cmp;
 use std::marker::Unpin;
 use std::net::SocketAddr;
-use std::time::SystemTime;
+use std::time::{Instant, SystemTime};
 use tokio::select;
 use tokio::sync::{broadcast, mpsc};
 use tracing::{debug, error, info, warn};
@@ -249,6 +253,7 @@ impl PeerLoopHandler {
 
             peer.send(PeerMessage::BlockRequestByHash(parent_digest))
                 .await?;
+            peer_state.outstanding_block_requests += 1;
 
             return Ok(());
         }
@@ -299,11 +304,169 @@ impl PeerLoopHandler {
                 peer_state.highest_shared_block_height,
             ))
             .await?;
+            peer_state.outstanding_block_requests += 1;
         }
 
         Ok(())
     }
 
+    /// Handle a single block that the peer sent, whether in full or as a compact block
+    ///
+    /// Locking:
+    ///   * acquires `global_state_lock` for read
+    ///   * acquires `global_state_lock` for write via Self::punish()
+    async fn handle_received_block<S>(
+        &self,
+        block: Box<Block>,
+        peer: &mut S,
+        peer_state_info: &mut MutablePeerState,
+    ) -> Result<()>
+    where
+        S: Sink<PeerMessage> + TryStream<Ok = PeerMessage> + Unpin,
+        <S as Sink<PeerMessage>>::Error: std::error::Error + Sync + Send + 'static,
+        <S as TryStream>::Error: std::error::Error,
+    {
+        info!(
+            "Got new block from peer {}, height {}, mined {}",
+            self.peer_address,
+            block.kernel.header.height,
+            crate::utc_timestamp_to_localtime(block.kernel.header.timestamp.value()).to_string()
+        );
+        let new_block_height = block.kernel.header.height;
+
+        // Update the value for the highest known height that peer possesses iff
+        // we are not in a fork reconciliation state.
+        if peer_state_info.fork_reconciliation_blocks.is_empty() {
+            peer_state_info.highest_shared_block_height = new_block_height;
+        }
+
+        let incoming_block_is_heavier = self
+            .global_state_lock
+            .lock_guard()
+            .await
+            .chain
+            .light_state()
+            .kernel
+            .header
+            .proof_of_work_family
+            < block.kernel.header.proof_of_work_family;
+        let reconciliation_ongoing = match peer_state_info.fork_reconciliation_blocks.last() {
+            Some(last_block) => last_block.kernel.header.prev_block_digest == block.hash(),
+            None => false,
+        };
+
+        // Determine whether
+        //  a) the incoming block's POW family is larger than what we have; or
+        //  b) we are populating a fork reconciliation blocks list.
+        if incoming_block_is_heavier || reconciliation_ongoing {
+            debug!("block is new");
+            self.receive_new_block(block, peer, peer_state_info).await?;
+        } else {
+            info!(
+                "Got non-canonical block from peer, height: {}, PoW family: {:?}",
+                new_block_height, block.kernel.header.proof_of_work_family,
+            );
+        }
+        Ok(())
+    }
+
+    /// Rebuild a compact block from its transactions and handle it as a received
+    /// block. If the rebuilt block does not have the requested hash, which happens if
+    /// short transaction IDs collide, the full block is requested instead.
+    ///
+    /// Locking:
+    ///   * acquires `global_state_lock` for write
+    async fn receive_compact_block<S>(
+        &self,
+        block_digest: Digest,
+        compact_block: CompactBlock,
+        transactions: Vec<Transaction>,
+        peer: &mut S,
+        peer_state_info: &mut MutablePeerState,
+    ) -> Result<()>
+    where
+        S: Sink<PeerMessage> + TryStream<Ok = PeerMessage> + Unpin,
+        <S as Sink<PeerMessage>>::Error: std::error::Error + Sync + Send + 'static,
+        <S as TryStream>::Error: std::error::Error,
+    {
+        let composition = BlockComposition {
+            coinbase_transaction: compact_block.coinbase_transaction.clone(),
+            transactions: transactions.clone(),
+            short_id_nonce: random(),
+        };
+        let block = match compact_block.reconstruct(transactions) {
+            Ok(block) if block.hash() == block_digest => block,
+            Ok(_) => {
+                warn!(
+                    "Compact block from {} did not rebuild to block {}. Requesting full block.",
+                    self.peer_address, block_digest
+                );
+                peer.send(PeerMessage::BlockRequestByHash(block_digest))
+                    .await?;
+                peer_state_info.outstanding_block_requests += 1;
+                return Ok(());
+            }
+            Err(err) => {
+                warn!(
+                    "Could not rebuild compact block from {}: {err}. Requesting full block.",
+                    self.peer_address
+                );
+                peer.send(PeerMessage::BlockRequestByHash(block_digest))
+                    .await?;
+                peer_state_info.outstanding_block_requests += 1;
+                return Ok(());
+            }
+        };
+        debug!("Rebuilt block {} from compact block", block_digest);
+
+        self.global_state_lock
+            .lock_guard_mut()
+            .await
+            .net
+            .store_block_composition(block_digest, composition);
+        self.handle_received_block(Box::new(block), peer, peer_state_info)
+            .await
+    }
+
+    /// Return true if the peer has not exceeded the rate limit for the type of the
+    /// message. Otherwise sanction the peer, and return false if the message should be
+    /// ignored, or an error if the peer is banned. The size limit of the message type
+    /// is enforced by the codec before the message is decoded.
+    ///
+    /// Locking:
+    ///   * acquires `global_state_lock` for write via Self::punish()
+    async fn check_rate_limit(
+        &self,
+        msg: &PeerMessage,
+        peer_state_info: &mut MutablePeerState,
+    ) -> Result<bool> {
+        // Blocks that answer our own requests are not limited, as fork reconciliation
+        // requests as many as `max_number_of_blocks_before_syncing`, one at a time
+        if matches!(msg, PeerMessage::Block(_) | PeerMessage::CompactBlock(_))
+            && peer_state_info.outstanding_block_requests > 0
+        {
+            peer_state_info.outstanding_block_requests -= 1;
+            return Ok(true);
+        }
+
+        let message_type = PeerMessageType::from(msg);
+        if !peer_state_info
+            .rate_limiter
+            .allow(message_type, Instant::now())
+        {
+            warn!(
+                "Peer {} exceeded the rate limit for {}",
+                self.peer_address,
+                msg.get_type()
+            );
+            self.punish(PeerSanctionReason::RateLimitExceeded(message_type))
+                .await?;
+            return Ok(false);
+        }
+
+        Ok(true)
+    }
+
     /// Handle peer messages and returns Ok(true) if connection should be closed.
     /// Connection should also be closed if an error is returned.
     /// Otherwise returns OK(false).
@@ -379,50 +542,13 @@ impl PeerLoopHandler {
                 Ok(false)
             }
             PeerMessage::Block(t_block) => {
-                info!(
-                    "Got new block from peer {}, height {}, mined {}",
-                    self.peer_address,
-                    t_block.header.height,
-                    crate::utc_timestamp_to_localtime(t_block.header.timestamp.value()).to_string()
-                );
-                let new_block_height = t_block.header.height;
-
                 let block: Box<Block> = Box::new((*t_block).into());
 
-                // Update the value for the highest known height that peer possesses iff
-                // we are not in a fork reconciliation state.
-                if peer_state_info.fork_reconciliation_blocks.is_empty() {
-                    peer_state_info.highest_shared_block_height = new_block_height;
-                }
-
-                let incoming_block_is_heavier = self
-                    .global_state_lock
-                    .lock_guard()
-                    .await
-                    .chain
-                    .light_state()
-                    .kernel
-                    .header
-                    .proof_of_work_family
-                    < block.kernel.header.proof_of_work_family;
-                let reconciliation_ongoing = match peer_state_info.fork_reconciliation_blocks.last()
-                {
-                    Some(last_block) => last_block.kernel.header.prev_block_digest == block.hash(),
-                    None => false,
-                };
-
-                // Determine whether
-                //  a) the incoming block's POW family is larger than what we have; or
-                //  b) we are populating a fork reconciliation blocks list.
-                if incoming_block_is_heavier || reconciliation_ongoing {
-                    debug!("block is new");
-                    self.receive_new_block(block, peer, peer_state_info).await?;
-                } else {
-                    info!(
-                        "Got non-canonical block from peer, height: {}, PoW family: {:?}",
-                        new_block_height, block.kernel.header.proof_of_work_family,
-                    );
-                }
+                // The peer may answer a request for a compact block, or for its missing
+                // transactions, with the full block
+                peer_state_info.pending_compact_blocks.remove(&block.hash());
+                self.handle_received_block(block, peer, peer_state_info)
+                    .await?;
                 Ok(false)
             }
             PeerMessage::BlockRequestBatch(
@@ -502,6 +628,8 @@ impl PeerLoopHandler {
                 let mut returned_blocks: Vec<TransferBlock> =
                     Vec::with_capacity(responded_batch_size);
 
+                // Variant and length of the vector
+                let mut response_size = 4 + 8;
                 let mut current_digest = peers_latest_canonical_block.kernel.mast_hash();
                 while returned_blocks.len() < responded_batch_size {
                     let children = global_state
@@ -531,14 +659,24 @@ impl PeerLoopHandler {
                         canonical
                     };
 
-                    // get block and append to list
+                    // get block and append to list, unless the response would exceed the
+                    // size limit of its type
                     let canonical_child: Block = global_state
                         .chain
                         .archival_state()
                         .get_block(canonical_child_digest)
                         .await?
                         .unwrap();
-                    returned_blocks.push(canonical_child.into());
+                    let canonical_child: TransferBlock = canonical_child.into();
+                    let block_size = bincode::serialized_size(&canonical_child)? as usize;
+                    if returned_blocks.len() >= MINIMUM_BLOCK_BATCH_SIZE
+                        && response_size + block_size
+                            > PeerMessageType::BlockResponseBatch.max_size()
+                    {
+                        break;
+                    }
+                    response_size += block_size;
+                    returned_blocks.push(canonical_child);
 
                     // prepare for next iteration
                     current_digest = canonical_child_digest;
@@ -636,7 +774,7 @@ impl PeerLoopHandler {
                 );
                 peer_state_info.highest_shared_block_height = block_notification.height;
                 {
-                    let block_is_new = self
+                    let tip_header = self
                         .global_state_lock
                         .lock_guard()
                         .await
@@ -644,8 +782,9 @@ impl PeerLoopHandler {
                         .light_state()
                         .kernel
                         .header
-                        .proof_of_work_family
-                        < block_notification.proof_of_work_family;
+                        .clone();
+                    let block_is_new =
+                        tip_header.proof_of_work_family < block_notification.proof_of_work_family;
 
                     // Only request block if it is new, and if we are not currently reconciling
                     // a fork. If we are reconciling, that is handled later, and the information
@@ -660,10 +799,33 @@ impl PeerLoopHandler {
                             )))
                             .await
                             .expect("Sending to main thread must succeed");
+                    } else if peer_state_info
+                        .pending_compact_blocks
+                        .contains_key(&block_notification.hash)
+                    {
+                        debug!(
+                            "Compact block {} was already requested from {}",
+                            block_notification.hash, self.peer_address
+                        );
+                    } else if block_is_new
+                        && peer_state_info.fork_reconciliation_blocks.is_empty()
+                        && block_notification.height == tip_header.height.next()
+                        && peer_state_info.pending_compact_blocks.len() < MAX_PENDING_COMPACT_BLOCKS
+                    {
+                        // A child of the tip is most likely made of transactions that are
+                        // in the mempool, so it is requested as a compact block. Too many
+                        // pending compact blocks are requested in full instead.
+                        peer.send(PeerMessage::CompactBlockRequest(block_notification.hash))
+                            .await?;
+                        peer_state_info.outstanding_block_requests += 1;
+                        peer_state_info
+                            .pending_compact_blocks
+                            .insert(block_notification.hash, PendingCompactBlock::Requested);
                     } else if block_is_new && peer_state_info.fork_reconciliation_blocks.is_empty()
                     {
                         peer.send(PeerMessage::BlockRequestByHeight(block_notification.height))
                             .await?;
+                        peer_state_info.outstanding_block_requests += 1;
                     }
                 }
 
@@ -690,6 +852,170 @@ impl PeerLoopHandler {
                     }
                 }
             }
+            PeerMessage::CompactBlockRequest(block_digest) => {
+                let global_state = self.global_state_lock.lock_guard().await;
+                let Some(block) = global_state
+                    .chain
+                    .archival_state()
+                    .get_block(block_digest)
+                    .await?
+                else {
+                    warn!(
+                        "Peer requested unknown compact block with hash {}",
+                        block_digest
+                    );
+                    return Ok(false);
+                };
+
+                // Blocks whose composition is not known are sent in full
+                let compact_block = global_state
+                    .net
+                    .block_composition(block_digest)
+                    .and_then(|composition| CompactBlock::new(&block, composition));
+                drop(global_state);
+
+                match compact_block {
+                    Some(compact_block) => {
+                        peer.send(PeerMessage::CompactBlock(Box::new(compact_block)))
+                            .await?
+                    }
+                    None => {
+                        peer.send(PeerMessage::Block(Box::new(block.into())))
+                            .await?
+                    }
+                }
+                Ok(false)
+            }
+            PeerMessage::CompactBlock(compact_block) => {
+                // The compact block commits to the hash of the block it describes, which
+                // must be the hash of a requested block
+                let block_digest = compact_block.hash();
+                let Some(PendingCompactBlock::Requested) =
+                    peer_state_info.pending_compact_blocks.remove(&block_digest)
+                else {
+                    warn!(
+                        "Got unrequested compact block {} from {}",
+                        block_digest, self.peer_address
+                    );
+                    self.punish(PeerSanctionReason::InvalidMessage).await?;
+                    return Ok(false);
+                };
+
+                let transactions = compact_block
+                    .find_transactions(&self.global_state_lock.lock_guard().await.mempool);
+                let missing = transactions
+                    .iter()
+                    .positions(Option::is_none)
+                    .map(|position| position as u32)
+                    .collect_vec();
+                if missing.is_empty() {
+                    self.receive_compact_block(
+                        block_digest,
+                        *compact_block,
+                        transactions.into_iter().flatten().collect_vec(),
+                        peer,
+                        peer_state_info,
+                    )
+                    .await?;
+                } else {
+                    debug!(
+                        "Requesting {} of {} transactions of compact block {}",
+                        missing.len(),
+                        transactions.len(),
+                        block_digest
+                    );
+                    peer.send(PeerMessage::CompactBlockTransactionsRequest(
+                        block_digest,
+                        missing,
+                    ))
+                    .await?;
+                    peer_state_info.pending_compact_blocks.insert(
+                        block_digest,
+                        PendingCompactBlock::MissingTransactions(compact_block, transactions),
+                    );
+                }
+                Ok(false)
+            }
+            PeerMessage::CompactBlockTransactionsRequest(block_digest, positions) => {
+                let global_state = self.global_state_lock.lock_guard().await;
+                let Some(composition) = global_state.net.block_composition(block_digest) else {
+                    // The composition may have been forgotten since the compact block was sent
+                    let block = global_state
+                        .chain
+                        .archival_state()
+                        .get_block(block_digest)
+                        .await?;
+                    drop(global_state);
+                    match block {
+                        Some(block) => {
+                            peer.send(PeerMessage::Block(Box::new(block.into())))
+                                .await?
+                        }
+                        None => warn!(
+                            "Peer requested transactions of unknown compact block with hash {}",
+                            block_digest
+                        ),
+                    }
+                    return Ok(false);
+                };
+
+                let transactions = positions
+                    .iter()
+                    .map(|&position| composition.transactions.get(position as usize).cloned())
+                    .collect::<Option<Vec<_>>>();
+                drop(global_state);
+
+                match transactions {
+                    Some(transactions) => {
+                        peer.send(PeerMessage::CompactBlockTransactions(
+                            block_digest,
+                            transactions,
+                        ))
+                        .await?
+                    }
+                    None => self.punish(PeerSanctionReason::InvalidMessage).await?,
+                }
+                Ok(false)
+            }
+            PeerMessage::CompactBlockTransactions(block_digest, received_transactions) => {
+                let Some(PendingCompactBlock::MissingTransactions(compact_block, mut transactions)) =
+                    peer_state_info.pending_compact_blocks.remove(&block_digest)
+                else {
+                    warn!(
+                        "Got unrequested transactions of compact block {} from {}",
+                        block_digest, self.peer_address
+                    );
+                    self.punish(PeerSanctionReason::InvalidMessage).await?;
+                    return Ok(false);
+                };
+
+                let missing_count = transactions.iter().filter(|t| t.is_none()).count();
+                if received_transactions.len() != missing_count {
+                    warn!(
+                        "Got wrong transactions for compact block {} from {}",
+                        block_digest, self.peer_address
+                    );
+                    self.punish(PeerSanctionReason::InvalidMessage).await?;
+                    peer.send(PeerMessage::BlockRequestByHash(block_digest))
+                        .await?;
+                    peer_state_info.outstanding_block_requests += 1;
+                    return Ok(false);
+                }
+
+                let mut received_transactions = received_transactions.into_iter();
+                for transaction in transactions.iter_mut().filter(|t| t.is_none()) {
+                    *transaction = received_transactions.next();
+                }
+                self.receive_compact_block(
+                    block_digest,
+                    *compact_block,
+                    transactions.into_iter().flatten().collect_vec(),
+                    peer,
+                    peer_state_info,
+                )
+                .await?;
+                Ok(false)
+            }
             PeerMessage::BlockRequestByHeight(block_height) => {
                 debug!("Got BlockRequestByHeight of height {}", block_height);
 
@@ -827,6 +1153,25 @@ impl PeerLoopHandler {
                     return Ok(KEEP_CONNECTION_ALIVE);
                 }
 
+                // 4. Ignore if transaction cannot be included in the next block
+                let next_block_height = self
+                    .global_state_lock
+                    .lock_guard()
+                    .await
+                    .chain
+                    .light_state()
+                    .kernel
+                    .header
+                    .height
+                    .next();
+                if transaction.kernel.minimum_block_height.value() > u64::from(next_block_height) {
+                    warn!(
+                        "Received tx that is only valid from block height {}",
+                        transaction.kernel.minimum_block_height
+                    );
+                    return Ok(KEEP_CONNECTION_ALIVE);
+                }
+
                 // Otherwise relay to main
                 let pt2m_transaction = PeerThreadToMainTransaction {
                     transaction: *transaction.to_owned(),
@@ -989,7 +1334,7 @@ impl PeerLoopHandler {
     where
         S: Sink<PeerMessage> + TryStream<Ok = PeerMessage> + Unpin,
         <S as Sink<PeerMessage>>::Error: std::error::Error + Sync + Send + 'static,
-        <S as TryStream>::Error: std::error::Error,
+        <S as TryStream>::Error: std::error::Error + 'static,
     {
         loop {
             select! {
@@ -1003,6 +1348,14 @@ impl PeerLoopHandler {
                                     break;
                                 }
                                 Some(peer_msg) => {
+                                    match self.check_rate_limit(&peer_msg, peer_state_info).await {
+                                        Ok(true) => (),
+                                        Ok(false) => continue,
+                                        Err(err) => {
+                                            warn!("{}. Closing connection.", err);
+                                            bail!("{}", err);
+                                        }
+                                    }
                                     let syncing = self.global_state_lock.lock(|s| s.net.syncing).await;
                                     if peer_msg.ignore_during_sync() && syncing {
                                         debug!("Ignoring {} message during syncing, from {}", peer_msg.get_type(), self.peer_address);
@@ -1028,6 +1381,10 @@ impl PeerLoopHandler {
                             }
                         }
                         Err(err) => {
+                            if let Some(oversized) = OversizedPeerMessage::from_stream_error(&err) {
+                                warn!("Peer {} sent {oversized}", self.peer_address);
+                                self.punish(PeerSanctionReason::OversizedMessage(oversized.message_type)).await?;
+                            }
                             error!("Error when receiving from peer: {}. Error: {err}", self.peer_address);
                             bail!("Error when receiving from peer: {}. Closing connection:", err);
                         }
@@ -1076,7 +1433,7 @@ impl PeerLoopHandler {
     where
         S: Sink<PeerMessage> + TryStream<Ok = PeerMessage> + Unpin,
         <S as Sink<PeerMessage>>::Error: std::error::Error + Sync + Send + 'static,
-        <S as TryStream>::Error: std::error::Error,
+        <S as TryStream>::Error: std::error::Error + 'static,
     {
         let global_state = self.global_state_lock.lock_guard().await;
         // Check if peer standing exists in database, return default if it does not.
@@ -1124,9 +1481,21 @@ impl PeerLoopHandler {
         }
         drop(global_state);
 
-        self.global_state_lock
-            .lock_mut(|s| s.net.peer_map.insert(self.peer_address, new_peer))
-            .await;
+        let listen_address = new_peer.listen_address();
+        let mut global_state_mut = self.global_state_lock.lock_guard_mut().await;
+        global_state_mut
+            .net
+            .peer_map
+            .insert(self.peer_address, new_peer);
+
+        // Remember where the peer can be reached, such that we can reconnect after a restart
+        if let Some(listen_address) = listen_address {
+            global_state_mut
+                .net
+                .record_peer_connection_success(listen_address)
+                .await;
+        }
+        drop(global_state_mut);
 
         // This message is used to determine if we are to enter synchronization mode.
         self.to_main_tx
@@ -1264,6 +1633,113 @@ mod peer_loop_tests {
         Ok(())
     }
 
+    #[traced_test]
+    #[tokio::test]
+    async fn peer_list_requests_beyond_rate_limit_are_ignored_and_sanctioned() -> Result<()> {
+        let (peer_broadcast_tx, _from_main_rx_clone, to_main_tx, _to_main_rx1, state_lock, _hsd) =
+            get_test_genesis_setup(Network::Alpha, 0).await?;
+        let (hsd, sa) = get_dummy_peer_connection_data_genesis(Network::Alpha, 0);
+
+        // Answer requests up to the burst size, then ignore the next one
+        let (burst, _) = PeerMessageType::PeerListRequest.rate_limit().unwrap();
+        let mut actions = vec![];
+        for _ in 0..burst as usize {
+            actions.push(Action::Read(PeerMessage::PeerListRequest));
+            actions.push(Action::Write(PeerMessage::PeerListResponse(vec![(
+                sa,
+                hsd.instance_id,
+            )])));
+        }
+        actions.push(Action::Read(PeerMessage::PeerListRequest));
+        actions.push(Action::Read(PeerMessage::Bye));
+        let mock = Mock::new(actions);
+
+        let peer_loop_handler =
+            PeerLoopHandler::new(to_main_tx, state_lock.clone(), sa, hsd, true, 0);
+        peer_loop_handler
+            .run_wrapper(mock, peer_broadcast_tx.subscribe())
+            .await?;
+
+        let standing = state_lock
+            .lock_guard()
+            .await
+            .net
+            .get_peer_standing_from_database(sa.ip())
+            .await
+            .unwrap();
+        assert_eq!(
+            Some(PeerSanctionReason::RateLimitExceeded(
+                PeerMessageType::PeerListRequest
+            )),
+            standing.latest_sanction
+        );
+
+        Ok(())
+    }
+
+    #[traced_test]
+    #[tokio::test]
+    async fn compact_block_requests_test() -> Result<()> {
+        // The client serves known blocks compactly, and requests new children of its
+        // tip as compact blocks.
+        let network = Network::Alpha;
+        let (peer_broadcast_tx, _from_main_rx_clone, to_main_tx, _to_main_rx1, state_lock, hsd) =
+            get_test_genesis_setup(network, 0).await?;
+        let mut global_state_mut = state_lock.lock_guard_mut().await;
+        let peer_address = get_dummy_socket_address(0);
+        let genesis_block: Block = global_state_mut
+            .chain
+            .archival_state()
+            .get_latest_block()
+            .await;
+
+        let a_wallet_secret = WalletSecret::new_random();
+        let a_recipient_address = a_wallet_secret.nth_generation_spending_key(0).to_address();
+        let (block_1, _, _) =
+            make_mock_block_with_valid_pow(&genesis_block, None, a_recipient_address);
+        let (block_2, _, _) = make_mock_block_with_valid_pow(&block_1, None, a_recipient_address);
+        add_block(&mut global_state_mut, block_1.clone()).await?;
+
+        let transaction = make_mock_transaction(vec![], vec![]);
+        let composition = BlockComposition {
+            coinbase_transaction: block_1.kernel.body.transaction.clone(),
+            transactions: vec![transaction.clone()],
+            short_id_nonce: random(),
+        };
+        global_state_mut
+            .net
+            .store_block_composition(block_1.hash(), composition.clone());
+        drop(global_state_mut);
+
+        let compact_block_1 = CompactBlock::new(&block_1, &composition).unwrap();
+        let mock = Mock::new(vec![
+            Action::Read(PeerMessage::CompactBlockRequest(block_1.hash())),
+            Action::Write(PeerMessage::CompactBlock(Box::new(compact_block_1))),
+            Action::Read(PeerMessage::CompactBlockTransactionsRequest(
+                block_1.hash(),
+                vec![0],
+            )),
+            Action::Write(PeerMessage::CompactBlockTransactions(
+                block_1.hash(),
+                vec![transaction],
+            )),
+            // Blocks of unknown composition are sent in full
+            Action::Read(PeerMessage::CompactBlockRequest(genesis_block.hash())),
+            Action::Write(PeerMessage::Block(Box::new(genesis_block.into()))),
+            Action::Read(PeerMessage::BlockNotification(block_2.clone().into())),
+            Action::Write(PeerMessage::CompactBlockRequest(block_2.hash())),
+            Action::Read(PeerMessage::Bye),
+        ]);
+
+        let peer_loop_handler =
+            PeerLoopHandler::new(to_main_tx, state_lock.clone(), peer_address, hsd, false, 1);
+        peer_loop_handler
+            .run_wrapper(mock, peer_broadcast_tx.subscribe())
+            .await?;
+
+        Ok(())
+    }
+
     #[traced_test]
     #[tokio::test]
     async fn different_genesis_test() -> Result<()> {
@@ -1872,7 +2348,8 @@ mod peer_loop_tests {
             .await;
         let own_recipient_address = global_state_mut
             .wallet_state
-            .wallet_secret
+            .spending_secret()
+            .unwrap()
             .nth_generation_spending_key(0)
             .to_address();
         let (block_1, _, _) =
@@ -1937,6 +2414,94 @@ mod peer_loop_tests {
         Ok(())
     }
 
+    #[traced_test]
+    #[tokio::test]
+    async fn block_reconciliation_deeper_than_block_rate_limit() -> Result<()> {
+        // Fork reconciliation requests the ancestors of a block one at a time. The blocks
+        // that answer these requests must not count against the rate limit for blocks, or
+        // reconciliations deeper than its burst capacity would get the peer sanctioned.
+        let network = Network::Alpha;
+        let (_peer_broadcast_tx, from_main_rx_clone, to_main_tx, mut to_main_rx1, state_lock, hsd) =
+            get_test_genesis_setup(network, 0).await?;
+        let peer_address = get_dummy_socket_address(0);
+        let (burst_capacity, _) = PeerMessageType::Block.rate_limit().unwrap();
+        let depth = burst_capacity as usize + 5;
+        assert!(depth < state_lock.cli().max_number_of_blocks_before_syncing);
+
+        let mut global_state_mut = state_lock.lock_guard_mut().await;
+        let genesis_block: Block = global_state_mut
+            .chain
+            .archival_state()
+            .get_latest_block()
+            .await;
+        let a_recipient_address = WalletSecret::new_random()
+            .nth_generation_spending_key(0)
+            .to_address();
+        let (block_1, _, _) =
+            make_mock_block_with_valid_pow(&genesis_block, None, a_recipient_address);
+        add_block(&mut global_state_mut, block_1.clone()).await?;
+        drop(global_state_mut);
+
+        let mut blocks = vec![block_1];
+        for _ in 0..depth {
+            let (block, _, _) =
+                make_mock_block_with_valid_pow(blocks.last().unwrap(), None, a_recipient_address);
+            blocks.push(block);
+        }
+
+        // The peer sends the newest block, and is asked for its ancestors one by one
+        let mut actions = vec![Action::Read(PeerMessage::Block(Box::new(
+            blocks[depth].clone().into(),
+        )))];
+        for block in blocks[1..depth].iter().rev() {
+            actions.push(Action::Write(PeerMessage::BlockRequestByHash(block.hash())));
+            actions.push(Action::Read(PeerMessage::Block(Box::new(
+                block.clone().into(),
+            ))));
+        }
+        actions.push(Action::Read(PeerMessage::Bye));
+
+        let peer_loop_handler = PeerLoopHandler::new(
+            to_main_tx.clone(),
+            state_lock.clone(),
+            peer_address,
+            hsd,
+            true,
+            1,
+        );
+        peer_loop_handler
+            .run_wrapper(Mock::new(actions), from_main_rx_clone)
+            .await?;
+
+        match to_main_rx1.recv().await {
+            Some(PeerThreadToMain::AddPeerMaxBlockHeight(_)) => (),
+            _ => bail!("Must receive add of peer block max height"),
+        }
+        match to_main_rx1.recv().await {
+            Some(PeerThreadToMain::NewBlocks(received_blocks)) => assert_eq!(
+                blocks[1..].iter().map(Block::hash).collect_vec(),
+                received_blocks.iter().map(Block::hash).collect_vec(),
+                "All reconciled blocks must be sent to main loop, oldest first"
+            ),
+            _ => bail!("Did not find msg sent to main thread"),
+        };
+        match to_main_rx1.recv().await {
+            Some(PeerThreadToMain::RemovePeerMaxBlockHeight(_)) => (),
+            _ => bail!("Must receive remove of peer block max height"),
+        }
+
+        // Verify that the peer was not sanctioned for answering our requests
+        assert!(state_lock
+            .lock_guard()
+            .await
+            .net
+            .get_peer_standing_from_database(peer_address.ip())
+            .await
+            .map_or(true, |peer_standing| !peer_standing.standing.is_negative()));
+
+        Ok(())
+    }
+
     #[traced_test]
     #[tokio::test]
     async fn test_peer_loop_receival_of_fourth_block_one_block_in_db() -> Result<()> {
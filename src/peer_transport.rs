@@ -0,0 +1,436 @@
+//! The encrypted and authenticated transport for peer connections.
+//!
+//! An outgoing connection is encrypted if it starts with [`ENCRYPTED_TRANSPORT_MARKER`].
+//! A plaintext connection cannot start with this byte, as it would be the first byte of
+//! a frame length above the max frame length. The marker is followed by a version byte
+//! and a Noise XX handshake, in which both nodes prove that they hold the private part
+//! of their static key. Each handshake message is prefixed by its length as a big-endian
+//! `u16`.
+//!
+//! After the Noise handshake, every frame that would otherwise be sent in plaintext is
+//! split into chunks, each of which is encrypted and prefixed by its length as a
+//! big-endian `u16`, and the concatenation of the encrypted chunks is sent as one
+//! length-delimited frame. The Neptune handshake then happens inside the encrypted
+//! channel, and the instance ID of each node must be the one bound to its static key.
+//!
+//! The static key of a peer is trusted on first use: the first key that a peer
+//! listening on some address presents is recorded in the address book, and later
+//! outgoing connections to that address are refused if the peer presents another key.
+
+use anyhow::{bail, ensure, Context, Result};
+use bytes::{Buf, BufMut, Bytes, BytesMut};
+use snow::params::DHChoice;
+use snow::resolvers::{CryptoResolver, DefaultResolver};
+use snow::{Builder, HandshakeState, TransportState};
+use std::fmt;
+use std::fs;
+use std::io;
+use std::path::Path;
+use std::time::Duration;
+use tokio::io::{AsyncRead, AsyncReadExt, AsyncWrite, AsyncWriteExt};
+use tokio_util::codec::{Decoder, Encoder, LengthDelimitedCodec};
+
+use crate::models::peer::InstanceId;
+use crate::util_types::secret_file::create_secret_file;
+
+/// The first byte of an encrypted connection
+pub const ENCRYPTED_TRANSPORT_MARKER: u8 = 0xff;
+
+/// The version of the encrypted transport
+pub const ENCRYPTED_TRANSPORT_VERSION: u8 = 0;
+
+pub const PEER_TRANSPORT_KEY_FILE_NAME: &str = "peer_transport_key.dat";
+
+const NOISE_PARAMS: &str = "Noise_XX_25519_ChaChaPoly_BLAKE2s";
+const NOISE_MAX_MESSAGE_LENGTH: usize = u16::MAX as usize;
+const NOISE_TAG_LENGTH: usize = 16;
+const NOISE_KEY_LENGTH: usize = 32;
+const MAX_CHUNK_LENGTH: usize = NOISE_MAX_MESSAGE_LENGTH - NOISE_TAG_LENGTH;
+
+/// The time a peer has to complete the Noise handshake
+const ENCRYPTED_HANDSHAKE_TIMEOUT: Duration = Duration::from_secs(10);
+
+/// The static public key of a node
+pub type PeerStaticKey = [u8; NOISE_KEY_LENGTH];
+
+/// The static key of a node, with which it authenticates itself in encrypted
+/// connections. The instance ID of the node is bound to the public part.
+#[derive(Clone)]
+pub struct PeerTransportKey {
+    private: Vec<u8>,
+    public: Vec<u8>,
+}
+
+impl fmt::Debug for PeerTransportKey {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("PeerTransportKey")
+            .field("public", &self.public)
+            .finish()
+    }
+}
+
+impl PeerTransportKey {
+    pub fn generate() -> Self {
+        let keypair = Builder::new(NOISE_PARAMS.parse().unwrap())
+            .generate_keypair()
+            .unwrap();
+
+        Self {
+            private: keypair.private,
+            public: keypair.public,
+        }
+    }
+
+    /// Read the key from a file, or create the file with a new key if it does not exist.
+    /// The file holds the private key followed by the public key, which must be the one
+    /// that belongs to the private key.
+    pub fn read_from_file_or_create(path: &Path) -> Result<Self> {
+        if path.exists() {
+            let bytes =
+                fs::read(path).with_context(|| format!("Failed to read {}", path.display()))?;
+            ensure!(
+                bytes.len() == 2 * NOISE_KEY_LENGTH,
+                "Peer transport key file {} has the wrong length",
+                path.display()
+            );
+            let (private, public) = bytes.split_at(NOISE_KEY_LENGTH);
+            ensure!(
+                public_key_from_private_key(private)? == public,
+                "Public key in peer transport key file {} does not belong to its private key",
+                path.display()
+            );
+            return Ok(Self {
+                private: private.to_vec(),
+                public: public.to_vec(),
+            });
+        }
+
+        let key = Self::generate();
+        let bytes = [key.private.clone(), key.public.clone()].concat();
+        create_secret_file(path, &bytes)
+            .with_context(|| format!("Failed to write {}", path.display()))?;
+        Ok(key)
+    }
+
+    pub fn public_key(&self) -> &[u8] {
+        &self.public
+    }
+
+    /// The instance ID that the node with this key must use in encrypted connections
+    pub fn instance_id(&self) -> InstanceId {
+        instance_id_from_public_key(&self.public)
+    }
+}
+
+fn public_key_from_private_key(private_key: &[u8]) -> Result<Vec<u8>> {
+    let mut dh = DefaultResolver
+        .resolve_dh(&DHChoice::Curve25519)
+        .context("Curve25519 must be available")?;
+    dh.set(private_key);
+    Ok(dh.pubkey().to_vec())
+}
+
+/// The instance ID bound to a static public key: its first 16 bytes.
+pub fn instance_id_from_public_key(public_key: &[u8]) -> InstanceId {
+    let mut bytes = [0u8; 16];
+    bytes.copy_from_slice(&public_key[..16]);
+    InstanceId::from_le_bytes(bytes)
+}
+
+async fn write_handshake_message<S>(
+    stream: &mut S,
+    handshake_state: &mut HandshakeState,
+) -> Result<()>
+where
+    S: AsyncWrite + Unpin,
+{
+    let mut message = vec![0u8; NOISE_MAX_MESSAGE_LENGTH];
+    let length = handshake_state.write_message(&[], &mut message)?;
+    stream.write_u16(length as u16).await?;
+    stream.write_all(&message[..length]).await?;
+    stream.flush().await?;
+    Ok(())
+}
+
+async fn read_handshake_message<S>(
+    stream: &mut S,
+    handshake_state: &mut HandshakeState,
+) -> Result<()>
+where
+    S: AsyncRead + Unpin,
+{
+    let length = stream.read_u16().await? as usize;
+    let mut message = vec![0u8; length];
+    stream.read_exact(&mut message).await?;
+    let mut payload = vec![0u8; NOISE_MAX_MESSAGE_LENGTH];
+    handshake_state.read_message(&message, &mut payload)?;
+    Ok(())
+}
+
+/// Perform the Noise handshake as the node that made the connection. Returns the
+/// transport state and the static key of the other node. Fails if the other node does
+/// not complete the handshake in time.
+pub async fn initiate_encrypted_transport<S>(
+    stream: &mut S,
+    own_key: &PeerTransportKey,
+) -> Result<(TransportState, PeerStaticKey)>
+where
+    S: AsyncRead + AsyncWrite + Unpin,
+{
+    tokio::time::timeout(ENCRYPTED_HANDSHAKE_TIMEOUT, async {
+        stream
+            .write_all(&[ENCRYPTED_TRANSPORT_MARKER, ENCRYPTED_TRANSPORT_VERSION])
+            .await?;
+
+        let mut handshake_state = Builder::new(NOISE_PARAMS.parse()?)
+            .local_private_key(&own_key.private)
+            .build_initiator()?;
+        write_handshake_message(stream, &mut handshake_state).await?;
+        read_handshake_message(stream, &mut handshake_state).await?;
+        write_handshake_message(stream, &mut handshake_state).await?;
+
+        into_transport(handshake_state)
+    })
+    .await
+    .context("Encrypted transport handshake timed out")?
+}
+
+/// Perform the Noise handshake as the node that accepted the connection, after the
+/// marker byte has been read. Returns the transport state and the static key of the
+/// other node. Fails if the other node does not complete the handshake in time.
+pub async fn respond_encrypted_transport<S>(
+    stream: &mut S,
+    own_key: &PeerTransportKey,
+) -> Result<(TransportState, PeerStaticKey)>
+where
+    S: AsyncRead + AsyncWrite + Unpin,
+{
+    tokio::time::timeout(ENCRYPTED_HANDSHAKE_TIMEOUT, async {
+        let version = stream.read_u8().await?;
+        if version != ENCRYPTED_TRANSPORT_VERSION {
+            bail!("Unsupported encrypted transport version {version}");
+        }
+
+        let mut handshake_state = Builder::new(NOISE_PARAMS.parse()?)
+            .local_private_key(&own_key.private)
+            .build_responder()?;
+        read_handshake_message(stream, &mut handshake_state).await?;
+        write_handshake_message(stream, &mut handshake_state).await?;
+        read_handshake_message(stream, &mut handshake_state).await?;
+
+        into_transport(handshake_state)
+    })
+    .await
+    .context("Encrypted transport handshake timed out")?
+}
+
+fn into_transport(handshake_state: HandshakeState) -> Result<(TransportState, PeerStaticKey)> {
+    let remote_static: PeerStaticKey = handshake_state
+        .get_remote_static()
+        .context("Peer did not send its static key")?
+        .try_into()
+        .context("Peer sent a static key of the wrong length")?;
+    let transport_state = handshake_state.into_transport_mode()?;
+    Ok((transport_state, remote_static))
+}
+
+/// The codec of peer connections: length-delimited frames, which are encrypted if the
+/// connection is.
+pub enum PeerCodec {
+    Plain(LengthDelimitedCodec),
+    Encrypted {
+        frames: LengthDelimitedCodec,
+        transport_state: Box<TransportState>,
+    },
+}
+
+impl fmt::Debug for PeerCodec {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            PeerCodec::Plain(_) => write!(f, "PeerCodec::Plain"),
+            PeerCodec::Encrypted { .. } => write!(f, "PeerCodec::Encrypted"),
+        }
+    }
+}
+
+impl PeerCodec {
+    /// A codec for plaintext frames of at most `max_frame_length` bytes
+    pub fn plain(max_frame_length: usize) -> Self {
+        let mut frames = LengthDelimitedCodec::new();
+        frames.set_max_frame_length(max_frame_length);
+        PeerCodec::Plain(frames)
+    }
+
+    /// A codec for encrypted frames that hold at most `max_frame_length` bytes of
+    /// plaintext
+    pub fn encrypted(max_frame_length: usize, transport_state: TransportState) -> Self {
+        let chunk_count = max_frame_length.div_ceil(MAX_CHUNK_LENGTH);
+        let overhead = chunk_count * (2 + NOISE_TAG_LENGTH);
+        let mut frames = LengthDelimitedCodec::new();
+        frames.set_max_frame_length(max_frame_length + overhead);
+        PeerCodec::Encrypted {
+            frames,
+            transport_state: Box::new(transport_state),
+        }
+    }
+}
+
+fn invalid_data(err: snow::Error) -> io::Error {
+    io::Error::new(io::ErrorKind::InvalidData, err)
+}
+
+impl Decoder for PeerCodec {
+    type Item = BytesMut;
+    type Error = io::Error;
+
+    fn decode(&mut self, src: &mut BytesMut) -> Result<Option<BytesMut>, io::Error> {
+        match self {
+            PeerCodec::Plain(frames) => frames.decode(src),
+            PeerCodec::Encrypted {
+                frames,
+                transport_state,
+            } => {
+                let Some(mut encrypted_frame) = frames.decode(src)? else {
+                    return Ok(None);
+                };
+
+                let mut frame = BytesMut::with_capacity(encrypted_frame.len());
+                let mut chunk = vec![0u8; MAX_CHUNK_LENGTH];
+                while encrypted_frame.has_remaining() {
+                    if encrypted_frame.remaining() < 2 {
+                        return Err(io::Error::new(
+                            io::ErrorKind::InvalidData,
+                            "Truncated encrypted chunk",
+                        ));
+                    }
+                    let length = encrypted_frame.get_u16() as usize;
+                    if encrypted_frame.remaining() < length {
+                        return Err(io::Error::new(
+                            io::ErrorKind::InvalidData,
+                            "Truncated encrypted chunk",
+                        ));
+                    }
+                    let chunk_length = transport_state
+                        .read_message(&encrypted_frame[..length], &mut chunk)
+                        .map_err(invalid_data)?;
+                    frame.extend_from_slice(&chunk[..chunk_length]);
+                    encrypted_frame.advance(length);
+                }
+
+                Ok(Some(frame))
+            }
+        }
+    }
+}
+
+impl Encoder<Bytes> for PeerCodec {
+    type Error = io::Error;
+
+    fn encode(&mut self, item: Bytes, dst: &mut BytesMut) -> Result<(), io::Error> {
+        match self {
+            PeerCodec::Plain(frames) => frames.encode(item, dst),
+            PeerCodec::Encrypted {
+                frames,
+                transport_state,
+            } => {
+                let mut encrypted_frame = BytesMut::new();
+                let mut message = vec![0u8; NOISE_MAX_MESSAGE_LENGTH];
+                for chunk in item.chunks(MAX_CHUNK_LENGTH) {
+                    let length = transport_state
+                        .write_message(chunk, &mut message)
+                        .map_err(invalid_data)?;
+                    encrypted_frame.put_u16(length as u16);
+                    encrypted_frame.extend_from_slice(&message[..length]);
+                }
+
+                frames.encode(encrypted_frame.freeze(), dst)
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod peer_transport_tests {
+    use super::*;
+    use crate::config_models::network::Network;
+    use crate::tests::shared::unit_test_data_directory;
+    use tokio::io::duplex;
+
+    #[tokio::test]
+    async fn encrypted_transport_authenticates_and_round_trips_frames() -> Result<()> {
+        let initiator_key = PeerTransportKey::generate();
+        let responder_key = PeerTransportKey::generate();
+        let (mut initiator_stream, mut responder_stream) = duplex(NOISE_MAX_MESSAGE_LENGTH);
+
+        let responder_key_clone = responder_key.clone();
+        let responder = tokio::spawn(async move {
+            assert_eq!(
+                ENCRYPTED_TRANSPORT_MARKER,
+                responder_stream.read_u8().await.unwrap()
+            );
+            respond_encrypted_transport(&mut responder_stream, &responder_key_clone)
+                .await
+                .unwrap()
+        });
+        let (initiator_transport, responder_static_key) =
+            initiate_encrypted_transport(&mut initiator_stream, &initiator_key).await?;
+        let (responder_transport, initiator_static_key) = responder.await?;
+
+        assert_eq!(initiator_key.public_key(), initiator_static_key);
+        assert_eq!(responder_key.public_key(), responder_static_key);
+
+        // Frames larger than a Noise message are split into several chunks
+        let max_frame_length = 3 * MAX_CHUNK_LENGTH;
+        let mut initiator_codec = PeerCodec::encrypted(max_frame_length, initiator_transport);
+        let mut responder_codec = PeerCodec::encrypted(max_frame_length, responder_transport);
+        for frame_length in [
+            0,
+            1,
+            MAX_CHUNK_LENGTH,
+            MAX_CHUNK_LENGTH + 1,
+            max_frame_length,
+        ] {
+            let frame = Bytes::from(vec![7u8; frame_length]);
+            let mut wire = BytesMut::new();
+            initiator_codec.encode(frame.clone(), &mut wire)?;
+            let decoded = responder_codec.decode(&mut wire)?.unwrap();
+            assert_eq!(frame, decoded.freeze());
+        }
+
+        // Frames are not sent in plaintext, and tampered frames are rejected
+        let mut wire = BytesMut::new();
+        initiator_codec.encode(Bytes::from_static(b"neptune"), &mut wire)?;
+        assert!(!wire.windows(7).any(|window| window == b"neptune"));
+        let last = wire.len() - 1;
+        wire[last] ^= 1;
+        assert!(responder_codec.decode(&mut wire).is_err());
+
+        Ok(())
+    }
+
+    #[test]
+    fn key_file_must_hold_matching_public_key() {
+        let data_dir = unit_test_data_directory(Network::Alpha).unwrap();
+        fs::create_dir_all(data_dir.root_dir_path()).unwrap();
+        let path = data_dir.root_dir_path().join(PEER_TRANSPORT_KEY_FILE_NAME);
+
+        let key = PeerTransportKey::read_from_file_or_create(&path).unwrap();
+        assert_eq!(
+            key.public_key(),
+            public_key_from_private_key(&key.private).unwrap()
+        );
+        let read_key = PeerTransportKey::read_from_file_or_create(&path).unwrap();
+        assert_eq!(key.public_key(), read_key.public_key());
+
+        let other_key = PeerTransportKey::generate();
+        fs::remove_file(&path).unwrap();
+        create_secret_file(&path, &[key.private.clone(), other_key.public].concat()).unwrap();
+        assert!(PeerTransportKey::read_from_file_or_create(&path).is_err());
+    }
+
+    #[test]
+    fn plaintext_frames_cannot_start_with_marker() {
+        let max_frame_length = crate::connect_to_peers::MAX_PEER_FRAME_LENGTH_IN_BYTES as u32;
+        assert!(max_frame_length.to_be_bytes()[0] < ENCRYPTED_TRANSPORT_MARKER);
+    }
+}
@@ -11,6 +11,7 @@ use std::time::Duration;
 use tarpc::context;
 use tokio::sync::mpsc::error::SendError;
 use tracing::{error, info};
+use twenty_first::shared_math::b_field_element::BFieldElement;
 use twenty_first::shared_math::digest::Digest;
 use twenty_first::util_types::algebraic_hasher::AlgebraicHasher;
 
@@ -19,14 +20,26 @@ use crate::models::blockchain::block::block_header::BlockHeader;
 use crate::models::blockchain::block::block_height::BlockHeight;
 use crate::models::blockchain::shared::Hash;
 use crate::models::blockchain::transaction::neptune_coins::NeptuneCoins;
+use crate::models::blockchain::transaction::timelock::Timelock;
+use crate::models::blockchain::transaction::token::TokenDefinition;
+use crate::models::blockchain::transaction::transaction_details::TransactionDetails;
 use crate::models::blockchain::transaction::utxo::Utxo;
+use crate::models::blockchain::transaction::{PublicAnnouncement, Transaction};
 use crate::models::channel::RPCServerToMain;
 use crate::models::peer::InstanceId;
 use crate::models::peer::PeerInfo;
 use crate::models::peer::PeerStanding;
 use crate::models::state::wallet::address::generation_address;
+use crate::models::state::wallet::address::multisig_address::MultisigAddress;
+use crate::models::state::wallet::coin_selection::CoinSelectionStrategy;
+use crate::models::state::wallet::multisig::{MultisigSetup, PartiallySignedTransaction};
+use crate::models::state::wallet::outgoing_transaction::{OffChainOutput, OutgoingTransaction};
+use crate::models::state::wallet::unconfirmed_send::UnconfirmedSend;
+use crate::models::state::wallet::unsigned_transaction::{SignedTransaction, UnsignedTransaction};
+use crate::models::state::wallet::utxo_transfer::{UtxoClaimStatus, UtxoTransferNotification};
+use crate::models::state::wallet::wallet_state::KnownToken;
 use crate::models::state::wallet::wallet_status::WalletStatus;
-use crate::models::state::{GlobalStateLock, UtxoReceiverData};
+use crate::models::state::{GlobalStateLock, UtxoNotificationMedium, UtxoReceiverData};
 
 #[derive(Clone, Debug, Serialize, Deserialize, PartialEq, Eq)]
 pub struct DashBoardOverviewDataFromClient {
@@ -98,7 +111,8 @@ pub trait RPC {
     /// Return information about funds in the wallet
     async fn wallet_status() -> WalletStatus;
 
-    /// Return an address that this client can receive funds on
+    /// Return the default address that this client can receive funds on, i.e. the
+    /// generation address with derivation index 0
     async fn own_receiving_address() -> generation_address::ReceivingAddress;
 
     /// Return the number of transactions in the mempool
@@ -107,6 +121,70 @@ pub trait RPC {
     // TODO: Change to return current size and max size
     async fn mempool_size() -> usize;
 
+    /// Return the transaction with the given ID from the mempool or from a block of the
+    /// canonical chain, in readable form
+    async fn get_transaction(txid: Digest) -> Option<TransactionDetails>;
+
+    /// Decode a transaction from the bytes that peers send, in readable form. Returns
+    /// `None` if the bytes are not a transaction.
+    async fn decode_transaction(bytes: Vec<u8>) -> Option<TransactionDetails>;
+
+    /// Estimate the fee that a transaction of the given size must pay to be mined within
+    /// `target_blocks` blocks, based on the mempool and the fill of recent blocks. If no size
+    /// is given, the average size of the transactions in the mempool is used. Returns `None`
+    /// if no estimate could be made.
+    async fn estimate_fee(
+        target_blocks: usize,
+        transaction_size: Option<usize>,
+    ) -> Option<NeptuneCoins>;
+
+    /// Estimate the fee for a transaction that pays the given outputs from this wallet to be
+    /// mined within `target_blocks` blocks, based on the size of that transaction. Each
+    /// output is given as for sending, with an optional time lock. Returns `None` if no
+    /// estimate could be made, e.g. because the balance does not cover the outputs.
+    async fn estimate_send_fee(
+        outputs: Vec<(
+            generation_address::ReceivingAddress,
+            NeptuneCoins,
+            UtxoNotificationMedium,
+            Option<Timelock>,
+        )>,
+        coin_selection: CoinSelectionStrategy,
+        target_blocks: usize,
+    ) -> Option<NeptuneCoins>;
+
+    /// Return the transactions sent from this wallet that are not confirmed yet, as the
+    /// transaction ID, the amount sent to the receivers, and the fee
+    async fn unconfirmed_sends() -> Vec<(Digest, NeptuneCoins, NeptuneCoins)>;
+
+    /// Return the transactions sent from this wallet, oldest first
+    async fn outgoing_transactions() -> Vec<OutgoingTransaction>;
+
+    /// Return notifications for the outputs of a transaction sent from this wallet whose
+    /// receivers must be notified off-chain. Returns `None` if they cannot be exported.
+    async fn utxo_transfer_notifications(txid: Digest) -> Option<Vec<UtxoTransferNotification>>;
+
+    /// Return this wallet's public key for multisig addresses with the given derivation
+    /// index. Returns `None` for watch-only wallets.
+    async fn multisig_public_key(index: u16) -> Option<Digest>;
+
+    /// Return the multisig addresses that this wallet participates in, with the amount of
+    /// their unspent UTXOs
+    async fn multisig_addresses() -> Vec<(MultisigAddress, NeptuneCoins)>;
+
+    /// Return the tokens registered with this wallet, with their synced, unspent amounts.
+    async fn tokens() -> Vec<(KnownToken, u64)>;
+
+    /// Create a transaction that spends all UTXOs of a multisig address, for its
+    /// participants to sign. The difference between the inputs and the outputs and fee
+    /// goes to the change address. Returns `None` if the transaction cannot be created.
+    async fn create_multisig_transaction(
+        address: MultisigAddress,
+        outputs: Vec<(generation_address::ReceivingAddress, NeptuneCoins)>,
+        change_address: Option<MultisigAddress>,
+        fee: NeptuneCoins,
+    ) -> Option<PartiallySignedTransaction>;
+
     /// Return the information used on the dashboard's overview tab
     async fn dashboard_overview_data() -> DashBoardOverviewDataFromClient;
 
@@ -131,13 +209,44 @@ pub trait RPC {
     /// Clears standing for ip, whether connected or not
     async fn clear_standing_by_ip(ip: IpAddr);
 
-    /// Send coins
+    /// Return a generation address that was not handed out before. Returns `None` if all
+    /// derivation indices are in use.
+    async fn next_receiving_address() -> Option<generation_address::ReceivingAddress>;
+
+    /// Send coins. If a time lock is given, the receiver can only spend the coins in
+    /// transactions with a timestamp at or after its release time, or that can only be
+    /// confirmed from its release height on.
     async fn send(
         amount: NeptuneCoins,
         address: generation_address::ReceivingAddress,
         fee: NeptuneCoins,
+        timelock: Option<Timelock>,
     ) -> Option<Digest>;
 
+    /// Send coins to many receivers in one transaction. Each output is given by the
+    /// receiving address, the amount, and how the receiver is notified. The inputs are
+    /// selected with the given coin selection strategy. Returns the transaction ID and the
+    /// notifications of the outputs whose receivers must be notified off-chain, in the
+    /// order of these outputs.
+    async fn send_to_many(
+        outputs: Vec<(
+            generation_address::ReceivingAddress,
+            NeptuneCoins,
+            UtxoNotificationMedium,
+        )>,
+        coin_selection: CoinSelectionStrategy,
+        fee: NeptuneCoins,
+    ) -> Option<(Digest, Vec<PublicAnnouncement>)>;
+
+    /// Replace an unconfirmed transaction sent from this wallet with one that spends the
+    /// same inputs and pays the same receivers, but pays the given, higher fee. The fee is
+    /// taken from the change. Returns the ID of the replacement transaction.
+    async fn bump_fee(txid: Digest, new_fee: NeptuneCoins) -> Option<Digest>;
+
+    /// Set the label of a transaction sent from this wallet. Returns false if this wallet
+    /// did not send a transaction with the given ID.
+    async fn label_outgoing_transaction(txid: Digest, label: String) -> bool;
+
     /// Stop miner if running
     async fn pause_miner();
 
@@ -147,6 +256,74 @@ pub trait RPC {
     /// mark MUTXOs as abandoned
     async fn prune_abandoned_monitored_utxos() -> usize;
 
+    /// Claim a UTXO that was sent to this wallet with an off-chain notification. Returns
+    /// `None` if the notification is invalid.
+    async fn claim_utxo(notification: UtxoTransferNotification) -> Option<UtxoClaimStatus>;
+
+    /// Start tracking a multisig address that this wallet participates in. Returns the
+    /// number of unspent UTXOs of the address, or `None` if the setup cannot be imported.
+    async fn import_multisig_setup(setup: MultisigSetup) -> Option<usize>;
+
+    /// Add this wallet's signature to a multisig transaction. Returns `None` if this wallet
+    /// cannot sign it, or if its key already signed another transaction.
+    async fn sign_multisig_transaction(
+        transaction: PartiallySignedTransaction,
+    ) -> Option<PartiallySignedTransaction>;
+
+    /// Broadcast a multisig transaction that has enough signatures. Returns the
+    /// transaction ID, or `None` if the transaction is invalid.
+    async fn broadcast_multisig_transaction(
+        transaction: PartiallySignedTransaction,
+    ) -> Option<Digest>;
+
+    /// Create a transaction that pays the given outputs from this wallet, for signing by a
+    /// wallet secret that is kept offline. Works on watch-only nodes. Returns `None` if the
+    /// transaction cannot be created.
+    async fn create_unsigned_transaction(
+        outputs: Vec<(generation_address::ReceivingAddress, NeptuneCoins)>,
+        fee: NeptuneCoins,
+    ) -> Option<UnsignedTransaction>;
+
+    /// Broadcast a transaction that was signed offline. Returns the transaction ID, or
+    /// `None` if the transaction is invalid.
+    async fn broadcast_signed_transaction(transaction: SignedTransaction) -> Option<Digest>;
+
+    /// Define a new token that this wallet issues, and register it under the given name.
+    /// Returns `None` if the name is taken.
+    async fn create_token(name: String) -> Option<TokenDefinition>;
+
+    /// Register a token that was defined elsewhere under the given name, such that its
+    /// UTXOs can be counted and spent. Returns false if the name or the token is already
+    /// registered.
+    async fn register_token(name: String, definition: TokenDefinition) -> bool;
+
+    /// Create new tokens of a token that this wallet issues, and send them to the given
+    /// address. Returns the transaction ID.
+    async fn issue_token(
+        name: String,
+        amount: u64,
+        address: generation_address::ReceivingAddress,
+        fee: NeptuneCoins,
+    ) -> Option<Digest>;
+
+    /// Send tokens of a registered token from this wallet's token UTXOs. The fee is paid
+    /// in native coins. Returns the transaction ID.
+    async fn send_token(
+        name: String,
+        amount: u64,
+        address: generation_address::ReceivingAddress,
+        fee: NeptuneCoins,
+    ) -> Option<Digest>;
+
+    /// Rebuild the wallet's monitored UTXOs from the blocks from the given height up to
+    /// the tip. Returns the number of UTXOs that were added, or `None` if the rescan failed.
+    async fn rescan(from_height: BlockHeight) -> Option<usize>;
+
+    /// Restore monitored UTXOs that are recorded in the wallet's file of incoming randomness
+    /// but missing from the wallet database. Returns the number of restored UTXOs, or `None`
+    /// if the restoration failed.
+    async fn restore_monitored_utxos() -> Option<usize>;
+
     /// Gracious shutdown.
     async fn shutdown() -> bool;
 }
@@ -178,6 +355,288 @@ impl NeptuneRPCServer {
             None => None,
         }
     }
+
+    /// Build, broadcast, and record a transaction with the given outputs. Each output is
+    /// given by the receiving address, the amount, how the receiver is notified, and the
+    /// time until which the output is locked, if any, in milliseconds since the UNIX epoch.
+    ///
+    /// Locking:
+    ///   * acquires `global_state_lock` for write
+    async fn send_outputs_internal(
+        &self,
+        outputs: Vec<(
+            generation_address::ReceivingAddress,
+            NeptuneCoins,
+            UtxoNotificationMedium,
+            Option<Timelock>,
+        )>,
+        coin_selection: CoinSelectionStrategy,
+        fee: NeptuneCoins,
+    ) -> Option<(Digest, Vec<PublicAnnouncement>)> {
+        let span = tracing::debug_span!("Constructing transaction objects");
+        let _enter = span.enter();
+
+        if outputs.is_empty() {
+            error!("Cannot send to an empty list of outputs.");
+            return None;
+        }
+        if let Some((_, amount, _, _)) = outputs
+            .iter()
+            .find(|(_, amount, _, _)| amount.is_negative())
+        {
+            error!("Cannot send negative amount {amount}.");
+            return None;
+        }
+
+        // 1. Build transaction object
+        // TODO: Allow user to set fee here. Don't set it automatically as we want the user
+        // to be in control of this. But we could add an endpoint to get recommended fee
+        // density.
+        let state = self.state.lock_guard().await;
+        let wallet_secret = match state.wallet_state.spending_secret() {
+            Ok(wallet_secret) => wallet_secret,
+            Err(err) => {
+                error!("Cannot send: {err}");
+                return None;
+            }
+        };
+        let block_height = state.chain.light_state().header().height;
+        let mut receiver_data = vec![];
+        let mut notification_media = vec![];
+        let mut off_chain_notifications = vec![];
+        let mut off_chain_outputs = vec![];
+        let recipients = outputs
+            .iter()
+            .map(|(address, amount, _notification_medium, _timelock)| (*address, *amount))
+            .collect::<Vec<_>>();
+        for (output_index, (address, amount, notification_medium, timelock)) in
+            outputs.into_iter().enumerate()
+        {
+            let coins = amount.to_native_coins();
+            let lock_script = match timelock {
+                Some(timelock) => address.timelocked_lock_script(timelock),
+                None => address.lock_script(),
+            };
+            let utxo = Utxo::new(lock_script, coins);
+            let receiver_privacy_digest = address.privacy_digest;
+
+            // Mix the output index into the sender randomness, such that outputs with the same
+            // receiver and amount get distinct commitments.
+            let sender_randomness = Hash::hash_pair(
+                wallet_secret.generate_sender_randomness(block_height, receiver_privacy_digest),
+                Hash::hash_varlen(&[BFieldElement::new(output_index as u64)]),
+            );
+
+            let public_announcement = match timelock {
+                Some(timelock) => address.generate_timelocked_public_announcement(
+                    &utxo,
+                    sender_randomness,
+                    timelock,
+                ),
+                None => address.generate_public_announcement(&utxo, sender_randomness),
+            };
+            let public_announcement = match public_announcement {
+                Ok(pa) => pa,
+                Err(_) => {
+                    tracing::error!(
+                        "Failed to generate transaction because could not encrypt to address."
+                    );
+                    return None;
+                }
+            };
+            if notification_medium == UtxoNotificationMedium::OffChain {
+                off_chain_notifications.push(public_announcement.clone());
+                off_chain_outputs.push(OffChainOutput {
+                    utxo: utxo.clone(),
+                    sender_randomness,
+                    receiver_privacy_digest,
+                });
+            }
+            receiver_data.push(UtxoReceiverData {
+                utxo,
+                sender_randomness,
+                receiver_privacy_digest,
+                public_announcement,
+            });
+            notification_media.push(notification_medium);
+        }
+        drop(state);
+
+        // Pause miner if we are mining
+        let was_mining = self.state.mining().await;
+        if was_mining {
+            let _ = self
+                .rpc_server_to_main_tx
+                .send(RPCServerToMain::PauseMiner)
+                .await;
+        }
+
+        let transaction_result = self
+            .state
+            .lock_guard_mut()
+            .await
+            .create_transaction_with_notification_media(
+                receiver_data.clone(),
+                &notification_media,
+                coin_selection,
+                fee,
+            )
+            .await;
+
+        let transaction = match transaction_result {
+            Ok(tx) => tx,
+            Err(err) => {
+                tracing::error!("Could not create transaction: {}", err);
+                return None;
+            }
+        };
+
+        // 2. Send transaction message to main
+        let response: Result<(), SendError<RPCServerToMain>> = self
+            .rpc_server_to_main_tx
+            .send(RPCServerToMain::Send(Box::new(transaction.clone())))
+            .await;
+
+        // Restart mining if it was paused
+        if was_mining {
+            let _ = self
+                .rpc_server_to_main_tx
+                .send(RPCServerToMain::RestartMiner)
+                .await;
+        }
+
+        if response.is_err() {
+            return None;
+        }
+
+        let txid = Hash::hash(&transaction);
+        let mut state = self.state.lock_guard_mut().await;
+        if let Err(err) = state.wallet_state.record_outgoing_transaction(
+            &transaction,
+            recipients,
+            off_chain_outputs,
+        ) {
+            error!("Could not record outgoing transaction {txid}: {err}");
+        }
+        state.wallet_state.add_unconfirmed_send(UnconfirmedSend {
+            transaction,
+            receiver_data,
+            notification_media,
+        });
+        state.flush_databases().await.expect("flushed DBs");
+
+        Some((txid, off_chain_notifications))
+    }
+
+    /// Build and broadcast a transaction that sends tokens, or issues them if `issue` is
+    /// set, to the given address.
+    ///
+    /// Locking:
+    ///   * acquires `global_state_lock` for write
+    async fn send_token_internal(
+        &self,
+        name: String,
+        amount: u64,
+        address: generation_address::ReceivingAddress,
+        fee: NeptuneCoins,
+        issue: bool,
+    ) -> Option<Digest> {
+        if fee.is_negative() {
+            error!("Cannot pay negative fee {fee}.");
+            return None;
+        }
+
+        let state = self.state.lock_guard().await;
+        let Some(known_token) = state.wallet_state.find_token(&name) else {
+            error!("No token named {name} is registered.");
+            return None;
+        };
+        let coin = match known_token.definition.coin(amount) {
+            Ok(coin) => coin,
+            Err(err) => {
+                error!("Cannot send token: {err}");
+                return None;
+            }
+        };
+        let wallet_secret = match state.wallet_state.spending_secret() {
+            Ok(wallet_secret) => wallet_secret,
+            Err(err) => {
+                error!("Cannot send token: {err}");
+                return None;
+            }
+        };
+        let utxo = Utxo::new(address.lock_script(), vec![coin]);
+        let block_height = state.chain.light_state().header().height;
+        let sender_randomness =
+            wallet_secret.generate_sender_randomness(block_height, address.privacy_digest);
+        let public_announcement =
+            match address.generate_public_announcement(&utxo, sender_randomness) {
+                Ok(public_announcement) => public_announcement,
+                Err(err) => {
+                    error!("Could not encrypt to address: {err}");
+                    return None;
+                }
+            };
+        let receiver_data = vec![UtxoReceiverData {
+            utxo,
+            sender_randomness,
+            receiver_privacy_digest: address.privacy_digest,
+            public_announcement,
+        }];
+        drop(state);
+
+        // Pause miner if we are mining
+        let was_mining = self.state.mining().await;
+        if was_mining {
+            let _ = self
+                .rpc_server_to_main_tx
+                .send(RPCServerToMain::PauseMiner)
+                .await;
+        }
+
+        let transaction_result = self
+            .state
+            .lock_guard_mut()
+            .await
+            .create_token_transaction(&name, receiver_data.clone(), issue, fee)
+            .await;
+
+        let transaction = match transaction_result {
+            Ok(transaction) => Some(transaction),
+            Err(err) => {
+                error!("Could not create token transaction: {err}");
+                None
+            }
+        };
+        let is_sent = match &transaction {
+            Some(transaction) => self
+                .rpc_server_to_main_tx
+                .send(RPCServerToMain::Send(Box::new(transaction.clone())))
+                .await
+                .is_ok(),
+            None => false,
+        };
+
+        // Restart mining if it was paused
+        if was_mining {
+            let _ = self
+                .rpc_server_to_main_tx
+                .send(RPCServerToMain::RestartMiner)
+                .await;
+        }
+
+        let transaction = transaction.filter(|_| is_sent)?;
+        let txid = Hash::hash(&transaction);
+        let mut state = self.state.lock_guard_mut().await;
+        state.wallet_state.add_unconfirmed_send(UnconfirmedSend {
+            transaction,
+            receiver_data,
+            notification_media: vec![UtxoNotificationMedium::OnChain],
+        });
+        state.flush_databases().await.expect("flushed DBs");
+
+        Some(txid)
+    }
 }
 
 impl RPC for NeptuneRPCServer {
@@ -364,9 +823,7 @@ impl RPC for NeptuneRPCServer {
             .lock_guard()
             .await
             .wallet_state
-            .wallet_secret
-            .nth_generation_spending_key(0)
-            .to_address()
+            .own_receiving_address()
     }
 
     async fn mempool_tx_count(self, _context: tarpc::context::Context) -> usize {
@@ -377,75 +834,348 @@ impl RPC for NeptuneRPCServer {
         self.state.lock_guard().await.mempool.get_size()
     }
 
-    async fn history(
+    async fn get_transaction(
         self,
         _context: tarpc::context::Context,
-    ) -> Vec<(Digest, BlockHeight, Duration, NeptuneCoins)> {
-        let history = self.state.lock_guard().await.get_balance_history().await;
-
-        // sort
-        let mut display_history: Vec<(Digest, BlockHeight, Duration, NeptuneCoins)> = history
-            .iter()
-            .map(|(h, t, bh, a)| (*h, *bh, *t, *a))
-            .collect::<Vec<_>>();
-        display_history.sort_by(|a, b| a.1.partial_cmp(&b.1).unwrap());
-
-        // return
-        display_history
+        txid: Digest,
+    ) -> Option<TransactionDetails> {
+        match self.state.lock_guard().await.get_transaction(txid).await {
+            Ok(transaction) => transaction.as_ref().map(TransactionDetails::new),
+            Err(err) => {
+                error!("Could not look up transaction {txid}: {err}");
+                None
+            }
+        }
     }
 
-    async fn dashboard_overview_data(
+    async fn decode_transaction(
         self,
         _context: tarpc::context::Context,
-    ) -> DashBoardOverviewDataFromClient {
-        let state = self.state.lock_guard().await;
-        let tip_header = state.chain.light_state().header().clone();
-        let wallet_status = state.get_wallet_status_for_tip().await;
-        let syncing = state.net.syncing;
-        let mempool_size = state.mempool.get_size();
-        let mempool_tx_count = state.mempool.len();
-
-        let peer_count = Some(state.net.peer_map.len());
-
-        let is_mining = Some(state.mining);
-        drop(state);
-
-        let confirmations = self.confirmations_internal().await;
+        bytes: Vec<u8>,
+    ) -> Option<TransactionDetails> {
+        match bincode::deserialize::<Transaction>(&bytes) {
+            Ok(transaction) => Some(TransactionDetails::new(&transaction)),
+            Err(err) => {
+                error!("Could not decode transaction: {err}");
+                None
+            }
+        }
+    }
 
-        DashBoardOverviewDataFromClient {
-            tip_header,
-            syncing,
-            synced_balance: wallet_status.synced_unspent_amount,
-            mempool_size,
-            mempool_tx_count,
-            peer_count,
-            is_mining,
-            confirmations,
+    async fn estimate_fee(
+        self,
+        _context: tarpc::context::Context,
+        target_blocks: usize,
+        transaction_size: Option<usize>,
+    ) -> Option<NeptuneCoins> {
+        match self
+            .state
+            .lock_guard()
+            .await
+            .estimate_fee(target_blocks, transaction_size)
+            .await
+        {
+            Ok(fee) => Some(fee),
+            Err(err) => {
+                error!("Could not estimate fee: {err}");
+                None
+            }
         }
     }
 
-    /******** CHANGE THINGS ********/
     /// Locking:
-    ///   * acquires `global_state_lock` for write
-    async fn clear_all_standings(self, _: context::Context) {
-        let mut global_state_mut = self.state.lock_guard_mut().await;
-        global_state_mut
-            .net
-            .peer_map
-            .iter_mut()
-            .for_each(|(_, peerinfo)| {
-                peerinfo.standing.clear_standing();
+    ///   * acquires `global_state_lock` for read
+    async fn estimate_send_fee(
+        self,
+        _context: tarpc::context::Context,
+        outputs: Vec<(
+            generation_address::ReceivingAddress,
+            NeptuneCoins,
+            UtxoNotificationMedium,
+            Option<Timelock>,
+        )>,
+        coin_selection: CoinSelectionStrategy,
+        target_blocks: usize,
+    ) -> Option<NeptuneCoins> {
+        // The outputs of the draft transaction have the size of the outputs that are sent,
+        // but random sender randomness.
+        let mut receiver_data = vec![];
+        let mut notification_media = vec![];
+        for (address, amount, notification_medium, timelock) in outputs {
+            let lock_script = match timelock {
+                Some(timelock) => address.timelocked_lock_script(timelock),
+                None => address.lock_script(),
+            };
+            let utxo = Utxo::new(lock_script, amount.to_native_coins());
+            let sender_randomness: Digest = rand::random();
+            let public_announcement = match timelock {
+                Some(timelock) => address.generate_timelocked_public_announcement(
+                    &utxo,
+                    sender_randomness,
+                    timelock,
+                ),
+                None => address.generate_public_announcement(&utxo, sender_randomness),
+            };
+            let Ok(public_announcement) = public_announcement else {
+                error!("Could not estimate fee, as could not encrypt to address.");
+                return None;
+            };
+            receiver_data.push(UtxoReceiverData {
+                utxo,
+                sender_randomness,
+                receiver_privacy_digest: address.privacy_digest,
+                public_announcement,
             });
+            notification_media.push(notification_medium);
+        }
 
-        // iterates and modifies standing field for all connected peers
-        global_state_mut.net.clear_all_standings_in_database().await;
-
-        global_state_mut
-            .flush_databases()
+        match self
+            .state
+            .lock_guard()
             .await
-            .expect("flushed DBs");
-    }
-
+            .estimate_fee_for_outputs(
+                receiver_data,
+                &notification_media,
+                coin_selection,
+                target_blocks,
+            )
+            .await
+        {
+            Ok(fee) => Some(fee),
+            Err(err) => {
+                error!("Could not estimate fee: {err}");
+                None
+            }
+        }
+    }
+
+    async fn unconfirmed_sends(
+        self,
+        _context: tarpc::context::Context,
+    ) -> Vec<(Digest, NeptuneCoins, NeptuneCoins)> {
+        self.state
+            .lock_guard()
+            .await
+            .wallet_state
+            .unconfirmed_sends()
+            .iter()
+            .map(|send| (send.txid(), send.amount(), send.fee()))
+            .collect()
+    }
+
+    async fn outgoing_transactions(
+        self,
+        _context: tarpc::context::Context,
+    ) -> Vec<OutgoingTransaction> {
+        self.state
+            .lock_guard()
+            .await
+            .wallet_state
+            .outgoing_transactions()
+    }
+
+    async fn utxo_transfer_notifications(
+        self,
+        _context: tarpc::context::Context,
+        txid: Digest,
+    ) -> Option<Vec<UtxoTransferNotification>> {
+        let state = self.state.lock_guard().await;
+        match state
+            .wallet_state
+            .utxo_transfer_notifications(txid, state.cli().network)
+        {
+            Ok(notifications) => Some(notifications),
+            Err(err) => {
+                error!("Could not export UTXO notifications: {err}");
+                None
+            }
+        }
+    }
+
+    async fn multisig_public_key(
+        self,
+        _context: tarpc::context::Context,
+        index: u16,
+    ) -> Option<Digest> {
+        let state = self.state.lock_guard().await;
+        match state.wallet_state.spending_secret() {
+            Ok(wallet_secret) => Some(wallet_secret.nth_multisig_key(index).public_key()),
+            Err(err) => {
+                error!("Could not derive multisig key: {err}");
+                None
+            }
+        }
+    }
+
+    async fn multisig_addresses(
+        self,
+        _context: tarpc::context::Context,
+    ) -> Vec<(MultisigAddress, NeptuneCoins)> {
+        let state = self.state.lock_guard().await;
+        state
+            .wallet_state
+            .multisig_setups()
+            .into_iter()
+            .map(|setup| {
+                let balance = state
+                    .wallet_state
+                    .unspent_multisig_utxos(&setup.address)
+                    .iter()
+                    .map(|multisig_utxo| multisig_utxo.utxo.get_native_coin_amount())
+                    .sum();
+                (setup.address, balance)
+            })
+            .collect()
+    }
+
+    async fn tokens(self, _context: tarpc::context::Context) -> Vec<(KnownToken, u64)> {
+        let state = self.state.lock_guard().await;
+        let wallet_status = state.get_wallet_status_for_tip().await;
+        state
+            .wallet_state
+            .known_tokens()
+            .into_iter()
+            .map(|known_token| {
+                let amount = wallet_status
+                    .synced_unspent_token_amounts
+                    .iter()
+                    .find(|(name, _amount)| *name == known_token.name)
+                    .map(|(_name, amount)| *amount)
+                    .unwrap_or_default();
+                (known_token, amount)
+            })
+            .collect()
+    }
+
+    async fn create_multisig_transaction(
+        self,
+        _context: tarpc::context::Context,
+        address: MultisigAddress,
+        outputs: Vec<(generation_address::ReceivingAddress, NeptuneCoins)>,
+        change_address: Option<MultisigAddress>,
+        fee: NeptuneCoins,
+    ) -> Option<PartiallySignedTransaction> {
+        if let Some((_, amount)) = outputs.iter().find(|(_, amount)| amount.is_negative()) {
+            error!("Cannot send negative amount {amount}.");
+            return None;
+        }
+
+        let mut receiver_data = vec![];
+        for (address, amount) in outputs {
+            let utxo = Utxo::new(address.lock_script(), amount.to_native_coins());
+            let sender_randomness: Digest = rand::random();
+            let public_announcement =
+                match address.generate_public_announcement(&utxo, sender_randomness) {
+                    Ok(pa) => pa,
+                    Err(err) => {
+                        error!("Could not encrypt to address: {err}");
+                        return None;
+                    }
+                };
+            receiver_data.push(UtxoReceiverData {
+                utxo,
+                sender_randomness,
+                receiver_privacy_digest: address.privacy_digest,
+                public_announcement,
+            });
+        }
+
+        let state = self.state.lock_guard().await;
+        match state.create_multisig_transaction(&address, receiver_data, change_address, fee) {
+            Ok(transaction) => Some(transaction),
+            Err(err) => {
+                error!("Could not create multisig transaction: {err}");
+                None
+            }
+        }
+    }
+
+    /// Locking:
+    ///   * acquires `global_state_lock` for write
+    async fn sign_multisig_transaction(
+        self,
+        _context: tarpc::context::Context,
+        transaction: PartiallySignedTransaction,
+    ) -> Option<PartiallySignedTransaction> {
+        let mut state = self.state.lock_guard_mut().await;
+        match state.sign_multisig_transaction(transaction) {
+            Ok(transaction) => Some(transaction),
+            Err(err) => {
+                error!("Could not sign multisig transaction: {err}");
+                None
+            }
+        }
+    }
+
+    async fn history(
+        self,
+        _context: tarpc::context::Context,
+    ) -> Vec<(Digest, BlockHeight, Duration, NeptuneCoins)> {
+        let history = self.state.lock_guard().await.get_balance_history().await;
+
+        // sort
+        let mut display_history: Vec<(Digest, BlockHeight, Duration, NeptuneCoins)> = history
+            .iter()
+            .map(|(h, t, bh, a)| (*h, *bh, *t, *a))
+            .collect::<Vec<_>>();
+        display_history.sort_by(|a, b| a.1.partial_cmp(&b.1).unwrap());
+
+        // return
+        display_history
+    }
+
+    async fn dashboard_overview_data(
+        self,
+        _context: tarpc::context::Context,
+    ) -> DashBoardOverviewDataFromClient {
+        let state = self.state.lock_guard().await;
+        let tip_header = state.chain.light_state().header().clone();
+        let wallet_status = state.get_wallet_status_for_tip().await;
+        let syncing = state.net.syncing;
+        let mempool_size = state.mempool.get_size();
+        let mempool_tx_count = state.mempool.len();
+
+        let peer_count = Some(state.net.peer_map.len());
+
+        let is_mining = Some(state.mining);
+        drop(state);
+
+        let confirmations = self.confirmations_internal().await;
+
+        DashBoardOverviewDataFromClient {
+            tip_header,
+            syncing,
+            synced_balance: wallet_status.synced_unspent_amount,
+            mempool_size,
+            mempool_tx_count,
+            peer_count,
+            is_mining,
+            confirmations,
+        }
+    }
+
+    /******** CHANGE THINGS ********/
+    /// Locking:
+    ///   * acquires `global_state_lock` for write
+    async fn clear_all_standings(self, _: context::Context) {
+        let mut global_state_mut = self.state.lock_guard_mut().await;
+        global_state_mut
+            .net
+            .peer_map
+            .iter_mut()
+            .for_each(|(_, peerinfo)| {
+                peerinfo.standing.clear_standing();
+            });
+
+        // iterates and modifies standing field for all connected peers
+        global_state_mut.net.clear_all_standings_in_database().await;
+
+        global_state_mut
+            .flush_databases()
+            .await
+            .expect("flushed DBs");
+    }
+
     /// Locking:
     ///   * acquires `global_state_lock` for write
     async fn clear_standing_by_ip(self, _: context::Context, ip: IpAddr) {
@@ -469,6 +1199,25 @@ impl RPC for NeptuneRPCServer {
             .expect("flushed DBs");
     }
 
+    /// Locking:
+    ///   * acquires `global_state_lock` for write
+    async fn next_receiving_address(
+        self,
+        _context: tarpc::context::Context,
+    ) -> Option<generation_address::ReceivingAddress> {
+        let mut global_state_mut = self.state.lock_guard_mut().await;
+        match global_state_mut
+            .wallet_state
+            .next_unused_generation_spending_key()
+        {
+            Ok(spending_key) => Some(spending_key.to_address()),
+            Err(err) => {
+                error!("Could not generate new receiving address: {err}");
+                None
+            }
+        }
+    }
+
     /// Locking:
     ///   * acquires `global_state_lock` for write
     async fn send(
@@ -477,45 +1226,48 @@ impl RPC for NeptuneRPCServer {
         amount: NeptuneCoins,
         address: generation_address::ReceivingAddress,
         fee: NeptuneCoins,
+        timelock: Option<Timelock>,
     ) -> Option<Digest> {
-        let span = tracing::debug_span!("Constructing transaction objects");
-        let _enter = span.enter();
-
-        let coins = amount.to_native_coins();
-        let utxo = Utxo::new(address.lock_script(), coins);
-
-        let state = self.state.lock_guard().await;
-        let block_height = state.chain.light_state().header().height;
-        let receiver_privacy_digest = address.privacy_digest;
-        let sender_randomness = state
-            .wallet_state
-            .wallet_secret
-            .generate_sender_randomness(block_height, receiver_privacy_digest);
-        drop(state);
+        self.send_outputs_internal(
+            vec![(address, amount, UtxoNotificationMedium::OnChain, timelock)],
+            CoinSelectionStrategy::default(),
+            fee,
+        )
+        .await
+        .map(|(txid, _off_chain_notifications)| txid)
+    }
 
-        // 1. Build transaction object
-        // TODO: Allow user to set fee here. Don't set it automatically as we want the user
-        // to be in control of this. But we could add an endpoint to get recommended fee
-        // density.
-        let public_announcement =
-            match address.generate_public_announcement(&utxo, sender_randomness) {
-                Ok(pa) => pa,
-                Err(_) => {
-                    tracing::error!(
-                        "Failed to generate transaction because could not encrypt to address."
-                    );
-                    return None;
-                }
-            };
-        let receiver_data = [(UtxoReceiverData {
-            utxo,
-            sender_randomness,
-            receiver_privacy_digest,
-            public_announcement,
-        })]
-        .to_vec();
+    /// Locking:
+    ///   * acquires `global_state_lock` for write
+    async fn send_to_many(
+        self,
+        _ctx: context::Context,
+        outputs: Vec<(
+            generation_address::ReceivingAddress,
+            NeptuneCoins,
+            UtxoNotificationMedium,
+        )>,
+        coin_selection: CoinSelectionStrategy,
+        fee: NeptuneCoins,
+    ) -> Option<(Digest, Vec<PublicAnnouncement>)> {
+        let outputs = outputs
+            .into_iter()
+            .map(|(address, amount, notification_medium)| {
+                (address, amount, notification_medium, None)
+            })
+            .collect();
+        self.send_outputs_internal(outputs, coin_selection, fee)
+            .await
+    }
 
-        // Pause miner if we are mining
+    /// Locking:
+    ///   * acquires `global_state_lock` for write
+    async fn bump_fee(
+        self,
+        _ctx: context::Context,
+        txid: Digest,
+        new_fee: NeptuneCoins,
+    ) -> Option<Digest> {
         let was_mining = self.state.mining().await;
         if was_mining {
             let _ = self
@@ -524,28 +1276,24 @@ impl RPC for NeptuneRPCServer {
                 .await;
         }
 
-        let transaction_result = self
+        let bump_result = self
             .state
             .lock_guard_mut()
             .await
-            .create_transaction(receiver_data, fee)
+            .create_fee_bump_transaction(txid, new_fee)
             .await;
 
-        let transaction = match transaction_result {
-            Ok(tx) => tx,
-            Err(err) => {
-                tracing::error!("Could not create transaction: {}", err);
-                return None;
+        let response = match &bump_result {
+            Ok(replacement) => {
+                self.rpc_server_to_main_tx
+                    .send(RPCServerToMain::Send(Box::new(
+                        replacement.transaction.clone(),
+                    )))
+                    .await
             }
+            Err(_) => Ok(()),
         };
 
-        // 2. Send transaction message to main
-        let response: Result<(), SendError<RPCServerToMain>> = self
-            .rpc_server_to_main_tx
-            .send(RPCServerToMain::Send(Box::new(transaction.clone())))
-            .await;
-
-        // Restart mining if it was paused
         if was_mining {
             let _ = self
                 .rpc_server_to_main_tx
@@ -553,13 +1301,244 @@ impl RPC for NeptuneRPCServer {
                 .await;
         }
 
-        self.state.flush_databases().await.expect("flushed DBs");
+        let replacement = match bump_result {
+            Ok(replacement) => replacement,
+            Err(err) => {
+                error!("Could not bump fee of transaction {txid}: {err}");
+                return None;
+            }
+        };
+        if response.is_err() {
+            return None;
+        }
 
-        if response.is_ok() {
-            Some(Hash::hash(&transaction))
-        } else {
-            None
+        let replacement_txid = replacement.txid();
+        let mut state = self.state.lock_guard_mut().await;
+        if let Err(err) = state
+            .wallet_state
+            .record_fee_bump(txid, &replacement.transaction)
+        {
+            error!("Could not record fee bump of outgoing transaction {txid}: {err}");
+        }
+        state.wallet_state.add_unconfirmed_send(replacement);
+        state.flush_databases().await.expect("flushed DBs");
+
+        Some(replacement_txid)
+    }
+
+    /// Locking:
+    ///   * acquires `global_state_lock` for write
+    async fn label_outgoing_transaction(
+        self,
+        _ctx: context::Context,
+        txid: Digest,
+        label: String,
+    ) -> bool {
+        let mut state = self.state.lock_guard_mut().await;
+        if let Err(err) = state
+            .wallet_state
+            .set_outgoing_transaction_label(txid, label)
+        {
+            error!("Could not label transaction: {err}");
+            return false;
+        }
+        state.flush_databases().await.expect("flushed DBs");
+
+        true
+    }
+
+    /// Locking:
+    ///   * acquires `global_state_lock` for write
+    async fn claim_utxo(
+        self,
+        _ctx: context::Context,
+        notification: UtxoTransferNotification,
+    ) -> Option<UtxoClaimStatus> {
+        let mut state = self.state.lock_guard_mut().await;
+        let claim_result = state.claim_utxo(notification).await;
+        state.flush_databases().await.expect("flushed DBs");
+
+        match claim_result {
+            Ok(status) => Some(status),
+            Err(err) => {
+                error!("Could not claim UTXO: {err}");
+                None
+            }
+        }
+    }
+
+    /// Locking:
+    ///   * acquires `global_state_lock` for write
+    async fn import_multisig_setup(
+        self,
+        _ctx: context::Context,
+        setup: MultisigSetup,
+    ) -> Option<usize> {
+        let mut state = self.state.lock_guard_mut().await;
+        let import_result = state.import_multisig_setup(setup).await;
+        state.flush_databases().await.expect("flushed DBs");
+
+        match import_result {
+            Ok(utxo_count) => Some(utxo_count),
+            Err(err) => {
+                error!("Could not import multisig setup: {err}");
+                None
+            }
+        }
+    }
+
+    async fn broadcast_multisig_transaction(
+        self,
+        _ctx: context::Context,
+        transaction: PartiallySignedTransaction,
+    ) -> Option<Digest> {
+        let finalize_result = self
+            .state
+            .lock_guard()
+            .await
+            .finalize_multisig_transaction(transaction)
+            .await;
+        let transaction = match finalize_result {
+            Ok(transaction) => transaction,
+            Err(err) => {
+                error!("Could not finalize multisig transaction: {err}");
+                return None;
+            }
+        };
+
+        let txid = Hash::hash(&transaction);
+        let response = self
+            .rpc_server_to_main_tx
+            .send(RPCServerToMain::Send(Box::new(transaction)))
+            .await;
+        if response.is_err() {
+            return None;
+        }
+
+        Some(txid)
+    }
+
+    /// Locking:
+    ///   * acquires `global_state_lock` for write
+    async fn create_unsigned_transaction(
+        self,
+        _ctx: context::Context,
+        outputs: Vec<(generation_address::ReceivingAddress, NeptuneCoins)>,
+        fee: NeptuneCoins,
+    ) -> Option<UnsignedTransaction> {
+        if let Some((_, amount)) = outputs.iter().find(|(_, amount)| amount.is_negative()) {
+            error!("Cannot send negative amount {amount}.");
+            return None;
+        }
+
+        match self
+            .state
+            .lock_guard_mut()
+            .await
+            .create_unsigned_transaction(outputs, CoinSelectionStrategy::default(), fee)
+            .await
+        {
+            Ok(unsigned_transaction) => Some(unsigned_transaction),
+            Err(err) => {
+                error!("Could not create unsigned transaction: {err}");
+                None
+            }
+        }
+    }
+
+    async fn broadcast_signed_transaction(
+        self,
+        _ctx: context::Context,
+        transaction: SignedTransaction,
+    ) -> Option<Digest> {
+        let verify_result = self
+            .state
+            .lock_guard()
+            .await
+            .verify_signed_transaction(transaction);
+        let transaction = match verify_result {
+            Ok(transaction) => transaction,
+            Err(err) => {
+                error!("Could not broadcast signed transaction: {err}");
+                return None;
+            }
+        };
+
+        let txid = Hash::hash(&transaction);
+        let response = self
+            .rpc_server_to_main_tx
+            .send(RPCServerToMain::Send(Box::new(transaction)))
+            .await;
+        if response.is_err() {
+            return None;
         }
+
+        Some(txid)
+    }
+
+    /// Locking:
+    ///   * acquires `global_state_lock` for write
+    async fn create_token(self, _ctx: context::Context, name: String) -> Option<TokenDefinition> {
+        let mut state = self.state.lock_guard_mut().await;
+        let create_result = state.wallet_state.create_token(name);
+        state.flush_databases().await.expect("flushed DBs");
+
+        match create_result {
+            Ok(known_token) => Some(known_token.definition),
+            Err(err) => {
+                error!("Could not create token: {err}");
+                None
+            }
+        }
+    }
+
+    /// Locking:
+    ///   * acquires `global_state_lock` for write
+    async fn register_token(
+        self,
+        _ctx: context::Context,
+        name: String,
+        definition: TokenDefinition,
+    ) -> bool {
+        let mut state = self.state.lock_guard_mut().await;
+        let register_result = state.wallet_state.register_token(name, definition);
+        state.flush_databases().await.expect("flushed DBs");
+
+        match register_result {
+            Ok(()) => true,
+            Err(err) => {
+                error!("Could not register token: {err}");
+                false
+            }
+        }
+    }
+
+    /// Locking:
+    ///   * acquires `global_state_lock` for write
+    async fn issue_token(
+        self,
+        _ctx: context::Context,
+        name: String,
+        amount: u64,
+        address: generation_address::ReceivingAddress,
+        fee: NeptuneCoins,
+    ) -> Option<Digest> {
+        self.send_token_internal(name, amount, address, fee, true)
+            .await
+    }
+
+    /// Locking:
+    ///   * acquires `global_state_lock` for write
+    async fn send_token(
+        self,
+        _ctx: context::Context,
+        name: String,
+        amount: u64,
+        address: generation_address::ReceivingAddress,
+        fee: NeptuneCoins,
+    ) -> Option<Digest> {
+        self.send_token_internal(name, amount, address, fee, false)
+            .await
     }
 
     async fn shutdown(self, _: context::Context) -> bool {
@@ -619,6 +1598,55 @@ impl RPC for NeptuneRPCServer {
             }
         }
     }
+
+    /// Locking:
+    ///   * acquires `global_state_lock` for write
+    async fn rescan(
+        self,
+        _context: tarpc::context::Context,
+        from_height: BlockHeight,
+    ) -> Option<usize> {
+        let mut global_state_mut = self.state.lock_guard_mut().await;
+        let rescan_res = global_state_mut.rescan_from_height(from_height).await;
+
+        global_state_mut
+            .flush_databases()
+            .await
+            .expect("flushed DBs");
+
+        match rescan_res {
+            Ok(restored_count) => {
+                info!("Rescan from height {from_height} restored {restored_count} monitored UTXOs");
+                Some(restored_count)
+            }
+            Err(err) => {
+                error!("Rescanning wallet failed with error: {err}");
+                None
+            }
+        }
+    }
+
+    /// Locking:
+    ///   * acquires `global_state_lock` for write
+    async fn restore_monitored_utxos(self, _context: tarpc::context::Context) -> Option<usize> {
+        let mut global_state_mut = self.state.lock_guard_mut().await;
+        let restore_res = global_state_mut
+            .restore_monitored_utxos_from_recovery_data()
+            .await;
+
+        global_state_mut
+            .flush_databases()
+            .await
+            .expect("flushed DBs");
+
+        match restore_res {
+            Ok(restored_count) => Some(restored_count),
+            Err(err) => {
+                error!("Restoring monitored UTXOs failed with error: {err}");
+                None
+            }
+        }
+    }
 }
 
 #[cfg(test)]
@@ -626,9 +1654,11 @@ mod rpc_server_tests {
     use super::*;
     use crate::{
         config_models::network::Network,
+        models::blockchain::transaction::TransactionPrimitiveWitness,
         models::{peer::PeerSanctionReason, state::wallet::WalletSecret},
         rpc_server::NeptuneRPCServer,
-        tests::shared::get_mock_global_state,
+        tests::shared::{get_mock_global_state, make_mock_transaction, random_transaction_kernel},
+        util_types::mutator_set::mutator_set_accumulator::MutatorSetAccumulator,
         RPC_CHANNEL_CAPACITY,
     };
     use anyhow::Result;
@@ -689,6 +1719,17 @@ mod rpc_server_tests {
         let own_receiving_address = rpc_server.clone().own_receiving_address(ctx).await;
         let _ = rpc_server.clone().mempool_tx_count(ctx).await;
         let _ = rpc_server.clone().mempool_size(ctx).await;
+        let _ = rpc_server.clone().estimate_fee(ctx, 3, None).await;
+        let _ = rpc_server
+            .clone()
+            .estimate_send_fee(ctx, vec![], CoinSelectionStrategy::default(), 3)
+            .await;
+        let _ = rpc_server.clone().unconfirmed_sends(ctx).await;
+        let _ = rpc_server.clone().outgoing_transactions(ctx).await;
+        let _ = rpc_server
+            .clone()
+            .utxo_transfer_notifications(ctx, Digest::default())
+            .await;
         let _ = rpc_server.clone().dashboard_overview_data(ctx).await;
         let _ = rpc_server
             .clone()
@@ -699,6 +1740,7 @@ mod rpc_server_tests {
             .clone()
             .clear_standing_by_ip(ctx, "127.0.0.1".parse().unwrap())
             .await;
+        let _ = rpc_server.clone().next_receiving_address(ctx).await;
         let _ = rpc_server
             .clone()
             .send(
@@ -706,6 +1748,27 @@ mod rpc_server_tests {
                 NeptuneCoins::one(),
                 own_receiving_address,
                 NeptuneCoins::one(),
+                None,
+            )
+            .await;
+        let _ = rpc_server
+            .clone()
+            .send_to_many(
+                ctx,
+                vec![
+                    (
+                        own_receiving_address,
+                        NeptuneCoins::one(),
+                        UtxoNotificationMedium::OnChain,
+                    ),
+                    (
+                        own_receiving_address,
+                        NeptuneCoins::one(),
+                        UtxoNotificationMedium::OffChain,
+                    ),
+                ],
+                CoinSelectionStrategy::BranchAndBound,
+                NeptuneCoins::one(),
             )
             .await;
         let _ = rpc_server.clone().pause_miner(ctx).await;
@@ -714,11 +1777,166 @@ mod rpc_server_tests {
             .clone()
             .prune_abandoned_monitored_utxos(ctx)
             .await;
+        let _ = rpc_server.clone().rescan(ctx, 0u64.into()).await;
+        let _ = rpc_server.clone().restore_monitored_utxos(ctx).await;
+        let _ = rpc_server
+            .clone()
+            .bump_fee(ctx, Digest::default(), NeptuneCoins::one())
+            .await;
+        let _ = rpc_server
+            .clone()
+            .label_outgoing_transaction(ctx, Digest::default(), "label".to_string())
+            .await;
+        let off_chain_output = OffChainOutput {
+            utxo: Utxo::new(
+                own_receiving_address.lock_script(),
+                NeptuneCoins::one().to_native_coins(),
+            ),
+            sender_randomness: Digest::default(),
+            receiver_privacy_digest: own_receiving_address.privacy_digest,
+        };
+        let _ = rpc_server
+            .clone()
+            .claim_utxo(
+                ctx,
+                UtxoTransferNotification::new(
+                    Network::Alpha,
+                    Digest::default(),
+                    &off_chain_output,
+                    None,
+                ),
+            )
+            .await;
+        let own_multisig_key = rpc_server
+            .clone()
+            .multisig_public_key(ctx, 0)
+            .await
+            .unwrap_or_default();
+        let multisig_setup =
+            MultisigSetup::new(1, vec![own_multisig_key], Digest::default()).unwrap();
+        let _ = rpc_server
+            .clone()
+            .import_multisig_setup(ctx, multisig_setup.clone())
+            .await;
+        let _ = rpc_server.clone().multisig_addresses(ctx).await;
+        let _ = rpc_server
+            .clone()
+            .create_multisig_transaction(
+                ctx,
+                multisig_setup.address.clone(),
+                vec![(own_receiving_address, NeptuneCoins::one())],
+                None,
+                NeptuneCoins::one(),
+            )
+            .await;
+        let partially_signed_transaction = PartiallySignedTransaction::new(
+            Network::Alpha,
+            multisig_setup.address,
+            random_transaction_kernel(),
+            TransactionPrimitiveWitness {
+                input_utxos: vec![],
+                input_lock_scripts: vec![],
+                type_scripts: vec![],
+                lock_script_witnesses: vec![],
+                input_membership_proofs: vec![],
+                output_utxos: vec![],
+                output_sender_randomnesses: vec![],
+                output_receiver_digests: vec![],
+                public_announcements: vec![],
+                mutator_set_accumulator: MutatorSetAccumulator::default(),
+            },
+        );
+        let _ = rpc_server
+            .clone()
+            .sign_multisig_transaction(ctx, partially_signed_transaction.clone())
+            .await;
+        let _ = rpc_server
+            .clone()
+            .broadcast_multisig_transaction(ctx, partially_signed_transaction)
+            .await;
+        let _ = rpc_server
+            .clone()
+            .create_unsigned_transaction(
+                ctx,
+                vec![(own_receiving_address, NeptuneCoins::one())],
+                NeptuneCoins::one(),
+            )
+            .await;
+        let _ = rpc_server
+            .clone()
+            .broadcast_signed_transaction(
+                ctx,
+                SignedTransaction {
+                    version: 0,
+                    network: Network::Alpha,
+                    transaction: make_mock_transaction(vec![], vec![]),
+                },
+            )
+            .await;
+        let _ = rpc_server.clone().tokens(ctx).await;
+        let token_definition = rpc_server
+            .clone()
+            .create_token(ctx, "token".to_string())
+            .await
+            .unwrap_or(TokenDefinition::new(Digest::default(), Digest::default()));
+        let _ = rpc_server
+            .clone()
+            .register_token(ctx, "other token".to_string(), token_definition)
+            .await;
+        let _ = rpc_server
+            .clone()
+            .issue_token(
+                ctx,
+                "token".to_string(),
+                1,
+                own_receiving_address,
+                NeptuneCoins::one(),
+            )
+            .await;
+        let _ = rpc_server
+            .clone()
+            .send_token(
+                ctx,
+                "token".to_string(),
+                1,
+                own_receiving_address,
+                NeptuneCoins::one(),
+            )
+            .await;
         let _ = rpc_server.shutdown(ctx).await;
 
         Ok(())
     }
 
+    #[traced_test]
+    #[tokio::test]
+    async fn next_receiving_address_is_fresh() -> Result<()> {
+        let (rpc_server, _) = test_rpc_server(Network::Alpha, WalletSecret::new_random(), 2).await;
+        let ctx = context::current();
+        let default_address = rpc_server.clone().own_receiving_address(ctx).await;
+        let first_new_address = rpc_server
+            .clone()
+            .next_receiving_address(ctx)
+            .await
+            .unwrap();
+        let second_new_address = rpc_server
+            .clone()
+            .next_receiving_address(ctx)
+            .await
+            .unwrap();
+
+        assert_ne!(default_address, first_new_address);
+        assert_ne!(default_address, second_new_address);
+        assert_ne!(first_new_address, second_new_address);
+        assert_eq!(
+            default_address,
+            rpc_server.own_receiving_address(ctx).await,
+            "Default address must not change when new addresses are handed out"
+        );
+
+        Ok(())
+    }
+
     #[traced_test]
     #[tokio::test]
     async fn balance_is_zero_at_init() -> Result<()> {
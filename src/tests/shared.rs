@@ -28,10 +28,9 @@ use std::{
     sync::Arc,
     time::{SystemTime, UNIX_EPOCH},
 };
-use tasm_lib::triton_vm::proof::Proof;
 use tasm_lib::twenty_first::util_types::mmr::mmr_accumulator::MmrAccumulator;
 use tokio::sync::{broadcast, mpsc};
-use tokio_serde::{formats::SymmetricalBincode, Serializer};
+use tokio_serde::Serializer;
 use tokio_util::codec::{Encoder, LengthDelimitedCodec};
 use twenty_first::shared_math::bfield_codec::BFieldCodec;
 use twenty_first::shared_math::digest::Digest;
@@ -44,18 +43,24 @@ use twenty_first::shared_math::other::random_elements_array;
 use crate::config_models::cli_args;
 use crate::config_models::data_directory::DataDirectory;
 use crate::config_models::network::Network;
+use crate::connect_to_peers::PeerMessageCodec;
 use crate::database::NeptuneLevelDb;
 use crate::models::blockchain::block::block_body::BlockBody;
 use crate::models::blockchain::block::block_header::BlockHeader;
 use crate::models::blockchain::block::block_header::TARGET_BLOCK_INTERVAL;
+use crate::models::blockchain::block::block_kernel::BlockKernel;
+use crate::models::blockchain::block::validity::BlockPrimitiveWitness;
 use crate::models::blockchain::block::{block_height::BlockHeight, Block};
 use crate::models::blockchain::transaction::neptune_coins::pseudorandom_amount;
 use crate::models::blockchain::transaction::neptune_coins::NeptuneCoins;
+use crate::models::blockchain::transaction::token;
 use crate::models::blockchain::transaction::transaction_kernel::pseudorandom_option;
 use crate::models::blockchain::transaction::transaction_kernel::pseudorandom_public_announcement;
 use crate::models::blockchain::transaction::transaction_kernel::pseudorandom_transaction_kernel;
 use crate::models::blockchain::transaction::transaction_kernel::TransactionKernel;
 use crate::models::blockchain::transaction::utxo::TypeScript;
+use crate::models::blockchain::transaction::validity::kernel_to_lock_scripts::KernelToLockScriptsWitness;
+use crate::models::blockchain::transaction::validity::kernel_to_type_scripts::KernelToTypeScriptsWitness;
 use crate::models::blockchain::transaction::validity::removal_records_integrity::RemovalRecordsIntegrityWitness;
 use crate::models::blockchain::transaction::validity::TransactionValidationLogic;
 use crate::models::blockchain::transaction::PublicAnnouncement;
@@ -78,6 +83,7 @@ use crate::models::state::wallet::wallet_state::WalletState;
 use crate::models::state::wallet::WalletSecret;
 use crate::models::state::UtxoReceiverData;
 use crate::models::state::{GlobalState, GlobalStateLock};
+use crate::peer_transport::PeerTransportKey;
 use crate::util_types::mutator_set::addition_record::pseudorandom_addition_record;
 use crate::util_types::mutator_set::addition_record::AdditionRecord;
 use crate::util_types::mutator_set::chunk_dictionary::pseudorandom_chunk_dictionary;
@@ -171,7 +177,7 @@ pub fn get_dummy_handshake_data_for_genesis(network: Network) -> HandshakeData {
 
 pub fn to_bytes(message: &PeerMessage) -> Result<Bytes> {
     let mut transport = LengthDelimitedCodec::new();
-    let mut formating = SymmetricalBincode::<PeerMessage>::default();
+    let mut formating = PeerMessageCodec;
     let mut buf = BytesMut::new();
     transport.encode(Pin::new(&mut formating).serialize(message)?, &mut buf)?;
     Ok(buf.freeze())
@@ -204,7 +210,8 @@ pub async fn get_mock_global_state(
             std::net::SocketAddr::from_str(&format!("123.123.123.{}:8080", i)).unwrap();
         peer_map.insert(peer_address, get_dummy_peer(peer_address));
     }
-    let networking_state = NetworkingState::new(peer_map, peer_db, syncing);
+    let networking_state =
+        NetworkingState::new(peer_map, peer_db, syncing, PeerTransportKey::generate());
     let (block, _, _) = get_dummy_latest_block(None);
     let light_state: LightState = LightState::from(block);
     let blockchain_state = BlockchainState::Archival(BlockchainArchivalState {
@@ -513,6 +520,7 @@ pub fn pseudorandom_removal_record_integrity_witness(
 
     RemovalRecordsIntegrityWitness {
         input_utxos,
+        input_utxos_salt: rng.gen(),
         membership_proofs,
         aocl,
         swbfi,
@@ -521,6 +529,79 @@ pub fn pseudorandom_removal_record_integrity_witness(
     }
 }
 
+pub fn pseudorandom_kernel_to_lock_scripts_witness(seed: [u8; 32]) -> KernelToLockScriptsWitness {
+    let mut rng: StdRng = SeedableRng::from_seed(seed);
+    let num_inputs = 3;
+
+    KernelToLockScriptsWitness {
+        input_utxos: (0..num_inputs)
+            .map(|_| pseudorandom_utxo(rng.gen::<[u8; 32]>()))
+            .collect_vec(),
+        input_utxos_salt: rng.gen(),
+    }
+}
+
+/// A witness for the type scripts claim of a transaction whose UTXOs hold native coins
+/// and the coins of two tokens.
+pub fn pseudorandom_kernel_to_type_scripts_witness(seed: [u8; 32]) -> KernelToTypeScriptsWitness {
+    let mut rng: StdRng = SeedableRng::from_seed(seed);
+    let num_inputs = 2;
+    let num_outputs = 2;
+    let num_pubscripts = 1;
+
+    let token_type_script_hashes: [Digest; 2] = rng.gen();
+    let mut utxo_with_token = |token_type_script_hash: Digest| {
+        let mut utxo = pseudorandom_utxo(rng.gen::<[u8; 32]>());
+        utxo.coins.push(token::token_coin(
+            token_type_script_hash,
+            rng.gen_range(0..1000),
+        ));
+        utxo
+    };
+    let input_utxos = vec![
+        utxo_with_token(token_type_script_hashes[0]),
+        utxo_with_token(token_type_script_hashes[1]),
+    ];
+    let output_utxos = vec![
+        utxo_with_token(token_type_script_hashes[1]),
+        pseudorandom_utxo(rng.gen::<[u8; 32]>()),
+    ];
+    assert_eq!(num_inputs, input_utxos.len());
+    assert_eq!(num_outputs, output_utxos.len());
+
+    let output_sender_randomnesses: Vec<Digest> = (0..num_outputs).map(|_| rng.gen()).collect();
+    let output_receiver_digests: Vec<Digest> = (0..num_outputs).map(|_| rng.gen()).collect();
+    let mut kernel =
+        pseudorandom_transaction_kernel(rng.gen(), num_inputs, num_outputs, num_pubscripts);
+    kernel.outputs = output_utxos
+        .iter()
+        .zip(output_sender_randomnesses.iter())
+        .zip(output_receiver_digests.iter())
+        .map(|((utxo, sender_randomness), receiver_digest)| {
+            commit(Hash::hash(utxo), *sender_randomness, *receiver_digest)
+        })
+        .collect_vec();
+
+    let type_script_hashes = input_utxos
+        .iter()
+        .chain(output_utxos.iter())
+        .flat_map(|utxo| utxo.coins.iter().map(|coin| coin.type_script_hash))
+        .sorted()
+        .dedup()
+        .collect_vec();
+
+    KernelToTypeScriptsWitness {
+        input_utxos,
+        input_utxos_salt: rng.gen(),
+        output_utxos,
+        output_utxos_salt: rng.gen(),
+        output_sender_randomnesses,
+        output_receiver_digests,
+        kernel,
+        type_script_hashes,
+    }
+}
+
 pub fn random_transaction_kernel() -> TransactionKernel {
     let mut rng = thread_rng();
     let num_inputs = 1 + (rng.next_u32() % 5) as usize;
@@ -773,6 +854,7 @@ pub fn make_mock_transaction_with_generation_key(
         timestamp: BFieldElement::new(timestamp),
         coinbase: None,
         mutator_set_hash: tip_msa.hash(),
+        minimum_block_height: BFieldElement::zero(),
     };
 
     let input_utxos = input_utxos_mps_keys
@@ -798,6 +880,14 @@ pub fn make_mock_transaction_with_generation_key(
         .iter()
         .map(|rd| rd.public_announcement.clone())
         .collect();
+    let output_sender_randomnesses = receiver_data
+        .iter()
+        .map(|rd| rd.sender_randomness)
+        .collect();
+    let output_receiver_digests = receiver_data
+        .iter()
+        .map(|rd| rd.receiver_privacy_digest)
+        .collect();
     let output_utxos = receiver_data.into_iter().map(|rd| rd.utxo).collect();
     let primitive_witness = TransactionPrimitiveWitness {
         input_utxos,
@@ -806,6 +896,8 @@ pub fn make_mock_transaction_with_generation_key(
         lock_script_witnesses: spending_key_unlock_keys,
         input_membership_proofs,
         output_utxos,
+        output_sender_randomnesses,
+        output_receiver_digests,
         public_announcements: pubscripts,
         mutator_set_accumulator: tip_msa,
     };
@@ -842,6 +934,7 @@ pub fn make_mock_transaction(
             timestamp,
             coinbase: None,
             mutator_set_hash: random(),
+            minimum_block_height: BFieldElement::zero(),
         },
         witness: TransactionWitness::Faith,
     }
@@ -874,6 +967,7 @@ pub fn make_mock_transaction_with_wallet(
         timestamp,
         coinbase: None,
         mutator_set_hash: random(),
+        minimum_block_height: BFieldElement::zero(),
     };
 
     Transaction {
@@ -924,6 +1018,7 @@ pub fn make_mock_block(
         timestamp: BFieldElement::new(block_timestamp),
         coinbase: Some(coinbase_amount),
         mutator_set_hash: previous_mutator_set.hash(),
+        minimum_block_height: new_block_height.into(),
     };
 
     let primitive_witness = TransactionPrimitiveWitness {
@@ -932,6 +1027,8 @@ pub fn make_mock_block(
         lock_script_witnesses: vec![],
         input_membership_proofs: vec![],
         output_utxos: vec![coinbase_utxo.clone()],
+        output_sender_randomnesses: vec![coinbase_output_randomness],
+        output_receiver_digests: vec![receiver_digest],
         public_announcements: vec![],
         mutator_set_accumulator: previous_mutator_set.clone(),
         input_lock_scripts: vec![],
@@ -970,12 +1067,35 @@ pub fn make_mock_block(
     };
 
     (
-        Block::new(block_header, block_body, Some(Proof(vec![]))),
+        Block::new(block_header, block_body, None),
         coinbase_utxo,
         coinbase_output_randomness,
     )
 }
 
+/// Make a mock block on top of a mock block on top of genesis, and return its kernel along
+/// with the primitive witness for its validity. The block is timestamped `block_interval`
+/// milliseconds after its predecessor, or the target block interval if `None`.
+pub fn make_mock_block_kernel_and_primitive_witness(
+    block_interval: Option<u64>,
+) -> (BlockKernel, BlockPrimitiveWitness) {
+    let address = WalletSecret::new_random()
+        .nth_generation_spending_key(0)
+        .to_address();
+    let genesis_block = Block::genesis_block();
+    let (block_1, _, _) = make_mock_block(&genesis_block, None, address);
+    let block_timestamp =
+        block_1.kernel.header.timestamp.value() + block_interval.unwrap_or(TARGET_BLOCK_INTERVAL);
+    let (block_2, _, _) = make_mock_block(&block_1, Some(block_timestamp), address);
+
+    (
+        block_2.kernel,
+        BlockPrimitiveWitness {
+            predecessor: block_1.kernel,
+        },
+    )
+}
+
 pub fn make_mock_block_with_valid_pow(
     previous_block: &Block,
     block_timestamp: Option<u64>,
@@ -1,4 +1,5 @@
 pub mod mutator_set;
+pub mod secret_file;
 pub mod sync;
 
 #[cfg(test)]
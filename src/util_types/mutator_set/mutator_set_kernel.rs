@@ -1149,10 +1149,11 @@ mod accumulation_scheme_tests {
         let network = Network::Alpha;
         let genesis_wallet_state = get_mock_wallet_state(None, network).await;
         let genesis_spending_key = genesis_wallet_state
-            .wallet_secret
+            .spending_secret()
+            .unwrap()
             .nth_generation_spending_key(0);
         let genesis_state_lock =
-            get_mock_global_state(network, 3, Some(genesis_wallet_state.wallet_secret)).await;
+            get_mock_global_state(network, 3, genesis_wallet_state.wallet_secret).await;
 
         let wallet_secret_alice = WalletSecret::new_pseudorandom(rng.gen());
         let alice_spending_key = wallet_secret_alice.nth_generation_spending_key(0);
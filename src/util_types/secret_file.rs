@@ -0,0 +1,85 @@
+//! Files holding secrets: wallet secrets, transport keys, signing keys and exported
+//! notifications. On Unix they are only readable and writable by their owner, to
+//! disallow other users on the same machine to access the secrets.
+
+use std::fs;
+use std::io;
+use std::io::Write;
+use std::path::Path;
+
+fn secret_file_options() -> fs::OpenOptions {
+    let mut options = fs::OpenOptions::new();
+    options.write(true);
+
+    #[cfg(unix)]
+    {
+        use std::os::unix::fs::OpenOptionsExt;
+        options.mode(0o600);
+    }
+
+    options
+}
+
+/// Write a new file that only the owner may read. Fails if the file already exists.
+pub fn create_secret_file(path: &Path, bytes: &[u8]) -> io::Result<()> {
+    secret_file_options()
+        .create_new(true)
+        .open(path)?
+        .write_all(bytes)
+}
+
+/// Write a file that only the owner may read, replacing its content if it already
+/// exists. The permissions of an existing file are restricted as well.
+pub fn write_secret_file(path: &Path, bytes: &[u8]) -> io::Result<()> {
+    let mut file = secret_file_options()
+        .create(true)
+        .truncate(true)
+        .open(path)?;
+
+    #[cfg(unix)]
+    {
+        use std::os::unix::fs::PermissionsExt;
+        file.set_permissions(fs::Permissions::from_mode(0o600))?;
+    }
+
+    file.write_all(bytes)
+}
+
+#[cfg(test)]
+mod secret_file_tests {
+    use super::*;
+    use crate::config_models::network::Network;
+    use crate::tests::shared::unit_test_data_directory;
+
+    #[test]
+    fn secret_files_are_owner_only() {
+        let data_dir = unit_test_data_directory(Network::Alpha).unwrap();
+        let directory = data_dir.root_dir_path();
+        fs::create_dir_all(&directory).unwrap();
+        let path = directory.join("secret");
+
+        create_secret_file(&path, b"first").unwrap();
+        assert!(
+            create_secret_file(&path, b"second").is_err(),
+            "Must not overwrite an existing secret file"
+        );
+        assert_eq!(b"first".to_vec(), fs::read(&path).unwrap());
+
+        write_secret_file(&path, b"second").unwrap();
+        assert_eq!(b"second".to_vec(), fs::read(&path).unwrap());
+
+        #[cfg(unix)]
+        {
+            use std::os::unix::fs::PermissionsExt;
+            let other_path = directory.join("public");
+            fs::write(&other_path, b"public").unwrap();
+            fs::set_permissions(&other_path, fs::Permissions::from_mode(0o644)).unwrap();
+            write_secret_file(&other_path, b"secret").unwrap();
+
+            for path in [path, other_path] {
+                let mode = fs::metadata(path).unwrap().permissions().mode();
+                assert_eq!(0o600, mode & 0o777);
+            }
+        }
+    }
+}